@@ -0,0 +1,5 @@
+//! Print the opcode table of the binary format as json
+
+fn main() {
+    print!("{}", sledgehammer_encoder::opcodes::to_json());
+}
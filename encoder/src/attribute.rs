@@ -1,22 +1,35 @@
 #![allow(non_camel_case_types)]
 
+use alloc::{borrow::Cow, string::String};
+
 use self::sealed::Sealed;
+#[cfg(feature = "attributes")]
+use crate::names::NameTable;
 use crate::{batch::Batch, InNamespace};
 
 mod sealed {
-    use crate::{Attribute, InNamespace};
+    use alloc::{borrow::Cow, string::String};
+
+    use crate::InNamespace;
 
     pub trait Sealed {}
 
-    impl Sealed for Attribute {}
-    impl<'a> Sealed for InNamespace<'a, Attribute> {}
-    impl<'a> Sealed for &'a str {}
+    #[cfg(feature = "attributes")]
+    impl Sealed for crate::Attribute {}
+    #[cfg(feature = "attributes")]
+    impl<'a> Sealed for InNamespace<'a, crate::Attribute> {}
+    impl Sealed for &str {}
+    impl Sealed for &String {}
+    impl Sealed for &Cow<'_, str> {}
     impl<'a, 'b> Sealed for InNamespace<'b, &'a str> {}
+    impl Sealed for super::AnyAttribute<'_, '_> {}
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum AnyAttribute<'a, 'b> {
+    #[cfg(feature = "attributes")]
     Attribute(Attribute),
+    #[cfg(feature = "attributes")]
     InNamespace(InNamespace<'a, Attribute>),
     Str(&'a str),
     InNamespaceStr(InNamespace<'a, &'b str>),
@@ -25,7 +38,9 @@ pub enum AnyAttribute<'a, 'b> {
 impl AnyAttribute<'_, '_> {
     pub(crate) unsafe fn encode_u8_discriminant_prealloc(self, v: &mut Batch) {
         match self {
+            #[cfg(feature = "attributes")]
             AnyAttribute::Attribute(a) => a.encode_u8_discriminant_prealloc(v),
+            #[cfg(feature = "attributes")]
             AnyAttribute::InNamespace(a) => a.encode_u8_discriminant_prealloc(v),
             AnyAttribute::Str(a) => a.encode_u8_discriminant_prealloc(v),
             AnyAttribute::InNamespaceStr(a) => a.encode_u8_discriminant_prealloc(v),
@@ -34,7 +49,9 @@ impl AnyAttribute<'_, '_> {
 
     pub(crate) fn size_with_u8_discriminant(&self) -> usize {
         match self {
+            #[cfg(feature = "attributes")]
             AnyAttribute::Attribute(_) => 1,
+            #[cfg(feature = "attributes")]
             AnyAttribute::InNamespace(_) => 1 + 1 + 2,
             AnyAttribute::Str(_) => 1 + 2,
             AnyAttribute::InNamespaceStr(_) => 1 + 2 + 2,
@@ -42,6 +59,23 @@ impl AnyAttribute<'_, '_> {
     }
 }
 
+impl<'a, 'b> IntoAttribue<'a, 'b> for AnyAttribute<'a, 'b> {
+    fn encode(self, v: &mut Batch) {
+        match self {
+            #[cfg(feature = "attributes")]
+            AnyAttribute::Attribute(a) => a.encode(v),
+            #[cfg(feature = "attributes")]
+            AnyAttribute::InNamespace(a) => a.encode(v),
+            AnyAttribute::Str(a) => a.encode(v),
+            AnyAttribute::InNamespaceStr(a) => a.encode(v),
+        }
+    }
+
+    unsafe fn encode_u8_discriminant_prealloc(self, v: &mut Batch) {
+        AnyAttribute::encode_u8_discriminant_prealloc(self, v)
+    }
+}
+
 /// Anything that can be turned into an attribute
 pub trait IntoAttribue<'a, 'b>: Sealed + Into<AnyAttribute<'a, 'b>> {
     /// If the attribute can be encoded in a single byte
@@ -70,6 +104,7 @@ pub trait IntoAttribue<'a, 'b>: Sealed + Into<AnyAttribute<'a, 'b>> {
     unsafe fn encode_u8_discriminant_prealloc(self, v: &mut Batch);
 }
 
+#[cfg(feature = "attributes")]
 impl<'a, 'b> Attribute {
     /// Turn into an [`AnyAttribute`] in a const context
     pub const fn any_attr_const(self) -> AnyAttribute<'a, 'b> {
@@ -77,6 +112,7 @@ impl<'a, 'b> Attribute {
     }
 }
 
+#[cfg(feature = "attributes")]
 impl<'a, 'b> IntoAttribue<'a, 'b> for Attribute {
     const SINGLE_BYTE: bool = true;
 
@@ -104,18 +140,21 @@ impl<'a, 'b> IntoAttribue<'a, 'b> for Attribute {
     }
 }
 
+#[cfg(feature = "attributes")]
 impl<'a, 'b> From<Attribute> for AnyAttribute<'a, 'b> {
     fn from(a: Attribute) -> Self {
         AnyAttribute::Attribute(a)
     }
 }
 
+#[cfg(feature = "attributes")]
 impl<'a, 'b> InNamespace<'a, Attribute> {
     pub const fn any_attr_const(self) -> AnyAttribute<'a, 'b> {
         AnyAttribute::InNamespace(self)
     }
 }
 
+#[cfg(feature = "attributes")]
 impl<'a, 'b> IntoAttribue<'a, 'b> for InNamespace<'a, Attribute> {
     #[inline(always)]
     fn encode(self, v: &mut Batch) {
@@ -133,6 +172,7 @@ impl<'a, 'b> IntoAttribue<'a, 'b> for InNamespace<'a, Attribute> {
     }
 }
 
+#[cfg(feature = "attributes")]
 impl<'a, 'b> From<InNamespace<'a, Attribute>> for AnyAttribute<'a, 'b> {
     fn from(a: InNamespace<'a, Attribute>) -> Self {
         AnyAttribute::InNamespace(a)
@@ -158,6 +198,38 @@ impl<'a, 'b> From<&'a str> for AnyAttribute<'a, 'b> {
     }
 }
 
+impl<'a, 'b> IntoAttribue<'a, 'b> for &'a String {
+    fn encode(self, v: &mut Batch) {
+        self.as_str().encode(v);
+    }
+
+    unsafe fn encode_u8_discriminant_prealloc(self, v: &mut Batch) {
+        self.as_str().encode_u8_discriminant_prealloc(v);
+    }
+}
+
+impl<'a, 'b> From<&'a String> for AnyAttribute<'a, 'b> {
+    fn from(a: &'a String) -> Self {
+        AnyAttribute::Str(a)
+    }
+}
+
+impl<'a, 'b> IntoAttribue<'a, 'b> for &'a Cow<'_, str> {
+    fn encode(self, v: &mut Batch) {
+        self.as_ref().encode(v);
+    }
+
+    unsafe fn encode_u8_discriminant_prealloc(self, v: &mut Batch) {
+        self.as_ref().encode_u8_discriminant_prealloc(v);
+    }
+}
+
+impl<'a, 'b> From<&'a Cow<'_, str>> for AnyAttribute<'a, 'b> {
+    fn from(a: &'a Cow<'_, str>) -> Self {
+        AnyAttribute::Str(a)
+    }
+}
+
 impl<'a, 'b> InNamespace<'a, &'b str> {
     pub const fn any_attr_const(self) -> AnyAttribute<'a, 'b> {
         AnyAttribute::InNamespaceStr(self)
@@ -185,65 +257,104 @@ impl<'a, 'b> From<InNamespace<'a, &'b str>> for AnyAttribute<'a, 'b> {
     }
 }
 
+// attributes that are not valid rust identifiers are given their name explicitly
+#[cfg(feature = "attributes")]
+macro_rules! attribute_name {
+    ($i: ident) => {
+        stringify!($i)
+    };
+    ($i: ident, $name: literal) => {
+        $name
+    };
+}
+
+#[cfg(feature = "attributes")]
 macro_rules! attributes {
-    ($($i: ident),*) => {
+    ($($i: ident $(= $name: literal)?),*) => {
         /// All built-in attributes
         /// These are the attributes can be encoded with a single byte so they are more efficient (but less flexable) than a &str attribute
-        #[derive(Copy, Clone)]
+        #[derive(Copy, Clone, Debug, PartialEq, Eq)]
         pub enum Attribute {
             $(
                 $i
             ),*
         }
 
+        impl Attribute {
+            /// All built-in attributes in the order of their discriminants
+            pub const VARIANTS: &'static [Attribute] = &[$(Self::$i),*];
+
+            /// The names of all built-in attributes in the order of their discriminants, each followed by a comma
+            pub const NAMES: &'static str = concat!($(attribute_name!($i $(, $name)?), ","),*);
+
+            /// The name of the attribute
+            pub const fn name(self) -> &'static str {
+                NAME_TABLE.get(self as usize)
+            }
+
+            /// Get the attribute encoded with the given byte
+            pub fn from_u8(v: u8) -> Option<Self> {
+                Self::VARIANTS.get(v as usize).copied()
+            }
+        }
+
+        /// The offsets of the names in [`Attribute::NAMES`]
+        const NAME_TABLE: NameTable<{ Attribute::VARIANTS.len() }> = NameTable::new(Attribute::NAMES);
+
         pub struct NotElementError;
 
-        impl std::str::FromStr for Attribute {
+        impl core::str::FromStr for Attribute {
             type Err = NotElementError;
 
             fn from_str(s: &str) -> Result<Self, Self::Err> {
-                Ok(match s{
-                    $(
-                        stringify!($i) => Self::$i,
-                    )*
-                    _ => return Err(NotElementError)
-                })
+                NAME_TABLE
+                    .position(s)
+                    .map(|index| Self::VARIANTS[index])
+                    .ok_or(NotElementError)
             }
         }
     };
 }
 
+/// The table of attribute names the interpreter uses to decode single byte attributes. This is empty if the `attributes` feature is disabled.
+#[cfg(feature = "attributes")]
+pub const TABLE: &str = Attribute::NAMES;
+/// The table of attribute names the interpreter uses to decode single byte attributes. This is empty if the `attributes` feature is disabled.
+#[cfg(not(feature = "attributes"))]
+pub const TABLE: &str = "";
+
+#[cfg(feature = "attributes")]
 attributes! {
-    accept_charset,
+    accept_charset = "accept-charset",
     accept,
     accesskey,
     action,
     align,
     allow,
     alt,
-    aria_atomic,
-    aria_busy,
-    aria_controls,
-    aria_current,
-    aria_describedby,
-    aria_description,
-    aria_details,
-    aria_disabled,
-    aria_dropeffect,
-    aria_errormessage,
-    aria_flowto,
-    aria_grabbed,
-    aria_haspopup,
-    aria_hidden,
-    aria_invalid,
-    aria_keyshortcuts,
-    aria_label,
-    aria_labelledby,
-    aria_live,
-    aria_owns,
-    aria_relevant,
-    aria_roledescription,
-    r#async,
+    aria_atomic = "aria-atomic",
+    aria_busy = "aria-busy",
+    aria_controls = "aria-controls",
+    aria_current = "aria-current",
+    aria_describedby = "aria-describedby",
+    aria_description = "aria-description",
+    aria_details = "aria-details",
+    aria_disabled = "aria-disabled",
+    aria_dropeffect = "aria-dropeffect",
+    aria_errormessage = "aria-errormessage",
+    aria_flowto = "aria-flowto",
+    aria_grabbed = "aria-grabbed",
+    aria_haspopup = "aria-haspopup",
+    aria_hidden = "aria-hidden",
+    aria_invalid = "aria-invalid",
+    aria_keyshortcuts = "aria-keyshortcuts",
+    aria_label = "aria-label",
+    aria_labelledby = "aria-labelledby",
+    aria_live = "aria-live",
+    aria_owns = "aria-owns",
+    aria_relevant = "aria-relevant",
+    aria_roledescription = "aria-roledescription",
+    r#async = "async",
     autocapitalize,
     autocomplete,
     autofocus,
@@ -282,7 +393,7 @@ attributes! {
     draggable,
     enctype,
     enterkeyhint,
-    r#for,
+    r#for = "for",
     form,
     formaction,
     formenctype,
@@ -295,7 +406,7 @@ attributes! {
     high,
     href,
     hreflang,
-    http_equiv,
+    http_equiv = "http-equiv",
     icon,
     id,
     importance,
@@ -311,7 +422,7 @@ attributes! {
     language,
     list,
     loading,
-    r#loop,
+    r#loop = "loop",
     low,
     manifest,
     max,
@@ -362,7 +473,7 @@ attributes! {
     target,
     title,
     translate,
-    r#type,
+    r#type = "type",
     usemap,
     value,
     width,
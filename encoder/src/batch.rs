@@ -1,8 +1,13 @@
+use alloc::vec::Vec;
+use core::fmt::Write;
+
 use crate::{
-    ElementBuilder, IntoAttribue, IntoElement, MaybeId, NodeId, TextBuilder, WritableText,
+    ElementBuilder, FragmentBuilder, HandlerId, IntoAttribue, IntoElement, MaybeId, NodeId,
+    OwnedElementBuilder, QueryId, StorageArea, TextBuilder, WritableText,
 };
 
 // operations that have no booleans can be encoded as a half byte, these are placed first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Op {
     /// Navigates to the last node to the first child of the current node.
     FirstChild = 0,
@@ -66,6 +71,97 @@ pub enum Op {
 
     /// Does nothing, but allows us to skip a byte.
     NoOp = 20,
+
+    /// Stores the body of an iframe's content document with a new id.
+    AdoptIframeDocument = 21,
+
+    /// Sets how many removed nodes with a tag name are kept to be reused.
+    SetPoolSize = 22,
+
+    /// Grows the node table to hold a number of ids.
+    HintNodeCapacity = 23,
+
+    /// Caches a value with a one byte id.
+    CacheValue = 24,
+
+    /// Set the value of a node's attribute to a cached value.
+    SetAttributeCached = 25,
+
+    /// Pushes the last node onto the stack of parents, or appends the last node to the topmost parent and pops it.
+    ParentStack = 26,
+
+    /// Adds or removes an event listener that dispatches events to a handler.
+    Listener = 27,
+
+    /// Sets the value of a form control without moving the cursor.
+    SetValue = 28,
+
+    /// An operation from [`ExtendedOp`], selected by the byte after the operation.
+    Extended = 29,
+}
+
+/// Operations that are encoded as [`Op::Extended`] followed by a byte that selects the operation, because the five bits of the operation byte are almost used up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtendedOp {
+    /// Subscribes a handler to the changes of a media query, or unsubscribes it.
+    MediaQuery = 0,
+
+    /// Reads an item from local or session storage and answers a query with it.
+    GetStorageItem = 1,
+
+    /// Sets an item in local or session storage.
+    SetStorageItem = 2,
+
+    /// Removes an item from local or session storage.
+    RemoveStorageItem = 3,
+
+    /// Forwards the lifecycle callbacks of a custom element to a handler, or stops forwarding them.
+    CustomElementLifecycle = 4,
+
+    /// Shows or hides a modal dialog or a popover.
+    TopLayer = 5,
+
+    /// Requests or exits fullscreen.
+    Fullscreen = 6,
+
+    /// Requests or exits pointer lock.
+    PointerLock = 7,
+
+    /// Subscribes a handler to animation frames, or unsubscribes it.
+    AnimationFrames = 8,
+
+    /// Keeps the size of a canvas in sync with its size on the page, or stops keeping it in sync.
+    CanvasSize = 9,
+
+    /// Answers a query with the id of the focused element.
+    ActiveElement = 10,
+
+    /// Answers a query with the id of the topmost element at a point in the viewport.
+    ElementFromPoint = 11,
+
+    /// Answers a query with whether an element is rendered.
+    CheckVisibility = 12,
+
+    /// Adds a class to the class list of an element or removes it.
+    ToggleClass = 13,
+
+    /// Sets the text of a node to a number or date formatted with `Intl`.
+    SetFormattedText = 14,
+
+    /// Applies a nested batch inside of a view transition.
+    ViewTransition = 15,
+
+    /// Appends the last node to the topmost parent and optionally pushes it, or pops the topmost parent without appending to it.
+    StreamTree = 16,
+
+    /// Replaces the children of a node with html cleaned with an allowlist.
+    SetSanitizedHtml = 17,
+
+    /// Sets when an image loads and how it is decoded.
+    SetImageLoading = 18,
+
+    /// Answers a query once an image is decoded.
+    DecodeImage = 19,
 }
 
 /// A batch of operations ready to perform on the DOM.
@@ -89,7 +185,7 @@ impl PreparedBatch for FinalizedBatch {
     }
 }
 
-impl<'a> PreparedBatch for &'a FinalizedBatch {
+impl PreparedBatch for &FinalizedBatch {
     fn msg(&self) -> &[u8] {
         &self.msg
     }
@@ -114,7 +210,7 @@ impl PreparedBatch for StaticBatch {
     }
 }
 
-impl<'a> PreparedBatch for &'a StaticBatch {
+impl PreparedBatch for &StaticBatch {
     fn msg(&self) -> &[u8] {
         self.msg
     }
@@ -129,6 +225,8 @@ impl<'a> PreparedBatch for &'a StaticBatch {
 /// This is useful for building up a batch of operations to perform on the DOM many times. If the operation is only performed once, it is better to use the `MsgChannel` directly because it reuses the same allocation from the last batch of operations.
 /// See [`MsgChannel::append`] and [`MsgChannel::run_batch`] for examples.
 /// The methods on this struct are a subset of the methods on [`MsgChannel`] and work the same with the exception of [`Batch::finalize`].
+///
+/// Batches are `Send` and `Sync`, so independent subtrees can be encoded on other threads (for example with wasm-bindgen-rayon) and appended to the batch on the main thread with [`Batch::append`] or [`Extend`].
 pub struct Batch {
     #[doc(hidden)]
     pub msg: Vec<u8>,
@@ -140,6 +238,11 @@ pub struct Batch {
     pub current_op_byte_idx: usize,
     #[doc(hidden)]
     pub current_op_bit_pack_index: u8,
+    op_count: usize,
+    msg_high_water_mark: usize,
+    str_high_water_mark: usize,
+    /// The position in the message of the length of the last string that was encoded
+    last_str_len_pos: Option<usize>,
 }
 
 impl Default for Batch {
@@ -150,12 +253,17 @@ impl Default for Batch {
             current_op_byte_idx: 3,
             current_op_bit_pack_index: 0,
             current_op_batch_idx: 0,
+            op_count: 0,
+            msg_high_water_mark: 0,
+            str_high_water_mark: 0,
+            last_str_len_pos: None,
         }
     }
 }
 
 impl Batch {
     /// Finalizes the batch and prepares it to be run
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn finalize(mut self) -> FinalizedBatch {
         self.encode_op(Op::Stop);
         FinalizedBatch {
@@ -164,7 +272,180 @@ impl Batch {
         }
     }
 
+    /// Remove all operations from the batch while keeping the allocated memory so it can be reused.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn clear(&mut self) {
+        self.msg_high_water_mark = self.msg_high_water_mark.max(self.msg.len());
+        self.str_high_water_mark = self.str_high_water_mark.max(self.str_buf.len());
+        self.msg.clear();
+        self.str_buf.clear();
+        self.current_op_batch_idx = 0;
+        self.current_op_byte_idx = 3;
+        self.current_op_bit_pack_index = 0;
+        self.op_count = 0;
+        self.last_str_len_pos = None;
+    }
+
+    /// Encode operations in a closure and remove them again if the closure returns an error or panics, so a batch is never left with half of a group of operations that only make sense together. The operations before the scope are kept.
+    ///
+    /// The closure must not clear or replace the batch. If it does and then fails, the operations before the scope may be removed as well.
+    ///
+    /// Example:
+    /// ```rust
+    /// # use sledgehammer_encoder::{batch::Batch, *};
+    /// let mut batch = Batch::default();
+    /// batch.set_text("kept", MaybeId::Node(NodeId(0)));
+    /// let result: Result<(), &str> = batch.scope(|batch| {
+    ///     batch.create_element(Element::div, Some(NodeId(1)));
+    ///     // the element is never appended, so it is removed with the error
+    ///     Err("the row failed to render")
+    /// });
+    /// assert!(result.is_err());
+    /// assert_eq!(batch.op_count(), 1);
+    /// ```
+    pub fn scope<T, E>(&mut self, f: impl FnOnce(&mut Batch) -> Result<T, E>) -> Result<T, E> {
+        let mut scope = Scope {
+            checkpoint: self.checkpoint(),
+            batch: self,
+            done: false,
+        };
+        let result = f(scope.batch);
+        scope.done = result.is_ok();
+        result
+    }
+
+    /// Encode operations in a closure and apply them inside of `document.startViewTransition`, so the browser animates from the current state of the document to the state after the operations. Once the transition finished, a `viewtransitionend` event is dispatched to the handler.
+    ///
+    /// The operations are encoded as a nested batch. The interpreter applies them when the browser calls the update callback of the transition, after the rest of the batch, and reports errors in the event instead of failing the batch. Browsers without view transitions apply them without animating them.
+    ///
+    /// Example:
+    /// ```rust
+    /// # use sledgehammer_encoder::{batch::Batch, *};
+    /// let mut batch = Batch::default();
+    /// let route_changed = HandlerId(0);
+    /// let rows = batch.with_view_transition(route_changed, |batch| {
+    ///     batch.remove(MaybeId::Node(NodeId(1)));
+    ///     batch.create_element(Element::main, Some(NodeId(1)));
+    ///     batch.append_child(MaybeId::Node(NodeId(0)), MaybeId::LastNode);
+    ///     3
+    /// });
+    /// assert_eq!(rows, 3);
+    /// // the transition is a single operation in the outer batch
+    /// assert_eq!(batch.op_count(), 1);
+    /// ```
+    pub fn with_view_transition<T>(
+        &mut self,
+        handler: HandlerId,
+        f: impl FnOnce(&mut Batch) -> T,
+    ) -> T {
+        let mut transition = Batch::default();
+        let result = f(&mut transition);
+        transition.encode_op(Op::Stop);
+        // the unused operation bytes of the last group are uninitialized, so they are filled before the nested batch is copied
+        while transition.current_op_byte_idx - transition.current_op_batch_idx < 3 {
+            transition.encode_op(Op::NoOp);
+        }
+        self.encode_view_transition(handler, &transition.msg, &transition.str_buf);
+        result
+    }
+
+    pub(crate) fn encode_view_transition(
+        &mut self,
+        handler: HandlerId,
+        msg: &[u8],
+        str_buf: &[u8],
+    ) {
+        self.encode_extended_op(ExtendedOp::ViewTransition);
+        self.encode_u32(handler.0);
+        self.encode_bytes(msg);
+        self.encode_bytes(str_buf);
+    }
+
+    fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            msg_len: self.msg.len(),
+            str_len: self.str_buf.len(),
+            current_op_batch_idx: self.current_op_batch_idx,
+            current_op_byte_idx: self.current_op_byte_idx,
+            current_op_bit_pack_index: self.current_op_bit_pack_index,
+            op_count: self.op_count,
+            last_str_len_pos: self.last_str_len_pos,
+        }
+    }
+
+    fn rollback(&mut self, checkpoint: Checkpoint) {
+        self.msg_high_water_mark = self.msg_high_water_mark.max(self.msg.len());
+        self.str_high_water_mark = self.str_high_water_mark.max(self.str_buf.len());
+        // the batch was cleared or replaced after the checkpoint, so the positions of the checkpoint may be past the end of the message
+        if self.msg.len() < checkpoint.msg_len || self.str_buf.len() < checkpoint.str_len {
+            self.clear();
+            return;
+        }
+        // the unused operation bytes of the group the checkpoint is in are overwritten when the next operation is encoded
+        self.msg.truncate(checkpoint.msg_len);
+        self.str_buf.truncate(checkpoint.str_len);
+        self.current_op_batch_idx = checkpoint.current_op_batch_idx;
+        self.current_op_byte_idx = checkpoint.current_op_byte_idx;
+        self.current_op_bit_pack_index = checkpoint.current_op_bit_pack_index;
+        self.op_count = checkpoint.op_count;
+        self.last_str_len_pos = checkpoint.last_str_len_pos;
+    }
+
+    /// A writer that appends formatted text to the last string that was encoded in the batch, so values can be formatted directly into the batch without allocating a temporary string.
+    ///
+    /// Writing fails if no string has been encoded since the batch was created or cleared, or if the string would be longer than `u16::MAX` bytes.
+    ///
+    /// Example:
+    /// ```rust
+    /// # use sledgehammer_encoder::{batch::Batch, decoder::DecodedOp, MaybeId};
+    /// use core::fmt::Write;
+    ///
+    /// let mut batch = Batch::default();
+    /// let width = 100;
+    /// // encode an empty value and then format the value into it
+    /// batch.set_style("width", "", MaybeId::LastNode);
+    /// write!(batch.str_writer(), "{}px", width).unwrap();
+    /// let (_, op) = batch.decode().next().unwrap().unwrap();
+    /// assert_eq!(
+    ///     op,
+    ///     DecodedOp::SetStyle {
+    ///         root: MaybeId::LastNode,
+    ///         style: "width",
+    ///         value: "100px"
+    ///     }
+    /// );
+    /// ```
+    pub fn str_writer(&mut self) -> StrWriter<'_> {
+        StrWriter { batch: self }
+    }
+
+    /// The number of operations encoded in the batch since it was created or last cleared.
+    pub fn op_count(&self) -> usize {
+        self.op_count
+    }
+
+    /// The number of bytes of operations in the batch.
+    pub fn msg_bytes(&self) -> usize {
+        self.msg.len()
+    }
+
+    /// The number of bytes of strings in the batch.
+    pub fn str_bytes(&self) -> usize {
+        self.str_buf.len()
+    }
+
+    /// The largest number of bytes of operations the batch has held.
+    pub fn msg_high_water_mark(&self) -> usize {
+        self.msg_high_water_mark.max(self.msg.len())
+    }
+
+    /// The largest number of bytes of strings the batch has held.
+    pub fn str_high_water_mark(&self) -> usize {
+        self.str_high_water_mark.max(self.str_buf.len())
+    }
+
     /// Appends a number of nodes as children of the given node.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn append_child(&mut self, root: MaybeId, child: MaybeId) {
         self.encode_op(Op::AppendChildren);
         let size = root.encoded_size() + child.encoded_size();
@@ -176,17 +457,20 @@ impl Batch {
     }
 
     /// Replace a node with another node
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn replace_with(&mut self, root: MaybeId, node: MaybeId) {
         self.encode_op(Op::ReplaceWith);
         let size = root.encoded_size() + node.encoded_size();
         self.msg.reserve(size as usize);
         unsafe {
+            self.encode_bool(false);
             self.encode_maybe_id_prealloc(root);
             self.encode_maybe_id_prealloc(node);
         }
     }
 
     /// Replace a node with many nodes
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn replace_with_nodes(&mut self, root: MaybeId, nodes: &[MaybeId]) {
         self.encode_op(Op::ReplaceWith);
         self.encode_bool(true);
@@ -198,6 +482,7 @@ impl Batch {
     }
 
     /// Insert a single node after a given node.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn insert_after(&mut self, root: MaybeId, node: MaybeId) {
         self.encode_op(Op::InsertAfter);
         let size = root.encoded_size() + node.encoded_size();
@@ -210,6 +495,7 @@ impl Batch {
     }
 
     /// Insert many nodes after a given node.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn insert_nodes_after(&mut self, root: MaybeId, nodes: &[MaybeId]) {
         self.encode_op(Op::InsertAfter);
         self.encode_bool(true);
@@ -221,6 +507,7 @@ impl Batch {
     }
 
     /// Insert a single node before a given node.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn insert_before(&mut self, root: MaybeId, node: MaybeId) {
         self.encode_op(Op::InsertBefore);
         let size = root.encoded_size() + node.encoded_size();
@@ -233,6 +520,7 @@ impl Batch {
     }
 
     /// Insert many nodes before a given node.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn insert_nodes_before(&mut self, root: MaybeId, nodes: &[MaybeId]) {
         self.encode_op(Op::InsertBefore);
         self.encode_bool(true);
@@ -244,12 +532,14 @@ impl Batch {
     }
 
     /// Remove a node from the DOM.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn remove(&mut self, id: MaybeId) {
         self.encode_op(Op::Remove);
         self.encode_maybe_id(id);
     }
 
     /// Create a new text node
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn create_text_node(&mut self, text: impl WritableText, id: Option<NodeId>) {
         self.encode_op(Op::CreateTextNode);
         let size = (id.is_some() as u8) * 4 + 2;
@@ -261,6 +551,7 @@ impl Batch {
     }
 
     /// Create a new element node
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn create_element<'a, 'b, E>(&mut self, tag: E, id: Option<NodeId>)
     where
         E: IntoElement<'a, 'b>,
@@ -270,11 +561,18 @@ impl Batch {
             .reserve((E::SINGLE_BYTE as u8 + (id.is_some() as u8) * 4) as usize);
         unsafe {
             tag.encode_prealloc(self);
+        }
+        // elements that are not a single byte are encoded without the preallocated memory and may use up the memory reserved for the id
+        if !E::SINGLE_BYTE {
+            self.msg.reserve((id.is_some() as usize) * 4);
+        }
+        unsafe {
             self.encode_optional_id_prealloc(id);
         }
     }
 
     /// Set the textcontent of a node.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn set_text(&mut self, text: impl WritableText, root: MaybeId) {
         self.encode_op(Op::SetText);
         let size = root.encoded_size() + 2;
@@ -285,7 +583,22 @@ impl Batch {
         }
     }
 
+    /// Set the value of a form control like an `input`, `textarea` or `select`. Unlike the `value` attribute, this changes the value the control shows after the user edited it.
+    ///
+    /// The interpreter keeps the cursor the same distance from the end of the value, and it waits to set the value of a control the user is composing text in with an input method until the composition ends.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn set_value(&mut self, value: impl WritableText, root: MaybeId) {
+        self.encode_op(Op::SetValue);
+        let size = root.encoded_size() + 2;
+        self.msg.reserve(size as usize);
+        unsafe {
+            self.encode_maybe_id_prealloc(root);
+            self.encode_str_prealloc(value);
+        }
+    }
+
     /// Set the value of a node's attribute.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn set_attribute<'a, 'b, A>(&mut self, attr: A, value: impl WritableText, root: MaybeId)
     where
         A: IntoAttribue<'a, 'b>,
@@ -296,11 +609,46 @@ impl Batch {
         unsafe {
             self.encode_maybe_id_prealloc(root);
             attr.encode_prealloc(self);
+        }
+        // attributes that are not a single byte are encoded without the preallocated memory and may use up the memory reserved for the value
+        if A::SINGLE_BYTE {
+            unsafe {
+                self.encode_str_prealloc(value);
+            }
+        } else {
+            self.encode_str(value);
+        }
+    }
+
+    /// Cache a value with a one byte id so it can be set as an attribute with [`Batch::set_attribute_cached`] without encoding the string again. Caching a value with an id that is already in use replaces the old value.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn cache_value(&mut self, id: u8, value: impl WritableText) {
+        self.encode_op(Op::CacheValue);
+        self.msg.reserve(1 + 2);
+        unsafe {
+            self.encode_u8_prealloc(id);
             self.encode_str_prealloc(value);
         }
     }
 
+    /// Set the value of a node's attribute to a value that was cached with [`Batch::cache_value`]. Only the id of the value is encoded, so toggling an attribute between a few cached values costs a single byte for the value.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn set_attribute_cached<'a, 'b, A>(&mut self, attr: A, value: u8, root: MaybeId)
+    where
+        A: IntoAttribue<'a, 'b>,
+    {
+        self.encode_op(Op::SetAttributeCached);
+        self.msg
+            .reserve((A::SINGLE_BYTE as u8 + root.encoded_size() + 1) as usize);
+        unsafe {
+            self.encode_maybe_id_prealloc(root);
+            attr.encode_prealloc(self);
+        }
+        self.msg.push(value);
+    }
+
     /// Remove an attribute from a node.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn remove_attribute<'a, 'b, A>(&mut self, attr: A, root: MaybeId)
     where
         A: IntoAttribue<'a, 'b>,
@@ -314,7 +662,47 @@ impl Batch {
         }
     }
 
+    /// Set an attribute if `condition` is true, or remove it otherwise. Diffing code can pass the condition instead of branching between [`Batch::set_attribute`] and [`Batch::remove_attribute`].
+    ///
+    /// Example:
+    /// ```rust
+    /// # use sledgehammer_encoder::{batch::Batch, decoder::DecodedOp, *};
+    /// let mut batch = Batch::default();
+    /// batch.set_attribute_if(Attribute::disabled, "", false, MaybeId::Node(NodeId(1)));
+    /// let op = batch.decode().next().unwrap().unwrap().1;
+    /// assert!(matches!(op, DecodedOp::RemoveAttribute { .. }));
+    /// ```
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn set_attribute_if<'a, 'b, A>(
+        &mut self,
+        attr: A,
+        value: impl WritableText,
+        condition: bool,
+        root: MaybeId,
+    ) where
+        A: IntoAttribue<'a, 'b>,
+    {
+        if condition {
+            self.set_attribute(attr, value, root);
+        } else {
+            self.remove_attribute(attr, root);
+        }
+    }
+
+    /// Add a class to the class list of an element if `condition` is true, or remove it otherwise. The other classes of the element are kept.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn toggle_class_if(&mut self, class: impl WritableText, condition: bool, root: MaybeId) {
+        self.encode_extended_op(ExtendedOp::ToggleClass);
+        self.msg.reserve(root.encoded_size() as usize + 2);
+        unsafe {
+            self.encode_bool(condition);
+            self.encode_maybe_id_prealloc(root);
+            self.encode_str_prealloc(class);
+        }
+    }
+
     /// Clone a node and store it with a new id.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn clone_node(&mut self, id: MaybeId, new_id: MaybeId) {
         self.encode_op(Op::CloneNode);
         let size = id.encoded_size() + new_id.encoded_size();
@@ -324,45 +712,480 @@ impl Batch {
     }
 
     /// Move the last node to the first child
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn first_child(&mut self) {
         self.encode_op(Op::FirstChild);
     }
 
     /// Move the last node to the next sibling
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn next_sibling(&mut self) {
         self.encode_op(Op::NextSibling);
     }
 
     /// Move the last node to the parent node
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn parent_node(&mut self) {
         self.encode_op(Op::ParentNode);
     }
 
     /// Store the last node with the given id. This is useful when traversing the document tree.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn store_with_id(&mut self, id: NodeId) {
         self.encode_op(Op::StoreWithId);
         self.encode_id(id);
     }
 
     /// Set the last node to the given id. The last node can be used to traverse the document tree without passing objects between wasm and js every time.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn set_last_node(&mut self, id: NodeId) {
         self.encode_op(Op::SetLastNode);
         self.encode_id(id);
     }
 
+    /// Push the last node onto the stack of parents so children can be appended to it with [`Batch::pop_parent`] without storing it with an id.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn push_parent(&mut self) {
+        self.encode_op(Op::ParentStack);
+        self.encode_bool(true);
+    }
+
+    /// Append the last node to the topmost parent that was pushed with [`Batch::push_parent`], then pop the parent and make it the last node.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn pop_parent(&mut self) {
+        self.encode_op(Op::ParentStack);
+        self.encode_bool(false);
+    }
+
+    /// Append the last node to the topmost parent and push it onto the stack of parents. Unlike [`Batch::push_parent`], the node is in the document before its children are appended, so a tree can be applied in several batches and the top of the tree is shown before the rest is encoded. Close the node with [`Batch::close_parent`].
+    ///
+    /// Example:
+    /// ```rust
+    /// # use sledgehammer_encoder::{batch::Batch, *};
+    /// let mut batch = Batch::default();
+    /// batch.set_last_node(NodeId(0));
+    /// batch.push_parent();
+    /// batch.create_element(Element::ul, None);
+    /// batch.open_parent();
+    /// batch.create_element(Element::li, None);
+    /// batch.append_to_parent();
+    /// // the parent stack is kept between batches, so the list can be closed in a later batch
+    /// batch.close_parent();
+    /// batch.close_parent();
+    /// assert_eq!(batch.op_count(), 8);
+    /// ```
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn open_parent(&mut self) {
+        self.encode_stream_tree(true, true);
+    }
+
+    /// Append the last node to the topmost parent without popping the parent. The last node does not change.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn append_to_parent(&mut self) {
+        self.encode_stream_tree(true, false);
+    }
+
+    /// Pop the topmost parent without appending the last node to it and make the parent the last node. This closes a node opened with [`Batch::open_parent`] or pushed with [`Batch::push_parent`].
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn close_parent(&mut self) {
+        self.encode_stream_tree(false, false);
+    }
+
+    pub(crate) fn encode_stream_tree(&mut self, append: bool, push: bool) {
+        self.encode_extended_op(ExtendedOp::StreamTree);
+        self.encode_bool(append);
+        self.encode_bool(push);
+    }
+
+    /// Add a listener for an event to a node. When the event is dispatched to the node, the interpreter calls the handler registered with the handle on the rust side.
+    ///
+    /// Adding the same handler for the same event to a node twice only adds one listener, like `addEventListener`.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn add_listener(&mut self, event: impl WritableText, handler: HandlerId, root: MaybeId) {
+        self.encode_listener(true, false, event, handler, root);
+    }
+
+    /// Add a passive listener that dispatches at most one event per animation frame. Events for `scroll`, `wheel` or `pointermove` can fire many times per frame; the interpreter keeps the latest event for each node, event type and handler and dispatches it before the next frame is painted.
+    ///
+    /// The listener is passive, so handlers cannot prevent the default action of the event.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn add_coalesced_listener(
+        &mut self,
+        event: impl WritableText,
+        handler: HandlerId,
+        root: MaybeId,
+    ) {
+        self.encode_listener(true, true, event, handler, root);
+    }
+
+    /// Remove a listener that was added with [`Batch::add_listener`] or [`Batch::add_coalesced_listener`].
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn remove_listener(&mut self, event: impl WritableText, handler: HandlerId, root: MaybeId) {
+        self.encode_listener(false, false, event, handler, root);
+    }
+
+    fn encode_listener(
+        &mut self,
+        add: bool,
+        coalesced: bool,
+        event: impl WritableText,
+        handler: HandlerId,
+        root: MaybeId,
+    ) {
+        self.encode_op(Op::Listener);
+        self.msg.reserve((root.encoded_size() + 4 + 2) as usize);
+        unsafe {
+            self.encode_maybe_id_prealloc(root);
+            self.encode_bool(add);
+            self.encode_bool(coalesced);
+            self.encode_u32_prealloc(handler.0);
+            self.encode_str_prealloc(event);
+        }
+    }
+
+    /// Subscribe a handler to a media query like `(prefers-color-scheme: dark)` or `(min-width: 600px)`. The interpreter dispatches a `change` event with the current state to the handler after the batch is applied, and again every time the query starts or stops matching.
+    ///
+    /// Subscribing the same handler to the same query twice only subscribes it once.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn subscribe_media_query(&mut self, query: impl WritableText, handler: HandlerId) {
+        self.encode_media_query(true, query, handler);
+    }
+
+    /// Unsubscribe a handler that was subscribed with [`Batch::subscribe_media_query`].
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn unsubscribe_media_query(&mut self, query: impl WritableText, handler: HandlerId) {
+        self.encode_media_query(false, query, handler);
+    }
+
+    fn encode_media_query(
+        &mut self,
+        subscribe: bool,
+        query: impl WritableText,
+        handler: HandlerId,
+    ) {
+        self.encode_extended_op(ExtendedOp::MediaQuery);
+        self.msg.reserve(4 + 2);
+        unsafe {
+            self.encode_bool(subscribe);
+            self.encode_u32_prealloc(handler.0);
+            self.encode_str_prealloc(query);
+        }
+    }
+
+    /// Forward the `connectedCallback`, `disconnectedCallback` and `attributeChangedCallback` of the custom element with a tag to a handler. The interpreter dispatches `connected`, `disconnected` and `attributechanged` events with the id of the element to the handler after the callback returns. Elements that are not stored with an id are ignored.
+    ///
+    /// Observing the same tag with the same handler twice only forwards the callbacks once.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn observe_custom_element(&mut self, tag: impl WritableText, handler: HandlerId) {
+        self.encode_custom_element_lifecycle(true, tag, handler);
+    }
+
+    /// Stop forwarding lifecycle callbacks that were forwarded with [`Batch::observe_custom_element`].
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn unobserve_custom_element(&mut self, tag: impl WritableText, handler: HandlerId) {
+        self.encode_custom_element_lifecycle(false, tag, handler);
+    }
+
+    fn encode_custom_element_lifecycle(
+        &mut self,
+        observe: bool,
+        tag: impl WritableText,
+        handler: HandlerId,
+    ) {
+        self.encode_extended_op(ExtendedOp::CustomElementLifecycle);
+        self.msg.reserve(4 + 2);
+        unsafe {
+            self.encode_bool(observe);
+            self.encode_u32_prealloc(handler.0);
+            self.encode_str_prealloc(tag);
+        }
+    }
+
+    /// Show a `<dialog>` element as a modal with `showModal()`. The rest of the page is inert until the dialog is closed.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn show_modal(&mut self, root: MaybeId) {
+        self.encode_top_layer(false, true, root);
+    }
+
+    /// Close a `<dialog>` element with `close()`. The dialog dispatches a `close` event.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn close_dialog(&mut self, root: MaybeId) {
+        self.encode_top_layer(false, false, root);
+    }
+
+    /// Show an element with the `popover` attribute with `showPopover()`. The element dispatches a `toggle` event.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn show_popover(&mut self, root: MaybeId) {
+        self.encode_top_layer(true, true, root);
+    }
+
+    /// Hide an element with the `popover` attribute with `hidePopover()`. The element dispatches a `toggle` event.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn hide_popover(&mut self, root: MaybeId) {
+        self.encode_top_layer(true, false, root);
+    }
+
+    fn encode_top_layer(&mut self, popover: bool, show: bool, root: MaybeId) {
+        self.encode_extended_op(ExtendedOp::TopLayer);
+        self.msg.reserve(root.encoded_size() as usize);
+        unsafe {
+            self.encode_maybe_id_prealloc(root);
+            self.encode_bool(popover);
+            self.encode_bool(show);
+        }
+    }
+
+    /// Show an element in fullscreen with `requestFullscreen()`. The interpreter dispatches the `fullscreenchange` and `fullscreenerror` events of the document to the handler until fullscreen is requested with a different handler.
+    ///
+    /// Browsers only allow fullscreen while handling a user gesture, so flush the batch in an event handler like a click handler.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn request_fullscreen(&mut self, root: MaybeId, handler: HandlerId) {
+        self.encode_lock(ExtendedOp::Fullscreen, Some((root, handler)));
+    }
+
+    /// Leave fullscreen with `document.exitFullscreen()`.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn exit_fullscreen(&mut self) {
+        self.encode_lock(ExtendedOp::Fullscreen, None);
+    }
+
+    /// Lock the pointer to an element with `requestPointerLock()`. The interpreter dispatches the `pointerlockchange` and `pointerlockerror` events of the document to the handler until pointer lock is requested with a different handler.
+    ///
+    /// Browsers only allow pointer lock while handling a user gesture, so flush the batch in an event handler like a click handler.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn request_pointer_lock(&mut self, root: MaybeId, handler: HandlerId) {
+        self.encode_lock(ExtendedOp::PointerLock, Some((root, handler)));
+    }
+
+    /// Release the pointer with `document.exitPointerLock()`.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn exit_pointer_lock(&mut self) {
+        self.encode_lock(ExtendedOp::PointerLock, None);
+    }
+
+    /// Subscribe a handler to animation frames. The interpreter dispatches an `animationframe` event with the timestamp of the frame to the handler in every `requestAnimationFrame` callback until the handler is unsubscribed.
+    ///
+    /// Subscribing the same handler twice only subscribes it once.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn subscribe_animation_frames(&mut self, handler: HandlerId) {
+        self.encode_animation_frames(true, handler);
+    }
+
+    /// Unsubscribe a handler that was subscribed with [`Batch::subscribe_animation_frames`].
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn unsubscribe_animation_frames(&mut self, handler: HandlerId) {
+        self.encode_animation_frames(false, handler);
+    }
+
+    fn encode_animation_frames(&mut self, subscribe: bool, handler: HandlerId) {
+        self.encode_extended_op(ExtendedOp::AnimationFrames);
+        self.msg.reserve(4);
+        unsafe {
+            self.encode_bool(subscribe);
+            self.encode_u32_prealloc(handler.0);
+        }
+    }
+
+    /// Keep the `width` and `height` of a canvas equal to its css size times `devicePixelRatio` with a `ResizeObserver`, so the canvas is drawn at the resolution of the screen. After every resize, the interpreter dispatches a `canvasresize` event with the new size to the handler. The handler also receives the size of the canvas after it is first laid out.
+    ///
+    /// The canvas must be stored with an id when it is observed, so the event can refer to it.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn observe_canvas_size(&mut self, root: MaybeId, handler: HandlerId) {
+        self.encode_canvas_size(root, Some(handler));
+    }
+
+    /// Stop keeping the size of a canvas in sync that was observed with [`Batch::observe_canvas_size`].
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn unobserve_canvas_size(&mut self, root: MaybeId) {
+        self.encode_canvas_size(root, None);
+    }
+
+    fn encode_canvas_size(&mut self, root: MaybeId, handler: Option<HandlerId>) {
+        self.encode_extended_op(ExtendedOp::CanvasSize);
+        self.msg.reserve(root.encoded_size() as usize + 4);
+        unsafe {
+            self.encode_bool(handler.is_some());
+            self.encode_maybe_id_prealloc(root);
+            if let Some(handler) = handler {
+                self.encode_u32_prealloc(handler.0);
+            }
+        }
+    }
+
+    /// Read the focused element. The interpreter answers the query with the id of `document.activeElement`, or with no value if no element is focused or the focused element is not stored with an id.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn active_element(&mut self, query: QueryId) {
+        self.encode_extended_op(ExtendedOp::ActiveElement);
+        self.encode_u32(query.0);
+    }
+
+    /// Read the topmost element at a point in the viewport, in whole css pixels from the top left corner. The interpreter answers the query with the id of `document.elementFromPoint(x, y)`, or with no value if there is no element at the point or the element is not stored with an id.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn element_from_point(&mut self, x: i32, y: i32, query: QueryId) {
+        self.encode_extended_op(ExtendedOp::ElementFromPoint);
+        self.msg.reserve(4 + 4 + 4);
+        unsafe {
+            self.encode_u32_prealloc(query.0);
+            self.encode_u32_prealloc(x as u32);
+            self.encode_u32_prealloc(y as u32);
+        }
+    }
+
+    /// Read if an element is rendered. The interpreter answers the query with `true` or `false` from `checkVisibility()`, which is false if the element or one of its ancestors is not displayed.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn check_visibility(&mut self, root: MaybeId, query: QueryId) {
+        self.encode_extended_op(ExtendedOp::CheckVisibility);
+        self.msg.reserve(root.encoded_size() as usize + 4);
+        unsafe {
+            self.encode_maybe_id_prealloc(root);
+            self.encode_u32_prealloc(query.0);
+        }
+    }
+
+    /// Set when an image loads and how it is decoded. Lazy images get `loading="lazy"` and only load once they are close to the viewport, other images get `loading="eager"`. Images decoded asynchronously get `decoding="async"`, so other content is shown without waiting for them, other images get `decoding="auto"`.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn set_image_loading(&mut self, lazy: bool, async_decoding: bool, root: MaybeId) {
+        self.encode_extended_op(ExtendedOp::SetImageLoading);
+        self.msg.reserve(root.encoded_size() as usize);
+        unsafe {
+            self.encode_bool(lazy);
+            self.encode_bool(async_decoding);
+            self.encode_maybe_id_prealloc(root);
+        }
+    }
+
+    /// Decode an image with `decode()`. The interpreter answers the query with `true` once the image is loaded and decoded, or with `false` if it fails to load or decode. Unlike other queries, the answer usually comes after the batch is applied.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn decode_image(&mut self, root: MaybeId, query: QueryId) {
+        self.encode_extended_op(ExtendedOp::DecodeImage);
+        self.msg.reserve(root.encoded_size() as usize + 4);
+        unsafe {
+            self.encode_maybe_id_prealloc(root);
+            self.encode_u32_prealloc(query.0);
+        }
+    }
+
+    fn encode_lock(&mut self, op: ExtendedOp, request: Option<(MaybeId, HandlerId)>) {
+        self.encode_extended_op(op);
+        match request {
+            Some((root, handler)) => {
+                self.msg.reserve(root.encoded_size() as usize + 4);
+                unsafe {
+                    self.encode_bool(true);
+                    self.encode_maybe_id_prealloc(root);
+                    self.encode_u32_prealloc(handler.0);
+                }
+            }
+            None => self.encode_bool(false),
+        }
+    }
+
+    /// Read an item from local or session storage. The interpreter answers the query with the value of the item, or with no value if the item is not set.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn get_storage_item(&mut self, area: StorageArea, key: impl WritableText, query: QueryId) {
+        self.encode_extended_op(ExtendedOp::GetStorageItem);
+        self.msg.reserve(4 + 2);
+        unsafe {
+            self.encode_bool(area == StorageArea::Session);
+            self.encode_u32_prealloc(query.0);
+            self.encode_str_prealloc(key);
+        }
+    }
+
+    /// Set an item in local or session storage.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn set_storage_item(
+        &mut self,
+        area: StorageArea,
+        key: impl WritableText,
+        value: impl WritableText,
+    ) {
+        self.encode_extended_op(ExtendedOp::SetStorageItem);
+        self.msg.reserve(2 + 2);
+        unsafe {
+            self.encode_bool(area == StorageArea::Session);
+            self.encode_str_prealloc(key);
+            self.encode_str_prealloc(value);
+        }
+    }
+
+    /// Remove an item from local or session storage.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn remove_storage_item(&mut self, area: StorageArea, key: impl WritableText) {
+        self.encode_extended_op(ExtendedOp::RemoveStorageItem);
+        self.msg.reserve(2);
+        unsafe {
+            self.encode_bool(area == StorageArea::Session);
+            self.encode_str_prealloc(key);
+        }
+    }
+
+    /// Grow the table the interpreter stores nodes in to hold ids up to `capacity` at once. Storing nodes with ids below the capacity will not grow the table again, which avoids repeatedly growing it during large initial renders.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn hint_node_capacity(&mut self, capacity: u32) {
+        self.encode_op(Op::HintNodeCapacity);
+        self.encode_u32(capacity);
+    }
+
     /// Build a full element, slightly more efficent than creating the element creating the element with `create_element` and then setting the attributes.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn build_full_element(&mut self, el: ElementBuilder) {
         self.encode_op(Op::BuildFullElement);
         el.encode(self);
     }
 
+    /// Build an element with attributes and children that were added from iterators. See [`OwnedElementBuilder`]
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn build_owned_element(&mut self, el: OwnedElementBuilder) {
+        self.encode_op(Op::BuildFullElement);
+        el.encode(self);
+    }
+
+    /// Build the roots of a fragment and append them to the end of the children of a node. See [`FragmentBuilder`]
+    ///
+    /// The node must be stored with an id because building the roots changes the last node.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn append_fragment(&mut self, parent: NodeId, fragment: FragmentBuilder) {
+        for root in fragment.roots {
+            root.encode_build(self);
+            self.append_child(MaybeId::Node(parent), MaybeId::LastNode);
+        }
+    }
+
+    /// Build the roots of a fragment and insert them before a node. See [`FragmentBuilder`]
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn insert_fragment_before(&mut self, root: NodeId, fragment: FragmentBuilder) {
+        for node in fragment.roots {
+            node.encode_build(self);
+            self.insert_before(MaybeId::Node(root), MaybeId::LastNode);
+        }
+    }
+
+    /// Build the roots of a fragment and insert them after a node. See [`FragmentBuilder`]
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn insert_fragment_after(&mut self, root: NodeId, fragment: FragmentBuilder) {
+        // every root is inserted directly after the node, so the roots are built in reverse to keep them in order
+        for node in fragment.roots.iter().rev() {
+            node.encode_build(self);
+            self.insert_after(MaybeId::Node(root), MaybeId::LastNode);
+        }
+    }
+
+    /// Build the roots of a fragment and replace a node with them. See [`FragmentBuilder`]
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn replace_with_fragment(&mut self, root: NodeId, fragment: FragmentBuilder) {
+        self.insert_fragment_before(root, fragment);
+        self.remove(MaybeId::Node(root));
+    }
+
     /// Build a text node
     pub fn build_text_node(&mut self, text: TextBuilder) {
         self.create_text_node(text.text, text.id)
     }
 
     /// Set a style property on a node.
-    pub fn set_style(&mut self, style: &str, value: &str, id: MaybeId) {
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn set_style(&mut self, style: impl WritableText, value: impl WritableText, id: MaybeId) {
         self.encode_op(Op::SetStyle);
         let size = id.encoded_size() + 2 + 2;
         self.msg.reserve(size as usize);
@@ -374,7 +1197,8 @@ impl Batch {
     }
 
     /// Remove a style property from a node.
-    pub fn remove_style(&mut self, style: &str, id: MaybeId) {
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn remove_style(&mut self, style: impl WritableText, id: MaybeId) {
         self.encode_op(Op::RemoveStyle);
         let size = id.encoded_size() + 2;
         self.msg.reserve(size as usize);
@@ -384,6 +1208,29 @@ impl Batch {
         }
     }
 
+    /// Store the body of a same-origin iframe's content document with the given id so nodes can be built inside of the iframe.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn adopt_iframe_document(&mut self, iframe: MaybeId, id: NodeId) {
+        self.encode_op(Op::AdoptIframeDocument);
+        let size = iframe.encoded_size() + 4;
+        self.msg.reserve(size as usize);
+        unsafe {
+            self.encode_maybe_id_prealloc(iframe);
+            self.encode_id_prealloc(id);
+        }
+    }
+
+    /// Set how many removed nodes with the tag name `tag` the interpreter keeps to reuse when a node with the same tag name is created. Text nodes use the tag name `#text`. A size of 0 disables the pool for the tag name and drops the nodes in it.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn set_pool_size(&mut self, tag: impl WritableText, size: u32) {
+        self.encode_op(Op::SetPoolSize);
+        self.msg.reserve(2 + 4);
+        unsafe {
+            self.encode_str_prealloc(tag);
+            self.encode_u32_prealloc(size);
+        }
+    }
+
     #[inline]
     pub(crate) unsafe fn encode_optional_id_prealloc(&mut self, id: Option<NodeId>) {
         match id {
@@ -446,6 +1293,12 @@ impl Batch {
         self.encode_u32(id.0);
     }
 
+    /// Encode a slice of bytes in the message after its length as a u32
+    pub(crate) fn encode_bytes(&mut self, bytes: &[u8]) {
+        self.encode_u32(bytes.len() as u32);
+        self.msg.extend_from_slice(bytes);
+    }
+
     #[inline(always)]
     pub(crate) fn encode_u32(&mut self, val: u32) {
         self.msg.reserve(4);
@@ -499,6 +1352,7 @@ impl Batch {
         let prev_len = self.str_buf.len();
         string.write_as_text(&mut self.str_buf);
         let len = self.str_buf.len() - prev_len;
+        self.last_str_len_pos = Some(self.msg.len());
         self.encode_u16(len as u16);
     }
 
@@ -507,6 +1361,7 @@ impl Batch {
         let prev_len = self.str_buf.len();
         string.write_as_text(&mut self.str_buf);
         let len = self.str_buf.len() - prev_len;
+        self.last_str_len_pos = Some(self.msg.len());
         self.encode_u16_prealloc(len as u16);
     }
 
@@ -515,6 +1370,7 @@ impl Batch {
         let prev_len = self.str_buf.len();
         string.write_as_text(&mut self.str_buf);
         let len = self.str_buf.len() - prev_len;
+        self.last_str_len_pos = Some(self.msg.len());
         self.encode_u16(len as u16);
     }
 
@@ -541,6 +1397,14 @@ impl Batch {
             }
         }
         self.current_op_bit_pack_index = 0;
+        self.op_count += 1;
+    }
+
+    /// Encode [`Op::Extended`] and the byte that selects the extended operation. The booleans of the extended operation are still encoded in the operation byte
+    pub(crate) fn encode_extended_op(&mut self, op: ExtendedOp) {
+        self.encode_op(Op::Extended);
+        self.msg.reserve(1);
+        self.encode_u8_prealloc(op as u8);
     }
 
     pub(crate) fn encode_bool(&mut self, value: bool) {
@@ -557,17 +1421,93 @@ impl Batch {
         }
     }
 
+    /// Append the operations of another batch to the end of this batch
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn append(&mut self, mut batch: Self) {
         // add empty operations to the batch to make sure the batch is aligned
         let operations_left = 3 - (self.current_op_byte_idx - self.current_op_batch_idx);
         for _ in 0..operations_left {
             self.encode_op(Op::NoOp);
         }
+        // the padding is not counted as operations
+        self.op_count = self.op_count - operations_left + batch.op_count;
 
         self.current_op_byte_idx = self.msg.len() + batch.current_op_byte_idx;
         self.current_op_batch_idx = self.msg.len() + batch.current_op_batch_idx;
         self.current_op_bit_pack_index = batch.current_op_bit_pack_index;
+        if let Some(pos) = batch.last_str_len_pos {
+            self.last_str_len_pos = Some(self.msg.len() + pos);
+        }
         self.str_buf.extend_from_slice(&batch.str_buf);
         self.msg.append(&mut batch.msg);
     }
 }
+
+/// The end of a batch at the start of a [`Batch::scope`]
+#[derive(Clone, Copy)]
+struct Checkpoint {
+    msg_len: usize,
+    str_len: usize,
+    current_op_batch_idx: usize,
+    current_op_byte_idx: usize,
+    current_op_bit_pack_index: u8,
+    op_count: usize,
+    last_str_len_pos: Option<usize>,
+}
+
+/// Rolls the batch back to the checkpoint when it is dropped before the scope is done, which also happens while a panic unwinds
+struct Scope<'a> {
+    batch: &'a mut Batch,
+    checkpoint: Checkpoint,
+    done: bool,
+}
+
+impl Drop for Scope<'_> {
+    fn drop(&mut self) {
+        if !self.done {
+            self.batch.rollback(self.checkpoint);
+        }
+    }
+}
+
+/// Appends formatted text to the last string encoded in a [`Batch`]. See [`Batch::str_writer`]
+pub struct StrWriter<'a> {
+    batch: &'a mut Batch,
+}
+
+impl Write for StrWriter<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let pos = self.batch.last_str_len_pos.ok_or(core::fmt::Error)?;
+        let len_bytes = &mut self.batch.msg[pos..pos + 2];
+        let len = u16::from_le_bytes([len_bytes[0], len_bytes[1]]) as usize + s.len();
+        let len = u16::try_from(len).map_err(|_| core::fmt::Error)?;
+        len_bytes.copy_from_slice(&len.to_le_bytes());
+        self.batch.str_buf.extend_from_slice(s.as_bytes());
+        Ok(())
+    }
+}
+
+impl Extend<Batch> for Batch {
+    fn extend<T: IntoIterator<Item = Batch>>(&mut self, iter: T) {
+        for batch in iter {
+            self.append(batch);
+        }
+    }
+}
+
+// every type used to build a batch must stay Send and Sync so batches can be encoded off the main thread
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Batch>();
+    assert_send_sync::<FinalizedBatch>();
+    assert_send_sync::<StaticBatch>();
+    assert_send_sync::<Op>();
+    assert_send_sync::<MaybeId>();
+    assert_send_sync::<NodeId>();
+    assert_send_sync::<ElementBuilder>();
+    assert_send_sync::<TextBuilder>();
+    assert_send_sync::<crate::NodeBuilder>();
+    assert_send_sync::<crate::element::AnyElement>();
+    assert_send_sync::<crate::attribute::AnyAttribute>();
+    assert_send_sync::<crate::decoder::DecodedOp>();
+};
@@ -0,0 +1,244 @@
+//! LZ4 compression for sending finalized batches over the network.
+//!
+//! Batches for big renders repeat the same operations, elements and attributes many times, so they compress well. The compressed format is a byte with the [`WIRE_FORMAT_VERSION`] the batch was encoded with, a little endian u32 with the length of the message, a little endian u32 with the length of the string buffer, and then the message followed by the string buffer as one [LZ4 block](https://github.com/lz4/lz4/blob/dev/doc/lz4_Block_format.md).
+//!
+//! `decompress.js` in the sledgehammer crate decompresses the same format in the browser. Both decompressors reject batches with a different format version, so a server and client from different releases fail loudly instead of applying operations they would misread.
+//!
+//! Example:
+//! ```rust
+//! # use sledgehammer_encoder::batch::{Batch, FinalizedBatch};
+//! let mut batch = Batch::default();
+//! for _ in 0..100 {
+//!     batch.create_element("div", None);
+//! }
+//! let finalized = batch.finalize();
+//! let compressed = finalized.to_compressed_bytes();
+//! assert!(compressed.len() < finalized.msg.len());
+//! let decompressed = FinalizedBatch::from_compressed_bytes(&compressed).unwrap();
+//! assert_eq!(decompressed.msg, finalized.msg);
+//! ```
+
+use alloc::vec::Vec;
+use core::fmt::Display;
+
+use crate::{batch::FinalizedBatch, WIRE_FORMAT_VERSION};
+
+/// The shortest match LZ4 can encode
+const MIN_MATCH: usize = 4;
+/// The last five bytes of a block are always literals
+const LAST_LITERALS: usize = 5;
+/// A match must start at least twelve bytes before the end of a block
+const MFLIMIT: usize = 12;
+/// Offsets are encoded as a u16
+const MAX_OFFSET: usize = u16::MAX as usize;
+const HASH_LOG: u32 = 12;
+
+/// An error that occurred while decompressing a batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecompressError {
+    /// The input ended in the middle of a sequence or header
+    UnexpectedEnd,
+    /// A match refers to data before the start of the output
+    InvalidOffset,
+    /// The decompressed data is not the length the header says it is
+    LengthMismatch,
+    /// The batch was encoded with a different version of the binary format
+    VersionMismatch {
+        /// The version this crate reads
+        expected: u8,
+        /// The version in the header of the batch
+        found: u8,
+    },
+}
+
+impl Display for DecompressError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            DecompressError::UnexpectedEnd => write!(f, "the compressed batch ended unexpectedly"),
+            DecompressError::InvalidOffset => {
+                write!(f, "the compressed batch refers to data before its start")
+            }
+            DecompressError::LengthMismatch => {
+                write!(
+                    f,
+                    "the compressed batch does not match the length in its header"
+                )
+            }
+            DecompressError::VersionMismatch { expected, found } => write!(
+                f,
+                "the compressed batch was encoded with format version {found} but format version {expected} was expected"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecompressError {}
+
+fn hash(sequence: u32) -> usize {
+    (sequence.wrapping_mul(2654435761) >> (32 - HASH_LOG)) as usize
+}
+
+fn read_u32(input: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([input[pos], input[pos + 1], input[pos + 2], input[pos + 3]])
+}
+
+/// Write a length that did not fit in the token as a sequence of bytes that are summed
+fn write_length(mut len: usize, out: &mut Vec<u8>) {
+    while len >= 255 {
+        out.push(255);
+        len -= 255;
+    }
+    out.push(len as u8);
+}
+
+fn write_sequence(literals: &[u8], offset: u16, match_len: usize, out: &mut Vec<u8>) {
+    let token_literals = literals.len().min(15);
+    let token_match = match_len.saturating_sub(MIN_MATCH).min(15);
+    out.push((token_literals << 4 | token_match) as u8);
+    if literals.len() >= 15 {
+        write_length(literals.len() - 15, out);
+    }
+    out.extend_from_slice(literals);
+    if match_len >= MIN_MATCH {
+        out.extend_from_slice(&offset.to_le_bytes());
+        if match_len - MIN_MATCH >= 15 {
+            write_length(match_len - MIN_MATCH - 15, out);
+        }
+    }
+}
+
+/// Compress bytes into a single LZ4 block
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len() / 2 + 16);
+    let mut table = alloc::vec![usize::MAX; 1 << HASH_LOG];
+    let mut anchor = 0;
+    let mut pos = 0;
+    if input.len() > MFLIMIT {
+        let match_limit = input.len() - LAST_LITERALS;
+        while pos < input.len() - MFLIMIT {
+            let sequence = read_u32(input, pos);
+            let h = hash(sequence);
+            let candidate = table[h];
+            table[h] = pos;
+            if candidate == usize::MAX
+                || pos - candidate > MAX_OFFSET
+                || read_u32(input, candidate) != sequence
+            {
+                pos += 1;
+                continue;
+            }
+            let mut len = MIN_MATCH;
+            while pos + len < match_limit && input[candidate + len] == input[pos + len] {
+                len += 1;
+            }
+            write_sequence(&input[anchor..pos], (pos - candidate) as u16, len, &mut out);
+            pos += len;
+            anchor = pos;
+        }
+    }
+    write_sequence(&input[anchor..], 0, 0, &mut out);
+    out
+}
+
+/// Decompress a single LZ4 block into `len` bytes
+pub fn decompress(input: &[u8], len: usize) -> Result<Vec<u8>, DecompressError> {
+    fn read_length(input: &[u8], pos: &mut usize) -> Result<usize, DecompressError> {
+        let mut len = 0;
+        loop {
+            let byte = *input.get(*pos).ok_or(DecompressError::UnexpectedEnd)?;
+            *pos += 1;
+            len += byte as usize;
+            if byte != 255 {
+                return Ok(len);
+            }
+        }
+    }
+
+    // every input byte can expand to at most 255 output bytes, so a corrupt header cannot allocate much more than that
+    let mut out: Vec<u8> = Vec::with_capacity(len.min(input.len().saturating_mul(255)));
+    let mut pos = 0;
+    while pos < input.len() {
+        let token = input[pos];
+        pos += 1;
+        let mut literals = (token >> 4) as usize;
+        if literals == 15 {
+            literals += read_length(input, &mut pos)?;
+        }
+        let end = pos
+            .checked_add(literals)
+            .filter(|end| *end <= input.len())
+            .ok_or(DecompressError::UnexpectedEnd)?;
+        if out.len() + literals > len {
+            return Err(DecompressError::LengthMismatch);
+        }
+        out.extend_from_slice(&input[pos..end]);
+        pos = end;
+        // the last sequence only contains literals
+        if pos == input.len() {
+            break;
+        }
+        let offset = input
+            .get(pos..pos + 2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+            .ok_or(DecompressError::UnexpectedEnd)?;
+        pos += 2;
+        let mut match_len = (token & 0xF) as usize;
+        if match_len == 15 {
+            match_len += read_length(input, &mut pos)?;
+        }
+        match_len += MIN_MATCH;
+        if offset == 0 || offset > out.len() {
+            return Err(DecompressError::InvalidOffset);
+        }
+        if out.len() + match_len > len {
+            return Err(DecompressError::LengthMismatch);
+        }
+        // matches can overlap the bytes they copy, so copy one byte at a time
+        let start = out.len() - offset;
+        for i in 0..match_len {
+            out.push(out[start + i]);
+        }
+    }
+    if out.len() != len {
+        return Err(DecompressError::LengthMismatch);
+    }
+    Ok(out)
+}
+
+impl FinalizedBatch {
+    /// Serialize the batch and compress it. See [`crate::compression`] for the format
+    pub fn to_compressed_bytes(&self) -> Vec<u8> {
+        let mut joined = Vec::with_capacity(self.msg.len() + self.str.len());
+        joined.extend_from_slice(&self.msg);
+        joined.extend_from_slice(&self.str);
+        let mut out = Vec::with_capacity(9 + joined.len() / 2);
+        out.push(WIRE_FORMAT_VERSION);
+        out.extend_from_slice(&(self.msg.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(self.str.len() as u32).to_le_bytes());
+        out.extend_from_slice(&compress(&joined));
+        out
+    }
+
+    /// Read a batch that was compressed with [`FinalizedBatch::to_compressed_bytes`]
+    pub fn from_compressed_bytes(bytes: &[u8]) -> Result<Self, DecompressError> {
+        let (&version, bytes) = bytes.split_first().ok_or(DecompressError::UnexpectedEnd)?;
+        if version != WIRE_FORMAT_VERSION {
+            return Err(DecompressError::VersionMismatch {
+                expected: WIRE_FORMAT_VERSION,
+                found: version,
+            });
+        }
+        if bytes.len() < 8 {
+            return Err(DecompressError::UnexpectedEnd);
+        }
+        let msg_len = read_u32(bytes, 0) as usize;
+        let str_len = read_u32(bytes, 4) as usize;
+        let len = msg_len
+            .checked_add(str_len)
+            .ok_or(DecompressError::LengthMismatch)?;
+        let mut msg = decompress(&bytes[8..], len)?;
+        let str = msg.split_off(msg_len);
+        Ok(Self { msg, str })
+    }
+}
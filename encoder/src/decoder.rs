@@ -0,0 +1,1143 @@
+//! Decodes the operations in a batch back into rust types.
+//! This follows the same format the javascript interpreter reads, so it can be used to inspect the operations in a batch before they are sent to the interpreter.
+
+use alloc::{vec, vec::Vec};
+use core::fmt::Display;
+
+#[cfg(feature = "attributes")]
+use crate::Attribute;
+#[cfg(feature = "elements")]
+use crate::Element;
+use crate::{
+    attribute::AnyAttribute, batch::Batch, element::AnyElement, ElementBuilder, ExtendedOp,
+    HandlerId, InNamespace, MaybeId, NodeId, Op, QueryId, StorageArea, TextBuilder,
+};
+
+/// A single decoded operation
+#[derive(Debug, PartialEq, Eq)]
+pub enum DecodedOp<'a> {
+    FirstChild,
+    NextSibling,
+    ParentNode,
+    StoreWithId(NodeId),
+    SetLastNode(NodeId),
+    Stop,
+    BuildFullElement(DecodedNode<'a>),
+    AppendChildren {
+        root: MaybeId,
+        child: MaybeId,
+    },
+    ReplaceWith {
+        root: MaybeId,
+        nodes: Vec<MaybeId>,
+    },
+    InsertAfter {
+        root: MaybeId,
+        nodes: Vec<MaybeId>,
+    },
+    InsertBefore {
+        root: MaybeId,
+        nodes: Vec<MaybeId>,
+    },
+    Remove(MaybeId),
+    CreateTextNode {
+        text: &'a str,
+        id: Option<NodeId>,
+    },
+    CreateElement {
+        tag: AnyElement<'a, 'a>,
+        id: Option<NodeId>,
+    },
+    SetText {
+        root: MaybeId,
+        text: &'a str,
+    },
+    SetAttribute {
+        root: MaybeId,
+        attr: AnyAttribute<'a, 'a>,
+        value: &'a str,
+    },
+    RemoveAttribute {
+        root: MaybeId,
+        attr: AnyAttribute<'a, 'a>,
+    },
+    SetStyle {
+        root: MaybeId,
+        style: &'a str,
+        value: &'a str,
+    },
+    RemoveStyle {
+        root: MaybeId,
+        style: &'a str,
+    },
+    CloneNode {
+        id: MaybeId,
+        new_id: MaybeId,
+    },
+    NoOp,
+    AdoptIframeDocument {
+        iframe: MaybeId,
+        id: NodeId,
+    },
+    SetPoolSize {
+        tag: &'a str,
+        size: u32,
+    },
+    HintNodeCapacity(u32),
+    CacheValue {
+        id: u8,
+        value: &'a str,
+    },
+    SetAttributeCached {
+        root: MaybeId,
+        attr: AnyAttribute<'a, 'a>,
+        value: u8,
+    },
+    PushParent,
+    PopParent,
+    AddListener {
+        root: MaybeId,
+        event: &'a str,
+        handler: HandlerId,
+        /// If the listener was added with [`Batch::add_coalesced_listener`]
+        coalesced: bool,
+    },
+    RemoveListener {
+        root: MaybeId,
+        event: &'a str,
+        handler: HandlerId,
+    },
+    SetValue {
+        root: MaybeId,
+        value: &'a str,
+    },
+    SubscribeMediaQuery {
+        query: &'a str,
+        handler: HandlerId,
+    },
+    UnsubscribeMediaQuery {
+        query: &'a str,
+        handler: HandlerId,
+    },
+    GetStorageItem {
+        area: StorageArea,
+        key: &'a str,
+        query: QueryId,
+    },
+    SetStorageItem {
+        area: StorageArea,
+        key: &'a str,
+        value: &'a str,
+    },
+    RemoveStorageItem {
+        area: StorageArea,
+        key: &'a str,
+    },
+    ObserveCustomElement {
+        tag: &'a str,
+        handler: HandlerId,
+    },
+    UnobserveCustomElement {
+        tag: &'a str,
+        handler: HandlerId,
+    },
+    ShowModal(MaybeId),
+    CloseDialog(MaybeId),
+    ShowPopover(MaybeId),
+    HidePopover(MaybeId),
+    RequestFullscreen {
+        root: MaybeId,
+        handler: HandlerId,
+    },
+    ExitFullscreen,
+    RequestPointerLock {
+        root: MaybeId,
+        handler: HandlerId,
+    },
+    ExitPointerLock,
+    SubscribeAnimationFrames(HandlerId),
+    UnsubscribeAnimationFrames(HandlerId),
+    ObserveCanvasSize {
+        root: MaybeId,
+        handler: HandlerId,
+    },
+    UnobserveCanvasSize(MaybeId),
+    ActiveElement(QueryId),
+    ElementFromPoint {
+        x: i32,
+        y: i32,
+        query: QueryId,
+    },
+    CheckVisibility {
+        root: MaybeId,
+        query: QueryId,
+    },
+    ToggleClass {
+        root: MaybeId,
+        class: &'a str,
+        add: bool,
+    },
+    SetImageLoading {
+        root: MaybeId,
+        lazy: bool,
+        async_decoding: bool,
+    },
+    DecodeImage {
+        root: MaybeId,
+        query: QueryId,
+    },
+    /// The text of a node formatted by the browser. The value is a number written the way javascript parses it, and the options are a json object. See [`crate::intl`]
+    SetFormattedText {
+        root: MaybeId,
+        date: bool,
+        value: &'a str,
+        locale: &'a str,
+        options: &'a str,
+    },
+    /// A finalized batch applied inside of a view transition. It can be decoded with [`Decoder::new`]
+    ViewTransition {
+        handler: HandlerId,
+        msg: &'a [u8],
+        str_buf: &'a [u8],
+    },
+    /// Append the last node to the topmost parent and push it
+    OpenParent,
+    /// Append the last node to the topmost parent without popping it
+    AppendToParent,
+    /// Pop the topmost parent without appending to it
+    CloseParent,
+    /// Html that replaces the children of a node after it is cleaned. The allowed elements and attributes are separated by commas. See [`crate::sanitize`]
+    SetSanitizedHtml {
+        root: MaybeId,
+        html: &'a str,
+        elements: &'a str,
+        attributes: &'a str,
+    },
+}
+
+/// A node decoded from a [`crate::ElementBuilder`] or [`crate::TextBuilder`]
+#[derive(Debug, PartialEq, Eq)]
+pub enum DecodedNode<'a> {
+    Text {
+        id: Option<NodeId>,
+        text: &'a str,
+    },
+    Element {
+        id: Option<NodeId>,
+        kind: AnyElement<'a, 'a>,
+        attrs: Vec<(AnyAttribute<'a, 'a>, &'a str)>,
+        children: Vec<DecodedNode<'a>>,
+    },
+}
+
+/// The reason a batch could not be decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeErrorKind {
+    /// The message ended in the middle of an operation
+    UnexpectedEnd,
+    /// The string buffer ended before the string was read
+    UnexpectedStrEnd,
+    /// The operation byte does not match any operation
+    UnknownOp(u8),
+    /// The element byte does not match any built-in element
+    UnknownElement(u8),
+    /// The attribute byte does not match any built-in attribute
+    UnknownAttribute(u8),
+    /// The byte after an extended operation does not match any extended operation
+    UnknownExtendedOp(u8),
+    /// The string is not valid utf-8
+    InvalidUtf8,
+}
+
+/// An error that occurred while decoding a batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeError {
+    /// The offset in bytes of the operation that could not be decoded from the start of the message
+    pub offset: usize,
+    pub kind: DecodeErrorKind,
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "failed to decode the operation at offset {}: ",
+            self.offset
+        )?;
+        match self.kind {
+            DecodeErrorKind::UnexpectedEnd => write!(f, "unexpected end of message"),
+            DecodeErrorKind::UnexpectedStrEnd => write!(f, "unexpected end of strings"),
+            DecodeErrorKind::UnknownOp(op) => write!(f, "unknown operation {}", op),
+            DecodeErrorKind::UnknownElement(el) => write!(f, "unknown element {}", el),
+            DecodeErrorKind::UnknownAttribute(attr) => write!(f, "unknown attribute {}", attr),
+            DecodeErrorKind::UnknownExtendedOp(op) => {
+                write!(f, "unknown extended operation {}", op)
+            }
+            DecodeErrorKind::InvalidUtf8 => write!(f, "invalid utf-8"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {}
+
+/// The position of a [`Decoder`] in a message. Decoding can be continued from this position later with [`Decoder::resume`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecoderState {
+    pos: usize,
+    str_pos: usize,
+    op_batch_idx: usize,
+    op_idx: usize,
+    done: bool,
+}
+
+/// An iterator over the operations in a batch along with the offset of each operation byte from the start of the message.
+///
+/// Decoding stops after a [`Op::Stop`] operation, at the end of the message, or after the first error.
+pub struct Decoder<'a> {
+    msg: &'a [u8],
+    str_buf: &'a [u8],
+    pos: usize,
+    str_pos: usize,
+    op_batch_idx: usize,
+    op_idx: usize,
+    last_op_idx: Option<usize>,
+    op_offset: usize,
+    done: bool,
+}
+
+impl<'a> Decoder<'a> {
+    /// Decode a finalized message and string buffer. Decoding stops at the [`Op::Stop`] operation.
+    pub fn new(msg: &'a [u8], str_buf: &'a [u8]) -> Self {
+        Self {
+            msg,
+            str_buf,
+            pos: 0,
+            str_pos: 0,
+            op_batch_idx: 0,
+            op_idx: 4,
+            last_op_idx: None,
+            op_offset: 0,
+            done: false,
+        }
+    }
+
+    /// Decode a batch that may not be finalized. Decoding stops after the last operation that was encoded.
+    pub fn from_batch(batch: &'a Batch) -> Self {
+        let mut decoder = Self::new(&batch.msg, &batch.str_buf);
+        decoder.last_op_idx = Some(batch.current_op_byte_idx);
+        decoder
+    }
+
+    /// Continue decoding a finalized message and string buffer from a position returned by [`Decoder::state`]. The message and strings must be the same ones the state was taken from.
+    pub fn resume(msg: &'a [u8], str_buf: &'a [u8], state: DecoderState) -> Self {
+        Self {
+            msg,
+            str_buf,
+            pos: state.pos,
+            str_pos: state.str_pos,
+            op_batch_idx: state.op_batch_idx,
+            op_idx: state.op_idx,
+            last_op_idx: None,
+            op_offset: state.op_batch_idx + state.op_idx,
+            done: state.done,
+        }
+    }
+
+    /// The position of the decoder after the last operation it returned
+    pub fn state(&self) -> DecoderState {
+        DecoderState {
+            pos: self.pos,
+            str_pos: self.str_pos,
+            op_batch_idx: self.op_batch_idx,
+            op_idx: self.op_idx,
+            done: self.done,
+        }
+    }
+
+    fn error(&mut self, kind: DecodeErrorKind) -> DecodeError {
+        self.done = true;
+        DecodeError {
+            offset: self.op_offset,
+            kind,
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8, DecodeError> {
+        match self.msg.get(self.pos) {
+            Some(v) => {
+                self.pos += 1;
+                Ok(*v)
+            }
+            None => Err(self.error(DecodeErrorKind::UnexpectedEnd)),
+        }
+    }
+
+    fn u16(&mut self) -> Result<u16, DecodeError> {
+        match self.msg.get(self.pos..self.pos + 2) {
+            Some(v) => {
+                self.pos += 2;
+                Ok(u16::from_le_bytes([v[0], v[1]]))
+            }
+            None => Err(self.error(DecodeErrorKind::UnexpectedEnd)),
+        }
+    }
+
+    fn u32(&mut self) -> Result<u32, DecodeError> {
+        match self.msg.get(self.pos..self.pos + 4) {
+            Some(v) => {
+                self.pos += 4;
+                Ok(u32::from_le_bytes([v[0], v[1], v[2], v[3]]))
+            }
+            None => Err(self.error(DecodeErrorKind::UnexpectedEnd)),
+        }
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], DecodeError> {
+        let len = self.u32()? as usize;
+        let msg = self.msg;
+        match msg.get(self.pos..self.pos.saturating_add(len)) {
+            Some(bytes) => {
+                self.pos += len;
+                Ok(bytes)
+            }
+            None => Err(self.error(DecodeErrorKind::UnexpectedEnd)),
+        }
+    }
+
+    fn id(&mut self) -> Result<NodeId, DecodeError> {
+        self.u32().map(NodeId)
+    }
+
+    fn maybe_id(&mut self, has_id: bool) -> Result<MaybeId, DecodeError> {
+        Ok(if has_id {
+            MaybeId::Node(self.id()?)
+        } else {
+            MaybeId::LastNode
+        })
+    }
+
+    fn optional_id(&mut self, has_id: bool) -> Result<Option<NodeId>, DecodeError> {
+        Ok(if has_id { Some(self.id()?) } else { None })
+    }
+
+    fn maybe_id_u8_discriminant(&mut self) -> Result<MaybeId, DecodeError> {
+        let has_id = self.u8()? != 0;
+        self.maybe_id(has_id)
+    }
+
+    fn str(&mut self) -> Result<&'a str, DecodeError> {
+        let len = self.u16()? as usize;
+        let str_buf = self.str_buf;
+        let bytes = match str_buf.get(self.str_pos..self.str_pos + len) {
+            Some(bytes) => bytes,
+            None => return Err(self.error(DecodeErrorKind::UnexpectedStrEnd)),
+        };
+        self.str_pos += len;
+        core::str::from_utf8(bytes).map_err(|_| self.error(DecodeErrorKind::InvalidUtf8))
+    }
+
+    #[cfg(feature = "elements")]
+    fn element(&mut self, v: u8) -> Result<Element, DecodeError> {
+        Element::from_u8(v).ok_or_else(|| self.error(DecodeErrorKind::UnknownElement(v)))
+    }
+
+    #[cfg(feature = "attributes")]
+    fn attribute(&mut self, v: u8) -> Result<Attribute, DecodeError> {
+        Attribute::from_u8(v).ok_or_else(|| self.error(DecodeErrorKind::UnknownAttribute(v)))
+    }
+
+    fn any_element(&mut self) -> Result<AnyElement<'a, 'a>, DecodeError> {
+        Ok(match self.u8()? {
+            #[cfg(feature = "elements")]
+            255 => {
+                let el = self.u8()?;
+                let el = self.element(el)?;
+                AnyElement::InNamespace(InNamespace(el, self.str()?))
+            }
+            254 => AnyElement::Str(self.str()?),
+            253 => {
+                let name = self.str()?;
+                AnyElement::InNamespaceStr(InNamespace(name, self.str()?))
+            }
+            #[cfg(feature = "elements")]
+            el => AnyElement::Element(self.element(el)?),
+            // single byte elements cannot be decoded without the element table
+            #[cfg(not(feature = "elements"))]
+            el => return Err(self.error(DecodeErrorKind::UnknownElement(el))),
+        })
+    }
+
+    fn any_attribute_u8_discriminant(&mut self) -> Result<AnyAttribute<'a, 'a>, DecodeError> {
+        Ok(match self.u8()? {
+            #[cfg(feature = "attributes")]
+            255 => {
+                let attr = self.u8()?;
+                let attr = self.attribute(attr)?;
+                AnyAttribute::InNamespace(InNamespace(attr, self.str()?))
+            }
+            254 => AnyAttribute::Str(self.str()?),
+            253 => {
+                let name = self.str()?;
+                AnyAttribute::InNamespaceStr(InNamespace(name, self.str()?))
+            }
+            #[cfg(feature = "attributes")]
+            attr => AnyAttribute::Attribute(self.attribute(attr)?),
+            // single byte attributes cannot be decoded without the attribute table
+            #[cfg(not(feature = "attributes"))]
+            attr => return Err(self.error(DecodeErrorKind::UnknownAttribute(attr))),
+        })
+    }
+
+    fn any_attribute_bit_packed(
+        &mut self,
+        is_str: bool,
+        has_ns: bool,
+    ) -> Result<AnyAttribute<'a, 'a>, DecodeError> {
+        Ok(match (is_str, has_ns) {
+            #[cfg(feature = "attributes")]
+            (false, false) => {
+                let attr = self.u8()?;
+                AnyAttribute::Attribute(self.attribute(attr)?)
+            }
+            #[cfg(feature = "attributes")]
+            (false, true) => {
+                let attr = self.u8()?;
+                let attr = self.attribute(attr)?;
+                AnyAttribute::InNamespace(InNamespace(attr, self.str()?))
+            }
+            (true, false) => AnyAttribute::Str(self.str()?),
+            (true, true) => {
+                let name = self.str()?;
+                AnyAttribute::InNamespaceStr(InNamespace(name, self.str()?))
+            }
+            #[cfg(not(feature = "attributes"))]
+            (false, _) => {
+                let attr = self.u8()?;
+                return Err(self.error(DecodeErrorKind::UnknownAttribute(attr)));
+            }
+        })
+    }
+
+    fn node(&mut self) -> Result<DecodedNode<'a>, DecodeError> {
+        let header = self.u8()?;
+        let id = self.optional_id(header & 0x1 != 0)?;
+        if header & 0x2 != 0 {
+            return Ok(DecodedNode::Text {
+                id,
+                text: self.str()?,
+            });
+        }
+        let kind = self.any_element()?;
+        let num_attrs = self.u8()?;
+        let num_children = self.u8()?;
+        let mut attrs = Vec::with_capacity(num_attrs as usize);
+        for _ in 0..num_attrs {
+            let attr = self.any_attribute_u8_discriminant()?;
+            attrs.push((attr, self.str()?));
+        }
+        let mut children = Vec::with_capacity(num_children as usize);
+        for _ in 0..num_children {
+            children.push(self.node()?);
+        }
+        Ok(DecodedNode::Element {
+            id,
+            kind,
+            attrs,
+            children,
+        })
+    }
+
+    fn nodes(&mut self, many: bool, has_id: bool) -> Result<Vec<MaybeId>, DecodeError> {
+        if many {
+            let len = self.u8()?;
+            (0..len).map(|_| self.maybe_id_u8_discriminant()).collect()
+        } else {
+            Ok(vec![self.maybe_id(has_id)?])
+        }
+    }
+
+    fn op(&mut self, byte: u8) -> Result<DecodedOp<'a>, DecodeError> {
+        // the first three bools are encoded in the top three bits of the operation byte
+        let first = byte & 0x20 != 0;
+        let second = byte & 0x40 != 0;
+        let third = byte & 0x80 != 0;
+        const FIRST_CHILD: u8 = Op::FirstChild as u8;
+        const NEXT_SIBLING: u8 = Op::NextSibling as u8;
+        const PARENT_NODE: u8 = Op::ParentNode as u8;
+        const STORE_WITH_ID: u8 = Op::StoreWithId as u8;
+        const SET_LAST_NODE: u8 = Op::SetLastNode as u8;
+        const STOP: u8 = Op::Stop as u8;
+        const BUILD_FULL_ELEMENT: u8 = Op::BuildFullElement as u8;
+        const APPEND_CHILDREN: u8 = Op::AppendChildren as u8;
+        const REPLACE_WITH: u8 = Op::ReplaceWith as u8;
+        const INSERT_AFTER: u8 = Op::InsertAfter as u8;
+        const INSERT_BEFORE: u8 = Op::InsertBefore as u8;
+        const REMOVE: u8 = Op::Remove as u8;
+        const CREATE_TEXT_NODE: u8 = Op::CreateTextNode as u8;
+        const CREATE_ELEMENT: u8 = Op::CreateElement as u8;
+        const SET_TEXT: u8 = Op::SetText as u8;
+        const SET_ATTRIBUTE: u8 = Op::SetAttribute as u8;
+        const REMOVE_ATTRIBUTE: u8 = Op::RemoveAttribute as u8;
+        const SET_STYLE: u8 = Op::SetStyle as u8;
+        const REMOVE_STYLE: u8 = Op::RemoveStyle as u8;
+        const CLONE_NODE: u8 = Op::CloneNode as u8;
+        const NO_OP: u8 = Op::NoOp as u8;
+        const ADOPT_IFRAME_DOCUMENT: u8 = Op::AdoptIframeDocument as u8;
+        const SET_POOL_SIZE: u8 = Op::SetPoolSize as u8;
+        const HINT_NODE_CAPACITY: u8 = Op::HintNodeCapacity as u8;
+        const CACHE_VALUE: u8 = Op::CacheValue as u8;
+        const SET_ATTRIBUTE_CACHED: u8 = Op::SetAttributeCached as u8;
+        const PARENT_STACK: u8 = Op::ParentStack as u8;
+        const LISTENER: u8 = Op::Listener as u8;
+        const SET_VALUE: u8 = Op::SetValue as u8;
+        const EXTENDED: u8 = Op::Extended as u8;
+        const MEDIA_QUERY: u8 = ExtendedOp::MediaQuery as u8;
+        const GET_STORAGE_ITEM: u8 = ExtendedOp::GetStorageItem as u8;
+        const SET_STORAGE_ITEM: u8 = ExtendedOp::SetStorageItem as u8;
+        const REMOVE_STORAGE_ITEM: u8 = ExtendedOp::RemoveStorageItem as u8;
+        const CUSTOM_ELEMENT_LIFECYCLE: u8 = ExtendedOp::CustomElementLifecycle as u8;
+        const TOP_LAYER: u8 = ExtendedOp::TopLayer as u8;
+        const FULLSCREEN: u8 = ExtendedOp::Fullscreen as u8;
+        const POINTER_LOCK: u8 = ExtendedOp::PointerLock as u8;
+        const ANIMATION_FRAMES: u8 = ExtendedOp::AnimationFrames as u8;
+        const CANVAS_SIZE: u8 = ExtendedOp::CanvasSize as u8;
+        const ACTIVE_ELEMENT: u8 = ExtendedOp::ActiveElement as u8;
+        const ELEMENT_FROM_POINT: u8 = ExtendedOp::ElementFromPoint as u8;
+        const CHECK_VISIBILITY: u8 = ExtendedOp::CheckVisibility as u8;
+        const TOGGLE_CLASS: u8 = ExtendedOp::ToggleClass as u8;
+        const SET_FORMATTED_TEXT: u8 = ExtendedOp::SetFormattedText as u8;
+        const VIEW_TRANSITION: u8 = ExtendedOp::ViewTransition as u8;
+        const STREAM_TREE: u8 = ExtendedOp::StreamTree as u8;
+        const SET_SANITIZED_HTML: u8 = ExtendedOp::SetSanitizedHtml as u8;
+        const SET_IMAGE_LOADING: u8 = ExtendedOp::SetImageLoading as u8;
+        const DECODE_IMAGE: u8 = ExtendedOp::DecodeImage as u8;
+        let area = if first {
+            StorageArea::Session
+        } else {
+            StorageArea::Local
+        };
+        Ok(match byte & 0x1F {
+            FIRST_CHILD => DecodedOp::FirstChild,
+            NEXT_SIBLING => DecodedOp::NextSibling,
+            PARENT_NODE => DecodedOp::ParentNode,
+            STORE_WITH_ID => DecodedOp::StoreWithId(self.id()?),
+            SET_LAST_NODE => DecodedOp::SetLastNode(self.id()?),
+            STOP => DecodedOp::Stop,
+            BUILD_FULL_ELEMENT => DecodedOp::BuildFullElement(self.node()?),
+            APPEND_CHILDREN => {
+                let root = self.maybe_id(first)?;
+                DecodedOp::AppendChildren {
+                    root,
+                    child: self.maybe_id(second)?,
+                }
+            }
+            REPLACE_WITH | INSERT_AFTER | INSERT_BEFORE => {
+                let root = self.maybe_id(second)?;
+                let nodes = self.nodes(first, third)?;
+                match byte & 0x1F {
+                    REPLACE_WITH => DecodedOp::ReplaceWith { root, nodes },
+                    INSERT_AFTER => DecodedOp::InsertAfter { root, nodes },
+                    _ => DecodedOp::InsertBefore { root, nodes },
+                }
+            }
+            REMOVE => DecodedOp::Remove(self.maybe_id(first)?),
+            CREATE_TEXT_NODE => {
+                let text = self.str()?;
+                DecodedOp::CreateTextNode {
+                    text,
+                    id: self.optional_id(first)?,
+                }
+            }
+            CREATE_ELEMENT => {
+                let tag = self.any_element()?;
+                DecodedOp::CreateElement {
+                    tag,
+                    id: self.optional_id(first)?,
+                }
+            }
+            SET_TEXT => {
+                let root = self.maybe_id(first)?;
+                DecodedOp::SetText {
+                    root,
+                    text: self.str()?,
+                }
+            }
+            SET_ATTRIBUTE => {
+                let root = self.maybe_id(first)?;
+                let attr = self.any_attribute_bit_packed(second, third)?;
+                DecodedOp::SetAttribute {
+                    root,
+                    attr,
+                    value: self.str()?,
+                }
+            }
+            REMOVE_ATTRIBUTE => {
+                let root = self.maybe_id(first)?;
+                DecodedOp::RemoveAttribute {
+                    root,
+                    attr: self.any_attribute_bit_packed(second, third)?,
+                }
+            }
+            SET_STYLE => {
+                let root = self.maybe_id(first)?;
+                let style = self.str()?;
+                DecodedOp::SetStyle {
+                    root,
+                    style,
+                    value: self.str()?,
+                }
+            }
+            REMOVE_STYLE => {
+                let root = self.maybe_id(first)?;
+                DecodedOp::RemoveStyle {
+                    root,
+                    style: self.str()?,
+                }
+            }
+            CLONE_NODE => {
+                let id = self.maybe_id(first)?;
+                DecodedOp::CloneNode {
+                    id,
+                    new_id: self.maybe_id(second)?,
+                }
+            }
+            NO_OP => DecodedOp::NoOp,
+            ADOPT_IFRAME_DOCUMENT => {
+                let iframe = self.maybe_id(first)?;
+                DecodedOp::AdoptIframeDocument {
+                    iframe,
+                    id: self.id()?,
+                }
+            }
+            SET_POOL_SIZE => DecodedOp::SetPoolSize {
+                tag: self.str()?,
+                size: self.u32()?,
+            },
+            HINT_NODE_CAPACITY => DecodedOp::HintNodeCapacity(self.u32()?),
+            CACHE_VALUE => DecodedOp::CacheValue {
+                id: self.u8()?,
+                value: self.str()?,
+            },
+            SET_ATTRIBUTE_CACHED => {
+                let root = self.maybe_id(first)?;
+                let attr = self.any_attribute_bit_packed(second, third)?;
+                DecodedOp::SetAttributeCached {
+                    root,
+                    attr,
+                    value: self.u8()?,
+                }
+            }
+            PARENT_STACK if first => DecodedOp::PushParent,
+            PARENT_STACK => DecodedOp::PopParent,
+            LISTENER => {
+                let root = self.maybe_id(first)?;
+                let handler = HandlerId(self.u32()?);
+                let event = self.str()?;
+                if second {
+                    DecodedOp::AddListener {
+                        root,
+                        event,
+                        handler,
+                        coalesced: third,
+                    }
+                } else {
+                    DecodedOp::RemoveListener {
+                        root,
+                        event,
+                        handler,
+                    }
+                }
+            }
+            SET_VALUE => {
+                let root = self.maybe_id(first)?;
+                DecodedOp::SetValue {
+                    root,
+                    value: self.str()?,
+                }
+            }
+            EXTENDED => match self.u8()? {
+                MEDIA_QUERY => {
+                    let handler = HandlerId(self.u32()?);
+                    let query = self.str()?;
+                    if first {
+                        DecodedOp::SubscribeMediaQuery { query, handler }
+                    } else {
+                        DecodedOp::UnsubscribeMediaQuery { query, handler }
+                    }
+                }
+                GET_STORAGE_ITEM => {
+                    let query = QueryId(self.u32()?);
+                    DecodedOp::GetStorageItem {
+                        area,
+                        key: self.str()?,
+                        query,
+                    }
+                }
+                SET_STORAGE_ITEM => {
+                    let key = self.str()?;
+                    DecodedOp::SetStorageItem {
+                        area,
+                        key,
+                        value: self.str()?,
+                    }
+                }
+                REMOVE_STORAGE_ITEM => DecodedOp::RemoveStorageItem {
+                    area,
+                    key: self.str()?,
+                },
+                CUSTOM_ELEMENT_LIFECYCLE => {
+                    let handler = HandlerId(self.u32()?);
+                    let tag = self.str()?;
+                    if first {
+                        DecodedOp::ObserveCustomElement { tag, handler }
+                    } else {
+                        DecodedOp::UnobserveCustomElement { tag, handler }
+                    }
+                }
+                TOP_LAYER => {
+                    let root = self.maybe_id(first)?;
+                    match (second, third) {
+                        (false, true) => DecodedOp::ShowModal(root),
+                        (false, false) => DecodedOp::CloseDialog(root),
+                        (true, true) => DecodedOp::ShowPopover(root),
+                        (true, false) => DecodedOp::HidePopover(root),
+                    }
+                }
+                FULLSCREEN => {
+                    if first {
+                        let root = self.maybe_id(second)?;
+                        DecodedOp::RequestFullscreen {
+                            root,
+                            handler: HandlerId(self.u32()?),
+                        }
+                    } else {
+                        DecodedOp::ExitFullscreen
+                    }
+                }
+                POINTER_LOCK => {
+                    if first {
+                        let root = self.maybe_id(second)?;
+                        DecodedOp::RequestPointerLock {
+                            root,
+                            handler: HandlerId(self.u32()?),
+                        }
+                    } else {
+                        DecodedOp::ExitPointerLock
+                    }
+                }
+                ANIMATION_FRAMES => {
+                    let handler = HandlerId(self.u32()?);
+                    if first {
+                        DecodedOp::SubscribeAnimationFrames(handler)
+                    } else {
+                        DecodedOp::UnsubscribeAnimationFrames(handler)
+                    }
+                }
+                CANVAS_SIZE => {
+                    let root = self.maybe_id(second)?;
+                    if first {
+                        DecodedOp::ObserveCanvasSize {
+                            root,
+                            handler: HandlerId(self.u32()?),
+                        }
+                    } else {
+                        DecodedOp::UnobserveCanvasSize(root)
+                    }
+                }
+                ACTIVE_ELEMENT => DecodedOp::ActiveElement(QueryId(self.u32()?)),
+                ELEMENT_FROM_POINT => {
+                    let query = QueryId(self.u32()?);
+                    let x = self.u32()? as i32;
+                    DecodedOp::ElementFromPoint {
+                        x,
+                        y: self.u32()? as i32,
+                        query,
+                    }
+                }
+                CHECK_VISIBILITY => {
+                    let root = self.maybe_id(first)?;
+                    DecodedOp::CheckVisibility {
+                        root,
+                        query: QueryId(self.u32()?),
+                    }
+                }
+                TOGGLE_CLASS => {
+                    let root = self.maybe_id(second)?;
+                    DecodedOp::ToggleClass {
+                        root,
+                        class: self.str()?,
+                        add: first,
+                    }
+                }
+                SET_FORMATTED_TEXT => {
+                    let root = self.maybe_id(first)?;
+                    let value = self.str()?;
+                    let locale = self.str()?;
+                    DecodedOp::SetFormattedText {
+                        root,
+                        date: second,
+                        value,
+                        locale,
+                        options: self.str()?,
+                    }
+                }
+                VIEW_TRANSITION => {
+                    let handler = HandlerId(self.u32()?);
+                    let msg = self.bytes()?;
+                    DecodedOp::ViewTransition {
+                        handler,
+                        msg,
+                        str_buf: self.bytes()?,
+                    }
+                }
+                STREAM_TREE => match (first, second) {
+                    (true, true) => DecodedOp::OpenParent,
+                    (true, false) => DecodedOp::AppendToParent,
+                    (false, _) => DecodedOp::CloseParent,
+                },
+                SET_IMAGE_LOADING => DecodedOp::SetImageLoading {
+                    root: self.maybe_id(third)?,
+                    lazy: first,
+                    async_decoding: second,
+                },
+                DECODE_IMAGE => {
+                    let root = self.maybe_id(first)?;
+                    DecodedOp::DecodeImage {
+                        root,
+                        query: QueryId(self.u32()?),
+                    }
+                }
+                SET_SANITIZED_HTML => {
+                    let root = self.maybe_id(first)?;
+                    let html = self.str()?;
+                    let elements = self.str()?;
+                    DecodedOp::SetSanitizedHtml {
+                        root,
+                        html,
+                        elements,
+                        attributes: self.str()?,
+                    }
+                }
+                op => return Err(self.error(DecodeErrorKind::UnknownExtendedOp(op))),
+            },
+            op => return Err(self.error(DecodeErrorKind::UnknownOp(op))),
+        })
+    }
+}
+
+impl<'a> Iterator for Decoder<'a> {
+    type Item = Result<(usize, DecodedOp<'a>), DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        // operations are packed into batches of four bytes followed by the data for each operation
+        if self.op_idx == 4 {
+            if self.pos >= self.msg.len() {
+                self.done = true;
+                return None;
+            }
+            self.op_batch_idx = self.pos;
+            self.op_idx = 0;
+            self.pos += 4;
+            if self.pos > self.msg.len() {
+                self.op_offset = self.op_batch_idx;
+                return Some(Err(self.error(DecodeErrorKind::UnexpectedEnd)));
+            }
+        }
+        self.op_offset = self.op_batch_idx + self.op_idx;
+        if let Some(last) = self.last_op_idx {
+            if self.op_offset > last {
+                self.done = true;
+                return None;
+            }
+            if self.op_offset == last {
+                self.done = true;
+            }
+        }
+        self.op_idx += 1;
+        let byte = self.msg[self.op_offset];
+        let offset = self.op_offset;
+        Some(self.op(byte).map(|op| {
+            if let DecodedOp::Stop = op {
+                self.done = true;
+            }
+            (offset, op)
+        }))
+    }
+}
+
+impl DecodedNode<'_> {
+    /// Encode the node in the same format as a [`crate::ElementBuilder`] or [`crate::TextBuilder`]
+    pub(crate) fn encode(&self, v: &mut Batch) {
+        match self {
+            DecodedNode::Text { id, text } => TextBuilder { id: *id, text }.encode(v),
+            DecodedNode::Element {
+                id,
+                kind,
+                attrs,
+                children,
+            } => {
+                let mut el = ElementBuilder::new(*kind).attrs(attrs);
+                if let Some(id) = id {
+                    el = el.id(*id);
+                }
+                el.encode_without_children(v, children.len() as u8);
+                for child in children {
+                    child.encode(v);
+                }
+            }
+        }
+    }
+}
+
+impl Batch {
+    /// Decode the operations that have been encoded in this batch so far
+    pub fn decode(&self) -> Decoder<'_> {
+        Decoder::from_batch(self)
+    }
+
+    /// Build a node that was decoded from a batch or assembled from a captured dom tree. Like [`ElementBuilder`], elements can have at most 255 attributes and 255 children.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn build_decoded_node(&mut self, node: &DecodedNode) {
+        self.encode_op(Op::BuildFullElement);
+        node.encode(self);
+    }
+
+    /// Encode an operation that was decoded from another batch. [`DecodedOp::Stop`] and [`DecodedOp::NoOp`] are skipped because they only mark the end and the padding of a batch.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn encode_decoded_op(&mut self, op: &DecodedOp) {
+        match *op {
+            DecodedOp::FirstChild => self.first_child(),
+            DecodedOp::NextSibling => self.next_sibling(),
+            DecodedOp::ParentNode => self.parent_node(),
+            DecodedOp::StoreWithId(id) => self.store_with_id(id),
+            DecodedOp::SetLastNode(id) => self.set_last_node(id),
+            DecodedOp::Stop | DecodedOp::NoOp => {}
+            DecodedOp::BuildFullElement(ref node) => self.build_decoded_node(node),
+            DecodedOp::AppendChildren { root, child } => self.append_child(root, child),
+            DecodedOp::ReplaceWith { root, ref nodes } => match nodes[..] {
+                [node] => self.replace_with(root, node),
+                _ => self.replace_with_nodes(root, nodes),
+            },
+            DecodedOp::InsertAfter { root, ref nodes } => match nodes[..] {
+                [node] => self.insert_after(root, node),
+                _ => self.insert_nodes_after(root, nodes),
+            },
+            DecodedOp::InsertBefore { root, ref nodes } => match nodes[..] {
+                [node] => self.insert_before(root, node),
+                _ => self.insert_nodes_before(root, nodes),
+            },
+            DecodedOp::Remove(id) => self.remove(id),
+            DecodedOp::CreateTextNode { text, id } => self.create_text_node(text, id),
+            DecodedOp::CreateElement { tag, id } => self.create_element(tag, id),
+            DecodedOp::SetText { root, text } => self.set_text(text, root),
+            DecodedOp::SetAttribute { root, attr, value } => self.set_attribute(attr, value, root),
+            DecodedOp::RemoveAttribute { root, attr } => self.remove_attribute(attr, root),
+            DecodedOp::SetStyle { root, style, value } => self.set_style(style, value, root),
+            DecodedOp::RemoveStyle { root, style } => self.remove_style(style, root),
+            DecodedOp::CloneNode { id, new_id } => self.clone_node(id, new_id),
+            DecodedOp::AdoptIframeDocument { iframe, id } => self.adopt_iframe_document(iframe, id),
+            DecodedOp::SetPoolSize { tag, size } => self.set_pool_size(tag, size),
+            DecodedOp::HintNodeCapacity(capacity) => self.hint_node_capacity(capacity),
+            DecodedOp::CacheValue { id, value } => self.cache_value(id, value),
+            DecodedOp::SetAttributeCached { root, attr, value } => {
+                self.set_attribute_cached(attr, value, root)
+            }
+            DecodedOp::PushParent => self.push_parent(),
+            DecodedOp::PopParent => self.pop_parent(),
+            DecodedOp::AddListener {
+                root,
+                event,
+                handler,
+                coalesced: false,
+            } => self.add_listener(event, handler, root),
+            DecodedOp::AddListener {
+                root,
+                event,
+                handler,
+                coalesced: true,
+            } => self.add_coalesced_listener(event, handler, root),
+            DecodedOp::RemoveListener {
+                root,
+                event,
+                handler,
+            } => self.remove_listener(event, handler, root),
+            DecodedOp::SetValue { root, value } => self.set_value(value, root),
+            DecodedOp::SubscribeMediaQuery { query, handler } => {
+                self.subscribe_media_query(query, handler)
+            }
+            DecodedOp::UnsubscribeMediaQuery { query, handler } => {
+                self.unsubscribe_media_query(query, handler)
+            }
+            DecodedOp::GetStorageItem { area, key, query } => {
+                self.get_storage_item(area, key, query)
+            }
+            DecodedOp::SetStorageItem { area, key, value } => {
+                self.set_storage_item(area, key, value)
+            }
+            DecodedOp::RemoveStorageItem { area, key } => self.remove_storage_item(area, key),
+            DecodedOp::ObserveCustomElement { tag, handler } => {
+                self.observe_custom_element(tag, handler)
+            }
+            DecodedOp::UnobserveCustomElement { tag, handler } => {
+                self.unobserve_custom_element(tag, handler)
+            }
+            DecodedOp::ShowModal(root) => self.show_modal(root),
+            DecodedOp::CloseDialog(root) => self.close_dialog(root),
+            DecodedOp::ShowPopover(root) => self.show_popover(root),
+            DecodedOp::HidePopover(root) => self.hide_popover(root),
+            DecodedOp::RequestFullscreen { root, handler } => {
+                self.request_fullscreen(root, handler)
+            }
+            DecodedOp::ExitFullscreen => self.exit_fullscreen(),
+            DecodedOp::RequestPointerLock { root, handler } => {
+                self.request_pointer_lock(root, handler)
+            }
+            DecodedOp::ExitPointerLock => self.exit_pointer_lock(),
+            DecodedOp::SubscribeAnimationFrames(handler) => {
+                self.subscribe_animation_frames(handler)
+            }
+            DecodedOp::UnsubscribeAnimationFrames(handler) => {
+                self.unsubscribe_animation_frames(handler)
+            }
+            DecodedOp::ObserveCanvasSize { root, handler } => {
+                self.observe_canvas_size(root, handler)
+            }
+            DecodedOp::UnobserveCanvasSize(root) => self.unobserve_canvas_size(root),
+            DecodedOp::ActiveElement(query) => self.active_element(query),
+            DecodedOp::ElementFromPoint { x, y, query } => self.element_from_point(x, y, query),
+            DecodedOp::CheckVisibility { root, query } => self.check_visibility(root, query),
+            DecodedOp::ToggleClass { root, class, add } => self.toggle_class_if(class, add, root),
+            DecodedOp::SetImageLoading {
+                root,
+                lazy,
+                async_decoding,
+            } => self.set_image_loading(lazy, async_decoding, root),
+            DecodedOp::DecodeImage { root, query } => self.decode_image(root, query),
+            DecodedOp::SetFormattedText {
+                root,
+                date,
+                value,
+                locale,
+                options,
+            } => self.encode_formatted_text(root, date, value, locale, options),
+            DecodedOp::ViewTransition {
+                handler,
+                msg,
+                str_buf,
+            } => self.encode_view_transition(handler, msg, str_buf),
+            DecodedOp::OpenParent => self.open_parent(),
+            DecodedOp::AppendToParent => self.append_to_parent(),
+            DecodedOp::CloseParent => self.close_parent(),
+            DecodedOp::SetSanitizedHtml {
+                root,
+                html,
+                elements,
+                attributes,
+            } => self.encode_sanitized_html(root, html, elements, attributes),
+        }
+    }
+}
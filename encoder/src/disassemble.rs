@@ -0,0 +1,369 @@
+use alloc::string::String;
+use core::fmt::Write;
+
+use crate::{
+    attribute::AnyAttribute,
+    batch::{Batch, FinalizedBatch},
+    decoder::{DecodedNode, DecodedOp, Decoder},
+    element::AnyElement,
+    MaybeId, NodeId,
+};
+
+impl Batch {
+    /// Decode the operations in the batch into human readable lines, one operation per line prefixed with the offset of the operation.
+    pub fn disassemble(&self) -> String {
+        disassemble(Decoder::from_batch(self))
+    }
+}
+
+impl FinalizedBatch {
+    /// Decode the operations in the batch into human readable lines, one operation per line prefixed with the offset of the operation.
+    pub fn disassemble(&self) -> String {
+        disassemble(Decoder::new(&self.msg, &self.str))
+    }
+}
+
+fn disassemble(decoder: Decoder) -> String {
+    let mut out = String::new();
+    for op in decoder {
+        match op {
+            Ok((offset, op)) => {
+                let _ = write!(out, "{:04} ", offset);
+                write_op(&mut out, &op);
+                out.push('\n');
+            }
+            Err(err) => {
+                let _ = writeln!(out, "{:04} <{}>", err.offset, err);
+            }
+        }
+    }
+    out
+}
+
+struct Id(MaybeId);
+
+impl core::fmt::Display for Id {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.0 {
+            MaybeId::LastNode => write!(f, "last"),
+            MaybeId::Node(id) => write!(f, "#{}", Generational(id)),
+        }
+    }
+}
+
+struct Generational(NodeId);
+
+impl core::fmt::Display for Generational {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.0.generation() {
+            0 => write!(f, "{}", self.0.index()),
+            generation => write!(f, "{}@{}", self.0.index(), generation),
+        }
+    }
+}
+
+fn write_ids(out: &mut String, nodes: &[MaybeId]) {
+    out.push('[');
+    for (i, node) in nodes.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        let _ = write!(out, "{}", Id(*node));
+    }
+    out.push(']');
+}
+
+fn write_optional_id(out: &mut String, id: Option<NodeId>) {
+    if let Some(id) = id {
+        let _ = write!(out, " id={}", Generational(id));
+    }
+}
+
+fn write_element(out: &mut String, el: &AnyElement) {
+    let _ = match el {
+        #[cfg(feature = "elements")]
+        AnyElement::Element(el) => write!(out, "{}", el.name()),
+        #[cfg(feature = "elements")]
+        AnyElement::InNamespace(el) => write!(out, "{} ns={:?}", el.0.name(), el.1),
+        AnyElement::Str(el) => write!(out, "{:?}", el),
+        AnyElement::InNamespaceStr(el) => write!(out, "{:?} ns={:?}", el.0, el.1),
+    };
+}
+
+fn write_attribute(out: &mut String, attr: &AnyAttribute) {
+    let _ = match attr {
+        #[cfg(feature = "attributes")]
+        AnyAttribute::Attribute(attr) => write!(out, "{}", attr.name()),
+        #[cfg(feature = "attributes")]
+        AnyAttribute::InNamespace(attr) => write!(out, "{} ns={:?}", attr.0.name(), attr.1),
+        AnyAttribute::Str(attr) => write!(out, "{:?}", attr),
+        AnyAttribute::InNamespaceStr(attr) => write!(out, "{:?} ns={:?}", attr.0, attr.1),
+    };
+}
+
+fn write_node(out: &mut String, node: &DecodedNode, depth: usize) {
+    out.push('\n');
+    for _ in 0..depth {
+        out.push_str("  ");
+    }
+    match node {
+        DecodedNode::Text { id, text } => {
+            let _ = write!(out, "{:?}", text);
+            write_optional_id(out, *id);
+        }
+        DecodedNode::Element {
+            id,
+            kind,
+            attrs,
+            children,
+        } => {
+            write_element(out, kind);
+            write_optional_id(out, *id);
+            let _ = write!(out, " attrs={} children={}", attrs.len(), children.len());
+            for (attr, value) in attrs {
+                out.push('\n');
+                for _ in 0..depth + 1 {
+                    out.push_str("  ");
+                }
+                write_attribute(out, attr);
+                let _ = write!(out, " = {:?}", value);
+            }
+            for child in children {
+                write_node(out, child, depth + 1);
+            }
+        }
+    }
+}
+
+fn write_op(out: &mut String, op: &DecodedOp) {
+    let _ = match op {
+        DecodedOp::FirstChild => write!(out, "FirstChild"),
+        DecodedOp::NextSibling => write!(out, "NextSibling"),
+        DecodedOp::ParentNode => write!(out, "ParentNode"),
+        DecodedOp::StoreWithId(id) => write!(out, "StoreWithId id={}", Generational(*id)),
+        DecodedOp::SetLastNode(id) => write!(out, "SetLastNode id={}", Generational(*id)),
+        DecodedOp::Stop => write!(out, "Stop"),
+        DecodedOp::BuildFullElement(node) => {
+            out.push_str("BuildFullElement");
+            write_node(out, node, 3);
+            Ok(())
+        }
+        DecodedOp::AppendChildren { root, child } => {
+            write!(out, "AppendChildren {} child={}", Id(*root), Id(*child))
+        }
+        DecodedOp::ReplaceWith { root, nodes } => {
+            let _ = write!(out, "ReplaceWith {} nodes=", Id(*root));
+            write_ids(out, nodes);
+            Ok(())
+        }
+        DecodedOp::InsertAfter { root, nodes } => {
+            let _ = write!(out, "InsertAfter {} nodes=", Id(*root));
+            write_ids(out, nodes);
+            Ok(())
+        }
+        DecodedOp::InsertBefore { root, nodes } => {
+            let _ = write!(out, "InsertBefore {} nodes=", Id(*root));
+            write_ids(out, nodes);
+            Ok(())
+        }
+        DecodedOp::Remove(id) => write!(out, "Remove {}", Id(*id)),
+        DecodedOp::CreateTextNode { text, id } => {
+            let _ = write!(out, "CreateTextNode {:?}", text);
+            write_optional_id(out, *id);
+            Ok(())
+        }
+        DecodedOp::CreateElement { tag, id } => {
+            out.push_str("CreateElement ");
+            write_element(out, tag);
+            write_optional_id(out, *id);
+            Ok(())
+        }
+        DecodedOp::SetText { root, text } => write!(out, "SetText {} {:?}", Id(*root), text),
+        DecodedOp::SetAttribute { root, attr, value } => {
+            let _ = write!(out, "SetAttribute {} ", Id(*root));
+            write_attribute(out, attr);
+            write!(out, " = {:?}", value)
+        }
+        DecodedOp::RemoveAttribute { root, attr } => {
+            let _ = write!(out, "RemoveAttribute {} ", Id(*root));
+            write_attribute(out, attr);
+            Ok(())
+        }
+        DecodedOp::SetStyle { root, style, value } => {
+            write!(out, "SetStyle {} {:?} = {:?}", Id(*root), style, value)
+        }
+        DecodedOp::RemoveStyle { root, style } => {
+            write!(out, "RemoveStyle {} {:?}", Id(*root), style)
+        }
+        DecodedOp::CloneNode { id, new_id } => {
+            write!(out, "CloneNode {} new_id={}", Id(*id), Id(*new_id))
+        }
+        DecodedOp::NoOp => write!(out, "NoOp"),
+        DecodedOp::AdoptIframeDocument { iframe, id } => {
+            write!(
+                out,
+                "AdoptIframeDocument {} id={}",
+                Id(*iframe),
+                Generational(*id)
+            )
+        }
+        DecodedOp::SetPoolSize { tag, size } => write!(out, "SetPoolSize {:?} size={}", tag, size),
+        DecodedOp::HintNodeCapacity(capacity) => write!(out, "HintNodeCapacity {}", capacity),
+        DecodedOp::CacheValue { id, value } => write!(out, "CacheValue {} = {:?}", id, value),
+        DecodedOp::SetAttributeCached { root, attr, value } => {
+            let _ = write!(out, "SetAttributeCached {} ", Id(*root));
+            write_attribute(out, attr);
+            write!(out, " = cached {}", value)
+        }
+        DecodedOp::PushParent => write!(out, "PushParent"),
+        DecodedOp::PopParent => write!(out, "PopParent"),
+        DecodedOp::OpenParent => write!(out, "OpenParent"),
+        DecodedOp::AppendToParent => write!(out, "AppendToParent"),
+        DecodedOp::CloseParent => write!(out, "CloseParent"),
+        DecodedOp::SetSanitizedHtml {
+            root,
+            html,
+            elements,
+            attributes,
+        } => write!(
+            out,
+            "SetSanitizedHtml {} {:?} elements={} attributes={}",
+            Id(*root),
+            html,
+            elements,
+            attributes
+        ),
+        DecodedOp::AddListener {
+            root,
+            event,
+            handler,
+            coalesced,
+        } => write!(
+            out,
+            "AddListener {} {:?} handler={}{}",
+            Id(*root),
+            event,
+            handler.0,
+            if *coalesced { " coalesced" } else { "" }
+        ),
+        DecodedOp::RemoveListener {
+            root,
+            event,
+            handler,
+        } => write!(
+            out,
+            "RemoveListener {} {:?} handler={}",
+            Id(*root),
+            event,
+            handler.0
+        ),
+        DecodedOp::SetValue { root, value } => write!(out, "SetValue {} {:?}", Id(*root), value),
+        DecodedOp::SubscribeMediaQuery { query, handler } => {
+            write!(out, "SubscribeMediaQuery {:?} handler={}", query, handler.0)
+        }
+        DecodedOp::UnsubscribeMediaQuery { query, handler } => {
+            write!(
+                out,
+                "UnsubscribeMediaQuery {:?} handler={}",
+                query, handler.0
+            )
+        }
+        DecodedOp::GetStorageItem { area, key, query } => {
+            write!(out, "GetStorageItem {:?} {:?} query={}", area, key, query.0)
+        }
+        DecodedOp::SetStorageItem { area, key, value } => {
+            write!(out, "SetStorageItem {:?} {:?} = {:?}", area, key, value)
+        }
+        DecodedOp::RemoveStorageItem { area, key } => {
+            write!(out, "RemoveStorageItem {:?} {:?}", area, key)
+        }
+        DecodedOp::ObserveCustomElement { tag, handler } => {
+            write!(out, "ObserveCustomElement {:?} handler={}", tag, handler.0)
+        }
+        DecodedOp::UnobserveCustomElement { tag, handler } => {
+            write!(
+                out,
+                "UnobserveCustomElement {:?} handler={}",
+                tag, handler.0
+            )
+        }
+        DecodedOp::ShowModal(root) => write!(out, "ShowModal {}", Id(*root)),
+        DecodedOp::CloseDialog(root) => write!(out, "CloseDialog {}", Id(*root)),
+        DecodedOp::ShowPopover(root) => write!(out, "ShowPopover {}", Id(*root)),
+        DecodedOp::HidePopover(root) => write!(out, "HidePopover {}", Id(*root)),
+        DecodedOp::RequestFullscreen { root, handler } => {
+            write!(out, "RequestFullscreen {} handler={}", Id(*root), handler.0)
+        }
+        DecodedOp::ExitFullscreen => write!(out, "ExitFullscreen"),
+        DecodedOp::RequestPointerLock { root, handler } => {
+            write!(
+                out,
+                "RequestPointerLock {} handler={}",
+                Id(*root),
+                handler.0
+            )
+        }
+        DecodedOp::ExitPointerLock => write!(out, "ExitPointerLock"),
+        DecodedOp::SubscribeAnimationFrames(handler) => {
+            write!(out, "SubscribeAnimationFrames handler={}", handler.0)
+        }
+        DecodedOp::UnsubscribeAnimationFrames(handler) => {
+            write!(out, "UnsubscribeAnimationFrames handler={}", handler.0)
+        }
+        DecodedOp::ObserveCanvasSize { root, handler } => {
+            write!(out, "ObserveCanvasSize {} handler={}", Id(*root), handler.0)
+        }
+        DecodedOp::UnobserveCanvasSize(root) => write!(out, "UnobserveCanvasSize {}", Id(*root)),
+        DecodedOp::ActiveElement(query) => write!(out, "ActiveElement query={}", query.0),
+        DecodedOp::ElementFromPoint { x, y, query } => {
+            write!(out, "ElementFromPoint {} {} query={}", x, y, query.0)
+        }
+        DecodedOp::CheckVisibility { root, query } => {
+            write!(out, "CheckVisibility {} query={}", Id(*root), query.0)
+        }
+        DecodedOp::ToggleClass { root, class, add } => {
+            write!(out, "ToggleClass {} {:?} add={}", Id(*root), class, add)
+        }
+        DecodedOp::SetImageLoading {
+            root,
+            lazy,
+            async_decoding,
+        } => write!(
+            out,
+            "SetImageLoading {} lazy={} async_decoding={}",
+            Id(*root),
+            lazy,
+            async_decoding
+        ),
+        DecodedOp::DecodeImage { root, query } => {
+            write!(out, "DecodeImage {} query={}", Id(*root), query.0)
+        }
+        DecodedOp::SetFormattedText {
+            root,
+            date,
+            value,
+            locale,
+            options,
+        } => write!(
+            out,
+            "SetFormattedText {} {} date={} locale={:?} options={}",
+            Id(*root),
+            value,
+            date,
+            locale,
+            options
+        ),
+        DecodedOp::ViewTransition {
+            handler,
+            msg,
+            str_buf,
+        } => {
+            let _ = write!(out, "ViewTransition handler={}", handler.0);
+            // the operations of the nested batch are listed below the transition with their offsets in the nested batch
+            for line in disassemble(Decoder::new(msg, str_buf)).lines() {
+                let _ = write!(out, "\n      {}", line);
+            }
+            Ok(())
+        }
+    };
+}
@@ -1,22 +1,40 @@
 #![allow(non_camel_case_types)]
 
-use crate::{attribute::AnyAttribute, batch::Batch, InNamespace, NodeId};
+use alloc::{borrow::Cow, string::String, vec::Vec};
+
+use crate::{
+    attribute::AnyAttribute,
+    batch::{Batch, Op},
+    InNamespace, NodeId,
+};
 
 use self::sealed::Sealed;
+#[cfg(feature = "elements")]
+use crate::names::NameTable;
 
 mod sealed {
-    use crate::{Element, InNamespace};
+    use alloc::{borrow::Cow, string::String};
+
+    use crate::InNamespace;
 
     pub trait Sealed {}
 
-    impl Sealed for Element {}
-    impl<'a> Sealed for &'a str {}
-    impl<'a> Sealed for InNamespace<'a, Element> {}
+    #[cfg(feature = "elements")]
+    impl Sealed for crate::Element {}
+    impl Sealed for &str {}
+    impl Sealed for &String {}
+    impl Sealed for &Cow<'_, str> {}
+    #[cfg(feature = "elements")]
+    impl<'a> Sealed for InNamespace<'a, crate::Element> {}
     impl<'a, 'b> Sealed for InNamespace<'a, &'b str> {}
+    impl Sealed for super::AnyElement<'_, '_> {}
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum AnyElement<'a, 'b> {
+    #[cfg(feature = "elements")]
     Element(Element),
+    #[cfg(feature = "elements")]
     InNamespace(InNamespace<'a, Element>),
     Str(&'a str),
     InNamespaceStr(InNamespace<'a, &'b str>),
@@ -25,7 +43,9 @@ pub enum AnyElement<'a, 'b> {
 impl AnyElement<'_, '_> {
     pub fn encode(&self, v: &mut Batch) {
         match self {
+            #[cfg(feature = "elements")]
             AnyElement::Element(a) => a.encode(v),
+            #[cfg(feature = "elements")]
             AnyElement::InNamespace(a) => a.encode(v),
             AnyElement::Str(a) => a.encode(v),
             AnyElement::InNamespaceStr(a) => a.encode(v),
@@ -34,7 +54,9 @@ impl AnyElement<'_, '_> {
 
     pub(crate) unsafe fn encode_prealloc(&self, v: &mut Batch) {
         match self {
+            #[cfg(feature = "elements")]
             AnyElement::Element(a) => a.encode_prealloc(v),
+            #[cfg(feature = "elements")]
             AnyElement::InNamespace(a) => a.encode_prealloc(v),
             AnyElement::Str(a) => a.encode_prealloc(v),
             AnyElement::InNamespaceStr(a) => a.encode_prealloc(v),
@@ -43,14 +65,22 @@ impl AnyElement<'_, '_> {
 
     pub(crate) fn size(&self) -> usize {
         match self {
+            #[cfg(feature = "elements")]
             AnyElement::Element(_) => 1,
-            AnyElement::InNamespace(_) => 1 + 2,
-            AnyElement::Str(_) => 2,
-            AnyElement::InNamespaceStr(_) => 2 + 2,
+            #[cfg(feature = "elements")]
+            AnyElement::InNamespace(_) => 1 + 1 + 2,
+            AnyElement::Str(_) => 1 + 2,
+            AnyElement::InNamespaceStr(_) => 1 + 2 + 2,
         }
     }
 }
 
+impl<'a, 'b> IntoElement<'a, 'b> for AnyElement<'a, 'b> {
+    fn encode(&self, v: &mut Batch) {
+        AnyElement::encode(self, v)
+    }
+}
+
 /// Anything that can be turned into an element name
 pub trait IntoElement<'a, 'b>: Sealed + Into<AnyElement<'a, 'b>> {
     /// If the element name can be encoded in a single byte
@@ -71,12 +101,14 @@ pub trait IntoElement<'a, 'b>: Sealed + Into<AnyElement<'a, 'b>> {
     }
 }
 
+#[cfg(feature = "elements")]
 impl<'a, 'b> Element {
     pub const fn any_element_const(self) -> AnyElement<'a, 'b> {
         AnyElement::Element(self)
     }
 }
 
+#[cfg(feature = "elements")]
 impl<'a, 'b> IntoElement<'a, 'b> for Element {
     const SINGLE_BYTE: bool = true;
 
@@ -99,12 +131,14 @@ impl<'a, 'b> IntoElement<'a, 'b> for Element {
     }
 }
 
+#[cfg(feature = "elements")]
 impl<'a, 'b> From<Element> for AnyElement<'a, 'b> {
     fn from(e: Element) -> Self {
         AnyElement::Element(e)
     }
 }
 
+#[cfg(feature = "elements")]
 impl<'a, 'b> InNamespace<'a, Element> {
     /// Turn into an [`AnyElement`] in a const context
     pub const fn any_element_const(self) -> AnyElement<'a, 'b> {
@@ -112,6 +146,7 @@ impl<'a, 'b> InNamespace<'a, Element> {
     }
 }
 
+#[cfg(feature = "elements")]
 impl<'a, 'b> IntoElement<'a, 'b> for InNamespace<'a, Element> {
     fn encode(&self, v: &mut Batch) {
         v.msg.push(255);
@@ -120,6 +155,7 @@ impl<'a, 'b> IntoElement<'a, 'b> for InNamespace<'a, Element> {
     }
 }
 
+#[cfg(feature = "elements")]
 impl<'a, 'b> From<InNamespace<'a, Element>> for AnyElement<'a, 'b> {
     fn from(e: InNamespace<'a, Element>) -> Self {
         AnyElement::InNamespace(e)
@@ -139,6 +175,30 @@ impl<'a, 'b> From<&'a str> for AnyElement<'a, 'b> {
     }
 }
 
+impl<'a, 'b> IntoElement<'a, 'b> for &'a String {
+    fn encode(&self, v: &mut Batch) {
+        self.as_str().encode(v);
+    }
+}
+
+impl<'a, 'b> From<&'a String> for AnyElement<'a, 'b> {
+    fn from(e: &'a String) -> Self {
+        AnyElement::Str(e)
+    }
+}
+
+impl<'a, 'b> IntoElement<'a, 'b> for &'a Cow<'_, str> {
+    fn encode(&self, v: &mut Batch) {
+        self.as_ref().encode(v);
+    }
+}
+
+impl<'a, 'b> From<&'a Cow<'_, str>> for AnyElement<'a, 'b> {
+    fn from(e: &'a Cow<'_, str>) -> Self {
+        AnyElement::Str(e)
+    }
+}
+
 impl<'a, 'b> IntoElement<'a, 'b> for InNamespace<'a, &'b str> {
     fn encode(&self, v: &mut Batch) {
         v.msg.push(253);
@@ -173,6 +233,48 @@ impl NodeBuilder<'_> {
             NodeBuilder::Element(e) => e.encode(v),
         }
     }
+
+    /// Encode the operation that builds the node and sets it as the last node
+    pub(crate) fn encode_build(&self, v: &mut Batch) {
+        match self {
+            NodeBuilder::Text(t) => v.create_text_node(t.text, t.id),
+            NodeBuilder::Element(e) => {
+                v.encode_op(Op::BuildFullElement);
+                e.encode(v);
+            }
+        }
+    }
+}
+
+/// A builder for several sibling nodes that are inserted together, like a component that renders more than one top level node
+///
+/// The roots are built and inserted one at a time, so each root can have an id like any other node.
+///
+/// Example:
+/// ```rust
+/// # use sledgehammer_encoder::{batch::Batch, *};
+/// let mut batch = Batch::default();
+/// // insert a heading and a paragraph into the node with the id 0 without a wrapper element
+/// batch.append_fragment(
+///     NodeId(0),
+///     FragmentBuilder::new(&[
+///         ElementBuilder::new(Element::h1.into())
+///             .id(NodeId(1))
+///             .children(&[TextBuilder::new("Title").into()])
+///             .into(),
+///         ElementBuilder::new(Element::p.into()).into(),
+///     ]),
+/// );
+/// ```
+pub struct FragmentBuilder<'a> {
+    pub(crate) roots: &'a [NodeBuilder<'a>],
+}
+
+impl<'a> FragmentBuilder<'a> {
+    /// Create a new fragment builder with the sibling roots in order
+    pub const fn new(roots: &'a [NodeBuilder<'a>]) -> Self {
+        Self { roots }
+    }
 }
 
 impl<'a> From<TextBuilder<'a>> for NodeBuilder<'a> {
@@ -222,12 +324,12 @@ impl<'a> TextBuilder<'a> {
 
 /// A builder for a element with an id, kind, attributes, and children
 ///
-/// /// Example:
+/// Example:
 /// ```rust
-/// let mut channel = MsgChannel::default();
-
+/// # use sledgehammer_encoder::{batch::Batch, *};
+/// let mut batch = Batch::default();
 /// // create an element using sledgehammer
-/// channel.build_full_element(
+/// batch.build_full_element(
 ///     ElementBuilder::new("div".into())
 ///         .id(NodeId(1))
 ///         .attrs(&[(Attribute::style.into(), "color: blue")])
@@ -237,7 +339,6 @@ impl<'a> TextBuilder<'a> {
 ///             TextBuilder::new("Hello from sledgehammer!").into(),
 ///         ]),
 /// );
-/// channel.flush();
 /// ```
 pub struct ElementBuilder<'a> {
     id: Option<NodeId>,
@@ -277,71 +378,277 @@ impl<'a> ElementBuilder<'a> {
 
     /// Encode the element into the a batch
     pub(crate) fn encode(&self, v: &mut Batch) {
-        let size = 1
-            + (self.id.is_some() as usize) * 4
-            + self.kind.size()
-            + 1
-            + 1
-            + self
+        self.encode_without_children(v, self.children.len() as u8);
+        for child in self.children {
+            child.encode(v);
+        }
+    }
+
+    /// Encode the element with a number of children that are encoded after it by the caller
+    pub(crate) fn encode_without_children(&self, v: &mut Batch, children: u8) {
+        encode_element_header(
+            v,
+            self.id,
+            &self.kind,
+            self.attrs.iter().map(|(attr, value)| (attr, *value)),
+            children,
+        );
+    }
+
+    /// Convert the builder into an [`OwnedElementBuilder`] that attributes and children can be added to from iterators
+    pub fn into_owned(self) -> OwnedElementBuilder<'a> {
+        OwnedElementBuilder {
+            id: self.id,
+            kind: self.kind,
+            attrs: self
                 .attrs
                 .iter()
-                .map(|(k, _)| k.size_with_u8_discriminant() + 2)
-                .sum::<usize>();
-        v.msg.reserve(size);
-        unsafe {
-            match self.id {
-                Some(id) => {
-                    v.encode_u8_prealloc(1);
-                    v.encode_id_prealloc(id);
-                }
-                None => {
-                    v.encode_u8_prealloc(0);
-                }
+                .map(|(attr, value)| (*attr, Cow::Borrowed(*value)))
+                .collect(),
+            children: self
+                .children
+                .iter()
+                .map(|child| match child {
+                    NodeBuilder::Text(text) => OwnedNodeBuilder::Text(TextBuilder {
+                        id: text.id,
+                        text: text.text,
+                    }),
+                    NodeBuilder::Element(el) => OwnedNodeBuilder::Element(
+                        ElementBuilder {
+                            id: el.id,
+                            kind: el.kind,
+                            attrs: el.attrs,
+                            children: el.children,
+                        }
+                        .into_owned(),
+                    ),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Encode the id, kind and attributes of an element followed by the number of children that are encoded after it by the caller
+fn encode_element_header<'a>(
+    v: &mut Batch,
+    id: Option<NodeId>,
+    kind: &AnyElement,
+    attrs: impl ExactSizeIterator<Item = (&'a AnyAttribute<'a, 'a>, &'a str)> + Clone,
+    children: u8,
+) {
+    let size = 1
+        + (id.is_some() as usize) * 4
+        + kind.size()
+        + 1
+        + 1
+        + attrs
+            .clone()
+            .map(|(k, _)| k.size_with_u8_discriminant() + 2)
+            .sum::<usize>();
+    v.msg.reserve(size);
+    unsafe {
+        match id {
+            Some(id) => {
+                v.encode_u8_prealloc(1);
+                v.encode_id_prealloc(id);
             }
-            self.kind.encode_prealloc(v);
-            // these are packed together so they can be read as a u16
-            v.encode_u8_prealloc(self.attrs.len() as u8);
-            v.encode_u8_prealloc(self.children.len() as u8);
-            for (attr, value) in self.attrs {
-                attr.encode_u8_discriminant_prealloc(v);
-                v.encode_str_prealloc(*value);
+            None => {
+                v.encode_u8_prealloc(0);
             }
         }
-        for child in self.children {
+        kind.encode_prealloc(v);
+        // these are packed together so they can be read as a u16
+        v.encode_u8_prealloc(attrs.len() as u8);
+        v.encode_u8_prealloc(children);
+        for (attr, value) in attrs {
+            attr.encode_u8_discriminant_prealloc(v);
+            v.encode_str_prealloc(value);
+        }
+    }
+}
+
+/// A builder for any node in an [`OwnedElementBuilder`]
+pub enum OwnedNodeBuilder<'a> {
+    Text(TextBuilder<'a>),
+    Element(OwnedElementBuilder<'a>),
+}
+
+impl OwnedNodeBuilder<'_> {
+    /// Encode the node into a batch
+    pub(crate) fn encode(&self, v: &mut Batch) {
+        match self {
+            OwnedNodeBuilder::Text(t) => t.encode(v),
+            OwnedNodeBuilder::Element(e) => e.encode(v),
+        }
+    }
+}
+
+impl<'a> From<TextBuilder<'a>> for OwnedNodeBuilder<'a> {
+    fn from(t: TextBuilder<'a>) -> Self {
+        OwnedNodeBuilder::Text(t)
+    }
+}
+
+impl<'a> From<OwnedElementBuilder<'a>> for OwnedNodeBuilder<'a> {
+    fn from(e: OwnedElementBuilder<'a>) -> Self {
+        OwnedNodeBuilder::Element(e)
+    }
+}
+
+impl<'a> From<ElementBuilder<'a>> for OwnedNodeBuilder<'a> {
+    fn from(e: ElementBuilder<'a>) -> Self {
+        OwnedNodeBuilder::Element(e.into_owned())
+    }
+}
+
+impl<'a> From<NodeBuilder<'a>> for OwnedNodeBuilder<'a> {
+    fn from(node: NodeBuilder<'a>) -> Self {
+        match node {
+            NodeBuilder::Text(t) => t.into(),
+            NodeBuilder::Element(e) => e.into(),
+        }
+    }
+}
+
+/// A builder for a element that owns its attributes and children, so they can be added from iterators instead of slices
+///
+/// Like [`ElementBuilder`], elements can have at most 255 attributes and 255 children.
+///
+/// Example:
+/// ```rust
+/// # use sledgehammer_encoder::{batch::Batch, *};
+/// let items = ["one", "two", "three"];
+/// let mut batch = Batch::default();
+/// // create a list item for every item without collecting them into a slice first
+/// batch.build_owned_element(
+///     OwnedElementBuilder::new(Element::ul.into())
+///         .attrs_iter([(Attribute::class, format!("items-{}", items.len()))])
+///         .children_iter(items.iter().map(|item| {
+///             ElementBuilder::new(Element::li.into())
+///                 .into_owned()
+///                 .children_iter([TextBuilder::new(item)])
+///         })),
+/// );
+/// ```
+pub struct OwnedElementBuilder<'a> {
+    id: Option<NodeId>,
+    kind: AnyElement<'a, 'a>,
+    attrs: Vec<(AnyAttribute<'a, 'a>, Cow<'a, str>)>,
+    children: Vec<OwnedNodeBuilder<'a>>,
+}
+
+impl<'a> OwnedElementBuilder<'a> {
+    /// Create a new element builder
+    pub const fn new(kind: AnyElement<'a, 'a>) -> Self {
+        Self {
+            id: None,
+            kind,
+            attrs: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    /// Set the id of the element
+    pub fn id(mut self, id: NodeId) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    /// Add attributes to the element
+    pub fn attrs_iter<A: crate::IntoAttribue<'a, 'a>, V: Into<Cow<'a, str>>>(
+        mut self,
+        attrs: impl IntoIterator<Item = (A, V)>,
+    ) -> Self {
+        self.attrs.extend(
+            attrs
+                .into_iter()
+                .map(|(attr, value)| (attr.into(), value.into())),
+        );
+        self
+    }
+
+    /// Add children to the element
+    pub fn children_iter(
+        mut self,
+        children: impl IntoIterator<Item = impl Into<OwnedNodeBuilder<'a>>>,
+    ) -> Self {
+        self.children
+            .extend(children.into_iter().map(|child| child.into()));
+        self
+    }
+
+    /// Encode the element into the a batch
+    pub(crate) fn encode(&self, v: &mut Batch) {
+        encode_element_header(
+            v,
+            self.id,
+            &self.kind,
+            self.attrs.iter().map(|(attr, value)| (attr, &**value)),
+            self.children.len() as u8,
+        );
+        for child in &self.children {
             child.encode(v);
         }
     }
 }
 
+#[cfg(feature = "elements")]
 macro_rules! elements {
     ($($i: ident),*) => {
         /// All built-in elements
         /// These are the element can be encoded with a single byte so they are more efficient (but less flexable) than a &str element
         #[allow(unused)]
-        #[derive(Copy, Clone)]
+        #[derive(Copy, Clone, Debug, PartialEq, Eq)]
         pub enum Element {
             $(
                 $i
             ),*
         }
 
+        impl Element {
+            /// All built-in elements in the order of their discriminants
+            pub const VARIANTS: &'static [Element] = &[$(Self::$i),*];
+
+            /// The tag names of all built-in elements in the order of their discriminants, each followed by a comma
+            pub const NAMES: &'static str = concat!($(stringify!($i), ","),*);
+
+            /// The tag name of the element
+            pub const fn name(self) -> &'static str {
+                NAME_TABLE.get(self as usize)
+            }
+
+            /// Get the element encoded with the given byte
+            pub fn from_u8(v: u8) -> Option<Self> {
+                Self::VARIANTS.get(v as usize).copied()
+            }
+        }
+
+        /// The offsets of the names in [`Element::NAMES`]
+        const NAME_TABLE: NameTable<{ Element::VARIANTS.len() }> = NameTable::new(Element::NAMES);
+
         pub struct NotElementError;
 
-        impl std::str::FromStr for Element {
+        impl core::str::FromStr for Element {
             type Err = NotElementError;
 
             fn from_str(s: &str) -> Result<Self, Self::Err> {
-                Ok(match s{
-                    $(
-                        stringify!($i) => Self::$i,
-                    )*
-                    _ => return Err(NotElementError)
-                })
+                NAME_TABLE
+                    .position(s)
+                    .map(|index| Self::VARIANTS[index])
+                    .ok_or(NotElementError)
             }
         }
     };
 }
 
+/// The table of element names the interpreter uses to decode single byte elements. This is empty if the `elements` feature is disabled.
+#[cfg(feature = "elements")]
+pub const TABLE: &str = Element::NAMES;
+/// The table of element names the interpreter uses to decode single byte elements. This is empty if the `elements` feature is disabled.
+#[cfg(not(feature = "elements"))]
+pub const TABLE: &str = "";
+
+#[cfg(feature = "elements")]
 elements! {
     a,
     abbr,
@@ -0,0 +1,140 @@
+//! Resource hints in the head of the document.
+//!
+//! [`ResourceHint`] describes a `<link rel="preload">`, `<link rel="prefetch">` or `<link rel="modulepreload">` element. [`Batch::append_resource_hint`] builds the link and appends it to the head, so routing code can warm the resources of the next route before it is rendered. The head must be stored with an id before the batch is applied.
+//!
+//! Example:
+//! ```rust
+//! # use sledgehammer_encoder::{batch::Batch, head::*, *};
+//! const HEAD: NodeId = NodeId(0);
+//! let mut batch = Batch::default();
+//! batch.append_resource_hint(
+//!     ResourceHint::preload("/fonts/inter.woff2", "font").crossorigin(CrossOrigin::Anonymous),
+//!     HEAD,
+//! );
+//! batch.append_resource_hint(ResourceHint::module_preload("/routes/settings.js"), HEAD);
+//! // the link is built and appended with two operations
+//! assert_eq!(batch.op_count(), 4);
+//! ```
+
+use alloc::vec::Vec;
+
+use crate::{attribute::AnyAttribute, batch::Batch, ElementBuilder, MaybeId, NodeId};
+
+/// The kind of a resource hint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rel {
+    /// Fetch a resource the current page needs soon with a high priority
+    Preload,
+    /// Fetch a resource a future navigation may need with a low priority
+    Prefetch,
+    /// Fetch, parse and compile a javascript module and its dependencies
+    ModulePreload,
+}
+
+impl Rel {
+    /// The value of the `rel` attribute
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Rel::Preload => "preload",
+            Rel::Prefetch => "prefetch",
+            Rel::ModulePreload => "modulepreload",
+        }
+    }
+}
+
+/// The value of the `crossorigin` attribute of a resource hint. It must match the mode the resource is requested with later or the browser fetches it again. Fonts are always requested in cors mode, so font preloads need [`CrossOrigin::Anonymous`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrossOrigin {
+    /// Request the resource without credentials for other origins
+    Anonymous,
+    /// Request the resource with credentials
+    UseCredentials,
+}
+
+impl CrossOrigin {
+    /// The value of the `crossorigin` attribute
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CrossOrigin::Anonymous => "anonymous",
+            CrossOrigin::UseCredentials => "use-credentials",
+        }
+    }
+}
+
+/// A `<link>` element that hints the browser to fetch a resource. See [`crate::head`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceHint<'a> {
+    pub rel: Rel,
+    pub href: &'a str,
+    /// The `as` attribute with the kind of the resource like `script`, `style`, `font`, `image` or `fetch`
+    pub destination: Option<&'a str>,
+    pub crossorigin: Option<CrossOrigin>,
+    /// The `type` attribute with the mime type of the resource. Browsers that do not support the type skip the hint.
+    pub mime_type: Option<&'a str>,
+}
+
+impl<'a> ResourceHint<'a> {
+    fn new(rel: Rel, href: &'a str) -> Self {
+        Self {
+            rel,
+            href,
+            destination: None,
+            crossorigin: None,
+            mime_type: None,
+        }
+    }
+
+    /// Preload a resource of the current page. Preloads without a destination are ignored by browsers, so it is required.
+    pub fn preload(href: &'a str, destination: &'a str) -> Self {
+        Self::new(Rel::Preload, href).destination(destination)
+    }
+
+    /// Prefetch a resource for a future navigation
+    pub fn prefetch(href: &'a str) -> Self {
+        Self::new(Rel::Prefetch, href)
+    }
+
+    /// Preload a javascript module and its dependencies
+    pub fn module_preload(href: &'a str) -> Self {
+        Self::new(Rel::ModulePreload, href)
+    }
+
+    /// Set the kind of the resource (`as`)
+    pub fn destination(mut self, destination: &'a str) -> Self {
+        self.destination = Some(destination);
+        self
+    }
+
+    /// Set the cors mode of the request
+    pub fn crossorigin(mut self, crossorigin: CrossOrigin) -> Self {
+        self.crossorigin = Some(crossorigin);
+        self
+    }
+
+    /// Set the mime type of the resource
+    pub fn mime_type(mut self, mime_type: &'a str) -> Self {
+        self.mime_type = Some(mime_type);
+        self
+    }
+}
+
+impl Batch {
+    /// Build the `<link>` element of a resource hint and append it to the head stored with the id `head`. See [`crate::head`]
+    pub fn append_resource_hint(&mut self, hint: ResourceHint, head: NodeId) {
+        let mut attrs: Vec<(AnyAttribute, &str)> = Vec::from([
+            ("rel".into(), hint.rel.as_str()),
+            ("href".into(), hint.href),
+        ]);
+        if let Some(destination) = hint.destination {
+            attrs.push(("as".into(), destination));
+        }
+        if let Some(crossorigin) = hint.crossorigin {
+            attrs.push(("crossorigin".into(), crossorigin.as_str()));
+        }
+        if let Some(mime_type) = hint.mime_type {
+            attrs.push(("type".into(), mime_type));
+        }
+        self.build_full_element(ElementBuilder::new("link".into()).attrs(&attrs));
+        self.append_child(MaybeId::Node(head), MaybeId::LastNode);
+    }
+}
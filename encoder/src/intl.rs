@@ -0,0 +1,314 @@
+//! Text that is formatted by the browser with `Intl.NumberFormat` and `Intl.DateTimeFormat`.
+//!
+//! [`Batch::set_formatted_number`] and [`Batch::set_formatted_date`] send the raw value with a [`NumberFormat`] or [`DateTimeFormat`] that describes the format, and the interpreter sets the text of the node to the localized value. The locale data stays in the browser instead of being compiled into the wasm module. The interpreter creates one formatter for every format and reuses it.
+//!
+//! Example:
+//! ```rust
+//! # use sledgehammer_encoder::{batch::Batch, intl::*, *};
+//! let price = NumberFormat::new()
+//!     .locale("de-DE")
+//!     .style(NumberStyle::Currency("EUR"));
+//! let updated = DateTimeFormat::new().date_style(DateTimeStyle::Medium);
+//! let mut batch = Batch::default();
+//! // shows 1.234,50 €
+//! batch.set_formatted_number(1234.5, &price, MaybeId::Node(NodeId(1)));
+//! // the value, the locale and the options are sent as strings
+//! assert_eq!(batch.str_buf, br#"1234.5de-DE{"style":"currency","currency":"EUR"}"#);
+//! batch.set_formatted_date(1_700_000_000_000.0, &updated, MaybeId::Node(NodeId(2)));
+//! ```
+
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use crate::{batch::Batch, BufWriter, ExtendedOp, MaybeId, WritableText};
+
+/// What a number is formatted as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumberStyle<'a> {
+    #[default]
+    Decimal,
+    /// A fraction formatted as a percentage, so `0.25` is formatted as `25%`
+    Percent,
+    /// An amount of money in a currency like `"EUR"`
+    Currency(&'a str),
+    /// A measurement in a unit like `"kilometer-per-hour"`
+    Unit(&'a str),
+}
+
+/// How large and small numbers are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Notation {
+    #[default]
+    Standard,
+    Scientific,
+    Engineering,
+    /// A short form like `1.2K` or `3M`
+    Compact,
+}
+
+/// The options of an `Intl.NumberFormat`. Options that are not set use the default of the locale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NumberFormat<'a> {
+    /// A BCP 47 language tag like `"en-US"`. The locale of the browser is used if it is not set.
+    pub locale: Option<&'a str>,
+    pub style: NumberStyle<'a>,
+    pub notation: Notation,
+    pub minimum_fraction_digits: Option<u8>,
+    pub maximum_fraction_digits: Option<u8>,
+    /// Set to false to write large numbers without grouping separators
+    pub use_grouping: Option<bool>,
+}
+
+impl<'a> NumberFormat<'a> {
+    /// A format with the defaults of the locale of the browser
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Format for a locale instead of the locale of the browser
+    pub fn locale(mut self, locale: &'a str) -> Self {
+        self.locale = Some(locale);
+        self
+    }
+
+    /// Set what the number is formatted as
+    pub fn style(mut self, style: NumberStyle<'a>) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Set how large and small numbers are written
+    pub fn notation(mut self, notation: Notation) -> Self {
+        self.notation = notation;
+        self
+    }
+
+    /// Set the minimum and maximum number of digits after the decimal separator
+    pub fn fraction_digits(mut self, minimum: u8, maximum: u8) -> Self {
+        self.minimum_fraction_digits = Some(minimum);
+        self.maximum_fraction_digits = Some(maximum);
+        self
+    }
+
+    /// Set if grouping separators are written
+    pub fn use_grouping(mut self, use_grouping: bool) -> Self {
+        self.use_grouping = Some(use_grouping);
+        self
+    }
+
+    /// Write the options as a json object
+    fn write_options(&self, to: &mut Vec<u8>) {
+        let mut options = Options::new(to);
+        match self.style {
+            NumberStyle::Decimal => {}
+            NumberStyle::Percent => options.str("style", "percent"),
+            NumberStyle::Currency(currency) => {
+                options.str("style", "currency");
+                options.str("currency", currency);
+            }
+            NumberStyle::Unit(unit) => {
+                options.str("style", "unit");
+                options.str("unit", unit);
+            }
+        }
+        match self.notation {
+            Notation::Standard => {}
+            Notation::Scientific => options.str("notation", "scientific"),
+            Notation::Engineering => options.str("notation", "engineering"),
+            Notation::Compact => options.str("notation", "compact"),
+        }
+        if let Some(digits) = self.minimum_fraction_digits {
+            options.value("minimumFractionDigits", digits);
+        }
+        if let Some(digits) = self.maximum_fraction_digits {
+            options.value("maximumFractionDigits", digits);
+        }
+        if let Some(use_grouping) = self.use_grouping {
+            options.value("useGrouping", use_grouping);
+        }
+        options.end();
+    }
+}
+
+/// The length of the date or time in a [`DateTimeFormat`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateTimeStyle {
+    Full,
+    Long,
+    Medium,
+    Short,
+}
+
+impl DateTimeStyle {
+    fn as_str(self) -> &'static str {
+        match self {
+            DateTimeStyle::Full => "full",
+            DateTimeStyle::Long => "long",
+            DateTimeStyle::Medium => "medium",
+            DateTimeStyle::Short => "short",
+        }
+    }
+}
+
+/// The options of an `Intl.DateTimeFormat`. Only the date is written if neither style is set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DateTimeFormat<'a> {
+    /// A BCP 47 language tag like `"en-US"`. The locale of the browser is used if it is not set.
+    pub locale: Option<&'a str>,
+    pub date_style: Option<DateTimeStyle>,
+    pub time_style: Option<DateTimeStyle>,
+    /// An IANA time zone like `"Europe/Berlin"`. The time zone of the browser is used if it is not set.
+    pub time_zone: Option<&'a str>,
+    /// Set to use a 12 or 24 hour clock instead of the clock of the locale
+    pub hour12: Option<bool>,
+}
+
+impl<'a> DateTimeFormat<'a> {
+    /// A format with the defaults of the locale of the browser
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Format for a locale instead of the locale of the browser
+    pub fn locale(mut self, locale: &'a str) -> Self {
+        self.locale = Some(locale);
+        self
+    }
+
+    /// Set the length of the date
+    pub fn date_style(mut self, style: DateTimeStyle) -> Self {
+        self.date_style = Some(style);
+        self
+    }
+
+    /// Set the length of the time
+    pub fn time_style(mut self, style: DateTimeStyle) -> Self {
+        self.time_style = Some(style);
+        self
+    }
+
+    /// Format in a time zone instead of the time zone of the browser
+    pub fn time_zone(mut self, time_zone: &'a str) -> Self {
+        self.time_zone = Some(time_zone);
+        self
+    }
+
+    /// Set if a 12 hour clock is used
+    pub fn hour12(mut self, hour12: bool) -> Self {
+        self.hour12 = Some(hour12);
+        self
+    }
+
+    /// Write the options as a json object
+    fn write_options(&self, to: &mut Vec<u8>) {
+        let mut options = Options::new(to);
+        if let Some(style) = self.date_style {
+            options.str("dateStyle", style.as_str());
+        }
+        if let Some(style) = self.time_style {
+            options.str("timeStyle", style.as_str());
+        }
+        if let Some(time_zone) = self.time_zone {
+            options.str("timeZone", time_zone);
+        }
+        if let Some(hour12) = self.hour12 {
+            options.value("hour12", hour12);
+        }
+        options.end();
+    }
+}
+
+/// Writes the entries of a json object
+struct Options<'a> {
+    to: &'a mut Vec<u8>,
+    empty: bool,
+}
+
+impl<'a> Options<'a> {
+    fn new(to: &'a mut Vec<u8>) -> Self {
+        to.push(b'{');
+        Self { to, empty: true }
+    }
+
+    fn key(&mut self, key: &str) {
+        if !self.empty {
+            self.to.push(b',');
+        }
+        self.empty = false;
+        let _ = write!(BufWriter(self.to), "\"{key}\":");
+    }
+
+    fn str(&mut self, key: &str, value: &str) {
+        self.key(key);
+        self.to.push(b'"');
+        for c in value.chars() {
+            let _ = match c {
+                '"' | '\\' => write!(BufWriter(self.to), "\\{c}"),
+                c if c.is_control() => write!(BufWriter(self.to), "\\u{:04x}", c as u32),
+                c => write!(BufWriter(self.to), "{c}"),
+            };
+        }
+        self.to.push(b'"');
+    }
+
+    fn value(&mut self, key: &str, value: impl core::fmt::Display) {
+        self.key(key);
+        let _ = write!(BufWriter(self.to), "{value}");
+    }
+
+    fn end(self) {
+        self.to.push(b'}');
+    }
+}
+
+/// Write a number the way javascript's `Number` parses it
+fn write_number(value: f64, to: &mut Vec<u8>) {
+    if value.is_infinite() {
+        let sign = if value < 0.0 { "-" } else { "" };
+        let _ = write!(BufWriter(to), "{sign}Infinity");
+    } else {
+        let _ = write!(BufWriter(to), "{value}");
+    }
+}
+
+impl Batch {
+    /// Set the text of a node to a number formatted by the browser with `Intl.NumberFormat`. See [`crate::intl`]
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn set_formatted_number(&mut self, value: f64, format: &NumberFormat, root: MaybeId) {
+        self.encode_formatted_text(
+            root,
+            false,
+            |to: &mut Vec<u8>| write_number(value, to),
+            format.locale.unwrap_or_default(),
+            |to: &mut Vec<u8>| format.write_options(to),
+        );
+    }
+
+    /// Set the text of a node to a date formatted by the browser with `Intl.DateTimeFormat`. The timestamp is in milliseconds since the unix epoch like `Date.now()`. See [`crate::intl`]
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn set_formatted_date(&mut self, timestamp: f64, format: &DateTimeFormat, root: MaybeId) {
+        self.encode_formatted_text(
+            root,
+            true,
+            |to: &mut Vec<u8>| write_number(timestamp, to),
+            format.locale.unwrap_or_default(),
+            |to: &mut Vec<u8>| format.write_options(to),
+        );
+    }
+
+    pub(crate) fn encode_formatted_text(
+        &mut self,
+        root: MaybeId,
+        date: bool,
+        value: impl WritableText,
+        locale: &str,
+        options: impl WritableText,
+    ) {
+        self.encode_extended_op(ExtendedOp::SetFormattedText);
+        self.encode_maybe_id(root);
+        self.encode_bool(date);
+        self.encode_str(value);
+        self.encode_str(locale);
+        self.encode_str(options);
+    }
+}
@@ -0,0 +1,84 @@
+//! A map from string keys to node ids.
+//!
+//! [`NodeKeys`] lets nodes be addressed with stable keys, like the keys in server-rendered markup or the names of nodes in a test, instead of tracking their numeric ids everywhere. Keys are resolved to ids in Rust before the operations are encoded, so they do not change the binary format.
+//!
+//! Example:
+//! ```rust
+//! # use sledgehammer_encoder::{batch::Batch, keys::NodeKeys, *};
+//! let mut keys = NodeKeys::default();
+//! keys.insert("header", NodeId(1));
+//! let mut batch = Batch::default();
+//! batch.set_text("hello", keys.node("header"));
+//! assert_eq!(keys.key_of(NodeId(1)), Some("header"));
+//! ```
+
+use alloc::{collections::BTreeMap, string::String};
+
+use crate::{MaybeId, NodeId};
+
+/// A map from caller-chosen string keys to node ids. See [`crate::keys`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NodeKeys {
+    ids: BTreeMap<String, NodeId>,
+}
+
+impl NodeKeys {
+    /// Register a node with a key. Returns the id the key was registered with before, if any.
+    pub fn insert(&mut self, key: impl Into<String>, id: NodeId) -> Option<NodeId> {
+        self.ids.insert(key.into(), id)
+    }
+
+    /// The id registered with a key
+    pub fn get(&self, key: &str) -> Option<NodeId> {
+        self.ids.get(key).copied()
+    }
+
+    /// The node registered with a key as a [`MaybeId`] that can be passed to operations.
+    ///
+    /// Panics if no node is registered with the key.
+    #[track_caller]
+    pub fn node(&self, key: &str) -> MaybeId {
+        match self.get(key) {
+            Some(id) => MaybeId::Node(id),
+            None => panic!("no node is registered with the key {key:?}"),
+        }
+    }
+
+    /// Remove a key. Returns the id the key was registered with, if any.
+    pub fn remove(&mut self, key: &str) -> Option<NodeId> {
+        self.ids.remove(key)
+    }
+
+    /// Remove every key registered with an id. Call this when the id is reused for another node.
+    pub fn remove_node(&mut self, id: NodeId) {
+        self.ids.retain(|_, registered| *registered != id);
+    }
+
+    /// The first key registered with an id. This is a linear search, so it is meant for debugging.
+    pub fn key_of(&self, id: NodeId) -> Option<&str> {
+        self.ids
+            .iter()
+            .find(|(_, registered)| **registered == id)
+            .map(|(key, _)| key.as_str())
+    }
+
+    /// The keys and the ids they are registered with, sorted by key
+    pub fn iter(&self) -> impl Iterator<Item = (&str, NodeId)> {
+        self.ids.iter().map(|(key, id)| (key.as_str(), *id))
+    }
+
+    /// The number of registered keys
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// If no keys are registered
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Remove every key
+    pub fn clear(&mut self) {
+        self.ids.clear();
+    }
+}
@@ -1,21 +1,68 @@
+//! The encoding core of sledgehammer. This crate builds and decodes batches of dom operations without depending on wasm-bindgen or web-sys, so batches can be generated on the server or on other targets.
+//!
+//! The crate is `no_std` compatible and only requires `alloc` when the default `std` feature is disabled.
+//!
+//! With the `compression` feature, finalized batches can be compressed with LZ4 before they are sent over the network. See the [`compression`] module.
+//!
+//! The [`Element`] and [`Attribute`] enums and their name tables can be compiled out by disabling the default `elements` and `attributes` features. Elements and attributes can still be created from strings.
+//!
+//! The [`opcodes`] module describes the layout of every operation in the binary format for interpreters written in other languages.
+
+#![no_std]
+
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
 pub mod attribute;
 pub mod batch;
+#[cfg(feature = "compression")]
+pub mod compression;
+pub mod decoder;
+#[cfg(feature = "debug")]
+mod disassemble;
 pub mod element;
+pub mod head;
+pub mod intl;
+pub mod keys;
+pub mod mutation;
+#[cfg(any(feature = "elements", feature = "attributes"))]
+mod names;
+pub mod opcodes;
+pub mod optimize;
+pub mod sanitize;
+pub mod svg;
 
-use std::{fmt::Arguments, io::Write};
+use alloc::{borrow::Cow, string::String, vec::Vec};
+use core::fmt::{Arguments, Write};
 
-pub use attribute::{Attribute, IntoAttribue};
-pub use batch::{Op, StaticBatch};
-pub use element::{Element, ElementBuilder, IntoElement, NodeBuilder, TextBuilder};
+#[cfg(feature = "attributes")]
+pub use attribute::Attribute;
+pub use attribute::IntoAttribue;
+pub use batch::{ExtendedOp, Op, StaticBatch};
+#[cfg(feature = "elements")]
+pub use element::Element;
+pub use element::{
+    ElementBuilder, FragmentBuilder, IntoElement, NodeBuilder, OwnedElementBuilder,
+    OwnedNodeBuilder, TextBuilder,
+};
+
+/// The version of the binary format batches are encoded in. It changes every time the encoding of an operation changes. Interpreters and decoders reject operations they do not know, so adding an operation does not change the version.
+///
+/// The version is written into the metadata the interpreter reads before every batch and into the headers of serialized batches. Interpreters and decoders reject batches with a different version instead of applying operations they would misread.
+///
+/// The version is stored in the upper four bits of the metadata byte, so it must stay below 15.
+pub const WIRE_FORMAT_VERSION: u8 = 1;
+const _: () = assert!(WIRE_FORMAT_VERSION < 15);
 
 /// Something that lives in a namespace like a tag or attribute
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct InNamespace<'a, T>(pub T, pub &'a str);
 
 /// Something that can live in a namespace
 pub trait WithNsExt {
     /// Moves the item into a namespace
-    fn in_namespace(self, namespace: &str) -> InNamespace<Self>
+    fn in_namespace(self, namespace: &str) -> InNamespace<'_, Self>
     where
         Self: Sized,
     {
@@ -23,12 +70,14 @@ pub trait WithNsExt {
     }
 }
 
+#[cfg(feature = "elements")]
 impl WithNsExt for Element {}
+#[cfg(feature = "attributes")]
 impl WithNsExt for Attribute {}
-impl<'a> WithNsExt for &'a str {}
+impl WithNsExt for &str {}
 
 /// An id that may be either the last node or a node with an assigned id.
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum MaybeId {
     /// The last node that was created or navigated to.
     LastNode,
@@ -48,9 +97,72 @@ impl MaybeId {
 
 /// A node that was created and stored with an id
 /// It is recommended to create and store ids with a slab allocator with an exposed slab index for example the excellent [slab](https://docs.rs/slab) crate.
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct NodeId(pub u32);
 
+impl NodeId {
+    /// Create an id from an index and the generation of the slot the index refers to.
+    ///
+    /// When the `debug` feature is enabled, the generation is encoded in the top 8 bits of the id and the interpreter checks that the generation matches the generation the node was stored with. This turns using an id after its slot was reused into an error instead of silently modifying the wrong node. In debug mode, the index must be less than 2^24.
+    /// When the `debug` feature is disabled, the generation is ignored.
+    pub const fn with_generation(index: u32, generation: u8) -> Self {
+        #[cfg(feature = "debug")]
+        {
+            debug_assert!(
+                index < 1 << 24,
+                "the index of a generational id must be less than 2^24"
+            );
+            Self(index | (generation as u32) << 24)
+        }
+        #[cfg(not(feature = "debug"))]
+        {
+            let _ = generation;
+            Self(index)
+        }
+    }
+
+    /// The index of the id without the generation
+    pub const fn index(self) -> u32 {
+        #[cfg(feature = "debug")]
+        {
+            self.0 & 0xFFFFFF
+        }
+        #[cfg(not(feature = "debug"))]
+        {
+            self.0
+        }
+    }
+
+    /// The generation of the id. This is always 0 when the `debug` feature is disabled.
+    pub const fn generation(self) -> u8 {
+        #[cfg(feature = "debug")]
+        {
+            (self.0 >> 24) as u8
+        }
+        #[cfg(not(feature = "debug"))]
+        {
+            0
+        }
+    }
+}
+
+/// A handle to an event handler that listeners refer to. The handler itself is registered on the rust side, and the interpreter passes the handle back when an event is dispatched to a listener.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HandlerId(pub u32);
+
+/// A handle to a read that the interpreter answers after it applies the operation. The interpreter passes the handle back with the value it read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct QueryId(pub u32);
+
+/// One of the web storage areas of the page
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StorageArea {
+    /// `localStorage`, which is kept after the page is closed
+    Local,
+    /// `sessionStorage`, which is cleared when the tab is closed
+    Session,
+}
+
 /// Something that can be written as a utf-8 string to a buffer
 pub trait WritableText {
     fn write_as_text(self, to: &mut Vec<u8>);
@@ -58,11 +170,12 @@ pub trait WritableText {
 
 impl WritableText for char {
     fn write_as_text(self, to: &mut Vec<u8>) {
-        to.push(self as u8);
+        let mut buf = [0; 4];
+        to.extend_from_slice(self.encode_utf8(&mut buf).as_bytes());
     }
 }
 
-impl<'a> WritableText for &'a str {
+impl WritableText for &str {
     #[inline(always)]
     fn write_as_text(self, to: &mut Vec<u8>) {
         let len = self.len();
@@ -82,9 +195,47 @@ impl<'a> WritableText for &'a str {
     }
 }
 
+impl WritableText for &String {
+    #[inline(always)]
+    fn write_as_text(self, to: &mut Vec<u8>) {
+        self.as_str().write_as_text(to)
+    }
+}
+
+impl WritableText for String {
+    #[inline(always)]
+    fn write_as_text(self, to: &mut Vec<u8>) {
+        self.as_str().write_as_text(to)
+    }
+}
+
+impl WritableText for &Cow<'_, str> {
+    #[inline(always)]
+    fn write_as_text(self, to: &mut Vec<u8>) {
+        self.as_ref().write_as_text(to)
+    }
+}
+
+impl WritableText for Cow<'_, str> {
+    #[inline(always)]
+    fn write_as_text(self, to: &mut Vec<u8>) {
+        self.as_ref().write_as_text(to)
+    }
+}
+
 impl WritableText for Arguments<'_> {
     fn write_as_text(self, to: &mut Vec<u8>) {
-        let _ = to.write_fmt(self);
+        let _ = BufWriter(to).write_fmt(self);
+    }
+}
+
+/// Writes formatted text into a byte buffer without going through `std::io`
+struct BufWriter<'a>(&'a mut Vec<u8>);
+
+impl Write for BufWriter<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.0.extend_from_slice(s.as_bytes());
+        Ok(())
     }
 }
 
@@ -102,8 +253,9 @@ macro_rules! write_unsized {
         impl WritableText for $t {
             fn write_as_text(self, to: &mut Vec<u8>) {
                 let mut n = self;
-                let mut n2 = n;
-                let mut num_digits = 0;
+                // every number has at least one digit, even zero
+                let mut n2 = n / 10;
+                let mut num_digits = 1;
                 while n2 > 0 {
                     n2 /= 10;
                     num_digits += 1;
@@ -138,16 +290,11 @@ macro_rules! write_sized {
         impl WritableText for $t {
             fn write_as_text(self, to: &mut Vec<u8>) {
                 let neg = self < 0;
-                let mut n = if neg {
-                    match self.checked_abs() {
-                        Some(n) => n,
-                        None => <$t>::MAX / 2 + 1,
-                    }
-                } else {
-                    self
-                };
-                let mut n2 = n;
-                let mut num_digits = 0;
+                // the absolute value of the minimum value does not fit in the signed type
+                let mut n = self.unsigned_abs();
+                // every number has at least one digit, even zero
+                let mut n2 = n / 10;
+                let mut num_digits = 1;
                 while n2 > 0 {
                     n2 /= 10;
                     num_digits += 1;
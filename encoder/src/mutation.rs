@@ -0,0 +1,221 @@
+//! A fluent api that encodes operations as soon as each method is called.
+//!
+//! [`Batch::create`] creates an element and returns a [`MutationWriter`] for it. Attributes, styles and text are set on the element with the last node, and children are built inside of [`MutationWriter::child`] and appended with the parent stack, so no ids are needed to build a tree.
+//!
+//! Example:
+//! ```rust
+//! # use sledgehammer_encoder::{batch::Batch, *};
+//! let items = ["one", "two"];
+//! let mut batch = Batch::default();
+//! let mut list = batch.create(Element::ul).attr(Attribute::class, "items");
+//! for item in items {
+//!     list = list.child(|c| c.create(Element::li).text(item));
+//! }
+//! list.mount(NodeId(0));
+//! ```
+
+use crate::{batch::Batch, IntoAttribue, IntoElement, MaybeId, NodeId, WritableText};
+
+/// Encodes operations on an element that was created with [`Batch::create`]. The element is the last node while the writer is used.
+pub struct MutationWriter<'a> {
+    batch: &'a mut Batch,
+}
+
+impl Batch {
+    /// Create an element and return a [`MutationWriter`] that sets its attributes and children. See [`crate::mutation`]
+    pub fn create<'a, 'b>(&mut self, tag: impl IntoElement<'a, 'b>) -> MutationWriter<'_> {
+        self.create_element(tag, None);
+        MutationWriter { batch: self }
+    }
+}
+
+impl<'a> MutationWriter<'a> {
+    /// Store the element with an id
+    pub fn id(self, id: NodeId) -> Self {
+        self.batch.store_with_id(id);
+        self
+    }
+
+    /// Set an attribute on the element
+    pub fn attr<'b, 'c>(self, attr: impl IntoAttribue<'b, 'c>, value: impl WritableText) -> Self {
+        self.batch.set_attribute(attr, value, MaybeId::LastNode);
+        self
+    }
+
+    /// Set a style property on the element
+    pub fn style(self, style: impl WritableText, value: impl WritableText) -> Self {
+        self.batch.set_style(style, value, MaybeId::LastNode);
+        self
+    }
+
+    /// Set the text content of the element
+    pub fn text(self, text: impl WritableText) -> Self {
+        self.batch.set_text(text, MaybeId::LastNode);
+        self
+    }
+
+    /// Build a child with the batch passed to `f` and append it to the element. `f` must leave the child as the last node, which every [`MutationWriter`] does.
+    pub fn child<F>(self, f: F) -> Self
+    where
+        F: for<'b> FnOnce(&'b mut Batch) -> MutationWriter<'b>,
+    {
+        self.batch.push_parent();
+        f(self.batch);
+        self.batch.pop_parent();
+        self
+    }
+
+    /// Append a text node to the element
+    pub fn text_child(self, text: impl WritableText) -> Self {
+        self.batch.push_parent();
+        self.batch.create_text_node(text, None);
+        self.batch.pop_parent();
+        self
+    }
+
+    /// Append the element to the children of a node
+    pub fn mount(self, parent: NodeId) {
+        self.batch
+            .append_child(MaybeId::Node(parent), MaybeId::LastNode);
+    }
+
+    /// Insert the element before a node
+    pub fn mount_before(self, sibling: NodeId) {
+        self.batch
+            .insert_before(MaybeId::Node(sibling), MaybeId::LastNode);
+    }
+
+    /// Insert the element after a node
+    pub fn mount_after(self, sibling: NodeId) {
+        self.batch
+            .insert_after(MaybeId::Node(sibling), MaybeId::LastNode);
+    }
+}
+
+/// Encode a block of mutations into a [`Batch`] or `MsgChannel`.
+///
+/// The first argument is the batch, followed by statements that each end with a semicolon:
+/// - `set_text id => text;`
+/// - `set_attribute id => attr = value;`
+/// - `remove_attribute id => attr;`
+/// - `set_style id => style = value;`
+/// - `remove_style id => style;`
+/// - `remove id;`
+/// - `append parent => { nodes }`
+/// - `insert_before id => { nodes }`
+/// - `replace id => { nodes }`
+///
+/// Ids are [`crate::NodeId`]s. Nodes are written as `tag [id] (attr = value, ...) { children }` where the id and attributes are optional, or as a string literal or parenthesized expression for a text node. Tags and attributes are either built-in names like `div` and `class` or string literals like `"my-element"`. Elements are built with a [`MutationWriter`], so they do not need ids.
+///
+/// Example:
+/// ```rust
+/// # use sledgehammer_encoder::{batch::Batch, *};
+/// let count = 3;
+/// let mut batch = Batch::default();
+/// mutations!(batch,
+///     set_text NodeId(1) => "hi";
+///     set_attribute NodeId(1) => "data-count" = count;
+///     remove NodeId(2);
+///     append NodeId(0) => {
+///         div [NodeId(3)] (class = "items") {
+///             p { "count: " (count) }
+///             "my-element" {}
+///         }
+///     }
+/// );
+/// ```
+#[macro_export]
+macro_rules! mutations {
+    (@tag $tag:ident) => {
+        $crate::Element::$tag
+    };
+    (@tag $tag:expr) => {
+        $tag
+    };
+    (@attr $attr:ident) => {
+        $crate::Attribute::$attr
+    };
+    (@attr $attr:expr) => {
+        $attr
+    };
+    (@element $batch:expr, $tag:tt $([$id:expr])? $(($($attr:tt = $value:expr),* $(,)?))? { $($children:tt)* }) => {
+        $crate::mutations!(@children
+            $batch.create($crate::mutations!(@tag $tag))
+                $(.id($id))?
+                $($(.attr($crate::mutations!(@attr $attr), $value))*)?;
+            $($children)*
+        )
+    };
+    (@children $writer:expr;) => {
+        $writer
+    };
+    (@children $writer:expr; $tag:tt $([$id:expr])? $(($($attr:tt = $value:expr),* $(,)?))? { $($children:tt)* } $($rest:tt)*) => {
+        $crate::mutations!(@children
+            $writer.child(|batch| {
+                $crate::mutations!(@element batch, $tag $([$id])? $(($($attr = $value),*))? { $($children)* })
+            });
+            $($rest)*
+        )
+    };
+    (@children $writer:expr; $text:literal $($rest:tt)*) => {
+        $crate::mutations!(@children $writer.text_child($text); $($rest)*)
+    };
+    (@children $writer:expr; ($text:expr) $($rest:tt)*) => {
+        $crate::mutations!(@children $writer.text_child($text); $($rest)*)
+    };
+    // build every node and insert it with the `$insert` method of the batch
+    (@insert $batch:expr, $insert:ident $target:expr;) => {};
+    (@insert $batch:expr, $insert:ident $target:expr; $tag:tt $([$id:expr])? $(($($attr:tt = $value:expr),* $(,)?))? { $($children:tt)* } $($rest:tt)*) => {
+        $crate::mutations!(@element $batch, $tag $([$id])? $(($($attr = $value),*))? { $($children)* });
+        $batch.$insert($crate::MaybeId::Node($target), $crate::MaybeId::LastNode);
+        $crate::mutations!(@insert $batch, $insert $target; $($rest)*);
+    };
+    (@insert $batch:expr, $insert:ident $target:expr; $text:literal $($rest:tt)*) => {
+        $batch.create_text_node($text, None);
+        $batch.$insert($crate::MaybeId::Node($target), $crate::MaybeId::LastNode);
+        $crate::mutations!(@insert $batch, $insert $target; $($rest)*);
+    };
+    (@insert $batch:expr, $insert:ident $target:expr; ($text:expr) $($rest:tt)*) => {
+        $batch.create_text_node($text, None);
+        $batch.$insert($crate::MaybeId::Node($target), $crate::MaybeId::LastNode);
+        $crate::mutations!(@insert $batch, $insert $target; $($rest)*);
+    };
+    ($batch:expr $(,)?) => {};
+    ($batch:expr, set_text $id:expr => $text:expr; $($rest:tt)*) => {
+        $batch.set_text($text, $crate::MaybeId::Node($id));
+        $crate::mutations!($batch, $($rest)*);
+    };
+    ($batch:expr, set_attribute $id:expr => $attr:tt = $value:expr; $($rest:tt)*) => {
+        $batch.set_attribute($crate::mutations!(@attr $attr), $value, $crate::MaybeId::Node($id));
+        $crate::mutations!($batch, $($rest)*);
+    };
+    ($batch:expr, remove_attribute $id:expr => $attr:tt; $($rest:tt)*) => {
+        $batch.remove_attribute($crate::mutations!(@attr $attr), $crate::MaybeId::Node($id));
+        $crate::mutations!($batch, $($rest)*);
+    };
+    ($batch:expr, set_style $id:expr => $style:tt = $value:expr; $($rest:tt)*) => {
+        $batch.set_style($style, $value, $crate::MaybeId::Node($id));
+        $crate::mutations!($batch, $($rest)*);
+    };
+    ($batch:expr, remove_style $id:expr => $style:tt; $($rest:tt)*) => {
+        $batch.remove_style($style, $crate::MaybeId::Node($id));
+        $crate::mutations!($batch, $($rest)*);
+    };
+    ($batch:expr, remove $id:expr; $($rest:tt)*) => {
+        $batch.remove($crate::MaybeId::Node($id));
+        $crate::mutations!($batch, $($rest)*);
+    };
+    ($batch:expr, append $parent:expr => { $($nodes:tt)* } $($rest:tt)*) => {
+        $crate::mutations!(@insert $batch, append_child $parent; $($nodes)*);
+        $crate::mutations!($batch, $($rest)*);
+    };
+    ($batch:expr, insert_before $id:expr => { $($nodes:tt)* } $($rest:tt)*) => {
+        $crate::mutations!(@insert $batch, insert_before $id; $($nodes)*);
+        $crate::mutations!($batch, $($rest)*);
+    };
+    ($batch:expr, replace $id:expr => { $($nodes:tt)* } $($rest:tt)*) => {
+        $crate::mutations!(@insert $batch, insert_before $id; $($nodes)*);
+        $batch.remove($crate::MaybeId::Node($id));
+        $crate::mutations!($batch, $($rest)*);
+    };
+}
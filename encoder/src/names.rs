@@ -0,0 +1,54 @@
+//! The name tables of the built-in elements and attributes.
+//!
+//! The names are stored once, in the comma separated string the interpreter decodes single byte elements and attributes with, next to the offset every name starts at. Converting between variants and names reads the table instead of matching on every variant, so the encoder, the headless dom, the prebuild macros and the disassembler share one copy of the names in the binary.
+
+/// The names of the variants of an enum in the order of their discriminants
+pub(crate) struct NameTable<const N: usize> {
+    /// Every name followed by a comma
+    names: &'static str,
+    /// The offset of every name in `names`
+    starts: [u16; N],
+}
+
+impl<const N: usize> NameTable<N> {
+    /// Find the offsets of the names. The table is built at compile time, so a wrong number of names fails the build.
+    pub(crate) const fn new(names: &'static str) -> Self {
+        let bytes = names.as_bytes();
+        let mut starts = [0; N];
+        let mut count = 0;
+        let mut start = 0;
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] == b',' {
+                assert!(count < N, "the table has more names than variants");
+                starts[count] = start as u16;
+                count += 1;
+                start = i + 1;
+            }
+            i += 1;
+        }
+        assert!(count == N, "the table has fewer names than variants");
+        assert!(
+            start == bytes.len(),
+            "the last name must be followed by a comma"
+        );
+        Self { names, starts }
+    }
+
+    /// The name at an index
+    pub(crate) const fn get(&self, index: usize) -> &'static str {
+        let start = self.starts[index] as usize;
+        let end = if index + 1 < N {
+            self.starts[index + 1] as usize
+        } else {
+            self.names.len()
+        };
+        // skip the comma after the name
+        self.names.split_at(end - 1).0.split_at(start).1
+    }
+
+    /// The index of a name
+    pub(crate) fn position(&self, name: &str) -> Option<usize> {
+        (0..N).position(|index| self.get(index) == name)
+    }
+}
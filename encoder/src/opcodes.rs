@@ -0,0 +1,479 @@
+//! A machine readable description of every operation in the binary format.
+//!
+//! [`OPCODES`] lists the discriminant of each [`Op`] and [`ExtendedOp`], the meaning of the bools packed into the operation byte and the layout of the data that follows the operation. It is the reference interpreters in other languages are checked against. [`to_json`] renders the table as json:
+//!
+//! ```sh
+//! cargo run -p sledgehammer-encoder --example opcodes > opcodes.json
+//! ```
+//!
+//! Operations are packed into groups of four operation bytes followed by the data of each operation in order. The lower five bits of an operation byte are the [`Op`] discriminant and the upper three bits are the [`Opcode::flags`] (`0x20`, `0x40` and `0x80`). The data of an [`Op::Extended`] operation starts with the [`ExtendedOp`] discriminant.
+//!
+//! Example:
+//! ```rust
+//! # use sledgehammer_encoder::{opcodes::*, Op};
+//! let set_text = OPCODES.iter().find(|code| code.op == Op::SetText).unwrap();
+//! assert_eq!(set_text.flags, ["root_is_id"]);
+//! assert_eq!(set_text.operands[0].kind, OperandKind::Id);
+//! assert_eq!(set_text.operands[0].when, [("root_is_id", true)]);
+//! ```
+
+use alloc::string::String;
+use core::fmt::Write;
+
+use crate::{ExtendedOp, Op};
+use OperandKind::*;
+
+/// The encoding of an operand. Numbers are little endian.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperandKind {
+    /// A single byte
+    U8,
+    /// An unsigned 32 bit integer
+    U32,
+    /// A signed 32 bit integer
+    I32,
+    /// A 32 bit [`crate::NodeId`]
+    Id,
+    /// The length of a string in the string buffer as an unsigned 16 bit integer. The string starts where the last string ended.
+    Str,
+    /// An element: a byte with the element discriminant, `255` followed by the element discriminant and a namespace [`OperandKind::Str`], `254` followed by a tag [`OperandKind::Str`] or `253` followed by a tag and a namespace [`OperandKind::Str`]
+    Element,
+    /// A node in the format of [`crate::NodeBuilder`]: a header byte where `0x1` marks an [`OperandKind::Id`] after the header and `0x2` marks a text node. Text nodes are followed by a [`OperandKind::Str`]. Elements are followed by an [`OperandKind::Element`], the number of attributes and the number of children as bytes, each attribute as a byte discriminant in the format of [`OperandKind::Element`] followed by a value [`OperandKind::Str`], and then each child node.
+    Node,
+    /// A byte with the number of nodes followed by a byte for each node that is `0` for the last node, or `1` followed by an [`OperandKind::Id`]
+    Nodes,
+    /// An unsigned 32 bit length followed by that many bytes in the message
+    Bytes,
+}
+
+impl OperandKind {
+    /// The name of the kind in the json table
+    pub fn name(self) -> &'static str {
+        match self {
+            OperandKind::U8 => "u8",
+            OperandKind::U32 => "u32",
+            OperandKind::I32 => "i32",
+            OperandKind::Id => "id",
+            OperandKind::Str => "str",
+            OperandKind::Element => "element",
+            OperandKind::Node => "node",
+            OperandKind::Nodes => "nodes",
+            OperandKind::Bytes => "bytes",
+        }
+    }
+}
+
+/// A value in the data of an operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Operand {
+    pub name: &'static str,
+    pub kind: OperandKind,
+    /// The flags the operand depends on and the value each flag must have for the operand to be encoded. The operand is always encoded if this is empty.
+    pub when: &'static [(&'static str, bool)],
+}
+
+/// The encoding of an operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Opcode {
+    /// The name of the [`Op`] or [`ExtendedOp`] variant
+    pub name: &'static str,
+    pub op: Op,
+    pub extended: Option<ExtendedOp>,
+    /// The names of the bools in the upper bits of the operation byte, starting with `0x20`
+    pub flags: &'static [&'static str],
+    /// The data after the operation in the order it is encoded
+    pub operands: &'static [Operand],
+}
+
+const fn always(name: &'static str, kind: OperandKind) -> Operand {
+    Operand {
+        name,
+        kind,
+        when: &[],
+    }
+}
+
+const fn when(
+    name: &'static str,
+    kind: OperandKind,
+    when: &'static [(&'static str, bool)],
+) -> Operand {
+    Operand { name, kind, when }
+}
+
+const fn op(
+    name: &'static str,
+    op: Op,
+    flags: &'static [&'static str],
+    operands: &'static [Operand],
+) -> Opcode {
+    Opcode {
+        name,
+        op,
+        extended: None,
+        flags,
+        operands,
+    }
+}
+
+const fn extended(
+    name: &'static str,
+    op: ExtendedOp,
+    flags: &'static [&'static str],
+    operands: &'static [Operand],
+) -> Opcode {
+    Opcode {
+        name,
+        op: Op::Extended,
+        extended: Some(op),
+        flags,
+        operands,
+    }
+}
+
+const ROOT: Operand = when("root", Id, &[("root_is_id", true)]);
+
+const INSERT: (&[&str], &[Operand]) = (
+    &["many", "root_is_id", "node_is_id"],
+    &[
+        ROOT,
+        when("nodes", Nodes, &[("many", true)]),
+        when("node", Id, &[("many", false), ("node_is_id", true)]),
+    ],
+);
+
+const ATTRIBUTE_FLAGS: &[&str] = &["root_is_id", "attribute_is_str", "has_namespace"];
+
+const ATTRIBUTE: [Operand; 4] = [
+    ROOT,
+    when("attribute", U8, &[("attribute_is_str", false)]),
+    when("attribute", Str, &[("attribute_is_str", true)]),
+    when("namespace", Str, &[("has_namespace", true)]),
+];
+
+const LOCK: (&[&str], &[Operand]) = (
+    &["request", "root_is_id"],
+    &[
+        when("root", Id, &[("request", true), ("root_is_id", true)]),
+        when("handler", U32, &[("request", true)]),
+    ],
+);
+
+/// Every operation in the binary format
+pub const OPCODES: &[Opcode] = &[
+    op("FirstChild", Op::FirstChild, &[], &[]),
+    op("NextSibling", Op::NextSibling, &[], &[]),
+    op("ParentNode", Op::ParentNode, &[], &[]),
+    op("StoreWithId", Op::StoreWithId, &[], &[always("id", Id)]),
+    op("SetLastNode", Op::SetLastNode, &[], &[always("id", Id)]),
+    op("Stop", Op::Stop, &[], &[]),
+    op(
+        "BuildFullElement",
+        Op::BuildFullElement,
+        &[],
+        &[always("node", Node)],
+    ),
+    op(
+        "AppendChildren",
+        Op::AppendChildren,
+        &["root_is_id", "child_is_id"],
+        &[ROOT, when("child", Id, &[("child_is_id", true)])],
+    ),
+    op("ReplaceWith", Op::ReplaceWith, INSERT.0, INSERT.1),
+    op("InsertAfter", Op::InsertAfter, INSERT.0, INSERT.1),
+    op("InsertBefore", Op::InsertBefore, INSERT.0, INSERT.1),
+    op("Remove", Op::Remove, &["root_is_id"], &[ROOT]),
+    op(
+        "CreateTextNode",
+        Op::CreateTextNode,
+        &["has_id"],
+        &[always("text", Str), when("id", Id, &[("has_id", true)])],
+    ),
+    op(
+        "CreateElement",
+        Op::CreateElement,
+        &["has_id"],
+        &[
+            always("element", Element),
+            when("id", Id, &[("has_id", true)]),
+        ],
+    ),
+    op(
+        "SetText",
+        Op::SetText,
+        &["root_is_id"],
+        &[ROOT, always("text", Str)],
+    ),
+    op(
+        "SetAttribute",
+        Op::SetAttribute,
+        ATTRIBUTE_FLAGS,
+        &[
+            ATTRIBUTE[0],
+            ATTRIBUTE[1],
+            ATTRIBUTE[2],
+            ATTRIBUTE[3],
+            always("value", Str),
+        ],
+    ),
+    op(
+        "RemoveAttribute",
+        Op::RemoveAttribute,
+        ATTRIBUTE_FLAGS,
+        &ATTRIBUTE,
+    ),
+    op(
+        "SetStyle",
+        Op::SetStyle,
+        &["root_is_id"],
+        &[ROOT, always("style", Str), always("value", Str)],
+    ),
+    op(
+        "RemoveStyle",
+        Op::RemoveStyle,
+        &["root_is_id"],
+        &[ROOT, always("style", Str)],
+    ),
+    op(
+        "CloneNode",
+        Op::CloneNode,
+        &["root_is_id", "new_id_is_id"],
+        &[ROOT, when("new_id", Id, &[("new_id_is_id", true)])],
+    ),
+    op("NoOp", Op::NoOp, &[], &[]),
+    op(
+        "AdoptIframeDocument",
+        Op::AdoptIframeDocument,
+        &["iframe_is_id"],
+        &[
+            when("iframe", Id, &[("iframe_is_id", true)]),
+            always("id", Id),
+        ],
+    ),
+    op(
+        "SetPoolSize",
+        Op::SetPoolSize,
+        &[],
+        &[always("tag", Str), always("size", U32)],
+    ),
+    op(
+        "HintNodeCapacity",
+        Op::HintNodeCapacity,
+        &[],
+        &[always("capacity", U32)],
+    ),
+    op(
+        "CacheValue",
+        Op::CacheValue,
+        &[],
+        &[always("id", U8), always("value", Str)],
+    ),
+    op(
+        "SetAttributeCached",
+        Op::SetAttributeCached,
+        ATTRIBUTE_FLAGS,
+        &[
+            ATTRIBUTE[0],
+            ATTRIBUTE[1],
+            ATTRIBUTE[2],
+            ATTRIBUTE[3],
+            always("value", U8),
+        ],
+    ),
+    op("ParentStack", Op::ParentStack, &["push"], &[]),
+    op(
+        "Listener",
+        Op::Listener,
+        &["root_is_id", "add", "coalesced"],
+        &[ROOT, always("handler", U32), always("event", Str)],
+    ),
+    op(
+        "SetValue",
+        Op::SetValue,
+        &["root_is_id"],
+        &[ROOT, always("value", Str)],
+    ),
+    extended(
+        "MediaQuery",
+        ExtendedOp::MediaQuery,
+        &["subscribe"],
+        &[always("handler", U32), always("query", Str)],
+    ),
+    extended(
+        "GetStorageItem",
+        ExtendedOp::GetStorageItem,
+        &["session"],
+        &[always("query", U32), always("key", Str)],
+    ),
+    extended(
+        "SetStorageItem",
+        ExtendedOp::SetStorageItem,
+        &["session"],
+        &[always("key", Str), always("value", Str)],
+    ),
+    extended(
+        "RemoveStorageItem",
+        ExtendedOp::RemoveStorageItem,
+        &["session"],
+        &[always("key", Str)],
+    ),
+    extended(
+        "CustomElementLifecycle",
+        ExtendedOp::CustomElementLifecycle,
+        &["observe"],
+        &[always("handler", U32), always("tag", Str)],
+    ),
+    extended(
+        "TopLayer",
+        ExtendedOp::TopLayer,
+        &["root_is_id", "popover", "show"],
+        &[ROOT],
+    ),
+    extended("Fullscreen", ExtendedOp::Fullscreen, LOCK.0, LOCK.1),
+    extended("PointerLock", ExtendedOp::PointerLock, LOCK.0, LOCK.1),
+    extended(
+        "AnimationFrames",
+        ExtendedOp::AnimationFrames,
+        &["subscribe"],
+        &[always("handler", U32)],
+    ),
+    extended(
+        "CanvasSize",
+        ExtendedOp::CanvasSize,
+        &["observe", "root_is_id"],
+        &[ROOT, when("handler", U32, &[("observe", true)])],
+    ),
+    extended(
+        "ActiveElement",
+        ExtendedOp::ActiveElement,
+        &[],
+        &[always("query", U32)],
+    ),
+    extended(
+        "ElementFromPoint",
+        ExtendedOp::ElementFromPoint,
+        &[],
+        &[always("query", U32), always("x", I32), always("y", I32)],
+    ),
+    extended(
+        "CheckVisibility",
+        ExtendedOp::CheckVisibility,
+        &["root_is_id"],
+        &[ROOT, always("query", U32)],
+    ),
+    extended(
+        "ToggleClass",
+        ExtendedOp::ToggleClass,
+        &["add", "root_is_id"],
+        &[ROOT, always("class", Str)],
+    ),
+    extended(
+        "SetImageLoading",
+        ExtendedOp::SetImageLoading,
+        &["lazy", "async_decoding", "root_is_id"],
+        &[ROOT],
+    ),
+    extended(
+        "DecodeImage",
+        ExtendedOp::DecodeImage,
+        &["root_is_id"],
+        &[ROOT, always("query", U32)],
+    ),
+    extended(
+        "SetFormattedText",
+        ExtendedOp::SetFormattedText,
+        &["root_is_id", "date"],
+        &[
+            ROOT,
+            always("value", Str),
+            always("locale", Str),
+            always("options", Str),
+        ],
+    ),
+    extended(
+        "ViewTransition",
+        ExtendedOp::ViewTransition,
+        &[],
+        &[
+            always("handler", U32),
+            always("msg", Bytes),
+            always("str", Bytes),
+        ],
+    ),
+    extended(
+        "StreamTree",
+        ExtendedOp::StreamTree,
+        &["append", "push"],
+        &[],
+    ),
+    extended(
+        "SetSanitizedHtml",
+        ExtendedOp::SetSanitizedHtml,
+        &["root_is_id"],
+        &[
+            ROOT,
+            always("html", Str),
+            always("elements", Str),
+            always("attributes", Str),
+        ],
+    ),
+];
+
+/// Render [`OPCODES`] as a json array along with the [`crate::WIRE_FORMAT_VERSION`]
+pub fn to_json() -> String {
+    let mut json = String::new();
+    let _ = write_json(&mut json);
+    json
+}
+
+fn write_json(json: &mut String) -> core::fmt::Result {
+    writeln!(
+        json,
+        "{{\n  \"version\": {},\n  \"opcodes\": [",
+        crate::WIRE_FORMAT_VERSION
+    )?;
+    for (i, code) in OPCODES.iter().enumerate() {
+        write!(
+            json,
+            "    {{\"name\": \"{}\", \"op\": {}",
+            code.name, code.op as u8
+        )?;
+        match code.extended {
+            Some(extended) => write!(json, ", \"extended\": {}", extended as u8)?,
+            None => write!(json, ", \"extended\": null")?,
+        }
+        json.push_str(", \"flags\": [");
+        for (i, flag) in code.flags.iter().enumerate() {
+            if i > 0 {
+                json.push_str(", ");
+            }
+            write!(json, "\"{flag}\"")?;
+        }
+        json.push_str("], \"operands\": [");
+        for (i, operand) in code.operands.iter().enumerate() {
+            if i > 0 {
+                json.push_str(", ");
+            }
+            write!(
+                json,
+                "{{\"name\": \"{}\", \"kind\": \"{}\", \"when\": {{",
+                operand.name,
+                operand.kind.name()
+            )?;
+            for (i, (flag, value)) in operand.when.iter().enumerate() {
+                if i > 0 {
+                    json.push_str(", ");
+                }
+                write!(json, "\"{flag}\": {value}")?;
+            }
+            json.push_str("}}");
+        }
+        json.push_str("]}");
+        if i + 1 < OPCODES.len() {
+            json.push(',');
+        }
+        json.push('\n');
+    }
+    json.push_str("  ]\n}\n");
+    Ok(())
+}
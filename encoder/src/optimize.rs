@@ -0,0 +1,522 @@
+//! An optional pass that removes redundant operations from a batch before it is sent to the interpreter.
+//!
+//! [`Batch::optimized`] decodes a batch with the rust [`Decoder`](crate::decoder::Decoder) and encodes it again without the operations whose effect is overwritten later in the same batch:
+//! - setting the text of a node, directly or formatted with `Intl`, or replacing its children with sanitized html, when the text or html is set again
+//! - setting or removing an attribute that is set or removed again
+//! - setting or removing a style property that is set again, or removing a style property that is removed again
+//! - setting the value of a form control that has its value set again
+//!
+//! Only operations on nodes that are addressed by id are coalesced. The pass is conservative: storing another node with the id keeps the operations before it, and cloning a node, walking the tree, reading the focused element or the visibility of an element, or starting a view transition keeps every operation before it because they can observe the state the operations wrote. Moving or removing a node keeps the text writes before it because setting text removes the children a node has at that point.
+//!
+//! Nodes that are created and removed in the same batch are dropped with every operation on them and on the nodes built inside of them, including the listeners added to them, as long as those operations do not change any other node and the batch has no view transition. A node that has a node from outside of the removed subtree moved into it, is cloned, or is reached by walking the tree is kept. Ids that were stored on removed nodes are treated as free after the batch, so they must not be used again until a node is stored with them, and the last node after the batch may be different.
+//!
+//! The optimized batch applies fewer operations, so the offsets in errors refer to the optimized batch and an operation that would have failed may be dropped. Attributes that were first added by a dropped operation can also end up in a different order on the element.
+//!
+//! Example:
+//! ```rust
+//! # use sledgehammer_encoder::{batch::Batch, *};
+//! let mut batch = Batch::default();
+//! for i in 0..10 {
+//!     batch.set_text(i, MaybeId::Node(NodeId(1)));
+//! }
+//! let optimized = batch.optimized().unwrap();
+//! assert_eq!(optimized.op_count(), 1);
+//!
+//! // the element never reaches the document, so nothing is sent
+//! let mut batch = Batch::default();
+//! batch.create_element(Element::div, Some(NodeId(2)));
+//! batch.set_attribute(Attribute::class, "hidden", MaybeId::LastNode);
+//! batch.append_child(MaybeId::Node(NodeId(0)), MaybeId::LastNode);
+//! batch.remove(MaybeId::Node(NodeId(2)));
+//! assert_eq!(batch.optimized().unwrap().op_count(), 0);
+//! ```
+
+use alloc::{collections::BTreeMap, vec, vec::Vec};
+
+use crate::{
+    attribute::AnyAttribute,
+    batch::Batch,
+    decoder::{DecodeError, DecodedNode, DecodedOp},
+    MaybeId, NodeId,
+};
+
+/// The part of a node an operation overwrites
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Target<'a> {
+    Text(NodeId),
+    /// The node, the name of the attribute and its namespace
+    Attribute(NodeId, &'a str, Option<&'a str>),
+    Style(NodeId, &'a str),
+    Value(NodeId),
+}
+
+impl Target<'_> {
+    fn id(&self) -> NodeId {
+        match *self {
+            Target::Text(id)
+            | Target::Attribute(id, ..)
+            | Target::Style(id, _)
+            | Target::Value(id) => id,
+        }
+    }
+}
+
+fn attribute_name<'a>(attr: &AnyAttribute<'a, 'a>) -> (&'a str, Option<&'a str>) {
+    match *attr {
+        #[cfg(feature = "attributes")]
+        AnyAttribute::Attribute(attr) => (attr.name(), None),
+        #[cfg(feature = "attributes")]
+        AnyAttribute::InNamespace(attr) => (attr.0.name(), Some(attr.1)),
+        AnyAttribute::Str(attr) => (attr, None),
+        AnyAttribute::InNamespaceStr(attr) => (attr.0, Some(attr.1)),
+    }
+}
+
+/// The part of a node the operation overwrites completely, if it only writes to a node stored with an id
+fn target<'a>(op: &DecodedOp<'a>) -> Option<Target<'a>> {
+    match *op {
+        DecodedOp::SetText {
+            root: MaybeId::Node(id),
+            ..
+        }
+        | DecodedOp::SetFormattedText {
+            root: MaybeId::Node(id),
+            ..
+        }
+        | DecodedOp::SetSanitizedHtml {
+            root: MaybeId::Node(id),
+            ..
+        } => Some(Target::Text(id)),
+        DecodedOp::SetAttribute {
+            root: MaybeId::Node(id),
+            ref attr,
+            ..
+        }
+        | DecodedOp::SetAttributeCached {
+            root: MaybeId::Node(id),
+            ref attr,
+            ..
+        }
+        | DecodedOp::RemoveAttribute {
+            root: MaybeId::Node(id),
+            ref attr,
+        } => {
+            let (name, namespace) = attribute_name(attr);
+            Some(Target::Attribute(id, name, namespace))
+        }
+        DecodedOp::SetStyle {
+            root: MaybeId::Node(id),
+            style,
+            ..
+        }
+        | DecodedOp::RemoveStyle {
+            root: MaybeId::Node(id),
+            style,
+        } => Some(Target::Style(id, style)),
+        DecodedOp::SetValue {
+            root: MaybeId::Node(id),
+            ..
+        } => Some(Target::Value(id)),
+        _ => None,
+    }
+}
+
+/// Push the ids of every node in a decoded tree
+fn node_ids(node: &DecodedNode, ids: &mut Vec<NodeId>) {
+    match node {
+        DecodedNode::Text { id, .. } => ids.extend(*id),
+        DecodedNode::Element { id, children, .. } => {
+            ids.extend(*id);
+            for child in children {
+                node_ids(child, ids);
+            }
+        }
+    }
+}
+
+/// The ids an operation stores a node with
+fn stored_ids(op: &DecodedOp) -> Vec<NodeId> {
+    let mut ids = Vec::new();
+    match *op {
+        DecodedOp::StoreWithId(id) | DecodedOp::AdoptIframeDocument { id, .. } => ids.push(id),
+        DecodedOp::CreateTextNode { id, .. } | DecodedOp::CreateElement { id, .. } => {
+            ids.extend(id)
+        }
+        DecodedOp::CloneNode {
+            new_id: MaybeId::Node(id),
+            ..
+        } => ids.push(id),
+        DecodedOp::BuildFullElement(ref node) => node_ids(node, &mut ids),
+        _ => {}
+    }
+    ids
+}
+
+/// Mark the operations that are overwritten later in the batch
+fn mark_superseded(ops: &[DecodedOp], keep: &mut [bool]) {
+    // the last operation that wrote to each target
+    let mut writes: BTreeMap<Target, usize> = BTreeMap::new();
+    for (i, op) in ops.iter().enumerate() {
+        if let Some(target) = target(op) {
+            if let Some(previous) = writes.insert(target, i) {
+                // setting a style property adds a style attribute that stays after the property is removed, so removing a property only overwrites another removal
+                let overwrites = !matches!(op, DecodedOp::RemoveStyle { .. })
+                    || matches!(ops[previous], DecodedOp::RemoveStyle { .. });
+                if overwrites {
+                    keep[previous] = false;
+                }
+            }
+            continue;
+        }
+        // clones copy the current state of a node, walking the tree can reach the text node an earlier write created, queries read the state earlier writes left, and view transitions capture it
+        if let DecodedOp::CloneNode { .. }
+        | DecodedOp::FirstChild
+        | DecodedOp::NextSibling
+        | DecodedOp::ParentNode
+        | DecodedOp::ActiveElement(_)
+        | DecodedOp::ElementFromPoint { .. }
+        | DecodedOp::CheckVisibility { .. }
+        | DecodedOp::DecodeImage { .. }
+        | DecodedOp::ViewTransition { .. } = op
+        {
+            writes.clear();
+        }
+        // setting text removes the children a node has when the text is set, so a later write does not remove children that were moved into the node in between
+        if let DecodedOp::AppendChildren { .. }
+        | DecodedOp::InsertBefore { .. }
+        | DecodedOp::InsertAfter { .. }
+        | DecodedOp::ReplaceWith { .. }
+        | DecodedOp::Remove(_)
+        | DecodedOp::PopParent
+        | DecodedOp::OpenParent
+        | DecodedOp::AppendToParent
+        | DecodedOp::AdoptIframeDocument { .. } = op
+        {
+            writes.retain(|target, _| !matches!(target, Target::Text(_)));
+        }
+        for id in stored_ids(op) {
+            writes.retain(|target, _| target.id() != id);
+        }
+    }
+}
+
+/// A node an operation refers to
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Node {
+    /// A node that existed before the batch or that the pass does not track
+    Live,
+    /// The index of a node created in the batch
+    New(usize),
+}
+
+/// Where a node created in the batch is
+#[derive(Clone, Copy)]
+enum Position {
+    Detached,
+    In(Node),
+    Removed,
+}
+
+/// Tracks the nodes created in a batch while the operations are walked
+struct Nodes {
+    last: Node,
+    ids: BTreeMap<NodeId, Node>,
+    positions: Vec<Position>,
+    parents: Vec<Node>,
+}
+
+impl Nodes {
+    fn get(&self, id: MaybeId) -> Node {
+        match id {
+            MaybeId::LastNode => self.last,
+            MaybeId::Node(id) => self.stored(id),
+        }
+    }
+
+    fn stored(&self, id: NodeId) -> Node {
+        self.ids.get(&id).copied().unwrap_or(Node::Live)
+    }
+
+    fn create(&mut self, position: Position) -> Node {
+        self.positions.push(position);
+        Node::New(self.positions.len() - 1)
+    }
+
+    fn place(&mut self, node: Node, position: Position) {
+        if let Node::New(index) = node {
+            self.positions[index] = position;
+        }
+    }
+
+    /// The position nodes inserted next to `node` end up in
+    fn next_to(&self, node: Node) -> Position {
+        match node {
+            Node::Live => Position::In(Node::Live),
+            Node::New(index) => self.positions[index],
+        }
+    }
+
+    /// Record the nodes an operation refers to, and if it changes a node that existed before the batch
+    fn apply(&mut self, op: &DecodedOp) -> (Vec<Node>, bool) {
+        match *op {
+            DecodedOp::CreateTextNode { id, .. } | DecodedOp::CreateElement { id, .. } => {
+                let node = self.create(Position::Detached);
+                self.last = node;
+                if let Some(id) = id {
+                    self.ids.insert(id, node);
+                }
+                (vec![node], false)
+            }
+            DecodedOp::BuildFullElement(ref root) => {
+                let node = self.create(Position::Detached);
+                self.last = node;
+                let (id, children) = match root {
+                    DecodedNode::Text { id, .. } => (*id, &[][..]),
+                    DecodedNode::Element { id, children, .. } => (*id, &children[..]),
+                };
+                if let Some(id) = id {
+                    self.ids.insert(id, node);
+                }
+                // nodes inside the element can only be moved out by their ids
+                let mut ids = Vec::new();
+                for child in children {
+                    node_ids(child, &mut ids);
+                }
+                let mut touched = vec![node];
+                for id in ids {
+                    let child = self.create(Position::In(node));
+                    self.ids.insert(id, child);
+                    touched.push(child);
+                }
+                (touched, false)
+            }
+            DecodedOp::AppendChildren { root, child } => {
+                let (root, child) = (self.get(root), self.get(child));
+                self.place(child, Position::In(root));
+                (vec![root, child], child == Node::Live)
+            }
+            DecodedOp::InsertBefore { root, ref nodes }
+            | DecodedOp::InsertAfter { root, ref nodes }
+            | DecodedOp::ReplaceWith { root, ref nodes } => {
+                let anchor = self.get(root);
+                let position = self.next_to(anchor);
+                let mut touched = vec![anchor];
+                for node in nodes {
+                    let node = self.get(*node);
+                    self.place(node, position);
+                    touched.push(node);
+                }
+                let mut changes_live = touched[1..].contains(&Node::Live);
+                // a node replaced with itself stays where it is
+                if matches!(op, DecodedOp::ReplaceWith { .. }) && !touched[1..].contains(&anchor) {
+                    self.place(anchor, Position::Removed);
+                    changes_live |= anchor == Node::Live;
+                }
+                (touched, changes_live)
+            }
+            DecodedOp::Remove(id) => {
+                let node = self.get(id);
+                self.place(node, Position::Removed);
+                (vec![node], node == Node::Live)
+            }
+            DecodedOp::SetText { root, .. }
+            | DecodedOp::SetAttribute { root, .. }
+            | DecodedOp::SetAttributeCached { root, .. }
+            | DecodedOp::RemoveAttribute { root, .. }
+            | DecodedOp::SetStyle { root, .. }
+            | DecodedOp::RemoveStyle { root, .. }
+            | DecodedOp::AddListener { root, .. }
+            | DecodedOp::RemoveListener { root, .. }
+            | DecodedOp::SetValue { root, .. }
+            | DecodedOp::ShowModal(root)
+            | DecodedOp::CloseDialog(root)
+            | DecodedOp::ShowPopover(root)
+            | DecodedOp::HidePopover(root)
+            | DecodedOp::RequestFullscreen { root, .. }
+            | DecodedOp::RequestPointerLock { root, .. }
+            | DecodedOp::ObserveCanvasSize { root, .. }
+            | DecodedOp::UnobserveCanvasSize(root)
+            | DecodedOp::ToggleClass { root, .. }
+            | DecodedOp::SetImageLoading { root, .. }
+            | DecodedOp::SetFormattedText { root, .. }
+            | DecodedOp::SetSanitizedHtml { root, .. } => {
+                let node = self.get(root);
+                (vec![node], node == Node::Live)
+            }
+            DecodedOp::StoreWithId(id) => {
+                let node = self.last;
+                self.ids.insert(id, node);
+                (vec![node], node == Node::Live)
+            }
+            DecodedOp::SetLastNode(id) => {
+                let node = self.stored(id);
+                self.last = node;
+                (vec![node], node == Node::Live)
+            }
+            DecodedOp::FirstChild | DecodedOp::NextSibling | DecodedOp::ParentNode => {
+                let node = self.last;
+                self.last = Node::Live;
+                (vec![node], true)
+            }
+            DecodedOp::CloneNode { id, new_id } => {
+                let node = self.get(id);
+                // clones are not tracked, so they are never dropped
+                self.last = Node::Live;
+                if let MaybeId::Node(new_id) = new_id {
+                    self.ids.insert(new_id, Node::Live);
+                }
+                (vec![node], true)
+            }
+            // the query must be answered even if the node is removed later in the batch
+            DecodedOp::CheckVisibility { root, .. } | DecodedOp::DecodeImage { root, .. } => {
+                (vec![self.get(root)], true)
+            }
+            DecodedOp::AdoptIframeDocument { iframe, id } => {
+                let node = self.get(iframe);
+                self.ids.insert(id, Node::Live);
+                (vec![node], true)
+            }
+            DecodedOp::PushParent => {
+                let node = self.last;
+                self.parents.push(node);
+                (vec![node], node == Node::Live)
+            }
+            DecodedOp::PopParent => {
+                let child = self.last;
+                // parents pushed before the batch are not tracked
+                let parent = self.parents.pop().unwrap_or(Node::Live);
+                self.place(child, Position::In(parent));
+                self.last = parent;
+                // popping a parent from outside of the batch changes the stack and the last node
+                (
+                    vec![child, parent],
+                    child == Node::Live || parent == Node::Live,
+                )
+            }
+            DecodedOp::OpenParent | DecodedOp::AppendToParent => {
+                let child = self.last;
+                let parent = self.parents.last().copied().unwrap_or(Node::Live);
+                self.place(child, Position::In(parent));
+                if let DecodedOp::OpenParent = op {
+                    self.parents.push(child);
+                }
+                (
+                    vec![child, parent],
+                    child == Node::Live || parent == Node::Live,
+                )
+            }
+            DecodedOp::CloseParent => {
+                let parent = self.parents.pop().unwrap_or(Node::Live);
+                self.last = parent;
+                (vec![parent], parent == Node::Live)
+            }
+            DecodedOp::Stop
+            | DecodedOp::NoOp
+            | DecodedOp::SetPoolSize { .. }
+            | DecodedOp::HintNodeCapacity(_)
+            | DecodedOp::CacheValue { .. }
+            | DecodedOp::SubscribeMediaQuery { .. }
+            | DecodedOp::UnsubscribeMediaQuery { .. }
+            | DecodedOp::GetStorageItem { .. }
+            | DecodedOp::SetStorageItem { .. }
+            | DecodedOp::RemoveStorageItem { .. }
+            | DecodedOp::ObserveCustomElement { .. }
+            | DecodedOp::UnobserveCustomElement { .. }
+            | DecodedOp::ExitFullscreen
+            | DecodedOp::ExitPointerLock
+            | DecodedOp::SubscribeAnimationFrames(_)
+            | DecodedOp::UnsubscribeAnimationFrames(_)
+            | DecodedOp::ActiveElement(_)
+            | DecodedOp::ElementFromPoint { .. }
+            | DecodedOp::ViewTransition { .. } => (Vec::new(), true),
+        }
+    }
+
+    /// If the node ends up removed at the end of the batch, either directly or inside of a removed node
+    fn removed(&self, mut index: usize) -> bool {
+        // a node moved into its own subtree is an error, so stop after visiting every node once
+        for _ in 0..self.positions.len() {
+            match self.positions[index] {
+                Position::Removed => return true,
+                Position::In(Node::New(parent)) => index = parent,
+                Position::Detached | Position::In(Node::Live) => return false,
+            }
+        }
+        false
+    }
+}
+
+/// Mark the operations on nodes that are created and removed in the batch without changing any other node
+fn mark_dead(ops: &[DecodedOp], keep: &mut [bool]) {
+    // the operations of a view transition can refer to any node by id, so no node is dropped
+    if ops
+        .iter()
+        .any(|op| matches!(op, DecodedOp::ViewTransition { .. }))
+    {
+        return;
+    }
+    let mut nodes = Nodes {
+        last: Node::Live,
+        ids: BTreeMap::new(),
+        positions: Vec::new(),
+        parents: Vec::new(),
+    };
+    let effects: Vec<_> = ops.iter().map(|op| nodes.apply(op)).collect();
+    let mut dead: Vec<bool> = (0..nodes.positions.len())
+        .map(|index| nodes.removed(index))
+        .collect();
+    if !dead.contains(&true) {
+        return;
+    }
+    // operations that only change nodes outside of the batch through their relationship to a dead node, like appending a dead node to them, can be dropped with the dead node
+    let is_kept = |dead: &[bool], node: &Node| match node {
+        Node::Live => false,
+        Node::New(index) => !dead[*index],
+    };
+    // every node a kept operation refers to must be kept as well, until nothing changes
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (touched, changes_live) in &effects {
+            let kept = *changes_live || touched.iter().any(|node| is_kept(&dead, node));
+            if !kept {
+                continue;
+            }
+            for node in touched {
+                if let Node::New(index) = *node {
+                    if dead[index] {
+                        dead[index] = false;
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+    for ((touched, changes_live), keep) in effects.iter().zip(keep) {
+        let refers_to_new = touched.iter().any(|node| *node != Node::Live);
+        if !changes_live && refers_to_new && !touched.iter().any(|node| is_kept(&dead, node)) {
+            *keep = false;
+        }
+    }
+}
+
+impl Batch {
+    /// Decode the batch and encode it again without the operations that are overwritten later in the batch or only change nodes that are removed in the batch. See [`crate::optimize`]
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn optimized(&self) -> Result<Batch, DecodeError> {
+        let ops = self
+            .decode()
+            .map(|op| op.map(|(_, op)| op))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut keep = vec![true; ops.len()];
+        mark_dead(&ops, &mut keep);
+        mark_superseded(&ops, &mut keep);
+        let mut optimized = Batch::default();
+        for (op, keep) in ops.iter().zip(keep) {
+            if keep {
+                optimized.encode_decoded_op(op);
+            }
+        }
+        Ok(optimized)
+    }
+}
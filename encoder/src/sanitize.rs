@@ -0,0 +1,126 @@
+//! Html from untrusted sources, like comments or chat messages, inserted without running scripts.
+//!
+//! [`Batch::set_sanitized_html`] replaces the children of a node with html that is parsed by the browser and cleaned with an [`Allowlist`] of elements and attributes. Browsers with the Sanitizer API insert the html with `Element.setHTML`, which also removes scripts and event handler attributes that are in the allowlist. Other browsers parse the html in an inert `<template>`, so nothing in it runs or loads, and the interpreter removes the elements that are not allowed with their content, the attributes that are not allowed, every `on*` attribute and every attribute with a `javascript:` url before the html is inserted.
+//!
+//! Example:
+//! ```rust
+//! # use sledgehammer_encoder::{batch::Batch, sanitize::Allowlist, *};
+//! let comment = r#"<p>nice <b>post</b><img src="x" onerror="alert(1)"></p>"#;
+//! let mut batch = Batch::default();
+//! // inserts <p>nice <b>post</b></p>
+//! batch.set_sanitized_html(comment, &Allowlist::DEFAULT, MaybeId::Node(NodeId(1)));
+//!
+//! // allow images with a source and a description as well
+//! let with_images = Allowlist::new(&["p", "b", "img"], &["src", "alt"]);
+//! batch.set_sanitized_html(comment, &with_images, MaybeId::Node(NodeId(2)));
+//! ```
+
+use alloc::vec::Vec;
+
+use crate::{batch::Batch, ExtendedOp, MaybeId, WritableText};
+
+/// The elements and attributes html inserted with [`Batch::set_sanitized_html`] may contain. Names are lowercase, and attributes are allowed on every allowed element. See [`crate::sanitize`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Allowlist<'a> {
+    pub elements: &'a [&'a str],
+    pub attributes: &'a [&'a str],
+}
+
+impl Allowlist<'static> {
+    /// Text formatting, headings, links, lists, quotes and code. Images and other elements that load resources are not allowed.
+    pub const DEFAULT: Self = Self {
+        elements: &[
+            "a",
+            "abbr",
+            "b",
+            "blockquote",
+            "br",
+            "code",
+            "del",
+            "em",
+            "h1",
+            "h2",
+            "h3",
+            "h4",
+            "h5",
+            "h6",
+            "hr",
+            "i",
+            "li",
+            "ol",
+            "p",
+            "pre",
+            "s",
+            "small",
+            "span",
+            "strong",
+            "sub",
+            "sup",
+            "u",
+            "ul",
+        ],
+        attributes: &["href", "title"],
+    };
+}
+
+impl Default for Allowlist<'static> {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl<'a> Allowlist<'a> {
+    /// Allow only these elements and attributes
+    pub const fn new(elements: &'a [&'a str], attributes: &'a [&'a str]) -> Self {
+        Self {
+            elements,
+            attributes,
+        }
+    }
+}
+
+/// Write names separated by commas
+fn write_names(names: &[&str], to: &mut Vec<u8>) {
+    for (i, name) in names.iter().enumerate() {
+        debug_assert!(
+            !name.contains(','),
+            "{name:?} is not an element or attribute name"
+        );
+        if i > 0 {
+            to.push(b',');
+        }
+        to.extend_from_slice(name.as_bytes());
+    }
+}
+
+impl Batch {
+    /// Replace the children of a node with html that is cleaned with an allowlist of elements and attributes before it is inserted. See [`crate::sanitize`]
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn set_sanitized_html(
+        &mut self,
+        html: impl WritableText,
+        allowlist: &Allowlist,
+        root: MaybeId,
+    ) {
+        self.encode_sanitized_html(
+            root,
+            html,
+            |to: &mut Vec<u8>| write_names(allowlist.elements, to),
+            |to: &mut Vec<u8>| write_names(allowlist.attributes, to),
+        );
+    }
+
+    pub(crate) fn encode_sanitized_html(
+        &mut self,
+        root: MaybeId,
+        html: impl WritableText,
+        elements: impl WritableText,
+        attributes: impl WritableText,
+    ) {
+        self.encode_extended_op(ExtendedOp::SetSanitizedHtml);
+        self.encode_maybe_id(root);
+        self.encode_str(html);
+        self.encode_str(elements);
+        self.encode_str(attributes);
+    }
+}
@@ -0,0 +1,137 @@
+//! Builders for svg attribute values.
+//!
+//! [`PathData`] formats the `d` attribute of a path directly into the string buffer of a batch, so charts that generate thousands of path segments per frame do not build an intermediate `String`. [`path`] wraps a builder in a [`WritableText`] that can be passed to any operation that takes text.
+//!
+//! Example:
+//! ```rust
+//! # use sledgehammer_encoder::{batch::Batch, svg::path, *};
+//! let points = [(0.0, 10.0), (5.0, 2.5), (10.0, 7.25)];
+//! let mut batch = Batch::default();
+//! batch.set_attribute(
+//!     "d",
+//!     path(|d| {
+//!         d.move_to(points[0].0, points[0].1);
+//!         for &(x, y) in &points[1..] {
+//!             d.line_to(x, y);
+//!         }
+//!     }),
+//!     MaybeId::Node(NodeId(0)),
+//! );
+//! assert_eq!(batch.str_buf, b"dM0 10L5 2.5L10 7.25");
+//! ```
+
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use crate::{BufWriter, WritableText};
+
+/// Path data that is written as it is built. See [`crate::svg`]
+///
+/// Numbers are written with the shortest representation that reads back as the same value, or with at most the number of decimal places set with [`PathData::precision`].
+#[derive(Debug)]
+pub struct PathData<'a> {
+    buf: &'a mut Vec<u8>,
+    precision: Option<usize>,
+}
+
+impl<'a> PathData<'a> {
+    /// Write path data to the end of a buffer
+    pub fn new(buf: &'a mut Vec<u8>) -> Self {
+        Self {
+            buf,
+            precision: None,
+        }
+    }
+
+    /// Round the numbers of the following commands to at most `digits` decimal places. Trailing zeros are not written.
+    pub fn precision(&mut self, digits: usize) -> &mut Self {
+        self.precision = Some(digits);
+        self
+    }
+
+    /// Start a new subpath at a point (`M`)
+    pub fn move_to(&mut self, x: f64, y: f64) -> &mut Self {
+        self.command(b'M', &[x, y])
+    }
+
+    /// Draw a line to a point (`L`)
+    pub fn line_to(&mut self, x: f64, y: f64) -> &mut Self {
+        self.command(b'L', &[x, y])
+    }
+
+    /// Draw a quadratic bezier curve to a point with one control point (`Q`)
+    pub fn quad_to(&mut self, cx: f64, cy: f64, x: f64, y: f64) -> &mut Self {
+        self.command(b'Q', &[cx, cy, x, y])
+    }
+
+    /// Draw a cubic bezier curve to a point with two control points (`C`)
+    pub fn cubic_to(
+        &mut self,
+        c1x: f64,
+        c1y: f64,
+        c2x: f64,
+        c2y: f64,
+        x: f64,
+        y: f64,
+    ) -> &mut Self {
+        self.command(b'C', &[c1x, c1y, c2x, c2y, x, y])
+    }
+
+    /// Draw an elliptical arc to a point (`A`). The arguments are in the same order as the arguments of the svg command.
+    #[allow(clippy::too_many_arguments)]
+    pub fn arc_to(
+        &mut self,
+        rx: f64,
+        ry: f64,
+        rotation: f64,
+        large_arc: bool,
+        sweep: bool,
+        x: f64,
+        y: f64,
+    ) -> &mut Self {
+        let large_arc = if large_arc { 1.0 } else { 0.0 };
+        let sweep = if sweep { 1.0 } else { 0.0 };
+        self.command(b'A', &[rx, ry, rotation, large_arc, sweep, x, y])
+    }
+
+    /// Close the current subpath with a line to its start (`Z`)
+    pub fn close(&mut self) -> &mut Self {
+        self.command(b'Z', &[])
+    }
+
+    fn command(&mut self, command: u8, numbers: &[f64]) -> &mut Self {
+        self.buf.push(command);
+        for (i, &n) in numbers.iter().enumerate() {
+            if i > 0 {
+                self.buf.push(b' ');
+            }
+            self.number(n);
+        }
+        self
+    }
+
+    fn number(&mut self, n: f64) {
+        let start = self.buf.len();
+        match self.precision {
+            Some(digits) => {
+                let _ = write!(BufWriter(self.buf), "{n:.digits$}");
+                if self.buf[start..].contains(&b'.') {
+                    while self.buf.last() == Some(&b'0') {
+                        self.buf.pop();
+                    }
+                    if self.buf.last() == Some(&b'.') {
+                        self.buf.pop();
+                    }
+                }
+            }
+            None => {
+                let _ = write!(BufWriter(self.buf), "{n}");
+            }
+        }
+    }
+}
+
+/// Build path data when it is written into a batch. See [`crate::svg`]
+pub fn path(build: impl FnOnce(&mut PathData<'_>)) -> impl WritableText {
+    move |buf: &mut Vec<u8>| build(&mut PathData::new(buf))
+}
@@ -0,0 +1,76 @@
+//! Checks that compressed batches decompress to the same bytes and that corrupt input returns an error instead of panicking.
+
+use proptest::prelude::*;
+use sledgehammer_encoder::{
+    batch::{Batch, FinalizedBatch},
+    compression::{compress, decompress, DecompressError},
+    MaybeId, NodeId, WIRE_FORMAT_VERSION,
+};
+
+proptest! {
+    #[test]
+    fn arbitrary_bytes_round_trip(input: Vec<u8>) {
+        prop_assert_eq!(decompress(&compress(&input), input.len()).unwrap(), input);
+    }
+
+    #[test]
+    fn repetitive_bytes_round_trip(chunk in proptest::collection::vec(any::<u8>(), 1..16), repeats in 0..2000usize) {
+        let input = chunk.repeat(repeats);
+        let compressed = compress(&input);
+        prop_assert_eq!(decompress(&compressed, input.len()).unwrap(), input);
+    }
+
+    #[test]
+    fn decompressing_arbitrary_bytes_does_not_panic(input: Vec<u8>, len in 0..4096usize) {
+        let _ = decompress(&input, len);
+        let _ = FinalizedBatch::from_compressed_bytes(&input);
+    }
+}
+
+#[test]
+fn big_renders_compress_well() {
+    let mut batch = Batch::default();
+    for i in 0..1000 {
+        batch.create_element("tr", None);
+        batch.set_attribute("class", "row", MaybeId::LastNode);
+        batch.create_text_node(i, None);
+        batch.append_child(MaybeId::Node(NodeId(1)), MaybeId::LastNode);
+    }
+    let finalized = batch.finalize();
+    let compressed = finalized.to_compressed_bytes();
+    assert!(compressed.len() * 4 < finalized.msg.len() + finalized.str.len());
+    let decompressed = FinalizedBatch::from_compressed_bytes(&compressed).unwrap();
+    assert_eq!(decompressed.msg, finalized.msg);
+    assert_eq!(decompressed.str, finalized.str);
+}
+
+#[test]
+fn truncated_batches_are_rejected() {
+    let mut batch = Batch::default();
+    batch.create_element("div", None);
+    let compressed = batch.finalize().to_compressed_bytes();
+    assert_eq!(
+        FinalizedBatch::from_compressed_bytes(&compressed[..compressed.len() - 1]).err(),
+        Some(DecompressError::UnexpectedEnd)
+    );
+    assert_eq!(
+        FinalizedBatch::from_compressed_bytes(&compressed[..4]).err(),
+        Some(DecompressError::UnexpectedEnd)
+    );
+}
+
+#[test]
+fn batches_from_other_format_versions_are_rejected() {
+    let mut batch = Batch::default();
+    batch.create_element("div", None);
+    let mut compressed = batch.finalize().to_compressed_bytes();
+    assert_eq!(compressed[0], WIRE_FORMAT_VERSION);
+    compressed[0] = WIRE_FORMAT_VERSION + 1;
+    assert_eq!(
+        FinalizedBatch::from_compressed_bytes(&compressed).err(),
+        Some(DecompressError::VersionMismatch {
+            expected: WIRE_FORMAT_VERSION,
+            found: WIRE_FORMAT_VERSION + 1,
+        })
+    );
+}
@@ -0,0 +1,38 @@
+use sledgehammer_encoder::{batch::Batch, Attribute, Element, MaybeId, NodeId};
+
+#[test]
+fn counts_operations_and_bytes() {
+    let mut batch = Batch::default();
+    assert_eq!(batch.op_count(), 0);
+    batch.create_element("div", Some(NodeId(0)));
+    batch.set_attribute(Attribute::id, "main", MaybeId::LastNode);
+    batch.first_child();
+    assert_eq!(batch.op_count(), 3);
+    assert_eq!(batch.str_bytes(), "divmain".len());
+    assert_eq!(batch.msg_bytes(), batch.msg.len());
+}
+
+#[test]
+fn append_does_not_count_padding() {
+    let mut batch = Batch::default();
+    batch.create_element(Element::div, None);
+    let mut other = Batch::default();
+    other.first_child();
+    other.next_sibling();
+    batch.append(other);
+    assert_eq!(batch.op_count(), 3);
+}
+
+#[test]
+fn clear_keeps_high_water_marks() {
+    let mut batch = Batch::default();
+    batch.create_text_node("hello world", None);
+    let msg_bytes = batch.msg_bytes();
+    batch.clear();
+    assert_eq!(batch.op_count(), 0);
+    assert_eq!(batch.msg_bytes(), 0);
+    assert_eq!(batch.str_bytes(), 0);
+    batch.create_text_node("hi", None);
+    assert_eq!(batch.msg_high_water_mark(), msg_bytes);
+    assert_eq!(batch.str_high_water_mark(), "hello world".len());
+}
@@ -0,0 +1,93 @@
+//! Checks the opcode table against the decoder, which is the reference for the binary format
+
+use sledgehammer_encoder::{
+    decoder::{DecodeErrorKind, Decoder},
+    opcodes::{Opcode, OperandKind, OPCODES},
+    Op,
+};
+
+const NO_OP: u8 = Op::NoOp as u8;
+
+fn lookup(op: u8, extended: Option<u8>) -> Option<&'static Opcode> {
+    OPCODES
+        .iter()
+        .find(|code| code.op as u8 == op && code.extended.map(|op| op as u8) == extended)
+}
+
+/// Encode an operation with the flags set in `bits` and every operand the table lists as zeros
+fn encode(code: &Opcode, bits: u8) -> Vec<u8> {
+    let mut msg = vec![code.op as u8 | bits << 5, NO_OP, NO_OP, NO_OP];
+    if let Some(extended) = code.extended {
+        msg.push(extended as u8);
+    }
+    let flag = |name: &str| {
+        let idx = code.flags.iter().position(|flag| *flag == name);
+        let idx = idx.unwrap_or_else(|| panic!("{} has no flag {name}", code.name));
+        bits & (1 << idx) != 0
+    };
+    for operand in code.operands {
+        if operand
+            .when
+            .iter()
+            .all(|(name, value)| flag(name) == *value)
+        {
+            let len = match operand.kind {
+                OperandKind::U8 | OperandKind::Element | OperandKind::Nodes => 1,
+                OperandKind::Str => 2,
+                OperandKind::U32 | OperandKind::I32 | OperandKind::Id | OperandKind::Bytes => 4,
+                // a header, an element, and no attributes or children
+                OperandKind::Node => 4,
+            };
+            msg.resize(msg.len() + len, 0);
+        }
+    }
+    msg
+}
+
+#[test]
+fn operand_layouts_match_the_decoder() {
+    for code in OPCODES {
+        assert_eq!(
+            code.name,
+            match code.extended {
+                Some(extended) => format!("{extended:?}"),
+                None => format!("{:?}", code.op),
+            }
+        );
+        for bits in 0..1 << code.flags.len() {
+            let msg = encode(code, bits);
+            let ops: Vec<_> = Decoder::new(&msg, &[])
+                .map(|op| op.unwrap_or_else(|err| panic!("{} {bits:03b}: {err}", code.name)))
+                .collect();
+            // extra or missing operand bytes are decoded as more operations or fail to decode
+            let expected = if code.op == Op::Stop { 1 } else { 4 };
+            assert_eq!(ops.len(), expected, "{} {bits:03b}: {ops:?}", code.name);
+        }
+    }
+}
+
+#[test]
+fn every_operation_is_in_the_table() {
+    let zeros = [0; 64];
+    for op in 0..32 {
+        if op == Op::Extended as u8 {
+            for extended in 0..=u8::MAX {
+                let mut msg = vec![op, NO_OP, NO_OP, NO_OP, extended];
+                msg.extend(zeros);
+                let unknown = matches!(
+                    Decoder::new(&msg, &[]).next(),
+                    Some(Err(err)) if err.kind == DecodeErrorKind::UnknownExtendedOp(extended)
+                );
+                assert_eq!(lookup(op, Some(extended)).is_none(), unknown, "{extended}");
+            }
+            continue;
+        }
+        let mut msg = vec![op, NO_OP, NO_OP, NO_OP];
+        msg.extend(zeros);
+        let unknown = matches!(
+            Decoder::new(&msg, &[]).next(),
+            Some(Err(err)) if err.kind == DecodeErrorKind::UnknownOp(op)
+        );
+        assert_eq!(lookup(op, None).is_none(), unknown, "{op}");
+    }
+}
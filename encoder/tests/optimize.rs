@@ -0,0 +1,217 @@
+//! Checks which operations the optimization pass removes from a batch.
+
+use sledgehammer_encoder::{
+    attribute::AnyAttribute,
+    batch::Batch,
+    decoder::{DecodedOp, Decoder},
+    Attribute, Element, ElementBuilder, HandlerId, MaybeId, NodeId, QueryId, WithNsExt,
+};
+
+fn decode(batch: &Batch) -> Vec<DecodedOp<'_>> {
+    Decoder::from_batch(batch).map(|op| op.unwrap().1).collect()
+}
+
+const NODE: MaybeId = MaybeId::Node(NodeId(1));
+
+#[test]
+fn drops_overwritten_text_attributes_and_styles() {
+    let mut batch = Batch::default();
+    batch.set_text("a", NODE);
+    batch.set_attribute(Attribute::class, "a", NODE);
+    // the same attribute by name
+    batch.set_attribute("class", "b", NODE);
+    batch.remove_style("color", NODE);
+    batch.remove_style("color", NODE);
+    batch.set_style("color", "blue", NODE);
+    batch.set_style("color", "red", NODE);
+    batch.remove_style("color", NODE);
+    batch.set_value("a", NODE);
+    batch.set_text("b", NODE);
+    batch.set_value("b", NODE);
+    batch.cache_value(0, "c");
+    batch.set_attribute_cached(Attribute::class, 0, NODE);
+    let optimized = batch.optimized().unwrap();
+    assert_eq!(
+        decode(&optimized),
+        [
+            // setting the style adds a style attribute, so it is not overwritten by removing the style
+            DecodedOp::SetStyle {
+                root: NODE,
+                style: "color",
+                value: "red"
+            },
+            DecodedOp::RemoveStyle {
+                root: NODE,
+                style: "color"
+            },
+            DecodedOp::SetText {
+                root: NODE,
+                text: "b"
+            },
+            DecodedOp::SetValue {
+                root: NODE,
+                value: "b"
+            },
+            DecodedOp::CacheValue { id: 0, value: "c" },
+            DecodedOp::SetAttributeCached {
+                root: NODE,
+                attr: AnyAttribute::Attribute(Attribute::class),
+                value: 0
+            },
+        ]
+    );
+}
+
+#[test]
+fn keeps_writes_to_different_targets() {
+    let mut batch = Batch::default();
+    batch.set_attribute(Attribute::class, "a", NODE);
+    batch.set_attribute(Attribute::class.in_namespace("ns"), "b", NODE);
+    batch.set_attribute(Attribute::class, "c", MaybeId::Node(NodeId(2)));
+    // operations on the last node are never coalesced
+    batch.set_text("a", MaybeId::LastNode);
+    batch.set_text("b", MaybeId::LastNode);
+    assert_eq!(batch.optimized().unwrap().op_count(), 5);
+}
+
+#[test]
+fn keeps_writes_before_the_id_is_reused() {
+    let mut batch = Batch::default();
+    batch.set_text("a", NODE);
+    batch.create_element(Element::div, Some(NodeId(1)));
+    batch.set_text("b", NODE);
+    batch.set_attribute(Attribute::class, "a", NODE);
+    batch.build_full_element(ElementBuilder::new(Element::p.into()).id(NodeId(1)));
+    batch.set_attribute(Attribute::class, "b", NODE);
+    assert_eq!(batch.optimized().unwrap().op_count(), 6);
+}
+
+#[test]
+fn keeps_writes_before_clones_and_traversals() {
+    let mut batch = Batch::default();
+    batch.set_attribute(Attribute::class, "a", NODE);
+    batch.clone_node(MaybeId::LastNode, MaybeId::Node(NodeId(2)));
+    batch.set_attribute(Attribute::class, "b", NODE);
+    // the first child is the text node the first write created
+    batch.set_text("a", NODE);
+    batch.set_last_node(NodeId(1));
+    batch.first_child();
+    batch.set_text("b", NODE);
+    assert_eq!(batch.optimized().unwrap().op_count(), 7);
+}
+
+#[test]
+fn keeps_writes_before_queries() {
+    let mut batch = Batch::default();
+    batch.set_style("display", "none", NODE);
+    batch.check_visibility(NODE, QueryId(0));
+    batch.set_style("display", "block", NODE);
+    batch.set_attribute("hidden", "", NODE);
+    batch.active_element(QueryId(1));
+    batch.remove_attribute("hidden", NODE);
+    assert_eq!(batch.optimized().unwrap().op_count(), 6);
+
+    // the query on a removed node is still answered
+    let mut batch = Batch::default();
+    batch.create_element(Element::div, Some(NodeId(1)));
+    batch.check_visibility(NODE, QueryId(0));
+    batch.remove(NODE);
+    assert_eq!(batch.optimized().unwrap().op_count(), 3);
+}
+
+#[test]
+fn keeps_text_writes_before_nodes_are_moved() {
+    // the first write removes node 2 from node 1 before it is moved into node 1 again
+    let mut batch = Batch::default();
+    batch.set_text("a", NODE);
+    batch.append_child(NODE, MaybeId::Node(NodeId(2)));
+    batch.set_text("b", NODE);
+    assert_eq!(batch.optimized().unwrap().op_count(), 3);
+}
+
+#[test]
+fn drops_subtrees_created_and_removed_in_the_batch() {
+    let mut batch = Batch::default();
+    batch.build_full_element(
+        ElementBuilder::new(Element::ul.into())
+            .id(NodeId(1))
+            .children(&[ElementBuilder::new(Element::li.into()).id(NodeId(2)).into()]),
+    );
+    batch.append_child(MaybeId::Node(NodeId(0)), MaybeId::LastNode);
+    batch.add_listener("click", HandlerId(0), MaybeId::Node(NodeId(2)));
+    batch.set_text("item", MaybeId::Node(NodeId(2)));
+    batch.create_element(Element::li, None);
+    batch.insert_after(MaybeId::Node(NodeId(2)), MaybeId::LastNode);
+    batch.push_parent();
+    batch.create_text_node("text", None);
+    batch.pop_parent();
+    batch.remove(NODE);
+    batch.set_text("kept", MaybeId::Node(NodeId(0)));
+    let optimized = batch.optimized().unwrap();
+    assert_eq!(
+        decode(&optimized),
+        [DecodedOp::SetText {
+            root: MaybeId::Node(NodeId(0)),
+            text: "kept"
+        }]
+    );
+}
+
+#[test]
+fn keeps_removed_subtrees_that_change_other_nodes() {
+    // a node from outside of the batch is moved into the removed node
+    let mut batch = Batch::default();
+    batch.create_element(Element::div, Some(NodeId(1)));
+    batch.append_child(NODE, MaybeId::Node(NodeId(5)));
+    batch.remove(NODE);
+    assert_eq!(batch.optimized().unwrap().op_count(), 3);
+
+    // a child is moved out of the removed node before it is removed
+    let mut batch = Batch::default();
+    batch.create_element(Element::div, Some(NodeId(1)));
+    batch.create_element(Element::p, Some(NodeId(2)));
+    batch.append_child(NODE, MaybeId::LastNode);
+    batch.append_child(MaybeId::Node(NodeId(0)), MaybeId::Node(NodeId(2)));
+    batch.remove(NODE);
+    assert_eq!(batch.optimized().unwrap().op_count(), 5);
+
+    // the removed node is reached by walking the tree
+    let mut batch = Batch::default();
+    batch.create_element(Element::div, Some(NodeId(1)));
+    batch.append_child(MaybeId::Node(NodeId(0)), MaybeId::LastNode);
+    batch.next_sibling();
+    batch.remove(NODE);
+    assert_eq!(batch.optimized().unwrap().op_count(), 4);
+
+    // nodes that are only detached are not removed
+    let mut batch = Batch::default();
+    batch.create_element(Element::div, Some(NodeId(1)));
+    batch.set_text("template", NODE);
+    assert_eq!(batch.optimized().unwrap().op_count(), 2);
+
+    // a parent from outside of the batch is popped
+    let mut batch = Batch::default();
+    batch.set_last_node(NodeId(0));
+    batch.push_parent();
+    batch.create_element(Element::div, Some(NodeId(1)));
+    batch.pop_parent();
+    batch.remove(NODE);
+    assert_eq!(batch.optimized().unwrap().op_count(), 5);
+
+    // an element opened in a parent from outside of the batch stays on the stack of parents
+    let mut batch = Batch::default();
+    batch.create_element(Element::ul, Some(NodeId(1)));
+    batch.open_parent();
+    batch.create_element(Element::li, None);
+    batch.append_to_parent();
+    batch.close_parent();
+    batch.remove(NODE);
+    assert_eq!(batch.optimized().unwrap().op_count(), 6);
+
+    // a node replaced with itself is not removed
+    let mut batch = Batch::default();
+    batch.create_element(Element::div, Some(NodeId(1)));
+    batch.append_child(MaybeId::Node(NodeId(0)), MaybeId::LastNode);
+    batch.replace_with(NODE, NODE);
+    assert_eq!(batch.optimized().unwrap().op_count(), 3);
+}
@@ -0,0 +1,961 @@
+//! Encodes random operations into a batch, decodes them with the rust decoder and checks that the decoded operations match the encoded operations.
+
+use bumpalo::Bump;
+use proptest::prelude::*;
+use sledgehammer_encoder::{
+    attribute::AnyAttribute,
+    batch::Batch,
+    decoder::{DecodedNode, DecodedOp, Decoder},
+    element::AnyElement,
+    intl::{DateTimeFormat, NumberFormat},
+    sanitize::Allowlist,
+    Attribute, Element, ElementBuilder, HandlerId, InNamespace, MaybeId, NodeBuilder, NodeId,
+    OwnedElementBuilder, QueryId, StorageArea, TextBuilder, WritableText,
+};
+
+#[derive(Debug, Clone)]
+enum Tag {
+    Element(Element),
+    ElementNs(Element, String),
+    Str(String),
+    StrNs(String, String),
+}
+
+impl Tag {
+    fn any(&self) -> AnyElement<'_, '_> {
+        match self {
+            Tag::Element(el) => AnyElement::Element(*el),
+            Tag::ElementNs(el, ns) => AnyElement::InNamespace(InNamespace(*el, ns)),
+            Tag::Str(el) => AnyElement::Str(el),
+            Tag::StrNs(el, ns) => AnyElement::InNamespaceStr(InNamespace(el, ns)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Attr {
+    Attribute(Attribute),
+    AttributeNs(Attribute, String),
+    Str(String),
+    StrNs(String, String),
+}
+
+impl Attr {
+    fn any(&self) -> AnyAttribute<'_, '_> {
+        match self {
+            Attr::Attribute(attr) => AnyAttribute::Attribute(*attr),
+            Attr::AttributeNs(attr, ns) => AnyAttribute::InNamespace(InNamespace(*attr, ns)),
+            Attr::Str(attr) => AnyAttribute::Str(attr),
+            Attr::StrNs(attr, ns) => AnyAttribute::InNamespaceStr(InNamespace(attr, ns)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Text(Option<u32>, String),
+    Element(Option<u32>, Tag, Vec<(Attr, String)>, Vec<Node>),
+}
+
+impl Node {
+    fn builder<'a>(&'a self, bump: &'a Bump) -> NodeBuilder<'a> {
+        match self {
+            Node::Text(id, text) => {
+                let mut builder = TextBuilder::new(text);
+                if let Some(id) = id {
+                    builder = builder.id(NodeId(*id));
+                }
+                builder.into()
+            }
+            Node::Element(id, tag, attrs, children) => {
+                let attrs: Vec<_> = attrs
+                    .iter()
+                    .map(|(attr, value)| (attr.any(), value.as_str()))
+                    .collect();
+                let children: Vec<_> = children.iter().map(|c| c.builder(bump)).collect();
+                let mut builder = ElementBuilder::new(tag.any())
+                    .attrs(bump.alloc(attrs))
+                    .children(bump.alloc(children));
+                if let Some(id) = id {
+                    builder = builder.id(NodeId(*id));
+                }
+                builder.into()
+            }
+        }
+    }
+
+    fn decoded(&self) -> DecodedNode<'_> {
+        match self {
+            Node::Text(id, text) => DecodedNode::Text {
+                id: id.map(NodeId),
+                text,
+            },
+            Node::Element(id, tag, attrs, children) => DecodedNode::Element {
+                id: id.map(NodeId),
+                kind: tag.any(),
+                attrs: attrs
+                    .iter()
+                    .map(|(attr, value)| (attr.any(), value.as_str()))
+                    .collect(),
+                children: children.iter().map(Node::decoded).collect(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum TestOp {
+    FirstChild,
+    NextSibling,
+    ParentNode,
+    StoreWithId(u32),
+    SetLastNode(u32),
+    BuildFullElement(Node),
+    AppendChild(MaybeId, MaybeId),
+    ReplaceWith(MaybeId, MaybeId),
+    ReplaceWithNodes(MaybeId, Vec<MaybeId>),
+    InsertAfter(MaybeId, MaybeId),
+    InsertNodesAfter(MaybeId, Vec<MaybeId>),
+    InsertBefore(MaybeId, MaybeId),
+    InsertNodesBefore(MaybeId, Vec<MaybeId>),
+    Remove(MaybeId),
+    CreateTextNode(String, Option<u32>),
+    CreateElement(Tag, Option<u32>),
+    SetText(String, MaybeId),
+    SetAttribute(Attr, String, MaybeId),
+    RemoveAttribute(Attr, MaybeId),
+    SetStyle(String, String, MaybeId),
+    RemoveStyle(String, MaybeId),
+    CloneNode(MaybeId, MaybeId),
+    AdoptIframeDocument(MaybeId, u32),
+    SetPoolSize(String, u32),
+    HintNodeCapacity(u32),
+    CacheValue(u8, String),
+    SetAttributeCached(Attr, u8, MaybeId),
+    PushParent,
+    PopParent,
+    AddListener(String, u32, MaybeId, bool),
+    RemoveListener(String, u32, MaybeId),
+    SetValue(String, MaybeId),
+    SubscribeMediaQuery(String, u32),
+    UnsubscribeMediaQuery(String, u32),
+    GetStorageItem(StorageArea, String, u32),
+    SetStorageItem(StorageArea, String, String),
+    RemoveStorageItem(StorageArea, String),
+    ObserveCustomElement(String, u32),
+    UnobserveCustomElement(String, u32),
+    ShowModal(MaybeId),
+    CloseDialog(MaybeId),
+    ShowPopover(MaybeId),
+    HidePopover(MaybeId),
+    RequestFullscreen(MaybeId, u32),
+    ExitFullscreen,
+    RequestPointerLock(MaybeId, u32),
+    ExitPointerLock,
+    SubscribeAnimationFrames(u32),
+    UnsubscribeAnimationFrames(u32),
+    ObserveCanvasSize(MaybeId, u32),
+    UnobserveCanvasSize(MaybeId),
+    ActiveElement(u32),
+    ElementFromPoint(i32, i32, u32),
+    CheckVisibility(MaybeId, u32),
+    /// If the image is lazy and if it is decoded asynchronously
+    SetImageLoading(bool, bool, MaybeId),
+    DecodeImage(MaybeId, u32),
+    ToggleClass(String, bool, MaybeId),
+    /// An integer written as a string, which is how the number is encoded
+    SetFormattedText(String, bool, MaybeId),
+    /// The handler and the message and strings of the finalized nested batch
+    ViewTransition(u32, Vec<u8>, Vec<u8>),
+    OpenParent,
+    AppendToParent,
+    CloseParent,
+    /// The html and the allowed elements and attributes separated by commas
+    SetSanitizedHtml(String, String, String, MaybeId),
+}
+
+impl TestOp {
+    fn encode(&self, batch: &mut Batch) {
+        match self {
+            TestOp::FirstChild => batch.first_child(),
+            TestOp::NextSibling => batch.next_sibling(),
+            TestOp::ParentNode => batch.parent_node(),
+            TestOp::StoreWithId(id) => batch.store_with_id(NodeId(*id)),
+            TestOp::SetLastNode(id) => batch.set_last_node(NodeId(*id)),
+            TestOp::BuildFullElement(node) => {
+                let bump = Bump::new();
+                match node.builder(&bump) {
+                    NodeBuilder::Element(el) => batch.build_full_element(el),
+                    NodeBuilder::Text(text) => batch.build_text_node(text),
+                }
+            }
+            TestOp::AppendChild(root, child) => batch.append_child(*root, *child),
+            TestOp::ReplaceWith(root, node) => batch.replace_with(*root, *node),
+            TestOp::ReplaceWithNodes(root, nodes) => batch.replace_with_nodes(*root, nodes),
+            TestOp::InsertAfter(root, node) => batch.insert_after(*root, *node),
+            TestOp::InsertNodesAfter(root, nodes) => batch.insert_nodes_after(*root, nodes),
+            TestOp::InsertBefore(root, node) => batch.insert_before(*root, *node),
+            TestOp::InsertNodesBefore(root, nodes) => batch.insert_nodes_before(*root, nodes),
+            TestOp::Remove(id) => batch.remove(*id),
+            TestOp::CreateTextNode(text, id) => {
+                batch.create_text_node(text.as_str(), id.map(NodeId))
+            }
+            TestOp::CreateElement(tag, id) => {
+                let id = id.map(NodeId);
+                match tag {
+                    Tag::Element(el) => batch.create_element(*el, id),
+                    Tag::ElementNs(el, ns) => batch.create_element(InNamespace(*el, ns), id),
+                    Tag::Str(el) => batch.create_element(el.as_str(), id),
+                    Tag::StrNs(el, ns) => batch.create_element(InNamespace(el.as_str(), ns), id),
+                }
+            }
+            TestOp::SetText(text, root) => batch.set_text(text.as_str(), *root),
+            TestOp::SetAttribute(attr, value, root) => {
+                let value = value.as_str();
+                match attr {
+                    Attr::Attribute(attr) => batch.set_attribute(*attr, value, *root),
+                    Attr::AttributeNs(attr, ns) => {
+                        batch.set_attribute(InNamespace(*attr, ns), value, *root)
+                    }
+                    Attr::Str(attr) => batch.set_attribute(attr.as_str(), value, *root),
+                    Attr::StrNs(attr, ns) => {
+                        batch.set_attribute(InNamespace(attr.as_str(), ns), value, *root)
+                    }
+                }
+            }
+            TestOp::RemoveAttribute(attr, root) => match attr {
+                Attr::Attribute(attr) => batch.remove_attribute(*attr, *root),
+                Attr::AttributeNs(attr, ns) => {
+                    batch.remove_attribute(InNamespace(*attr, ns), *root)
+                }
+                Attr::Str(attr) => batch.remove_attribute(attr.as_str(), *root),
+                Attr::StrNs(attr, ns) => {
+                    batch.remove_attribute(InNamespace(attr.as_str(), ns), *root)
+                }
+            },
+            TestOp::SetStyle(style, value, root) => batch.set_style(style, value, *root),
+            TestOp::RemoveStyle(style, root) => batch.remove_style(style, *root),
+            TestOp::CloneNode(id, new_id) => batch.clone_node(*id, *new_id),
+            TestOp::AdoptIframeDocument(iframe, id) => {
+                batch.adopt_iframe_document(*iframe, NodeId(*id))
+            }
+            TestOp::SetPoolSize(tag, size) => batch.set_pool_size(tag, *size),
+            TestOp::HintNodeCapacity(capacity) => batch.hint_node_capacity(*capacity),
+            TestOp::CacheValue(id, value) => batch.cache_value(*id, value),
+            TestOp::PushParent => batch.push_parent(),
+            TestOp::PopParent => batch.pop_parent(),
+            TestOp::OpenParent => batch.open_parent(),
+            TestOp::AppendToParent => batch.append_to_parent(),
+            TestOp::CloseParent => batch.close_parent(),
+            TestOp::SetSanitizedHtml(html, elements, attributes, root) => {
+                let elements: Vec<_> = elements.split(',').collect();
+                let attributes: Vec<_> = attributes.split(',').collect();
+                batch.set_sanitized_html(html, &Allowlist::new(&elements, &attributes), *root)
+            }
+            TestOp::AddListener(event, handler, root, false) => {
+                batch.add_listener(event, HandlerId(*handler), *root)
+            }
+            TestOp::AddListener(event, handler, root, true) => {
+                batch.add_coalesced_listener(event, HandlerId(*handler), *root)
+            }
+            TestOp::RemoveListener(event, handler, root) => {
+                batch.remove_listener(event, HandlerId(*handler), *root)
+            }
+            TestOp::SetValue(value, root) => batch.set_value(value, *root),
+            TestOp::SubscribeMediaQuery(query, handler) => {
+                batch.subscribe_media_query(query, HandlerId(*handler))
+            }
+            TestOp::UnsubscribeMediaQuery(query, handler) => {
+                batch.unsubscribe_media_query(query, HandlerId(*handler))
+            }
+            TestOp::GetStorageItem(area, key, query) => {
+                batch.get_storage_item(*area, key, QueryId(*query))
+            }
+            TestOp::SetStorageItem(area, key, value) => batch.set_storage_item(*area, key, value),
+            TestOp::RemoveStorageItem(area, key) => batch.remove_storage_item(*area, key),
+            TestOp::ObserveCustomElement(tag, handler) => {
+                batch.observe_custom_element(tag, HandlerId(*handler))
+            }
+            TestOp::UnobserveCustomElement(tag, handler) => {
+                batch.unobserve_custom_element(tag, HandlerId(*handler))
+            }
+            TestOp::ShowModal(root) => batch.show_modal(*root),
+            TestOp::CloseDialog(root) => batch.close_dialog(*root),
+            TestOp::ShowPopover(root) => batch.show_popover(*root),
+            TestOp::HidePopover(root) => batch.hide_popover(*root),
+            TestOp::RequestFullscreen(root, handler) => {
+                batch.request_fullscreen(*root, HandlerId(*handler))
+            }
+            TestOp::ExitFullscreen => batch.exit_fullscreen(),
+            TestOp::RequestPointerLock(root, handler) => {
+                batch.request_pointer_lock(*root, HandlerId(*handler))
+            }
+            TestOp::ExitPointerLock => batch.exit_pointer_lock(),
+            TestOp::SubscribeAnimationFrames(handler) => {
+                batch.subscribe_animation_frames(HandlerId(*handler))
+            }
+            TestOp::UnsubscribeAnimationFrames(handler) => {
+                batch.unsubscribe_animation_frames(HandlerId(*handler))
+            }
+            TestOp::ObserveCanvasSize(root, handler) => {
+                batch.observe_canvas_size(*root, HandlerId(*handler))
+            }
+            TestOp::UnobserveCanvasSize(root) => batch.unobserve_canvas_size(*root),
+            TestOp::ActiveElement(query) => batch.active_element(QueryId(*query)),
+            TestOp::ElementFromPoint(x, y, query) => {
+                batch.element_from_point(*x, *y, QueryId(*query))
+            }
+            TestOp::CheckVisibility(root, query) => batch.check_visibility(*root, QueryId(*query)),
+            TestOp::SetImageLoading(lazy, async_decoding, root) => {
+                batch.set_image_loading(*lazy, *async_decoding, *root)
+            }
+            TestOp::DecodeImage(root, query) => batch.decode_image(*root, QueryId(*query)),
+            TestOp::ToggleClass(class, add, root) => batch.toggle_class_if(class, *add, *root),
+            TestOp::SetFormattedText(value, false, root) => {
+                batch.set_formatted_number(value.parse().unwrap(), &NumberFormat::new(), *root)
+            }
+            TestOp::SetFormattedText(value, true, root) => {
+                batch.set_formatted_date(value.parse().unwrap(), &DateTimeFormat::new(), *root)
+            }
+            TestOp::ViewTransition(handler, msg, str_buf) => {
+                batch.with_view_transition(HandlerId(*handler), |batch| {
+                    for op in decode(Decoder::new(msg, str_buf)) {
+                        batch.encode_decoded_op(&op);
+                    }
+                })
+            }
+            TestOp::SetAttributeCached(attr, value, root) => match attr {
+                Attr::Attribute(attr) => batch.set_attribute_cached(*attr, *value, *root),
+                Attr::AttributeNs(attr, ns) => {
+                    batch.set_attribute_cached(InNamespace(*attr, ns), *value, *root)
+                }
+                Attr::Str(attr) => batch.set_attribute_cached(attr.as_str(), *value, *root),
+                Attr::StrNs(attr, ns) => {
+                    batch.set_attribute_cached(InNamespace(attr.as_str(), ns), *value, *root)
+                }
+            },
+        }
+    }
+
+    fn decoded(&self) -> DecodedOp<'_> {
+        match self {
+            TestOp::FirstChild => DecodedOp::FirstChild,
+            TestOp::NextSibling => DecodedOp::NextSibling,
+            TestOp::ParentNode => DecodedOp::ParentNode,
+            TestOp::StoreWithId(id) => DecodedOp::StoreWithId(NodeId(*id)),
+            TestOp::SetLastNode(id) => DecodedOp::SetLastNode(NodeId(*id)),
+            // text nodes are built with the create text node operation
+            TestOp::BuildFullElement(Node::Text(id, text)) => DecodedOp::CreateTextNode {
+                text,
+                id: id.map(NodeId),
+            },
+            TestOp::BuildFullElement(node) => DecodedOp::BuildFullElement(node.decoded()),
+            TestOp::AppendChild(root, child) => DecodedOp::AppendChildren {
+                root: *root,
+                child: *child,
+            },
+            TestOp::ReplaceWith(root, node) => DecodedOp::ReplaceWith {
+                root: *root,
+                nodes: vec![*node],
+            },
+            TestOp::ReplaceWithNodes(root, nodes) => DecodedOp::ReplaceWith {
+                root: *root,
+                nodes: nodes.clone(),
+            },
+            TestOp::InsertAfter(root, node) => DecodedOp::InsertAfter {
+                root: *root,
+                nodes: vec![*node],
+            },
+            TestOp::InsertNodesAfter(root, nodes) => DecodedOp::InsertAfter {
+                root: *root,
+                nodes: nodes.clone(),
+            },
+            TestOp::InsertBefore(root, node) => DecodedOp::InsertBefore {
+                root: *root,
+                nodes: vec![*node],
+            },
+            TestOp::InsertNodesBefore(root, nodes) => DecodedOp::InsertBefore {
+                root: *root,
+                nodes: nodes.clone(),
+            },
+            TestOp::Remove(id) => DecodedOp::Remove(*id),
+            TestOp::CreateTextNode(text, id) => DecodedOp::CreateTextNode {
+                text,
+                id: id.map(NodeId),
+            },
+            TestOp::CreateElement(tag, id) => DecodedOp::CreateElement {
+                tag: tag.any(),
+                id: id.map(NodeId),
+            },
+            TestOp::SetText(text, root) => DecodedOp::SetText { root: *root, text },
+            TestOp::SetAttribute(attr, value, root) => DecodedOp::SetAttribute {
+                root: *root,
+                attr: attr.any(),
+                value,
+            },
+            TestOp::RemoveAttribute(attr, root) => DecodedOp::RemoveAttribute {
+                root: *root,
+                attr: attr.any(),
+            },
+            TestOp::SetStyle(style, value, root) => DecodedOp::SetStyle {
+                root: *root,
+                style,
+                value,
+            },
+            TestOp::RemoveStyle(style, root) => DecodedOp::RemoveStyle { root: *root, style },
+            TestOp::CloneNode(id, new_id) => DecodedOp::CloneNode {
+                id: *id,
+                new_id: *new_id,
+            },
+            TestOp::AdoptIframeDocument(iframe, id) => DecodedOp::AdoptIframeDocument {
+                iframe: *iframe,
+                id: NodeId(*id),
+            },
+            TestOp::SetPoolSize(tag, size) => DecodedOp::SetPoolSize { tag, size: *size },
+            TestOp::HintNodeCapacity(capacity) => DecodedOp::HintNodeCapacity(*capacity),
+            TestOp::CacheValue(id, value) => DecodedOp::CacheValue { id: *id, value },
+            TestOp::PushParent => DecodedOp::PushParent,
+            TestOp::PopParent => DecodedOp::PopParent,
+            TestOp::OpenParent => DecodedOp::OpenParent,
+            TestOp::AppendToParent => DecodedOp::AppendToParent,
+            TestOp::CloseParent => DecodedOp::CloseParent,
+            TestOp::SetSanitizedHtml(html, elements, attributes, root) => {
+                DecodedOp::SetSanitizedHtml {
+                    root: *root,
+                    html,
+                    elements,
+                    attributes,
+                }
+            }
+            TestOp::AddListener(event, handler, root, coalesced) => DecodedOp::AddListener {
+                root: *root,
+                event,
+                handler: HandlerId(*handler),
+                coalesced: *coalesced,
+            },
+            TestOp::RemoveListener(event, handler, root) => DecodedOp::RemoveListener {
+                root: *root,
+                event,
+                handler: HandlerId(*handler),
+            },
+            TestOp::SetValue(value, root) => DecodedOp::SetValue { root: *root, value },
+            TestOp::SubscribeMediaQuery(query, handler) => DecodedOp::SubscribeMediaQuery {
+                query,
+                handler: HandlerId(*handler),
+            },
+            TestOp::UnsubscribeMediaQuery(query, handler) => DecodedOp::UnsubscribeMediaQuery {
+                query,
+                handler: HandlerId(*handler),
+            },
+            TestOp::GetStorageItem(area, key, query) => DecodedOp::GetStorageItem {
+                area: *area,
+                key,
+                query: QueryId(*query),
+            },
+            TestOp::SetStorageItem(area, key, value) => DecodedOp::SetStorageItem {
+                area: *area,
+                key,
+                value,
+            },
+            TestOp::RemoveStorageItem(area, key) => {
+                DecodedOp::RemoveStorageItem { area: *area, key }
+            }
+            TestOp::ObserveCustomElement(tag, handler) => DecodedOp::ObserveCustomElement {
+                tag,
+                handler: HandlerId(*handler),
+            },
+            TestOp::UnobserveCustomElement(tag, handler) => DecodedOp::UnobserveCustomElement {
+                tag,
+                handler: HandlerId(*handler),
+            },
+            TestOp::ShowModal(root) => DecodedOp::ShowModal(*root),
+            TestOp::CloseDialog(root) => DecodedOp::CloseDialog(*root),
+            TestOp::ShowPopover(root) => DecodedOp::ShowPopover(*root),
+            TestOp::HidePopover(root) => DecodedOp::HidePopover(*root),
+            TestOp::RequestFullscreen(root, handler) => DecodedOp::RequestFullscreen {
+                root: *root,
+                handler: HandlerId(*handler),
+            },
+            TestOp::ExitFullscreen => DecodedOp::ExitFullscreen,
+            TestOp::RequestPointerLock(root, handler) => DecodedOp::RequestPointerLock {
+                root: *root,
+                handler: HandlerId(*handler),
+            },
+            TestOp::ExitPointerLock => DecodedOp::ExitPointerLock,
+            TestOp::SubscribeAnimationFrames(handler) => {
+                DecodedOp::SubscribeAnimationFrames(HandlerId(*handler))
+            }
+            TestOp::UnsubscribeAnimationFrames(handler) => {
+                DecodedOp::UnsubscribeAnimationFrames(HandlerId(*handler))
+            }
+            TestOp::ObserveCanvasSize(root, handler) => DecodedOp::ObserveCanvasSize {
+                root: *root,
+                handler: HandlerId(*handler),
+            },
+            TestOp::UnobserveCanvasSize(root) => DecodedOp::UnobserveCanvasSize(*root),
+            TestOp::ActiveElement(query) => DecodedOp::ActiveElement(QueryId(*query)),
+            TestOp::ElementFromPoint(x, y, query) => DecodedOp::ElementFromPoint {
+                x: *x,
+                y: *y,
+                query: QueryId(*query),
+            },
+            TestOp::SetImageLoading(lazy, async_decoding, root) => DecodedOp::SetImageLoading {
+                root: *root,
+                lazy: *lazy,
+                async_decoding: *async_decoding,
+            },
+            TestOp::DecodeImage(root, query) => DecodedOp::DecodeImage {
+                root: *root,
+                query: QueryId(*query),
+            },
+            TestOp::CheckVisibility(root, query) => DecodedOp::CheckVisibility {
+                root: *root,
+                query: QueryId(*query),
+            },
+            TestOp::ToggleClass(class, add, root) => DecodedOp::ToggleClass {
+                root: *root,
+                class,
+                add: *add,
+            },
+            TestOp::SetFormattedText(value, date, root) => DecodedOp::SetFormattedText {
+                root: *root,
+                date: *date,
+                value,
+                locale: "",
+                options: "{}",
+            },
+            TestOp::ViewTransition(handler, msg, str_buf) => DecodedOp::ViewTransition {
+                handler: HandlerId(*handler),
+                msg,
+                str_buf,
+            },
+            TestOp::SetAttributeCached(attr, value, root) => DecodedOp::SetAttributeCached {
+                root: *root,
+                attr: attr.any(),
+                value: *value,
+            },
+        }
+    }
+}
+
+fn string() -> impl Strategy<Value = String> {
+    "\\PC{0,12}"
+}
+
+fn id() -> impl Strategy<Value = u32> {
+    prop_oneof![0..16u32, any::<u32>()]
+}
+
+fn maybe_id() -> impl Strategy<Value = MaybeId> {
+    prop_oneof![
+        Just(MaybeId::LastNode),
+        id().prop_map(|id| MaybeId::Node(NodeId(id)))
+    ]
+}
+
+fn tag() -> impl Strategy<Value = Tag> {
+    let element = prop::sample::select(Element::VARIANTS);
+    prop_oneof![
+        element.clone().prop_map(Tag::Element),
+        (element, string()).prop_map(|(el, ns)| Tag::ElementNs(el, ns)),
+        string().prop_map(Tag::Str),
+        (string(), string()).prop_map(|(el, ns)| Tag::StrNs(el, ns)),
+    ]
+}
+
+fn attr() -> impl Strategy<Value = Attr> {
+    let attribute = prop::sample::select(Attribute::VARIANTS);
+    prop_oneof![
+        attribute.clone().prop_map(Attr::Attribute),
+        (attribute, string()).prop_map(|(attr, ns)| Attr::AttributeNs(attr, ns)),
+        string().prop_map(Attr::Str),
+        (string(), string()).prop_map(|(attr, ns)| Attr::StrNs(attr, ns)),
+    ]
+}
+
+fn node() -> impl Strategy<Value = Node> {
+    let leaf = prop_oneof![
+        (prop::option::of(id()), string()).prop_map(|(id, text)| Node::Text(id, text)),
+        (prop::option::of(id()), tag()).prop_map(|(id, tag)| Node::Element(
+            id,
+            tag,
+            Vec::new(),
+            Vec::new()
+        )),
+    ];
+    leaf.prop_recursive(4, 32, 6, |inner| {
+        (
+            prop::option::of(id()),
+            tag(),
+            prop::collection::vec((attr(), string()), 0..6),
+            prop::collection::vec(inner, 0..6),
+        )
+            .prop_map(|(id, tag, attrs, children)| Node::Element(id, tag, attrs, children))
+    })
+}
+
+fn storage_area() -> impl Strategy<Value = StorageArea> {
+    prop_oneof![Just(StorageArea::Local), Just(StorageArea::Session)]
+}
+
+fn nodes() -> impl Strategy<Value = Vec<MaybeId>> {
+    prop::collection::vec(maybe_id(), 0..8)
+}
+
+fn op() -> impl Strategy<Value = TestOp> {
+    prop_oneof![
+        Just(TestOp::FirstChild),
+        Just(TestOp::NextSibling),
+        Just(TestOp::ParentNode),
+        id().prop_map(TestOp::StoreWithId),
+        id().prop_map(TestOp::SetLastNode),
+        node().prop_map(TestOp::BuildFullElement),
+        (maybe_id(), maybe_id()).prop_map(|(a, b)| TestOp::AppendChild(a, b)),
+        (maybe_id(), maybe_id()).prop_map(|(a, b)| TestOp::ReplaceWith(a, b)),
+        (maybe_id(), nodes()).prop_map(|(a, b)| TestOp::ReplaceWithNodes(a, b)),
+        (maybe_id(), maybe_id()).prop_map(|(a, b)| TestOp::InsertAfter(a, b)),
+        (maybe_id(), nodes()).prop_map(|(a, b)| TestOp::InsertNodesAfter(a, b)),
+        (maybe_id(), maybe_id()).prop_map(|(a, b)| TestOp::InsertBefore(a, b)),
+        (maybe_id(), nodes()).prop_map(|(a, b)| TestOp::InsertNodesBefore(a, b)),
+        maybe_id().prop_map(TestOp::Remove),
+        (string(), prop::option::of(id())).prop_map(|(a, b)| TestOp::CreateTextNode(a, b)),
+        (tag(), prop::option::of(id())).prop_map(|(a, b)| TestOp::CreateElement(a, b)),
+        (string(), maybe_id()).prop_map(|(a, b)| TestOp::SetText(a, b)),
+        (attr(), string(), maybe_id()).prop_map(|(a, b, c)| TestOp::SetAttribute(a, b, c)),
+        (attr(), maybe_id()).prop_map(|(a, b)| TestOp::RemoveAttribute(a, b)),
+        (string(), string(), maybe_id()).prop_map(|(a, b, c)| TestOp::SetStyle(a, b, c)),
+        (string(), maybe_id()).prop_map(|(a, b)| TestOp::RemoveStyle(a, b)),
+        (maybe_id(), maybe_id()).prop_map(|(a, b)| TestOp::CloneNode(a, b)),
+        (maybe_id(), id()).prop_map(|(a, b)| TestOp::AdoptIframeDocument(a, b)),
+        (string(), any::<u32>()).prop_map(|(a, b)| TestOp::SetPoolSize(a, b)),
+        any::<u32>().prop_map(TestOp::HintNodeCapacity),
+        (any::<u8>(), string()).prop_map(|(a, b)| TestOp::CacheValue(a, b)),
+        Just(TestOp::PushParent),
+        Just(TestOp::PopParent),
+        Just(TestOp::OpenParent),
+        Just(TestOp::AppendToParent),
+        Just(TestOp::CloseParent),
+        (attr(), any::<u8>(), maybe_id()).prop_map(|(a, b, c)| TestOp::SetAttributeCached(a, b, c)),
+        (string(), any::<u32>(), maybe_id(), any::<bool>())
+            .prop_map(|(a, b, c, d)| TestOp::AddListener(a, b, c, d)),
+        (string(), any::<u32>(), maybe_id()).prop_map(|(a, b, c)| TestOp::RemoveListener(a, b, c)),
+        (string(), maybe_id()).prop_map(|(a, b)| TestOp::SetValue(a, b)),
+        (string(), any::<u32>()).prop_map(|(a, b)| TestOp::SubscribeMediaQuery(a, b)),
+        (string(), any::<u32>()).prop_map(|(a, b)| TestOp::UnsubscribeMediaQuery(a, b)),
+        (storage_area(), string(), any::<u32>())
+            .prop_map(|(a, b, c)| TestOp::GetStorageItem(a, b, c)),
+        (storage_area(), string(), string()).prop_map(|(a, b, c)| TestOp::SetStorageItem(a, b, c)),
+        (storage_area(), string()).prop_map(|(a, b)| TestOp::RemoveStorageItem(a, b)),
+        (string(), any::<u32>()).prop_map(|(a, b)| TestOp::ObserveCustomElement(a, b)),
+        (string(), any::<u32>()).prop_map(|(a, b)| TestOp::UnobserveCustomElement(a, b)),
+        maybe_id().prop_map(TestOp::ShowModal),
+        maybe_id().prop_map(TestOp::CloseDialog),
+        maybe_id().prop_map(TestOp::ShowPopover),
+        maybe_id().prop_map(TestOp::HidePopover),
+        (maybe_id(), any::<u32>()).prop_map(|(a, b)| TestOp::RequestFullscreen(a, b)),
+        Just(TestOp::ExitFullscreen),
+        (maybe_id(), any::<u32>()).prop_map(|(a, b)| TestOp::RequestPointerLock(a, b)),
+        Just(TestOp::ExitPointerLock),
+        any::<u32>().prop_map(TestOp::SubscribeAnimationFrames),
+        any::<u32>().prop_map(TestOp::UnsubscribeAnimationFrames),
+        (maybe_id(), any::<u32>()).prop_map(|(a, b)| TestOp::ObserveCanvasSize(a, b)),
+        maybe_id().prop_map(TestOp::UnobserveCanvasSize),
+        any::<u32>().prop_map(TestOp::ActiveElement),
+        (any::<i32>(), any::<i32>(), any::<u32>())
+            .prop_map(|(a, b, c)| TestOp::ElementFromPoint(a, b, c)),
+        (maybe_id(), any::<u32>()).prop_map(|(a, b)| TestOp::CheckVisibility(a, b)),
+        (any::<bool>(), any::<bool>(), maybe_id())
+            .prop_map(|(a, b, c)| TestOp::SetImageLoading(a, b, c)),
+        (maybe_id(), any::<u32>()).prop_map(|(a, b)| TestOp::DecodeImage(a, b)),
+        (string(), any::<bool>(), maybe_id()).prop_map(|(a, b, c)| TestOp::ToggleClass(a, b, c)),
+        (any::<i32>(), any::<bool>(), maybe_id()).prop_map(|(a, b, c)| TestOp::SetFormattedText(
+            a.to_string(),
+            b,
+            c
+        )),
+        (any::<u32>(), string(), maybe_id()).prop_map(|(handler, text, root)| {
+            let mut batch = Batch::default();
+            batch.with_view_transition(HandlerId(handler), |batch| batch.set_text(text, root));
+            match batch.decode().next() {
+                Some(Ok((_, DecodedOp::ViewTransition { msg, str_buf, .. }))) => {
+                    TestOp::ViewTransition(handler, msg.to_vec(), str_buf.to_vec())
+                }
+                op => unreachable!("{op:?}"),
+            }
+        }),
+        (string(), names(), names(), maybe_id())
+            .prop_map(|(a, b, c, d)| TestOp::SetSanitizedHtml(a, b, c, d)),
+    ]
+}
+
+/// Element or attribute names separated by commas
+fn names() -> impl Strategy<Value = String> {
+    prop::collection::vec("[a-z][a-z0-9-]{0,8}", 0..4).prop_map(|names| names.join(","))
+}
+
+fn ops() -> impl Strategy<Value = Vec<TestOp>> {
+    prop::collection::vec(op(), 0..32)
+}
+
+fn encode(ops: &[TestOp]) -> Batch {
+    let mut batch = Batch::default();
+    for op in ops {
+        op.encode(&mut batch);
+    }
+    batch
+}
+
+fn decode(decoder: Decoder) -> Vec<DecodedOp> {
+    decoder.map(|op| op.unwrap().1).collect()
+}
+
+proptest! {
+    #[test]
+    fn batch_round_trip(ops in ops()) {
+        let batch = encode(&ops);
+        let expected: Vec<_> = ops.iter().map(TestOp::decoded).collect();
+        prop_assert_eq!(decode(batch.decode()), expected);
+    }
+
+    #[test]
+    fn finalized_batch_round_trip(ops in ops()) {
+        let finalized = encode(&ops).finalize();
+        let mut expected: Vec<_> = ops.iter().map(TestOp::decoded).collect();
+        expected.push(DecodedOp::Stop);
+        prop_assert_eq!(decode(Decoder::new(&finalized.msg, &finalized.str)), expected);
+    }
+
+    #[test]
+    fn appended_batch_round_trip(first in ops(), second in ops()) {
+        let mut batch = encode(&first);
+        batch.append(encode(&second));
+        // appending pads the last group of operations with no-ops
+        let decoded: Vec<_> = decode(batch.decode())
+            .into_iter()
+            .filter(|op| *op != DecodedOp::NoOp)
+            .collect();
+        let expected: Vec<_> = first.iter().chain(&second).map(TestOp::decoded).collect();
+        prop_assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn resumed_decoder_round_trip(ops in ops(), split in 0..33usize) {
+        let finalized = encode(&ops).finalize();
+        let mut decoder = Decoder::new(&finalized.msg, &finalized.str);
+        let mut decoded: Vec<_> = decoder.by_ref().take(split).map(|op| op.unwrap().1).collect();
+        decoded.extend(decode(Decoder::resume(&finalized.msg, &finalized.str, decoder.state())));
+        let mut expected: Vec<_> = ops.iter().map(TestOp::decoded).collect();
+        expected.push(DecodedOp::Stop);
+        prop_assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn decoded_ops_reencode_the_same(ops in ops()) {
+        let batch = encode(&ops);
+        let decoded = decode(batch.decode());
+        let mut reencoded = Batch::default();
+        for op in &decoded {
+            reencoded.encode_decoded_op(op);
+        }
+        prop_assert_eq!(decode(reencoded.decode()), decoded);
+    }
+
+    #[test]
+    fn decoded_nodes_reencode_the_same(node in node()) {
+        let bump = Bump::new();
+        let mut batch = Batch::default();
+        match node.builder(&bump) {
+            NodeBuilder::Element(el) => batch.build_full_element(el),
+            NodeBuilder::Text(text) => batch.build_text_node(text),
+        }
+        let mut reencoded = Batch::default();
+        reencoded.build_decoded_node(&node.decoded());
+        match (decode(batch.decode()).pop(), decode(reencoded.decode()).pop()) {
+            (Some(DecodedOp::BuildFullElement(expected)), Some(DecodedOp::BuildFullElement(decoded))) => {
+                prop_assert_eq!(decoded, expected)
+            }
+            // text nodes are built with a create text node operation
+            (Some(DecodedOp::CreateTextNode { text, id }), Some(DecodedOp::BuildFullElement(decoded))) => {
+                prop_assert_eq!(decoded, DecodedNode::Text { text, id })
+            }
+            (expected, decoded) => prop_assert!(false, "{:?} != {:?}", decoded, expected),
+        }
+    }
+
+    #[test]
+    fn decoding_arbitrary_bytes_does_not_panic(msg: Vec<u8>, str_buf: Vec<u8>) {
+        for op in Decoder::new(&msg, &str_buf) {
+            let _ = op;
+        }
+    }
+
+    #[test]
+    fn writes_integers(n: i64, u: u64, i: i8, c: char) {
+        for (written, expected) in [
+            (written(n), n.to_string()),
+            (written(u), u.to_string()),
+            (written(i), i.to_string()),
+            (written(c), c.to_string()),
+        ] {
+            prop_assert_eq!(written, expected);
+        }
+    }
+}
+
+fn written(text: impl WritableText) -> String {
+    let mut buf = Vec::new();
+    text.write_as_text(&mut buf);
+    String::from_utf8(buf).unwrap()
+}
+
+#[test]
+fn writes_integer_edge_cases() {
+    assert_eq!(written(0u32), "0");
+    assert_eq!(written(0i32), "0");
+    assert_eq!(written(i8::MIN), "-128");
+    assert_eq!(written(i128::MIN), i128::MIN.to_string());
+    assert_eq!(written(u128::MAX), u128::MAX.to_string());
+}
+
+#[test]
+fn owned_strings_encode_the_same_as_borrowed_strings() {
+    use std::borrow::Cow;
+
+    let owned = String::from("owned");
+    let cow: Cow<str> = Cow::Owned("cow".to_string());
+    let mut borrowed_batch = Batch::default();
+    borrowed_batch.create_element("div", None);
+    borrowed_batch.set_attribute("data-owned", "owned", MaybeId::LastNode);
+    borrowed_batch.set_attribute(Attribute::class, "cow", MaybeId::LastNode);
+    borrowed_batch.set_style("color", "owned", MaybeId::LastNode);
+    borrowed_batch.create_text_node("owned", None);
+
+    let mut owned_batch = Batch::default();
+    owned_batch.create_element(&String::from("div"), None);
+    owned_batch.set_attribute(&String::from("data-owned"), &owned, MaybeId::LastNode);
+    owned_batch.set_attribute(Attribute::class, cow.clone(), MaybeId::LastNode);
+    owned_batch.set_style(Cow::from("color"), owned.clone(), MaybeId::LastNode);
+    owned_batch.create_text_node(owned, None);
+
+    let (borrowed, owned) = (borrowed_batch.finalize(), owned_batch.finalize());
+    assert_eq!(
+        decode(Decoder::new(&borrowed.msg, &borrowed.str)),
+        decode(Decoder::new(&owned.msg, &owned.str))
+    );
+    assert_eq!(borrowed.str, owned.str);
+    assert_eq!(written(&cow), "cow");
+}
+
+#[test]
+fn owned_builders_encode_the_same_as_borrowed_builders() {
+    let items = ["one", "two"];
+    let mut borrowed = Batch::default();
+    borrowed.build_full_element(
+        ElementBuilder::new(Element::ul.into())
+            .id(NodeId(1))
+            .attrs(&[(AnyAttribute::Attribute(Attribute::class), "items")])
+            .children(&[
+                ElementBuilder::new(Element::li.into())
+                    .children(&[TextBuilder::new("one").into()])
+                    .into(),
+                ElementBuilder::new(Element::li.into())
+                    .children(&[TextBuilder::new("two").into()])
+                    .into(),
+            ]),
+    );
+
+    let mut owned = Batch::default();
+    owned.build_owned_element(
+        OwnedElementBuilder::new(Element::ul.into())
+            .id(NodeId(1))
+            .attrs_iter([(Attribute::class, String::from("items"))])
+            .children_iter(items.iter().map(|item| {
+                OwnedElementBuilder::new(Element::li.into()).children_iter([TextBuilder::new(item)])
+            })),
+    );
+    assert_eq!(decode(owned.decode()), decode(borrowed.decode()));
+
+    let mut converted = Batch::default();
+    converted.build_owned_element(
+        ElementBuilder::new(Element::ul.into())
+            .id(NodeId(1))
+            .attrs(&[(AnyAttribute::Attribute(Attribute::class), "items")])
+            .into_owned()
+            .children_iter(items.iter().map(|item| {
+                ElementBuilder::new(Element::li.into())
+                    .into_owned()
+                    .children_iter([TextBuilder::new(item)])
+            })),
+    );
+    assert_eq!(decode(converted.decode()), decode(borrowed.decode()));
+}
+
+#[test]
+fn str_writer_formats_into_the_last_string() {
+    use std::fmt::Write;
+
+    let mut formatted = Batch::default();
+    formatted.create_element("div", None);
+    formatted.set_style("width", "100px", MaybeId::LastNode);
+    formatted.set_text("count: 3", MaybeId::LastNode);
+
+    let mut written = Batch::default();
+    assert!(write!(written.str_writer(), "nothing to write to").is_err());
+    written.create_element("div", None);
+    written.set_style("width", "", MaybeId::LastNode);
+    write!(written.str_writer(), "{}px", 100).unwrap();
+    let mut appended = Batch::default();
+    appended.set_text("count: ", MaybeId::LastNode);
+    written.append(appended);
+    write!(written.str_writer(), "{}", 3).unwrap();
+
+    let decoded: Vec<_> = decode(written.decode())
+        .into_iter()
+        .filter(|op| *op != DecodedOp::NoOp)
+        .collect();
+    assert_eq!(decoded, decode(formatted.decode()));
+}
+
+/// Elements and attributes are encoded as a single byte, 253, 254, and 255 are reserved to mark elements and attributes encoded as strings or with a namespace.
+#[test]
+fn built_in_discriminants_do_not_overlap_sentinels() {
+    assert!(Element::VARIANTS.len() <= 253);
+    assert!(Attribute::VARIANTS.len() <= 253);
+    for (i, el) in Element::VARIANTS.iter().enumerate() {
+        assert_eq!(*el as usize, i);
+    }
+    for (i, attr) in Attribute::VARIANTS.iter().enumerate() {
+        assert_eq!(*attr as usize, i);
+    }
+}
+
+/// The interpreter decodes single byte elements and attributes with the name tables, so the tables must match the names of the enums.
+#[test]
+fn name_tables_match_the_enums() {
+    let names: Vec<_> = Element::NAMES.split(',').collect();
+    for el in Element::VARIANTS {
+        assert_eq!(names[*el as usize], el.name());
+        assert_eq!(el.name().parse::<Element>().ok(), Some(*el));
+    }
+    let names: Vec<_> = Attribute::NAMES.split(',').collect();
+    for attr in Attribute::VARIANTS {
+        assert_eq!(names[*attr as usize], attr.name());
+        assert_eq!(attr.name().parse::<Attribute>().ok(), Some(*attr));
+    }
+    assert_eq!(Attribute::accept_charset.name(), "accept-charset");
+    assert_eq!(Attribute::r#type.name(), "type");
+    // the disassembler prints the names from the same table
+    #[cfg(feature = "debug")]
+    {
+        let mut batch = Batch::default();
+        batch.create_element(Element::blockquote, None);
+        batch.set_attribute(Attribute::aria_label, "label", MaybeId::LastNode);
+        let disassembled = batch.disassemble();
+        assert!(disassembled.contains("blockquote"), "{disassembled}");
+        assert!(disassembled.contains("aria-label"), "{disassembled}");
+    }
+    // the comma after the last name does not start another name
+    assert!("".parse::<Element>().is_err());
+    assert!("".parse::<Attribute>().is_err());
+    assert!("div,".parse::<Element>().is_err());
+}
@@ -0,0 +1,125 @@
+use std::{fmt::Write, panic::AssertUnwindSafe};
+
+use sledgehammer_encoder::{batch::Batch, decoder::DecodedOp, Attribute, Element, MaybeId, NodeId};
+
+fn ops(batch: &Batch) -> Vec<DecodedOp<'_>> {
+    batch.decode().map(|op| op.unwrap().1).collect()
+}
+
+/// A batch with operations that end in the middle of a group of four operations
+fn started() -> Batch {
+    let mut batch = Batch::default();
+    batch.create_element(Element::div, Some(NodeId(1)));
+    batch.set_attribute(Attribute::class, "row", MaybeId::LastNode);
+    batch
+}
+
+fn render_rest(batch: &mut Batch) {
+    batch.set_text("done", MaybeId::Node(NodeId(1)));
+    batch.append_child(MaybeId::Node(NodeId(0)), MaybeId::Node(NodeId(1)));
+}
+
+#[test]
+fn errors_remove_the_operations_of_the_scope() {
+    let mut batch = started();
+    let result: Result<(), ()> = batch.scope(|batch| {
+        // enough operations to start new groups of operations
+        for i in 0..6 {
+            batch.set_style("width", i, MaybeId::Node(NodeId(1)));
+        }
+        Err(())
+    });
+    assert!(result.is_err());
+    render_rest(&mut batch);
+
+    let mut expected = started();
+    render_rest(&mut expected);
+    assert_eq!(batch.op_count(), expected.op_count());
+    assert_eq!(ops(&batch), ops(&expected));
+    assert_eq!(batch.str_buf, expected.str_buf);
+}
+
+#[test]
+fn panics_remove_the_operations_of_the_scope() {
+    let mut batch = started();
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+        batch.scope(|batch| -> Result<(), ()> {
+            batch.set_text("partial", MaybeId::Node(NodeId(1)));
+            panic!("the row failed to render");
+        })
+    }));
+    assert!(result.is_err());
+    // the last string is the one before the scope again
+    write!(batch.str_writer(), " selected").unwrap();
+    render_rest(&mut batch);
+
+    let mut expected_with_attribute = started();
+    write!(expected_with_attribute.str_writer(), " selected").unwrap();
+    render_rest(&mut expected_with_attribute);
+    assert_eq!(ops(&batch), ops(&expected_with_attribute));
+    assert_eq!(batch.str_buf, expected_with_attribute.str_buf);
+}
+
+#[test]
+fn scopes_that_clear_the_batch_and_fail_leave_an_empty_batch() {
+    let mut batch = started();
+    let result: Result<(), ()> = batch.scope(|batch| {
+        batch.clear();
+        Err(())
+    });
+    assert!(result.is_err());
+    assert_eq!(batch.op_count(), 0);
+    render_rest(&mut batch);
+
+    let mut expected = Batch::default();
+    render_rest(&mut expected);
+    assert_eq!(ops(&batch), ops(&expected));
+    assert_eq!(batch.str_buf, expected.str_buf);
+}
+
+#[test]
+fn scopes_that_replace_the_batch_and_panic_leave_an_empty_batch() {
+    let mut batch = started();
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+        batch.scope(|batch| -> Result<(), ()> {
+            *batch = Batch::default();
+            panic!("the row failed to render");
+        })
+    }));
+    assert!(result.is_err());
+    assert_eq!(batch.op_count(), 0);
+    render_rest(&mut batch);
+
+    let mut expected = Batch::default();
+    render_rest(&mut expected);
+    assert_eq!(ops(&batch), ops(&expected));
+    assert_eq!(batch.str_buf, expected.str_buf);
+}
+
+#[test]
+fn successful_scopes_keep_their_operations() {
+    let mut batch = started();
+    let value = batch.scope(|batch| {
+        render_rest(batch);
+        Ok::<_, ()>(2)
+    });
+    assert_eq!(value, Ok(2));
+
+    let mut expected = started();
+    render_rest(&mut expected);
+    assert_eq!(ops(&batch), ops(&expected));
+
+    // scopes can be nested, and only the failing scope is removed
+    let mut batch = started();
+    let result = batch.scope(|batch| {
+        render_rest(batch);
+        let inner: Result<(), ()> = batch.scope(|batch| {
+            batch.remove(MaybeId::Node(NodeId(1)));
+            Err(())
+        });
+        assert!(inner.is_err());
+        Ok::<_, ()>(())
+    });
+    assert!(result.is_ok());
+    assert_eq!(ops(&batch), ops(&expected));
+}
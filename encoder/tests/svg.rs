@@ -0,0 +1,55 @@
+use sledgehammer_encoder::{
+    batch::Batch,
+    svg::{path, PathData},
+    MaybeId, NodeId,
+};
+
+fn written(build: impl FnOnce(&mut PathData<'_>)) -> String {
+    let mut buf = Vec::new();
+    build(&mut PathData::new(&mut buf));
+    String::from_utf8(buf).unwrap()
+}
+
+#[test]
+fn commands_are_written_in_the_svg_syntax() {
+    assert_eq!(
+        written(|d| {
+            d.move_to(0.0, -1.5)
+                .line_to(10.0, 0.1)
+                .quad_to(1.0, 2.0, 3.0, 4.0)
+                .cubic_to(1.0, 2.0, 3.0, 4.0, 5.0, 6.0)
+                .arc_to(5.0, 5.0, 0.0, true, false, 10.0, 10.0)
+                .close();
+        }),
+        "M0 -1.5L10 0.1Q1 2 3 4C1 2 3 4 5 6A5 5 0 1 0 10 10Z"
+    );
+}
+
+#[test]
+fn precision_rounds_and_trims_numbers() {
+    assert_eq!(
+        written(|d| {
+            d.move_to(1.0 / 3.0, 2.0 / 3.0)
+                .precision(2)
+                .line_to(1.0 / 3.0, 2.5)
+                .line_to(100.0, 0.004)
+                .precision(0)
+                .line_to(1.6, 2.0);
+        }),
+        "M0.3333333333333333 0.6666666666666666L0.33 2.5L100 0L2 2"
+    );
+}
+
+#[test]
+fn paths_are_written_into_the_string_buffer() {
+    let mut batch = Batch::default();
+    batch.set_attribute(
+        "d",
+        path(|d| {
+            d.move_to(0.0, 0.0).line_to(5.0, 5.0);
+        }),
+        MaybeId::Node(NodeId(0)),
+    );
+    batch.set_text("after", MaybeId::Node(NodeId(1)));
+    assert_eq!(batch.str_buf, b"dM0 0L5 5after");
+}
@@ -0,0 +1,41 @@
+use std::thread;
+
+use sledgehammer_encoder::{batch::Batch, Element, ElementBuilder, MaybeId, NodeId};
+
+/// Encode a row of the table with the id of the row
+fn row(batch: &mut Batch, id: u32) {
+    batch.build_full_element(
+        ElementBuilder::new(Element::tr.into())
+            .id(NodeId(id))
+            .children(&[ElementBuilder::new(Element::td.into()).into()]),
+    );
+    batch.set_text(format_args!("row {}", id), MaybeId::LastNode);
+    batch.append_child(MaybeId::Node(NodeId(0)), MaybeId::LastNode);
+}
+
+#[test]
+fn batches_encoded_on_other_threads_can_be_appended() {
+    let mut sequential = Batch::default();
+    for id in 1..=4 {
+        let mut batch = Batch::default();
+        row(&mut batch, id);
+        sequential.append(batch);
+    }
+
+    let handles: Vec<_> = (1..=4)
+        .map(|id| {
+            thread::spawn(move || {
+                let mut batch = Batch::default();
+                row(&mut batch, id);
+                batch
+            })
+        })
+        .collect();
+    let mut parallel = Batch::default();
+    parallel.extend(handles.into_iter().map(|handle| handle.join().unwrap()));
+
+    assert_eq!(parallel.op_count(), sequential.op_count());
+    let (parallel, sequential) = (parallel.finalize(), sequential.finalize());
+    assert_eq!(parallel.msg, sequential.msg);
+    assert_eq!(parallel.str, sequential.str);
+}
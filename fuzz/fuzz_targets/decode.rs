@@ -0,0 +1,14 @@
+//! Decoding and disassembling arbitrary bytes should never panic.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sledgehammer_encoder::{batch::FinalizedBatch, decoder::Decoder};
+
+fuzz_target!(|data: (Vec<u8>, Vec<u8>)| {
+    let (msg, str) = data;
+    for op in Decoder::new(&msg, &str) {
+        let _ = op;
+    }
+    let _ = FinalizedBatch { msg, str }.disassemble();
+});
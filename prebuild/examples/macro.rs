@@ -1,7 +1,7 @@
 use sledgehammer_encoder::*;
 use sledgehammer_prebuild::html;
 
-fn test() {
+fn main() {
     const EL: StaticBatch = html! {
         <tr sledgehammer-id="2">
             <td class="col-md-1">
@@ -20,4 +20,9 @@ fn test() {
             </td>
         </tr>
     };
+    println!(
+        "the row is encoded in {} bytes of operations and {} bytes of strings",
+        EL.msg.len(),
+        EL.str.len()
+    );
 }
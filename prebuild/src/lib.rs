@@ -1,16 +1,19 @@
-use std::{any::Any, convert::TryFrom, str::FromStr, thread::Builder};
+#[cfg(any(feature = "elements", feature = "attributes"))]
+use std::str::FromStr;
 
 use bumpalo::Bump;
 use proc_macro::TokenStream;
 use quote::quote;
+#[cfg(feature = "attributes")]
+use sledgehammer_encoder::Attribute;
+#[cfg(feature = "elements")]
+use sledgehammer_encoder::Element;
 use sledgehammer_encoder::{
-    attribute::AnyAttribute,
-    batch::{Batch, FinalizedBatch},
-    element::AnyElement,
-    Attribute, Element, ElementBuilder, NodeBuilder, NodeId, TextBuilder,
+    attribute::AnyAttribute, batch::Batch, element::AnyElement, ElementBuilder, NodeBuilder,
+    NodeId, TextBuilder,
 };
 use syn::{Expr, Lit};
-use syn_rsx::{parse, Node, NodeType};
+use syn_rsx::{parse, Node};
 
 enum NodeInProgress {
     Element(ElementInProgress),
@@ -51,7 +54,7 @@ fn walk_nodes(nodes: &Vec<Node>, inside: &mut Option<ElementInProgress>) {
                 let key_str = attribute.key.to_string();
                 if let Some(el) = inside {
                     if let Some(val) = &attribute.value {
-                        el.attributes.push((key_str, as_str_lit(&val)))
+                        el.attributes.push((key_str, as_str_lit(val)))
                     }
                 }
             }
@@ -81,20 +84,24 @@ fn as_str_lit(expr: &Expr) -> String {
     panic!("expected string")
 }
 
-/// Converts HTML to `String`.
+/// Encodes static HTML into a [`StaticBatch`](sledgehammer_encoder::batch::StaticBatch) at compile time. `StaticBatch` must be in scope where the macro is used.
 ///
-/// Values returned from braced blocks `{}` are expected to return something
-/// that implements `Display`.
+/// Elements can be assigned an id with the `sledgehammer-id` attribute.
 ///
 /// See [syn-rsx docs](https://docs.rs/syn-rsx/) for supported tags and syntax.
 ///
 /// # Example
 ///
 /// ```
-/// use html_to_string_macro::html;
+/// use sledgehammer_encoder::StaticBatch;
+/// use sledgehammer_prebuild::html;
 ///
-/// let world = "planet";
-/// assert_eq!(html!(<div>"hello "{world}</div>), "<div>hello planet</div>");
+/// const ROW: StaticBatch = html! {
+///     <tr sledgehammer-id="1">
+///         <td class="col-md-1">"hello"</td>
+///     </tr>
+/// };
+/// assert!(!ROW.msg.is_empty());
 /// ```
 #[proc_macro]
 pub fn html(tokens: TokenStream) -> TokenStream {
@@ -137,10 +144,14 @@ pub fn html(tokens: TokenStream) -> TokenStream {
 fn build_in_progress<'a>(allocator: &'a Bump, node: &'a NodeInProgress) -> NodeBuilder<'a> {
     match node {
         NodeInProgress::Element(el) => {
-            let mut builder = ElementBuilder::new(match Element::from_str(&el.kind) {
+            #[cfg(feature = "elements")]
+            let kind = match Element::from_str(&el.kind) {
                 Ok(el) => AnyElement::Element(el),
                 Err(_) => AnyElement::Str(&el.kind),
-            });
+            };
+            #[cfg(not(feature = "elements"))]
+            let kind = AnyElement::Str(&el.kind);
+            let mut builder = ElementBuilder::new(kind);
             let children: Vec<_> = el
                 .children
                 .iter()
@@ -156,13 +167,14 @@ fn build_in_progress<'a>(allocator: &'a Bump, node: &'a NodeInProgress) -> NodeB
                         id = Some(value.parse().unwrap());
                         None
                     } else {
-                        Some((
-                            match Attribute::from_str(attr) {
-                                Ok(a) => AnyAttribute::Attribute(a),
-                                Err(_) => AnyAttribute::Str(attr),
-                            },
-                            &*allocator.alloc_str(value),
-                        ))
+                        #[cfg(feature = "attributes")]
+                        let attr = match Attribute::from_str(attr) {
+                            Ok(a) => AnyAttribute::Attribute(a),
+                            Err(_) => AnyAttribute::Str(attr),
+                        };
+                        #[cfg(not(feature = "attributes"))]
+                        let attr = AnyAttribute::Str(attr);
+                        Some((attr, &*allocator.alloc_str(value)))
                     }
                 })
                 .collect();
@@ -0,0 +1,325 @@
+//! Scenarios from the js-framework-benchmark run against sledgehammer and the equivalent web-sys calls.
+//! Only the time spent in dom operations is measured, not paint time.
+//!
+//! Run it in a browser with `NO_HEADLESS=1 CARGO_TARGET_WASM32_UNKNOWN_UNKNOWN_RUNNER=wasm-bindgen-test-runner cargo bench -p sledgehammer --target wasm32-unknown-unknown` and open the printed address. The results are printed to the page.
+
+#![cfg(target_arch = "wasm32")]
+
+use std::cell::RefCell;
+
+use sledgehammer::{Element, ElementBuilder, MaybeId, MsgChannel, NodeId};
+use wasm_bindgen::JsCast;
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+/// The number of times each scenario is run
+const ITERATIONS: usize = 5;
+
+const TBODY: NodeId = NodeId(0);
+const TEMPLATE: NodeId = NodeId(1);
+/// Rows are stored with the ids `ROWS + 2 * i` and their labels with the ids `ROWS + 2 * i + 1`
+const ROWS: u32 = 2;
+
+const ADJECTIVES: &[&str] = &[
+    "pretty",
+    "large",
+    "big",
+    "small",
+    "tall",
+    "short",
+    "long",
+    "handsome",
+    "plain",
+    "quaint",
+    "clean",
+    "elegant",
+    "easy",
+    "angry",
+    "crazy",
+    "helpful",
+    "mushy",
+    "odd",
+    "unsightly",
+    "adorable",
+    "important",
+    "inexpensive",
+    "cheap",
+    "expensive",
+    "fancy",
+];
+const COLOURS: &[&str] = &[
+    "red", "yellow", "blue", "green", "pink", "brown", "purple", "brown", "white", "black",
+    "orange",
+];
+const NOUNS: &[&str] = &[
+    "table", "chair", "house", "bbq", "desk", "car", "pony", "cookie", "sandwich", "burger",
+    "pizza", "mouse", "keyboard",
+];
+
+thread_local! {
+    static CHANNEL: RefCell<MsgChannel> = RefCell::new(MsgChannel::default());
+}
+
+/// Generates the labels for the rows deterministically so both implementations do the same work
+struct Labels(u32);
+
+impl Labels {
+    fn next(&mut self) -> String {
+        let mut pick = |len: usize| {
+            self.0 = self.0.wrapping_mul(1103515245).wrapping_add(12345);
+            (self.0 >> 16) as usize % len
+        };
+        format!(
+            "{} {} {}",
+            ADJECTIVES[pick(ADJECTIVES.len())],
+            COLOURS[pick(COLOURS.len())],
+            NOUNS[pick(NOUNS.len())]
+        )
+    }
+}
+
+fn document() -> web_sys::Document {
+    web_sys::window().unwrap().document().unwrap()
+}
+
+fn now() -> f64 {
+    web_sys::window().unwrap().performance().unwrap().now()
+}
+
+/// A dom implementation of the benchmark scenarios
+trait Rows {
+    fn create(&mut self, count: usize, labels: &mut Labels);
+    fn update_every_10th(&mut self);
+    fn swap(&mut self);
+    fn clear(&mut self);
+}
+
+/// Renders rows in the form `<tr><td>{id}</td><td><a>{label}</a></td></tr>` using web-sys
+struct WebSysRows {
+    tbody: web_sys::Element,
+    rows: Vec<(web_sys::Element, web_sys::Element)>,
+}
+
+impl WebSysRows {
+    fn new() -> Self {
+        let tbody = document().create_element("tbody").unwrap();
+        document().body().unwrap().append_child(&tbody).unwrap();
+        Self {
+            tbody,
+            rows: Vec::new(),
+        }
+    }
+}
+
+impl Rows for WebSysRows {
+    fn create(&mut self, count: usize, labels: &mut Labels) {
+        let document = document();
+        for i in 0..count {
+            let row = document.create_element("tr").unwrap();
+            let id = document.create_element("td").unwrap();
+            id.set_text_content(Some(&(i + 1).to_string()));
+            let td = document.create_element("td").unwrap();
+            let label = document.create_element("a").unwrap();
+            label.set_text_content(Some(&labels.next()));
+            td.append_child(&label).unwrap();
+            row.append_child(&id).unwrap();
+            row.append_child(&td).unwrap();
+            self.tbody.append_child(&row).unwrap();
+            self.rows.push((row, label));
+        }
+    }
+
+    fn update_every_10th(&mut self) {
+        for (_, label) in self.rows.iter().step_by(10) {
+            let text = label.text_content().unwrap_or_default() + " !!!";
+            label.set_text_content(Some(&text));
+        }
+    }
+
+    fn swap(&mut self) {
+        let (front, _) = &self.rows[1];
+        let (back, _) = &self.rows[998];
+        self.tbody
+            .insert_before(back, Some(&self.rows[2].0))
+            .unwrap();
+        self.tbody
+            .insert_before(front, Some(&self.rows[999].0))
+            .unwrap();
+        self.rows.swap(1, 998);
+    }
+
+    fn clear(&mut self) {
+        self.tbody.set_text_content(None);
+        self.rows.clear();
+    }
+}
+
+/// Renders the same rows as [`WebSysRows`] by cloning a template row with sledgehammer
+struct SledgehammerRows {
+    labels: Vec<String>,
+    order: Vec<u32>,
+}
+
+impl SledgehammerRows {
+    fn new() -> Self {
+        let tbody = document().create_element("tbody").unwrap();
+        document().body().unwrap().append_child(&tbody).unwrap();
+        CHANNEL.with(|c| {
+            let mut channel = c.borrow_mut();
+            channel.set_node(TBODY, tbody.unchecked_into());
+            channel.build_full_element(
+                ElementBuilder::new(Element::tr.into())
+                    .id(TEMPLATE)
+                    .children(&[
+                        ElementBuilder::new(Element::td.into()).into(),
+                        ElementBuilder::new(Element::td.into())
+                            .children(&[ElementBuilder::new(Element::a.into()).into()])
+                            .into(),
+                    ]),
+            );
+            channel.flush().unwrap();
+        });
+        Self {
+            labels: Vec::new(),
+            order: Vec::new(),
+        }
+    }
+}
+
+fn row(i: u32) -> NodeId {
+    NodeId(ROWS + 2 * i)
+}
+
+fn label(i: u32) -> NodeId {
+    NodeId(ROWS + 2 * i + 1)
+}
+
+impl Rows for SledgehammerRows {
+    fn create(&mut self, count: usize, labels: &mut Labels) {
+        CHANNEL.with(|c| {
+            let mut channel = c.borrow_mut();
+            for i in 0..count as u32 {
+                let text = labels.next();
+                channel.clone_node(MaybeId::Node(TEMPLATE), MaybeId::Node(row(i)));
+                channel.first_child();
+                channel.set_text(i + 1, MaybeId::LastNode);
+                channel.next_sibling();
+                channel.first_child();
+                channel.store_with_id(label(i));
+                channel.set_text(text.as_str(), MaybeId::LastNode);
+                channel.append_child(MaybeId::Node(TBODY), MaybeId::Node(row(i)));
+                self.labels.push(text);
+                self.order.push(i);
+            }
+            channel.flush().unwrap();
+        });
+    }
+
+    fn update_every_10th(&mut self) {
+        CHANNEL.with(|c| {
+            let mut channel = c.borrow_mut();
+            for &i in self.order.iter().step_by(10) {
+                let text = &mut self.labels[i as usize];
+                text.push_str(" !!!");
+                channel.set_text(text.as_str(), MaybeId::Node(label(i)));
+            }
+            channel.flush().unwrap();
+        });
+    }
+
+    fn swap(&mut self) {
+        CHANNEL.with(|c| {
+            let mut channel = c.borrow_mut();
+            channel.insert_before(
+                MaybeId::Node(row(self.order[2])),
+                MaybeId::Node(row(self.order[998])),
+            );
+            channel.insert_before(
+                MaybeId::Node(row(self.order[999])),
+                MaybeId::Node(row(self.order[1])),
+            );
+            channel.flush().unwrap();
+        });
+        self.order.swap(1, 998);
+    }
+
+    fn clear(&mut self) {
+        CHANNEL.with(|c| {
+            let mut channel = c.borrow_mut();
+            channel.set_text("", MaybeId::Node(TBODY));
+            channel.flush().unwrap();
+        });
+        self.labels.clear();
+        self.order.clear();
+    }
+}
+
+/// Run a scenario on both implementations and print the mean time of each
+fn bench(name: &str, setup: impl Fn(&mut dyn Rows), run: impl Fn(&mut dyn Rows)) {
+    let mut implementations: [(&str, Box<dyn Rows>); 2] = [
+        ("web-sys", Box::new(WebSysRows::new())),
+        ("sledgehammer", Box::new(SledgehammerRows::new())),
+    ];
+    for (implementation, rows) in &mut implementations {
+        let mut total = 0.0;
+        for _ in 0..ITERATIONS {
+            setup(rows.as_mut());
+            let start = now();
+            run(rows.as_mut());
+            total += now() - start;
+            rows.clear();
+        }
+        console_log!(
+            "{} ({}): {:.2}ms",
+            name,
+            implementation,
+            total / ITERATIONS as f64
+        );
+    }
+}
+
+#[wasm_bindgen_test]
+fn create_1k_rows() {
+    bench(
+        "create 1,000 rows",
+        |_| {},
+        |rows| rows.create(1000, &mut Labels(0)),
+    );
+}
+
+#[wasm_bindgen_test]
+fn create_10k_rows() {
+    bench(
+        "create 10,000 rows",
+        |_| {},
+        |rows| rows.create(10000, &mut Labels(0)),
+    );
+}
+
+#[wasm_bindgen_test]
+fn update_every_10th_row() {
+    bench(
+        "update every 10th row of 1,000 rows",
+        |rows| rows.create(1000, &mut Labels(0)),
+        |rows| rows.update_every_10th(),
+    );
+}
+
+#[wasm_bindgen_test]
+fn swap_rows() {
+    bench(
+        "swap 2 rows of 1,000 rows",
+        |rows| rows.create(1000, &mut Labels(0)),
+        |rows| rows.swap(),
+    );
+}
+
+#[wasm_bindgen_test]
+fn clear_rows() {
+    bench(
+        "clear 1,000 rows",
+        |rows| rows.create(1000, &mut Labels(0)),
+        |rows| rows.clear(),
+    );
+}
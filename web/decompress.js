@@ -0,0 +1,62 @@
+// decompresses batches compressed with FinalizedBatch::to_compressed_bytes from sledgehammer-encoder with the compression feature
+// the format is the format version the batch was encoded with as a byte, the length of the message as a little endian u32, the length of the string buffer as a little endian u32, and then the message followed by the string buffer as one lz4 block
+
+// the version of the binary format the interpreter reads. It must match WIRE_FORMAT_VERSION in sledgehammer-encoder
+const FORMAT_VERSION = 1;
+
+// decompress a single lz4 block into a new array of len bytes
+export function decompress(input, len) {
+    const out = new Uint8Array(len);
+    let pos = 0, outPos = 0, token, length, offset, byte, end;
+    while (pos < input.length) {
+        token = input[pos++];
+        length = token >>> 4;
+        if (length === 15) {
+            do {
+                if (pos >= input.length) throw new Error("the compressed batch ended unexpectedly");
+                byte = input[pos++];
+                length += byte;
+            } while (byte === 255);
+        }
+        end = pos + length;
+        if (end > input.length) throw new Error("the compressed batch ended unexpectedly");
+        if (outPos + length > len) throw new Error("the compressed batch does not match the length in its header");
+        out.set(input.subarray(pos, end), outPos);
+        outPos += length;
+        pos = end;
+        // the last sequence only contains literals
+        if (pos === input.length) break;
+        if (pos + 2 > input.length) throw new Error("the compressed batch ended unexpectedly");
+        offset = input[pos] | (input[pos + 1] << 8);
+        pos += 2;
+        length = token & 0xF;
+        if (length === 15) {
+            do {
+                if (pos >= input.length) throw new Error("the compressed batch ended unexpectedly");
+                byte = input[pos++];
+                length += byte;
+            } while (byte === 255);
+        }
+        length += 4;
+        if (offset === 0 || offset > outPos) throw new Error("the compressed batch refers to data before its start");
+        if (outPos + length > len) throw new Error("the compressed batch does not match the length in its header");
+        // matches can overlap the bytes they copy, so copy one byte at a time
+        for (end = outPos + length; outPos < end; outPos++) {
+            out[outPos] = out[outPos - offset];
+        }
+    }
+    if (outPos !== len) throw new Error("the compressed batch does not match the length in its header");
+    return out;
+}
+
+// decompress a batch into its message and string buffer. They can be applied with JsInterpreter.WorkBytes
+export function decompressBatch(bytes) {
+    if (bytes.length < 9) throw new Error("the compressed batch ended unexpectedly");
+    // reject batches from a server running a different release instead of misreading their operations
+    if (bytes[0] !== FORMAT_VERSION) throw new Error("the compressed batch was encoded with format version " + bytes[0] + " but format version " + FORMAT_VERSION + " was expected");
+    const view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+    const msgLen = view.getUint32(1, true);
+    const strLen = view.getUint32(5, true);
+    const out = decompress(bytes.subarray(9), msgLen + strLen);
+    return { msg: out.subarray(0, msgLen), str: out.subarray(msgLen) };
+}
@@ -0,0 +1,20 @@
+//! Regenerates `interpreter_opt.js`, the interpreter the wasm bindings import, from `interpreter.js`.
+//!
+//! Run it after every change to `interpreter.js`:
+//!
+//! ```sh
+//! cargo run -p sledgehammer --example minify
+//! ```
+//!
+//! The `minified_interpreter` test fails if the minified interpreter is out of date.
+
+// the tests look up the names the variables were renamed to
+#[allow(dead_code)]
+mod minify;
+
+fn main() {
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+    let src = std::fs::read_to_string(dir.join("interpreter.js")).unwrap();
+    let minified = minify::minify(&src);
+    std::fs::write(dir.join("interpreter_opt.js"), minified.code).unwrap();
+}
@@ -0,0 +1,832 @@
+//! A minifier for the javascript interpreter. It removes comments and whitespace, gives every variable declared in the interpreter a short name and shortens the properties of the interpreter that are read for every operation.
+//!
+//! The minifier only understands the subset of javascript the interpreter is written in. It panics on syntax it cannot rename safely, like template literals or object destructuring, instead of producing a broken interpreter.
+
+use std::collections::{HashMap, HashSet};
+
+/// The properties of the interpreter read for every operation with the shorter names they are renamed to. Object literal keys with the same names are renamed as well, so the state saved by idle batches keeps working.
+pub const PROPERTIES: &[(&str, &str)] = &[
+    ("u8BufPos", "u"),
+    ("strPos", "o"),
+    ("strings", "s"),
+    ("lastNode", "l"),
+    ("view", "v"),
+    ("last_start_pos", "lp"),
+    ("last_str_start", "ls"),
+];
+
+const KEYWORDS: &[&str] = &[
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "implements",
+    "import",
+    "in",
+    "instanceof",
+    "interface",
+    "let",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+];
+
+/// Names that are valid variables, but are not used as short names because they have a meaning in some places
+const AVOIDED: &[&str] = &[
+    "arguments",
+    "as",
+    "async",
+    "eval",
+    "from",
+    "get",
+    "of",
+    "set",
+    "undefined",
+    "Infinity",
+    "NaN",
+];
+
+/// The keywords a regular expression can follow
+const BEFORE_EXPRESSION: &[&str] = &[
+    "case",
+    "delete",
+    "do",
+    "else",
+    "in",
+    "instanceof",
+    "new",
+    "of",
+    "return",
+    "throw",
+    "typeof",
+    "void",
+];
+
+const PUNCTUATORS: &[&str] = &[
+    ">>>=", "...", "===", "!==", "**=", "<<=", ">>=", ">>>", "&&=", "||=", "??=", "=>", "==", "!=",
+    "<=", ">=", "&&", "||", "??", "?.", "++", "--", "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=",
+    "<<", ">>", "**",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Ident,
+    Number,
+    String,
+    Regex,
+    Punct,
+}
+
+#[derive(Debug)]
+struct Token<'a> {
+    kind: Kind,
+    text: &'a str,
+    /// If there is a line break between this token and the last token
+    newline: bool,
+}
+
+impl Token<'_> {
+    fn is(&self, text: &str) -> bool {
+        self.kind != Kind::String && self.kind != Kind::Regex && self.text == text
+    }
+
+    fn is_name(&self) -> bool {
+        self.kind == Kind::Ident && !KEYWORDS.contains(&self.text)
+    }
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || c == '$'
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '$'
+}
+
+fn tokenize(src: &str) -> Vec<Token<'_>> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut newline = false;
+    let mut i = 0;
+    while i < src.len() {
+        let rest = &src[i..];
+        let c = rest.chars().next().unwrap();
+        if c.is_whitespace() {
+            newline |= c == '\n';
+            i += c.len_utf8();
+            continue;
+        }
+        if rest.starts_with("//") {
+            i += rest.find('\n').unwrap_or(rest.len());
+            continue;
+        }
+        if rest.starts_with("/*") {
+            let end = rest.find("*/").expect("unterminated comment") + 2;
+            newline |= rest[..end].contains('\n');
+            i += end;
+            continue;
+        }
+        assert!(c != '`', "template literals are not supported");
+        let regex_allowed = match tokens.last() {
+            None => true,
+            Some(last) => match last.kind {
+                Kind::Ident => BEFORE_EXPRESSION.contains(&last.text),
+                Kind::Punct => !matches!(last.text, ")" | "]" | "}"),
+                _ => false,
+            },
+        };
+        let (kind, len) = if c == '"' || c == '\'' {
+            (Kind::String, quoted_len(rest))
+        } else if c == '/' && regex_allowed {
+            (Kind::Regex, regex_len(rest))
+        } else if is_ident_start(c) {
+            (
+                Kind::Ident,
+                rest.find(|c| !is_ident(c)).unwrap_or(rest.len()),
+            )
+        } else if c.is_ascii_digit()
+            || (c == '.' && rest[1..].starts_with(|c: char| c.is_ascii_digit()))
+        {
+            (Kind::Number, number_len(rest))
+        } else {
+            let len = PUNCTUATORS
+                .iter()
+                .find(|punct| rest.starts_with(**punct))
+                .map_or(c.len_utf8(), |punct| punct.len());
+            (Kind::Punct, len)
+        };
+        tokens.push(Token {
+            kind,
+            text: &rest[..len],
+            newline,
+        });
+        newline = false;
+        i += len;
+    }
+    tokens
+}
+
+fn quoted_len(src: &str) -> usize {
+    let quote = src.as_bytes()[0];
+    let mut i = 1;
+    while src.as_bytes()[i] != quote {
+        assert!(src.as_bytes()[i] != b'\n', "unterminated string");
+        i += if src.as_bytes()[i] == b'\\' { 2 } else { 1 };
+    }
+    i + 1
+}
+
+fn regex_len(src: &str) -> usize {
+    let bytes = src.as_bytes();
+    let mut i = 1;
+    let mut class = false;
+    loop {
+        match bytes[i] {
+            b'\\' => i += 1,
+            b'[' => class = true,
+            b']' => class = false,
+            b'/' if !class => break,
+            b'\n' => panic!("unterminated regular expression"),
+            _ => {}
+        }
+        i += 1;
+    }
+    i + 1
+        + src[i + 1..]
+            .find(|c| !is_ident(c))
+            .unwrap_or(src.len() - i - 1)
+}
+
+fn number_len(src: &str) -> usize {
+    let mut len = 0;
+    let bytes = src.as_bytes();
+    while len < bytes.len() {
+        let c = bytes[len];
+        let exponent_sign = (c == b'+' || c == b'-')
+            && len > 0
+            && matches!(bytes[len - 1], b'e' | b'E')
+            && !src.starts_with("0x");
+        if !(c.is_ascii_alphanumeric() || c == b'.' || c == b'_' || exponent_sign) {
+            break;
+        }
+        len += 1;
+    }
+    len
+}
+
+/// The index of the bracket that closes each opening bracket
+fn match_brackets(tokens: &[Token]) -> Vec<usize> {
+    let mut matching = vec![usize::MAX; tokens.len()];
+    let mut open = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if token.kind != Kind::Punct {
+            continue;
+        }
+        match token.text {
+            "(" | "[" | "{" => open.push(i),
+            ")" | "]" | "}" => {
+                let start = open.pop().expect("unbalanced brackets");
+                matching[start] = i;
+                matching[i] = start;
+            }
+            _ => {}
+        }
+    }
+    assert!(open.is_empty(), "unbalanced brackets");
+    matching
+}
+
+struct Scope {
+    parent: Option<usize>,
+    /// The last token in the scope
+    end: usize,
+    function: bool,
+    bindings: HashMap<String, usize>,
+}
+
+struct Binding {
+    name: String,
+    exported: bool,
+    uses: usize,
+}
+
+/// How an identifier token is renamed
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Role {
+    /// A keyword, a property or a name that is not declared in the interpreter
+    Keep,
+    /// A property or an object literal key that is renamed with [`PROPERTIES`]
+    Property,
+    /// A variable resolved in a scope
+    Variable(usize),
+}
+
+struct Analysis<'a, 'b> {
+    tokens: &'b [Token<'a>],
+    matching: Vec<usize>,
+    scopes: Vec<Scope>,
+    bindings: Vec<Binding>,
+    /// The scope each token is in
+    scope_of: Vec<usize>,
+    /// The identifiers that name a binding instead of reading it, with the scope they are declared in
+    declared: HashMap<usize, usize>,
+    /// The identifiers that are not variables
+    not_variables: HashSet<usize>,
+    /// The shorthand properties of object literals, which keep their key if the variable is renamed
+    shorthands: HashSet<usize>,
+    /// The tokens that open the body of a function, catch clause or loop whose scope was already created
+    bodies: HashSet<usize>,
+    /// The opening parenthesis and identifiers that are the parameters of arrow functions, with the last token of the arrow function
+    arrows: HashMap<usize, usize>,
+}
+
+impl<'a, 'b> Analysis<'a, 'b> {
+    fn new(tokens: &'b [Token<'a>]) -> Self {
+        let matching = match_brackets(tokens);
+        let mut analysis = Self {
+            tokens,
+            matching,
+            scopes: vec![Scope {
+                parent: None,
+                end: tokens.len(),
+                function: true,
+                bindings: HashMap::new(),
+            }],
+            bindings: Vec::new(),
+            scope_of: vec![0; tokens.len()],
+            declared: HashMap::new(),
+            not_variables: HashSet::new(),
+            shorthands: HashSet::new(),
+            bodies: HashSet::new(),
+            arrows: HashMap::new(),
+        };
+        analysis.find_arrows();
+        analysis.declare_all();
+        analysis
+    }
+
+    fn is(&self, i: usize, text: &str) -> bool {
+        self.tokens.get(i).is_some_and(|token| token.is(text))
+    }
+
+    fn find_arrows(&mut self) {
+        for i in 0..self.tokens.len() {
+            if !self.is(i, "=>") {
+                continue;
+            }
+            let params = if self.is(i - 1, ")") {
+                self.matching[i - 1]
+            } else {
+                i - 1
+            };
+            let end = if self.is(i + 1, "{") {
+                self.matching[i + 1]
+            } else {
+                // an expression body ends at the first separator outside of brackets
+                let mut j = i + 1;
+                loop {
+                    let token = &self.tokens[j];
+                    if token.kind == Kind::Punct {
+                        match token.text {
+                            "(" | "[" | "{" => j = self.matching[j],
+                            "," | ")" | "]" | "}" | ";" => break j - 1,
+                            _ => {}
+                        }
+                    }
+                    j += 1;
+                }
+            };
+            self.arrows.insert(params, end);
+        }
+    }
+
+    fn push_scope(&mut self, current: usize, end: usize, function: bool) -> usize {
+        self.scopes.push(Scope {
+            parent: Some(current),
+            end,
+            function,
+            bindings: HashMap::new(),
+        });
+        self.scopes.len() - 1
+    }
+
+    fn declare(&mut self, scope: usize, token: usize, exported: bool) {
+        let name = self.tokens[token].text;
+        assert!(
+            self.tokens[token].is_name(),
+            "cannot declare {name:?} as a variable"
+        );
+        let binding = match self.scopes[scope].bindings.get(name) {
+            Some(binding) => *binding,
+            None => {
+                self.bindings.push(Binding {
+                    name: name.to_string(),
+                    exported,
+                    uses: 0,
+                });
+                let binding = self.bindings.len() - 1;
+                self.scopes[scope]
+                    .bindings
+                    .insert(name.to_string(), binding);
+                binding
+            }
+        };
+        self.bindings[binding].exported |= exported;
+        self.declared.insert(token, scope);
+    }
+
+    /// The closest function scope, which `var` declarations belong to
+    fn function_scope(&self, mut scope: usize) -> usize {
+        while !self.scopes[scope].function {
+            scope = self.scopes[scope].parent.unwrap();
+        }
+        scope
+    }
+
+    /// Declare the parameters between the parentheses at `open` in `scope`
+    fn declare_params(&mut self, open: usize, scope: usize) {
+        let close = self.matching[open];
+        let mut i = open + 1;
+        while i < close {
+            if self.is(i, "...") {
+                i += 1;
+            }
+            self.declare(scope, i, false);
+            i += 1;
+            if self.is(i, "=") {
+                i = self.skip_expression(i + 1, close);
+            }
+            if self.is(i, ",") {
+                i += 1;
+            }
+        }
+    }
+
+    /// Skip an expression and return the index of the `,`, `;` or `end` after it
+    fn skip_expression(&self, mut i: usize, end: usize) -> usize {
+        while i < end {
+            let token = &self.tokens[i];
+            if token.kind == Kind::Punct {
+                match token.text {
+                    "(" | "[" | "{" => i = self.matching[i],
+                    "," | ";" => return i,
+                    _ => {}
+                }
+            }
+            i += 1;
+        }
+        end
+    }
+
+    /// Declare the variables of a `let`, `const` or `var` declaration starting at `i` and return the index after the declarations
+    fn declare_variables(&mut self, mut i: usize, scope: usize, exported: bool) -> usize {
+        let end = self.scopes[scope].end;
+        loop {
+            if self.is(i, "[") {
+                let close = self.matching[i];
+                for j in i + 1..close {
+                    if !self.is(j, ",") {
+                        self.declare(scope, j, exported);
+                    }
+                }
+                i = close + 1;
+            } else {
+                assert!(!self.is(i, "{"), "object destructuring is not supported");
+                self.declare(scope, i, exported);
+                i += 1;
+            }
+            if self.is(i, "=") {
+                i = self.skip_expression(i + 1, end);
+            }
+            if !self.is(i, ",") {
+                return i;
+            }
+            i += 1;
+        }
+    }
+
+    fn declare_all(&mut self) {
+        // the kind of each open brace, true for object literals
+        let mut braces: Vec<(usize, bool)> = Vec::new();
+        let mut current = 0;
+        let mut i = 0;
+        while i < self.tokens.len() {
+            while i > self.scopes[current].end {
+                current = self.scopes[current].parent.unwrap();
+            }
+            while braces.last().is_some_and(|(close, _)| i > *close) {
+                braces.pop();
+            }
+            self.scope_of[i] = current;
+            let token = &self.tokens[i];
+            let previous = i.checked_sub(1).map(|i| &self.tokens[i]);
+            let exported = previous.is_some_and(|token| token.is("export"));
+            // the parameters of an arrow function
+            if let Some(end) = self.arrows.get(&i).copied() {
+                let scope = self.push_scope(current, end, true);
+                if token.is("(") {
+                    self.scope_of[i] = scope;
+                    self.declare_params(i, scope);
+                    i = self.matching[i];
+                } else {
+                    self.declare(scope, i, false);
+                }
+                self.scope_of[i] = scope;
+                if self.is(i + 2, "{") {
+                    self.bodies.insert(i + 2);
+                }
+                current = scope;
+                i += 1;
+                continue;
+            }
+            if previous.is_some_and(|token| token.is(".") || token.is("?.")) {
+                // a property, even if it has the name of a keyword like `.catch`
+                self.not_variables.insert(i);
+                i += 1;
+                continue;
+            }
+            match token.kind {
+                Kind::Ident if token.is("function") => {
+                    let mut open = i + 1;
+                    if !self.is(open, "(") {
+                        self.scope_of[open] = current;
+                        self.declare(current, open, exported);
+                        open += 1;
+                    }
+                    let body = self.matching[open] + 1;
+                    assert!(self.is(body, "{"), "expected a function body");
+                    let scope = self.push_scope(current, self.matching[body], true);
+                    self.declare_params(open, scope);
+                    for j in open..self.matching[open] {
+                        self.scope_of[j] = scope;
+                    }
+                    self.bodies.insert(body);
+                    current = scope;
+                    i = self.matching[open];
+                }
+                Kind::Ident if token.is("catch") && self.is(i + 1, "(") => {
+                    let body = self.matching[i + 1] + 1;
+                    let scope = self.push_scope(current, self.matching[body], false);
+                    self.declare_params(i + 1, scope);
+                    for j in i + 1..body {
+                        self.scope_of[j] = scope;
+                    }
+                    self.bodies.insert(body);
+                    current = scope;
+                    i = body - 1;
+                }
+                Kind::Ident if token.is("for") => {
+                    let body = self.matching[i + 1] + 1;
+                    assert!(self.is(body, "{"), "loops without braces are not supported");
+                    current = self.push_scope(current, self.matching[body], false);
+                    self.bodies.insert(body);
+                }
+                Kind::Ident if token.is("let") || token.is("const") || token.is("var") => {
+                    let scope = if token.is("var") {
+                        self.function_scope(current)
+                    } else {
+                        current
+                    };
+                    let end = self.declare_variables(i + 1, scope, exported);
+                    for j in i + 1..end {
+                        self.scope_of[j] = current;
+                    }
+                    // the initializers can contain functions, which are scanned like any other code
+                    i = self.first_initializer(i + 1, end);
+                    continue;
+                }
+                Kind::Ident if token.is("class") => {
+                    self.declare(current, i + 1, exported);
+                    self.scope_of[i + 1] = current;
+                    i += 1;
+                }
+                Kind::Ident if token.is_name() => {
+                    let next = self.tokens.get(i + 1);
+                    let in_object = braces.last().is_some_and(|(_, object)| *object);
+                    let starts_member = previous.is_none_or(|token| {
+                        token.is("{") || token.is("}") || token.is(";") || token.is(",")
+                    });
+                    if starts_member && next.is_some_and(|token| token.is(":")) {
+                        // an object literal key or a label
+                        self.not_variables.insert(i);
+                    } else if previous
+                        .is_some_and(|token| token.is("break") || token.is("continue"))
+                    {
+                        self.not_variables.insert(i);
+                    } else if starts_member
+                        && next.is_some_and(|token| token.is("("))
+                        && self.is(self.matching[i + 1] + 1, "{")
+                    {
+                        // a method of a class or an object literal
+                        self.not_variables.insert(i);
+                        let open = i + 1;
+                        let body = self.matching[open] + 1;
+                        let scope = self.push_scope(current, self.matching[body], true);
+                        self.declare_params(open, scope);
+                        for j in open..body {
+                            self.scope_of[j] = scope;
+                        }
+                        self.bodies.insert(body);
+                        current = scope;
+                        i = body - 1;
+                    } else if in_object
+                        && starts_member
+                        && next.is_some_and(|token| token.is(",") || token.is("}"))
+                    {
+                        self.shorthands.insert(i);
+                    }
+                }
+                Kind::Punct if token.is("{") => {
+                    let object = !self.bodies.contains(&i) && is_object_literal(previous);
+                    braces.push((self.matching[i], object));
+                    if !self.bodies.contains(&i) && !object {
+                        current = self.push_scope(current, self.matching[i], false);
+                        self.scope_of[i] = current;
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+
+    /// The first token of the first initializer in a declaration, or `end` if no variable is initialized
+    fn first_initializer(&self, start: usize, end: usize) -> usize {
+        let mut i = start;
+        while i < end {
+            if self.is(i, "[") {
+                i = self.matching[i];
+            } else if self.is(i, "=") {
+                return i + 1;
+            }
+            i += 1;
+        }
+        end
+    }
+
+    fn resolve(&self, token: usize) -> Option<usize> {
+        let name = self.tokens[token].text;
+        if let Some(scope) = self.declared.get(&token) {
+            return Some(self.scopes[*scope].bindings[name]);
+        }
+        let mut scope = Some(self.scope_of[token]);
+        while let Some(current) = scope {
+            if let Some(binding) = self.scopes[current].bindings.get(name) {
+                return Some(*binding);
+            }
+            scope = self.scopes[current].parent;
+        }
+        None
+    }
+
+    fn roles(&mut self) -> Vec<Role> {
+        let properties: HashMap<&str, &str> = PROPERTIES.iter().copied().collect();
+        let mut roles = vec![Role::Keep; self.tokens.len()];
+        for (i, token) in self.tokens.iter().enumerate() {
+            if !token.is_name() {
+                continue;
+            }
+            if self.not_variables.contains(&i) {
+                if properties.contains_key(token.text) {
+                    roles[i] = Role::Property;
+                }
+                continue;
+            }
+            if let Some(binding) = self.resolve(i) {
+                self.bindings[binding].uses += 1;
+                roles[i] = Role::Variable(binding);
+            }
+        }
+        roles
+    }
+}
+
+/// If a `{` after `previous` starts an object literal instead of a block
+fn is_object_literal(previous: Option<&Token>) -> bool {
+    match previous {
+        None => false,
+        Some(token) => match token.kind {
+            Kind::Punct => !matches!(token.text, ")" | "}" | ";" | "{" | "=>"),
+            Kind::Ident => {
+                BEFORE_EXPRESSION.contains(&token.text)
+                    && token.text != "else"
+                    && token.text != "do"
+            }
+            _ => false,
+        },
+    }
+}
+
+/// The short names in order: `a` to `Z`, then `aa`, `ab`, ...
+fn short_names() -> impl Iterator<Item = String> {
+    const FIRST: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    const REST: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+    let single = FIRST.chars().map(String::from);
+    let double = FIRST
+        .chars()
+        .flat_map(|first| REST.chars().map(move |second| format!("{first}{second}")));
+    single.chain(double)
+}
+
+/// The minified interpreter with the names the variables and properties were renamed to
+pub struct Minified {
+    pub code: String,
+    names: HashMap<String, String>,
+}
+
+impl Minified {
+    /// The name a variable declared in the top level of the interpreter or a property in [`PROPERTIES`] was renamed to
+    pub fn name<'a>(&'a self, original: &'a str) -> &'a str {
+        self.names.get(original).map_or(original, String::as_str)
+    }
+}
+
+/// Minify the source of the interpreter
+pub fn minify(src: &str) -> Minified {
+    let tokens = tokenize(src);
+    let mut analysis = Analysis::new(&tokens);
+    let roles = analysis.roles();
+
+    for (original, short) in PROPERTIES {
+        assert!(
+            !tokens
+                .iter()
+                .enumerate()
+                .any(|(i, token)| token.is(short) && analysis.not_variables.contains(&i)),
+            "the property {original} cannot be renamed to {short} because {short} is already a property"
+        );
+        assert!(
+            !tokens.iter().any(|token| token.kind == Kind::String
+                && token.text[1..token.text.len() - 1] == **original),
+            "the property {original} is read by name"
+        );
+    }
+
+    // names that are not renamed are kept, so the short names cannot shadow them
+    let mut taken: HashSet<&str> = KEYWORDS.iter().chain(AVOIDED).copied().collect();
+    for (i, token) in tokens.iter().enumerate() {
+        if token.kind == Kind::Ident
+            && roles[i] == Role::Keep
+            && !analysis.not_variables.contains(&i)
+        {
+            taken.insert(token.text);
+        }
+    }
+    for binding in analysis.bindings.iter().filter(|binding| binding.exported) {
+        taken.insert(&binding.name);
+    }
+    // the most used variables get the shortest names
+    let mut order: Vec<usize> = (0..analysis.bindings.len())
+        .filter(|binding| !analysis.bindings[*binding].exported)
+        .collect();
+    order.sort_by_key(|binding| std::cmp::Reverse(analysis.bindings[*binding].uses));
+    let mut short = short_names().filter(|name| !taken.contains(name.as_str()));
+    let mut renamed: Vec<Option<String>> = vec![None; analysis.bindings.len()];
+    for binding in order {
+        renamed[binding] = short.next();
+    }
+
+    let mut names: HashMap<String, String> = PROPERTIES
+        .iter()
+        .map(|(original, short)| (original.to_string(), short.to_string()))
+        .collect();
+    for (name, binding) in &analysis.scopes[0].bindings {
+        if let Some(renamed) = &renamed[*binding] {
+            names.insert(name.clone(), renamed.clone());
+        }
+    }
+
+    let properties: HashMap<&str, &str> = PROPERTIES.iter().copied().collect();
+    let mut code = String::new();
+    let mut last: Option<&Token> = None;
+    for (i, token) in tokens.iter().enumerate() {
+        let mut text = match roles[i] {
+            Role::Keep => token.text.to_string(),
+            Role::Property => properties[token.text].to_string(),
+            Role::Variable(binding) => renamed[binding]
+                .as_deref()
+                .unwrap_or(token.text)
+                .to_string(),
+        };
+        if analysis.shorthands.contains(&i) {
+            let key = properties.get(token.text).copied().unwrap_or(token.text);
+            if key != text {
+                text = format!("{key}:{text}");
+            }
+        }
+        if let Some(last) = last {
+            code.push_str(separator(last, token, &text, &code));
+        }
+        code.push_str(&text);
+        last = Some(token);
+    }
+    Minified { code, names }
+}
+
+/// The whitespace needed between the last token and the next token
+fn separator(last: &Token, next: &Token, text: &str, code: &str) -> &'static str {
+    let word = |token: &Token| matches!(token.kind, Kind::Ident | Kind::Number);
+    // keep line breaks that could end a statement without a semicolon
+    let ends_expression = matches!(
+        last.kind,
+        Kind::Ident | Kind::Number | Kind::String | Kind::Regex
+    ) && (!KEYWORDS.contains(&last.text)
+        || matches!(last.text, "this" | "true" | "false" | "null"))
+        || matches!(last.text, ")" | "]" | "++" | "--") && last.kind == Kind::Punct;
+    let starts_expression = matches!(
+        next.kind,
+        Kind::Ident | Kind::Number | Kind::String | Kind::Regex
+    ) && !KEYWORDS.contains(&next.text)
+        || matches!(next.text, "(" | "[" | "++" | "--" | "+" | "-" | "/")
+            && next.kind == Kind::Punct;
+    if next.newline && ends_expression && starts_expression {
+        return "\n";
+    }
+    if word(last) && (word(next) || next.kind == Kind::Regex)
+        || word(last) && next.is(".") && last.kind == Kind::Number
+    {
+        return " ";
+    }
+    // `a + +b` and `a - -b`
+    let last_char = code.chars().last();
+    let next_char = text.chars().next();
+    if matches!(
+        (last_char, next_char),
+        (Some('+'), Some('+')) | (Some('-'), Some('-')) | (Some('/'), Some('/'))
+    ) {
+        return " ";
+    }
+    ""
+}
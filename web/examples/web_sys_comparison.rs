@@ -1,7 +1,15 @@
-use sledgehammer::{channel::MaybeId, *};
-use wasm_bindgen::JsCast;
+//! Create the same elements with web-sys and sledgehammer. This example must be run in the browser.
 
+#[cfg(not(target_arch = "wasm32"))]
 fn main() {
+    eprintln!("the web_sys_comparison example only runs on wasm32-unknown-unknown");
+}
+
+#[cfg(target_arch = "wasm32")]
+fn main() {
+    use sledgehammer::{sledgehammer_encoder::WithNsExt, *};
+    use wasm_bindgen::JsCast;
+
     let window = web_sys::window().unwrap();
     let document = window.document().unwrap();
     let body = document.body().unwrap();
@@ -50,13 +58,10 @@ fn main() {
     channel.set_text("Hello from sledehammer!", MaybeId::Node(NodeId(2)));
 
     // append the new node to the body
-    channel.append_child(
-        MaybeId::Node(NodeId(0)),
-        sledgehammer::channel::MaybeId::Node(NodeId(1)),
-    );
+    channel.append_child(MaybeId::Node(NodeId(0)), MaybeId::Node(NodeId(1)));
 
     // execute the queued operations
-    channel.flush();
+    channel.flush().unwrap();
 
     // we can also get web-sys nodes out of sledgehammer
     let element = channel.get_node(NodeId(2));
@@ -1,11 +1,24 @@
-let op, len, ns, attr, i, j, value, element, ptr, pos, end, out, char, numAttributes, endRounded, inptr, buffer, metadata, parent, numNodes, children, node, name, id, nodes;
+let op, len, ns, attr, i, j, value, element, ptr, pos, end, out, char, numAttributes, endRounded, inptr, metadata, parent, numNodes, children, node, name, id, nodes, els, attrs;
+
+// the version of the binary format this interpreter reads. It must match WIRE_FORMAT_VERSION in sledgehammer-encoder
+export const FORMAT_VERSION = 1;
+
+const HTML_NS = "http://www.w3.org/1999/xhtml";
 
 export function work_last_created() {
     inptr.Work();
 }
 
-export function update_last_memory(mem) {
-    inptr.UpdateMemory(mem);
+export function work_last_created_checked() {
+    return inptr.WorkChecked();
+}
+
+export function work_last_created_profiled() {
+    return inptr.WorkProfiled();
+}
+
+export function work_last_created_idle(deadline, resume) {
+    return inptr.WorkIdle(deadline, resume);
 }
 
 function exOp() {
@@ -21,7 +34,7 @@ function exOp() {
         case 1:
             inptr.lastNode = inptr.lastNode.nextSibling;
             break;
-        // parent
+        // parent node
         case 2:
             inptr.lastNode = inptr.lastNode.parentNode;
             break;
@@ -38,7 +51,7 @@ function exOp() {
         // stop
         case 5:
             return true;
-        // create full element
+        // build full element
         case 6:
             inptr.lastNode = inptr.createFullElement();
             break;
@@ -96,6 +109,9 @@ function exOp() {
                     parent.replaceWith(inptr.lastNode);
                 }
             }
+            if (inptr.pools.size > 0) {
+                inptr.recycle(parent);
+            }
             break;
         // insert after
         case 9:
@@ -171,16 +187,20 @@ function exOp() {
         case 11:
             // the first bool is encoded as op & (1 << 5)
             if (op & 0x20) {
-                inptr.nodes[inptr.view.getUint32(inptr.u8BufPos, true)].remove();
+                node = inptr.nodes[inptr.view.getUint32(inptr.u8BufPos, true)];
                 inptr.u8BufPos += 4;
             }
             else {
-                inptr.lastNode.remove();
+                node = inptr.lastNode;
+            }
+            node.remove();
+            if (inptr.pools.size > 0) {
+                inptr.recycle(node);
             }
             break;
         // create text node
         case 12:
-            inptr.lastNode = document.createTextNode(inptr.strings.substring(inptr.strPos, inptr.strPos += inptr.view.getUint16(inptr.u8BufPos, true)));
+            inptr.lastNode = inptr.createTextNode(inptr.strings.substring(inptr.strPos, inptr.strPos += inptr.view.getUint16(inptr.u8BufPos, true)));
             inptr.u8BufPos += 2;
             // the first bool is encoded as op & (1 << 5)
             if (op & 0x20) {
@@ -245,8 +265,8 @@ function exOp() {
                 // the third bool is encoded as op & (1 << 7)
                 // second bool encodes if the attribute has a namespace
                 if (op & 0x80) {
-                    ns = inptr.strings.substring(inptr.strPos, inptr.strPos += i & 0xFFFF);
-                    node.setAttributeNS(ns, attrs[(i & 0xFF0000) >>> 16], inptr.strings.substring(inptr.strPos, inptr.strPos += inptr.view.getUint16(inptr.u8BufPos, true)));
+                    ns = inptr.strings.substring(inptr.strPos, inptr.strPos += (i & 0xFFFF00) >>> 8);
+                    node.setAttributeNS(ns, attrs[i & 0xFF], inptr.strings.substring(inptr.strPos, inptr.strPos += inptr.view.getUint16(inptr.u8BufPos, true)));
                     inptr.u8BufPos += 2;
                 }
                 else {
@@ -336,15 +356,528 @@ function exOp() {
                 inptr.u8BufPos += 4;
             }
             break;
-        default:
+        // no op
+        case 20:
+            break;
+        // adopt iframe document
+        case 21:
+            // the first bool is encoded as op & (1 << 5)
+            if (op & 0x20) {
+                node = inptr.nodes[inptr.view.getUint32(inptr.u8BufPos, true)];
+                inptr.u8BufPos += 4;
+            }
+            else {
+                node = inptr.lastNode;
+            }
+            inptr.nodes[inptr.view.getUint32(inptr.u8BufPos, true)] = node.contentDocument.body;
+            inptr.u8BufPos += 4;
+            break;
+        // set pool size
+        case 22:
+            name = inptr.strings.substring(inptr.strPos, inptr.strPos += inptr.view.getUint16(inptr.u8BufPos, true));
+            inptr.u8BufPos += 2;
+            inptr.setPoolSize(name, inptr.view.getUint32(inptr.u8BufPos, true));
+            inptr.u8BufPos += 4;
+            break;
+        // hint node capacity
+        case 23:
+            inptr.hintNodeCapacity(inptr.view.getUint32(inptr.u8BufPos, true));
+            inptr.u8BufPos += 4;
+            break;
+        // cache value
+        case 24:
+            id = inptr.view.getUint8(inptr.u8BufPos++);
+            inptr.cachedValues[id] = inptr.strings.substring(inptr.strPos, inptr.strPos += inptr.view.getUint16(inptr.u8BufPos, true));
+            inptr.u8BufPos += 2;
+            break;
+        // set attribute cached
+        case 25:
+            // the first bool is encoded as op & (1 << 5)
+            if (op & 0x20) {
+                node = inptr.nodes[inptr.view.getUint32(inptr.u8BufPos, true)];
+                inptr.u8BufPos += 4;
+            }
+            else {
+                node = inptr.lastNode;
+            }
+            // the attribute is encoded the same way as in remove attribute
+            // the second bool is encoded as op & (1 << 6)
+            if (op & 0x40) {
+                // the third bool is encoded as op & (1 << 7)
+                if (op & 0x80) {
+                    i = inptr.view.getUint32(inptr.u8BufPos, true);
+                    inptr.u8BufPos += 4;
+                    attr = inptr.strings.substring(inptr.strPos, inptr.strPos += i & 0xFFFF);
+                    ns = inptr.strings.substring(inptr.strPos, inptr.strPos += (i & 0xFFFF0000) >>> 16);
+                }
+                else {
+                    attr = inptr.strings.substring(inptr.strPos, inptr.strPos += inptr.view.getUint16(inptr.u8BufPos, true));
+                    inptr.u8BufPos += 2;
+                    ns = null;
+                }
+            }
+            else {
+                // the third bool is encoded as op & (1 << 7)
+                if (op & 0x80) {
+                    i = inptr.view.getUint32(inptr.u8BufPos, true);
+                    // we only read 3 bytes out of the 4
+                    inptr.u8BufPos += 3;
+                    attr = attrs[i & 0xFF];
+                    ns = inptr.strings.substring(inptr.strPos, inptr.strPos += (i & 0xFFFF00) >>> 8);
+                }
+                else {
+                    attr = attrs[inptr.view.getUint8(inptr.u8BufPos++)];
+                    ns = null;
+                }
+            }
+            value = inptr.cachedValues[inptr.view.getUint8(inptr.u8BufPos++)];
+            if (ns === null) {
+                node.setAttribute(attr, value);
+            }
+            else {
+                node.setAttributeNS(ns, attr, value);
+            }
+            break;
+        // parent stack
+        case 26:
+            // the first bool is encoded as op & (1 << 5)
+            if (op & 0x20) {
+                inptr.parents.push(inptr.lastNode);
+            }
+            else {
+                node = inptr.parents.pop();
+                node.appendChild(inptr.lastNode);
+                inptr.lastNode = node;
+            }
+            break;
+        // listener
+        case 27:
+            // the first bool is encoded as op & (1 << 5)
+            if (op & 0x20) {
+                node = inptr.nodes[inptr.view.getUint32(inptr.u8BufPos, true)];
+                inptr.u8BufPos += 4;
+            }
+            else {
+                node = inptr.lastNode;
+            }
+            id = inptr.view.getUint32(inptr.u8BufPos, true);
+            inptr.u8BufPos += 4;
+            name = inptr.strings.substring(inptr.strPos, inptr.strPos += inptr.view.getUint16(inptr.u8BufPos, true));
+            inptr.u8BufPos += 2;
+            // the second bool is encoded as op & (1 << 6)
+            if (op & 0x40) {
+                // the third bool is encoded as op & (1 << 7)
+                inptr.addListener(node, name, id, (op & 0x80) !== 0);
+            }
+            else {
+                inptr.removeListener(node, name, id);
+            }
+            break;
+        // set value
+        case 28:
+            // the first bool is encoded as op & (1 << 5)
+            if (op & 0x20) {
+                node = inptr.nodes[inptr.view.getUint32(inptr.u8BufPos, true)];
+                inptr.u8BufPos += 4;
+            }
+            else {
+                node = inptr.lastNode;
+            }
+            inptr.setValue(node, inptr.strings.substring(inptr.strPos, inptr.strPos += inptr.view.getUint16(inptr.u8BufPos, true)));
+            inptr.u8BufPos += 2;
+            break;
+        // extended operations, selected by the next byte
+        case 29:
+            switch (inptr.view.getUint8(inptr.u8BufPos++)) {
+                // media query
+                case 0:
+                    id = inptr.view.getUint32(inptr.u8BufPos, true);
+                    inptr.u8BufPos += 4;
+                    name = inptr.strings.substring(inptr.strPos, inptr.strPos += inptr.view.getUint16(inptr.u8BufPos, true));
+                    inptr.u8BufPos += 2;
+                    // the first bool is encoded as op & (1 << 5)
+                    if (op & 0x20) {
+                        inptr.subscribeMediaQuery(name, id);
+                    }
+                    else {
+                        inptr.unsubscribeMediaQuery(name, id);
+                    }
+                    break;
+                // custom element lifecycle
+                case 4:
+                    id = inptr.view.getUint32(inptr.u8BufPos, true);
+                    inptr.u8BufPos += 4;
+                    name = inptr.strings.substring(inptr.strPos, inptr.strPos += inptr.view.getUint16(inptr.u8BufPos, true));
+                    inptr.u8BufPos += 2;
+                    // the first bool is encoded as op & (1 << 5)
+                    if (op & 0x20) {
+                        inptr.observeCustomElement(name, id);
+                    }
+                    else {
+                        inptr.unobserveCustomElement(name, id);
+                    }
+                    break;
+                // top layer
+                case 5:
+                    // the first bool is encoded as op & (1 << 5)
+                    if (op & 0x20) {
+                        node = inptr.nodes[inptr.view.getUint32(inptr.u8BufPos, true)];
+                        inptr.u8BufPos += 4;
+                    }
+                    else {
+                        node = inptr.lastNode;
+                    }
+                    // the second bool is encoded as op & (1 << 6) and the third as op & (1 << 7)
+                    if (op & 0x40) {
+                        if (op & 0x80) {
+                            node.showPopover();
+                        }
+                        else {
+                            node.hidePopover();
+                        }
+                    }
+                    else {
+                        if (op & 0x80) {
+                            node.showModal();
+                        }
+                        else {
+                            node.close();
+                        }
+                    }
+                    break;
+                // fullscreen
+                case 6:
+                // pointer lock
+                case 7:
+                    id = inptr.view.getUint8(inptr.u8BufPos - 1) - 6;
+                    // the first bool is encoded as op & (1 << 5)
+                    if (op & 0x20) {
+                        // the second bool is encoded as op & (1 << 6)
+                        if (op & 0x40) {
+                            node = inptr.nodes[inptr.view.getUint32(inptr.u8BufPos, true)];
+                            inptr.u8BufPos += 4;
+                        }
+                        else {
+                            node = inptr.lastNode;
+                        }
+                        inptr.routeLockEvents(id, inptr.view.getUint32(inptr.u8BufPos, true));
+                        inptr.u8BufPos += 4;
+                        // the promises reject when the error event is dispatched, which the handler receives
+                        value = id === 0 ? node.requestFullscreen() : node.requestPointerLock();
+                        if (value instanceof Promise) {
+                            value.catch(() => { });
+                        }
+                    }
+                    else if (id === 0) {
+                        if (document.fullscreenElement !== null) {
+                            document.exitFullscreen();
+                        }
+                    }
+                    else {
+                        document.exitPointerLock();
+                    }
+                    break;
+                // get storage item
+                case 1:
+                    id = inptr.view.getUint32(inptr.u8BufPos, true);
+                    inptr.u8BufPos += 4;
+                    name = inptr.strings.substring(inptr.strPos, inptr.strPos += inptr.view.getUint16(inptr.u8BufPos, true));
+                    inptr.u8BufPos += 2;
+                    // the first bool is encoded as op & (1 << 5)
+                    inptr.answer(id, (op & 0x20 ? sessionStorage : localStorage).getItem(name));
+                    break;
+                // set storage item
+                case 2:
+                    name = inptr.strings.substring(inptr.strPos, inptr.strPos += inptr.view.getUint16(inptr.u8BufPos, true));
+                    inptr.u8BufPos += 2;
+                    value = inptr.strings.substring(inptr.strPos, inptr.strPos += inptr.view.getUint16(inptr.u8BufPos, true));
+                    inptr.u8BufPos += 2;
+                    // the first bool is encoded as op & (1 << 5)
+                    (op & 0x20 ? sessionStorage : localStorage).setItem(name, value);
+                    break;
+                // remove storage item
+                case 3:
+                    name = inptr.strings.substring(inptr.strPos, inptr.strPos += inptr.view.getUint16(inptr.u8BufPos, true));
+                    inptr.u8BufPos += 2;
+                    // the first bool is encoded as op & (1 << 5)
+                    (op & 0x20 ? sessionStorage : localStorage).removeItem(name);
+                    break;
+                // animation frames
+                case 8:
+                    id = inptr.view.getUint32(inptr.u8BufPos, true);
+                    inptr.u8BufPos += 4;
+                    // the first bool is encoded as op & (1 << 5)
+                    if (op & 0x20) {
+                        inptr.subscribeAnimationFrames(id);
+                    }
+                    else {
+                        inptr.unsubscribeAnimationFrames(id);
+                    }
+                    break;
+                // canvas size
+                case 9:
+                    // the second bool is encoded as op & (1 << 6)
+                    if (op & 0x40) {
+                        id = inptr.view.getUint32(inptr.u8BufPos, true);
+                        node = inptr.nodes[id];
+                        inptr.u8BufPos += 4;
+                    }
+                    else {
+                        id = -1;
+                        node = inptr.lastNode;
+                    }
+                    // the first bool is encoded as op & (1 << 5)
+                    if (op & 0x20) {
+                        inptr.observeCanvasSize(node, id, inptr.view.getUint32(inptr.u8BufPos, true));
+                        inptr.u8BufPos += 4;
+                    }
+                    else {
+                        inptr.unobserveCanvasSize(node);
+                    }
+                    break;
+                // active element
+                case 10:
+                    id = inptr.view.getUint32(inptr.u8BufPos, true);
+                    inptr.u8BufPos += 4;
+                    inptr.answerNode(id, document.activeElement);
+                    break;
+                // element from point
+                case 11:
+                    id = inptr.view.getUint32(inptr.u8BufPos, true);
+                    inptr.answerNode(id, document.elementFromPoint(inptr.view.getInt32(inptr.u8BufPos + 4, true), inptr.view.getInt32(inptr.u8BufPos + 8, true)));
+                    inptr.u8BufPos += 12;
+                    break;
+                // check visibility
+                case 12:
+                    // the first bool is encoded as op & (1 << 5)
+                    if (op & 0x20) {
+                        node = inptr.nodes[inptr.view.getUint32(inptr.u8BufPos, true)];
+                        inptr.u8BufPos += 4;
+                    }
+                    else {
+                        node = inptr.lastNode;
+                    }
+                    id = inptr.view.getUint32(inptr.u8BufPos, true);
+                    inptr.u8BufPos += 4;
+                    // browsers without checkVisibility fall back to checking if the element has a box
+                    inptr.answer(id, String(node.checkVisibility ? node.checkVisibility() : node.getClientRects().length > 0));
+                    break;
+                // toggle class
+                case 13:
+                    // the second bool is encoded as op & (1 << 6)
+                    if (op & 0x40) {
+                        node = inptr.nodes[inptr.view.getUint32(inptr.u8BufPos, true)];
+                        inptr.u8BufPos += 4;
+                    }
+                    else {
+                        node = inptr.lastNode;
+                    }
+                    // the first bool is encoded as op & (1 << 5)
+                    node.classList.toggle(inptr.strings.substring(inptr.strPos, inptr.strPos += inptr.view.getUint16(inptr.u8BufPos, true)), (op & 0x20) !== 0);
+                    inptr.u8BufPos += 2;
+                    break;
+                // set formatted text
+                case 14:
+                    // the first bool is encoded as op & (1 << 5)
+                    if (op & 0x20) {
+                        node = inptr.nodes[inptr.view.getUint32(inptr.u8BufPos, true)];
+                        inptr.u8BufPos += 4;
+                    }
+                    else {
+                        node = inptr.lastNode;
+                    }
+                    value = inptr.strings.substring(inptr.strPos, inptr.strPos += inptr.view.getUint16(inptr.u8BufPos, true));
+                    inptr.u8BufPos += 2;
+                    ns = inptr.strings.substring(inptr.strPos, inptr.strPos += inptr.view.getUint16(inptr.u8BufPos, true));
+                    inptr.u8BufPos += 2;
+                    // the second bool is encoded as op & (1 << 6)
+                    node.textContent = inptr.format((op & 0x40) !== 0, ns, inptr.strings.substring(inptr.strPos, inptr.strPos += inptr.view.getUint16(inptr.u8BufPos, true)))(Number(value));
+                    inptr.u8BufPos += 2;
+                    break;
+                // view transition
+                case 15:
+                    id = inptr.view.getUint32(inptr.u8BufPos, true);
+                    inptr.u8BufPos += 4;
+                    // the nested batch is applied after this batch, so it is copied out of the memory rust reuses
+                    len = inptr.view.getUint32(inptr.u8BufPos, true);
+                    inptr.u8BufPos += 4;
+                    value = new Uint8Array(inptr.view.buffer, inptr.view.byteOffset + inptr.u8BufPos, len).slice();
+                    inptr.u8BufPos += len;
+                    len = inptr.view.getUint32(inptr.u8BufPos, true);
+                    inptr.u8BufPos += 4;
+                    inptr.startViewTransition(id, value, new Uint8Array(inptr.view.buffer, inptr.view.byteOffset + inptr.u8BufPos, len).slice());
+                    inptr.u8BufPos += len;
+                    break;
+                // stream tree
+                case 16:
+                    // the parents stay on the stack between batches, so a tree can be applied over several batches
+                    if (op & 0x20) {
+                        inptr.parents[inptr.parents.length - 1].appendChild(inptr.lastNode);
+                        if (op & 0x40) {
+                            inptr.parents.push(inptr.lastNode);
+                        }
+                    }
+                    else {
+                        inptr.lastNode = inptr.parents.pop();
+                    }
+                    break;
+                // set sanitized html
+                case 17:
+                    // the first bool is encoded as op & (1 << 5)
+                    if (op & 0x20) {
+                        node = inptr.nodes[inptr.view.getUint32(inptr.u8BufPos, true)];
+                        inptr.u8BufPos += 4;
+                    }
+                    else {
+                        node = inptr.lastNode;
+                    }
+                    value = inptr.strings.substring(inptr.strPos, inptr.strPos += inptr.view.getUint16(inptr.u8BufPos, true));
+                    inptr.u8BufPos += 2;
+                    name = inptr.strings.substring(inptr.strPos, inptr.strPos += inptr.view.getUint16(inptr.u8BufPos, true));
+                    inptr.u8BufPos += 2;
+                    inptr.setSanitizedHtml(node, value, name, inptr.strings.substring(inptr.strPos, inptr.strPos += inptr.view.getUint16(inptr.u8BufPos, true)));
+                    inptr.u8BufPos += 2;
+                    break;
+                // set image loading
+                case 18:
+                    // the third bool is encoded as op & (1 << 7)
+                    if (op & 0x80) {
+                        node = inptr.nodes[inptr.view.getUint32(inptr.u8BufPos, true)];
+                        inptr.u8BufPos += 4;
+                    }
+                    else {
+                        node = inptr.lastNode;
+                    }
+                    // the first bool is encoded as op & (1 << 5) and the second bool as op & (1 << 6)
+                    node.setAttribute("loading", op & 0x20 ? "lazy" : "eager");
+                    node.setAttribute("decoding", op & 0x40 ? "async" : "auto");
+                    break;
+                // decode image
+                case 19:
+                    // the first bool is encoded as op & (1 << 5)
+                    if (op & 0x20) {
+                        node = inptr.nodes[inptr.view.getUint32(inptr.u8BufPos, true)];
+                        inptr.u8BufPos += 4;
+                    }
+                    else {
+                        node = inptr.lastNode;
+                    }
+                    id = inptr.view.getUint32(inptr.u8BufPos, true);
+                    inptr.u8BufPos += 4;
+                    inptr.decodeImage(node, id);
+                    break;
+                default:
+                    // the data of an unknown operation cannot be skipped, so the rest of the batch would be misread
+                    throw new Error("unknown extended operation " + inptr.view.getUint8(inptr.u8BufPos - 1));
+            }
             break;
+        default:
+            throw new Error("unknown operation " + (op & 0x1F));
+    }
+}
+
+// the codes of the pointer types Event::decode reads in rust. Other pointer types are encoded as 3
+const POINTER_TYPES = new Map([["mouse", 0], ["pen", 1], ["touch", 2]]);
+
+// the change and error events of fullscreen and pointer lock on the document
+const LOCK_EVENTS = [
+    ["fullscreenchange", "fullscreenerror"],
+    ["pointerlockchange", "pointerlockerror"],
+];
+
+// the lifecycle callbacks of custom elements and the events they are forwarded as
+const LIFECYCLE_CALLBACKS = [
+    ["connectedCallback", "connected"],
+    ["disconnectedCallback", "disconnected"],
+    ["attributeChangedCallback", "attributechanged"],
+];
+
+// the event dispatched to the handlers subscribed to animation frames with the timestamp passed to the requestAnimationFrame callback
+class AnimationFrameEvent extends Event {
+    constructor(time) {
+        super("animationframe");
+        this.time = time;
+    }
+}
+
+// the event dispatched to the handler of a view transition once the transition finished
+class ViewTransitionEvent extends Event {
+    constructor(animated, error) {
+        super("viewtransitionend");
+        this.animated = animated;
+        this.error = error;
+    }
+}
+
+// remove the children of a node that are not text or allowed html elements along with their content, and the attributes of the allowed elements that are not allowed, handle events or hold a javascript: url
+function sanitizeChildren(parent, elements, attributes) {
+    let child = parent.firstChild;
+    while (child !== null) {
+        const next = child.nextSibling;
+        if (child.nodeType === Node.ELEMENT_NODE && child.namespaceURI === HTML_NS && elements.has(child.localName)) {
+            for (const attribute of [...child.attributes]) {
+                if (!attributes.has(attribute.name) || attribute.name.startsWith("on") || JAVASCRIPT_URL.test(attribute.value.replace(URL_WHITESPACE, ""))) {
+                    child.removeAttributeNode(attribute);
+                }
+            }
+            sanitizeChildren(child.localName === "template" ? child.content : child, elements, attributes);
+        }
+        else if (child.nodeType !== Node.TEXT_NODE) {
+            child.remove();
+        }
+        child = next;
     }
 }
 
+// urls that run a script when they are followed. Browsers ignore control characters and spaces before the scheme, and tabs and newlines anywhere in a url
+const JAVASCRIPT_URL = /^[\u0000-\u0020]*javascript:/i;
+const URL_WHITESPACE = /[\t\n\r]/g;
+
+// in debug mode the top 8 bits of an id are the generation of the id. Reading a node with a different generation than the one it was stored with throws an error
+function generationalNodes(table, generations) {
+    function split(prop) {
+        const id = typeof prop === "string" ? Number(prop) : NaN;
+        return Number.isInteger(id) ? [id & 0xFFFFFF, id >>> 24] : null;
+    }
+    return new Proxy(table, {
+        get(target, prop) {
+            const id = split(prop);
+            if (id === null) {
+                return Reflect.get(target, prop);
+            }
+            if (generations[id[0]] === undefined) {
+                throw new Error("node " + id[0] + " was used before it was stored");
+            }
+            if (generations[id[0]] !== id[1]) {
+                throw new Error("node " + id[0] + " was used with the stale generation " + id[1] + ", the current generation is " + generations[id[0]]);
+            }
+            return target[id[0]];
+        },
+        set(target, prop, value) {
+            const id = split(prop);
+            if (id === null) {
+                return Reflect.set(target, prop, value);
+            }
+            generations[id[0]] = id[1];
+            target[id[0]] = value;
+            return true;
+        },
+    });
+}
+
 export class JsInterpreter {
-    constructor(mem, _metadata_ptr, _ptr_ptr, _str_ptr_ptr, _str_len_ptr) {
+    // elements and attributes are the names of the built-in elements and attributes in the order of their discriminants, each followed by a comma
+    // they are empty if the tables are compiled out of the rust side
+    constructor(mem, _metadata_ptr, _ptr_ptr, _str_ptr_ptr, _str_len_ptr, debug, elements, attributes) {
+        els = elements.split(",");
+        attrs = attributes.split(",");
         this.lastNode;
-        this.nodes = [];
+        // the node table is a dense array indexed by id
+        this.nodeTable = [];
+        // the generation each id was stored with in debug mode
+        this.generations = [];
+        this.nodes = debug ? generationalNodes(this.nodeTable, this.generations) : this.nodeTable;
+        // the parents pushed with the parent stack operation
         this.parents = [];
         this.UpdateMemory(mem);
         this.last_start_pos;
@@ -357,20 +890,241 @@ export class JsInterpreter {
         this.strPos = 0;
         this.decoder = new TextDecoder();
         this.idSize = 1;
+        // removed nodes that can be reused, keyed by tag name
+        this.pools = new Map();
+        // values cached with a one byte id for cached attributes
+        this.cachedValues = [];
+        // the function rust passes to SetEventHandler that dispatches events to the handlers
+        this.eventHandler = null;
+        // the function rust passes to SetQueryHandler that answers queries with the values the interpreter reads
+        this.queryHandler = null;
+        // the function added as the listener for each handler
+        this.listeners = [];
+        // the function added as the coalesced listener for each handler
+        this.coalescedListeners = [];
+        // the handler, the node and the latest event of each coalesced listener, dispatched in the next animation frame
+        this.pendingEvents = [];
+        // the event types and listeners added to each node, so they can be removed when the node is pooled
+        this.nodeListeners = new WeakMap();
+        // the media query lists handlers are subscribed to, keyed by the handler and the query
+        this.mediaQueries = new Map();
+        // the handlers the events of fullscreen and pointer lock are dispatched to, in the order of LOCK_EVENTS
+        this.lockHandlers = [null, null];
+        // the id and the handler of each canvas observed with observeCanvasSize, and the observer that resizes them
+        this.canvases = new Map();
+        this.resizeObserver = null;
+        // the handlers subscribed to animation frames and the id of the requested frame, or 0 if no frame is requested
+        this.frameHandlers = new Set();
+        this.frameRequest = 0;
+        // the handlers the lifecycle callbacks of each observed custom element tag are forwarded to
+        this.lifecycleHandlers = new Map();
+        // the custom element tags whose callbacks were wrapped when they were defined
+        this.wrappedElements = new Set();
+        // custom elements read their lifecycle callbacks when they are defined, so the callbacks of every element defined after the interpreter is created are wrapped
+        // the original define and the wrapper that replaced it, so Dispose can restore the original
+        this.define = null;
+        this.wrappedDefine = null;
+        if (typeof customElements !== "undefined") {
+            const define = this.define = customElements.define;
+            const interpreter = this;
+            this.wrappedDefine = customElements.define = function (tag, constructor, options) {
+                interpreter.wrapLifecycle(tag, constructor.prototype);
+                return define.call(this, tag, constructor, options);
+            };
+        }
+        // setting the value of a control cancels the composition the user is typing with an input method, so values set during a composition wait until it ends
+        this.composing = null;
+        this.pendingValues = new Map();
+        this.compositionListeners = [
+            ["compositionstart", (event) => {
+                this.composing = event.target;
+            }],
+            ["compositionend", () => {
+                this.composing = null;
+                for (const [node, value] of this.pendingValues) {
+                    this.writeValue(node, value);
+                }
+                this.pendingValues.clear();
+            }],
+        ];
+        // listen in the capture phase so the composition is tracked before any handler runs
+        for (const [name, listener] of this.compositionListeners) {
+            document.addEventListener(name, listener, true);
+        }
+        // the format function of each Intl.NumberFormat and Intl.DateTimeFormat, keyed by the kind, the locale and the options, because creating a formatter is much slower than formatting
+        this.formats = new Map();
+        this.allowlists = new Map();
         inptr = this;
     }
 
-    NeedsMemory() {
-        return this.view.buffer.byteLength === 0;
-    }
-
     UpdateMemory(mem) {
+        this.mem = mem;
         this.view = new DataView(mem.buffer);
-        buffer = mem.buffer;
     }
 
     Work() {
+        this.prepare();
+
+        // this is faster than a while(true) loop
+        for (; ;) {
+            // op = this.view.getUint8(this.u8BufPos++);
+            // if (this.exOp(op & 0x1F)) return;
+            op = this.view.getUint32(this.u8BufPos, true);
+            this.u8BufPos += 4;
+            if (exOp()) return;
+            op >>>= 8;
+            if (exOp()) return;
+            op >>>= 8;
+            if (exOp()) return;
+            op >>>= 8;
+            if (exOp()) return;
+        }
+    }
+
+    // the same as Work, but tracks the operation being executed and returns the offset of the operation that failed and the error message if one of the operations throws
+    WorkChecked() {
+        let batchPos, k;
+        this.prepare();
+        try {
+            for (; ;) {
+                batchPos = this.u8BufPos;
+                op = this.view.getUint32(this.u8BufPos, true);
+                this.u8BufPos += 4;
+                for (k = 0; k < 4; k++) {
+                    if (exOp()) return null;
+                    op >>>= 8;
+                }
+            }
+        }
+        catch (e) {
+            return [batchPos + k - this.last_start_pos, String(e)];
+        }
+    }
+
+    // the same as WorkChecked, but also counts the operations executed and wraps the batch in performance marks so it shows up in the browser's performance timeline
+    // returns [error, number of operations, duration in ms] where error is null or the same value returned by WorkChecked
+    WorkProfiled() {
+        let batchPos, k, error = null, ops = 0;
+        const start = performance.now();
+        performance.mark("sledgehammer-flush-start");
+        this.prepare();
+        try {
+            run: for (; ;) {
+                batchPos = this.u8BufPos;
+                op = this.view.getUint32(this.u8BufPos, true);
+                this.u8BufPos += 4;
+                for (k = 0; k < 4; k++) {
+                    if (exOp()) break run;
+                    ops++;
+                    op >>>= 8;
+                }
+            }
+        }
+        catch (e) {
+            error = [batchPos + k - this.last_start_pos, String(e)];
+        }
+        performance.mark("sledgehammer-flush-end");
+        // older browsers return undefined from measure
+        const measure = performance.measure("sledgehammer-flush", "sledgehammer-flush-start", "sledgehammer-flush-end");
+        return [error, ops, measure ? measure.duration : performance.now() - start];
+    }
+
+    // applies the queued batch until performance.now() passes the deadline, checking the time after every four operations
+    // if resume is true, the batch that ran out of time last is continued from where it stopped instead of reading a new batch
+    // idle batches keep their own last node so they do not change the last node of the batches that run between them
+    // returns true if the batch finished, false if it ran out of time, or the same value returned by WorkChecked if an operation throws
+    WorkIdle(deadline, resume) {
+        let batchPos, k, start;
+        const lastNode = this.lastNode;
+        if (resume) {
+            if (this.view.buffer !== this.mem.buffer) {
+                this.view = new DataView(this.mem.buffer);
+            }
+            start = this.idle.start;
+            this.u8BufPos = this.idle.pos;
+            this.strings = this.idle.strings;
+            this.strPos = this.idle.strPos;
+            this.lastNode = this.idle.lastNode;
+        }
+        else {
+            this.prepare();
+            start = this.last_start_pos;
+        }
+        try {
+            for (; ;) {
+                batchPos = this.u8BufPos;
+                op = this.view.getUint32(this.u8BufPos, true);
+                this.u8BufPos += 4;
+                for (k = 0; k < 4; k++) {
+                    if (exOp()) {
+                        this.idle = null;
+                        this.lastNode = lastNode;
+                        return true;
+                    }
+                    op >>>= 8;
+                }
+                if (performance.now() >= deadline) {
+                    this.idle = { start: start, pos: this.u8BufPos, strings: this.strings, strPos: this.strPos, lastNode: this.lastNode };
+                    this.lastNode = lastNode;
+                    return false;
+                }
+            }
+        }
+        catch (e) {
+            this.idle = null;
+            this.lastNode = lastNode;
+            return [batchPos + k - start, String(e)];
+        }
+    }
+
+    // applies a batch from arrays outside of wasm memory, like a batch received over the network and decompressed with decompressBatch from decompress.js
+    // returns the same value as WorkChecked
+    WorkBytes(msg, str) {
+        let batchPos, k;
+        // batches applied later, like the update of a view transition, run between other batches and must not change the state those batches read
+        const view = this.view, start = this.last_start_pos, pos = this.u8BufPos, strings = this.strings, strPos = this.strPos, lastNode = this.lastNode;
+        // the last group of operations is read as a u32 even if the message ends before it
+        const padded = new Uint8Array(msg.length + 4);
+        padded.set(msg);
+        this.view = new DataView(padded.buffer);
+        this.u8BufPos = 0;
+        this.last_start_pos = 0;
+        this.strings = this.decoder.decode(str);
+        this.strPos = 0;
+        try {
+            for (; ;) {
+                batchPos = this.u8BufPos;
+                op = this.view.getUint32(this.u8BufPos, true);
+                this.u8BufPos += 4;
+                for (k = 0; k < 4; k++) {
+                    if (exOp()) return null;
+                    op >>>= 8;
+                }
+            }
+        }
+        catch (e) {
+            return [batchPos + k, String(e)];
+        }
+        finally {
+            this.view = view;
+            this.last_start_pos = start;
+            this.u8BufPos = pos;
+            this.strings = strings;
+            this.strPos = strPos;
+            this.lastNode = lastNode;
+        }
+    }
+
+    prepare() {
+        // growing the wasm memory replaces (and detaches) the old buffer, so we need to create a new view into the current buffer
+        if (this.view.buffer !== this.mem.buffer) {
+            this.view = new DataView(this.mem.buffer);
+        }
         metadata = this.view.getUint8(this.metadata_ptr);
+        // the upper four bits contain the format version the batch was encoded with
+        if ((metadata >>> 4) !== FORMAT_VERSION) {
+            throw new Error("sledgehammer batch was encoded with format version " + (metadata >>> 4) + " but the interpreter reads format version " + FORMAT_VERSION + ". The interpreter and the wasm module are from different releases");
+        }
         if (metadata & 0x01) {
             this.last_start_pos = this.view.getUint32(this.ptr_ptr, true);
         }
@@ -412,21 +1166,6 @@ export class JsInterpreter {
             }
             this.strPos = 0;
         }
-
-        // this is faster than a while(true) loop
-        for (; ;) {
-            // op = this.view.getUint8(this.u8BufPos++);
-            // if (this.exOp(op & 0x1F)) return;
-            op = this.view.getUint32(this.u8BufPos, true);
-            this.u8BufPos += 4;
-            if (exOp()) return;
-            op >>>= 8;
-            if (exOp()) return;
-            op >>>= 8;
-            if (exOp()) return;
-            op >>>= 8;
-            if (exOp()) return;
-        }
     }
 
     createElement() {
@@ -443,8 +1182,8 @@ export class JsInterpreter {
                 // the element is encoded as a string
                 // we use 3 bytes of i just read
                 this.u8BufPos += 3;
-                element = document.createElement(this.strings.substring(this.strPos, this.strPos += (j & 0xFFFF00) >>> 8));
-                return element;
+                element = this.strings.substring(this.strPos, this.strPos += (j & 0xFFFF00) >>> 8);
+                return this.pooled(element) || document.createElement(element);
             case 253:
                 // the element and namespace are encoded as strings
                 // we use 3 bytes of i just read
@@ -456,7 +1195,7 @@ export class JsInterpreter {
             default:
                 this.u8BufPos++;
                 // the element is encoded as an enum
-                return document.createElement(els[element]);
+                return this.pooled(els[element]) || document.createElement(els[element]);
         }
     }
 
@@ -468,7 +1207,7 @@ export class JsInterpreter {
             this.u8BufPos += 4;
         }
         if (j & 0x2) {
-            node = document.createTextNode(this.strings.substring(this.strPos, this.strPos += this.view.getUint16(this.u8BufPos, true)));
+            node = this.createTextNode(this.strings.substring(this.strPos, this.strPos += this.view.getUint16(this.u8BufPos, true)));
             this.u8BufPos += 2;
             if (parent_id !== null) {
                 this.nodes[parent_id] = node;
@@ -489,8 +1228,10 @@ export class JsInterpreter {
                         // the attribute is encoded as an enum and the namespace is encoded as a string
                         // we use all 4 bytes of j just read
                         this.u8BufPos += 4;
-                        attr = attrs[this.view.getUint8((j & 0xFF00) >>> 8)];
-                        parent_element.setAttributeNS(this.strings.substring(this.strPos, this.strPos += (j & 0xFFFF0000) >>> 16), attr);
+                        attr = attrs[(j & 0xFF00) >>> 8];
+                        ns = this.strings.substring(this.strPos, this.strPos += (j & 0xFFFF0000) >>> 16);
+                        parent_element.setAttributeNS(ns, attr, this.strings.substring(this.strPos, this.strPos += this.view.getUint16(this.u8BufPos, true)));
+                        this.u8BufPos += 2;
                         break;
                     case 254:
                         // the attribute is encoded as a string
@@ -529,6 +1270,603 @@ export class JsInterpreter {
         }
     }
 
+    // fill the node table up to the capacity so storing nodes below it does not grow the array or leave holes in it
+    hintNodeCapacity(capacity) {
+        for (i = this.nodeTable.length; i < capacity; i++) {
+            this.nodeTable.push(null);
+        }
+    }
+
+    createTextNode(text) {
+        const pooled = this.pooled("#text");
+        if (pooled === undefined) {
+            return document.createTextNode(text);
+        }
+        pooled.data = text;
+        return pooled;
+    }
+
+    // take a node out of the pool for a tag name, or return undefined if the pool is empty
+    pooled(tag) {
+        if (this.pools.size === 0) {
+            return undefined;
+        }
+        const pool = this.pools.get(tag);
+        return pool === undefined ? undefined : pool.nodes.pop();
+    }
+
+    setPoolSize(tag, size) {
+        const pool = this.pools.get(tag);
+        if (size === 0) {
+            this.pools.delete(tag);
+        }
+        else if (pool === undefined) {
+            this.pools.set(tag, { size, nodes: [] });
+        }
+        else {
+            pool.size = size;
+            pool.nodes.length = Math.min(pool.nodes.length, size);
+        }
+    }
+
+    // reset a node that was removed and the nodes inside of it and add them to the pools for their tag names
+    // a node that replaced itself is still in the document and must not be reused
+    recycle(root) {
+        if (root.parentNode === null) {
+            this.recycleTree(root);
+        }
+    }
+
+    recycleTree(node) {
+        let child = node.firstChild, next;
+        while (child !== null) {
+            next = child.nextSibling;
+            this.recycleTree(child);
+            child = next;
+        }
+        // only html elements and text nodes are pooled
+        const pool = this.pools.get(node.nodeType === 3 ? "#text" : node.namespaceURI === HTML_NS ? node.localName : null);
+        if (pool !== undefined && pool.nodes.length < pool.size) {
+            node.remove();
+            const added = this.nodeListeners.get(node);
+            if (added !== undefined) {
+                for (let j = 0; j < added.length; j += 2) {
+                    node.removeEventListener(added[j], added[j + 1]);
+                }
+                this.nodeListeners.delete(node);
+            }
+            if (node.nodeType === 1) {
+                node.textContent = "";
+                while (node.attributes.length > 0) {
+                    node.removeAttributeNode(node.attributes[0]);
+                }
+            }
+            pool.nodes.push(node);
+        }
+    }
+
+    // the function added as the listener for a handler. Adding the same function twice does nothing, so it is created once per handler
+    listener(handler) {
+        let listener = this.listeners[handler];
+        if (listener === undefined) {
+            listener = this.listeners[handler] = (event) => this.dispatch(handler, event);
+        }
+        return listener;
+    }
+
+    // the function added as the coalesced listener for a handler. It keeps the latest event for each node and event type until the next animation frame
+    coalescedListener(handler) {
+        let listener = this.coalescedListeners[handler];
+        if (listener === undefined) {
+            listener = this.coalescedListeners[handler] = (event) => this.coalesce(handler, event);
+        }
+        return listener;
+    }
+
+    coalesce(handler, event) {
+        const pending = this.pendingEvents;
+        for (let k = 0; k < pending.length; k += 3) {
+            if (pending[k] === handler && pending[k + 1] === event.currentTarget && pending[k + 2].type === event.type) {
+                pending[k + 2] = event;
+                return;
+            }
+        }
+        if (pending.length === 0) {
+            requestAnimationFrame(() => this.dispatchPending());
+        }
+        pending.push(handler, event.currentTarget, event);
+    }
+
+    dispatchPending() {
+        // take the events first, so events dispatched by the handlers wait for the next frame
+        const pending = this.pendingEvents;
+        this.pendingEvents = [];
+        for (let k = 0; k < pending.length; k += 3) {
+            this.dispatch(pending[k], pending[k + 2]);
+        }
+    }
+
+    addListener(node, name, handler, coalesced) {
+        const listener = coalesced ? this.coalescedListener(handler) : this.listener(handler);
+        let added = this.nodeListeners.get(node);
+        if (added === undefined) {
+            added = [];
+            this.nodeListeners.set(node, added);
+        }
+        for (let j = 0; j < added.length; j += 2) {
+            if (added[j] === name && added[j + 1] === listener) {
+                return;
+            }
+        }
+        if (coalesced) {
+            // coalesced events are dispatched after the event, so the listener cannot prevent the default action
+            node.addEventListener(name, listener, { passive: true });
+        }
+        else {
+            node.addEventListener(name, listener);
+        }
+        added.push(name, listener);
+    }
+
+    removeListener(node, name, handler) {
+        const listener = this.listener(handler);
+        const coalesced = this.coalescedListener(handler);
+        node.removeEventListener(name, listener);
+        node.removeEventListener(name, coalesced);
+        const added = this.nodeListeners.get(node);
+        if (added !== undefined) {
+            for (let j = added.length - 2; j >= 0; j -= 2) {
+                if (added[j] === name && (added[j + 1] === listener || added[j + 1] === coalesced)) {
+                    added.splice(j, 2);
+                }
+            }
+        }
+        const pending = this.pendingEvents;
+        for (let k = 0; k < pending.length; k += 3) {
+            if (pending[k] === handler && pending[k + 1] === node && pending[k + 2].type === name) {
+                pending.splice(k, 3);
+                break;
+            }
+        }
+    }
+
+    subscribeMediaQuery(query, handler) {
+        const key = handler + " " + query;
+        if (this.mediaQueries.has(key)) {
+            return;
+        }
+        const list = matchMedia(query);
+        list.addEventListener("change", this.listener(handler));
+        this.mediaQueries.set(key, list);
+        // send the current state once the batch is done, so the handler does not run while rust is applying the batch
+        queueMicrotask(() => {
+            if (this.mediaQueries.get(key) === list) {
+                this.dispatch(handler, new MediaQueryListEvent("change", { media: list.media, matches: list.matches }));
+            }
+        });
+    }
+
+    unsubscribeMediaQuery(query, handler) {
+        const key = handler + " " + query;
+        const list = this.mediaQueries.get(key);
+        if (list !== undefined) {
+            list.removeEventListener("change", this.listener(handler));
+            this.mediaQueries.delete(key);
+        }
+    }
+
+    subscribeAnimationFrames(handler) {
+        this.frameHandlers.add(handler);
+        if (this.frameRequest === 0) {
+            this.frameRequest = requestAnimationFrame((time) => this.animationFrame(time));
+        }
+    }
+
+    unsubscribeAnimationFrames(handler) {
+        this.frameHandlers.delete(handler);
+        if (this.frameHandlers.size === 0 && this.frameRequest !== 0) {
+            cancelAnimationFrame(this.frameRequest);
+            this.frameRequest = 0;
+        }
+    }
+
+    animationFrame(time) {
+        // request the next frame first, so handlers that unsubscribe cancel it
+        this.frameRequest = requestAnimationFrame((time) => this.animationFrame(time));
+        for (const handler of [...this.frameHandlers]) {
+            if (this.frameHandlers.has(handler)) {
+                this.dispatch(handler, new AnimationFrameEvent(time));
+            }
+        }
+    }
+
+    observeCanvasSize(canvas, id, handler) {
+        if (!(canvas instanceof HTMLCanvasElement)) {
+            throw new Error(canvas.nodeName + " is not a canvas element");
+        }
+        if (id === -1) {
+            id = this.idOf(canvas);
+            if (id === -1) {
+                throw new Error("the canvas must be stored with an id to be observed");
+            }
+        }
+        if (this.resizeObserver === null) {
+            this.resizeObserver = new ResizeObserver((entries) => this.resizeCanvases(entries));
+        }
+        this.canvases.set(canvas, [id, handler]);
+        try {
+            // the device pixel box changes when the page is zoomed, even if the css size does not
+            this.resizeObserver.observe(canvas, { box: "device-pixel-content-box" });
+        }
+        catch (e) {
+            this.resizeObserver.observe(canvas);
+        }
+    }
+
+    unobserveCanvasSize(canvas) {
+        if (this.canvases.delete(canvas)) {
+            this.resizeObserver.unobserve(canvas);
+        }
+    }
+
+    resizeCanvases(entries) {
+        for (const entry of entries) {
+            const observed = this.canvases.get(entry.target);
+            if (observed === undefined) {
+                continue;
+            }
+            const canvas = entry.target;
+            const ratio = devicePixelRatio;
+            const width = entry.contentRect.width;
+            const height = entry.contentRect.height;
+            // the device pixel box is exact, while rounding the css size can be off by a pixel. Both assume a horizontal writing mode
+            const pixels = entry.devicePixelContentBoxSize;
+            canvas.width = pixels !== undefined ? pixels[0].inlineSize : Math.round(width * ratio);
+            canvas.height = pixels !== undefined ? pixels[0].blockSize : Math.round(height * ratio);
+            if (this.eventHandler === null) {
+                continue;
+            }
+            const payload = new Uint8Array(40);
+            const view = new DataView(payload.buffer);
+            const text = "canvasresize";
+            payload[0] = 13;
+            view.setUint16(2, text.length, true);
+            view.setUint32(4, observed[0], true);
+            view.setFloat64(8, width, true);
+            view.setFloat64(16, height, true);
+            view.setFloat64(24, ratio, true);
+            view.setUint32(32, canvas.width, true);
+            view.setUint32(36, canvas.height, true);
+            this.eventHandler(observed[1], payload, text);
+        }
+    }
+
+    // apply a nested batch with WorkBytes in the update callback of a view transition, and dispatch a ViewTransitionEvent to the handler once the transition finished
+    startViewTransition(handler, msg, str) {
+        let error = null;
+        const update = () => {
+            const result = this.WorkBytes(msg, str);
+            if (result !== null) {
+                error = result[1];
+                // rejecting the update callback skips the animation
+                throw new Error(error);
+            }
+        };
+        // browsers without view transitions apply the batch at the same time the update callback would run, without animating it
+        if (typeof document.startViewTransition !== "function") {
+            queueMicrotask(() => {
+                try {
+                    update();
+                }
+                catch (e) { }
+                this.dispatch(handler, new ViewTransitionEvent(false, error));
+            });
+            return;
+        }
+        let animated = true;
+        const transition = document.startViewTransition(update);
+        // ready rejects before finished settles if the transition is skipped. The error of the update callback is sent to the handler instead of being unhandled
+        transition.ready.catch(() => {
+            animated = false;
+        });
+        transition.updateCallbackDone.catch(() => { });
+        const finished = () => this.dispatch(handler, new ViewTransitionEvent(animated, error));
+        transition.finished.then(finished, finished);
+    }
+
+    // the function that formats a number or a timestamp with a locale and the options as json. An empty locale uses the locale of the browser
+    format(date, locale, options) {
+        const key = (date ? "d" : "n") + locale + "\0" + options;
+        let format = this.formats.get(key);
+        if (format === undefined) {
+            const locales = locale === "" ? undefined : locale;
+            const formatter = date ? new Intl.DateTimeFormat(locales, JSON.parse(options)) : new Intl.NumberFormat(locales, JSON.parse(options));
+            format = formatter.format;
+            this.formats.set(key, format);
+        }
+        return format;
+    }
+
+    // answer a query with true once an image is decoded, or with false if it fails to load or decode. The query is usually answered after the batch is applied
+    decodeImage(image, query) {
+        if (typeof image.decode !== "function") {
+            throw new Error("the node is not an image");
+        }
+        image.decode().then(() => this.answer(query, "true"), () => this.answer(query, "false"));
+    }
+
+    // replace the children of a node with html that only contains the allowed elements and attributes. The names are separated by commas
+    setSanitizedHtml(node, html, elements, attributes) {
+        const key = elements + "\0" + attributes;
+        let allowlist = this.allowlists.get(key);
+        if (allowlist === undefined) {
+            allowlist = [new Set(elements === "" ? [] : elements.split(",")), new Set(attributes === "" ? [] : attributes.split(","))];
+            this.allowlists.set(key, allowlist);
+        }
+        // setHTML also removes scripts and event handler attributes that are allowed
+        if (typeof node.setHTML === "function") {
+            node.setHTML(html, { sanitizer: { elements: [...allowlist[0]], attributes: [...allowlist[1]] } });
+            return;
+        }
+        // nothing parsed into a template runs or loads before it is cleaned
+        const template = document.createElement("template");
+        template.innerHTML = html;
+        sanitizeChildren(template.content, allowlist[0], allowlist[1]);
+        node.replaceChildren(template.content);
+    }
+
+    // dispatch the change and error events of fullscreen or pointer lock to a handler instead of the handler of the last request
+    routeLockEvents(lock, handler) {
+        if (this.lockHandlers[lock] !== null) {
+            for (const name of LOCK_EVENTS[lock]) {
+                document.removeEventListener(name, this.listener(this.lockHandlers[lock]));
+            }
+        }
+        this.lockHandlers[lock] = handler;
+        for (const name of LOCK_EVENTS[lock]) {
+            document.addEventListener(name, this.listener(handler));
+        }
+    }
+
+    observeCustomElement(tag, handler) {
+        if (customElements.get(tag) !== undefined && !this.wrappedElements.has(tag)) {
+            throw new Error("the custom element " + tag + " was defined before the interpreter was created, so its lifecycle callbacks cannot be observed");
+        }
+        let handlers = this.lifecycleHandlers.get(tag);
+        if (handlers === undefined) {
+            handlers = new Set();
+            this.lifecycleHandlers.set(tag, handlers);
+        }
+        handlers.add(handler);
+    }
+
+    unobserveCustomElement(tag, handler) {
+        const handlers = this.lifecycleHandlers.get(tag);
+        if (handlers !== undefined) {
+            handlers.delete(handler);
+            if (handlers.size === 0) {
+                this.lifecycleHandlers.delete(tag);
+            }
+        }
+    }
+
+    // wrap the lifecycle callbacks of a custom element before it is defined so they are forwarded to the handlers observing its tag
+    wrapLifecycle(tag, prototype) {
+        this.wrappedElements.add(tag);
+        const interpreter = this;
+        for (const [callback, name] of LIFECYCLE_CALLBACKS) {
+            const original = prototype[callback];
+            // elements that extend a wrapped element inherit its wrapped callbacks
+            if (original !== undefined && original.sledgehammerLifecycle) {
+                continue;
+            }
+            const wrapped = function (...args) {
+                if (original !== undefined) {
+                    original.apply(this, args);
+                }
+                interpreter.lifecycle(this, name, args);
+            };
+            wrapped.sledgehammerLifecycle = true;
+            prototype[callback] = wrapped;
+        }
+    }
+
+    // forward a lifecycle callback once the batch that triggered it is applied, so handlers do not run while rust is applying the batch and elements stored with an id later in the batch are found
+    lifecycle(element, name, args) {
+        const handlers = this.lifecycleHandlers.get(element.localName);
+        if (handlers === undefined) {
+            return;
+        }
+        queueMicrotask(() => {
+            const id = this.idOf(element);
+            if (this.eventHandler === null || id === -1) {
+                return;
+            }
+            const payload = new Uint8Array(24);
+            const view = new DataView(payload.buffer);
+            let text = name;
+            payload[0] = 5;
+            view.setUint16(2, text.length, true);
+            view.setUint32(4, id, true);
+            if (name === "attributechanged") {
+                payload[8] = 1 | (args[1] !== null ? 2 : 0) | (args[2] !== null ? 4 : 0);
+                view.setUint16(10, args[0].length, true);
+                view.setUint16(12, args[1] !== null ? args[1].length : 0, true);
+                text += args[0] + (args[1] !== null ? args[1] : "") + (args[2] !== null ? args[2] : "");
+            }
+            for (const handler of handlers) {
+                this.eventHandler(handler, payload, text);
+            }
+        });
+    }
+
+    // encode an event in the layout Event::decode reads in rust and dispatch it to a handler
+    dispatch(handler, event) {
+        if (this.eventHandler === null) {
+            return;
+        }
+        // touch events only exist on devices with a touch screen in some browsers
+        const touch = typeof TouchEvent !== "undefined" && event instanceof TouchEvent;
+        let touches, changed;
+        if (touch) {
+            touches = Math.min(event.touches.length, 255);
+            changed = Math.min(event.changedTouches.length, 255);
+        }
+        // pointer and touch events have more data after the first 24 bytes
+        const payload = new Uint8Array(event instanceof PointerEvent ? 48 : touch ? 24 + 16 * (touches + changed) : 24);
+        const view = new DataView(payload.buffer);
+        let text = event.type;
+        view.setUint16(2, text.length, true);
+        if (event instanceof MouseEvent || event instanceof KeyboardEvent || touch) {
+            payload[1] = (event.altKey ? 1 : 0) | (event.ctrlKey ? 2 : 0) | (event.metaKey ? 4 : 0) | (event.shiftKey ? 8 : 0);
+        }
+        if (event instanceof MouseEvent) {
+            payload[0] = 1;
+            view.setInt16(4, event.button, true);
+            view.setUint16(6, event.buttons, true);
+            view.setFloat64(8, event.clientX, true);
+            view.setFloat64(16, event.clientY, true);
+            if (event instanceof PointerEvent) {
+                payload[0] = 9;
+                view.setInt32(24, event.pointerId, true);
+                view.setFloat32(28, event.pressure, true);
+                view.setFloat32(32, event.width, true);
+                view.setFloat32(36, event.height, true);
+                payload[40] = POINTER_TYPES.has(event.pointerType) ? POINTER_TYPES.get(event.pointerType) : 3;
+                payload[41] = event.isPrimary ? 1 : 0;
+            }
+        }
+        else if (touch) {
+            payload[0] = 10;
+            payload[4] = touches;
+            payload[5] = changed;
+            // events can be dispatched while a batch is applied, so the loop does not use the shared variables
+            for (let k = 0; k < touches + changed; k++) {
+                const point = k < touches ? event.touches[k] : event.changedTouches[k - touches];
+                view.setInt32(24 + k * 16, point.identifier, true);
+                view.setFloat32(28 + k * 16, point.clientX, true);
+                view.setFloat32(32 + k * 16, point.clientY, true);
+                view.setFloat32(36 + k * 16, point.force, true);
+            }
+        }
+        else if (event instanceof KeyboardEvent) {
+            payload[0] = 2;
+            payload[4] = event.repeat ? 1 : 0;
+            payload[5] = event.location;
+            view.setUint16(6, event.key.length, true);
+            text += event.key + event.code;
+        }
+        else if (event instanceof CompositionEvent) {
+            payload[0] = 11;
+            text += event.data;
+        }
+        else if (event instanceof AnimationFrameEvent) {
+            payload[0] = 12;
+            view.setFloat64(8, event.time, true);
+        }
+        else if (event instanceof ViewTransitionEvent) {
+            payload[0] = 14;
+            payload[4] = event.animated ? 1 : 0;
+            if (event.error !== null) {
+                payload[5] = 1;
+                text += event.error;
+            }
+        }
+        else if (event instanceof MediaQueryListEvent) {
+            payload[0] = 4;
+            payload[4] = event.matches ? 1 : 0;
+            text += event.media;
+        }
+        else if (typeof ToggleEvent !== "undefined" && event instanceof ToggleEvent) {
+            payload[0] = 6;
+            payload[4] = event.oldState === "open" ? 1 : 0;
+            payload[5] = event.newState === "open" ? 1 : 0;
+        }
+        else if (event.type === "close" && event.target instanceof HTMLDialogElement) {
+            payload[0] = 7;
+            text += event.target.returnValue;
+        }
+        else if (event.type.startsWith("fullscreen")) {
+            payload[0] = 8;
+            payload[4] = document.fullscreenElement !== null ? 1 : 0;
+        }
+        else if (event.type.startsWith("pointerlock")) {
+            payload[0] = 8;
+            payload[4] = document.pointerLockElement !== null ? 1 : 0;
+        }
+        else if (event.target !== null && typeof event.target.value === "string") {
+            payload[0] = 3;
+            payload[4] = event.target.checked ? 1 : 0;
+            payload[5] = event.isComposing ? 1 : 0;
+            text += event.target.value;
+        }
+        // the handler returns the flags to apply to the event and the value to write back to the target
+        const result = this.eventHandler(handler, payload, text);
+        if (result[0] & 1) {
+            event.preventDefault();
+        }
+        if (result[0] & 2) {
+            event.stopPropagation();
+        }
+        if (result[1] !== undefined) {
+            this.setValue(event.target, result[1]);
+        }
+    }
+
+    setValue(node, value) {
+        if (node === this.composing) {
+            this.pendingValues.set(node, value);
+        }
+        else {
+            this.writeValue(node, value);
+        }
+    }
+
+    // set the value of a form control and keep the cursor the same distance from the end of the value, so reverting or correcting an edit leaves the cursor where the user was typing
+    writeValue(node, value) {
+        const old = node.value;
+        if (old === value) {
+            return;
+        }
+        const end = node.selectionEnd;
+        node.value = value;
+        if (node === document.activeElement && typeof end === "number") {
+            const cursor = Math.max(value.length - (old.length - end), 0);
+            node.setSelectionRange(cursor, cursor);
+        }
+    }
+
+    SetEventHandler(handler) {
+        this.eventHandler = handler;
+    }
+
+    // answer a query with a value the interpreter read. null is sent to rust as None
+    answer(query, value) {
+        if (this.queryHandler !== null) {
+            this.queryHandler(query, value);
+        }
+    }
+
+    // answer a query with the id a node is stored with, or with null if it is not stored with an id
+    answerNode(query, node) {
+        const id = node === null ? -1 : this.idOf(node);
+        this.answer(query, id === -1 ? null : String(id));
+    }
+
+    // the id a node is stored with, or -1 if it is not stored with an id. Lookups are rare compared to operations, so the node table is searched instead of tracking the id of every node
+    idOf(node) {
+        const index = this.nodeTable.indexOf(node);
+        if (index === -1) {
+            return -1;
+        }
+        // in debug mode the generation of the id is stored in the top 8 bits
+        return index + (this.generations[index] || 0) * 0x1000000;
+    }
+
+    SetQueryHandler(handler) {
+        this.queryHandler = handler;
+    }
+
     decodeU32() {
         this.u8BufPos += 4;
         return this.view.getUint32(this.u8BufPos - 4, true);
@@ -541,297 +1879,68 @@ export class JsInterpreter {
     GetNode(id) {
         return this.nodes[id];
     }
-}
 
-const els = [
-    "a",
-    "abbr",
-    "acronym",
-    "address",
-    "applet",
-    "area",
-    "article",
-    "aside",
-    "audio",
-    "b",
-    "base",
-    "bdi",
-    "bdo",
-    "bgsound",
-    "big",
-    "blink",
-    "blockquote",
-    "body",
-    "br",
-    "button",
-    "canvas",
-    "caption",
-    "center",
-    "cite",
-    "code",
-    "col",
-    "colgroup",
-    "content",
-    "data",
-    "datalist",
-    "dd",
-    "del",
-    "details",
-    "dfn",
-    "dialog",
-    "dir",
-    "div",
-    "dl",
-    "dt",
-    "em",
-    "embed",
-    "fieldset",
-    "figcaption",
-    "figure",
-    "font",
-    "footer",
-    "form",
-    "frame",
-    "frameset",
-    "h1",
-    "head",
-    "header",
-    "hgroup",
-    "hr",
-    "html",
-    "i",
-    "iframe",
-    "image",
-    "img",
-    "input",
-    "ins",
-    "kbd",
-    "keygen",
-    "label",
-    "legend",
-    "li",
-    "link",
-    "main",
-    "map",
-    "mark",
-    "marquee",
-    "menu",
-    "menuitem",
-    "meta",
-    "meter",
-    "nav",
-    "nobr",
-    "noembed",
-    "noframes",
-    "noscript",
-    "object",
-    "ol",
-    "optgroup",
-    "option",
-    "output",
-    "p",
-    "param",
-    "picture",
-    "plaintext",
-    "portal",
-    "pre",
-    "progress",
-    "q",
-    "rb",
-    "rp",
-    "rt",
-    "rtc",
-    "ruby",
-    "s",
-    "samp",
-    "script",
-    "section",
-    "select",
-    "shadow",
-    "slot",
-    "small",
-    "source",
-    "spacer",
-    "span",
-    "strike",
-    "strong",
-    "style",
-    "sub",
-    "summary",
-    "sup",
-    "table",
-    "tbody",
-    "td",
-    "template",
-    "textarea",
-    "tfoot",
-    "th",
-    "thead",
-    "time",
-    "title",
-    "tr",
-    "track",
-    "tt",
-    "u",
-    "ul",
-    "var",
-    "video",
-    "wbr",
-    "xmp",
-];
+    // store the nodes the previous interpreter stored with the ids, or every node it stored if ids is undefined, with the generation they were stored with
+    // ids no node is stored with are skipped
+    CopyNodes(previous, ids) {
+        if (ids === undefined) {
+            previous.nodeTable.forEach((node, index) => {
+                this.nodeTable[index] = node;
+                this.generations[index] = previous.generations[index];
+            });
+            return;
+        }
+        for (const stored of ids) {
+            let copied;
+            // reading an id that is not stored throws in debug mode
+            try {
+                copied = previous.nodes[stored];
+            }
+            catch (e) {
+                continue;
+            }
+            if (copied !== undefined) {
+                this.nodes[stored] = copied;
+            }
+        }
+    }
 
-const attrs = [
-    "accept-charset",
-    "accept",
-    "accesskey",
-    "action",
-    "align",
-    "allow",
-    "alt",
-    "aria-atomic",
-    "aria-busy",
-    "aria-controls",
-    "aria-current",
-    "aria-describedby",
-    "aria-description",
-    "aria-details",
-    "aria-disabled",
-    "aria-dropeffect",
-    "aria-errormessage",
-    "aria-flowto",
-    "aria-grabbed",
-    "aria-haspopup",
-    "aria-hidden",
-    "aria-invalid",
-    "aria-keyshortcuts",
-    "aria-label",
-    "aria-labelledby",
-    "aria-live",
-    "aria-owns",
-    "aria-relevant",
-    "aria-roledescription",
-    "async",
-    "autocapitalize",
-    "autocomplete",
-    "autofocus",
-    "autoplay",
-    "background",
-    "bgcolor",
-    "border",
-    "buffered",
-    "capture",
-    "challenge",
-    "charset",
-    "checked",
-    "cite",
-    "class",
-    "code",
-    "codebase",
-    "color",
-    "cols",
-    "colspan",
-    "content",
-    "contenteditable",
-    "contextmenu",
-    "controls",
-    "coords",
-    "crossorigin",
-    "csp",
-    "data",
-    "datetime",
-    "decoding",
-    "default",
-    "defer",
-    "dir",
-    "dirname",
-    "disabled",
-    "download",
-    "draggable",
-    "enctype",
-    "enterkeyhint",
-    "for",
-    "form",
-    "formaction",
-    "formenctype",
-    "formmethod",
-    "formnovalidate",
-    "formtarget",
-    "headers",
-    "height",
-    "hidden",
-    "high",
-    "href",
-    "hreflang",
-    "http-equiv",
-    "icon",
-    "id",
-    "importance",
-    "inputmode",
-    "integrity",
-    "intrinsicsize",
-    "ismap",
-    "itemprop",
-    "keytype",
-    "kind",
-    "label",
-    "lang",
-    "language",
-    "list",
-    "loading",
-    "loop",
-    "low",
-    "manifest",
-    "max",
-    "maxlength",
-    "media",
-    "method",
-    "min",
-    "minlength",
-    "multiple",
-    "muted",
-    "name",
-    "novalidate",
-    "open",
-    "optimum",
-    "pattern",
-    "ping",
-    "placeholder",
-    "poster",
-    "preload",
-    "radiogroup",
-    "readonly",
-    "referrerpolicy",
-    "rel",
-    "required",
-    "reversed",
-    "role",
-    "rows",
-    "rowspan",
-    "sandbox",
-    "scope",
-    "scoped",
-    "selected",
-    "shape",
-    "size",
-    "sizes",
-    "slot",
-    "span",
-    "spellcheck",
-    "src",
-    "srcdoc",
-    "srclang",
-    "srcset",
-    "start",
-    "step",
-    "style",
-    "summary",
-    "tabindex",
-    "target",
-    "title",
-    "translate",
-    "type",
-    "usemap",
-    "value",
-    "width",
-    "wrap",
-];
+    // stop dispatching events and answering queries, and remove the listeners, subscriptions and observers the interpreter added outside of the nodes it created, so a new interpreter can take over the page
+    // listeners added to nodes stay on the nodes, but do nothing
+    Dispose() {
+        this.eventHandler = null;
+        this.queryHandler = null;
+        this.pendingEvents = [];
+        for (const [key, list] of this.mediaQueries) {
+            // the key starts with the handler
+            list.removeEventListener("change", this.listener(parseInt(key)));
+        }
+        this.mediaQueries.clear();
+        for (let lock = 0; lock < LOCK_EVENTS.length; lock++) {
+            if (this.lockHandlers[lock] !== null) {
+                for (const name of LOCK_EVENTS[lock]) {
+                    document.removeEventListener(name, this.listener(this.lockHandlers[lock]));
+                }
+                this.lockHandlers[lock] = null;
+            }
+        }
+        this.frameHandlers.clear();
+        if (this.frameRequest !== 0) {
+            cancelAnimationFrame(this.frameRequest);
+            this.frameRequest = 0;
+        }
+        if (this.resizeObserver !== null) {
+            this.resizeObserver.disconnect();
+            this.resizeObserver = null;
+        }
+        this.canvases.clear();
+        this.lifecycleHandlers.clear();
+        for (const [name, listener] of this.compositionListeners) {
+            document.removeEventListener(name, listener, true);
+        }
+        // elements defined while the interpreter was running keep their wrapped callbacks, which do nothing once the handlers are cleared
+        if (this.wrappedDefine !== null && customElements.define === this.wrappedDefine) {
+            customElements.define = this.define;
+        }
+    }
+}
@@ -1 +1 @@
-let e,t,s,r,i,u,a,o,l,h,c,d,b,p,f,m,v,g,k;export function work_last_created(){b.Work()}export function update_last_memory(e){b.UpdateMemory(e)}function y(){switch(e&31){case 0:b.l=b.l.firstChild;break;case 1:b.l=b.l.nextSibling;break;case 2:b.l=b.l.parentNode;break;case 3:b.n[b.v.u32(b.u,true)]=b.l;b.u+=4;break;case 4:b.l=b.n[b.v.u32(b.u,true)];b.u+=4;break;case 5:return true;case 6:b.l=b.createFullElement();break;case 7:if(e&32){f=b.n[b.v.u32(b.u,true)];b.u+=4}else{f=b.l}if(e&64){f.appendChild(b.n[b.v.u32(b.u,true)]);b.u+=4}else{f.appendChild(b.l)}break;case 8:if(e&64){f=b.n[b.v.u32(b.u,true)];b.u+=4}else{f=b.l}if(e&32){m=b.v.u8(b.u++,true);k=[];for(i=0;i<m;i++){if(b.v.u8(b.u++,true)){k.push(b.n[b.v.u32(b.u,true)]);b.u+=4}else{k.push(b.l)}}f.replaceWith(...n)}else{if(e&128){f.replaceWith(b.n[b.v.u32(b.u,true)]);b.u+=4}else{f.replaceWith(b.l)}}break;case 9:if(e&64){f=b.n[b.v.u32(b.u,true)];b.u+=4}else{f=b.l}if(e&32){m=b.v.u8(b.u++,true);k=[];for(i=0;i<m;i++){if(b.v.u8(b.u++,true)){k.push(b.n[b.v.u32(b.u,true)]);b.u+=4}else{k.push(b.l)}}f.after(...n)}else{if(e&128){f.after(b.n[b.v.u32(b.u,true)]);b.u+=4}else{f.after(b.l)}}break;case 10:if(e&64){f=b.n[b.v.u32(b.u,true)];b.u+=4}else{f=b.l}if(e&32){m=b.v.u8(b.u++,true);k=[];for(i=0;i<m;i++){if(b.v.u8(b.u++,true)){k.push(b.n[b.v.u32(b.u,true)]);b.u+=4}else{k.push(b.l)}}f.before(...n)}else{if(e&128){f.before(b.n[b.v.u32(b.u,true)]);b.u+=4}else{f.before(b.l)}}break;case 11:if(e&32){b.n[b.v.u32(b.u,true)].remove();b.u+=4}else{b.l.remove()}break;case 12:b.l=document.createTextNode(b.s.substring(b.o,b.o+=b.v.u16(b.u,true)));b.u+=2;if(e&32){b.n[b.v.u32(b.u,true)]=b.l;b.u+=4}break;case 13:b.l=b.createElement();if(e&32){b.n[b.v.u32(b.u,true)]=b.l;b.u+=4}break;case 14:if(e&32){g=b.v.u32(b.u,true);b.u+=4;b.n[g].textContent=b.s.substring(b.o,b.o+=b.v.u16(b.u,true));b.u+=2}else{b.l.textContent=b.s.substring(b.o,b.o+=b.v.u16(b.u,true));b.u+=2}break;case 15:if(e&32){v=b.n[b.v.u32(b.u,true)];b.u+=4}else{v=b.l}if(e&64){i=b.v.u32(b.u,true);b.u+=4;r=b.s.substring(b.o,b.o+=i&65535);if(e&128){v.setAttributeNS(b.s.substring(b.o,b.o+=(i&4294901760)>>>16),r,b.s.substring(b.o,b.o+=b.v.u16(b.u,true)));b.u+=2}else{v.setAttribute(r,b.s.substring(b.o,b.o+=(i&4294901760)>>>16))}}else{i=b.v.u32(b.u,true);b.u+=3;if(e&128){s=b.s.substring(b.o,b.o+=i&65535);v.setAttributeNS(s,x[(i&16711680)>>>16],b.s.substring(b.o,b.o+=b.v.u16(b.u,true)));b.u+=2}else{v.setAttribute(x[i&255],b.s.substring(b.o,b.o+=(i&16776960)>>>8))}}break;case 16:if(e&32){v=b.n[b.v.u32(b.u,true)];b.u+=4}else{v=b.l}if(e&64){if(e&128){i=b.v.u32(b.u,true);b.u+=4;r=b.s.substring(b.o,b.o+=i&65535);v.removeAttributeNS(b.s.substring(b.o,b.o+=(i&4294901760)>>>16),r)}else{v.removeAttribute(b.s.substring(b.o,b.o+=b.v.u16(b.u,true)));b.u+=2}}else{if(e&128){i=b.v.u32(b.u,true);b.u+=3;r=x[i&255];v.removeAttributeNS(b.s.substring(b.o,b.o+=(i&16776960)>>>8),r)}else{v.removeAttribute(x[b.v.u8(b.u++)])}}break;case 17:if(e&32){v=b.n[b.v.u32(b.u,true)];b.u+=4}else{v=b.l}i=b.v.u32(b.u,true);b.u+=4;v.style.setProperty(b.s.substring(b.o,b.o+=i&65535),b.s.substring(b.o,b.o+=(i&4294901760)>>>16));break;case 18:if(e&32){v=b.n[b.v.u32(b.u,true)];b.u+=4}else{v=b.l}v.style.removeProperty(b.s.substring(b.o,b.o+=b.v.u16(b.u,true)));b.u+=2;break;case 19:if(e&32){b.l=b.n[b.v.u32(b.u,true)].cloneNode(true);b.u+=4}else{b.l=b.l.cloneNode(true)}if(e&64){b.n[b.v.u32(b.u,true)]=b.l;b.u+=4}break;default:break}}export class JsInterpreter{constructor(e,t,s,r,i){this.l;this.n=[];this.p=[];this.UpdateMemory(e);this.lp;this.ls;this.m=t;this.pt=s;this.sp=r;this.sl=i;this.s="";this.o=0;this.d=new TextDecoder;this.i=1;b=this}NeedsMemory(){return this.v.buffer.byteLength===0}UpdateMemory(e){this.v=new DataView(e.buffer);this.v.u32=this.v.getUint32;this.v.u16=this.v.getUint16;this.v.u8=this.v.getUint8}Work(){p=this.v.u8(this.m);if(p&1){this.lp=this.v.u32(this.pt,true)}this.u=this.lp;if(p&4){t=this.v.u32(this.sl,true);if(p&2){this.ls=this.v.u32(this.sp,true)}if(p&8){l=this.ls;this.s="";d=l+(t/4|0)*4;while(l<d){h=this.v.u32(l);this.s+=String.fromCharCode(h>>24,(h&16711680)>>16,(h&65280)>>8,h&255);l+=4}switch(this.ls+t-l){case 3:h=this.v.u32(l);this.s+=String.fromCharCode(h>>24,(h&16711680)>>16,(h&65280)>>8);break;case 2:h=this.v.u16(l);this.s+=String.fromCharCode(h>>8,h&255);break;case 1:this.s+=String.fromCharCode(this.v.u8(l));break;case 0:break}}else{this.s=this.d.decode(new DataView(this.v.buffer,this.ls,t))}this.o=0}for(;;){e=this.v.u32(this.u,true);this.u+=4;if(y())return;e>>>=8;if(y())return;e>>>=8;if(y())return;e>>>=8;if(y())return}}createElement(){u=this.v.u32(this.u,true);o=u&255;switch(o){case 255:this.u+=4;o=document.createElement(w[(u&65280)>>>8],this.s.substring(this.o,this.o+=(u&4294901760)>>>16));return o;case 254:this.u+=3;o=document.createElement(this.s.substring(this.o,this.o+=(u&16776960)>>>8));return o;case 253:this.u+=3;o=this.s.substring(this.o,this.o+=(u&16776960)>>>8);o=document.createElementNS(this.s.substring(this.o,this.o+=this.v.u16(this.u,true)),o);this.u+=2;return o;default:this.u++;return document.createElement(w[o])}}createFullElement(){let e;u=this.v.u8(this.u++);if(u&1){e=this.v.u32(this.u,true);this.u+=4}if(u&2){v=document.createTextNode(this.s.substring(this.o,this.o+=this.v.u16(this.u,true)));this.u+=2;if(e!==null){this.n[e]=v}return v}else{const t=this.createElement();u=this.v.u16(this.u,true);this.u+=2;c=u&255;const o=(u&65280)>>>8;for(i=0;i<c;i++){u=this.v.u32(this.u,true);r=u&255;switch(r){case 255:this.u+=4;r=x[this.v.u8((u&65280)>>>8)];t.setAttributeNS(this.s.substring(this.o,this.o+=(u&4294901760)>>>16),r);break;case 254:this.u++;u=this.v.u32(this.u,true);this.u+=4;r=this.s.substring(this.o,this.o+=u&65535);t.setAttribute(r,this.s.substring(this.o,this.o+=(u&4294901760)>>>16));break;case 253:this.u+=3;r=this.s.substring(this.o,this.o+=(u&16776960)>>>8);u=this.v.u32(this.u,true);this.u+=4;s=this.s.substring(this.o,this.o+=u&65535);a=this.s.substring(this.o,this.o+=(u&4294901760)>>>16);t.setAttributeNS(s,r,a);break;default:this.u+=3;t.setAttribute(x[r],this.s.substring(this.o,this.o+=(u&16776960)>>>8));break}}for(let l=0;l<o;l++){t.appendChild(this.createFullElement())}if(e!==null){this.n[e]=t}return t}}decodeU32(){this.u+=4;return this.v.u32(this.u-4,true)}SetNode(e,t){this.n[e]=t}GetNode(e){return this.n[e]}}const w=["a","abbr","acronym","address","applet","area","article","aside","audio","b","base","bdi","bdo","bgsound","big","blink","blockquote","body","br","button","canvas","caption","center","cite","code","col","colgroup","content","data","datalist","dd","del","details","dfn","dialog","dir","div","dl","dt","em","embed","fieldset","figcaption","figure","font","footer","form","frame","frameset","h1","head","header","hgroup","hr","html","i","iframe","image","img","input","ins","kbd","keygen","label","legend","li","link","main","map","mark","marquee","menu","menuitem","meta","meter","nav","nobr","noembed","noframes","noscript","object","ol","optgroup","option","output","p","param","picture","plaintext","portal","pre","progress","q","rb","rp","rt","rtc","ruby","s","samp","script","section","select","shadow","slot","small","source","spacer","span","strike","strong","style","sub","summary","sup","table","tbody","td","template","textarea","tfoot","th","thead","time","title","tr","track","tt","u","ul","var","video","wbr","xmp"];const x=["accept-charset","accept","accesskey","action","align","allow","alt","aria-atomic","aria-busy","aria-controls","aria-current","aria-describedby","aria-description","aria-details","aria-disabled","aria-dropeffect","aria-errormessage","aria-flowto","aria-grabbed","aria-haspopup","aria-hidden","aria-invalid","aria-keyshortcuts","aria-label","aria-labelledby","aria-live","aria-owns","aria-relevant","aria-roledescription","async","autocapitalize","autocomplete","autofocus","autoplay","background","bgcolor","border","buffered","capture","challenge","charset","checked","cite","class","code","codebase","color","cols","colspan","content","contenteditable","contextmenu","controls","coords","crossorigin","csp","data","datetime","decoding","default","defer","dir","dirname","disabled","download","draggable","enctype","enterkeyhint","for","form","formaction","formenctype","formmethod","formnovalidate","formtarget","headers","height","hidden","high","href","hreflang","http-equiv","icon","id","importance","inputmode","integrity","intrinsicsize","ismap","itemprop","keytype","kind","label","lang","language","list","loading","loop","low","manifest","max","maxlength","media","method","min","minlength","multiple","muted","name","novalidate","open","optimum","pattern","ping","placeholder","poster","preload","radiogroup","readonly","referrerpolicy","rel","required","reversed","role","rows","rowspan","sandbox","scope","scoped","selected","shape","size","sizes","slot","span","spellcheck","src","srcdoc","srclang","srcset","start","step","style","summary","tabindex","target","title","translate","type","usemap","value","width","wrap"];
\ No newline at end of file
+let b,t,n,i,e,h,l,p,dN,v,dO,dP,r,bd,be,a,B,j,H,dQ,c,k,f,s,X,w;export const FORMAT_VERSION=1;const bf="http://www.w3.org/1999/xhtml";export function work_last_created(){a.Work();}export function work_last_created_checked(){return a.WorkChecked();}export function work_last_created_profiled(){return a.WorkProfiled();}export function work_last_created_idle(cr,cs){return a.WorkIdle(cr,cs);}function z(){switch(b&0x1F){case 0:a.l=a.l.firstChild;break;case 1:a.l=a.l.nextSibling;break;case 2:a.l=a.l.parentNode;break;case 3:a.nodes[a.v.getUint32(a.u,true)]=a.l;a.u+=4;break;case 4:a.l=a.nodes[a.v.getUint32(a.u,true)];a.u+=4;break;case 5:return true;case 6:a.l=a.createFullElement();break;case 7:if(b&0x20){j=a.nodes[a.v.getUint32(a.u,true)];a.u+=4;}else{j=a.l;}if(b&0x40){j.appendChild(a.nodes[a.v.getUint32(a.u,true)]);a.u+=4;}else{j.appendChild(a.l);}break;case 8:if(b&0x40){j=a.nodes[a.v.getUint32(a.u,true)];a.u+=4;}else{j=a.l;}if(b&0x20){H=a.v.getUint8(a.u++,true);s=[];for(e=0;e<H;e++){if(a.v.getUint8(a.u++,true)){s.push(a.nodes[a.v.getUint32(a.u,true)]);a.u+=4;}else{s.push(a.l);}}j.replaceWith(...s);}else{if(b&0x80){j.replaceWith(a.nodes[a.v.getUint32(a.u,true)]);a.u+=4;}else{j.replaceWith(a.l);}}if(a.pools.size>0){a.recycle(j);}break;case 9:if(b&0x40){j=a.nodes[a.v.getUint32(a.u,true)];a.u+=4;}else{j=a.l;}if(b&0x20){H=a.v.getUint8(a.u++,true);s=[];for(e=0;e<H;e++){if(a.v.getUint8(a.u++,true)){s.push(a.nodes[a.v.getUint32(a.u,true)]);a.u+=4;}else{s.push(a.l);}}j.after(...s);}else{if(b&0x80){j.after(a.nodes[a.v.getUint32(a.u,true)]);a.u+=4;}else{j.after(a.l);}}break;case 10:if(b&0x40){j=a.nodes[a.v.getUint32(a.u,true)];a.u+=4;}else{j=a.l;}if(b&0x20){H=a.v.getUint8(a.u++,true);s=[];for(e=0;e<H;e++){if(a.v.getUint8(a.u++,true)){s.push(a.nodes[a.v.getUint32(a.u,true)]);a.u+=4;}else{s.push(a.l);}}j.before(...s);}else{if(b&0x80){j.before(a.nodes[a.v.getUint32(a.u,true)]);a.u+=4;}else{j.before(a.l);}}break;case 11:if(b&0x20){c=a.nodes[a.v.getUint32(a.u,true)];a.u+=4;}else{c=a.l;}c.remove();if(a.pools.size>0){a.recycle(c);}break;case 12:a.l=a.createTextNode(a.s.substring(a.o,a.o+=a.v.getUint16(a.u,true)));a.u+=2;if(b&0x20){a.nodes[a.v.getUint32(a.u,true)]=a.l;a.u+=4;}break;case 13:a.l=a.createElement();if(b&0x20){a.nodes[a.v.getUint32(a.u,true)]=a.l;a.u+=4;}break;case 14:if(b&0x20){f=a.v.getUint32(a.u,true);a.u+=4;a.nodes[f].textContent=a.s.substring(a.o,a.o+=a.v.getUint16(a.u,true));a.u+=2;}else{a.l.textContent=a.s.substring(a.o,a.o+=a.v.getUint16(a.u,true));a.u+=2;}break;case 15:if(b&0x20){c=a.nodes[a.v.getUint32(a.u,true)];a.u+=4;}else{c=a.l;}if(b&0x40){e=a.v.getUint32(a.u,true);a.u+=4;i=a.s.substring(a.o,a.o+=e&0xFFFF);if(b&0x80){c.setAttributeNS(a.s.substring(a.o,a.o+=(e&0xFFFF0000)>>>16),i,a.s.substring(a.o,a.o+=a.v.getUint16(a.u,true)));a.u+=2;}else{c.setAttribute(i,a.s.substring(a.o,a.o+=(e&0xFFFF0000)>>>16));}}else{e=a.v.getUint32(a.u,true);a.u+=3;if(b&0x80){n=a.s.substring(a.o,a.o+=(e&0xFFFF00)>>>8);c.setAttributeNS(n,w[e&0xFF],a.s.substring(a.o,a.o+=a.v.getUint16(a.u,true)));a.u+=2;}else{c.setAttribute(w[e&0xFF],a.s.substring(a.o,a.o+=(e&0xFFFF00)>>>8));}}break;case 16:if(b&0x20){c=a.nodes[a.v.getUint32(a.u,true)];a.u+=4;}else{c=a.l;}if(b&0x40){if(b&0x80){e=a.v.getUint32(a.u,true);a.u+=4;i=a.s.substring(a.o,a.o+=e&0xFFFF);c.removeAttributeNS(a.s.substring(a.o,a.o+=(e&0xFFFF0000)>>>16),i);}else{c.removeAttribute(a.s.substring(a.o,a.o+=a.v.getUint16(a.u,true)));a.u+=2;}}else{if(b&0x80){e=a.v.getUint32(a.u,true);a.u+=3;i=w[e&0xFF];c.removeAttributeNS(a.s.substring(a.o,a.o+=(e&0xFFFF00)>>>8),i);}else{c.removeAttribute(w[a.v.getUint8(a.u++)]);}}break;case 17:if(b&0x20){c=a.nodes[a.v.getUint32(a.u,true)];a.u+=4;}else{c=a.l;}e=a.v.getUint32(a.u,true);a.u+=4;c.style.setProperty(a.s.substring(a.o,a.o+=e&0xFFFF),a.s.substring(a.o,a.o+=(e&0xFFFF0000)>>>16));break;case 18:if(b&0x20){c=a.nodes[a.v.getUint32(a.u,true)];a.u+=4;}else{c=a.l;}c.style.removeProperty(a.s.substring(a.o,a.o+=a.v.getUint16(a.u,true)));a.u+=2;break;case 19:if(b&0x20){a.l=a.nodes[a.v.getUint32(a.u,true)].cloneNode(true);a.u+=4;}else{a.l=a.l.cloneNode(true);}if(b&0x40){a.nodes[a.v.getUint32(a.u,true)]=a.l;a.u+=4;}break;case 20:break;case 21:if(b&0x20){c=a.nodes[a.v.getUint32(a.u,true)];a.u+=4;}else{c=a.l;}a.nodes[a.v.getUint32(a.u,true)]=c.contentDocument.body;a.u+=4;break;case 22:k=a.s.substring(a.o,a.o+=a.v.getUint16(a.u,true));a.u+=2;a.setPoolSize(k,a.v.getUint32(a.u,true));a.u+=4;break;case 23:a.hintNodeCapacity(a.v.getUint32(a.u,true));a.u+=4;break;case 24:f=a.v.getUint8(a.u++);a.cachedValues[f]=a.s.substring(a.o,a.o+=a.v.getUint16(a.u,true));a.u+=2;break;case 25:if(b&0x20){c=a.nodes[a.v.getUint32(a.u,true)];a.u+=4;}else{c=a.l;}if(b&0x40){if(b&0x80){e=a.v.getUint32(a.u,true);a.u+=4;i=a.s.substring(a.o,a.o+=e&0xFFFF);n=a.s.substring(a.o,a.o+=(e&0xFFFF0000)>>>16);}else{i=a.s.substring(a.o,a.o+=a.v.getUint16(a.u,true));a.u+=2;n=null;}}else{if(b&0x80){e=a.v.getUint32(a.u,true);a.u+=3;i=w[e&0xFF];n=a.s.substring(a.o,a.o+=(e&0xFFFF00)>>>8);}else{i=w[a.v.getUint8(a.u++)];n=null;}}l=a.cachedValues[a.v.getUint8(a.u++)];if(n===null){c.setAttribute(i,l);}else{c.setAttributeNS(n,i,l);}break;case 26:if(b&0x20){a.parents.push(a.l);}else{c=a.parents.pop();c.appendChild(a.l);a.l=c;}break;case 27:if(b&0x20){c=a.nodes[a.v.getUint32(a.u,true)];a.u+=4;}else{c=a.l;}f=a.v.getUint32(a.u,true);a.u+=4;k=a.s.substring(a.o,a.o+=a.v.getUint16(a.u,true));a.u+=2;if(b&0x40){a.addListener(c,k,f,(b&0x80)!==0);}else{a.removeListener(c,k,f);}break;case 28:if(b&0x20){c=a.nodes[a.v.getUint32(a.u,true)];a.u+=4;}else{c=a.l;}a.setValue(c,a.s.substring(a.o,a.o+=a.v.getUint16(a.u,true)));a.u+=2;break;case 29:switch(a.v.getUint8(a.u++)){case 0:f=a.v.getUint32(a.u,true);a.u+=4;k=a.s.substring(a.o,a.o+=a.v.getUint16(a.u,true));a.u+=2;if(b&0x20){a.subscribeMediaQuery(k,f);}else{a.unsubscribeMediaQuery(k,f);}break;case 4:f=a.v.getUint32(a.u,true);a.u+=4;k=a.s.substring(a.o,a.o+=a.v.getUint16(a.u,true));a.u+=2;if(b&0x20){a.observeCustomElement(k,f);}else{a.unobserveCustomElement(k,f);}break;case 5:if(b&0x20){c=a.nodes[a.v.getUint32(a.u,true)];a.u+=4;}else{c=a.l;}if(b&0x40){if(b&0x80){c.showPopover();}else{c.hidePopover();}}else{if(b&0x80){c.showModal();}else{c.close();}}break;case 6:case 7:f=a.v.getUint8(a.u-1)-6;if(b&0x20){if(b&0x40){c=a.nodes[a.v.getUint32(a.u,true)];a.u+=4;}else{c=a.l;}a.routeLockEvents(f,a.v.getUint32(a.u,true));a.u+=4;l=f===0?c.requestFullscreen():c.requestPointerLock();if(l instanceof Promise){l.catch(()=>{});}}else if(f===0){if(document.fullscreenElement!==null){document.exitFullscreen();}}else{document.exitPointerLock();}break;case 1:f=a.v.getUint32(a.u,true);a.u+=4;k=a.s.substring(a.o,a.o+=a.v.getUint16(a.u,true));a.u+=2;a.answer(f,(b&0x20?sessionStorage:localStorage).getItem(k));break;case 2:k=a.s.substring(a.o,a.o+=a.v.getUint16(a.u,true));a.u+=2;l=a.s.substring(a.o,a.o+=a.v.getUint16(a.u,true));a.u+=2;(b&0x20?sessionStorage:localStorage).setItem(k,l);break;case 3:k=a.s.substring(a.o,a.o+=a.v.getUint16(a.u,true));a.u+=2;(b&0x20?sessionStorage:localStorage).removeItem(k);break;case 8:f=a.v.getUint32(a.u,true);a.u+=4;if(b&0x20){a.subscribeAnimationFrames(f);}else{a.unsubscribeAnimationFrames(f);}break;case 9:if(b&0x40){f=a.v.getUint32(a.u,true);c=a.nodes[f];a.u+=4;}else{f=-1;c=a.l;}if(b&0x20){a.observeCanvasSize(c,f,a.v.getUint32(a.u,true));a.u+=4;}else{a.unobserveCanvasSize(c);}break;case 10:f=a.v.getUint32(a.u,true);a.u+=4;a.answerNode(f,document.activeElement);break;case 11:f=a.v.getUint32(a.u,true);a.answerNode(f,document.elementFromPoint(a.v.getInt32(a.u+4,true),a.v.getInt32(a.u+8,true)));a.u+=12;break;case 12:if(b&0x20){c=a.nodes[a.v.getUint32(a.u,true)];a.u+=4;}else{c=a.l;}f=a.v.getUint32(a.u,true);a.u+=4;a.answer(f,String(c.checkVisibility?c.checkVisibility():c.getClientRects().length>0));break;case 13:if(b&0x40){c=a.nodes[a.v.getUint32(a.u,true)];a.u+=4;}else{c=a.l;}c.classList.toggle(a.s.substring(a.o,a.o+=a.v.getUint16(a.u,true)),(b&0x20)!==0);a.u+=2;break;case 14:if(b&0x20){c=a.nodes[a.v.getUint32(a.u,true)];a.u+=4;}else{c=a.l;}l=a.s.substring(a.o,a.o+=a.v.getUint16(a.u,true));a.u+=2;n=a.s.substring(a.o,a.o+=a.v.getUint16(a.u,true));a.u+=2;c.textContent=a.format((b&0x40)!==0,n,a.s.substring(a.o,a.o+=a.v.getUint16(a.u,true)))(Number(l));a.u+=2;break;case 15:f=a.v.getUint32(a.u,true);a.u+=4;t=a.v.getUint32(a.u,true);a.u+=4;l=new Uint8Array(a.v.buffer,a.v.byteOffset+a.u,t).slice();a.u+=t;t=a.v.getUint32(a.u,true);a.u+=4;a.startViewTransition(f,l,new Uint8Array(a.v.buffer,a.v.byteOffset+a.u,t).slice());a.u+=t;break;case 16:if(b&0x20){a.parents[a.parents.length-1].appendChild(a.l);if(b&0x40){a.parents.push(a.l);}}else{a.l=a.parents.pop();}break;case 17:if(b&0x20){c=a.nodes[a.v.getUint32(a.u,true)];a.u+=4;}else{c=a.l;}l=a.s.substring(a.o,a.o+=a.v.getUint16(a.u,true));a.u+=2;k=a.s.substring(a.o,a.o+=a.v.getUint16(a.u,true));a.u+=2;a.setSanitizedHtml(c,l,k,a.s.substring(a.o,a.o+=a.v.getUint16(a.u,true)));a.u+=2;break;case 18:if(b&0x80){c=a.nodes[a.v.getUint32(a.u,true)];a.u+=4;}else{c=a.l;}c.setAttribute("loading",b&0x20?"lazy":"eager");c.setAttribute("decoding",b&0x40?"async":"auto");break;case 19:if(b&0x20){c=a.nodes[a.v.getUint32(a.u,true)];a.u+=4;}else{c=a.l;}f=a.v.getUint32(a.u,true);a.u+=4;a.decodeImage(c,f);break;default:throw new Error("unknown extended operation "+a.v.getUint8(a.u-1));}break;default:throw new Error("unknown operation "+(b&0x1F));}}const bg=new Map([["mouse",0],["pen",1],["touch",2]]);const Y=[["fullscreenchange","fullscreenerror"],["pointerlockchange","pointerlockerror"],];const ct=[["connectedCallback","connected"],["disconnectedCallback","disconnected"],["attributeChangedCallback","attributechanged"],];class bh extends Event{constructor(cu){super("animationframe");this.time=cu;}}class aI extends Event{constructor(cv,cw){super("viewtransitionend");this.animated=cv;this.error=cw;}}function bi(cx,bj,bk){let q=cx.firstChild;while(q!==null){const cy=q.nextSibling;if(q.nodeType===Node.ELEMENT_NODE&&q.namespaceURI===bf&&bj.has(q.localName)){for(const Z of[...q.attributes]){if(!bk.has(Z.name)||Z.name.startsWith("on")||cz.test(Z.value.replace(cA,""))){q.removeAttributeNode(Z);}}bi(q.localName==="template"?q.content:q,bj,bk);}else if(q.nodeType!==Node.TEXT_NODE){q.remove();}q=cy;}}const cz=/^[\u0000-\u0020]*javascript:/i;const cA=/[\t\n\r]/g;function cB(cC,aa){function bl(bm){const aJ=typeof bm==="string"?Number(bm):NaN;return Number.isInteger(aJ)?[aJ&0xFFFFFF,aJ>>>24]:null;}return new Proxy(cC,{get(bn,bo){const x=bl(bo);if(x===null){return Reflect.get(bn,bo);}if(aa[x[0]]===undefined){throw new Error("node "+x[0]+" was used before it was stored");}if(aa[x[0]]!==x[1]){throw new Error("node "+x[0]+" was used with the stale generation "+x[1]+", the current generation is "+aa[x[0]]);}return bn[x[0]];},set(bp,bq,br){const ab=bl(bq);if(ab===null){return Reflect.set(bp,bq,br);}aa[ab[0]]=ab[1];bp[ab[0]]=br;return true;},});}export class JsInterpreter{constructor(cD,cE,cF,cG,cH,cI,cJ,cK){X=cJ.split(",");w=cK.split(",");this.l;this.nodeTable=[];this.generations=[];this.nodes=cI?cB(this.nodeTable,this.generations):this.nodeTable;this.parents=[];this.UpdateMemory(cD);this.lp;this.ls;this.metadata_ptr=cE;this.ptr_ptr=cF;this.str_ptr_ptr=cG;this.str_len_ptr=cH;this.s="";this.o=0;this.decoder=new TextDecoder();this.idSize=1;this.pools=new Map();this.cachedValues=[];this.eventHandler=null;this.queryHandler=null;this.listeners=[];this.coalescedListeners=[];this.pendingEvents=[];this.nodeListeners=new WeakMap();this.mediaQueries=new Map();this.lockHandlers=[null,null];this.canvases=new Map();this.resizeObserver=null;this.frameHandlers=new Set();this.frameRequest=0;this.lifecycleHandlers=new Map();this.wrappedElements=new Set();this.define=null;this.wrappedDefine=null;if(typeof customElements!=="undefined"){const cL=this.define=customElements.define;const cM=this;this.wrappedDefine=customElements.define=function(bs,bt,cN){cM.wrapLifecycle(bs,bt.prototype);return cL.call(this,bs,bt,cN);};}this.composing=null;this.pendingValues=new Map();this.compositionListeners=[["compositionstart",(cO)=>{this.composing=cO.target;}],["compositionend",()=>{this.composing=null;for(const[cP,cQ]of this.pendingValues){this.writeValue(cP,cQ);}this.pendingValues.clear();}],];for(const[cR,cS]of this.compositionListeners){document.addEventListener(cR,cS,true);}this.formats=new Map();this.allowlists=new Map();a=this;}UpdateMemory(bu){this.mem=bu;this.v=new DataView(bu.buffer);}Work(){this.prepare();for(;;){b=this.v.getUint32(this.u,true);this.u+=4;if(z())return;b>>>=8;if(z())return;b>>>=8;if(z())return;b>>>=8;if(z())return;}}WorkChecked(){let bv,ac;this.prepare();try{for(;;){bv=this.u;b=this.v.getUint32(this.u,true);this.u+=4;for(ac=0;ac<4;ac++){if(z())return null;b>>>=8;}}}catch(cT){return[bv+ac-this.lp,String(cT)];}}WorkProfiled(){let bw,ad,bx=null,by=0;const cU=performance.now();performance.mark("sledgehammer-flush-start");this.prepare();try{run:for(;;){bw=this.u;b=this.v.getUint32(this.u,true);this.u+=4;for(ad=0;ad<4;ad++){if(z())break run;by++;b>>>=8;}}}catch(cV){bx=[bw+ad-this.lp,String(cV)];}performance.mark("sledgehammer-flush-end");const bz=performance.measure("sledgehammer-flush","sledgehammer-flush-start","sledgehammer-flush-end");return[bx,by,bz?bz.duration:performance.now()-cU];}WorkIdle(cW,cX){let bA,ae,af;const aK=this.l;if(cX){if(this.v.buffer!==this.mem.buffer){this.v=new DataView(this.mem.buffer);}af=this.idle.start;this.u=this.idle.pos;this.s=this.idle.s;this.o=this.idle.o;this.l=this.idle.l;}else{this.prepare();af=this.lp;}try{for(;;){bA=this.u;b=this.v.getUint32(this.u,true);this.u+=4;for(ae=0;ae<4;ae++){if(z()){this.idle=null;this.l=aK;return true;}b>>>=8;}if(performance.now()>=cW){this.idle={start:af,pos:this.u,s:this.s,o:this.o,l:this.l};this.l=aK;return false;}}}catch(cY){this.idle=null;this.l=aK;return[bA+ae-af,String(cY)];}}WorkBytes(bB,cZ){let bC,ag;const c0=this.v,c1=this.lp,c2=this.u,c3=this.s,c4=this.o,c5=this.l;const bD=new Uint8Array(bB.length+4);bD.set(bB);this.v=new DataView(bD.buffer);this.u=0;this.lp=0;this.s=this.decoder.decode(cZ);this.o=0;try{for(;;){bC=this.u;b=this.v.getUint32(this.u,true);this.u+=4;for(ag=0;ag<4;ag++){if(z())return null;b>>>=8;}}}catch(c6){return[bC+ag,String(c6)];}finally{this.v=c0;this.lp=c1;this.u=c2;this.s=c3;this.o=c4;this.l=c5;}}prepare(){if(this.v.buffer!==this.mem.buffer){this.v=new DataView(this.mem.buffer);}B=this.v.getUint8(this.metadata_ptr);if((B>>>4)!==FORMAT_VERSION){throw new Error("sledgehammer batch was encoded with format version "+(B>>>4)+" but the interpreter reads format version "+FORMAT_VERSION+". The interpreter and the wasm module are from different releases");}if(B&0x01){this.lp=this.v.getUint32(this.ptr_ptr,true);}this.u=this.lp;if(B&0x04){t=this.v.getUint32(this.str_len_ptr,true);if(B&0x02){this.ls=this.v.getUint32(this.str_ptr_ptr,true);}if(B&0x08){v=this.ls;this.s="";be=v+((t/4)|0)*4;while(v<be){r=this.v.getUint32(v);this.s+=String.fromCharCode(r>>24,(r&0x00FF0000)>>16,(r&0x0000FF00)>>8,(r&0x000000FF));v+=4;}switch(this.ls+t-v){case 3:r=this.v.getUint32(v);this.s+=String.fromCharCode(r>>24,(r&0x00FF0000)>>16,(r&0x0000FF00)>>8);break;case 2:r=this.v.getUint16(v);this.s+=String.fromCharCode(r>>8,r&0xFF);break;case 1:this.s+=String.fromCharCode(this.v.getUint8(v));break;case 0:break;}}else{this.s=this.decoder.decode(new DataView(this.v.buffer,this.ls,t));}this.o=0;}}createElement(){h=this.v.getUint32(this.u,true);p=h&0xFF;switch(p){case 255:this.u+=4;p=document.createElement(X[(h&0xFF00)>>>8],this.s.substring(this.o,this.o+=(h&0xFFFF0000)>>>16));return p;case 254:this.u+=3;p=this.s.substring(this.o,this.o+=(h&0xFFFF00)>>>8);return this.pooled(p)||document.createElement(p);case 253:this.u+=3;p=this.s.substring(this.o,this.o+=(h&0xFFFF00)>>>8);p=document.createElementNS(this.s.substring(this.o,this.o+=this.v.getUint16(this.u,true)),p);this.u+=2;return p;default:this.u++;return this.pooled(X[p])||document.createElement(X[p]);}}createFullElement(){let P;h=this.v.getUint8(this.u++);if(h&0x1){P=this.v.getUint32(this.u,true);this.u+=4;}if(h&0x2){c=this.createTextNode(this.s.substring(this.o,this.o+=this.v.getUint16(this.u,true)));this.u+=2;if(P!==null){this.nodes[P]=c;}return c;}else{const C=this.createElement();h=this.v.getUint16(this.u,true);this.u+=2;bd=h&0xFF;const c7=(h&0xFF00)>>>8;for(e=0;e<bd;e++){h=this.v.getUint32(this.u,true);i=h&0xFF;switch(i){case 255:this.u+=4;i=w[(h&0xFF00)>>>8];n=this.s.substring(this.o,this.o+=(h&0xFFFF0000)>>>16);C.setAttributeNS(n,i,this.s.substring(this.o,this.o+=this.v.getUint16(this.u,true)));this.u+=2;break;case 254:this.u++;h=this.v.getUint32(this.u,true);this.u+=4;i=this.s.substring(this.o,this.o+=h&0xFFFF);C.setAttribute(i,this.s.substring(this.o,this.o+=(h&0xFFFF0000)>>>16));break;case 253:this.u+=3;i=this.s.substring(this.o,this.o+=(h&0xFFFF00)>>>8);h=this.v.getUint32(this.u,true);this.u+=4;n=this.s.substring(this.o,this.o+=h&0xFFFF);l=this.s.substring(this.o,this.o+=(h&0xFFFF0000)>>>16);C.setAttributeNS(n,i,l);break;default:this.u+=3;C.setAttribute(w[i],this.s.substring(this.o,this.o+=(h&0xFFFF00)>>>8));break;}}for(let bE=0;bE<c7;bE++){C.appendChild(this.createFullElement());}if(P!==null){this.nodes[P]=C;}return C;}}hintNodeCapacity(c8){for(e=this.nodeTable.length;e<c8;e++){this.nodeTable.push(null);}}createTextNode(bF){const aL=this.pooled("#text");if(aL===undefined){return document.createTextNode(bF);}aL.data=bF;return aL;}pooled(c9){if(this.pools.size===0){return undefined;}const bG=this.pools.get(c9);return bG===undefined?undefined:bG.nodes.pop();}setPoolSize(aM,ah){const ai=this.pools.get(aM);if(ah===0){this.pools.delete(aM);}else if(ai===undefined){this.pools.set(aM,{size:ah,nodes:[]});}else{ai.size=ah;ai.nodes.length=Math.min(ai.nodes.length,ah);}}recycle(bH){if(bH.parentNode===null){this.recycleTree(bH);}}recycleTree(o){let aj=o.firstChild,bI;while(aj!==null){bI=aj.nextSibling;this.recycleTree(aj);aj=bI;}const ak=this.pools.get(o.nodeType===3?"#text":o.namespaceURI===bf?o.localName:null);if(ak!==undefined&&ak.nodes.length<ak.size){o.remove();const al=this.nodeListeners.get(o);if(al!==undefined){for(let am=0;am<al.length;am+=2){o.removeEventListener(al[am],al[am+1]);}this.nodeListeners.delete(o);}if(o.nodeType===1){o.textContent="";while(o.attributes.length>0){o.removeAttributeNode(o.attributes[0]);}}ak.nodes.push(o);}}listener(aN){let aO=this.listeners[aN];if(aO===undefined){aO=this.listeners[aN]=(da)=>this.dispatch(aN,da);}return aO;}coalescedListener(aP){let aQ=this.coalescedListeners[aP];if(aQ===undefined){aQ=this.coalescedListeners[aP]=(db)=>this.coalesce(aP,db);}return aQ;}coalesce(bJ,Q){const D=this.pendingEvents;for(let I=0;I<D.length;I+=3){if(D[I]===bJ&&D[I+1]===Q.currentTarget&&D[I+2].type===Q.type){D[I+2]=Q;return;}}if(D.length===0){requestAnimationFrame(()=>this.dispatchPending());}D.push(bJ,Q.currentTarget,Q);}dispatchPending(){const aR=this.pendingEvents;this.pendingEvents=[];for(let an=0;an<aR.length;an+=3){this.dispatch(aR[an],aR[an+2]);}}addListener(ao,ap,bK,bL){const aq=bL?this.coalescedListener(bK):this.listener(bK);let E=this.nodeListeners.get(ao);if(E===undefined){E=[];this.nodeListeners.set(ao,E);}for(let ar=0;ar<E.length;ar+=2){if(E[ar]===ap&&E[ar+1]===aq){return;}}if(bL){ao.addEventListener(ap,aq,{passive:true});}else{ao.addEventListener(ap,aq);}E.push(ap,aq);}removeListener(at,au,aS){const bM=this.listener(aS);const bN=this.coalescedListener(aS);at.removeEventListener(au,bM);at.removeEventListener(au,bN);const J=this.nodeListeners.get(at);if(J!==undefined){for(let K=J.length-2;K>=0;K-=2){if(J[K]===au&&(J[K+1]===bM||J[K+1]===bN)){J.splice(K,2);}}}const R=this.pendingEvents;for(let L=0;L<R.length;L+=3){if(R[L]===aS&&R[L+1]===at&&R[L+2].type===au){R.splice(L,3);break;}}}subscribeMediaQuery(bO,aT){const aU=aT+" "+bO;if(this.mediaQueries.has(aU)){return;}const S=matchMedia(bO);S.addEventListener("change",this.listener(aT));this.mediaQueries.set(aU,S);queueMicrotask(()=>{if(this.mediaQueries.get(aU)===S){this.dispatch(aT,new MediaQueryListEvent("change",{media:S.media,matches:S.matches}));}});}unsubscribeMediaQuery(dc,bP){const bQ=bP+" "+dc;const bR=this.mediaQueries.get(bQ);if(bR!==undefined){bR.removeEventListener("change",this.listener(bP));this.mediaQueries.delete(bQ);}}subscribeAnimationFrames(dd){this.frameHandlers.add(dd);if(this.frameRequest===0){this.frameRequest=requestAnimationFrame((de)=>this.animationFrame(de));}}unsubscribeAnimationFrames(df){this.frameHandlers.delete(df);if(this.frameHandlers.size===0&&this.frameRequest!==0){cancelAnimationFrame(this.frameRequest);this.frameRequest=0;}}animationFrame(dg){this.frameRequest=requestAnimationFrame((dh)=>this.animationFrame(dh));for(const bS of[...this.frameHandlers]){if(this.frameHandlers.has(bS)){this.dispatch(bS,new bh(dg));}}}observeCanvasSize(M,av,di){if(!(M instanceof HTMLCanvasElement)){throw new Error(M.nodeName+" is not a canvas element");}if(av===-1){av=this.idOf(M);if(av===-1){throw new Error("the canvas must be stored with an id to be observed");}}if(this.resizeObserver===null){this.resizeObserver=new ResizeObserver((dj)=>this.resizeCanvases(dj));}this.canvases.set(M,[av,di]);try{this.resizeObserver.observe(M,{box:"device-pixel-content-box"});}catch(dR){this.resizeObserver.observe(M);}}unobserveCanvasSize(bT){if(this.canvases.delete(bT)){this.resizeObserver.unobserve(bT);}}resizeCanvases(dk){for(const T of dk){const aV=this.canvases.get(T.target);if(aV===undefined){continue;}const aw=T.target;const aW=devicePixelRatio;const bU=T.contentRect.width;const bV=T.contentRect.height;const ax=T.devicePixelContentBoxSize;aw.width=ax!==undefined?ax[0].inlineSize:Math.round(bU*aW);aw.height=ax!==undefined?ax[0].blockSize:Math.round(bV*aW);if(this.eventHandler===null){continue;}const aX=new Uint8Array(40);const F=new DataView(aX.buffer);const bW="canvasresize";aX[0]=13;F.setUint16(2,bW.length,true);F.setUint32(4,aV[0],true);F.setFloat64(8,bU,true);F.setFloat64(16,bV,true);F.setFloat64(24,aW,true);F.setUint32(32,aw.width,true);F.setUint32(36,aw.height,true);this.eventHandler(aV[1],aX,bW);}}startViewTransition(bX,dl,dm){let ay=null;const bY=()=>{const bZ=this.WorkBytes(dl,dm);if(bZ!==null){ay=bZ[1];throw new Error(ay);}};if(typeof document.startViewTransition!=="function"){queueMicrotask(()=>{try{bY();}catch(dS){}this.dispatch(bX,new aI(false,ay));});return;}let b0=true;const aY=document.startViewTransition(bY);aY.ready.catch(()=>{b0=false;});aY.updateCallbackDone.catch(()=>{});const b1=()=>this.dispatch(bX,new aI(b0,ay));aY.finished.then(b1,b1);}format(b2,aZ,a0){const b3=(b2?"d":"n")+aZ+"\0"+a0;let az=this.formats.get(b3);if(az===undefined){const b4=aZ===""?undefined:aZ;const dn=b2?new Intl.DateTimeFormat(b4,JSON.parse(a0)):new Intl.NumberFormat(b4,JSON.parse(a0));az=dn.format;this.formats.set(b3,az);}return az;}decodeImage(b5,b6){if(typeof b5.decode!=="function"){throw new Error("the node is not an image");}b5.decode().then(()=>this.answer(b6,"true"),()=>this.answer(b6,"false"));}setSanitizedHtml(a1,b7,a2,a3){const b8=a2+"\0"+a3;let G=this.allowlists.get(b8);if(G===undefined){G=[new Set(a2===""?[]:a2.split(",")),new Set(a3===""?[]:a3.split(","))];this.allowlists.set(b8,G);}if(typeof a1.setHTML==="function"){a1.setHTML(b7,{sanitizer:{elements:[...G[0]],attributes:[...G[1]]}});return;}const a4=document.createElement("template");a4.innerHTML=b7;bi(a4.content,G[0],G[1]);a1.replaceChildren(a4.content);}routeLockEvents(U,b9){if(this.lockHandlers[U]!==null){for(const dp of Y[U]){document.removeEventListener(dp,this.listener(this.lockHandlers[U]));}}this.lockHandlers[U]=b9;for(const dq of Y[U]){document.addEventListener(dq,this.listener(b9));}}observeCustomElement(V,dr){if(customElements.get(V)!==undefined&&!this.wrappedElements.has(V)){throw new Error("the custom element "+V+" was defined before the interpreter was created, so its lifecycle callbacks cannot be observed");}let aA=this.lifecycleHandlers.get(V);if(aA===undefined){aA=new Set();this.lifecycleHandlers.set(V,aA);}aA.add(dr);}unobserveCustomElement(ca,ds){const a5=this.lifecycleHandlers.get(ca);if(a5!==undefined){a5.delete(ds);if(a5.size===0){this.lifecycleHandlers.delete(ca);}}}wrapLifecycle(dt,cb){this.wrappedElements.add(dt);const du=this;for(const[cc,dv]of ct){const aB=cb[cc];if(aB!==undefined&&aB.sledgehammerLifecycle){continue;}const cd=function(...ce){if(aB!==undefined){aB.apply(this,ce);}du.lifecycle(this,dv,ce);};cd.sledgehammerLifecycle=true;cb[cc]=cd;}}lifecycle(cf,cg,u){const ch=this.lifecycleHandlers.get(cf.localName);if(ch===undefined){return;}queueMicrotask(()=>{const ci=this.idOf(cf);if(this.eventHandler===null||ci===-1){return;}const aC=new Uint8Array(24);const aD=new DataView(aC.buffer);let a6=cg;aC[0]=5;aD.setUint16(2,a6.length,true);aD.setUint32(4,ci,true);if(cg==="attributechanged"){aC[8]=1|(u[1]!==null?2:0)|(u[2]!==null?4:0);aD.setUint16(10,u[0].length,true);aD.setUint16(12,u[1]!==null?u[1].length:0,true);a6+=u[0]+(u[1]!==null?u[1]:"")+(u[2]!==null?u[2]:"");}for(const dw of ch){this.eventHandler(dw,aC,a6);}});}dispatch(dx,d){if(this.eventHandler===null){return;}const aE=typeof TouchEvent!=="undefined"&&d instanceof TouchEvent;let N,aF;if(aE){N=Math.min(d.touches.length,255);aF=Math.min(d.changedTouches.length,255);}const g=new Uint8Array(d instanceof PointerEvent?48:aE?24+16*(N+aF):24);const m=new DataView(g.buffer);let A=d.type;m.setUint16(2,A.length,true);if(d instanceof MouseEvent||d instanceof KeyboardEvent||aE){g[1]=(d.altKey?1:0)|(d.ctrlKey?2:0)|(d.metaKey?4:0)|(d.shiftKey?8:0);}if(d instanceof MouseEvent){g[0]=1;m.setInt16(4,d.button,true);m.setUint16(6,d.buttons,true);m.setFloat64(8,d.clientX,true);m.setFloat64(16,d.clientY,true);if(d instanceof PointerEvent){g[0]=9;m.setInt32(24,d.pointerId,true);m.setFloat32(28,d.pressure,true);m.setFloat32(32,d.width,true);m.setFloat32(36,d.height,true);g[40]=bg.has(d.pointerType)?bg.get(d.pointerType):3;g[41]=d.isPrimary?1:0;}}else if(aE){g[0]=10;g[4]=N;g[5]=aF;for(let y=0;y<N+aF;y++){const aG=y<N?d.touches[y]:d.changedTouches[y-N];m.setInt32(24+y*16,aG.identifier,true);m.setFloat32(28+y*16,aG.clientX,true);m.setFloat32(32+y*16,aG.clientY,true);m.setFloat32(36+y*16,aG.force,true);}}else if(d instanceof KeyboardEvent){g[0]=2;g[4]=d.repeat?1:0;g[5]=d.location;m.setUint16(6,d.key.length,true);A+=d.key+d.code;}else if(d instanceof CompositionEvent){g[0]=11;A+=d.data;}else if(d instanceof bh){g[0]=12;m.setFloat64(8,d.time,true);}else if(d instanceof aI){g[0]=14;g[4]=d.animated?1:0;if(d.error!==null){g[5]=1;A+=d.error;}}else if(d instanceof MediaQueryListEvent){g[0]=4;g[4]=d.matches?1:0;A+=d.media;}else if(typeof ToggleEvent!=="undefined"&&d instanceof ToggleEvent){g[0]=6;g[4]=d.oldState==="open"?1:0;g[5]=d.newState==="open"?1:0;}else if(d.type==="close"&&d.target instanceof HTMLDialogElement){g[0]=7;A+=d.target.returnValue;}else if(d.type.startsWith("fullscreen")){g[0]=8;g[4]=document.fullscreenElement!==null?1:0;}else if(d.type.startsWith("pointerlock")){g[0]=8;g[4]=document.pointerLockElement!==null?1:0;}else if(d.target!==null&&typeof d.target.value==="string"){g[0]=3;g[4]=d.target.checked?1:0;g[5]=d.isComposing?1:0;A+=d.target.value;}const aH=this.eventHandler(dx,g,A);if(aH[0]&1){d.preventDefault();}if(aH[0]&2){d.stopPropagation();}if(aH[1]!==undefined){this.setValue(d.target,aH[1]);}}setValue(a7,cj){if(a7===this.composing){this.pendingValues.set(a7,cj);}else{this.writeValue(a7,cj);}}writeValue(W,a8){const ck=W.value;if(ck===a8){return;}const cl=W.selectionEnd;W.value=a8;if(W===document.activeElement&&typeof cl==="number"){const cm=Math.max(a8.length-(ck.length-cl),0);W.setSelectionRange(cm,cm);}}SetEventHandler(dy){this.eventHandler=dy;}answer(dz,dA){if(this.queryHandler!==null){this.queryHandler(dz,dA);}}answerNode(dB,cn){const co=cn===null?-1:this.idOf(cn);this.answer(dB,co===-1?null:String(co));}idOf(dC){const a9=this.nodeTable.indexOf(dC);if(a9===-1){return-1;}return a9+(this.generations[a9]||0)*0x1000000;}SetQueryHandler(dD){this.queryHandler=dD;}decodeU32(){this.u+=4;return this.v.getUint32(this.u-4,true);}SetNode(dE,dF){this.nodes[dE]=dF;}GetNode(dG){return this.nodes[dG];}CopyNodes(ba,cp){if(cp===undefined){ba.nodeTable.forEach((dH,bb)=>{this.nodeTable[bb]=dH;this.generations[bb]=ba.generations[bb];});return;}for(const cq of cp){let bc;try{bc=ba.nodes[cq];}catch(dT){continue;}if(bc!==undefined){this.nodes[cq]=bc;}}}Dispose(){this.eventHandler=null;this.queryHandler=null;this.pendingEvents=[];for(const[dI,dJ]of this.mediaQueries){dJ.removeEventListener("change",this.listener(parseInt(dI)));}this.mediaQueries.clear();for(let O=0;O<Y.length;O++){if(this.lockHandlers[O]!==null){for(const dK of Y[O]){document.removeEventListener(dK,this.listener(this.lockHandlers[O]));}this.lockHandlers[O]=null;}}this.frameHandlers.clear();if(this.frameRequest!==0){cancelAnimationFrame(this.frameRequest);this.frameRequest=0;}if(this.resizeObserver!==null){this.resizeObserver.disconnect();this.resizeObserver=null;}this.canvases.clear();this.lifecycleHandlers.clear();for(const[dL,dM]of this.compositionListeners){document.removeEventListener(dL,dM,true);}if(this.wrappedDefine!==null&&customElements.define===this.wrappedDefine){customElements.define=this.define;}}}
\ No newline at end of file
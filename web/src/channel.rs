@@ -3,31 +3,408 @@
 //!
 //!
 
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
+use sledgehammer_encoder::QueryId;
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
+use sledgehammer_encoder::WIRE_FORMAT_VERSION;
 use sledgehammer_encoder::{
-    batch::{Batch, PreparedBatch},
-    MaybeId, NodeId, Op, TextBuilder, WritableText,
+    batch::{Batch, FinalizedBatch, PreparedBatch, StrWriter},
+    head::ResourceHint,
+    intl::{DateTimeFormat, NumberFormat},
+    keys::NodeKeys,
+    mutation::MutationWriter,
+    sanitize::Allowlist,
+    HandlerId, MaybeId, NodeId, Op, StorageArea, TextBuilder, WritableText,
 };
+use std::{collections::VecDeque, fmt::Display};
+
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+#[cfg(target_arch = "wasm32")]
 use web_sys::Node;
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::headless::HeadlessDom;
+#[cfg(all(target_arch = "wasm32", feature = "web-sys-backend"))]
+use crate::web_sys_backend::WebSysInterpreter;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{events::Event, headless::NodeRef};
+use crate::{
+    events::Handlers,
+    queries::{Queries, Query},
+    recording::Recording,
+    reload::KeepNodes,
+    subtree::{SubtreeChannel, SubtreeStats, Subtrees},
+    ElementBuilder, FragmentBuilder, IntoAttribue, IntoElement, OwnedElementBuilder,
+};
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
 use crate::{
-    update_last_memory, work_last_created, ElementBuilder, IntoAttribue, IntoElement,
-    JsInterpreter, MSG_METADATA_PTR, MSG_PTR_PTR, STR_LEN_PTR, STR_PTR_PTR,
+    work_last_created, work_last_created_checked, work_last_created_idle,
+    work_last_created_profiled, JsInterpreter, MSG_METADATA_PTR, MSG_PTR_PTR, STR_LEN_PTR,
+    STR_PTR_PTR,
 };
 
+/// The number of idle batches that can wait to be applied before new batches are merged into the last waiting batch
+pub const DEFAULT_IDLE_BACKLOG_LIMIT: usize = 16;
+
 /// Tracks if a interpreter has been created. Used to prevent multiple interpreters from being created.
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
 static mut INTERPRETER_EXISTS: bool = false;
 
+/// An error that was thrown by the interpreter while exicuting a batch of operations.
+/// Errors are only reported in debug builds, in release builds the interpreter does not check for errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DomError {
+    /// The offset in bytes of the operation that failed from the start of the message
+    pub offset: usize,
+    /// The message of the javascript error that was thrown
+    pub message: String,
+}
+
+impl DomError {
+    #[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
+    fn from_js(value: JsValue) -> Result<(), Self> {
+        if value.is_null() {
+            return Ok(());
+        }
+        let info = js_sys::Array::from(&value);
+        Err(Self {
+            offset: info.get(0).as_f64().unwrap_or_default() as usize,
+            message: info.get(1).as_string().unwrap_or_default(),
+        })
+    }
+}
+
+impl Display for DomError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the operation at offset {} failed: {}",
+            self.offset, self.message
+        )
+    }
+}
+
+impl std::error::Error for DomError {}
+
+/// Measurements of a single flush. These are only collected when profiling is enabled with [`MsgChannel::set_profiling`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlushStats {
+    /// The number of bytes of operations sent to the interpreter
+    pub msg_bytes: usize,
+    /// The number of bytes of strings sent to the interpreter
+    pub str_bytes: usize,
+    /// The number of operations the interpreter executed, not including the final stop operation
+    pub ops: usize,
+    /// The time in milliseconds the interpreter spent applying the batch, as measured by `performance.measure`, or `performance.now` with the `web-sys-backend` feature
+    pub duration_ms: f64,
+}
+
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
+impl FlushStats {
+    fn from_js(msg: &[u8], str_buf: &[u8], value: JsValue) -> Result<Self, DomError> {
+        let info = js_sys::Array::from(&value);
+        DomError::from_js(info.get(0))?;
+        Ok(Self {
+            msg_bytes: msg.len(),
+            str_bytes: str_buf.len(),
+            ops: info.get(1).as_f64().unwrap_or_default() as usize,
+            duration_ms: info.get(2).as_f64().unwrap_or_default(),
+        })
+    }
+}
+
 /// The [`MsgChannel`] handles communication with the dom. It allows you to send batched operations to the dom.
 /// All of the functions that are not marked otherwise are qued and not exicuted imidately. When you want to exicute the que you have to call [`MsgChannel::flush`].
 /// There should only be one [`MsgChannel`] per application.
+///
+/// With the `web-sys-backend` feature, operations are applied with web-sys calls from rust instead of the javascript interpreter. On native targets, operations are applied to a [`HeadlessDom`] that can be read with [`MsgChannel::dom`].
 pub struct MsgChannel {
-    pub(crate) js_interpreter: JsInterpreter,
-    last_mem_size: usize,
+    backend: Backend,
     batch: Batch,
+    profile: bool,
+    /// If redundant operations are removed before each flush
+    optimize: bool,
+    last_flush_stats: Option<FlushStats>,
+    /// Batches waiting to be applied with [`MsgChannel::run_idle`]
+    idle: VecDeque<Batch>,
+    /// The idle batch that ran out of time and needs to be resumed
+    idle_started: Option<FinalizedBatch>,
+    /// The number of idle batches that can wait before new batches are merged into the last one. Uses [`DEFAULT_IDLE_BACKLOG_LIMIT`] if it is not set
+    idle_backlog_limit: Option<usize>,
+    /// The batches applied since [`MsgChannel::start_recording`] was called
+    recording: Option<Recording>,
+    /// The nodes registered with string keys
+    keys: NodeKeys,
+    /// The handlers listeners dispatch events to
+    handlers: Handlers,
+    /// The queries the interpreter has not answered yet
+    queries: Queries,
+    /// The subtrees that are stitched into the next flush
+    subtrees: Subtrees,
+}
+
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
+type Backend = JsBackend;
+#[cfg(all(target_arch = "wasm32", feature = "web-sys-backend"))]
+type Backend = WebSysInterpreter;
+#[cfg(not(target_arch = "wasm32"))]
+type Backend = HeadlessDom;
+
+/// The javascript interpreter
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
+struct JsBackend {
+    interpreter: JsInterpreter,
+    /// If the interpreter was supplied by the user. Custom interpreters are called through their methods instead of the functions exported from the built-in interpreter
+    custom: bool,
+    /// Creates the interpreter, and a new one when the interpreter is restarted
+    create: Box<CreateInterpreter>,
+    /// The function the interpreter calls with the encoded events that reach listeners. It returns the flags to apply to the event and the value to write back to the target. It is kept alive as long as the channel and passed to every interpreter the channel creates
+    event_handler: Closure<dyn FnMut(u32, Vec<u8>, String) -> JsValue>,
+    /// The function the interpreter calls with the values it reads to answer queries
+    query_handler: Closure<dyn FnMut(u32, Option<String>)>,
+}
+
+/// Creates an interpreter with the same arguments as the constructor of the built-in `JsInterpreter` class
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
+type CreateInterpreter =
+    dyn Fn(JsValue, usize, usize, usize, usize, bool, &str, &str) -> JsInterpreter;
+
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
+fn create_interpreter(create: &CreateInterpreter) -> JsInterpreter {
+    unsafe {
+        create(
+            wasm_bindgen::memory(),
+            MSG_METADATA_PTR as usize,
+            MSG_PTR_PTR as usize,
+            STR_PTR_PTR as usize,
+            STR_LEN_PTR as usize,
+            cfg!(feature = "debug"),
+            sledgehammer_encoder::element::TABLE,
+            sledgehammer_encoder::attribute::TABLE,
+        )
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
+impl JsBackend {
+    /// If the interpreter has a method. Custom interpreters do not need to support events, queries or restarts
+    fn supports(&self, method: &str) -> bool {
+        !self.custom || js_sys::Reflect::has(&self.interpreter, &method.into()).unwrap_or(false)
+    }
+
+    /// Pass the functions that dispatch events and answer queries to the interpreter
+    fn connect(&self) {
+        if self.supports("SetEventHandler") {
+            self.interpreter
+                .SetEventHandler(self.event_handler.as_ref().unchecked_ref());
+        }
+        if self.supports("SetQueryHandler") {
+            self.interpreter
+                .SetQueryHandler(self.query_handler.as_ref().unchecked_ref());
+        }
+    }
+
+    /// Dispose the interpreter and replace it with a new one that keeps the nodes stored with the ids in `keep`, or every stored node if it is None
+    fn restart(&mut self, keep: Option<&[NodeId]>) {
+        // dispose the old interpreter first, so the new interpreter wraps the original `customElements.define`
+        if self.supports("Dispose") {
+            self.interpreter.Dispose();
+        }
+        let previous = std::mem::replace(&mut self.interpreter, create_interpreter(&*self.create));
+        self.connect();
+        if self.supports("CopyNodes") {
+            let ids = keep.map(|ids| ids.iter().map(|id| id.0).collect());
+            self.interpreter.CopyNodes(&previous, ids);
+        }
+        // the new interpreter has not read where the batches are stored yet
+        unsafe {
+            let mut_metadata_ptr: *mut u8 = std::mem::transmute(MSG_METADATA_PTR);
+            *mut_metadata_ptr = 255;
+        }
+    }
+
+    fn set_node(&mut self, id: NodeId, node: Node) {
+        self.interpreter.SetNode(id.0, node);
+    }
+
+    fn get_node(&self, id: NodeId) -> Node {
+        self.interpreter.GetNode(id.0)
+    }
+
+    fn run(
+        &mut self,
+        msg: &[u8],
+        str_buf: &[u8],
+        profile: bool,
+    ) -> Result<Option<FlushStats>, DomError> {
+        run_batch(
+            self.custom.then_some(&self.interpreter),
+            msg,
+            str_buf,
+            profile,
+        )
+    }
+
+    fn run_idle(
+        &mut self,
+        msg: &[u8],
+        str_buf: &[u8],
+        deadline: f64,
+        resume: bool,
+    ) -> Result<bool, DomError> {
+        if !resume {
+            prepare_batch(msg, str_buf);
+        }
+        let result = if self.custom {
+            self.interpreter.WorkIdle(deadline, resume)
+        } else {
+            work_last_created_idle(deadline, resume)
+        };
+        match result.as_bool() {
+            Some(finished) => Ok(finished),
+            None => DomError::from_js(result).map(|_| true),
+        }
+    }
+}
+
+/// Convert the id an interpreter answers a query for a node with
+fn parse_id(id: Option<String>) -> Option<NodeId> {
+    id?.parse().ok().map(NodeId)
+}
+
+/// The current time in milliseconds. Idle deadlines are measured with this clock.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn now_ms() -> f64 {
+    web_sys::window().unwrap().performance().unwrap().now()
+}
+
+/// The current time in milliseconds. Idle deadlines are measured with this clock.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn now_ms() -> f64 {
+    static START: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+    START
+        .get_or_init(std::time::Instant::now)
+        .elapsed()
+        .as_secs_f64()
+        * 1000.0
+}
+
+/// Add a batch to the recording if the channel is recording
+fn record(recording: &mut Option<Recording>, msg: &[u8], str_buf: &[u8]) {
+    if let Some(recording) = recording {
+        recording.push(now_ms(), msg, str_buf);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for MsgChannel {
+    fn default() -> Self {
+        let queries = Queries::default();
+        Self::with_backend(
+            HeadlessDom::with_queries(queries.clone()),
+            Handlers::default(),
+            queries,
+        )
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", feature = "web-sys-backend"))]
+impl Default for MsgChannel {
+    fn default() -> Self {
+        let (handlers, queries) = (Handlers::default(), Queries::default());
+        Self::with_backend(
+            WebSysInterpreter::new(handlers.clone(), queries.clone()),
+            handlers,
+            queries,
+        )
+    }
 }
 
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
 impl Default for MsgChannel {
     fn default() -> Self {
+        #[cfg(feature = "no-modules")]
+        crate::install_interpreter();
+        Self::new(
+            |mem, metadata_ptr, msg_ptr, str_ptr, str_len_ptr, debug, elements, attributes| {
+                JsInterpreter::new(
+                    mem,
+                    metadata_ptr,
+                    msg_ptr,
+                    str_ptr,
+                    str_len_ptr,
+                    debug,
+                    elements,
+                    attributes,
+                )
+            },
+            false,
+        )
+    }
+}
+
+impl MsgChannel {
+    /// Create a [`MsgChannel`] that sends operations to a custom interpreter instead of the built-in interpreter.
+    ///
+    /// The constructor is called with the same arguments as the built-in `JsInterpreter` class: `(memory, metadata_ptr, msg_ptr, str_ptr, str_len_ptr, debug, elements, attributes)`. The object it creates must implement the methods of the built-in interpreter that sledgehammer calls:
+    /// - `Work()` applies the queued batch
+    /// - `WorkChecked()` applies the queued batch and returns `null` or `[offset, message]` if an operation throws
+    /// - `WorkProfiled()` applies the queued batch and returns `[error, ops, duration_ms]`
+    /// - `WorkIdle(deadline, resume)` applies the queued batch, or continues the last idle batch if `resume` is true, until `performance.now()` passes the deadline and returns `true` if it finished, `false` if it ran out of time, or `[offset, message]` if an operation throws
+    /// - `SetNode(id, node)` and `GetNode(id)` store and read nodes
+    ///
+    /// If the interpreter has a `SetEventHandler(handler)` method, it is called with the function that dispatches events to the [`Handlers`] of the channel. Interpreters without the method cannot dispatch events from listeners.
+    ///
+    /// Likewise, a `SetQueryHandler(handler)` method is called with the function that answers [`Queries`] with the query id and the value the interpreter read.
+    ///
+    /// [`MsgChannel::restart_interpreter`] calls the constructor again. It calls `Dispose()` on the old interpreter if it has the method, and `CopyNodes(previous, ids)` on the new interpreter with the old one and the ids of the nodes to keep, or `undefined` to keep every node. Interpreters that do not have `CopyNodes` start with no nodes.
+    ///
+    /// The easiest way to extend the interpreter is to import the `JsInterpreter` class from `interpreter.js` and subclass it.
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::*;
+    /// #[wasm_bindgen(module = "/my_interpreter.js")]
+    /// extern "C" {
+    ///     #[wasm_bindgen(js_name = MyInterpreter)]
+    ///     static MY_INTERPRETER: js_sys::Function;
+    /// }
+    ///
+    /// let mut channel = MsgChannel::with_interpreter(&MY_INTERPRETER);
+    /// channel.create_element("div", None);
+    /// channel.flush().unwrap();
+    /// ```
+    #[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
+    pub fn with_interpreter(constructor: &js_sys::Function) -> Self {
+        let constructor = constructor.clone();
+        Self::new(
+            move |mem, metadata_ptr, msg_ptr, str_ptr, str_len_ptr, debug, elements, attributes| {
+                let args = js_sys::Array::of5(
+                    &mem,
+                    &metadata_ptr.into(),
+                    &msg_ptr.into(),
+                    &str_ptr.into(),
+                    &str_len_ptr.into(),
+                );
+                args.push(&debug.into());
+                args.push(&elements.into());
+                args.push(&attributes.into());
+                js_sys::Reflect::construct(&constructor, &args)
+                    .expect("failed to construct the custom interpreter")
+                    .unchecked_into()
+            },
+            true,
+        )
+    }
+
+    #[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
+    #[allow(clippy::type_complexity)]
+    fn new(
+        create: impl Fn(JsValue, usize, usize, usize, usize, bool, &str, &str) -> JsInterpreter
+            + 'static,
+        custom_interpreter: bool,
+    ) -> Self {
         unsafe {
             debug_assert!(
                 !INTERPRETER_EXISTS,
@@ -35,36 +412,68 @@ impl Default for MsgChannel {
             );
             INTERPRETER_EXISTS = true;
         }
-        debug_assert!(0x1F > Op::NoOp as u8);
+        debug_assert!(0x1F > Op::Extended as u8);
         // format!(
         //     "init: {:?}, {:?}, {:?}",
         //     unsafe { MSG_PTR_PTR as usize },
         //     unsafe { STR_PTR_PTR as usize },
         //     unsafe { STR_LEN_PTR as usize }
         // );
-        let js_interpreter = unsafe {
-            JsInterpreter::new(
-                wasm_bindgen::memory(),
-                MSG_METADATA_PTR as usize,
-                MSG_PTR_PTR as usize,
-                STR_PTR_PTR as usize,
-                STR_LEN_PTR as usize,
+        let interpreter = create_interpreter(&create);
+
+        let handlers = Handlers::default();
+        let event_handler = {
+            let handlers = handlers.clone();
+            Closure::<dyn FnMut(u32, Vec<u8>, String) -> JsValue>::new(
+                move |handler, payload: Vec<u8>, text: String| {
+                    let (flags, value) = handlers.dispatch_encoded(handler, &payload, &text);
+                    let value = value.map(JsValue::from).unwrap_or(JsValue::UNDEFINED);
+                    js_sys::Array::of2(&flags.into(), &value).into()
+                },
             )
         };
+        let queries = Queries::default();
+        let query_handler = {
+            let queries = queries.clone();
+            Closure::<dyn FnMut(u32, Option<String>)>::new(move |query, value| {
+                queries.answer(QueryId(query), value);
+            })
+        };
+        let backend = JsBackend {
+            interpreter,
+            custom: custom_interpreter,
+            create: Box::new(create),
+            event_handler,
+            query_handler,
+        };
+        backend.connect();
+
+        Self::with_backend(backend, handlers, queries)
+    }
 
+    fn with_backend(backend: Backend, handlers: Handlers, queries: Queries) -> Self {
         Self {
-            js_interpreter,
-            last_mem_size: 0,
+            backend,
             batch: Batch::default(),
+            profile: false,
+            optimize: false,
+            last_flush_stats: None,
+            idle: VecDeque::new(),
+            idle_started: None,
+            idle_backlog_limit: None,
+            recording: None,
+            keys: NodeKeys::default(),
+            handlers,
+            queries,
+            subtrees: Subtrees::default(),
         }
     }
-}
 
-impl MsgChannel {
     /// IMPORTANT: This method is exicuted immediatly and does not wait for the next flush
     ///
     /// Example:
     /// ```no_run
+    /// # use sledgehammer::*;
     /// let window = web_sys::window().unwrap();
     /// let document = window.document().unwrap();
     /// let body = document.body().unwrap();
@@ -73,49 +482,116 @@ impl MsgChannel {
     /// channel.set_node(NodeId(0), JsCast::dyn_into(body).unwrap());
     /// // no need to call flush here because set_node is exicuted immediatly
     /// ```
+    #[cfg(target_arch = "wasm32")]
     pub fn set_node(&mut self, id: NodeId, node: Node) {
-        self.js_interpreter.SetNode(id.0, node);
+        self.backend.set_node(id, node);
     }
 
     /// IMPORTANT: This method is exicuted immediatly and does not wait for the next flush
     ///
     /// Example:
     /// ```no_run
+    /// # use sledgehammer::*;
     /// let mut channel = MsgChannel::default();
     /// channel.create_element("div", Some(NodeId(0)));
-    /// channel.flush();
+    /// channel.flush().unwrap();
     /// let element = channel.get_node(NodeId(0));
     /// let text = element.text_content().map(|t| t + " + web-sys");
     /// element.set_text_content(text.as_deref());
     /// // no need to call flush here because get_node is exicuted immediatly
     /// ```
+    #[cfg(target_arch = "wasm32")]
     pub fn get_node(&mut self, id: NodeId) -> Node {
-        self.js_interpreter.GetNode(id.0)
+        self.backend.get_node(id)
+    }
+
+    /// The headless dom the operations are applied to on native targets
+    ///
+    /// Example:
+    /// ```rust
+    /// # use sledgehammer::*;
+    /// let mut channel = MsgChannel::default();
+    /// let root = channel.dom_mut().create_element("main");
+    /// channel.dom_mut().set_node(NodeId(0), root);
+    /// channel.create_element("div", None);
+    /// channel.append_child(MaybeId::Node(NodeId(0)), MaybeId::LastNode);
+    /// channel.flush().unwrap();
+    /// assert_eq!(channel.dom().outer_html(root), "<main><div></div></main>");
+    /// ```
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn dom(&self) -> &HeadlessDom {
+        &self.backend
+    }
+
+    /// The headless dom the operations are applied to on native targets. Use this to create the root nodes and assign ids to them.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn dom_mut(&mut self) -> &mut HeadlessDom {
+        &mut self.backend
     }
 
     /// Exicutes any queued operations in the order they were added
     ///
+    /// The operations are applied before flush returns, so layout and focus can be read right after it. To wait for operations queued on a [`FrameScheduler`](crate::scheduler::FrameScheduler), await [`FrameScheduler::applied`](crate::scheduler::FrameScheduler::applied).
+    ///
+    /// In debug builds, this returns an error if any of the operations throws an error in javascript. The operations before the failing operation are still applied.
+    ///
     /// Example:
     ///
     /// ```no_run
+    /// # use sledgehammer::*;
     /// let mut channel = MsgChannel::default();
     /// // this does not immediatly create a <div> or <p>
     /// channel.create_element("div", None);
     /// channel.create_element("p", None);
     /// // this creates the <div> and <p> elements
-    /// channel.flush();
+    /// channel.flush().unwrap();
     /// ```
-    pub fn flush(&mut self) {
+    pub fn flush(&mut self) -> Result<(), DomError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("flush").entered();
+        self.subtrees.stitch_into(&mut self.batch);
+        self.optimize_batch();
         self.batch.encode_op(Op::Stop);
-        run_batch(
-            &self.batch.msg,
-            &self.batch.str_buf,
-            &mut self.last_mem_size,
-        );
-        self.batch.msg.clear();
-        self.batch.current_op_batch_idx = 0;
-        self.batch.current_op_byte_idx = 3;
-        self.batch.str_buf.clear();
+        record(&mut self.recording, &self.batch.msg, &self.batch.str_buf);
+        let result = self
+            .backend
+            .run(&self.batch.msg, &self.batch.str_buf, self.profile)
+            .map(|stats| self.last_flush_stats = stats);
+        self.batch.clear();
+        result
+    }
+
+    /// Apply the queued operations like [`MsgChannel::flush`] and return a batch that undoes them. Flushing the inverse batch with this method again returns a batch that redoes them, so editors can keep undo and redo stacks of batches.
+    ///
+    /// `scratch` is an id the inverse batch stores nodes with while it restores removed nodes. Only changes inside nodes that are stored with an id are undone. Removed and moved nodes are restored as new nodes with the ids they were stored with.
+    ///
+    /// The inverse is recorded by the rust backends, so this is only available on native targets and with the `web-sys-backend` feature. Errors are checked in release builds as well.
+    ///
+    /// Example:
+    /// ```rust
+    /// # use sledgehammer::*;
+    /// let mut channel = MsgChannel::default();
+    /// # #[cfg(not(target_arch = "wasm32"))]
+    /// # { let root = channel.dom_mut().create_element("div"); channel.dom_mut().set_node(NodeId(0), root); }
+    /// channel.set_attribute(Attribute::class, "selected", MaybeId::Node(NodeId(0)));
+    /// let undo = channel.flush_with_inverse(NodeId(1)).unwrap();
+    /// // remove the class again
+    /// channel.append(undo);
+    /// let redo = channel.flush_with_inverse(NodeId(1)).unwrap();
+    /// ```
+    #[cfg(any(not(target_arch = "wasm32"), feature = "web-sys-backend"))]
+    pub fn flush_with_inverse(&mut self, scratch: NodeId) -> Result<Batch, DomError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("flush_with_inverse").entered();
+        self.subtrees.stitch_into(&mut self.batch);
+        self.optimize_batch();
+        self.batch.encode_op(Op::Stop);
+        record(&mut self.recording, &self.batch.msg, &self.batch.str_buf);
+        let result = self
+            .backend
+            .run_with_inverse(&self.batch.msg, &self.batch.str_buf, scratch);
+        self.batch.clear();
+        result
     }
 
     /// Appends a number of nodes as children of the given node.
@@ -123,12 +599,13 @@ impl MsgChannel {
     /// Example:
     ///
     /// ```no_run
+    /// # use sledgehammer::*;
     /// let mut channel = MsgChannel::default();
     /// channel.create_element("div", Some(NodeId(0)));
     /// channel.create_element("p", None);
     /// // append the <p> element to the <div> element
     /// channel.append_child(MaybeId::Node(NodeId(0)), MaybeId::LastNode);
-    /// channel.flush();
+    /// channel.flush().unwrap();
     /// ```
     pub fn append_child(&mut self, root: MaybeId, child: MaybeId) {
         self.batch.append_child(root, child)
@@ -138,12 +615,13 @@ impl MsgChannel {
     ///
     /// Example:
     /// ```no_run
+    /// # use sledgehammer::*;
     /// let mut channel = MsgChannel::default();
     /// channel.create_element("div", Some(NodeId(0)));
     /// channel.create_element("p", None);
     /// // replace the <p> element with the <div> element
     /// channel.replace_with(MaybeId::Node(NodeId(0)), MaybeId::LastNode);
-    /// channel.flush();
+    /// channel.flush().unwrap();
     /// ```
     pub fn replace_with(&mut self, root: MaybeId, node: MaybeId) {
         self.batch.replace_with(root, node)
@@ -153,12 +631,13 @@ impl MsgChannel {
     ///
     /// Example:
     /// ```no_run
+    /// # use sledgehammer::*;
     /// let mut channel = MsgChannel::default();
     /// channel.create_element("div", Some(NodeId(0)));
     /// channel.create_element("p", None);
     /// // replace the <p> element with the <div> element
-    /// channel.replace_with_nodes(MaybeId::Node(NodeId(0)), MaybeId::LastNode);
-    /// channel.flush();
+    /// channel.replace_with_nodes(MaybeId::Node(NodeId(0)), &[MaybeId::LastNode]);
+    /// channel.flush().unwrap();
     /// ```
     pub fn replace_with_nodes(&mut self, root: MaybeId, nodes: &[MaybeId]) {
         self.batch.replace_with_nodes(root, nodes)
@@ -168,12 +647,13 @@ impl MsgChannel {
     ///
     /// Example:
     /// ```no_run
+    /// # use sledgehammer::*;
     /// let mut channel = MsgChannel::default();
     /// channel.create_element("div", Some(NodeId(0)));
     /// channel.create_element("p", None);
     /// // insert the <p> element after the <div> element
     /// channel.insert_after(MaybeId::Node(NodeId(0)), MaybeId::LastNode);
-    /// channel.flush();
+    /// channel.flush().unwrap();
     /// ```
     pub fn insert_after(&mut self, root: MaybeId, node: MaybeId) {
         self.batch.insert_after(root, node)
@@ -183,12 +663,13 @@ impl MsgChannel {
     ///
     /// Example:
     /// ```no_run
+    /// # use sledgehammer::*;
     /// let mut channel = MsgChannel::default();
     /// channel.create_element("div", Some(NodeId(0)));
     /// channel.create_element("p", None);
     /// // insert the <p> element after the <div> element
     /// channel.insert_nodes_after(MaybeId::Node(NodeId(0)), &[MaybeId::LastNode]);
-    /// channel.flush();
+    /// channel.flush().unwrap();
     /// ```
     pub fn insert_nodes_after(&mut self, root: MaybeId, nodes: &[MaybeId]) {
         self.batch.insert_nodes_after(root, nodes)
@@ -198,12 +679,13 @@ impl MsgChannel {
     ///
     /// Example:
     /// ```no_run
+    /// # use sledgehammer::*;
     /// let mut channel = MsgChannel::default();
     /// channel.create_element("div", Some(NodeId(0)));
     /// channel.create_element("p", None);
     /// // insert the <p> element before the <div> element
     /// channel.insert_before(MaybeId::Node(NodeId(0)), MaybeId::LastNode);
-    /// channel.flush();
+    /// channel.flush().unwrap();
     /// ```
     pub fn insert_before(&mut self, root: MaybeId, node: MaybeId) {
         self.batch.insert_before(root, node)
@@ -213,12 +695,13 @@ impl MsgChannel {
     ///
     /// Example:
     /// ```no_run
+    /// # use sledgehammer::*;
     /// let mut channel = MsgChannel::default();
     /// channel.create_element("div", Some(NodeId(0)));
     /// channel.create_element("p", None);
     /// // insert the <p> element before the <div> element
     /// channel.insert_nodes_before(MaybeId::Node(NodeId(0)), &[MaybeId::LastNode]);
-    /// channel.flush();
+    /// channel.flush().unwrap();
     /// ```
     pub fn insert_nodes_before(&mut self, root: MaybeId, nodes: &[MaybeId]) {
         self.batch.insert_nodes_before(root, nodes)
@@ -228,11 +711,12 @@ impl MsgChannel {
     ///
     /// Example:
     /// ```no_run
+    /// # use sledgehammer::*;
     /// let mut channel = MsgChannel::default();
     /// channel.create_element("p", None);
     /// // remove the <p> element
     /// channel.remove(MaybeId::LastNode);
-    /// channel.flush();
+    /// channel.flush().unwrap();
     /// ```
     pub fn remove(&mut self, id: MaybeId) {
         self.batch.remove(id)
@@ -242,10 +726,11 @@ impl MsgChannel {
     ///
     /// Example:
     /// ```no_run
+    /// # use sledgehammer::*;
     /// let mut channel = MsgChannel::default();
     /// // create a text node with the text "Hello World"
     /// channel.create_text_node("Hello World", None);
-    /// channel.flush();
+    /// channel.flush().unwrap();
     pub fn create_text_node(&mut self, text: impl WritableText, id: Option<NodeId>) {
         self.batch.create_text_node(text, id)
     }
@@ -254,10 +739,11 @@ impl MsgChannel {
     ///
     /// Example:
     /// ```no_run
+    /// # use sledgehammer::*;
     /// let mut channel = MsgChannel::default();
     /// // create a <div> element
     /// channel.create_element("div", None);
-    /// channel.flush();
+    /// channel.flush().unwrap();
     /// ```
     pub fn create_element<'a, 'b>(&mut self, tag: impl IntoElement<'a, 'b>, id: Option<NodeId>) {
         self.batch.create_element(tag, id)
@@ -267,27 +753,98 @@ impl MsgChannel {
     ///
     /// Example:
     /// ```no_run
+    /// # use sledgehammer::*;
     /// let mut channel = MsgChannel::default();
     /// // create a text node with the text "Hello World"
     /// channel.create_text_node("Hello ", None);
     /// // set the text content of the text node to "Hello World!!!"
-    /// channel.set_text_content("World!!!", MaybeId::LastNode);
-    /// channel.flush();
+    /// channel.set_text("World!!!", MaybeId::LastNode);
+    /// channel.flush().unwrap();
     /// ```
     pub fn set_text(&mut self, text: impl WritableText, root: MaybeId) {
         self.batch.set_text(text, root)
     }
 
+    /// Set the text of a node to a number formatted by the browser with `Intl.NumberFormat`, so the locale data is not compiled into the wasm module. The headless dom has no locale data and sets the text to the number without formatting it. See [`crate::intl`]
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::{intl::*, *};
+    /// let mut channel = MsgChannel::default();
+    /// let price = NumberFormat::new().style(NumberStyle::Currency("USD"));
+    /// channel.create_text_node("", None);
+    /// // shows $19.99 in the locale en-US
+    /// channel.set_formatted_number(19.99, &price, MaybeId::LastNode);
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn set_formatted_number(&mut self, value: f64, format: &NumberFormat, root: MaybeId) {
+        self.batch.set_formatted_number(value, format, root)
+    }
+
+    /// Set the text of a node to a timestamp in milliseconds since the unix epoch formatted by the browser with `Intl.DateTimeFormat`. The headless dom sets the text to the timestamp without formatting it. See [`crate::intl`]
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::{intl::*, *};
+    /// let mut channel = MsgChannel::default();
+    /// let format = DateTimeFormat::new()
+    ///     .date_style(DateTimeStyle::Long)
+    ///     .time_zone("UTC");
+    /// channel.create_text_node("", None);
+    /// // shows November 14, 2023 in the locale en-US
+    /// channel.set_formatted_date(1_700_000_000_000.0, &format, MaybeId::LastNode);
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn set_formatted_date(&mut self, timestamp: f64, format: &DateTimeFormat, root: MaybeId) {
+        self.batch.set_formatted_date(timestamp, format, root)
+    }
+
+    /// Replace the children of a node with html from an untrusted source. The browser parses the html and removes the elements and attributes that are not in the allowlist, event handlers and `javascript:` urls before it is inserted. The headless dom cannot parse html and fails to apply the batch. See [`crate::sanitize`]
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::{sanitize::Allowlist, *};
+    /// let mut channel = MsgChannel::default();
+    /// let comment = r#"<p>first!</p><script>steal()</script>"#;
+    /// channel.create_element("div", None);
+    /// // inserts <p>first!</p>
+    /// channel.set_sanitized_html(comment, &Allowlist::DEFAULT, MaybeId::LastNode);
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn set_sanitized_html(
+        &mut self,
+        html: impl WritableText,
+        allowlist: &Allowlist,
+        root: MaybeId,
+    ) {
+        self.batch.set_sanitized_html(html, allowlist, root)
+    }
+
+    /// Set the value of a form control. Unlike setting the `value` attribute, this changes the value the control shows after the user edited it. The cursor stays the same distance from the end of the value, and the value of a control the user is composing text in with an input method is set when the composition ends. See [`crate::controlled`] to keep the value of a control in rust.
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::*;
+    /// let mut channel = MsgChannel::default();
+    /// channel.create_element("input", Some(NodeId(0)));
+    /// channel.set_value("hello", MaybeId::Node(NodeId(0)));
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn set_value(&mut self, value: impl WritableText, root: MaybeId) {
+        self.batch.set_value(value, root)
+    }
+
     /// Set the value of a node's attribute.
     ///
     /// Example:
     /// ```no_run
+    /// # use sledgehammer::*;
     /// let mut channel = MsgChannel::default();
     /// // create a <div> element
     /// channel.create_element("div", None);
     /// // set the attribute "id" to "my-div" on the <div> element
     /// channel.set_attribute(Attribute::id, "my-div", MaybeId::LastNode);
-    /// channel.flush();
+    /// channel.flush().unwrap();
     /// ```
     pub fn set_attribute<'a, 'b>(
         &mut self,
@@ -298,32 +855,114 @@ impl MsgChannel {
         self.batch.set_attribute(attr, value, root)
     }
 
+    /// Cache a value with a one byte id so it can be set as an attribute with [`MsgChannel::set_attribute_cached`] without sending the string again. Caching a value with an id that is already in use replaces the old value.
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::*;
+    /// let mut channel = MsgChannel::default();
+    /// channel.cache_value(0, "active");
+    /// channel.cache_value(1, "inactive");
+    /// channel.create_element("li", Some(NodeId(0)));
+    /// channel.set_attribute_cached(Attribute::class, 0, MaybeId::Node(NodeId(0)));
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn cache_value(&mut self, id: u8, value: impl WritableText) {
+        self.batch.cache_value(id, value)
+    }
+
+    /// Set the value of a node's attribute to a value that was cached with [`MsgChannel::cache_value`]. Toggling an attribute between a few cached values only sends the id of the value instead of the string.
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::*;
+    /// let mut channel = MsgChannel::default();
+    /// channel.cache_value(0, "active");
+    /// channel.cache_value(1, "inactive");
+    /// channel.create_element("li", Some(NodeId(0)));
+    /// channel.flush().unwrap();
+    /// for i in 0..10 {
+    ///     // toggle the class between "active" and "inactive"
+    ///     channel.set_attribute_cached(Attribute::class, i % 2, MaybeId::Node(NodeId(0)));
+    ///     channel.flush().unwrap();
+    /// }
+    /// ```
+    pub fn set_attribute_cached<'a, 'b>(
+        &mut self,
+        attr: impl IntoAttribue<'a, 'b>,
+        value: u8,
+        root: MaybeId,
+    ) {
+        self.batch.set_attribute_cached(attr, value, root)
+    }
+
     /// Remove an attribute from a node.
     ///
     /// Example:
     /// ```no_run
+    /// # use sledgehammer::*;
     /// let mut channel = MsgChannel::default();
     /// // create a <div> element
     /// channel.create_element("div", None);
     /// channel.set_attribute(Attribute::id, "my-div", MaybeId::LastNode);
     /// // remove the attribute "id" from the <div> element
     /// channel.remove_attribute(Attribute::id, MaybeId::LastNode);
-    /// channel.flush();
+    /// channel.flush().unwrap();
     /// ```
     pub fn remove_attribute<'a, 'b>(&mut self, attr: impl IntoAttribue<'a, 'b>, root: MaybeId) {
         self.batch.remove_attribute(attr, root)
     }
 
+    /// Set an attribute if `condition` is true, or remove it otherwise.
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::*;
+    /// let mut channel = MsgChannel::default();
+    /// let disabled = true;
+    /// channel.create_element("button", None);
+    /// // sets the attribute "disabled" because the condition is true
+    /// channel.set_attribute_if(Attribute::disabled, "", disabled, MaybeId::LastNode);
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn set_attribute_if<'a, 'b>(
+        &mut self,
+        attr: impl IntoAttribue<'a, 'b>,
+        value: impl WritableText,
+        condition: bool,
+        root: MaybeId,
+    ) {
+        self.batch.set_attribute_if(attr, value, condition, root)
+    }
+
+    /// Add a class to an element if `condition` is true, or remove it otherwise. The other classes of the element are kept.
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::*;
+    /// let mut channel = MsgChannel::default();
+    /// let selected = false;
+    /// channel.create_element("li", None);
+    /// channel.set_attribute(Attribute::class, "row selected", MaybeId::LastNode);
+    /// // removes "selected" and keeps "row"
+    /// channel.toggle_class_if("selected", selected, MaybeId::LastNode);
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn toggle_class_if(&mut self, class: impl WritableText, condition: bool, root: MaybeId) {
+        self.batch.toggle_class_if(class, condition, root)
+    }
+
     /// Clone a node and store it with a new id.
     ///
     /// Example:
     /// ```no_run
+    /// # use sledgehammer::*;
     /// let mut channel = MsgChannel::default();
     /// // create a <div> element
     /// channel.create_element("div", None);
     /// // clone the <div> element and store it with the id 1
-    /// channel.clone_node(MaybeId::LastNode, Some(NodeId(1)));
-    /// channel.flush();
+    /// channel.clone_node(MaybeId::LastNode, MaybeId::Node(NodeId(1)));
+    /// channel.flush().unwrap();
     /// ```
     pub fn clone_node(&mut self, id: MaybeId, new_id: MaybeId) {
         self.batch.clone_node(id, new_id)
@@ -333,6 +972,7 @@ impl MsgChannel {
     ///
     /// Example:
     /// ```no_run
+    /// # use sledgehammer::*;
     /// let mut channel = MsgChannel::default();
     /// // create a element: <div><p></p></div>
     /// channel.build_full_element(
@@ -345,7 +985,7 @@ impl MsgChannel {
     /// // move from the <div> to the <p>
     /// channel.first_child();
     /// // operatons modifing the <p> element...
-    /// channel.flush();
+    /// channel.flush().unwrap();
     /// ```
     pub fn first_child(&mut self) {
         self.batch.first_child()
@@ -355,6 +995,7 @@ impl MsgChannel {
     ///
     /// Example:
     /// ```no_run
+    /// # use sledgehammer::*;
     /// let mut channel = MsgChannel::default();
     /// // create a element: <div><h1></h1><p></p></div>
     /// channel.build_full_element(
@@ -363,6 +1004,7 @@ impl MsgChannel {
     ///             ElementBuilder::new(Element::h1.into())
     ///                 .into(),
     ///             ElementBuilder::new(Element::p.into())
+    ///                 .into(),
     ///         ]),
     /// );
     /// // move from the <div> to the <h1>
@@ -370,7 +1012,7 @@ impl MsgChannel {
     /// // move from the <h1> to the <p>
     /// channel.next_sibling();
     /// // operatons modifing the <p> element...
-    /// channel.flush();
+    /// channel.flush().unwrap();
     /// ```
     pub fn next_sibling(&mut self) {
         self.batch.next_sibling()
@@ -380,6 +1022,7 @@ impl MsgChannel {
     ///
     /// Example:
     /// ```no_run
+    /// # use sledgehammer::*;
     /// let mut channel = MsgChannel::default();
     /// // create a element: <div><p></p></div>
     /// channel.build_full_element(
@@ -395,7 +1038,7 @@ impl MsgChannel {
     /// // move from the <p> to the <div>
     /// channel.parent_node();
     /// // operatons modifing the <p> element...
-    /// channel.flush();
+    /// channel.flush().unwrap();
     /// ```
     pub fn parent_node(&mut self) {
         self.batch.parent_node()
@@ -405,12 +1048,13 @@ impl MsgChannel {
     ///
     /// Example:
     /// ```no_run
+    /// # use sledgehammer::*;
     /// let mut channel = MsgChannel::default();
     /// // create a element without an id
     /// channel.create_element("div", None);
     /// // store the <div> element with the id 0
     /// channel.set_last_node(NodeId(0));
-    /// channel.flush();
+    /// channel.flush().unwrap();
     /// ```
     pub fn store_with_id(&mut self, id: NodeId) {
         self.batch.store_with_id(id)
@@ -420,14 +1064,15 @@ impl MsgChannel {
     ///
     /// Example:
     /// ```no_run
+    /// # use sledgehammer::*;
     /// let mut channel = MsgChannel::default();
-    /// // create a element: <div><h1><h2></h2></h1><p></p></div>
+    /// // create a element: <div><h1><span></span></h1><p></p></div>
     /// channel.build_full_element(
     ///     ElementBuilder::new("div".into())
     ///         .children(&[
     ///             ElementBuilder::new(Element::h1.into())
     ///                 .children(&[
-    ///                     ElementBuilder::new(Element::h2.into())
+    ///                     ElementBuilder::new(Element::span.into())
     ///                         .into(),
     ///                 ]).into(),
     ///             ElementBuilder::new(Element::p.into())
@@ -438,15 +1083,15 @@ impl MsgChannel {
     /// channel.first_child();
     /// // store the <h1> element with the id 0
     /// channel.store_with_id(NodeId(0));
-    /// // move from the <h1> to the <h2>
+    /// // move from the <h1> to the <span>
     /// channel.first_child();
-    /// // update something in the <h2> element...
+    /// // update something in the <span> element...
     /// // restore the <h1> element
     /// channel.set_last_node(NodeId(0));
     /// // move from the <h1> to the <p>
     /// channel.next_sibling();
     /// // operatons modifing the <p> element...
-    /// channel.flush();
+    /// channel.flush().unwrap();
     /// ```
     pub fn set_last_node(&mut self, id: NodeId) {
         self.batch.set_last_node(id)
@@ -456,6 +1101,7 @@ impl MsgChannel {
     ///
     /// Example:
     /// ```rust
+    /// # use sledgehammer::*;
     /// let mut channel = MsgChannel::default();
     /// // create an element using sledgehammer
     /// channel.build_full_element(
@@ -468,22 +1114,158 @@ impl MsgChannel {
     ///             TextBuilder::new("Hello from sledgehammer!").into(),
     ///         ]),
     /// );
-    /// channel.flush();
+    /// channel.flush().unwrap();
     /// ```
     pub fn build_full_element(&mut self, el: ElementBuilder) {
         self.batch.build_full_element(el)
     }
 
+    /// Build an element with attributes and children that were added from iterators. See [`OwnedElementBuilder`]
+    ///
+    /// Example:
+    /// ```rust
+    /// # use sledgehammer::*;
+    /// let rows = vec![("a", 1), ("b", 2)];
+    /// let mut channel = MsgChannel::default();
+    /// // create a row for every item without collecting them into a slice first
+    /// channel.build_owned_element(
+    ///     OwnedElementBuilder::new(Element::tbody.into())
+    ///         .id(NodeId(0))
+    ///         .children_iter(rows.iter().map(|(name, count)| {
+    ///             OwnedElementBuilder::new(Element::tr.into())
+    ///                 .attrs_iter([(Attribute::title, format!("{name}: {count}"))])
+    ///                 .children_iter([TextBuilder::new(name)])
+    ///         })),
+    /// );
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn build_owned_element(&mut self, el: OwnedElementBuilder) {
+        self.batch.build_owned_element(el)
+    }
+
+    /// Append a `<link>` that hints the browser to preload or prefetch a resource to the head stored with the id `head`. See [`crate::head`]
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::{head::*, *};
+    /// const HEAD: NodeId = NodeId(0);
+    /// let mut channel = MsgChannel::default();
+    /// // store the head once before the hints are flushed
+    /// # #[cfg(target_arch = "wasm32")]
+    /// channel.set_node(HEAD, web_sys::window().unwrap().document().unwrap().head().unwrap().into());
+    /// // warm the code and data of the route the pointer is over
+    /// channel.append_resource_hint(ResourceHint::module_preload("/routes/settings.js"), HEAD);
+    /// channel.append_resource_hint(
+    ///     ResourceHint::prefetch("/api/settings.json").destination("fetch"),
+    ///     HEAD,
+    /// );
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn append_resource_hint(&mut self, hint: ResourceHint, head: NodeId) {
+        self.batch.append_resource_hint(hint, head)
+    }
+
+    /// Build the roots of a fragment and append them to the end of the children of a node. The node must be stored with an id because building the roots changes the last node. See [`FragmentBuilder`]
+    ///
+    /// Example:
+    /// ```rust
+    /// # use sledgehammer::*;
+    /// let mut channel = MsgChannel::default();
+    /// channel.create_element("div", Some(NodeId(0)));
+    /// // render a heading and a paragraph without a wrapper element
+    /// channel.append_fragment(
+    ///     NodeId(0),
+    ///     FragmentBuilder::new(&[
+    ///         ElementBuilder::new(Element::h1.into()).into(),
+    ///         ElementBuilder::new(Element::p.into()).into(),
+    ///     ]),
+    /// );
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn append_fragment(&mut self, parent: NodeId, fragment: FragmentBuilder) {
+        self.batch.append_fragment(parent, fragment)
+    }
+
+    /// Build the roots of a fragment and insert them before a node. See [`FragmentBuilder`]
+    ///
+    /// Example:
+    /// ```rust
+    /// # use sledgehammer::*;
+    /// let mut channel = MsgChannel::default();
+    /// channel.create_element("div", Some(NodeId(1)));
+    /// channel.create_element("div", Some(NodeId(0)));
+    /// channel.append_child(MaybeId::Node(NodeId(1)), MaybeId::Node(NodeId(0)));
+    /// // render a heading and a paragraph without a wrapper element
+    /// channel.insert_fragment_before(
+    ///     NodeId(0),
+    ///     FragmentBuilder::new(&[
+    ///         ElementBuilder::new(Element::h1.into()).into(),
+    ///         ElementBuilder::new(Element::p.into()).into(),
+    ///     ]),
+    /// );
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn insert_fragment_before(&mut self, root: NodeId, fragment: FragmentBuilder) {
+        self.batch.insert_fragment_before(root, fragment)
+    }
+
+    /// Build the roots of a fragment and insert them after a node. See [`FragmentBuilder`]
+    ///
+    /// Example:
+    /// ```rust
+    /// # use sledgehammer::*;
+    /// let mut channel = MsgChannel::default();
+    /// channel.create_element("div", Some(NodeId(1)));
+    /// channel.create_element("div", Some(NodeId(0)));
+    /// channel.append_child(MaybeId::Node(NodeId(1)), MaybeId::Node(NodeId(0)));
+    /// // render a heading and a paragraph without a wrapper element
+    /// channel.insert_fragment_after(
+    ///     NodeId(0),
+    ///     FragmentBuilder::new(&[
+    ///         ElementBuilder::new(Element::h1.into()).into(),
+    ///         ElementBuilder::new(Element::p.into()).into(),
+    ///     ]),
+    /// );
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn insert_fragment_after(&mut self, root: NodeId, fragment: FragmentBuilder) {
+        self.batch.insert_fragment_after(root, fragment)
+    }
+
+    /// Build the roots of a fragment and replace a node with them. See [`FragmentBuilder`]
+    ///
+    /// Example:
+    /// ```rust
+    /// # use sledgehammer::*;
+    /// let mut channel = MsgChannel::default();
+    /// channel.create_element("div", Some(NodeId(1)));
+    /// channel.create_element("div", Some(NodeId(0)));
+    /// channel.append_child(MaybeId::Node(NodeId(1)), MaybeId::Node(NodeId(0)));
+    /// // render a heading and a paragraph without a wrapper element
+    /// channel.replace_with_fragment(
+    ///     NodeId(0),
+    ///     FragmentBuilder::new(&[
+    ///         ElementBuilder::new(Element::h1.into()).into(),
+    ///         ElementBuilder::new(Element::p.into()).into(),
+    ///     ]),
+    /// );
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn replace_with_fragment(&mut self, root: NodeId, fragment: FragmentBuilder) {
+        self.batch.replace_with_fragment(root, fragment)
+    }
+
     /// Build a text node
     ///
     /// Example:
     /// ```rust
+    /// # use sledgehammer::*;
     /// let mut channel = MsgChannel::default();
     /// // create an element using sledgehammer
     /// channel.build_text_node(
     ///     TextBuilder::new("div".into())
     /// );
-    /// channel.flush();
+    /// channel.flush().unwrap();
     /// ```
     pub fn build_text_node(&mut self, text: TextBuilder) {
         self.batch.build_text_node(text)
@@ -493,13 +1275,14 @@ impl MsgChannel {
     ///
     /// Example:
     /// ```rust
+    /// # use sledgehammer::*;
     /// let mut channel = MsgChannel::default();
     /// channel.create_element("div", None);
     /// // set the style property "color" to "blue"
     /// channel.set_style("color", "blue", MaybeId::LastNode);
-    /// channel.flush();
+    /// channel.flush().unwrap();
     /// ```
-    pub fn set_style(&mut self, style: &str, value: &str, id: MaybeId) {
+    pub fn set_style(&mut self, style: impl WritableText, value: impl WritableText, id: MaybeId) {
         self.batch.set_style(style, value, id)
     }
 
@@ -507,30 +1290,788 @@ impl MsgChannel {
     ///
     /// Example:
     /// ```rust
+    /// # use sledgehammer::*;
     /// let mut channel = MsgChannel::default();
     /// channel.create_element("div", None);
     /// channel.set_style("color", "blue", MaybeId::LastNode);
     /// // remove the color style
     /// channel.remove_style("color", MaybeId::LastNode);
-    /// channel.flush();
+    /// channel.flush().unwrap();
     /// ```
-    pub fn remove_style(&mut self, style: &str, id: MaybeId) {
+    pub fn remove_style(&mut self, style: impl WritableText, id: MaybeId) {
         self.batch.remove_style(style, id)
     }
 
-    /// Adds a batch of operations to the current batch.
+    /// Store the body of a same-origin iframe's content document with the given id. Nodes appended to it are adopted into the iframe's document.
     ///
     /// Example:
-    /// ```rust
+    /// ```no_run
+    /// # use sledgehammer::*;
     /// let mut channel = MsgChannel::default();
-    /// let mut batch = Batch::default();
-    /// batch.create_element("div", None);
-    /// // add the batch to the channel
-    /// channel.append(batch);
-    /// channel.flush();
+    /// channel.create_element("iframe", Some(NodeId(0)));
+    /// channel.append_child(MaybeId::Node(NodeId(1)), MaybeId::Node(NodeId(0)));
+    /// // store the iframe's body with the id 2
+    /// channel.adopt_iframe_document(MaybeId::Node(NodeId(0)), NodeId(2));
+    /// channel.create_element("p", None);
+    /// // build the <p> element inside of the iframe
+    /// channel.append_child(MaybeId::Node(NodeId(2)), MaybeId::LastNode);
+    /// channel.flush().unwrap();
     /// ```
-    pub fn append(&mut self, batch: Batch) {
-        self.batch.append(batch);
+    pub fn adopt_iframe_document(&mut self, iframe: MaybeId, id: NodeId) {
+        self.batch.adopt_iframe_document(iframe, id)
+    }
+
+    /// Set how many removed nodes with a tag name the interpreter keeps to reuse. When a node is removed with [`MsgChannel::remove`] or replaced with [`MsgChannel::replace_with`], it and its descendants are reset and kept in a pool for their tag name, and creating an element with the same tag name takes a node from the pool instead of calling `createElement`. Text nodes use the tag name `#text`.
+    ///
+    /// Pooling is disabled for every tag name by default. A size of 0 disables the pool for the tag name again and drops the nodes in it.
+    ///
+    /// Nodes that are pooled must not be used after they are removed because they may be reused for a different node. Only attributes, styles and children are reset, so tag names with state that is not stored in attributes like `input` should not be pooled. Only html elements and text nodes are pooled. The headless dom and the web-sys backend ignore this operation.
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::*;
+    /// let mut channel = MsgChannel::default();
+    /// // keep up to 1000 removed rows and cells around
+    /// channel.set_pool_size("tr", 1000);
+    /// channel.set_pool_size("td", 1000);
+    /// channel.set_pool_size("#text", 1000);
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn set_pool_size(&mut self, tag: impl WritableText, size: u32) {
+        self.batch.set_pool_size(tag, size)
+    }
+
+    /// Add a listener for an event type to a node that dispatches the events to a handler registered in [`MsgChannel::handlers`]. Adding the same event type and handler to a node twice does nothing. See [`crate::events`]
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::*;
+    /// let mut channel = MsgChannel::default();
+    /// let handler = channel.handlers().insert(|event| println!("{}", event.name));
+    /// channel.create_element("button", Some(NodeId(0)));
+    /// channel.add_listener("click", handler, MaybeId::Node(NodeId(0)));
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn add_listener(&mut self, event: impl WritableText, handler: HandlerId, root: MaybeId) {
+        self.batch.add_listener(event, handler, root)
+    }
+
+    /// Add a passive listener that dispatches at most one event per animation frame for each node, event type and handler. Use it for events like `scroll`, `wheel` and `pointermove` that can fire many times per frame, so the handler runs once with the latest event. The handler cannot prevent the default action of the event.
+    ///
+    /// The headless dom has no animation frames, so it dispatches every event to coalesced listeners.
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::*;
+    /// let mut channel = MsgChannel::default();
+    /// let handler = channel.handlers().insert(|event| println!("{}", event.name));
+    /// channel.create_element("div", Some(NodeId(0)));
+    /// channel.add_coalesced_listener("scroll", handler, MaybeId::Node(NodeId(0)));
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn add_coalesced_listener(
+        &mut self,
+        event: impl WritableText,
+        handler: HandlerId,
+        root: MaybeId,
+    ) {
+        self.batch.add_coalesced_listener(event, handler, root)
+    }
+
+    /// Remove a listener that was added with [`MsgChannel::add_listener`] or [`MsgChannel::add_coalesced_listener`]
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::*;
+    /// let mut channel = MsgChannel::default();
+    /// let handler = channel.handlers().insert(|event| println!("{}", event.name));
+    /// channel.create_element("button", Some(NodeId(0)));
+    /// channel.add_listener("click", handler, MaybeId::Node(NodeId(0)));
+    /// channel.remove_listener("click", handler, MaybeId::Node(NodeId(0)));
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn remove_listener(&mut self, event: impl WritableText, handler: HandlerId, root: MaybeId) {
+        self.batch.remove_listener(event, handler, root)
+    }
+
+    /// Subscribe a handler to a media query like `(prefers-color-scheme: dark)`, `(prefers-reduced-motion: reduce)` or `(min-width: 600px)`. The handler receives a `change` event with [`EventData::MediaQuery`](crate::events::EventData::MediaQuery) data with the current state after the channel is flushed, and again every time the query starts or stops matching. The first event is dispatched after the flush returns, so the handler can use the channel.
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::{events::EventData, *};
+    /// let mut channel = MsgChannel::default();
+    /// let theme = channel.handlers().insert(|event| {
+    ///     if let EventData::MediaQuery(query) = &event.data {
+    ///         println!("dark mode: {}", query.matches);
+    ///     }
+    /// });
+    /// channel.subscribe_media_query("(prefers-color-scheme: dark)", theme);
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn subscribe_media_query(&mut self, query: impl WritableText, handler: HandlerId) {
+        self.batch.subscribe_media_query(query, handler)
+    }
+
+    /// Unsubscribe a handler that was subscribed with [`MsgChannel::subscribe_media_query`]
+    pub fn unsubscribe_media_query(&mut self, query: impl WritableText, handler: HandlerId) {
+        self.batch.unsubscribe_media_query(query, handler)
+    }
+
+    /// Change if a media query matches in the headless dom and dispatch the change to the subscribed handlers. See [`HeadlessDom::set_media_matches`]
+    ///
+    /// Example:
+    /// ```rust
+    /// # use sledgehammer::{events::EventData, *};
+    /// # use std::{cell::Cell, rc::Rc};
+    /// let mut channel = MsgChannel::default();
+    /// let dark = Rc::new(Cell::new(false));
+    /// let theme = channel.handlers().insert({
+    ///     let dark = dark.clone();
+    ///     move |event| {
+    ///         if let EventData::MediaQuery(query) = &event.data {
+    ///             dark.set(query.matches);
+    ///         }
+    ///     }
+    /// });
+    /// channel.subscribe_media_query("(prefers-color-scheme: dark)", theme);
+    /// channel.flush().unwrap();
+    /// channel.set_media_matches("(prefers-color-scheme: dark)", true);
+    /// assert!(dark.get());
+    /// ```
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_media_matches(&mut self, query: &str, matches: bool) {
+        self.backend
+            .set_media_matches(query, matches, &self.handlers)
+    }
+
+    /// Subscribe a handler to animation frames. The handler receives an `animationframe` event with [`EventData::AnimationFrame`](crate::events::EventData::AnimationFrame) data in every `requestAnimationFrame` callback until it is unsubscribed, so animation loops run through the same handlers as other events. Handlers can queue operations and flush the channel while they run.
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::{events::EventData, *};
+    /// let mut channel = MsgChannel::default();
+    /// let tick = channel.handlers().insert(|event| {
+    ///     if let EventData::AnimationFrame(frame) = &event.data {
+    ///         println!("frame at {}ms", frame.timestamp);
+    ///     }
+    /// });
+    /// channel.subscribe_animation_frames(tick);
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn subscribe_animation_frames(&mut self, handler: HandlerId) {
+        self.batch.subscribe_animation_frames(handler)
+    }
+
+    /// Unsubscribe a handler that was subscribed with [`MsgChannel::subscribe_animation_frames`]. The interpreter stops requesting frames when no handler is subscribed.
+    pub fn unsubscribe_animation_frames(&mut self, handler: HandlerId) {
+        self.batch.unsubscribe_animation_frames(handler)
+    }
+
+    /// Dispatch an animation frame to the handlers subscribed in the headless dom. See [`HeadlessDom::animation_frame`]
+    ///
+    /// Example:
+    /// ```rust
+    /// # use sledgehammer::{events::EventData, *};
+    /// # use std::{cell::Cell, rc::Rc};
+    /// let mut channel = MsgChannel::default();
+    /// let last = Rc::new(Cell::new(0.0));
+    /// let tick = channel.handlers().insert({
+    ///     let last = last.clone();
+    ///     move |event| {
+    ///         if let EventData::AnimationFrame(frame) = &event.data {
+    ///             last.set(frame.timestamp);
+    ///         }
+    ///     }
+    /// });
+    /// channel.subscribe_animation_frames(tick);
+    /// channel.flush().unwrap();
+    /// channel.animation_frame(16.0);
+    /// assert_eq!(last.get(), 16.0);
+    /// ```
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn animation_frame(&self, timestamp: f64) {
+        self.backend.animation_frame(timestamp, &self.handlers)
+    }
+
+    /// Queue operations that are applied inside of a view transition, so the browser animates between the page before and after them. The operations of the closure are applied together when the transition captured the old page. The handler receives a `viewtransitionend` event with [`EventData::ViewTransition`](crate::events::EventData::ViewTransition) data when the transition finished, or right after the operations were applied in browsers without view transitions. The headless dom and the web-sys backend apply the operations in place without animating them. See [`Batch::with_view_transition`]
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::{events::EventData, *};
+    /// let mut channel = MsgChannel::default();
+    /// let done = channel.handlers().insert(|event| {
+    ///     if let EventData::ViewTransition(transition) = &event.data {
+    ///         println!("animated: {}", transition.animated);
+    ///     }
+    /// });
+    /// channel.with_view_transition(done, |batch| {
+    ///     batch.remove(MaybeId::Node(NodeId(1)));
+    ///     batch.create_element(Element::main, Some(NodeId(1)));
+    ///     batch.append_child(MaybeId::Node(NodeId(0)), MaybeId::LastNode);
+    /// });
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn with_view_transition<T>(
+        &mut self,
+        handler: HandlerId,
+        f: impl FnOnce(&mut Batch) -> T,
+    ) -> T {
+        self.batch.with_view_transition(handler, f)
+    }
+
+    /// Dispatch the end of every view transition the headless dom applied since the last call. See [`HeadlessDom::finish_view_transitions`]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn finish_view_transitions(&mut self) {
+        self.backend.finish_view_transitions(&self.handlers)
+    }
+
+    /// Keep the `width` and `height` of a canvas equal to its css size times `devicePixelRatio`, so it is drawn at the resolution of the screen. The handler receives a `canvasresize` event with [`EventData::CanvasSize`](crate::events::EventData::CanvasSize) data after the canvas is first laid out and after every resize or zoom, so it can redraw at the new size. The canvas must be stored with an id when it is observed.
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::{events::EventData, *};
+    /// let mut channel = MsgChannel::default();
+    /// let resized = channel.handlers().insert(|event| {
+    ///     if let EventData::CanvasSize(size) = &event.data {
+    ///         println!("{}x{} css pixels", size.width, size.height);
+    ///     }
+    /// });
+    /// channel.create_element("canvas", Some(NodeId(1)));
+    /// channel.append_child(MaybeId::Node(NodeId(0)), MaybeId::LastNode);
+    /// channel.observe_canvas_size(MaybeId::Node(NodeId(1)), resized);
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn observe_canvas_size(&mut self, root: MaybeId, handler: HandlerId) {
+        self.batch.observe_canvas_size(root, handler)
+    }
+
+    /// Stop keeping the size of a canvas in sync that was observed with [`MsgChannel::observe_canvas_size`]
+    pub fn unobserve_canvas_size(&mut self, root: MaybeId) {
+        self.batch.unobserve_canvas_size(root)
+    }
+
+    /// Resize an observed canvas in the headless dom and dispatch the new size to its handler. See [`HeadlessDom::resize_canvas`]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn resize_canvas(
+        &mut self,
+        canvas: NodeRef,
+        width: f64,
+        height: f64,
+        device_pixel_ratio: f64,
+    ) {
+        self.backend
+            .resize_canvas(canvas, width, height, device_pixel_ratio, &self.handlers)
+    }
+
+    /// Forward the `connectedCallback`, `disconnectedCallback` and `attributeChangedCallback` of a custom element to a handler. The handler receives `connected`, `disconnected` and `attributechanged` events with [`EventData::Lifecycle`](crate::events::EventData::Lifecycle) data that holds the id of the element, so components can run mount and unmount hooks. Elements that are not stored with an id are ignored, and `attributeChangedCallback` is only called for the `observedAttributes` of the element.
+    ///
+    /// Custom elements read their callbacks when they are defined, so only elements defined after the channel is created can be observed, and observing an element defined earlier fails the flush. The events are dispatched after the callback returns, once the batch that triggered it is applied. Only the javascript interpreter supports observing custom elements.
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::{events::EventData, *};
+    /// let mut channel = MsgChannel::default();
+    /// let lifecycle = channel.handlers().insert(|event| {
+    ///     if let EventData::Lifecycle(lifecycle) = &event.data {
+    ///         println!("{} {:?}", event.name, lifecycle.node);
+    ///     }
+    /// });
+    /// channel.observe_custom_element("my-widget", lifecycle);
+    /// channel.create_element("my-widget", Some(NodeId(1)));
+    /// channel.append_child(MaybeId::Node(NodeId(0)), MaybeId::LastNode);
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn observe_custom_element(&mut self, tag: impl WritableText, handler: HandlerId) {
+        self.batch.observe_custom_element(tag, handler)
+    }
+
+    /// Stop forwarding lifecycle callbacks that were forwarded with [`MsgChannel::observe_custom_element`]
+    pub fn unobserve_custom_element(&mut self, tag: impl WritableText, handler: HandlerId) {
+        self.batch.unobserve_custom_element(tag, handler)
+    }
+
+    /// Show a `<dialog>` element as a modal. The rest of the page is inert until the dialog is closed with [`MsgChannel::close_dialog`] or by the user pressing escape. Listen to the `close` event of the dialog to find out when it closes and with which [`DialogCloseData::return_value`](crate::events::DialogCloseData::return_value).
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::{events::EventData, *};
+    /// let mut channel = MsgChannel::default();
+    /// let closed = channel.handlers().insert(|event| {
+    ///     if let EventData::DialogClose(close) = &event.data {
+    ///         println!("the dialog closed with {:?}", close.return_value);
+    ///     }
+    /// });
+    /// channel.create_element("dialog", Some(NodeId(1)));
+    /// channel.add_listener("close", closed, MaybeId::Node(NodeId(1)));
+    /// channel.append_child(MaybeId::Node(NodeId(0)), MaybeId::Node(NodeId(1)));
+    /// channel.show_modal(MaybeId::Node(NodeId(1)));
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn show_modal(&mut self, root: MaybeId) {
+        self.batch.show_modal(root)
+    }
+
+    /// Close a `<dialog>` element. The dialog dispatches a `close` event
+    pub fn close_dialog(&mut self, root: MaybeId) {
+        self.batch.close_dialog(root)
+    }
+
+    /// Show an element with the `popover` attribute. The element dispatches a `toggle` event with [`ToggleData`](crate::events::ToggleData)
+    pub fn show_popover(&mut self, root: MaybeId) {
+        self.batch.show_popover(root)
+    }
+
+    /// Hide an element with the `popover` attribute. The element dispatches a `toggle` event with [`ToggleData`](crate::events::ToggleData)
+    pub fn hide_popover(&mut self, root: MaybeId) {
+        self.batch.hide_popover(root)
+    }
+
+    /// Show an element in fullscreen. The `fullscreenchange` and `fullscreenerror` events of the document are dispatched to the handler with [`LockData`](crate::events::LockData) until fullscreen is requested with a different handler.
+    ///
+    /// Browsers only allow fullscreen while handling a user gesture, so flush the channel in a handler for an event like `click`.
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::{events::EventData, *};
+    /// let mut channel = MsgChannel::default();
+    /// let changed = channel.handlers().insert(|event| {
+    ///     if let EventData::Lock(lock) = &event.data {
+    ///         println!("{}: in fullscreen {}", event.name, lock.active);
+    ///     }
+    /// });
+    /// channel.request_fullscreen(MaybeId::Node(NodeId(1)), changed);
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn request_fullscreen(&mut self, root: MaybeId, handler: HandlerId) {
+        self.batch.request_fullscreen(root, handler)
+    }
+
+    /// Leave fullscreen
+    pub fn exit_fullscreen(&mut self) {
+        self.batch.exit_fullscreen()
+    }
+
+    /// Lock the pointer to an element, so mouse events report movement without the pointer leaving the element. The `pointerlockchange` and `pointerlockerror` events of the document are dispatched to the handler with [`LockData`](crate::events::LockData) until pointer lock is requested with a different handler.
+    ///
+    /// Browsers only allow pointer lock while handling a user gesture, so flush the channel in a handler for an event like `click`.
+    pub fn request_pointer_lock(&mut self, root: MaybeId, handler: HandlerId) {
+        self.batch.request_pointer_lock(root, handler)
+    }
+
+    /// Release the pointer
+    pub fn exit_pointer_lock(&mut self) {
+        self.batch.exit_pointer_lock()
+    }
+
+    /// Read an item from local or session storage when the batch is applied. The query resolves to the value of the item, or None if the item is not set. See [`crate::queries`]
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::*;
+    /// # async fn restore(channel: &mut MsgChannel) {
+    /// let collapsed = channel.get_storage_item(StorageArea::Local, "sidebar-collapsed");
+    /// channel.flush().unwrap();
+    /// if collapsed.await.as_deref() == Some("true") {
+    ///     channel.set_attribute("class", "collapsed", MaybeId::Node(NodeId(0)));
+    /// }
+    /// # }
+    /// ```
+    pub fn get_storage_item(
+        &mut self,
+        area: StorageArea,
+        key: impl WritableText,
+    ) -> Query<Option<String>> {
+        let (id, query) = self.queries.insert(|value| value);
+        self.batch.get_storage_item(area, key, id);
+        query
+    }
+
+    /// Read the focused element when the batch is applied. The query resolves to the id of `document.activeElement`, or None if no element is focused or the focused element is not stored with an id. See [`crate::queries`]
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::*;
+    /// # async fn restore_focus(channel: &mut MsgChannel) {
+    /// let focused = channel.active_element();
+    /// channel.remove(MaybeId::Node(NodeId(1)));
+    /// channel.flush().unwrap();
+    /// if focused.await == Some(NodeId(1)) {
+    ///     // the focused element was removed, so focus moves to the next element
+    /// }
+    /// # }
+    /// ```
+    pub fn active_element(&mut self) -> Query<Option<NodeId>> {
+        let (id, query) = self.queries.insert(parse_id);
+        self.batch.active_element(id);
+        query
+    }
+
+    /// Read the topmost element at a point in the viewport, in css pixels from the top left corner, when the batch is applied. The query resolves to the id of the element, or None if there is no element at the point or the element is not stored with an id. The headless dom has no layout, so it always resolves to None. See [`crate::queries`]
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::*;
+    /// # async fn drop_target(channel: &mut MsgChannel, x: i32, y: i32) {
+    /// let target = channel.element_from_point(x, y);
+    /// channel.flush().unwrap();
+    /// if let Some(target) = target.await {
+    ///     channel.set_attribute("class", "drop-target", MaybeId::Node(target));
+    ///     channel.flush().unwrap();
+    /// }
+    /// # }
+    /// ```
+    pub fn element_from_point(&mut self, x: i32, y: i32) -> Query<Option<NodeId>> {
+        let (id, query) = self.queries.insert(parse_id);
+        self.batch.element_from_point(x, y, id);
+        query
+    }
+
+    /// Read if an element is rendered with `checkVisibility()` when the batch is applied. The query resolves to false if the element or one of its ancestors is not displayed. See [`crate::queries`] and [`HeadlessDom::check_visibility`](crate::headless::HeadlessDom::check_visibility)
+    ///
+    /// Example:
+    /// ```rust
+    /// # use sledgehammer::*;
+    /// let mut channel = MsgChannel::default();
+    /// # let root = channel.dom_mut().create_element("div");
+    /// # channel.dom_mut().set_node(NodeId(0), root);
+    /// channel.set_attribute("hidden", "", MaybeId::Node(NodeId(0)));
+    /// let mut visible = channel.check_visibility(MaybeId::Node(NodeId(0)));
+    /// channel.flush().unwrap();
+    /// assert_eq!(visible.try_take(), Some(false));
+    /// ```
+    pub fn check_visibility(&mut self, root: MaybeId) -> Query<bool> {
+        let (id, query) = self
+            .queries
+            .insert(|visible| visible.as_deref() == Some("true"));
+        self.batch.check_visibility(root, id);
+        query
+    }
+
+    /// Set when an image loads and how it is decoded. Lazy images get `loading="lazy"` and only load once they are close to the viewport, and images decoded asynchronously get `decoding="async"` so they do not delay the content around them. The other images get `loading="eager"` and `decoding="auto"`.
+    ///
+    /// Example:
+    /// ```rust
+    /// # use sledgehammer::*;
+    /// let mut channel = MsgChannel::default();
+    /// channel.create_element("img", Some(NodeId(0)));
+    /// channel.set_attribute("src", "/photos/1.jpg", MaybeId::LastNode);
+    /// // below the fold, so it loads once it is scrolled into view
+    /// channel.set_image_loading(true, true, MaybeId::LastNode);
+    /// channel.flush().unwrap();
+    /// let image = channel.dom().get_node(NodeId(0)).unwrap();
+    /// assert_eq!(
+    ///     channel.dom().outer_html(image),
+    ///     r#"<img src="/photos/1.jpg" loading="lazy" decoding="async">"#
+    /// );
+    /// ```
+    pub fn set_image_loading(&mut self, lazy: bool, async_decoding: bool, root: MaybeId) {
+        self.batch.set_image_loading(lazy, async_decoding, root)
+    }
+
+    /// Decode an image with `decode()` when the batch is applied. The query resolves to true once the image is loaded and decoded, or to false if it fails to load or decode, so an image can be inserted once it can be shown in full instead of popping in. Decoding usually finishes after the flush returns, so the query is awaited. The headless dom does not load images and resolves the query to true when the batch is applied. See [`crate::queries`]
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::*;
+    /// # async fn show_photo(channel: &mut MsgChannel) {
+    /// channel.create_element("img", Some(NodeId(1)));
+    /// channel.set_attribute("src", "/photos/1.jpg", MaybeId::LastNode);
+    /// let decoded = channel.decode_image(MaybeId::LastNode);
+    /// channel.flush().unwrap();
+    /// // insert the image once it is decoded, or show a placeholder if it failed to load
+    /// if decoded.await {
+    ///     channel.append_child(MaybeId::Node(NodeId(0)), MaybeId::Node(NodeId(1)));
+    ///     channel.flush().unwrap();
+    /// }
+    /// # }
+    /// ```
+    pub fn decode_image(&mut self, root: MaybeId) -> Query<bool> {
+        let (id, query) = self
+            .queries
+            .insert(|decoded| decoded.as_deref() == Some("true"));
+        self.batch.decode_image(root, id);
+        query
+    }
+
+    /// Set an item in local or session storage when the batch is applied
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::*;
+    /// let mut channel = MsgChannel::default();
+    /// channel.set_storage_item(StorageArea::Local, "theme", "dark");
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn set_storage_item(
+        &mut self,
+        area: StorageArea,
+        key: impl WritableText,
+        value: impl WritableText,
+    ) {
+        self.batch.set_storage_item(area, key, value)
+    }
+
+    /// Remove an item from local or session storage when the batch is applied
+    pub fn remove_storage_item(&mut self, area: StorageArea, key: impl WritableText) {
+        self.batch.remove_storage_item(area, key)
+    }
+
+    /// The queries the interpreter has not answered yet. See [`crate::queries`]
+    pub fn queries(&self) -> &Queries {
+        &self.queries
+    }
+
+    /// The handlers that listeners dispatch events to. Handlers can be registered and removed at any time, including from inside of a handler.
+    pub fn handlers(&self) -> &Handlers {
+        &self.handlers
+    }
+
+    /// Dispatch an event to a node in the headless dom. The event bubbles through the ancestors of the node like it would in a browser, so tests can check how the handlers react to events without a browser. See [`HeadlessDom::dispatch_event`]
+    ///
+    /// Returns false if a handler called [`Event::prevent_default`].
+    ///
+    /// Example:
+    /// ```rust
+    /// # use sledgehammer::{events::{Event, EventData}, *};
+    /// let mut channel = MsgChannel::default();
+    /// let root = channel.dom_mut().create_element("button");
+    /// channel.dom_mut().set_node(NodeId(0), root);
+    /// let handler = channel.handlers().insert(|event| event.prevent_default());
+    /// channel.add_listener("click", handler, MaybeId::Node(NodeId(0)));
+    /// channel.flush().unwrap();
+    /// assert!(!channel.dispatch_event(root, &Event::new("click", EventData::Other)));
+    /// ```
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn dispatch_event(&mut self, target: NodeRef, event: &Event) -> bool {
+        self.backend.dispatch_event(target, event, &self.handlers)
+    }
+
+    /// Create an element and return a [`MutationWriter`] that sets its attributes and children as the methods are called. See [`sledgehammer_encoder::mutation`]
+    ///
+    /// Example:
+    /// ```rust
+    /// # use sledgehammer::*;
+    /// let mut channel = MsgChannel::default();
+    /// channel.create_element("div", Some(NodeId(0)));
+    /// channel
+    ///     .create(Element::ul)
+    ///     .attr(Attribute::class, "items")
+    ///     .child(|c| c.create(Element::li).text("one"))
+    ///     .child(|c| c.create(Element::li).text("two"))
+    ///     .mount(NodeId(0));
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn create<'a, 'b>(&mut self, tag: impl IntoElement<'a, 'b>) -> MutationWriter<'_> {
+        self.batch.create(tag)
+    }
+
+    /// Push the last node onto the stack of parents so children can be appended to it with [`MsgChannel::pop_parent`] without storing it with an id.
+    ///
+    /// Example:
+    /// ```rust
+    /// # use sledgehammer::*;
+    /// let mut channel = MsgChannel::default();
+    /// channel.create_element("ul", None);
+    /// channel.push_parent();
+    /// channel.create_element("li", None);
+    /// // append the li to the ul and make the ul the last node again
+    /// channel.pop_parent();
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn push_parent(&mut self) {
+        self.batch.push_parent()
+    }
+
+    /// Append the last node to the topmost parent that was pushed with [`MsgChannel::push_parent`], then pop the parent and make it the last node.
+    pub fn pop_parent(&mut self) {
+        self.batch.pop_parent()
+    }
+
+    /// Append the last node to the topmost parent and push it onto the stack of parents, so it is in the document before its children are appended. The stack is kept between flushes, so a large tree can be flushed in several parts. See [`crate::stream`]
+    ///
+    /// Example:
+    /// ```rust
+    /// # use sledgehammer::*;
+    /// let mut channel = MsgChannel::default();
+    /// channel.create_element("main", Some(NodeId(0)));
+    /// channel.push_parent();
+    /// channel.create_element("ul", None);
+    /// channel.open_parent();
+    /// // the list is shown before its items are encoded
+    /// channel.flush().unwrap();
+    /// channel.create_element("li", None);
+    /// channel.append_to_parent();
+    /// channel.close_parent();
+    /// channel.close_parent();
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn open_parent(&mut self) {
+        self.batch.open_parent()
+    }
+
+    /// Append the last node to the topmost parent without popping the parent
+    pub fn append_to_parent(&mut self) {
+        self.batch.append_to_parent()
+    }
+
+    /// Pop the topmost parent without appending the last node to it and make the parent the last node
+    pub fn close_parent(&mut self) {
+        self.batch.close_parent()
+    }
+
+    /// Grow the table the interpreter stores nodes in to hold ids up to `capacity`, so storing many nodes during a large initial render does not repeatedly grow it.
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::*;
+    /// let mut channel = MsgChannel::default();
+    /// channel.hint_node_capacity(10_000);
+    /// for i in 0..10_000 {
+    ///     channel.create_element("div", Some(NodeId(i)));
+    /// }
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn hint_node_capacity(&mut self, capacity: u32) {
+        self.batch.hint_node_capacity(capacity)
+    }
+
+    /// A writer that appends formatted text to the last string that was queued, so values can be formatted directly into the batch without allocating a temporary string. See [`Batch::str_writer`]
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::*;
+    /// use std::fmt::Write;
+    ///
+    /// let mut channel = MsgChannel::default();
+    /// let width = 100;
+    /// channel.create_element("div", None);
+    /// channel.set_style("width", "", MaybeId::LastNode);
+    /// write!(channel.str_writer(), "{}px", width).unwrap();
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn str_writer(&mut self) -> StrWriter<'_> {
+        self.batch.str_writer()
+    }
+
+    /// Adds a batch of operations to the current batch.
+    ///
+    /// Example:
+    /// ```rust
+    /// # use sledgehammer::*;
+    /// # use sledgehammer::sledgehammer_encoder::batch::Batch;
+    /// let mut channel = MsgChannel::default();
+    /// let mut batch = Batch::default();
+    /// batch.create_element("div", None);
+    /// // add the batch to the channel
+    /// channel.append(batch);
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn append(&mut self, batch: Batch) {
+        self.batch.append(batch);
+    }
+
+    /// Queue the operations of a component rendered into a [`SubtreeChannel`]. Submitted subtrees are stitched into the batch after the operations queued on the channel when it is flushed, and a subtree marked with [`SubtreeChannel::after`] is applied after the subtrees it waits for. See [`crate::subtree`]
+    ///
+    /// Example:
+    /// ```rust
+    /// # use sledgehammer::{subtree::SubtreeChannel, *};
+    /// let mut channel = MsgChannel::default();
+    /// channel.create_element("ul", Some(NodeId(0)));
+    /// let mut list = SubtreeChannel::new(NodeId(0));
+    /// list.batch().create_element("li", None);
+    /// list.batch().append_child(MaybeId::Node(NodeId(0)), MaybeId::LastNode);
+    /// channel.submit_subtree(list);
+    /// // the subtree is not counted until it is stitched into the flush
+    /// assert_eq!(channel.op_count(), 1);
+    /// channel.flush().unwrap();
+    /// assert_eq!(channel.last_subtree_stats()[0].ops, 2);
+    /// ```
+    pub fn submit_subtree(&mut self, subtree: SubtreeChannel) {
+        self.subtrees.submit(subtree);
+    }
+
+    /// IMPORTANT: This method is exicuted immediatly and does not wait for the next flush
+    ///
+    /// Tear down the interpreter and create a new one that takes over the page, keeping some of the nodes stored with ids. The queued operations are applied by the new interpreter. See [`crate::reload`]
+    ///
+    /// Example:
+    /// ```rust
+    /// # use sledgehammer::{reload::KeepNodes, *};
+    /// let mut channel = MsgChannel::default();
+    /// channel.create_element("div", Some(NodeId(0)));
+    /// channel.create_element("div", Some(NodeId(1)));
+    /// channel.flush().unwrap();
+    /// channel.restart_interpreter(KeepNodes::Only(&[NodeId(0)]));
+    /// // the first div is still stored with its id, the second one has to be stored again
+    /// channel.set_text("still here", MaybeId::Node(NodeId(0)));
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn restart_interpreter(&mut self, keep: KeepNodes) {
+        let keyed: Vec<NodeId>;
+        let keep = match keep {
+            KeepNodes::All => None,
+            KeepNodes::Keyed => {
+                keyed = self.keys.iter().map(|(_, id)| id).collect();
+                Some(&keyed[..])
+            }
+            KeepNodes::Only(ids) => Some(ids),
+        };
+        self.backend.restart(keep);
+        self.idle_started = None;
+    }
+
+    /// The sizes of the subtrees stitched into the last flush, in the order they were applied. See [`crate::subtree`]
+    pub fn last_subtree_stats(&self) -> &[SubtreeStats] {
+        self.subtrees.last_stats()
+    }
+
+    /// The number of operations queued since the last flush.
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::*;
+    /// let mut channel = MsgChannel::default();
+    /// channel.create_element("div", None);
+    /// channel.set_attribute(Attribute::id, "my-div", MaybeId::LastNode);
+    /// assert_eq!(channel.op_count(), 2);
+    /// ```
+    pub fn op_count(&self) -> usize {
+        self.batch.op_count()
+    }
+
+    /// The number of bytes of operations queued since the last flush.
+    pub fn msg_bytes(&self) -> usize {
+        self.batch.msg_bytes()
+    }
+
+    /// The number of bytes of strings queued since the last flush.
+    pub fn str_bytes(&self) -> usize {
+        self.batch.str_bytes()
+    }
+
+    /// The largest number of bytes of operations that have been queued in a single flush.
+    pub fn msg_high_water_mark(&self) -> usize {
+        self.batch.msg_high_water_mark()
+    }
+
+    /// The largest number of bytes of strings that have been queued in a single flush.
+    pub fn str_high_water_mark(&self) -> usize {
+        self.batch.str_high_water_mark()
+    }
+
+    /// Decode the queued operations into human readable lines. This is useful for debugging the operations that will be sent to the interpreter.
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::*;
+    /// let mut channel = MsgChannel::default();
+    /// channel.create_element("div", Some(NodeId(0)));
+    /// // prints "0000 CreateElement "div" id=0"
+    /// println!("{}", channel.disassemble());
+    /// ```
+    #[cfg(feature = "debug")]
+    pub fn disassemble(&self) -> String {
+        self.batch.disassemble()
     }
 
     /// IMPORTANT: This method is exicuted immediatly and does not wait for the next flush
@@ -539,18 +2080,236 @@ impl MsgChannel {
     ///
     /// Example:
     /// ```rust
+    /// # use sledgehammer::*;
+    /// # use sledgehammer::sledgehammer_encoder::batch::Batch;
     /// let mut channel = MsgChannel::default();
     /// let mut batch = Batch::default();
     /// batch.create_element("div", None);
     /// // add the batch to the channel
-    /// channel.run_batch(&batch.finalize());
+    /// channel.run_batch(&batch.finalize()).unwrap();
     /// ```
-    pub fn run_batch(&mut self, batch: impl PreparedBatch) {
-        run_batch(batch.msg(), batch.str(), &mut self.last_mem_size);
+    pub fn run_batch(&mut self, batch: impl PreparedBatch) -> Result<(), DomError> {
+        record(&mut self.recording, batch.msg(), batch.str());
+        self.backend
+            .run(batch.msg(), batch.str(), self.profile)
+            .map(|stats| self.last_flush_stats = stats)
+    }
+
+    /// Enable or disable profiling. When profiling is enabled, every flush is wrapped in `performance.mark` and `performance.measure` calls named `sledgehammer-flush` so it shows up in the browser's performance timeline, and the measurements are available from [`MsgChannel::last_flush_stats`].
+    ///
+    /// Profiling checks for errors in release builds as well, so it is slightly slower than a normal flush.
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::*;
+    /// let mut channel = MsgChannel::default();
+    /// channel.set_profiling(true);
+    /// channel.create_element("div", None);
+    /// channel.flush().unwrap();
+    /// let stats = channel.last_flush_stats().unwrap();
+    /// println!("{} ops in {}ms", stats.ops, stats.duration_ms);
+    /// ```
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profile = enabled;
+        if !enabled {
+            self.last_flush_stats = None;
+        }
+    }
+
+    /// Remove operations that are overwritten later in the same batch before each flush, like setting the text of a node twice. This decodes and encodes the batch again in rust, so it is only worth it for emitters that queue many redundant operations. See [`sledgehammer_encoder::optimize`]
+    ///
+    /// Example:
+    /// ```rust
+    /// # use sledgehammer::*;
+    /// let mut channel = MsgChannel::default();
+    /// channel.set_optimize(true);
+    /// channel.create_element("div", Some(NodeId(0)));
+    /// for i in 0..100 {
+    ///     channel.set_text(i, MaybeId::Node(NodeId(0)));
+    /// }
+    /// // only the last text is sent
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn set_optimize(&mut self, enabled: bool) {
+        self.optimize = enabled;
+    }
+
+    /// Replace the queued operations with the optimized operations if optimization is enabled
+    fn optimize_batch(&mut self) {
+        if !self.optimize {
+            return;
+        }
+        // batches that cannot be decoded are sent as they are so the interpreter reports the error
+        if let Ok(optimized) = self.batch.optimized() {
+            self.batch.clear();
+            self.batch.append(optimized);
+        }
+    }
+
+    /// The measurements of the last successful flush or [`MsgChannel::run_batch`] call. Returns None if profiling is disabled.
+    pub fn last_flush_stats(&self) -> Option<FlushStats> {
+        self.last_flush_stats
+    }
+
+    /// Start recording every batch that is applied with [`MsgChannel::flush`], [`MsgChannel::flush_with_inverse`] or [`MsgChannel::run_batch`]. Starting a new recording discards the current one. See [`crate::recording`]
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Recording::default());
+    }
+
+    /// Stop recording and return the batches that were applied since [`MsgChannel::start_recording`] was called. Returns None if the channel was not recording.
+    pub fn stop_recording(&mut self) -> Option<Recording> {
+        self.recording.take()
+    }
+
+    /// If the channel is recording the batches it applies
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// The nodes registered with string keys. See [`sledgehammer_encoder::keys`]
+    pub fn keys(&self) -> &NodeKeys {
+        &self.keys
+    }
+
+    /// Register nodes with string keys so they can be addressed without tracking their ids. Keys are resolved in rust before operations are encoded.
+    ///
+    /// Example:
+    /// ```rust
+    /// # use sledgehammer::*;
+    /// let mut channel = MsgChannel::default();
+    /// channel.create_element("div", Some(NodeId(0)));
+    /// channel.keys_mut().insert("header", NodeId(0));
+    /// channel.set_text("hello", channel.keys().node("header"));
+    /// channel.flush().unwrap();
+    /// ```
+    pub fn keys_mut(&mut self) -> &mut NodeKeys {
+        &mut self.keys
+    }
+
+    /// Queue a batch of low priority operations, like prefetching offscreen content. Idle batches are not applied by [`MsgChannel::flush`], they are applied a few operations at a time with [`MsgChannel::run_idle`].
+    ///
+    /// Idle batches keep their own last node, so they cannot use the last node from the operations queued on the channel and the channel's last node is not changed by them.
+    ///
+    /// Example:
+    /// ```rust
+    /// # use sledgehammer::*;
+    /// # use sledgehammer::sledgehammer_encoder::batch::Batch;
+    /// let mut channel = MsgChannel::default();
+    /// let mut batch = Batch::default();
+    /// batch.create_element("div", Some(NodeId(0)));
+    /// channel.append_idle(batch);
+    /// // apply the idle batch for at most 5 milliseconds
+    /// while !channel.run_idle(5.0).unwrap() {}
+    /// ```
+    ///
+    /// If idle batches are queued faster than they are applied, for example while the tab is in the background, batches past [`MsgChannel::set_idle_backlog_limit`] are merged into the last waiting batch instead of growing the queue. Merged batches share a last node and an error skips the rest of the merged batch.
+    pub fn append_idle(&mut self, batch: Batch) {
+        let limit = self
+            .idle_backlog_limit
+            .unwrap_or(DEFAULT_IDLE_BACKLOG_LIMIT)
+            .max(1);
+        if self.idle.len() >= limit {
+            self.idle.back_mut().unwrap().append(batch);
+        } else {
+            self.idle.push_back(batch);
+        }
+    }
+
+    /// Set the number of idle batches that can wait to be applied before new batches are merged into the last waiting batch. Defaults to [`DEFAULT_IDLE_BACKLOG_LIMIT`]. The batch that is being applied does not count towards the limit and is never merged into.
+    pub fn set_idle_backlog_limit(&mut self, limit: usize) {
+        self.idle_backlog_limit = Some(limit);
+    }
+
+    /// The number of idle batches waiting to be applied, including a batch that ran out of time in the middle
+    pub fn idle_backlog(&self) -> usize {
+        self.idle.len() + usize::from(self.idle_started.is_some())
+    }
+
+    /// If there are idle batches that have not been completely applied
+    pub fn has_idle_work(&self) -> bool {
+        self.idle_backlog() > 0
+    }
+
+    /// Apply the queued idle batches until `budget_ms` milliseconds have passed. If the budget runs out in the middle of a batch, the next call continues from the operation where it stopped. Operations queued on the channel can be flushed between calls.
+    ///
+    /// The time is checked after every four operations, so at least four operations are applied on every call. Returns true once every idle batch has been applied.
+    ///
+    /// Errors are checked in release builds as well. If an operation in an idle batch fails, the rest of that batch is skipped and the error is returned.
+    pub fn run_idle(&mut self, budget_ms: f64) -> Result<bool, DomError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("run_idle", budget_ms).entered();
+        let deadline = now_ms() + budget_ms;
+        let mut first = true;
+        while self.has_idle_work() {
+            if !first && now_ms() >= deadline {
+                return Ok(false);
+            }
+            first = false;
+            let resume = self.idle_started.is_some();
+            let batch = match self.idle_started.take() {
+                Some(batch) => batch,
+                None => self.idle.pop_front().unwrap().finalize(),
+            };
+            if !self
+                .backend
+                .run_idle(&batch.msg, &batch.str, deadline, resume)?
+            {
+                self.idle_started = Some(batch);
+                return Ok(false);
+            }
+        }
+        Ok(true)
     }
 }
 
-fn run_batch(msg: &[u8], str_buf: &[u8], last_mem_size: &mut usize) {
+/// Run a batch of operations. If a custom interpreter is passed, the batch is run through its methods. Otherwise the functions exported from the built-in interpreter are used
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
+fn run_batch(
+    custom_interpreter: Option<&JsInterpreter>,
+    msg: &[u8],
+    str_buf: &[u8],
+    profile: bool,
+) -> Result<Option<FlushStats>, DomError> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!(
+        "run_batch",
+        msg_bytes = msg.len(),
+        str_bytes = str_buf.len(),
+        ops = tracing::field::Empty,
+        duration_ms = tracing::field::Empty,
+    )
+    .entered();
+    prepare_batch(msg, str_buf);
+    // the interpreter detects when the memory has grown and updates its view of the memory automatically
+    if profile {
+        let result = match custom_interpreter {
+            Some(interpreter) => interpreter.WorkProfiled(),
+            None => work_last_created_profiled(),
+        };
+        let stats = FlushStats::from_js(msg, str_buf, result)?;
+        #[cfg(feature = "tracing")]
+        _span
+            .record("ops", stats.ops)
+            .record("duration_ms", stats.duration_ms);
+        Ok(Some(stats))
+    } else if cfg!(debug_assertions) {
+        let result = match custom_interpreter {
+            Some(interpreter) => interpreter.WorkChecked(),
+            None => work_last_created_checked(),
+        };
+        DomError::from_js(result).map(|_| None)
+    } else {
+        match custom_interpreter {
+            Some(interpreter) => interpreter.Work(),
+            None => work_last_created(),
+        }
+        Ok(None)
+    }
+}
+
+/// Write the pointers and metadata of a batch to the statics the interpreter reads before running it
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
+fn prepare_batch(msg: &[u8], str_buf: &[u8]) {
     debug_assert_eq!(0usize.to_le_bytes().len(), 32 / 8);
     let msg_ptr = msg.as_ptr() as usize;
     let str_ptr = str_buf.as_ptr() as usize;
@@ -564,7 +2323,7 @@ fn run_batch(msg: &[u8], str_buf: &[u8], last_mem_size: &mut usize) {
             // the first bit encodes if the msg pointer has changed
             *mut_metadata_ptr = 1;
             let mut_str_ptr_ptr: *mut usize = std::mem::transmute(STR_PTR_PTR);
-            *mut_str_ptr_ptr = str_ptr as usize;
+            *mut_str_ptr_ptr = str_ptr;
             // the second bit encodes if the str pointer has changed
             *mut_metadata_ptr |= 2;
         }
@@ -587,7 +2346,7 @@ fn run_batch(msg: &[u8], str_buf: &[u8], last_mem_size: &mut usize) {
         if unsafe { *STR_PTR_PTR } != str_ptr {
             unsafe {
                 let mut_str_ptr_ptr: *mut usize = std::mem::transmute(STR_PTR_PTR);
-                *mut_str_ptr_ptr = str_ptr as usize;
+                *mut_str_ptr_ptr = str_ptr;
                 let mut_metadata_ptr: *mut u8 = std::mem::transmute(MSG_METADATA_PTR);
                 // the second bit encodes if the str pointer has changed
                 *mut_metadata_ptr |= 1 << 1;
@@ -600,19 +2359,13 @@ fn run_batch(msg: &[u8], str_buf: &[u8], last_mem_size: &mut usize) {
             // the third bit encodes if there is any strings
             *mut_metadata_ptr |= 1 << 2;
             let mut_str_len_ptr: *mut usize = std::mem::transmute(STR_LEN_PTR);
-            *mut_str_len_ptr = str_buf.len() as usize;
+            *mut_str_len_ptr = str_buf.len();
             if *mut_str_len_ptr < 100 {
                 // the fourth bit encodes if the strings are entirely ascii and small
                 *mut_metadata_ptr |= (str_buf.is_ascii() as u8) << 3;
             }
         }
+        // the upper four bits encode the format version so an interpreter from a different release rejects the batch instead of misreading it
+        *mut_metadata_ptr |= WIRE_FORMAT_VERSION << 4;
     }
-    let new_mem_size = core::arch::wasm32::memory_size(0);
-    // we need to update the memory if the memory has grown
-    if new_mem_size != *last_mem_size {
-        *last_mem_size = new_mem_size;
-        update_last_memory(wasm_bindgen::memory());
-    }
-
-    work_last_created();
 }
@@ -0,0 +1,132 @@
+//! Form controls whose value is owned by rust.
+//!
+//! A [`ControlledInput`] listens to the `input` events of a control and passes every edit to a callback that accepts, rejects or replaces it. Rejected and replaced edits are written back to the control by the interpreter while the event is dispatched, so the control never shows a value rust did not accept. The cursor stays the same distance from the end of the value when an edit is written back, and edits are only reconciled once an input method composition ends.
+//!
+//! Example:
+//! ```rust
+//! # use sledgehammer::{controlled::Reconcile, *};
+//! let mut channel = MsgChannel::default();
+//! # #[cfg(not(target_arch = "wasm32"))]
+//! # { let root = channel.dom_mut().create_element("div"); channel.dom_mut().set_node(NodeId(0), root); }
+//! channel.create_element("input", Some(NodeId(1)));
+//! channel.append_child(MaybeId::Node(NodeId(0)), MaybeId::LastNode);
+//! // only allow digits
+//! let input = channel.controlled_input(NodeId(1), "42", |_, edited| {
+//!     if edited.chars().all(|c| c.is_ascii_digit()) {
+//!         Reconcile::Accept
+//!     } else {
+//!         Reconcile::Reject
+//!     }
+//! });
+//! channel.flush().unwrap();
+//! assert_eq!(input.value(), "42");
+//! ```
+
+use std::{cell::RefCell, rc::Rc};
+
+use sledgehammer_encoder::{HandlerId, MaybeId, NodeId};
+
+use crate::{
+    events::{Event, EventData},
+    MsgChannel,
+};
+
+/// The events a controlled control reconciles edits on. Some browsers fire the last `input` event of a composition before `compositionend`, so edits are reconciled again when the composition ends
+const EVENTS: [&str; 2] = ["input", "compositionend"];
+
+/// What to do with an edit to a [`ControlledInput`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reconcile {
+    /// Keep the edited value
+    Accept,
+    /// Write the value from before the edit back to the control
+    Reject,
+    /// Write a different value to the control, like the edited value in upper case
+    Replace(String),
+}
+
+/// A form control whose value is owned by rust. See [`crate::controlled`]
+///
+/// Cloning the handle returns another handle to the same control.
+#[derive(Debug, Clone)]
+pub struct ControlledInput {
+    node: NodeId,
+    handler: HandlerId,
+    value: Rc<RefCell<String>>,
+}
+
+impl MsgChannel {
+    /// Control the value of the form control stored with an id. The control is set to `initial`, and every edit is passed to `reconcile` with the current value and the edited value. See [`crate::controlled`]
+    pub fn controlled_input(
+        &mut self,
+        node: NodeId,
+        initial: impl Into<String>,
+        mut reconcile: impl FnMut(&str, &str) -> Reconcile + 'static,
+    ) -> ControlledInput {
+        let value = Rc::new(RefCell::new(initial.into()));
+        let handler = {
+            let value = value.clone();
+            self.handlers().insert(move |event: &Event| {
+                let EventData::Input(input) = &event.data else {
+                    return;
+                };
+                if input.composing {
+                    return;
+                }
+                let current = value.borrow().clone();
+                if input.value == current {
+                    return;
+                }
+                match reconcile(&current, &input.value) {
+                    Reconcile::Accept => *value.borrow_mut() = input.value.clone(),
+                    Reconcile::Reject => event.set_value(current),
+                    Reconcile::Replace(replaced) => {
+                        event.set_value(replaced.clone());
+                        *value.borrow_mut() = replaced;
+                    }
+                }
+            })
+        };
+        self.set_value(value.borrow().as_str(), MaybeId::Node(node));
+        for event in EVENTS {
+            self.add_listener(event, handler, MaybeId::Node(node));
+        }
+        ControlledInput {
+            node,
+            handler,
+            value,
+        }
+    }
+}
+
+impl ControlledInput {
+    /// The id of the control
+    pub fn node(&self) -> NodeId {
+        self.node
+    }
+
+    /// The handle of the handler that reconciles edits
+    pub fn handler(&self) -> HandlerId {
+        self.handler
+    }
+
+    /// The last value that was accepted or set
+    pub fn value(&self) -> String {
+        self.value.borrow().clone()
+    }
+
+    /// Set the value of the control from rust. The value is written to the control when the channel is flushed.
+    pub fn set_value(&self, channel: &mut MsgChannel, value: impl Into<String>) {
+        let value = value.into();
+        channel.set_value(value.as_str(), MaybeId::Node(self.node));
+        *self.value.borrow_mut() = value;
+    }
+
+    /// Stop controlling the value. The listeners are removed when the channel is flushed, and the handler is removed immediately.
+    pub fn release(self, channel: &mut MsgChannel) {
+        for event in EVENTS {
+            channel.remove_listener(event, self.handler, MaybeId::Node(self.node));
+        }
+        channel.handlers().remove(self.handler);
+    }
+}
@@ -0,0 +1,630 @@
+//! Event handlers that the listeners added with [`MsgChannel::add_listener`] dispatch events to.
+//!
+//! Handlers are closures registered in the [`Handlers`] of a channel. Registering a handler returns a [`HandlerId`] that listener operations refer to instead of the closure, so one handler can listen to any number of nodes and events. When an event reaches a listener, the interpreter encodes the event into a small fixed layout payload and calls into wasm with the handle. The channel decodes the payload into an [`Event`] and calls the handler registered with the handle.
+//!
+//! Handlers run synchronously while the event is dispatched, so they can call [`Event::prevent_default`] and [`Event::stop_propagation`].
+//!
+//! Example:
+//! ```rust
+//! # use sledgehammer::{events::EventData, *};
+//! let mut channel = MsgChannel::default();
+//! # #[cfg(not(target_arch = "wasm32"))]
+//! # { let root = channel.dom_mut().create_element("div"); channel.dom_mut().set_node(NodeId(0), root); }
+//! let clicked = channel.handlers().insert(|event| {
+//!     if let EventData::Mouse(mouse) = &event.data {
+//!         println!("clicked at {}, {}", mouse.client_x, mouse.client_y);
+//!     }
+//! });
+//! channel.create_element("button", Some(NodeId(1)));
+//! channel.add_listener("click", clicked, MaybeId::Node(NodeId(1)));
+//! channel.append_child(MaybeId::Node(NodeId(0)), MaybeId::Node(NodeId(1)));
+//! channel.flush().unwrap();
+//! ```
+//!
+//! [`MsgChannel::add_listener`]: crate::MsgChannel::add_listener
+
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+pub use sledgehammer_encoder::HandlerId;
+use sledgehammer_encoder::NodeId;
+
+/// A registered event handler
+type Handler = Box<dyn FnMut(&Event)>;
+
+enum Slot {
+    Free,
+    Occupied(Handler),
+    /// The handler is running. If it is removed while it runs, the slot is freed when it returns
+    Running {
+        removed: bool,
+    },
+}
+
+#[derive(Default)]
+struct Slab {
+    slots: Vec<Slot>,
+    /// The indexes of the free slots
+    free: Vec<u32>,
+}
+
+/// The event handlers of a [`MsgChannel`](crate::MsgChannel). See [`crate::events`]
+///
+/// Cloning the handlers returns another reference to the same handlers, so handlers can be registered and removed from inside of other handlers.
+#[derive(Clone, Default)]
+pub struct Handlers {
+    slab: Rc<RefCell<Slab>>,
+}
+
+impl Handlers {
+    /// Register a handler and return the handle listeners refer to it with. The handle stays the same until the handler is removed, and then it is reused for the next handler that is registered.
+    pub fn insert(&self, handler: impl FnMut(&Event) + 'static) -> HandlerId {
+        let mut slab = self.slab.borrow_mut();
+        let handler = Slot::Occupied(Box::new(handler));
+        match slab.free.pop() {
+            Some(index) => {
+                slab.slots[index as usize] = handler;
+                HandlerId(index)
+            }
+            None => {
+                slab.slots.push(handler);
+                HandlerId(slab.slots.len() as u32 - 1)
+            }
+        }
+    }
+
+    /// Replace the handler registered with a handle without changing the handle, so the listeners that refer to it call the new handler. Returns false if no handler is registered with the handle.
+    pub fn replace(&self, id: HandlerId, handler: impl FnMut(&Event) + 'static) -> bool {
+        let mut slab = self.slab.borrow_mut();
+        match slab.slots.get_mut(id.0 as usize) {
+            Some(slot @ (Slot::Occupied(_) | Slot::Running { removed: false })) => {
+                *slot = Slot::Occupied(Box::new(handler));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Remove a handler. Events that reach listeners which still refer to the handle are ignored until the handle is reused, so remove the listeners first. Returns false if no handler is registered with the handle.
+    pub fn remove(&self, id: HandlerId) -> bool {
+        let mut slab = self.slab.borrow_mut();
+        let removed = match slab.slots.get_mut(id.0 as usize) {
+            Some(Slot::Running { removed }) if !*removed => {
+                // the slot is freed when the handler returns
+                *removed = true;
+                return true;
+            }
+            Some(slot @ Slot::Occupied(_)) => {
+                *slot = Slot::Free;
+                true
+            }
+            _ => false,
+        };
+        if removed {
+            slab.free.push(id.0);
+        }
+        removed
+    }
+
+    /// If a handler is registered with the handle
+    pub fn contains(&self, id: HandlerId) -> bool {
+        matches!(
+            self.slab.borrow().slots.get(id.0 as usize),
+            Some(Slot::Occupied(_) | Slot::Running { removed: false })
+        )
+    }
+
+    /// The number of registered handlers
+    pub fn len(&self) -> usize {
+        self.slab
+            .borrow()
+            .slots
+            .iter()
+            .filter(|slot| matches!(slot, Slot::Occupied(_) | Slot::Running { removed: false }))
+            .count()
+    }
+
+    /// If no handlers are registered
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Call the handler registered with a handle. Returns false if no handler is registered with the handle, or if the handler is already running because the event was dispatched from inside of it.
+    pub fn dispatch(&self, id: HandlerId, event: &Event) -> bool {
+        let mut handler = {
+            let mut slab = self.slab.borrow_mut();
+            match slab.slots.get_mut(id.0 as usize) {
+                Some(slot @ Slot::Occupied(_)) => {
+                    match std::mem::replace(slot, Slot::Running { removed: false }) {
+                        Slot::Occupied(handler) => handler,
+                        _ => unreachable!(),
+                    }
+                }
+                _ => return false,
+            }
+        };
+        // the slab is not borrowed while the handler runs so it can register and remove handlers
+        handler(event);
+        let mut slab = self.slab.borrow_mut();
+        let slot = &mut slab.slots[id.0 as usize];
+        match slot {
+            Slot::Running { removed: false } => *slot = Slot::Occupied(handler),
+            Slot::Running { removed: true } => {
+                *slot = Slot::Free;
+                slab.free.push(id.0);
+            }
+            // the handler was replaced while it ran
+            _ => {}
+        }
+        true
+    }
+
+    /// Decode an event the interpreter encoded and dispatch it. Returns the flags the interpreter applies to the event and the value to write back to the target
+    #[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
+    pub(crate) fn dispatch_encoded(
+        &self,
+        id: u32,
+        payload: &[u8],
+        text: &str,
+    ) -> (u8, Option<String>) {
+        match Event::decode(payload, text) {
+            Some(event) => {
+                self.dispatch(HandlerId(id), &event);
+                (event.flags.get(), event.take_value())
+            }
+            None => (0, None),
+        }
+    }
+}
+
+/// The modifier keys that were held when an event was dispatched
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub alt: bool,
+    pub ctrl: bool,
+    pub meta: bool,
+    pub shift: bool,
+}
+
+impl Modifiers {
+    fn from_bits(bits: u8) -> Self {
+        Self {
+            alt: bits & 1 != 0,
+            ctrl: bits & 2 != 0,
+            meta: bits & 4 != 0,
+            shift: bits & 8 != 0,
+        }
+    }
+}
+
+/// The data of a mouse, pointer, wheel or drag event
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MouseData {
+    /// The x coordinate of the pointer relative to the viewport
+    pub client_x: f64,
+    /// The y coordinate of the pointer relative to the viewport
+    pub client_y: f64,
+    /// The button that changed, 0 for the main button
+    pub button: i16,
+    /// The buttons that are held as a bit mask
+    pub buttons: u16,
+}
+
+/// The kind of device that caused a pointer event
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PointerType {
+    #[default]
+    Mouse,
+    Pen,
+    Touch,
+    /// A device the browser does not classify
+    Other,
+}
+
+/// The data of a pointer event, like `pointerdown` or `pointermove`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PointerData {
+    /// The position and buttons of the pointer
+    pub mouse: MouseData,
+    /// The id of the pointer, which stays the same for every event of one finger or pen contact
+    pub pointer_id: i32,
+    pub pointer_type: PointerType,
+    /// The pressure from 0 to 1. Devices that do not report pressure use 0.5 while a button is held
+    pub pressure: f32,
+    /// The width of the contact area in css pixels
+    pub width: f32,
+    /// The height of the contact area in css pixels
+    pub height: f32,
+    /// If the pointer is the primary pointer of its type, like the first finger that touched the screen
+    pub is_primary: bool,
+}
+
+/// A point where a finger touches the screen
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TouchPoint {
+    /// The id of the touch, which stays the same until the finger leaves the screen
+    pub identifier: i32,
+    /// The x coordinate of the touch relative to the viewport
+    pub client_x: f64,
+    /// The y coordinate of the touch relative to the viewport
+    pub client_y: f64,
+    /// The pressure from 0 to 1, or 0 if the device does not report pressure
+    pub force: f32,
+}
+
+/// The data of a touch event, like `touchstart` or `touchmove`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TouchData {
+    /// Every point that touches the screen
+    pub touches: Vec<TouchPoint>,
+    /// The points that changed in the event. For `touchend` these are the points that left the screen
+    pub changed: Vec<TouchPoint>,
+}
+
+/// The data of a keyboard event
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyboardData {
+    /// The value of the key, like `a` or `Enter`
+    pub key: String,
+    /// The physical key, like `KeyA`
+    pub code: String,
+    /// If the key is held down and the event repeats
+    pub repeat: bool,
+    /// Where the key is on the keyboard
+    pub location: u8,
+}
+
+/// The data of an event from a form control, like an `input` or `change` event
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputData {
+    /// The value of the target
+    pub value: String,
+    /// If the target is a checked checkbox or radio button
+    pub checked: bool,
+    /// If the user is composing text with an input method and the value is not final yet
+    pub composing: bool,
+}
+
+/// The data of a `change` event from a media query subscribed to with [`MsgChannel::subscribe_media_query`](crate::MsgChannel::subscribe_media_query)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MediaQueryData {
+    /// The media query, serialized the way the browser normalizes it
+    pub media: String,
+    /// If the document currently matches the query
+    pub matches: bool,
+}
+
+/// The data of a `connected`, `disconnected` or `attributechanged` event from a custom element observed with [`MsgChannel::observe_custom_element`](crate::MsgChannel::observe_custom_element)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LifecycleData {
+    /// The id the element is stored with
+    pub node: NodeId,
+    /// The attribute that changed, for `attributechanged` events
+    pub attribute: Option<AttributeChange>,
+}
+
+/// An attribute change passed to `attributeChangedCallback`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AttributeChange {
+    pub name: String,
+    /// The value before the change, or None if the attribute was added
+    pub old_value: Option<String>,
+    /// The value after the change, or None if the attribute was removed
+    pub new_value: Option<String>,
+}
+
+/// The data of a `toggle` or `beforetoggle` event from a popover, like the events dispatched by [`MsgChannel::show_popover`](crate::MsgChannel::show_popover) and [`MsgChannel::hide_popover`](crate::MsgChannel::hide_popover)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ToggleData {
+    /// If the element was open before the toggle
+    pub was_open: bool,
+    /// If the element is open after the toggle
+    pub open: bool,
+}
+
+/// The data of a `close` event from a `<dialog>` element, like the event dispatched by [`MsgChannel::close_dialog`](crate::MsgChannel::close_dialog)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DialogCloseData {
+    /// The return value of the dialog, like the value of the button that submitted a form with `method="dialog"`
+    pub return_value: String,
+}
+
+/// The data of a `fullscreenchange`, `fullscreenerror`, `pointerlockchange` or `pointerlockerror` event, like the events dispatched to the handler passed to [`MsgChannel::request_fullscreen`](crate::MsgChannel::request_fullscreen)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LockData {
+    /// If an element is in fullscreen, or if the pointer is locked, after the event
+    pub active: bool,
+}
+
+/// The data of a `compositionstart`, `compositionupdate` or `compositionend` event, dispatched while the user enters text with an input method like the ones for Chinese, Japanese or Korean
+///
+/// The value of the target is not final until `compositionend`, so editors should show the composed text without committing it before then.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompositionData {
+    /// The text that is being composed. For `compositionend` this is the text that is committed
+    pub data: String,
+}
+
+/// The data of an `animationframe` event dispatched to the handlers subscribed with [`MsgChannel::subscribe_animation_frames`](crate::MsgChannel::subscribe_animation_frames)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameData {
+    /// The time the frame started in milliseconds, on the same clock as `performance.now()`. Every handler receives the same timestamp in a frame
+    pub timestamp: f64,
+}
+
+/// The data of a `canvasresize` event dispatched to the handler passed to [`MsgChannel::observe_canvas_size`](crate::MsgChannel::observe_canvas_size)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CanvasSizeData {
+    /// The id the canvas was stored with when it was observed
+    pub node: NodeId,
+    /// The css width of the canvas in css pixels
+    pub width: f64,
+    /// The css height of the canvas in css pixels
+    pub height: f64,
+    /// The `devicePixelRatio` of the window when the canvas was resized
+    pub device_pixel_ratio: f64,
+    /// The `width` of the canvas, in device pixels
+    pub pixel_width: u32,
+    /// The `height` of the canvas, in device pixels
+    pub pixel_height: u32,
+}
+
+/// The data of a `viewtransitionend` event dispatched to the handler passed to [`MsgChannel::with_view_transition`](crate::MsgChannel::with_view_transition)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ViewTransitionData {
+    /// If the browser animated the transition. Transitions are not animated in browsers without view transitions, or when the browser skips them, like when the document is hidden or another transition starts
+    pub animated: bool,
+    /// The message of the error an operation of the transition failed with. The operations after it are not applied
+    pub error: Option<String>,
+}
+
+/// The data that is specific to the kind of event
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum EventData {
+    Mouse(MouseData),
+    Pointer(PointerData),
+    Touch(TouchData),
+    Keyboard(KeyboardData),
+    Input(InputData),
+    MediaQuery(MediaQueryData),
+    Lifecycle(LifecycleData),
+    Toggle(ToggleData),
+    DialogClose(DialogCloseData),
+    Lock(LockData),
+    Composition(CompositionData),
+    AnimationFrame(FrameData),
+    CanvasSize(CanvasSizeData),
+    ViewTransition(ViewTransitionData),
+    /// An event without any data, or with data that is not decoded
+    Other,
+}
+
+/// Set when [`Event::prevent_default`] is called
+const PREVENT_DEFAULT: u8 = 1;
+/// Set when [`Event::stop_propagation`] is called
+const STOP_PROPAGATION: u8 = 2;
+
+/// An event that was dispatched to a listener
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    /// The type of the event, like `click`
+    pub name: String,
+    /// The modifier keys that were held when the event was dispatched
+    pub modifiers: Modifiers,
+    pub data: EventData,
+    flags: Cell<u8>,
+    /// The value written back to the target after the handlers return
+    value: RefCell<Option<String>>,
+}
+
+impl Event {
+    /// Create an event, for example to dispatch it to a headless dom
+    pub fn new(name: impl Into<String>, data: EventData) -> Self {
+        Self {
+            name: name.into(),
+            modifiers: Modifiers::default(),
+            data,
+            flags: Cell::new(0),
+            value: RefCell::new(None),
+        }
+    }
+
+    /// Set the modifier keys of the event
+    pub fn with_modifiers(mut self, modifiers: Modifiers) -> Self {
+        self.modifiers = modifiers;
+        self
+    }
+
+    /// Cancel the default action of the event, like following a link or submitting a form
+    pub fn prevent_default(&self) {
+        self.flags.set(self.flags.get() | PREVENT_DEFAULT);
+    }
+
+    /// If [`Event::prevent_default`] was called
+    pub fn default_prevented(&self) -> bool {
+        self.flags.get() & PREVENT_DEFAULT != 0
+    }
+
+    /// Stop the event from reaching the listeners of the ancestors of the node it was dispatched to
+    pub fn stop_propagation(&self) {
+        self.flags.set(self.flags.get() | STOP_PROPAGATION);
+    }
+
+    /// If [`Event::stop_propagation`] was called
+    pub fn propagation_stopped(&self) -> bool {
+        self.flags.get() & STOP_PROPAGATION != 0
+    }
+
+    /// Set the value of the form control the event was dispatched to after the handlers return, keeping the cursor where it was relative to the end of the value. Use this to revert or correct an edit from an `input` event. If the user is composing text with an input method, the value is set when the composition ends. See [`crate::controlled`]
+    pub fn set_value(&self, value: impl Into<String>) {
+        *self.value.borrow_mut() = Some(value.into());
+    }
+
+    /// The value passed to [`Event::set_value`]
+    pub(crate) fn take_value(&self) -> Option<String> {
+        self.value.borrow_mut().take()
+    }
+
+    /// Decode an event from the payload the interpreter encodes. Returns None if the payload is malformed.
+    ///
+    /// The payload is 24 bytes with numbers in little endian, followed by more data for pointer, touch and canvas resize events. The strings of the event are concatenated in `text`:
+    /// - byte 0 is the kind of event: 0 for other events, 1 for mouse events, 2 for keyboard events, 3 for input events, 4 for media query events, 5 for custom element lifecycle events, 6 for toggle events, 7 for dialog close events, 8 for fullscreen and pointer lock events, 9 for pointer events, 10 for touch events, 11 for composition events, 12 for animation frames, 13 for canvas resizes and 14 for the end of view transitions
+    /// - byte 1 holds the modifier keys: 1 for alt, 2 for ctrl, 4 for meta and 8 for shift
+    /// - bytes 2..4 are the length of the event type at the start of `text`
+    /// - mouse events have the button as an i16 at 4..6, the buttons as a u16 at 6..8 and the client x and y as f64s at 8..16 and 16..24
+    /// - keyboard events have if the key repeats at 4, the location at 5 and the length of the key as a u16 at 6..8. The key and the code follow the type in `text`
+    /// - input events have if the target is checked at 4 and if the user is composing text at 5, and the value of the target follows the type in `text`
+    /// - media query events have if the query matches at 4, and the query follows the type in `text`
+    /// - lifecycle events have the id of the element as a u32 at 4..8. If an attribute changed, byte 8 is 1, plus 2 if there is an old value and 4 if there is a new value, and the lengths of the attribute name and the old value are u16s at 10..12 and 12..14. The name, the old value and the new value follow the type in `text`
+    /// - toggle events have if the element was open at 4 and if it is open at 5
+    /// - dialog close events have the return value of the dialog after the type in `text`
+    /// - fullscreen and pointer lock events have if an element is in fullscreen or the pointer is locked at 4
+    /// - pointer events have the layout of mouse events followed by 24 bytes with the pointer id as an i32 at 24..28, the pressure, width and height as f32s at 28..32, 32..36 and 36..40, the pointer type at 40 (0 for mouse, 1 for pen, 2 for touch and 3 for other devices) and if the pointer is primary at 41
+    /// - touch events have the number of touches at 4 and the number of changed touches at 5. The touches and then the changed touches follow the 24 bytes, each as 16 bytes with the identifier as an i32 and the client x, client y and force as f32s
+    /// - composition events have the composed text after the type in `text`
+    /// - animation frames have the timestamp as an f64 at 8..16
+    /// - canvas resizes have the id of the canvas as a u32 at 4..8, the css width and height as f64s at 8..16 and 16..24, followed by the device pixel ratio as an f64 at 24..32 and the width and height in device pixels as u32s at 32..36 and 36..40
+    /// - the end of a view transition has if the transition was animated at 4 and if an operation failed at 5, and the error message follows the type in `text`
+    ///
+    /// Lengths are counted in UTF-16 code units like the lengths of javascript strings.
+    pub fn decode(payload: &[u8], text: &str) -> Option<Self> {
+        let (payload, extra) = payload.split_first_chunk::<24>()?;
+        let u16_at = |pos: usize| u16::from_le_bytes([payload[pos], payload[pos + 1]]);
+        let f64_at = |pos: usize| f64::from_le_bytes(payload[pos..pos + 8].try_into().unwrap());
+        let f32_in =
+            |bytes: &[u8], pos: usize| f32::from_le_bytes(bytes[pos..pos + 4].try_into().unwrap());
+        let i32_in =
+            |bytes: &[u8], pos: usize| i32::from_le_bytes(bytes[pos..pos + 4].try_into().unwrap());
+        let (name, rest) = split_utf16(text, u16_at(2) as usize)?;
+        let mouse = MouseData {
+            client_x: f64_at(8),
+            client_y: f64_at(16),
+            button: u16_at(4) as i16,
+            buttons: u16_at(6),
+        };
+        // only pointer, touch and canvas resize events have data after the first 24 bytes
+        if !matches!(payload[0], 9 | 10 | 13) && !extra.is_empty() {
+            return None;
+        }
+        let data = match payload[0] {
+            1 => EventData::Mouse(mouse),
+            2 => {
+                let (key, code) = split_utf16(rest, u16_at(6) as usize)?;
+                EventData::Keyboard(KeyboardData {
+                    key: key.to_string(),
+                    code: code.to_string(),
+                    repeat: payload[4] != 0,
+                    location: payload[5],
+                })
+            }
+            3 => EventData::Input(InputData {
+                value: rest.to_string(),
+                checked: payload[4] != 0,
+                composing: payload[5] != 0,
+            }),
+            4 => EventData::MediaQuery(MediaQueryData {
+                media: rest.to_string(),
+                matches: payload[4] != 0,
+            }),
+            5 => {
+                let node = NodeId(u32::from_le_bytes(payload[4..8].try_into().unwrap()));
+                let flags = payload[8];
+                let attribute = if flags & 1 != 0 {
+                    let (name, rest) = split_utf16(rest, u16_at(10) as usize)?;
+                    let (old_value, new_value) = split_utf16(rest, u16_at(12) as usize)?;
+                    Some(AttributeChange {
+                        name: name.to_string(),
+                        old_value: (flags & 2 != 0).then(|| old_value.to_string()),
+                        new_value: (flags & 4 != 0).then(|| new_value.to_string()),
+                    })
+                } else {
+                    None
+                };
+                EventData::Lifecycle(LifecycleData { node, attribute })
+            }
+            6 => EventData::Toggle(ToggleData {
+                was_open: payload[4] != 0,
+                open: payload[5] != 0,
+            }),
+            7 => EventData::DialogClose(DialogCloseData {
+                return_value: rest.to_string(),
+            }),
+            8 => EventData::Lock(LockData {
+                active: payload[4] != 0,
+            }),
+            9 => {
+                let extra: &[u8; 24] = extra.try_into().ok()?;
+                EventData::Pointer(PointerData {
+                    mouse,
+                    pointer_id: i32_in(extra, 0),
+                    pointer_type: match extra[16] {
+                        0 => PointerType::Mouse,
+                        1 => PointerType::Pen,
+                        2 => PointerType::Touch,
+                        _ => PointerType::Other,
+                    },
+                    pressure: f32_in(extra, 4),
+                    width: f32_in(extra, 8),
+                    height: f32_in(extra, 12),
+                    is_primary: extra[17] != 0,
+                })
+            }
+            10 => {
+                let touches = payload[4] as usize;
+                if extra.len() != (touches + payload[5] as usize) * 16 {
+                    return None;
+                }
+                let mut points = extra.chunks_exact(16).map(|point| TouchPoint {
+                    identifier: i32_in(point, 0),
+                    client_x: f32_in(point, 4) as f64,
+                    client_y: f32_in(point, 8) as f64,
+                    force: f32_in(point, 12),
+                });
+                EventData::Touch(TouchData {
+                    touches: points.by_ref().take(touches).collect(),
+                    changed: points.collect(),
+                })
+            }
+            11 => EventData::Composition(CompositionData {
+                data: rest.to_string(),
+            }),
+            12 => EventData::AnimationFrame(FrameData {
+                timestamp: f64_at(8),
+            }),
+            13 => {
+                let extra: &[u8; 16] = extra.try_into().ok()?;
+                let u32_in =
+                    |pos: usize| u32::from_le_bytes(extra[pos..pos + 4].try_into().unwrap());
+                EventData::CanvasSize(CanvasSizeData {
+                    node: NodeId(u32::from_le_bytes(payload[4..8].try_into().unwrap())),
+                    width: f64_at(8),
+                    height: f64_at(16),
+                    device_pixel_ratio: f64::from_le_bytes(*extra.first_chunk().unwrap()),
+                    pixel_width: u32_in(8),
+                    pixel_height: u32_in(12),
+                })
+            }
+            14 => EventData::ViewTransition(ViewTransitionData {
+                animated: payload[4] != 0,
+                error: (payload[5] != 0).then(|| rest.to_string()),
+            }),
+            _ => EventData::Other,
+        };
+        Some(Self::new(name, data).with_modifiers(Modifiers::from_bits(payload[1])))
+    }
+}
+
+/// Split a string after a number of UTF-16 code units
+fn split_utf16(text: &str, len: usize) -> Option<(&str, &str)> {
+    let mut units = 0;
+    for (index, c) in text.char_indices() {
+        if units == len {
+            return Some(text.split_at(index));
+        }
+        units += c.len_utf16();
+    }
+    (units == len).then_some((text, ""))
+}
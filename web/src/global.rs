@@ -0,0 +1,463 @@
+//! A thread-local [`MsgChannel`] with free functions for every operation, enabled with the `global` feature. This is useful for small apps and examples that do not want to pass a channel through every function.
+//!
+//! The global channel is created the first time it is used. Because only one [`MsgChannel`] can exist, do not create another channel when using the global channel.
+//!
+//! Example:
+//! ```rust
+//! use sledgehammer::{global, MaybeId, NodeId};
+//!
+//! fn counter(count: u32) {
+//!     global::create_element("p", None);
+//!     global::set_text(format_args!("count: {}", count), MaybeId::LastNode);
+//! }
+//!
+//! counter(1);
+//! global::flush().unwrap();
+//! ```
+
+use std::cell::RefCell;
+
+use sledgehammer_encoder::{
+    batch::{Batch, PreparedBatch},
+    head::ResourceHint,
+    intl::{DateTimeFormat, NumberFormat},
+    sanitize::Allowlist,
+    HandlerId, MaybeId, NodeId, StorageArea, WritableText,
+};
+#[cfg(target_arch = "wasm32")]
+use web_sys::Node;
+
+use crate::{
+    events::Handlers, queries::Query, reload::KeepNodes, subtree::SubtreeChannel, DomError,
+    ElementBuilder, FragmentBuilder, IntoAttribue, IntoElement, MsgChannel, OwnedElementBuilder,
+    TextBuilder,
+};
+
+thread_local! {
+    static CHANNEL: RefCell<MsgChannel> = RefCell::new(MsgChannel::default());
+}
+
+/// Run a closure with the global channel
+///
+/// Panics if it is called from inside another call to [`channel`].
+pub fn channel<R>(f: impl FnOnce(&mut MsgChannel) -> R) -> R {
+    CHANNEL.with(|c| f(&mut c.borrow_mut()))
+}
+
+/// Assign an id to a node immediately. See [`MsgChannel::set_node`]
+#[cfg(target_arch = "wasm32")]
+pub fn set_node(id: NodeId, node: Node) {
+    channel(|c| c.set_node(id, node))
+}
+
+/// Get the node stored with an id immediately. See [`MsgChannel::get_node`]
+#[cfg(target_arch = "wasm32")]
+pub fn get_node(id: NodeId) -> Node {
+    channel(|c| c.get_node(id))
+}
+
+/// Execute the queued operations. See [`MsgChannel::flush`]
+pub fn flush() -> Result<(), DomError> {
+    channel(|c| c.flush())
+}
+
+/// Queue appending a child to a node. See [`MsgChannel::append_child`]
+pub fn append_child(root: MaybeId, child: MaybeId) {
+    channel(|c| c.append_child(root, child))
+}
+
+/// Queue replacing a node with another node. See [`MsgChannel::replace_with`]
+pub fn replace_with(root: MaybeId, node: MaybeId) {
+    channel(|c| c.replace_with(root, node))
+}
+
+/// Queue replacing a node with many nodes. See [`MsgChannel::replace_with_nodes`]
+pub fn replace_with_nodes(root: MaybeId, nodes: &[MaybeId]) {
+    channel(|c| c.replace_with_nodes(root, nodes))
+}
+
+/// Queue inserting a node after another node. See [`MsgChannel::insert_after`]
+pub fn insert_after(root: MaybeId, node: MaybeId) {
+    channel(|c| c.insert_after(root, node))
+}
+
+/// Queue inserting many nodes after a node. See [`MsgChannel::insert_nodes_after`]
+pub fn insert_nodes_after(root: MaybeId, nodes: &[MaybeId]) {
+    channel(|c| c.insert_nodes_after(root, nodes))
+}
+
+/// Queue inserting a node before another node. See [`MsgChannel::insert_before`]
+pub fn insert_before(root: MaybeId, node: MaybeId) {
+    channel(|c| c.insert_before(root, node))
+}
+
+/// Queue inserting many nodes before a node. See [`MsgChannel::insert_nodes_before`]
+pub fn insert_nodes_before(root: MaybeId, nodes: &[MaybeId]) {
+    channel(|c| c.insert_nodes_before(root, nodes))
+}
+
+/// Queue removing a node. See [`MsgChannel::remove`]
+pub fn remove(id: MaybeId) {
+    channel(|c| c.remove(id))
+}
+
+/// Queue creating a text node. See [`MsgChannel::create_text_node`]
+pub fn create_text_node(text: impl WritableText, id: Option<NodeId>) {
+    channel(|c| c.create_text_node(text, id))
+}
+
+/// Queue creating an element. See [`MsgChannel::create_element`]
+pub fn create_element<'a, 'b>(tag: impl IntoElement<'a, 'b>, id: Option<NodeId>) {
+    channel(|c| c.create_element(tag, id))
+}
+
+/// Queue setting the text content of a node. See [`MsgChannel::set_text`]
+pub fn set_text(text: impl WritableText, root: MaybeId) {
+    channel(|c| c.set_text(text, root))
+}
+
+/// Queue setting the text of a node to a formatted number. See [`MsgChannel::set_formatted_number`]
+pub fn set_formatted_number(value: f64, format: &NumberFormat, root: MaybeId) {
+    channel(|c| c.set_formatted_number(value, format, root))
+}
+
+/// Queue setting the text of a node to a formatted date. See [`MsgChannel::set_formatted_date`]
+pub fn set_formatted_date(timestamp: f64, format: &DateTimeFormat, root: MaybeId) {
+    channel(|c| c.set_formatted_date(timestamp, format, root))
+}
+
+/// Queue replacing the children of a node with sanitized html. See [`MsgChannel::set_sanitized_html`]
+pub fn set_sanitized_html(html: impl WritableText, allowlist: &Allowlist, root: MaybeId) {
+    channel(|c| c.set_sanitized_html(html, allowlist, root))
+}
+
+/// Queue setting the value of a form control. See [`MsgChannel::set_value`]
+pub fn set_value(value: impl WritableText, root: MaybeId) {
+    channel(|c| c.set_value(value, root))
+}
+
+/// Queue setting an attribute. See [`MsgChannel::set_attribute`]
+pub fn set_attribute<'a, 'b>(
+    attr: impl IntoAttribue<'a, 'b>,
+    value: impl WritableText,
+    root: MaybeId,
+) {
+    channel(|c| c.set_attribute(attr, value, root))
+}
+
+/// Queue caching a value with an id. See [`MsgChannel::cache_value`]
+pub fn cache_value(id: u8, value: impl WritableText) {
+    channel(|c| c.cache_value(id, value))
+}
+
+/// Queue setting an attribute to a cached value. See [`MsgChannel::set_attribute_cached`]
+pub fn set_attribute_cached<'a, 'b>(attr: impl IntoAttribue<'a, 'b>, value: u8, id: MaybeId) {
+    channel(|c| c.set_attribute_cached(attr, value, id))
+}
+
+/// Queue removing an attribute. See [`MsgChannel::remove_attribute`]
+pub fn remove_attribute<'a, 'b>(attr: impl IntoAttribue<'a, 'b>, root: MaybeId) {
+    channel(|c| c.remove_attribute(attr, root))
+}
+
+/// Queue setting or removing an attribute. See [`MsgChannel::set_attribute_if`]
+pub fn set_attribute_if<'a, 'b>(
+    attr: impl IntoAttribue<'a, 'b>,
+    value: impl WritableText,
+    condition: bool,
+    root: MaybeId,
+) {
+    channel(|c| c.set_attribute_if(attr, value, condition, root))
+}
+
+/// Queue adding or removing a class. See [`MsgChannel::toggle_class_if`]
+pub fn toggle_class_if(class: impl WritableText, condition: bool, root: MaybeId) {
+    channel(|c| c.toggle_class_if(class, condition, root))
+}
+
+/// Queue cloning a node. See [`MsgChannel::clone_node`]
+pub fn clone_node(id: MaybeId, new_id: MaybeId) {
+    channel(|c| c.clone_node(id, new_id))
+}
+
+/// Queue moving the last node to its first child. See [`MsgChannel::first_child`]
+pub fn first_child() {
+    channel(|c| c.first_child())
+}
+
+/// Queue moving the last node to its next sibling. See [`MsgChannel::next_sibling`]
+pub fn next_sibling() {
+    channel(|c| c.next_sibling())
+}
+
+/// Queue moving the last node to its parent. See [`MsgChannel::parent_node`]
+pub fn parent_node() {
+    channel(|c| c.parent_node())
+}
+
+/// Queue storing the last node with an id. See [`MsgChannel::store_with_id`]
+pub fn store_with_id(id: NodeId) {
+    channel(|c| c.store_with_id(id))
+}
+
+/// Queue setting the last node. See [`MsgChannel::set_last_node`]
+pub fn set_last_node(id: NodeId) {
+    channel(|c| c.set_last_node(id))
+}
+
+/// Queue building an element and its children. See [`MsgChannel::build_full_element`]
+pub fn build_full_element(el: ElementBuilder) {
+    channel(|c| c.build_full_element(el))
+}
+
+/// Queue building an element with attributes and children that were added from iterators. See [`MsgChannel::build_owned_element`]
+pub fn build_owned_element(el: OwnedElementBuilder) {
+    channel(|c| c.build_owned_element(el))
+}
+
+/// Queue appending a resource hint to the head. See [`MsgChannel::append_resource_hint`]
+pub fn append_resource_hint(hint: ResourceHint, head: NodeId) {
+    channel(|c| c.append_resource_hint(hint, head))
+}
+
+/// Queue appending the roots of a fragment to a node. See [`MsgChannel::append_fragment`]
+pub fn append_fragment(parent: NodeId, fragment: FragmentBuilder) {
+    channel(|c| c.append_fragment(parent, fragment))
+}
+
+/// Queue inserting the roots of a fragment before a node. See [`MsgChannel::insert_fragment_before`]
+pub fn insert_fragment_before(root: NodeId, fragment: FragmentBuilder) {
+    channel(|c| c.insert_fragment_before(root, fragment))
+}
+
+/// Queue inserting the roots of a fragment after a node. See [`MsgChannel::insert_fragment_after`]
+pub fn insert_fragment_after(root: NodeId, fragment: FragmentBuilder) {
+    channel(|c| c.insert_fragment_after(root, fragment))
+}
+
+/// Queue replacing a node with the roots of a fragment. See [`MsgChannel::replace_with_fragment`]
+pub fn replace_with_fragment(root: NodeId, fragment: FragmentBuilder) {
+    channel(|c| c.replace_with_fragment(root, fragment))
+}
+
+/// Queue building a text node. See [`MsgChannel::build_text_node`]
+pub fn build_text_node(text: TextBuilder) {
+    channel(|c| c.build_text_node(text))
+}
+
+/// Queue setting a style property. See [`MsgChannel::set_style`]
+pub fn set_style(style: impl WritableText, value: impl WritableText, id: MaybeId) {
+    channel(|c| c.set_style(style, value, id))
+}
+
+/// Queue removing a style property. See [`MsgChannel::remove_style`]
+pub fn remove_style(style: impl WritableText, id: MaybeId) {
+    channel(|c| c.remove_style(style, id))
+}
+
+/// Queue storing the body of an iframe's document with an id. See [`MsgChannel::adopt_iframe_document`]
+pub fn adopt_iframe_document(iframe: MaybeId, id: NodeId) {
+    channel(|c| c.adopt_iframe_document(iframe, id))
+}
+
+/// Queue setting how many removed nodes with a tag name are kept to be reused. See [`MsgChannel::set_pool_size`]
+pub fn set_pool_size(tag: impl WritableText, size: u32) {
+    channel(|c| c.set_pool_size(tag, size))
+}
+
+/// Queue adding an event listener to a node. See [`MsgChannel::add_listener`]
+pub fn add_listener(event: impl WritableText, handler: HandlerId, root: MaybeId) {
+    channel(|c| c.add_listener(event, handler, root))
+}
+
+/// Queue adding a listener that dispatches at most one event per animation frame. See [`MsgChannel::add_coalesced_listener`]
+pub fn add_coalesced_listener(event: impl WritableText, handler: HandlerId, root: MaybeId) {
+    channel(|c| c.add_coalesced_listener(event, handler, root))
+}
+
+/// Queue removing an event listener from a node. See [`MsgChannel::remove_listener`]
+pub fn remove_listener(event: impl WritableText, handler: HandlerId, root: MaybeId) {
+    channel(|c| c.remove_listener(event, handler, root))
+}
+
+/// Queue subscribing a handler to animation frames. See [`MsgChannel::subscribe_animation_frames`]
+pub fn subscribe_animation_frames(handler: HandlerId) {
+    channel(|c| c.subscribe_animation_frames(handler))
+}
+
+/// Queue unsubscribing a handler from animation frames. See [`MsgChannel::unsubscribe_animation_frames`]
+pub fn unsubscribe_animation_frames(handler: HandlerId) {
+    channel(|c| c.unsubscribe_animation_frames(handler))
+}
+
+/// Queue operations that are applied inside of a view transition. The closure receives the batch of the transition and must not use the global channel. See [`MsgChannel::with_view_transition`]
+pub fn with_view_transition<T>(handler: HandlerId, f: impl FnOnce(&mut Batch) -> T) -> T {
+    channel(|c| c.with_view_transition(handler, f))
+}
+
+/// Queue keeping the size of a canvas in sync with its size on the page. See [`MsgChannel::observe_canvas_size`]
+pub fn observe_canvas_size(root: MaybeId, handler: HandlerId) {
+    channel(|c| c.observe_canvas_size(root, handler))
+}
+
+/// Queue no longer keeping the size of a canvas in sync. See [`MsgChannel::unobserve_canvas_size`]
+pub fn unobserve_canvas_size(root: MaybeId) {
+    channel(|c| c.unobserve_canvas_size(root))
+}
+
+/// Queue subscribing a handler to a media query. See [`MsgChannel::subscribe_media_query`]
+pub fn subscribe_media_query(query: impl WritableText, handler: HandlerId) {
+    channel(|c| c.subscribe_media_query(query, handler))
+}
+
+/// Queue unsubscribing a handler from a media query. See [`MsgChannel::unsubscribe_media_query`]
+pub fn unsubscribe_media_query(query: impl WritableText, handler: HandlerId) {
+    channel(|c| c.unsubscribe_media_query(query, handler))
+}
+
+/// Queue forwarding the lifecycle callbacks of a custom element to a handler. See [`MsgChannel::observe_custom_element`]
+pub fn observe_custom_element(tag: impl WritableText, handler: HandlerId) {
+    channel(|c| c.observe_custom_element(tag, handler))
+}
+
+/// Queue stopping forwarding the lifecycle callbacks of a custom element. See [`MsgChannel::unobserve_custom_element`]
+pub fn unobserve_custom_element(tag: impl WritableText, handler: HandlerId) {
+    channel(|c| c.unobserve_custom_element(tag, handler))
+}
+
+/// Queue showing a dialog as a modal. See [`MsgChannel::show_modal`]
+pub fn show_modal(root: MaybeId) {
+    channel(|c| c.show_modal(root))
+}
+
+/// Queue closing a dialog. See [`MsgChannel::close_dialog`]
+pub fn close_dialog(root: MaybeId) {
+    channel(|c| c.close_dialog(root))
+}
+
+/// Queue showing a popover. See [`MsgChannel::show_popover`]
+pub fn show_popover(root: MaybeId) {
+    channel(|c| c.show_popover(root))
+}
+
+/// Queue hiding a popover. See [`MsgChannel::hide_popover`]
+pub fn hide_popover(root: MaybeId) {
+    channel(|c| c.hide_popover(root))
+}
+
+/// Queue requesting fullscreen for an element. See [`MsgChannel::request_fullscreen`]
+pub fn request_fullscreen(root: MaybeId, handler: HandlerId) {
+    channel(|c| c.request_fullscreen(root, handler))
+}
+
+/// Queue leaving fullscreen. See [`MsgChannel::exit_fullscreen`]
+pub fn exit_fullscreen() {
+    channel(|c| c.exit_fullscreen())
+}
+
+/// Queue locking the pointer to an element. See [`MsgChannel::request_pointer_lock`]
+pub fn request_pointer_lock(root: MaybeId, handler: HandlerId) {
+    channel(|c| c.request_pointer_lock(root, handler))
+}
+
+/// Queue releasing the pointer. See [`MsgChannel::exit_pointer_lock`]
+pub fn exit_pointer_lock() {
+    channel(|c| c.exit_pointer_lock())
+}
+
+/// Queue reading an item from local or session storage. See [`MsgChannel::get_storage_item`]
+pub fn get_storage_item(area: StorageArea, key: impl WritableText) -> Query<Option<String>> {
+    channel(|c| c.get_storage_item(area, key))
+}
+
+/// Queue reading the focused element. See [`MsgChannel::active_element`]
+pub fn active_element() -> Query<Option<NodeId>> {
+    channel(|c| c.active_element())
+}
+
+/// Queue reading the topmost element at a point in the viewport. See [`MsgChannel::element_from_point`]
+pub fn element_from_point(x: i32, y: i32) -> Query<Option<NodeId>> {
+    channel(|c| c.element_from_point(x, y))
+}
+
+/// Queue reading if an element is rendered. See [`MsgChannel::check_visibility`]
+pub fn check_visibility(root: MaybeId) -> Query<bool> {
+    channel(|c| c.check_visibility(root))
+}
+
+/// Queue setting when an image loads and how it is decoded. See [`MsgChannel::set_image_loading`]
+pub fn set_image_loading(lazy: bool, async_decoding: bool, root: MaybeId) {
+    channel(|c| c.set_image_loading(lazy, async_decoding, root))
+}
+
+/// Queue decoding an image. See [`MsgChannel::decode_image`]
+pub fn decode_image(root: MaybeId) -> Query<bool> {
+    channel(|c| c.decode_image(root))
+}
+
+/// Queue setting an item in local or session storage. See [`MsgChannel::set_storage_item`]
+pub fn set_storage_item(area: StorageArea, key: impl WritableText, value: impl WritableText) {
+    channel(|c| c.set_storage_item(area, key, value))
+}
+
+/// Queue removing an item from local or session storage. See [`MsgChannel::remove_storage_item`]
+pub fn remove_storage_item(area: StorageArea, key: impl WritableText) {
+    channel(|c| c.remove_storage_item(area, key))
+}
+
+/// The handlers of the global channel. See [`MsgChannel::handlers`]
+///
+/// The returned handlers share their closures with the channel, so they can be kept and used from inside of a handler while the channel is in use.
+pub fn handlers() -> Handlers {
+    channel(|c| c.handlers().clone())
+}
+
+/// Queue pushing the last node onto the stack of parents. See [`MsgChannel::push_parent`]
+pub fn push_parent() {
+    channel(|c| c.push_parent())
+}
+
+/// Queue appending the last node to the topmost parent and popping it. See [`MsgChannel::pop_parent`]
+pub fn pop_parent() {
+    channel(|c| c.pop_parent())
+}
+
+/// Queue appending the last node to the topmost parent and pushing it. See [`MsgChannel::open_parent`]
+pub fn open_parent() {
+    channel(|c| c.open_parent())
+}
+
+/// Queue appending the last node to the topmost parent. See [`MsgChannel::append_to_parent`]
+pub fn append_to_parent() {
+    channel(|c| c.append_to_parent())
+}
+
+/// Queue popping the topmost parent without appending to it. See [`MsgChannel::close_parent`]
+pub fn close_parent() {
+    channel(|c| c.close_parent())
+}
+
+/// Queue growing the node table. See [`MsgChannel::hint_node_capacity`]
+pub fn hint_node_capacity(capacity: u32) {
+    channel(|c| c.hint_node_capacity(capacity))
+}
+
+/// Queue the operations of a batch. See [`MsgChannel::append`]
+pub fn append(batch: Batch) {
+    channel(|c| c.append(batch))
+}
+
+/// Queue the operations of a component rendered into a subtree. See [`MsgChannel::submit_subtree`]
+pub fn submit_subtree(subtree: SubtreeChannel) {
+    channel(|c| c.submit_subtree(subtree))
+}
+
+/// Tear down the interpreter and create a new one immediately. See [`MsgChannel::restart_interpreter`]
+pub fn restart_interpreter(keep: KeepNodes) {
+    channel(|c| c.restart_interpreter(keep))
+}
+
+/// Run a batch immediately. See [`MsgChannel::run_batch`]
+pub fn run_batch(batch: impl PreparedBatch) -> Result<(), DomError> {
+    channel(|c| c.run_batch(batch))
+}
@@ -0,0 +1,1433 @@
+//! An in-memory dom that batches can be applied to without a browser. On native targets the [`crate::MsgChannel`] applies operations to a [`HeadlessDom`] so the same rendering code can run on the server, and the resulting tree can be serialized to html with [`HeadlessDom::outer_html`].
+//!
+//! Example:
+//! ```rust
+//! use sledgehammer::{headless::HeadlessDom, sledgehammer_encoder::batch::Batch, MaybeId, NodeId};
+//!
+//! let mut dom = HeadlessDom::default();
+//! let body = dom.create_element("body");
+//! dom.set_node(NodeId(0), body);
+//!
+//! let mut batch = Batch::default();
+//! batch.create_element("p", None);
+//! batch.set_text("Hello from the server", MaybeId::LastNode);
+//! batch.append_child(MaybeId::Node(NodeId(0)), MaybeId::LastNode);
+//! dom.run_batch(&batch.finalize()).unwrap();
+//!
+//! assert_eq!(dom.outer_html(body), "<body><p>Hello from the server</p></body>");
+//! ```
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use sledgehammer_encoder::{
+    attribute::AnyAttribute,
+    batch::{Batch, PreparedBatch},
+    decoder::{DecodedNode, DecodedOp, Decoder},
+    element::AnyElement,
+    HandlerId, MaybeId, NodeId, StorageArea,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use sledgehammer_encoder::decoder::DecoderState;
+
+use crate::{
+    channel::{DomError, FlushStats},
+    events::{
+        CanvasSizeData, Event, EventData, FrameData, Handlers, MediaQueryData, ViewTransitionData,
+    },
+    inverse::{attribute_name, run_with_inverse, InverseDom, Snapshot},
+    queries::Queries,
+};
+
+/// A reference to a node in a [`HeadlessDom`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeRef(usize);
+
+/// A node in a [`HeadlessDom`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeadlessNode {
+    Element(HeadlessElement),
+    Text(String),
+}
+
+/// An element in a [`HeadlessDom`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeadlessElement {
+    pub tag: String,
+    pub namespace: Option<String>,
+    /// The attributes of the element in the order they were first set
+    pub attributes: Vec<HeadlessAttribute>,
+    pub children: Vec<NodeRef>,
+}
+
+/// An attribute of a [`HeadlessElement`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeadlessAttribute {
+    pub name: String,
+    pub namespace: Option<String>,
+    pub value: String,
+}
+
+#[derive(Debug, Clone)]
+struct Slot {
+    parent: Option<NodeRef>,
+    node: HeadlessNode,
+    /// The event types and handlers of the listeners added to the node
+    listeners: Vec<(String, HandlerId)>,
+    /// The value of a form control set with [`Batch::set_value`] or by an input event
+    value: Option<String>,
+    /// If the node is a popover shown with [`Batch::show_popover`]
+    popover_open: bool,
+}
+
+/// A handler subscribed to a media query
+#[derive(Debug, Clone)]
+struct MediaSubscription {
+    query: String,
+    handler: HandlerId,
+    /// The state the handler last received
+    delivered: Option<bool>,
+}
+
+/// An in-memory dom. Nodes are never freed, so create a new [`HeadlessDom`] for every document you render.
+#[derive(Debug, Clone, Default)]
+pub struct HeadlessDom {
+    nodes: Vec<Slot>,
+    /// The nodes stored with an id and the generation they were stored with, by the index of the id. Ids can be sparse, so they are not stored in a vec indexed by the id
+    ids: BTreeMap<u32, (u8, NodeRef)>,
+    last_node: Option<NodeRef>,
+    /// The parents pushed with [`Batch::push_parent`]
+    parents: Vec<NodeRef>,
+    /// The values cached with an id for cached attributes
+    cached_values: Vec<String>,
+    /// The media queries handlers are subscribed to with [`Batch::subscribe_media_query`]
+    media_queries: Vec<MediaSubscription>,
+    /// The handlers subscribed with [`Batch::subscribe_animation_frames`], in the order they were subscribed
+    animation_frames: Vec<HandlerId>,
+    /// The canvases observed with [`Batch::observe_canvas_size`], the ids they were stored with and their handlers
+    canvases: Vec<(NodeRef, NodeId, HandlerId)>,
+    /// The custom element tags and handlers observed with [`Batch::observe_custom_element`]
+    custom_elements: Vec<(String, HandlerId)>,
+    /// The handlers of the view transitions that were applied and the errors their operations failed with, until [`HeadlessDom::finish_view_transitions`]
+    view_transitions: Vec<(HandlerId, Option<String>)>,
+    /// The element in fullscreen
+    fullscreen: Option<NodeRef>,
+    /// The element the pointer is locked to
+    pointer_lock: Option<NodeRef>,
+    /// The focused element set with [`HeadlessDom::set_active_element`]
+    active_element: Option<NodeRef>,
+    /// The items in local storage
+    local_storage: BTreeMap<String, String>,
+    /// The items in session storage
+    session_storage: BTreeMap<String, String>,
+    /// The queries reads like [`Batch::get_storage_item`] answer
+    queries: Queries,
+    /// Where the idle batch that ran out of time stopped and its last node
+    #[cfg(not(target_arch = "wasm32"))]
+    idle: Option<(DecoderState, Option<NodeRef>)>,
+}
+
+/// Elements that never have children or an end tag
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "basefont", "bgsound", "br", "col", "embed", "frame", "hr", "img", "input",
+    "keygen", "link", "meta", "param", "source", "track", "wbr",
+];
+
+/// Elements whose text children are serialized without escaping
+const RAW_TEXT_ELEMENTS: &[&str] = &[
+    "style",
+    "script",
+    "xmp",
+    "iframe",
+    "noembed",
+    "noframes",
+    "plaintext",
+];
+
+impl HeadlessDom {
+    /// Create a dom that answers reads like [`Batch::get_storage_item`] in `queries`. A dom created with [`HeadlessDom::default`] answers reads in queries nothing waits for
+    pub fn with_queries(queries: Queries) -> Self {
+        Self {
+            queries,
+            ..Self::default()
+        }
+    }
+
+    /// Create a detached element
+    pub fn create_element(&mut self, tag: &str) -> NodeRef {
+        self.insert(HeadlessNode::Element(HeadlessElement {
+            tag: tag.to_string(),
+            namespace: None,
+            attributes: Vec::new(),
+            children: Vec::new(),
+        }))
+    }
+
+    /// Create a detached text node
+    pub fn create_text_node(&mut self, text: &str) -> NodeRef {
+        self.insert(HeadlessNode::Text(text.to_string()))
+    }
+
+    /// Assign an id to a node so operations can refer to it
+    pub fn set_node(&mut self, id: NodeId, node: NodeRef) {
+        self.ids.insert(id.index(), (id.generation(), node));
+    }
+
+    /// Get the node stored with an id. Returns None if no node was stored with the id or it was stored with a different generation.
+    pub fn get_node(&self, id: NodeId) -> Option<NodeRef> {
+        self.stored(id).ok()
+    }
+
+    pub fn node(&self, node: NodeRef) -> &HeadlessNode {
+        &self.nodes[node.0].node
+    }
+
+    pub fn parent(&self, node: NodeRef) -> Option<NodeRef> {
+        self.nodes[node.0].parent
+    }
+
+    pub fn children(&self, node: NodeRef) -> &[NodeRef] {
+        match self.node(node) {
+            HeadlessNode::Element(el) => &el.children,
+            HeadlessNode::Text(_) => &[],
+        }
+    }
+
+    /// The event types and handlers of the listeners added to a node, in the order they were added
+    pub fn listeners(&self, node: NodeRef) -> &[(String, HandlerId)] {
+        &self.nodes[node.0].listeners
+    }
+
+    /// The value of a form control that was set with [`Batch::set_value`] or by an input event. Returns None if the value was never set, in which case a browser would show the `value` attribute.
+    pub fn value(&self, node: NodeRef) -> Option<&str> {
+        self.nodes[node.0].value.as_deref()
+    }
+
+    /// If the node is a popover shown with [`Batch::show_popover`]. Dialogs shown with [`Batch::show_modal`] have the `open` attribute instead, like in a browser
+    pub fn popover_open(&self, node: NodeRef) -> bool {
+        self.nodes[node.0].popover_open
+    }
+
+    /// Dispatch an event to the listeners for its type on a node and then on each of its ancestors, like an event that bubbles in a browser. Dispatching stops at the node where a handler calls [`Event::stop_propagation`]. The listeners are collected before any handler runs.
+    ///
+    /// An event with [`EventData::Input`](crate::events::EventData::Input) data sets the value of the target first, like the edit the user made, and a value passed to [`Event::set_value`] is set after the handlers return.
+    ///
+    /// Returns false if a handler called [`Event::prevent_default`].
+    pub fn dispatch_event(&mut self, target: NodeRef, event: &Event, handlers: &Handlers) -> bool {
+        if let EventData::Input(input) = &event.data {
+            self.nodes[target.0].value = Some(input.value.clone());
+        }
+        let mut path = Vec::new();
+        let mut node = Some(target);
+        while let Some(current) = node {
+            let listening = self.nodes[current.0]
+                .listeners
+                .iter()
+                .filter(|(name, _)| *name == event.name)
+                .map(|(_, handler)| *handler)
+                .collect::<Vec<_>>();
+            path.push(listening);
+            node = self.parent(current);
+        }
+        for listening in path {
+            for handler in listening {
+                handlers.dispatch(handler, event);
+            }
+            if event.propagation_stopped() {
+                break;
+            }
+        }
+        if let Some(value) = event.take_value() {
+            self.nodes[target.0].value = Some(value);
+        }
+        !event.default_prevented()
+    }
+
+    /// The element in fullscreen after [`Batch::request_fullscreen`]. The headless dom grants every request and does not dispatch the change events
+    pub fn fullscreen_element(&self) -> Option<NodeRef> {
+        self.fullscreen
+    }
+
+    /// The element the pointer is locked to after [`Batch::request_pointer_lock`]. The headless dom grants every request and does not dispatch the change events
+    pub fn pointer_lock_element(&self) -> Option<NodeRef> {
+        self.pointer_lock
+    }
+
+    /// The focused element. The headless dom has no focus of its own, so this is the element set with [`HeadlessDom::set_active_element`]
+    pub fn active_element(&self) -> Option<NodeRef> {
+        self.active_element
+    }
+
+    /// Focus an element, or remove the focus. Reads with [`Batch::active_element`] answer with the id of the element
+    pub fn set_active_element(&mut self, element: Option<NodeRef>) {
+        self.active_element = element;
+    }
+
+    /// If an element is rendered like `checkVisibility()` in a browser. The headless dom has no stylesheets, so an element is rendered unless it or one of its ancestors has the `hidden` attribute or an inline `display: none` style
+    pub fn check_visibility(&self, element: NodeRef) -> bool {
+        let mut current = Some(element);
+        while let Some(node) = current {
+            let hidden = self.attribute_value(&node, "hidden", None).is_some()
+                || self.style_value(&node, "display").as_deref() == Some("none");
+            if hidden {
+                return false;
+            }
+            current = self.parent(node);
+        }
+        true
+    }
+
+    /// The custom element tags and the handlers their lifecycle callbacks are forwarded to, in the order they were observed. The headless dom has no custom elements, so it never dispatches lifecycle events
+    pub fn observed_custom_elements(&self) -> &[(String, HandlerId)] {
+        &self.custom_elements
+    }
+
+    /// The items in local or session storage. Storage starts empty and lasts as long as the dom
+    pub fn storage(&self, area: StorageArea) -> &BTreeMap<String, String> {
+        match area {
+            StorageArea::Local => &self.local_storage,
+            StorageArea::Session => &self.session_storage,
+        }
+    }
+
+    /// The items in local or session storage, to set up storage before a batch reads it
+    pub fn storage_mut(&mut self, area: StorageArea) -> &mut BTreeMap<String, String> {
+        match area {
+            StorageArea::Local => &mut self.local_storage,
+            StorageArea::Session => &mut self.session_storage,
+        }
+    }
+
+    /// The media queries and the handlers subscribed to them, in the order they were subscribed
+    pub fn media_queries(&self) -> impl Iterator<Item = (&str, HandlerId)> + '_ {
+        self.media_queries
+            .iter()
+            .map(|subscription| (subscription.query.as_str(), subscription.handler))
+    }
+
+    /// Change if a media query matches and dispatch a `change` event with [`EventData::MediaQuery`](crate::events::EventData::MediaQuery) data to the handlers subscribed to the query. The headless dom has no media to evaluate queries against, so handlers only receive the states set here.
+    ///
+    /// Handlers that were subscribed since the last change receive the state even if it did not change, like the event a browser dispatches when a handler subscribes.
+    pub fn set_media_matches(&mut self, query: &str, matches: bool, handlers: &Handlers) {
+        let mut receiving = Vec::new();
+        for subscription in &mut self.media_queries {
+            if subscription.query == query && subscription.delivered != Some(matches) {
+                subscription.delivered = Some(matches);
+                receiving.push(subscription.handler);
+            }
+        }
+        for handler in receiving {
+            let event = Event::new(
+                "change",
+                EventData::MediaQuery(MediaQueryData {
+                    media: query.to_string(),
+                    matches,
+                }),
+            );
+            handlers.dispatch(handler, &event);
+        }
+    }
+
+    /// The handlers subscribed to animation frames, in the order they were subscribed
+    pub fn animation_frame_handlers(&self) -> &[HandlerId] {
+        &self.animation_frames
+    }
+
+    /// Dispatch an `animationframe` event with [`EventData::AnimationFrame`](crate::events::EventData::AnimationFrame) data to every handler subscribed to animation frames. The headless dom has no frames, so this is how tests step an animation.
+    pub fn animation_frame(&self, timestamp: f64, handlers: &Handlers) {
+        for &handler in &self.animation_frames {
+            let event = Event::new(
+                "animationframe",
+                EventData::AnimationFrame(FrameData { timestamp }),
+            );
+            handlers.dispatch(handler, &event);
+        }
+    }
+
+    /// Dispatch a `viewtransitionend` event with [`EventData::ViewTransition`] data to the handler of every view transition that was applied since the last call. The headless dom has nothing to animate, so it applies the operations of a transition in place and this is how tests end the transitions.
+    pub fn finish_view_transitions(&mut self, handlers: &Handlers) {
+        for (handler, error) in std::mem::take(&mut self.view_transitions) {
+            let data = ViewTransitionData {
+                animated: false,
+                error,
+            };
+            handlers.dispatch(
+                handler,
+                &Event::new("viewtransitionend", EventData::ViewTransition(data)),
+            );
+        }
+    }
+
+    /// The canvases observed with [`Batch::observe_canvas_size`] and their handlers
+    pub fn observed_canvases(&self) -> impl Iterator<Item = (NodeRef, HandlerId)> + '_ {
+        self.canvases
+            .iter()
+            .map(|&(canvas, _, handler)| (canvas, handler))
+    }
+
+    /// Resize a canvas observed with [`Batch::observe_canvas_size`] like a `ResizeObserver` in a browser: set its `width` and `height` to the css size times the device pixel ratio and dispatch a `canvasresize` event with [`EventData::CanvasSize`](crate::events::EventData::CanvasSize) data to its handler. The headless dom has no layout, so canvases are only resized here. Canvases that are not observed are not changed.
+    pub fn resize_canvas(
+        &mut self,
+        canvas: NodeRef,
+        width: f64,
+        height: f64,
+        device_pixel_ratio: f64,
+        handlers: &Handlers,
+    ) {
+        let Some(&(_, node, handler)) = self
+            .canvases
+            .iter()
+            .find(|(observed, ..)| *observed == canvas)
+        else {
+            return;
+        };
+        let data = CanvasSizeData {
+            node,
+            width,
+            height,
+            device_pixel_ratio,
+            pixel_width: (width * device_pixel_ratio).round() as u32,
+            pixel_height: (height * device_pixel_ratio).round() as u32,
+        };
+        for (name, value) in [("width", data.pixel_width), ("height", data.pixel_height)] {
+            // the node was checked to be a canvas when it was observed
+            let _ = self.set_attribute(canvas, &AnyAttribute::Str(name), &value.to_string());
+        }
+        handlers.dispatch(
+            handler,
+            &Event::new("canvasresize", EventData::CanvasSize(data)),
+        );
+    }
+
+    /// The text of the node and all of its descendants
+    pub fn text_content(&self, node: NodeRef) -> String {
+        let mut text = String::new();
+        self.write_text(node, &mut text);
+        text
+    }
+
+    fn write_text(&self, node: NodeRef, out: &mut String) {
+        match self.node(node) {
+            HeadlessNode::Text(text) => out.push_str(text),
+            HeadlessNode::Element(el) => {
+                for child in &el.children {
+                    self.write_text(*child, out);
+                }
+            }
+        }
+    }
+
+    /// Serialize the node and its descendants to html the same way `outerHTML` does in the browser
+    pub fn outer_html(&self, node: NodeRef) -> String {
+        let mut html = String::new();
+        self.write_html(node, false, &mut html);
+        html
+    }
+
+    /// Serialize the descendants of the node to html the same way `innerHTML` does in the browser
+    pub fn inner_html(&self, node: NodeRef) -> String {
+        let mut html = String::new();
+        let raw = self.is_raw_text(node);
+        for child in self.children(node) {
+            self.write_html(*child, raw, &mut html);
+        }
+        html
+    }
+
+    fn is_raw_text(&self, node: NodeRef) -> bool {
+        match self.node(node) {
+            HeadlessNode::Element(el) => {
+                el.namespace.is_none() && RAW_TEXT_ELEMENTS.contains(&el.tag.as_str())
+            }
+            HeadlessNode::Text(_) => false,
+        }
+    }
+
+    fn write_html(&self, node: NodeRef, raw: bool, out: &mut String) {
+        match self.node(node) {
+            HeadlessNode::Text(text) if raw => out.push_str(text),
+            HeadlessNode::Text(text) => escape(text, false, out),
+            HeadlessNode::Element(el) => {
+                let _ = write!(out, "<{}", el.tag);
+                for attr in &el.attributes {
+                    let _ = write!(out, " {}=\"", attr.name);
+                    escape(&attr.value, true, out);
+                    out.push('"');
+                }
+                out.push('>');
+                if el.namespace.is_none() && VOID_ELEMENTS.contains(&el.tag.as_str()) {
+                    return;
+                }
+                let raw = self.is_raw_text(node);
+                for child in &el.children {
+                    self.write_html(*child, raw, out);
+                }
+                let _ = write!(out, "</{}>", el.tag);
+            }
+        }
+    }
+
+    /// Apply a batch of operations to the dom
+    ///
+    /// Operations before a failing operation are still applied.
+    pub fn run_batch(&mut self, batch: impl PreparedBatch) -> Result<(), DomError> {
+        self.run(batch.msg(), batch.str(), false).map(|_| ())
+    }
+
+    /// Apply a batch of operations to the dom and return a batch that undoes them. Applying the inverse batch the same way returns a batch that redoes them.
+    ///
+    /// `scratch` is an id the inverse batch stores nodes with while it restores removed nodes. Only changes inside nodes that are stored with an id are undone, and removed nodes are restored as new nodes with the same ids.
+    ///
+    /// Operations before a failing operation are still applied, but no inverse is returned for them.
+    ///
+    /// Example:
+    /// ```rust
+    /// use sledgehammer::{headless::HeadlessDom, sledgehammer_encoder::batch::Batch, MaybeId, NodeId};
+    ///
+    /// let mut dom = HeadlessDom::default();
+    /// let body = dom.create_element("body");
+    /// dom.set_node(NodeId(0), body);
+    ///
+    /// let mut batch = Batch::default();
+    /// batch.create_element("p", None);
+    /// batch.append_child(MaybeId::Node(NodeId(0)), MaybeId::LastNode);
+    /// let undo = dom.run_batch_with_inverse(&batch.finalize(), NodeId(1)).unwrap();
+    /// assert_eq!(dom.inner_html(body), "<p></p>");
+    ///
+    /// dom.run_batch(&undo.finalize()).unwrap();
+    /// assert_eq!(dom.inner_html(body), "");
+    /// ```
+    pub fn run_batch_with_inverse(
+        &mut self,
+        batch: impl PreparedBatch,
+        scratch: NodeId,
+    ) -> Result<Batch, DomError> {
+        self.run_with_inverse(batch.msg(), batch.str(), scratch)
+    }
+
+    /// Apply all operations in a finalized message and return a batch that undoes them
+    pub(crate) fn run_with_inverse(
+        &mut self,
+        msg: &[u8],
+        str_buf: &[u8],
+        scratch: NodeId,
+    ) -> Result<Batch, DomError> {
+        run_with_inverse(self, msg, str_buf, scratch)
+    }
+
+    /// Apply all operations in a finalized message. Operations before a failing operation are still applied.
+    pub(crate) fn run(
+        &mut self,
+        msg: &[u8],
+        str_buf: &[u8],
+        profile: bool,
+    ) -> Result<Option<FlushStats>, DomError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            "run_batch",
+            msg_bytes = msg.len(),
+            str_bytes = str_buf.len(),
+            ops = tracing::field::Empty,
+            duration_ms = tracing::field::Empty,
+        )
+        .entered();
+        let start = profile.then(std::time::Instant::now);
+        let mut ops = 0;
+        for op in Decoder::new(msg, str_buf) {
+            let (offset, op) = op.map_err(|err| DomError {
+                offset: err.offset,
+                message: err.to_string(),
+            })?;
+            if op == DecodedOp::Stop {
+                break;
+            }
+            self.apply(op)
+                .map_err(|message| DomError { offset, message })?;
+            ops += 1;
+        }
+        let stats = start.map(|start| FlushStats {
+            msg_bytes: msg.len(),
+            str_bytes: str_buf.len(),
+            ops,
+            duration_ms: start.elapsed().as_secs_f64() * 1000.0,
+        });
+        #[cfg(feature = "tracing")]
+        if let Some(stats) = &stats {
+            _span
+                .record("ops", stats.ops)
+                .record("duration_ms", stats.duration_ms);
+        }
+        Ok(stats)
+    }
+
+    /// Apply the operations in a finalized message until the deadline from [`crate::channel::now_ms`] passes. If `resume` is true, the message that ran out of time last is continued. Returns true if the message finished.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn run_idle(
+        &mut self,
+        msg: &[u8],
+        str_buf: &[u8],
+        deadline: f64,
+        resume: bool,
+    ) -> Result<bool, DomError> {
+        let (mut decoder, last_node) = match self.idle.take().filter(|_| resume) {
+            Some((state, last_node)) => (Decoder::resume(msg, str_buf, state), last_node),
+            None => (Decoder::new(msg, str_buf), self.last_node),
+        };
+        // idle batches keep their own last node
+        let last_node = std::mem::replace(&mut self.last_node, last_node);
+        let mut ops = 0;
+        let result = loop {
+            let (offset, op) = match decoder.next() {
+                Some(Ok(op)) => op,
+                Some(Err(err)) => {
+                    break Err(DomError {
+                        offset: err.offset,
+                        message: err.to_string(),
+                    })
+                }
+                None => break Ok(true),
+            };
+            if op == DecodedOp::Stop {
+                break Ok(true);
+            }
+            if let Err(message) = self.apply(op) {
+                break Err(DomError { offset, message });
+            }
+            ops += 1;
+            // check the time after every four operations like the javascript interpreter
+            if ops % 4 == 0 && crate::channel::now_ms() >= deadline {
+                self.idle = Some((decoder.state(), self.last_node));
+                break Ok(false);
+            }
+        };
+        self.last_node = last_node;
+        result
+    }
+
+    /// Forget the state of the interpreter like a new interpreter that takes over the page. Keeps the nodes stored with the ids in `keep`, or every stored node if it is None. The listeners on the nodes and every subscription are removed, because the listeners of a disposed interpreter do nothing.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn restart(&mut self, keep: Option<&[NodeId]>) {
+        if let Some(keep) = keep {
+            let kept: Vec<_> = keep
+                .iter()
+                .filter_map(|&id| Some((id, self.get_node(id)?)))
+                .collect();
+            self.ids.clear();
+            for (id, node) in kept {
+                self.set_node(id, node);
+            }
+        }
+        for slot in &mut self.nodes {
+            slot.listeners.clear();
+        }
+        self.last_node = None;
+        self.parents.clear();
+        self.cached_values.clear();
+        self.media_queries.clear();
+        self.animation_frames.clear();
+        self.canvases.clear();
+        self.custom_elements.clear();
+        self.view_transitions.clear();
+        self.idle = None;
+    }
+
+    fn insert(&mut self, node: HeadlessNode) -> NodeRef {
+        self.nodes.push(Slot {
+            parent: None,
+            node,
+            listeners: Vec::new(),
+            value: None,
+            popover_open: false,
+        });
+        NodeRef(self.nodes.len() - 1)
+    }
+
+    fn stored(&self, id: NodeId) -> Result<NodeRef, String> {
+        match self.ids.get(&id.index()) {
+            Some((generation, node)) => {
+                if *generation != id.generation() {
+                    return Err(format!(
+                        "node {} was used with the stale generation {}, the current generation is {}",
+                        id.index(),
+                        id.generation(),
+                        generation
+                    ));
+                }
+                Ok(*node)
+            }
+            _ => Err(format!("node {} was used before it was stored", id.index())),
+        }
+    }
+
+    fn resolve(&self, id: MaybeId) -> Result<NodeRef, String> {
+        match id {
+            MaybeId::LastNode => self
+                .last_node
+                .ok_or_else(|| "there is no last node".to_string()),
+            MaybeId::Node(id) => self.stored(id),
+        }
+    }
+
+    /// The ids a node is stored with
+    fn ids_of(&self, node: NodeRef) -> impl Iterator<Item = NodeId> + '_ {
+        self.ids
+            .iter()
+            .filter(move |(_, (_, stored))| *stored == node)
+            .map(|(index, (generation, _))| NodeId::with_generation(*index, *generation))
+    }
+
+    fn element_mut(&mut self, node: NodeRef) -> Result<&mut HeadlessElement, String> {
+        match &mut self.nodes[node.0].node {
+            HeadlessNode::Element(el) => Ok(el),
+            HeadlessNode::Text(_) => Err("the node is not an element".to_string()),
+        }
+    }
+
+    fn create_any_element(&mut self, tag: &AnyElement) -> NodeRef {
+        let (tag, namespace) = match tag {
+            #[cfg(feature = "elements")]
+            AnyElement::Element(el) => (el.name(), None),
+            #[cfg(feature = "elements")]
+            AnyElement::InNamespace(el) => (el.0.name(), Some(el.1)),
+            AnyElement::Str(el) => (*el, None),
+            AnyElement::InNamespaceStr(el) => (el.0, Some(el.1)),
+        };
+        self.insert(HeadlessNode::Element(HeadlessElement {
+            tag: tag.to_string(),
+            namespace: namespace.map(str::to_string),
+            attributes: Vec::new(),
+            children: Vec::new(),
+        }))
+    }
+
+    fn set_attribute(
+        &mut self,
+        node: NodeRef,
+        attr: &AnyAttribute,
+        value: &str,
+    ) -> Result<(), String> {
+        let (name, namespace) = attribute_name(attr);
+        let el = self.element_mut(node)?;
+        match el
+            .attributes
+            .iter_mut()
+            .find(|a| a.name == name && a.namespace.as_deref() == namespace)
+        {
+            Some(existing) => existing.value = value.to_string(),
+            None => el.attributes.push(HeadlessAttribute {
+                name: name.to_string(),
+                namespace: namespace.map(str::to_string),
+                value: value.to_string(),
+            }),
+        }
+        Ok(())
+    }
+
+    fn remove_attribute(&mut self, node: NodeRef, attr: &AnyAttribute) -> Result<(), String> {
+        let (name, namespace) = attribute_name(attr);
+        self.element_mut(node)?
+            .attributes
+            .retain(|a| !(a.name == name && a.namespace.as_deref() == namespace));
+        Ok(())
+    }
+
+    /// Add or remove a class like `classList.toggle` with a force argument. The class attribute is only written if the class list changes, and it is written with the classes separated by single spaces
+    fn toggle_class(&mut self, node: NodeRef, class: &str, add: bool) -> Result<(), String> {
+        if class.is_empty() || class.contains(char::is_whitespace) {
+            return Err(format!("{class:?} is not a valid class"));
+        }
+        let el = self.element_mut(node)?;
+        let position = el
+            .attributes
+            .iter()
+            .position(|a| a.name == "class" && a.namespace.is_none());
+        let current = position.map(|i| el.attributes[i].value.as_str());
+        let mut classes: Vec<&str> = Vec::new();
+        for token in current.unwrap_or_default().split_whitespace() {
+            if !classes.contains(&token) {
+                classes.push(token);
+            }
+        }
+        if classes.contains(&class) == add {
+            return Ok(());
+        }
+        if add {
+            classes.push(class);
+        } else {
+            classes.retain(|token| *token != class);
+        }
+        let value = classes.join(" ");
+        match position {
+            Some(i) => el.attributes[i].value = value,
+            None => el.attributes.push(HeadlessAttribute {
+                name: "class".to_string(),
+                namespace: None,
+                value,
+            }),
+        }
+        Ok(())
+    }
+
+    /// Set or remove a property in the style attribute of an element. The attribute is serialized the same way the browser serializes `style.cssText`
+    fn set_style(&mut self, node: NodeRef, style: &str, value: Option<&str>) -> Result<(), String> {
+        let el = self.element_mut(node)?;
+        let position = el
+            .attributes
+            .iter()
+            .position(|a| a.name == "style" && a.namespace.is_none());
+        let current = position.map(|i| el.attributes[i].value.as_str());
+        if current.is_none() && value.is_none() {
+            return Ok(());
+        }
+        let mut declarations: Vec<(&str, &str)> = current
+            .unwrap_or_default()
+            .split(';')
+            .filter_map(|declaration| declaration.split_once(':'))
+            .map(|(property, value)| (property.trim(), value.trim()))
+            .filter(|(property, _)| !property.is_empty())
+            .collect();
+        declarations.retain(|(property, _)| *property != style);
+        if let Some(value) = value.filter(|value| !value.is_empty()) {
+            declarations.push((style, value));
+        }
+        let css_text = declarations
+            .iter()
+            .map(|(property, value)| format!("{}: {};", property, value))
+            .collect::<Vec<_>>()
+            .join(" ");
+        match position {
+            Some(i) => el.attributes[i].value = css_text,
+            None => el.attributes.push(HeadlessAttribute {
+                name: "style".to_string(),
+                namespace: None,
+                value: css_text,
+            }),
+        }
+        Ok(())
+    }
+
+    fn set_text(&mut self, node: NodeRef, text: &str) {
+        let children = match &mut self.nodes[node.0].node {
+            HeadlessNode::Text(data) => {
+                *data = text.to_string();
+                return;
+            }
+            HeadlessNode::Element(el) => std::mem::take(&mut el.children),
+        };
+        // setting textContent replaces all of the children with a single text node
+        for child in children {
+            self.nodes[child.0].parent = None;
+        }
+        if !text.is_empty() {
+            let text = self.create_text_node(text);
+            self.append(node, text);
+        }
+    }
+
+    fn detach(&mut self, node: NodeRef) {
+        if let Some(parent) = self.nodes[node.0].parent.take() {
+            if let HeadlessNode::Element(el) = &mut self.nodes[parent.0].node {
+                el.children.retain(|child| *child != node);
+            }
+        }
+    }
+
+    fn append(&mut self, parent: NodeRef, child: NodeRef) {
+        let len = self.children(parent).len();
+        self.insert_at(parent, len, child);
+    }
+
+    fn insert_at(&mut self, parent: NodeRef, index: usize, child: NodeRef) {
+        self.nodes[child.0].parent = Some(parent);
+        if let HeadlessNode::Element(el) = &mut self.nodes[parent.0].node {
+            el.children.insert(index, child);
+        }
+    }
+
+    fn index_in_parent(&self, node: NodeRef) -> Option<(NodeRef, usize)> {
+        let parent = self.parent(node)?;
+        let index = self.children(parent).iter().position(|c| *c == node)?;
+        Some((parent, index))
+    }
+
+    /// Check that inserting the node into the parent would not create a cycle
+    fn check_hierarchy(&self, parent: NodeRef, node: NodeRef) -> Result<(), String> {
+        if matches!(self.node(parent), HeadlessNode::Text(_)) {
+            return Err("HierarchyRequestError: text nodes cannot have children".to_string());
+        }
+        let mut ancestor = Some(parent);
+        while let Some(current) = ancestor {
+            if current == node {
+                return Err(
+                    "HierarchyRequestError: the new child is an ancestor of the parent".to_string(),
+                );
+            }
+            ancestor = self.parent(current);
+        }
+        Ok(())
+    }
+
+    /// Insert the nodes into the parent before the reference node or at the end if there is no reference node
+    fn insert_nodes(
+        &mut self,
+        parent: NodeRef,
+        before: Option<NodeRef>,
+        nodes: &[NodeRef],
+    ) -> Result<(), String> {
+        for node in nodes {
+            self.check_hierarchy(parent, *node)?;
+        }
+        for node in nodes {
+            self.detach(*node);
+        }
+        let index = match before {
+            Some(before) => self
+                .index_in_parent(before)
+                .map(|(_, index)| index)
+                .unwrap_or_else(|| self.children(parent).len()),
+            None => self.children(parent).len(),
+        };
+        for (offset, node) in nodes.iter().enumerate() {
+            self.insert_at(parent, index + offset, *node);
+        }
+        Ok(())
+    }
+
+    /// The first sibling after the node that is not one of the nodes being moved
+    fn viable_next_sibling(&self, node: NodeRef, nodes: &[NodeRef]) -> Option<NodeRef> {
+        let (parent, index) = self.index_in_parent(node)?;
+        self.children(parent)[index + 1..]
+            .iter()
+            .find(|sibling| !nodes.contains(sibling))
+            .copied()
+    }
+
+    fn next_sibling(&self, node: NodeRef) -> Option<NodeRef> {
+        let (parent, index) = self.index_in_parent(node)?;
+        self.children(parent).get(index + 1).copied()
+    }
+
+    fn clone_node(&mut self, node: NodeRef) -> NodeRef {
+        let cloned = match self.node(node).clone() {
+            HeadlessNode::Element(mut el) => {
+                let children = std::mem::take(&mut el.children);
+                let cloned = self.insert(HeadlessNode::Element(el));
+                for child in children {
+                    let child = self.clone_node(child);
+                    self.append(cloned, child);
+                }
+                return cloned;
+            }
+            text => text,
+        };
+        self.insert(cloned)
+    }
+
+    fn build(&mut self, node: &DecodedNode) -> Result<NodeRef, String> {
+        let (id, built) = match node {
+            DecodedNode::Text { id, text } => (id, self.create_text_node(text)),
+            DecodedNode::Element {
+                id,
+                kind,
+                attrs,
+                children,
+            } => {
+                let el = self.create_any_element(kind);
+                for (attr, value) in attrs {
+                    self.set_attribute(el, attr, value)?;
+                }
+                for child in children {
+                    let child = self.build(child)?;
+                    self.append(el, child);
+                }
+                (id, el)
+            }
+        };
+        if let Some(id) = id {
+            self.set_node(*id, built);
+        }
+        Ok(built)
+    }
+
+    fn apply(&mut self, op: DecodedOp) -> Result<(), String> {
+        match op {
+            DecodedOp::FirstChild => {
+                let last = self.resolve(MaybeId::LastNode)?;
+                self.last_node = self.children(last).first().copied();
+            }
+            DecodedOp::NextSibling => {
+                let last = self.resolve(MaybeId::LastNode)?;
+                self.last_node = self.next_sibling(last);
+            }
+            DecodedOp::ParentNode => {
+                let last = self.resolve(MaybeId::LastNode)?;
+                self.last_node = self.parent(last);
+            }
+            DecodedOp::StoreWithId(id) => {
+                let last = self.resolve(MaybeId::LastNode)?;
+                self.set_node(id, last);
+            }
+            DecodedOp::SetLastNode(id) => self.last_node = Some(self.stored(id)?),
+            // the headless dom does not pool nodes or preallocate ids
+            DecodedOp::Stop
+            | DecodedOp::NoOp
+            | DecodedOp::SetPoolSize { .. }
+            | DecodedOp::HintNodeCapacity(_) => {}
+            DecodedOp::BuildFullElement(node) => self.last_node = Some(self.build(&node)?),
+            DecodedOp::AppendChildren { root, child } => {
+                let (root, child) = (self.resolve(root)?, self.resolve(child)?);
+                self.insert_nodes(root, None, &[child])?;
+            }
+            DecodedOp::ReplaceWith { root, nodes } => {
+                let root = self.resolve(root)?;
+                let nodes = self.resolve_all(&nodes)?;
+                if let Some(parent) = self.parent(root) {
+                    let next = self.viable_next_sibling(root, &nodes);
+                    if !nodes.contains(&root) {
+                        self.detach(root);
+                    }
+                    self.insert_nodes(parent, next, &nodes)?;
+                }
+            }
+            DecodedOp::InsertAfter { root, nodes } => {
+                let root = self.resolve(root)?;
+                let nodes = self.resolve_all(&nodes)?;
+                if let Some(parent) = self.parent(root) {
+                    let next = self.viable_next_sibling(root, &nodes);
+                    self.insert_nodes(parent, next, &nodes)?;
+                }
+            }
+            DecodedOp::InsertBefore { root, nodes } => {
+                let root = self.resolve(root)?;
+                let nodes = self.resolve_all(&nodes)?;
+                if let Some((parent, index)) = self.index_in_parent(root) {
+                    // the first sibling before the root that is not being moved
+                    let previous = self.children(parent)[..index]
+                        .iter()
+                        .rev()
+                        .find(|sibling| !nodes.contains(sibling))
+                        .copied();
+                    let before = match previous {
+                        Some(previous) => self.viable_next_sibling(previous, &nodes),
+                        None => self
+                            .children(parent)
+                            .iter()
+                            .find(|child| !nodes.contains(child))
+                            .copied(),
+                    };
+                    self.insert_nodes(parent, before, &nodes)?;
+                }
+            }
+            DecodedOp::Remove(id) => {
+                let node = self.resolve(id)?;
+                self.detach(node);
+            }
+            DecodedOp::CreateTextNode { text, id } => {
+                let node = self.create_text_node(text);
+                if let Some(id) = id {
+                    self.set_node(id, node);
+                }
+                self.last_node = Some(node);
+            }
+            DecodedOp::CreateElement { tag, id } => {
+                let node = self.create_any_element(&tag);
+                if let Some(id) = id {
+                    self.set_node(id, node);
+                }
+                self.last_node = Some(node);
+            }
+            DecodedOp::SetText { root, text } => {
+                let root = self.resolve(root)?;
+                self.set_text(root, text);
+            }
+            // there is no locale data to format the value with, so the text is the value as it was sent
+            DecodedOp::SetFormattedText { root, value, .. } => {
+                let root = self.resolve(root)?;
+                self.set_text(root, value);
+            }
+            DecodedOp::ViewTransition {
+                handler,
+                msg,
+                str_buf,
+            } => {
+                // the interpreter applies the operations later, between batches, so they do not change the last node of the batch
+                let last_node = self.last_node;
+                let error = self.run(msg, str_buf, false).err().map(|err| err.message);
+                self.last_node = last_node;
+                self.view_transitions.push((handler, error));
+            }
+            DecodedOp::SetAttribute { root, attr, value } => {
+                let root = self.resolve(root)?;
+                self.set_attribute(root, &attr, value)?;
+            }
+            DecodedOp::RemoveAttribute { root, attr } => {
+                let root = self.resolve(root)?;
+                self.remove_attribute(root, &attr)?;
+            }
+            DecodedOp::ToggleClass { root, class, add } => {
+                let root = self.resolve(root)?;
+                self.toggle_class(root, class, add)?;
+            }
+            DecodedOp::SetImageLoading {
+                root,
+                lazy,
+                async_decoding,
+            } => {
+                let root = self.resolve(root)?;
+                let loading = if lazy { "lazy" } else { "eager" };
+                let decoding = if async_decoding { "async" } else { "auto" };
+                self.set_attribute(root, &AnyAttribute::Str("loading"), loading)?;
+                self.set_attribute(root, &AnyAttribute::Str("decoding"), decoding)?;
+            }
+            // images are never loaded, so there is nothing to wait for before they are shown
+            DecodedOp::DecodeImage { root, query } => {
+                let root = self.resolve(root)?;
+                if self.element_mut(root)?.tag != "img" {
+                    return Err("the node is not an image".to_string());
+                }
+                self.queries.answer(query, Some(true.to_string()));
+            }
+            // there are no animation frames to coalesce events in, so coalesced listeners are added like other listeners
+            DecodedOp::AddListener {
+                root,
+                event,
+                handler,
+                ..
+            } => {
+                let root = self.resolve(root)?;
+                let listeners = &mut self.nodes[root.0].listeners;
+                // like addEventListener, adding the same listener twice does nothing
+                if !listeners
+                    .iter()
+                    .any(|(name, added)| name == event && *added == handler)
+                {
+                    listeners.push((event.to_string(), handler));
+                }
+            }
+            DecodedOp::RemoveListener {
+                root,
+                event,
+                handler,
+            } => {
+                let root = self.resolve(root)?;
+                self.nodes[root.0]
+                    .listeners
+                    .retain(|(name, added)| !(name == event && *added == handler));
+            }
+            DecodedOp::SetValue { root, value } => {
+                let root = self.resolve(root)?;
+                self.element_mut(root)?;
+                self.nodes[root.0].value = Some(value.to_string());
+            }
+            DecodedOp::ShowModal(root) | DecodedOp::CloseDialog(root) => {
+                let root = self.resolve(root)?;
+                let el = self.element_mut(root)?;
+                if el.tag != "dialog" || el.namespace.is_some() {
+                    return Err(format!("{} is not a dialog element", el.tag));
+                }
+                let open = el
+                    .attributes
+                    .iter()
+                    .position(|attr| attr.name == "open" && attr.namespace.is_none());
+                match (op, open) {
+                    (DecodedOp::ShowModal(_), None) => el.attributes.push(HeadlessAttribute {
+                        name: "open".to_string(),
+                        namespace: None,
+                        value: String::new(),
+                    }),
+                    (DecodedOp::CloseDialog(_), Some(index)) => {
+                        el.attributes.remove(index);
+                    }
+                    // showing an open dialog or closing a closed dialog does nothing
+                    _ => {}
+                }
+            }
+            DecodedOp::ShowPopover(root) | DecodedOp::HidePopover(root) => {
+                let root = self.resolve(root)?;
+                let el = self.element_mut(root)?;
+                if !el
+                    .attributes
+                    .iter()
+                    .any(|attr| attr.name == "popover" && attr.namespace.is_none())
+                {
+                    return Err(format!(
+                        "the {} element does not have the popover attribute",
+                        el.tag
+                    ));
+                }
+                self.nodes[root.0].popover_open = matches!(op, DecodedOp::ShowPopover(_));
+            }
+            DecodedOp::RequestFullscreen { root, .. } => {
+                let root = self.resolve(root)?;
+                self.element_mut(root)?;
+                self.fullscreen = Some(root);
+            }
+            DecodedOp::ExitFullscreen => self.fullscreen = None,
+            DecodedOp::RequestPointerLock { root, .. } => {
+                let root = self.resolve(root)?;
+                self.element_mut(root)?;
+                self.pointer_lock = Some(root);
+            }
+            DecodedOp::ExitPointerLock => self.pointer_lock = None,
+            DecodedOp::SubscribeAnimationFrames(handler) => {
+                if !self.animation_frames.contains(&handler) {
+                    self.animation_frames.push(handler);
+                }
+            }
+            DecodedOp::UnsubscribeAnimationFrames(handler) => self
+                .animation_frames
+                .retain(|subscribed| *subscribed != handler),
+            DecodedOp::ObserveCanvasSize { root, handler } => {
+                let canvas = self.resolve(root)?;
+                let el = self.element_mut(canvas)?;
+                if el.tag != "canvas" || el.namespace.is_some() {
+                    return Err(format!("{} is not a canvas element", el.tag));
+                }
+                let node = match root {
+                    MaybeId::Node(id) => id,
+                    MaybeId::LastNode => self
+                        .ids_of(canvas)
+                        .next()
+                        .ok_or("the canvas must be stored with an id to be observed")?,
+                };
+                self.canvases.retain(|(observed, ..)| *observed != canvas);
+                self.canvases.push((canvas, node, handler));
+            }
+            DecodedOp::UnobserveCanvasSize(root) => {
+                let canvas = self.resolve(root)?;
+                self.canvases.retain(|(observed, ..)| *observed != canvas);
+            }
+            DecodedOp::SubscribeMediaQuery { query, handler } => {
+                // like matchMedia listeners, subscribing the same handler twice does nothing
+                if !self.media_queries.iter().any(|subscription| {
+                    subscription.query == query && subscription.handler == handler
+                }) {
+                    self.media_queries.push(MediaSubscription {
+                        query: query.to_string(),
+                        handler,
+                        delivered: None,
+                    });
+                }
+            }
+            DecodedOp::UnsubscribeMediaQuery { query, handler } => {
+                self.media_queries.retain(|subscription| {
+                    !(subscription.query == query && subscription.handler == handler)
+                })
+            }
+            DecodedOp::ObserveCustomElement { tag, handler } => {
+                if !self
+                    .custom_elements
+                    .iter()
+                    .any(|(observed, added)| observed == tag && *added == handler)
+                {
+                    self.custom_elements.push((tag.to_string(), handler));
+                }
+            }
+            DecodedOp::UnobserveCustomElement { tag, handler } => self
+                .custom_elements
+                .retain(|(observed, added)| !(observed == tag && *added == handler)),
+            DecodedOp::GetStorageItem { area, key, query } => {
+                let value = self.storage(area).get(key).cloned();
+                self.queries.answer(query, value);
+            }
+            DecodedOp::ActiveElement(query) => {
+                let id = self
+                    .active_element
+                    .and_then(|element| self.ids_of(element).next());
+                self.queries.answer(query, id.map(|id| id.0.to_string()));
+            }
+            // the headless dom has no layout, so there is never an element at a point
+            DecodedOp::ElementFromPoint { query, .. } => {
+                self.queries.answer(query, None);
+            }
+            DecodedOp::CheckVisibility { root, query } => {
+                let root = self.resolve(root)?;
+                self.element_mut(root)?;
+                let visible = self.check_visibility(root);
+                self.queries.answer(query, Some(visible.to_string()));
+            }
+            DecodedOp::SetStorageItem { area, key, value } => {
+                self.storage_mut(area)
+                    .insert(key.to_string(), value.to_string());
+            }
+            DecodedOp::RemoveStorageItem { area, key } => {
+                self.storage_mut(area).remove(key);
+            }
+            DecodedOp::CacheValue { id, value } => {
+                let index = id as usize;
+                if self.cached_values.len() <= index {
+                    self.cached_values.resize(index + 1, String::new());
+                }
+                self.cached_values[index] = value.to_string();
+            }
+            DecodedOp::SetAttributeCached { root, attr, value } => {
+                let root = self.resolve(root)?;
+                let value = self
+                    .cached_values
+                    .get(value as usize)
+                    .cloned()
+                    .ok_or_else(|| format!("no value was cached with the id {}", value))?;
+                self.set_attribute(root, &attr, &value)?;
+            }
+            DecodedOp::SetStyle { root, style, value } => {
+                let root = self.resolve(root)?;
+                self.set_style(root, style, Some(value))?;
+            }
+            DecodedOp::RemoveStyle { root, style } => {
+                let root = self.resolve(root)?;
+                self.set_style(root, style, None)?;
+            }
+            DecodedOp::CloneNode { id, new_id } => {
+                let node = self.resolve(id)?;
+                let cloned = self.clone_node(node);
+                if let MaybeId::Node(new_id) = new_id {
+                    self.set_node(new_id, cloned);
+                }
+                self.last_node = Some(cloned);
+            }
+            DecodedOp::AdoptIframeDocument { .. } => {
+                return Err("iframes do not have a document in a headless dom".to_string())
+            }
+            DecodedOp::SetSanitizedHtml { .. } => {
+                return Err("html cannot be parsed in a headless dom".to_string())
+            }
+            DecodedOp::PushParent => {
+                let last = self.resolve(MaybeId::LastNode)?;
+                self.parents.push(last);
+            }
+            DecodedOp::PopParent => {
+                let child = self.resolve(MaybeId::LastNode)?;
+                let parent = self.pop_parent()?;
+                self.insert_nodes(parent, None, &[child])?;
+                self.last_node = Some(parent);
+            }
+            DecodedOp::OpenParent | DecodedOp::AppendToParent => {
+                let child = self.resolve(MaybeId::LastNode)?;
+                let parent = self
+                    .top_parent()
+                    .ok_or_else(|| "there is no parent to append to".to_string())?;
+                self.insert_nodes(parent, None, &[child])?;
+                if let DecodedOp::OpenParent = op {
+                    self.parents.push(child);
+                }
+            }
+            DecodedOp::CloseParent => {
+                self.last_node = Some(self.pop_parent()?);
+            }
+        }
+        Ok(())
+    }
+
+    fn pop_parent(&mut self) -> Result<NodeRef, String> {
+        self.parents
+            .pop()
+            .ok_or_else(|| "there is no parent to append to".to_string())
+    }
+
+    fn resolve_all(&self, ids: &[MaybeId]) -> Result<Vec<NodeRef>, String> {
+        ids.iter().map(|id| self.resolve(*id)).collect()
+    }
+}
+
+impl InverseDom for HeadlessDom {
+    type Node = NodeRef;
+
+    fn resolve_node(&self, id: MaybeId) -> Result<NodeRef, String> {
+        self.resolve(id)
+    }
+
+    fn parent_of(&self, node: &NodeRef) -> Option<NodeRef> {
+        self.parent(*node)
+    }
+
+    fn children_of(&self, node: &NodeRef) -> Vec<NodeRef> {
+        self.children(*node).to_vec()
+    }
+
+    fn id_of(&self, node: &NodeRef, except: NodeId) -> Option<NodeId> {
+        self.ids_of(*node).find(|id| id.index() != except.index())
+    }
+
+    fn snapshot(&self, node: &NodeRef) -> Option<Snapshot> {
+        Some(match self.node(*node) {
+            HeadlessNode::Text(text) => Snapshot::Text(text.clone()),
+            HeadlessNode::Element(el) => Snapshot::Element {
+                tag: el.tag.clone(),
+                namespace: el.namespace.clone(),
+                attributes: el
+                    .attributes
+                    .iter()
+                    .map(|a| (a.name.clone(), a.namespace.clone(), a.value.clone()))
+                    .collect(),
+            },
+        })
+    }
+
+    fn attribute_value(
+        &self,
+        node: &NodeRef,
+        name: &str,
+        namespace: Option<&str>,
+    ) -> Option<String> {
+        match self.node(*node) {
+            HeadlessNode::Element(el) => el
+                .attributes
+                .iter()
+                .find(|a| a.name == name && a.namespace.as_deref() == namespace)
+                .map(|a| a.value.clone()),
+            HeadlessNode::Text(_) => None,
+        }
+    }
+
+    fn style_value(&self, node: &NodeRef, property: &str) -> Option<String> {
+        self.attribute_value(node, "style", None)?
+            .split(';')
+            .filter_map(|declaration| declaration.split_once(':'))
+            .find(|(name, _)| name.trim() == property)
+            .map(|(_, value)| value.trim().to_string())
+    }
+
+    fn form_value(&self, node: &NodeRef) -> String {
+        match self.value(*node) {
+            Some(value) => value.to_string(),
+            None => self
+                .attribute_value(node, "value", None)
+                .unwrap_or_default(),
+        }
+    }
+
+    fn popover_open(&self, node: &NodeRef) -> bool {
+        HeadlessDom::popover_open(self, *node)
+    }
+
+    fn canvas_handler(&self, node: &NodeRef) -> Option<HandlerId> {
+        self.observed_canvases()
+            .find_map(|(canvas, handler)| (canvas == *node).then_some(handler))
+    }
+
+    fn top_parent(&self) -> Option<NodeRef> {
+        self.parents.last().copied()
+    }
+
+    fn end_view_transition(&mut self, handler: HandlerId, error: Option<String>) {
+        self.view_transitions.push((handler, error));
+    }
+
+    fn apply_op(&mut self, op: DecodedOp) -> Result<(), String> {
+        self.apply(op)
+    }
+}
+
+/// Escape text the same way the html serialization algorithm does
+fn escape(text: &str, attribute: bool, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '\u{a0}' => out.push_str("&nbsp;"),
+            '"' if attribute => out.push_str("&quot;"),
+            '<' if !attribute => out.push_str("&lt;"),
+            '>' if !attribute => out.push_str("&gt;"),
+            c => out.push(c),
+        }
+    }
+}
@@ -0,0 +1,685 @@
+//! Recording the inverse of a batch while it is applied.
+//!
+//! Before each operation runs, the backend captures the state the operation overwrites: old attribute values, styles and text, and the subtrees of the parents whose children change. After the operation runs, an undo step is encoded that restores that state. The undo steps are returned in reverse order as one batch.
+//!
+//! Nodes in the undo batch are found by walking from the closest ancestor that is stored with an id, so only changes inside nodes that are stored with an id are recorded. Removed and moved nodes are recreated from templates of the captured subtrees with the ids they were stored with, so references to the removed node objects themselves do not point at the restored nodes.
+
+use sledgehammer_encoder::{
+    attribute::AnyAttribute,
+    batch::Batch,
+    decoder::{DecodedNode, DecodedOp, Decoder},
+    element::AnyElement,
+    HandlerId, InNamespace, MaybeId, NodeId, WithNsExt,
+};
+
+use crate::channel::DomError;
+
+/// The parts of a single node that are needed to recreate it
+pub(crate) enum Snapshot {
+    Text(String),
+    Element {
+        tag: String,
+        namespace: Option<String>,
+        /// The name, namespace and value of every attribute
+        attributes: Vec<(String, Option<String>, String)>,
+    },
+}
+
+/// A dom that can report the state an operation is about to overwrite
+pub(crate) trait InverseDom {
+    type Node: Clone + PartialEq;
+
+    fn resolve_node(&self, id: MaybeId) -> Result<Self::Node, String>;
+
+    fn parent_of(&self, node: &Self::Node) -> Option<Self::Node>;
+
+    fn children_of(&self, node: &Self::Node) -> Vec<Self::Node>;
+
+    /// An id the node is currently stored with other than `except`
+    fn id_of(&self, node: &Self::Node, except: NodeId) -> Option<NodeId>;
+
+    /// Returns None for nodes that cannot be recreated, like comments
+    fn snapshot(&self, node: &Self::Node) -> Option<Snapshot>;
+
+    fn attribute_value(
+        &self,
+        node: &Self::Node,
+        name: &str,
+        namespace: Option<&str>,
+    ) -> Option<String>;
+
+    fn style_value(&self, node: &Self::Node, property: &str) -> Option<String>;
+
+    /// The value a form control shows
+    fn form_value(&self, node: &Self::Node) -> String;
+
+    /// If the node is a popover that is showing
+    fn popover_open(&self, node: &Self::Node) -> bool;
+
+    /// The handler the size of the canvas is dispatched to if it is observed
+    fn canvas_handler(&self, node: &Self::Node) -> Option<HandlerId>;
+
+    /// The parent on top of the stack of parents
+    fn top_parent(&self) -> Option<Self::Node>;
+
+    /// Dispatch the end of a view transition whose operations were applied, with the error an operation failed with
+    fn end_view_transition(&mut self, handler: HandlerId, error: Option<String>);
+
+    fn apply_op(&mut self, op: DecodedOp) -> Result<(), String>;
+}
+
+/// A captured subtree that can be built again
+enum Template {
+    Text {
+        id: Option<NodeId>,
+        text: String,
+    },
+    Element {
+        id: Option<NodeId>,
+        tag: String,
+        namespace: Option<String>,
+        attributes: Vec<(String, Option<String>, String)>,
+        children: Vec<Template>,
+    },
+}
+
+impl Template {
+    fn decoded(&self) -> DecodedNode<'_> {
+        match self {
+            Template::Text { id, text } => DecodedNode::Text { id: *id, text },
+            Template::Element {
+                id,
+                tag,
+                namespace,
+                attributes,
+                children,
+            } => DecodedNode::Element {
+                id: *id,
+                kind: match namespace {
+                    Some(namespace) => AnyElement::InNamespaceStr(InNamespace(tag, namespace)),
+                    None => AnyElement::Str(tag),
+                },
+                attrs: attributes
+                    .iter()
+                    .map(|(name, namespace, value)| {
+                        let attr = match namespace {
+                            Some(namespace) => {
+                                AnyAttribute::InNamespaceStr(InNamespace(name, namespace))
+                            }
+                            None => AnyAttribute::Str(name),
+                        };
+                        (attr, value.as_str())
+                    })
+                    .collect(),
+                children: children.iter().map(Template::decoded).collect(),
+            },
+        }
+    }
+}
+
+/// The path from the closest ancestor stored with an id to a node
+struct Address {
+    anchor: NodeId,
+    /// The index of the child to move to at every level
+    path: Vec<usize>,
+}
+
+impl Address {
+    /// Encode the operations that make the node the last node
+    fn encode(&self, batch: &mut Batch) {
+        batch.set_last_node(self.anchor);
+        for index in &self.path {
+            batch.first_child();
+            for _ in 0..*index {
+                batch.next_sibling();
+            }
+        }
+    }
+}
+
+/// Apply a finalized message and return a batch that undoes every operation that was applied. `scratch` is an id the inverse batch overwrites to hold the parent or sibling it inserts restored nodes relative to.
+pub(crate) fn run_with_inverse<D: InverseDom>(
+    dom: &mut D,
+    msg: &[u8],
+    str_buf: &[u8],
+    scratch: NodeId,
+) -> Result<Batch, DomError> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!(
+        "run_with_inverse",
+        msg_bytes = msg.len(),
+        str_bytes = str_buf.len()
+    )
+    .entered();
+    let mut recorder = Recorder {
+        dom,
+        scratch,
+        steps: Vec::new(),
+    };
+    for op in Decoder::new(msg, str_buf) {
+        let (offset, op) = op.map_err(|err| DomError {
+            offset: err.offset,
+            message: err.to_string(),
+        })?;
+        if op == DecodedOp::Stop {
+            break;
+        }
+        recorder
+            .record(op)
+            .map_err(|message| DomError { offset, message })?;
+    }
+    let mut inverse = Batch::default();
+    inverse.extend(recorder.steps.into_iter().rev());
+    Ok(inverse)
+}
+
+struct Recorder<'a, D: InverseDom> {
+    dom: &'a mut D,
+    scratch: NodeId,
+    /// The undo step of every operation that changed the dom in the order the operations ran
+    steps: Vec<Batch>,
+}
+
+impl<D: InverseDom> Recorder<'_, D> {
+    fn record(&mut self, op: DecodedOp) -> Result<(), String> {
+        let mut undo = Batch::default();
+        match &op {
+            DecodedOp::SetAttribute { root, attr, .. }
+            | DecodedOp::SetAttributeCached { root, attr, .. }
+            | DecodedOp::RemoveAttribute { root, attr } => {
+                let node = self.dom.resolve_node(*root)?;
+                let (name, namespace) = attribute_name(attr);
+                // attributes do not change the structure, so the node can be found the same way before and after the operation
+                if let Some(address) = self.address(&node, &[]) {
+                    address.encode(&mut undo);
+                    match (self.dom.attribute_value(&node, name, namespace), namespace) {
+                        (Some(old), Some(namespace)) => undo.set_attribute(
+                            name.in_namespace(namespace),
+                            old.as_str(),
+                            MaybeId::LastNode,
+                        ),
+                        (Some(old), None) => {
+                            undo.set_attribute(name, old.as_str(), MaybeId::LastNode)
+                        }
+                        (None, Some(namespace)) => {
+                            undo.remove_attribute(name.in_namespace(namespace), MaybeId::LastNode)
+                        }
+                        (None, None) => undo.remove_attribute(name, MaybeId::LastNode),
+                    }
+                }
+                self.dom.apply_op(op)?;
+            }
+            DecodedOp::SetImageLoading { root, .. } => {
+                let node = self.dom.resolve_node(*root)?;
+                if let Some(address) = self.address(&node, &[]) {
+                    address.encode(&mut undo);
+                    for name in ["loading", "decoding"] {
+                        match self.dom.attribute_value(&node, name, None) {
+                            Some(old) => undo.set_attribute(name, old.as_str(), MaybeId::LastNode),
+                            None => undo.remove_attribute(name, MaybeId::LastNode),
+                        }
+                    }
+                }
+                self.dom.apply_op(op)?;
+            }
+            DecodedOp::SetStyle { root, style, .. } | DecodedOp::RemoveStyle { root, style } => {
+                let node = self.dom.resolve_node(*root)?;
+                if let Some(address) = self.address(&node, &[]) {
+                    address.encode(&mut undo);
+                    match self.dom.style_value(&node, style) {
+                        Some(old) => undo.set_style(*style, &old, MaybeId::LastNode),
+                        None => undo.remove_style(*style, MaybeId::LastNode),
+                    }
+                }
+                self.dom.apply_op(op)?;
+            }
+            DecodedOp::ToggleClass { root, class, add } => {
+                let node = self.dom.resolve_node(*root)?;
+                let had_class = self
+                    .dom
+                    .attribute_value(&node, "class", None)
+                    .is_some_and(|classes| classes.split_whitespace().any(|c| c == *class));
+                // toggling a class that is already in the requested state does nothing
+                if had_class != *add {
+                    if let Some(address) = self.address(&node, &[]) {
+                        address.encode(&mut undo);
+                        undo.toggle_class_if(*class, had_class, MaybeId::LastNode);
+                    }
+                }
+                self.dom.apply_op(op)?;
+            }
+            DecodedOp::AddListener {
+                root,
+                event,
+                handler,
+                ..
+            }
+            | DecodedOp::RemoveListener {
+                root,
+                event,
+                handler,
+            } => {
+                let node = self.dom.resolve_node(*root)?;
+                // the listeners of a node cannot be read in the browser, so the listener is assumed to not have been added before
+                if let Some(address) = self.address(&node, &[]) {
+                    address.encode(&mut undo);
+                    if matches!(op, DecodedOp::AddListener { .. }) {
+                        undo.remove_listener(*event, *handler, MaybeId::LastNode);
+                    } else {
+                        undo.add_listener(*event, *handler, MaybeId::LastNode);
+                    }
+                }
+                self.dom.apply_op(op)?;
+            }
+            DecodedOp::SetValue { root, .. } => {
+                let node = self.dom.resolve_node(*root)?;
+                if let Some(address) = self.address(&node, &[]) {
+                    address.encode(&mut undo);
+                    undo.set_value(self.dom.form_value(&node).as_str(), MaybeId::LastNode);
+                }
+                self.dom.apply_op(op)?;
+            }
+            DecodedOp::ShowModal(root)
+            | DecodedOp::CloseDialog(root)
+            | DecodedOp::ShowPopover(root)
+            | DecodedOp::HidePopover(root) => {
+                let node = self.dom.resolve_node(*root)?;
+                let was_open = match op {
+                    // the open attribute reflects if a dialog is open
+                    DecodedOp::ShowModal(_) | DecodedOp::CloseDialog(_) => {
+                        self.dom.attribute_value(&node, "open", None).is_some()
+                    }
+                    _ => self.dom.popover_open(&node),
+                };
+                let show = matches!(op, DecodedOp::ShowModal(_) | DecodedOp::ShowPopover(_));
+                if was_open != show {
+                    if let Some(address) = self.address(&node, &[]) {
+                        address.encode(&mut undo);
+                        // a dialog that was shown without being modal is restored as a modal
+                        match op {
+                            DecodedOp::ShowModal(_) => undo.close_dialog(MaybeId::LastNode),
+                            DecodedOp::CloseDialog(_) => undo.show_modal(MaybeId::LastNode),
+                            DecodedOp::ShowPopover(_) => undo.hide_popover(MaybeId::LastNode),
+                            _ => undo.show_popover(MaybeId::LastNode),
+                        }
+                    }
+                }
+                self.dom.apply_op(op)?;
+            }
+            DecodedOp::SubscribeMediaQuery { query, handler } => {
+                // like listeners, the subscription is assumed to not have existed before
+                undo.unsubscribe_media_query(*query, *handler);
+                self.dom.apply_op(op)?;
+            }
+            DecodedOp::UnsubscribeMediaQuery { query, handler } => {
+                undo.subscribe_media_query(*query, *handler);
+                self.dom.apply_op(op)?;
+            }
+            DecodedOp::ObserveCanvasSize { root, .. } => {
+                let node = self.dom.resolve_node(*root)?;
+                // like listeners, the canvas is assumed to not have been observed before
+                if let Some(address) = self.address(&node, &[]) {
+                    address.encode(&mut undo);
+                    undo.unobserve_canvas_size(MaybeId::LastNode);
+                }
+                self.dom.apply_op(op)?;
+            }
+            DecodedOp::UnobserveCanvasSize(root) => {
+                let node = self.dom.resolve_node(*root)?;
+                if let Some(handler) = self.dom.canvas_handler(&node) {
+                    if let Some(address) = self.address(&node, &[]) {
+                        address.encode(&mut undo);
+                        undo.observe_canvas_size(MaybeId::LastNode, handler);
+                    }
+                }
+                self.dom.apply_op(op)?;
+            }
+            DecodedOp::SubscribeAnimationFrames(handler) => {
+                undo.unsubscribe_animation_frames(*handler);
+                self.dom.apply_op(op)?;
+            }
+            DecodedOp::UnsubscribeAnimationFrames(handler) => {
+                undo.subscribe_animation_frames(*handler);
+                self.dom.apply_op(op)?;
+            }
+            DecodedOp::ObserveCustomElement { tag, handler } => {
+                undo.unobserve_custom_element(*tag, *handler);
+                self.dom.apply_op(op)?;
+            }
+            DecodedOp::UnobserveCustomElement { tag, handler } => {
+                undo.observe_custom_element(*tag, *handler);
+                self.dom.apply_op(op)?;
+            }
+            DecodedOp::SetText { root, .. } | DecodedOp::SetFormattedText { root, .. } => {
+                let node = self.dom.resolve_node(*root)?;
+                match self.dom.snapshot(&node) {
+                    Some(Snapshot::Text(old)) => {
+                        if let Some(address) = self.address(&node, &[]) {
+                            address.encode(&mut undo);
+                            undo.set_text(old.as_str(), MaybeId::LastNode);
+                        }
+                        self.dom.apply_op(op)?;
+                    }
+                    // setting the text of an element with no children only adds a text node
+                    Some(_) if self.dom.children_of(&node).is_empty() => {
+                        if let Some(address) = self.address(&node, &[]) {
+                            address.encode(&mut undo);
+                            undo.set_text("", MaybeId::LastNode);
+                        }
+                        self.dom.apply_op(op)?;
+                    }
+                    // otherwise it replaces all of the children
+                    _ => self.record_structural(op, &mut undo, Vec::new(), None, vec![node])?,
+                }
+            }
+            DecodedOp::SetSanitizedHtml { root, .. } => {
+                let node = self.dom.resolve_node(*root)?;
+                self.record_structural(op, &mut undo, Vec::new(), None, vec![node])?;
+            }
+            DecodedOp::Remove(id) => {
+                let node = self.dom.resolve_node(*id)?;
+                let position = self.position(&node, &[]);
+                let template = self.template(&node)?;
+                self.dom.apply_op(op)?;
+                if let (Some((parent, index)), Some(template)) = (position, template) {
+                    self.insert_at(&mut undo, &parent, index, &[], &template);
+                }
+            }
+            DecodedOp::AppendChildren { root, child } => {
+                let root = self.dom.resolve_node(*root)?;
+                let child = self.dom.resolve_node(*child)?;
+                self.record_structural(op, &mut undo, vec![child], None, vec![root])?;
+            }
+            DecodedOp::PopParent | DecodedOp::OpenParent | DecodedOp::AppendToParent => {
+                let root = self
+                    .dom
+                    .top_parent()
+                    .ok_or_else(|| "there is no parent to append to".to_string())?;
+                let child = self.dom.resolve_node(MaybeId::LastNode)?;
+                self.record_structural(op, &mut undo, vec![child], None, vec![root])?;
+            }
+            DecodedOp::ReplaceWith { root, nodes }
+            | DecodedOp::InsertAfter { root, nodes }
+            | DecodedOp::InsertBefore { root, nodes } => {
+                let replaced = matches!(op, DecodedOp::ReplaceWith { .. });
+                let root = self.dom.resolve_node(*root)?;
+                let nodes = nodes
+                    .iter()
+                    .map(|id| self.dom.resolve_node(*id))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let replaced = Some(root.clone()).filter(|root| replaced && !nodes.contains(root));
+                let new_parent = self.dom.parent_of(&root).into_iter().collect();
+                self.record_structural(op, &mut undo, nodes, replaced, new_parent)?;
+            }
+            // these only create detached nodes or change which node an id or the last node refers to
+            DecodedOp::FirstChild
+            | DecodedOp::NextSibling
+            | DecodedOp::ParentNode
+            | DecodedOp::StoreWithId(_)
+            | DecodedOp::SetLastNode(_)
+            | DecodedOp::Stop
+            | DecodedOp::NoOp
+            | DecodedOp::BuildFullElement(_)
+            | DecodedOp::CreateTextNode { .. }
+            | DecodedOp::CreateElement { .. }
+            | DecodedOp::CloneNode { .. }
+            | DecodedOp::AdoptIframeDocument { .. }
+            | DecodedOp::SetPoolSize { .. }
+            | DecodedOp::HintNodeCapacity(_)
+            | DecodedOp::CacheValue { .. }
+            | DecodedOp::PushParent
+            | DecodedOp::CloseParent
+            // queries only read
+            | DecodedOp::ActiveElement(_)
+            | DecodedOp::ElementFromPoint { .. }
+            | DecodedOp::CheckVisibility { .. }
+            | DecodedOp::DecodeImage { .. }
+            // storage is not part of the dom, so it is not restored
+            | DecodedOp::GetStorageItem { .. }
+            | DecodedOp::SetStorageItem { .. }
+            | DecodedOp::RemoveStorageItem { .. }
+            // fullscreen and pointer lock belong to the window and need a user gesture, so they are not restored either
+            | DecodedOp::RequestFullscreen { .. }
+            | DecodedOp::ExitFullscreen
+            | DecodedOp::RequestPointerLock { .. }
+            | DecodedOp::ExitPointerLock => self.dom.apply_op(op)?,
+            DecodedOp::ViewTransition {
+                handler,
+                msg,
+                str_buf,
+            } => {
+                // the operations are recorded like the operations of the batch, and undoing them starts a transition with the same handler
+                let mut transition = Recorder {
+                    dom: &mut *self.dom,
+                    scratch: self.scratch,
+                    steps: Vec::new(),
+                };
+                let mut error = None;
+                for op in Decoder::new(msg, str_buf) {
+                    let result = match op {
+                        Ok((_, DecodedOp::Stop)) => break,
+                        Ok((_, op)) => transition.record(op),
+                        Err(err) => Err(err.to_string()),
+                    };
+                    if let Err(message) = result {
+                        error = Some(message);
+                        break;
+                    }
+                }
+                let steps = transition.steps;
+                undo.with_view_transition(*handler, |undo| undo.extend(steps.into_iter().rev()));
+                self.dom.end_view_transition(*handler, error);
+            }
+        }
+        if undo.op_count() > 0 {
+            self.steps.push(undo);
+        }
+        Ok(())
+    }
+
+    /// Record an operation that moves `nodes` into the dom, removes `replaced` and changes the children of `parents`
+    fn record_structural(
+        &mut self,
+        op: DecodedOp,
+        undo: &mut Batch,
+        nodes: Vec<D::Node>,
+        replaced: Option<D::Node>,
+        mut parents: Vec<D::Node>,
+    ) -> Result<(), String> {
+        // inserting detached nodes is undone by removing them again
+        if !nodes.is_empty() && nodes.iter().all(|node| self.dom.parent_of(node).is_none()) {
+            let restore = match &replaced {
+                Some(replaced) => self.position(replaced, &[]).zip(self.template(replaced)?),
+                None => None,
+            };
+            self.dom.apply_op(op)?;
+            let mut removed = Vec::new();
+            for node in &nodes {
+                if removed.contains(node) {
+                    continue;
+                }
+                if let Some(address) = self.address(node, &removed) {
+                    address.encode(undo);
+                    undo.remove(MaybeId::LastNode);
+                }
+                removed.push(node.clone());
+            }
+            if let Some(((parent, index), template)) = restore {
+                self.insert_at(undo, &parent, index, &nodes, &template);
+            }
+            return Ok(());
+        }
+
+        // otherwise the children of every parent that changes are rebuilt
+        for node in nodes.iter().chain(&replaced) {
+            if let Some(parent) = self.dom.parent_of(node) {
+                parents.push(parent);
+            }
+        }
+        let mut unique: Vec<D::Node> = Vec::new();
+        for parent in parents {
+            if !unique.contains(&parent) {
+                unique.push(parent);
+            }
+        }
+        // a parent inside another parent is restored with the subtree of the outer parent
+        let outer: Vec<D::Node> = unique
+            .iter()
+            .filter(|parent| {
+                !unique
+                    .iter()
+                    .any(|other| other != *parent && self.is_ancestor(other, parent))
+            })
+            .cloned()
+            .collect();
+        let mut captured = Vec::new();
+        for parent in outer {
+            let mut children = Vec::new();
+            for child in self.dom.children_of(&parent) {
+                children.extend(self.template(&child)?);
+            }
+            captured.push((parent, children));
+        }
+        self.dom.apply_op(op)?;
+        // rebuilding a parent only changes its subtree, so rebuilding the deepest parents first keeps the paths to the others valid
+        let mut rebuilds: Vec<(usize, Address, Vec<Template>)> = captured
+            .into_iter()
+            .filter_map(|(parent, children)| {
+                let address = self.address(&parent, &[])?;
+                Some((self.depth(&parent), address, children))
+            })
+            .collect();
+        rebuilds.sort_by_key(|(depth, _, _)| std::cmp::Reverse(*depth));
+        for (_, address, children) in rebuilds {
+            address.encode(undo);
+            undo.store_with_id(self.scratch);
+            undo.set_text("", MaybeId::Node(self.scratch));
+            for child in children {
+                undo.build_decoded_node(&child.decoded());
+                undo.append_child(MaybeId::Node(self.scratch), MaybeId::LastNode);
+            }
+        }
+        Ok(())
+    }
+
+    /// Encode the operations that build the template and insert it as the `index`th child of the parent, ignoring the `excluded` nodes
+    fn insert_at(
+        &self,
+        undo: &mut Batch,
+        parent: &D::Node,
+        index: usize,
+        excluded: &[D::Node],
+        template: &Template,
+    ) {
+        let children: Vec<D::Node> = self
+            .dom
+            .children_of(parent)
+            .into_iter()
+            .filter(|child| !excluded.contains(child))
+            .collect();
+        let (reference, before) = match children.get(index) {
+            Some(sibling) => (self.address(sibling, excluded), true),
+            None => (self.address(parent, excluded), false),
+        };
+        if let Some(reference) = reference {
+            reference.encode(undo);
+            undo.store_with_id(self.scratch);
+            undo.build_decoded_node(&template.decoded());
+            if before {
+                undo.insert_before(MaybeId::Node(self.scratch), MaybeId::LastNode);
+            } else {
+                undo.append_child(MaybeId::Node(self.scratch), MaybeId::LastNode);
+            }
+        }
+    }
+
+    /// The parent of the node and the index of the node in it, ignoring the `excluded` nodes
+    fn position(&self, node: &D::Node, excluded: &[D::Node]) -> Option<(D::Node, usize)> {
+        let parent = self.dom.parent_of(node)?;
+        let index = self
+            .dom
+            .children_of(&parent)
+            .iter()
+            .filter(|child| !excluded.contains(child))
+            .position(|child| child == node)?;
+        Some((parent, index))
+    }
+
+    /// Find the path to a node as if the `excluded` nodes were removed
+    fn address(&self, node: &D::Node, excluded: &[D::Node]) -> Option<Address> {
+        let mut path = Vec::new();
+        let mut current = node.clone();
+        loop {
+            if let Some(anchor) = self.dom.id_of(&current, self.scratch) {
+                path.reverse();
+                return Some(Address { anchor, path });
+            }
+            let (parent, index) = self.position(&current, excluded)?;
+            path.push(index);
+            current = parent;
+        }
+    }
+
+    fn depth(&self, node: &D::Node) -> usize {
+        let mut depth = 0;
+        let mut current = self.dom.parent_of(node);
+        while let Some(node) = current {
+            depth += 1;
+            current = self.dom.parent_of(&node);
+        }
+        depth
+    }
+
+    fn is_ancestor(&self, ancestor: &D::Node, node: &D::Node) -> bool {
+        let mut current = self.dom.parent_of(node);
+        while let Some(node) = current {
+            if node == *ancestor {
+                return true;
+            }
+            current = self.dom.parent_of(&node);
+        }
+        false
+    }
+
+    fn template(&self, node: &D::Node) -> Result<Option<Template>, String> {
+        let id = self.dom.id_of(node, self.scratch);
+        Ok(match self.dom.snapshot(node) {
+            None => None,
+            Some(Snapshot::Text(text)) => Some(Template::Text { id, text }),
+            Some(Snapshot::Element {
+                tag,
+                namespace,
+                attributes,
+            }) => {
+                let mut children = Vec::new();
+                for child in self.dom.children_of(node) {
+                    children.extend(self.template(&child)?);
+                }
+                if children.len() > u8::MAX as usize || attributes.len() > u8::MAX as usize {
+                    return Err(format!(
+                        "the <{}> element has more than 255 children or attributes, so it cannot be restored by an inverse batch",
+                        tag
+                    ));
+                }
+                Some(Template::Element {
+                    id,
+                    tag,
+                    namespace,
+                    attributes,
+                    children,
+                })
+            }
+        })
+    }
+}
+
+pub(crate) fn attribute_name<'a>(attr: &AnyAttribute<'a, 'a>) -> (&'a str, Option<&'a str>) {
+    match *attr {
+        #[cfg(feature = "attributes")]
+        AnyAttribute::Attribute(attr) => (attr.name(), None),
+        #[cfg(feature = "attributes")]
+        AnyAttribute::InNamespace(attr) => (attr.0.name(), Some(attr.1)),
+        AnyAttribute::Str(attr) => (attr, None),
+        AnyAttribute::InNamespaceStr(attr) => (attr.0, Some(attr.1)),
+    }
+}
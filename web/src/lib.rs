@@ -24,6 +24,33 @@
 //!
 //! # Getting started
 //! - All operations go through a [`MsgChannel`] which handles the communication with js.
+//! - On native targets, a [`MsgChannel`] applies operations to a [`headless::HeadlessDom`] that can be rendered to html.
+//! - [`scheduler::FrameScheduler`] batches every update queued during a tick into one flush in the next animation frame.
+//! - [`MsgChannel::stream_into`] appends a large initial render to the document while it is built and flushes it in chunks, so the top of the page is shown first. See [`stream`]
+//! - [`subtree::SubtreeChannel`] gives every component its own batch bound to the node it renders into. The channel stitches the batches together when it is flushed, with child components after their parents, and keeps the size of every component's batch. See [`subtree`]
+//! - [`MsgChannel::restart_interpreter`] tears down the interpreter and creates a new one that keeps the nodes the page is rendered into, so hot reloading and pages recovering from an interpreter that threw do not need a full page refresh. See [`reload`]
+//! - [`MsgChannel::add_listener`] adds event listeners that dispatch events to closures registered in [`MsgChannel::handlers`]. See [`events`]
+//! - [`MsgChannel::add_coalesced_listener`] dispatches the latest `scroll`, `wheel` or `pointermove` event once per animation frame, so event storms do not overwhelm the handler
+//! - [`MsgChannel::set_attribute_if`] and [`MsgChannel::toggle_class_if`] set or remove an attribute or a class depending on a condition, so diffing code does not branch between two operations
+//! - [`MsgChannel::controlled_input`] keeps the value of a form control in rust and reverts or corrects edits. See [`controlled`]
+//! - [`MsgChannel::get_storage_item`] and [`MsgChannel::set_storage_item`] read and write local and session storage in the same batches as the dom operations. Reads return a [`queries::Query`] that resolves when the batch is flushed. See [`queries`]
+//! - [`MsgChannel::subscribe_animation_frames`] dispatches an event with the timestamp of every animation frame to a handler, so animation loops driven from rust use the same handlers as other events
+//! - [`MsgChannel::with_view_transition`] applies a group of operations inside of a view transition, so the browser animates between the old and the new page
+//! - [`MsgChannel::active_element`], [`MsgChannel::element_from_point`] and [`MsgChannel::check_visibility`] read the focused element, the element under a point and if an element is rendered for focus management and hit testing. They return a [`queries::Query`] like storage reads
+//! - [`MsgChannel::observe_canvas_size`] keeps the backing store of a canvas at its css size times `devicePixelRatio` and sends the new size to a handler after every resize
+//! - [`MsgChannel::subscribe_media_query`] dispatches the changes of media queries like `(prefers-color-scheme: dark)` to a handler, so themes and breakpoints can be handled in rust
+//! - [`MsgChannel::observe_custom_element`] forwards the `connectedCallback`, `disconnectedCallback` and `attributeChangedCallback` of custom elements to a handler with the id of the element
+//! - [`MsgChannel::show_modal`] and [`MsgChannel::show_popover`] open modal dialogs and popovers, and their `close` and `toggle` events are decoded into [`events::DialogCloseData`] and [`events::ToggleData`]
+//! - [`MsgChannel::request_fullscreen`] and [`MsgChannel::request_pointer_lock`] take a handler that receives the change and error events, for games and media players
+//! - Pointer and touch events are decoded into [`events::PointerData`] with the pointer type, pressure and contact size, and [`events::TouchData`] with every touch point, for drawing and gesture handling
+//! - `compositionstart`, `compositionupdate` and `compositionend` events carry the text entered with an input method in [`events::CompositionData`], so editors can handle Chinese, Japanese and Korean input
+//! - [`MsgChannel::append_resource_hint`] adds `<link rel="preload">`, `<link rel="prefetch">` and `<link rel="modulepreload">` hints to the head, so routing code can warm the resources of the next route. See [`head`]
+//! - [`MsgChannel::set_formatted_number`] and [`MsgChannel::set_formatted_date`] format numbers, currencies and dates with the `Intl` api of the browser instead of compiling locale data into the wasm module. See [`intl`]
+//! - [`MsgChannel::set_image_loading`] makes images lazy and decoded asynchronously, and [`MsgChannel::decode_image`] returns a [`queries::Query`] that resolves once an image is decoded, so galleries can insert images without them popping in
+//! - [`MsgChannel::set_sanitized_html`] inserts html from untrusted sources, like comments, after the browser removes scripts, event handlers and every element and attribute that is not in an allowlist. See [`sanitize`]
+//! - [`svg::path`] formats the `d` attribute of svg paths directly into the batch, without building a `String` for every frame of a chart
+//! - [`MsgChannel::start_recording`] records every flushed batch so a session can be replayed step by step with [`recording::Replay`].
+//! - With the `global` feature, the `global` module provides a thread-local [`MsgChannel`] with a free function for every operation.
 //!
 //!# Benchmarks
 //!
@@ -74,41 +101,85 @@
 #![allow(non_camel_case_types)]
 
 pub mod channel;
+pub mod controlled;
+pub mod events;
+#[cfg(feature = "global")]
+pub mod global;
+pub mod headless;
+mod inverse;
+pub mod queries;
+pub mod recording;
+pub mod reload;
+pub mod scheduler;
+pub mod stream;
+pub mod subtree;
+#[cfg(all(target_arch = "wasm32", feature = "web-sys-backend"))]
+mod web_sys_backend;
 
-pub use channel::MsgChannel;
+pub use channel::{DomError, FlushStats, MsgChannel};
+#[cfg(feature = "attributes")]
+pub use sledgehammer_encoder::Attribute;
+#[cfg(feature = "elements")]
+pub use sledgehammer_encoder::Element;
 pub use sledgehammer_encoder::{
-    Attribute, Element, ElementBuilder, IntoAttribue, IntoElement, MaybeId, NodeBuilder, NodeId,
-    StaticBatch, TextBuilder, WritableText,
+    ElementBuilder, FragmentBuilder, HandlerId, IntoAttribue, IntoElement, MaybeId, NodeBuilder,
+    NodeId, OwnedElementBuilder, OwnedNodeBuilder, StaticBatch, StorageArea, TextBuilder,
+    WritableText,
 };
 
 pub use sledgehammer_encoder;
+pub use sledgehammer_encoder::head;
+pub use sledgehammer_encoder::intl;
+pub use sledgehammer_encoder::mutations;
+pub use sledgehammer_encoder::sanitize;
+pub use sledgehammer_encoder::svg;
 
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
 use wasm_bindgen::prelude::*;
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
 use web_sys::Node;
 
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
 #[used]
 static mut MSG_PTR: usize = 0;
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
 #[used]
-static mut MSG_PTR_PTR: *const usize = unsafe { &MSG_PTR } as *const usize;
+static mut MSG_PTR_PTR: *const usize = std::ptr::addr_of!(MSG_PTR);
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
 #[used]
 static mut MSG_POS_UPDATED: u8 = 255;
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
 #[used]
-static mut MSG_METADATA_PTR: *const u8 = unsafe { &MSG_POS_UPDATED } as *const u8;
+static mut MSG_METADATA_PTR: *const u8 = std::ptr::addr_of!(MSG_POS_UPDATED);
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
 #[used]
 static mut STR_PTR: usize = 0;
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
 #[used]
-static mut STR_PTR_PTR: *const usize = unsafe { &STR_PTR } as *const usize;
+static mut STR_PTR_PTR: *const usize = std::ptr::addr_of!(STR_PTR);
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
 #[used]
 static mut STR_LEN: usize = 0;
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
 #[used]
-static mut STR_LEN_PTR: *const usize = unsafe { &STR_LEN } as *const usize;
+static mut STR_LEN_PTR: *const usize = std::ptr::addr_of!(STR_LEN);
 
-#[wasm_bindgen(module = "/interpreter_opt.js")]
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
+// with the no-modules feature the interpreter is read from the `sledgehammer` global instead of being imported as a module
+#[cfg_attr(
+    not(feature = "no-modules"),
+    wasm_bindgen(module = "/interpreter_opt.js")
+)]
 // #[wasm_bindgen(module = "/interpreter.js")]
+#[cfg_attr(feature = "no-modules", wasm_bindgen(js_namespace = sledgehammer))]
 extern "C" {
     fn work_last_created();
 
-    fn update_last_memory(mem: JsValue);
+    fn work_last_created_checked() -> JsValue;
+
+    fn work_last_created_profiled() -> JsValue;
+
+    fn work_last_created_idle(deadline: f64, resume: bool) -> JsValue;
 
     pub(crate) type JsInterpreter;
 
@@ -119,10 +190,22 @@ extern "C" {
         msg_ptr: usize,
         str_ptr: usize,
         str_len_ptr: usize,
+        debug: bool,
+        elements: &str,
+        attributes: &str,
     ) -> JsInterpreter;
 
     #[wasm_bindgen(method)]
-    pub(crate) fn UpdateMemory(this: &JsInterpreter, mem: JsValue);
+    pub(crate) fn Work(this: &JsInterpreter);
+
+    #[wasm_bindgen(method)]
+    pub(crate) fn WorkChecked(this: &JsInterpreter) -> JsValue;
+
+    #[wasm_bindgen(method)]
+    pub(crate) fn WorkProfiled(this: &JsInterpreter) -> JsValue;
+
+    #[wasm_bindgen(method)]
+    pub(crate) fn WorkIdle(this: &JsInterpreter, deadline: f64, resume: bool) -> JsValue;
 
     #[wasm_bindgen(method)]
     pub(crate) fn SetNode(this: &JsInterpreter, id: u32, node: Node);
@@ -130,4 +213,65 @@ extern "C" {
     #[allow(unused)]
     #[wasm_bindgen(method)]
     pub(crate) fn GetNode(this: &JsInterpreter, id: u32) -> Node;
+
+    #[wasm_bindgen(method)]
+    pub(crate) fn SetEventHandler(this: &JsInterpreter, handler: &js_sys::Function);
+
+    #[wasm_bindgen(method)]
+    pub(crate) fn SetQueryHandler(this: &JsInterpreter, handler: &js_sys::Function);
+
+    #[wasm_bindgen(method)]
+    pub(crate) fn CopyNodes(this: &JsInterpreter, previous: &JsInterpreter, ids: Option<Vec<u32>>);
+
+    #[wasm_bindgen(method)]
+    pub(crate) fn Dispose(this: &JsInterpreter);
+}
+
+/// The source of the interpreter as a classic script. Running the script defines the `sledgehammer` global that the bindings use when the `no-modules` feature is enabled.
+///
+/// The script is evaluated automatically when the first [`MsgChannel`] is created if the global is not already defined. If your content security policy does not allow evaluating scripts, write the script to a file and load it with a `<script>` tag before the wasm module.
+#[cfg(feature = "no-modules")]
+pub fn interpreter_script() -> String {
+    const MODULE: &str = include_str!("../interpreter_opt.js");
+    let mut script = String::with_capacity(MODULE.len() + 128);
+    let mut exports = Vec::new();
+    let mut rest = MODULE;
+    script.push_str("(function(){");
+    while let Some(idx) = rest.find("export ") {
+        script.push_str(&rest[..idx]);
+        rest = &rest[idx + "export ".len()..];
+        // the name of the exported constant, function or class
+        let declaration = rest
+            .trim_start_matches("const ")
+            .trim_start_matches("function ")
+            .trim_start_matches("class ");
+        let len = declaration
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+            .unwrap_or(declaration.len());
+        exports.push(&declaration[..len]);
+    }
+    script.push_str(rest);
+    script.push_str("\nglobalThis.sledgehammer={");
+    script.push_str(&exports.join(","));
+    script.push_str("};})();");
+    script
+}
+
+/// The source of the javascript module that decompresses batches compressed with [`sledgehammer_encoder::batch::FinalizedBatch::to_compressed_bytes`]. Serve it to clients that receive compressed batches over the network, and apply the decompressed batches with the `WorkBytes` method of the interpreter.
+#[cfg(feature = "compression")]
+pub const DECOMPRESS_JS: &str = include_str!("../decompress.js");
+
+#[cfg(all(
+    feature = "no-modules",
+    target_arch = "wasm32",
+    not(feature = "web-sys-backend")
+))]
+fn install_interpreter() {
+    let global = js_sys::global();
+    if js_sys::Reflect::has(&global, &"sledgehammer".into()).unwrap_or(false) {
+        return;
+    }
+    js_sys::Function::new_no_args(&interpreter_script())
+        .call0(&global)
+        .expect("failed to evaluate the sledgehammer interpreter");
 }
@@ -0,0 +1,151 @@
+//! Values the interpreter reads from the browser and sends back to rust.
+//!
+//! Reads like [`MsgChannel::get_storage_item`] are queued in the batch like any other operation and return a [`Query`]. When the interpreter applies the read, it calls into wasm with the [`QueryId`] of the query and the value it read, which resolves the query. Batches are applied synchronously, so every read in a batch is answered by the time the flush returns, except for [`MsgChannel::decode_image`] which is answered once the image is decoded. A query can be awaited, or checked with [`Query::try_take`] after the flush.
+//!
+//! If the batch fails before the read is applied, the interpreter is restarted before it answers, or the interpreter is a custom interpreter that cannot answer queries, the query is never answered.
+//!
+//! Example:
+//! ```rust
+//! # use sledgehammer::*;
+//! let mut channel = MsgChannel::default();
+//! channel.set_storage_item(StorageArea::Local, "theme", "dark");
+//! let mut theme = channel.get_storage_item(StorageArea::Local, "theme");
+//! channel.flush().unwrap();
+//! assert_eq!(theme.try_take(), Some(Some("dark".to_string())));
+//! ```
+//!
+//! [`MsgChannel::get_storage_item`]: crate::MsgChannel::get_storage_item
+//! [`MsgChannel::decode_image`]: crate::MsgChannel::decode_image
+
+use std::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll, Waker},
+};
+
+pub use sledgehammer_encoder::QueryId;
+
+/// The answer to a query and the task waiting for it
+#[derive(Default)]
+struct Answer {
+    value: Option<Option<String>>,
+    waker: Option<Waker>,
+}
+
+#[derive(Default)]
+struct Pending {
+    answers: Vec<Option<Rc<RefCell<Answer>>>>,
+    /// The indexes of the free slots
+    free: Vec<u32>,
+}
+
+/// The queries of a [`MsgChannel`](crate::MsgChannel) that the interpreter has not answered yet. See [`crate::queries`]
+///
+/// Cloning the queries returns another reference to the same queries.
+#[derive(Clone, Default)]
+pub struct Queries {
+    pending: Rc<RefCell<Pending>>,
+}
+
+impl Queries {
+    /// Register a query that converts the value the interpreter reads with `convert`
+    pub(crate) fn insert<T>(&self, convert: fn(Option<String>) -> T) -> (QueryId, Query<T>) {
+        let answer = Rc::new(RefCell::new(Answer::default()));
+        let mut pending = self.pending.borrow_mut();
+        let index = match pending.free.pop() {
+            Some(index) => {
+                pending.answers[index as usize] = Some(answer.clone());
+                index
+            }
+            None => {
+                pending.answers.push(Some(answer.clone()));
+                pending.answers.len() as u32 - 1
+            }
+        };
+        (QueryId(index), Query { answer, convert })
+    }
+
+    /// Answer a query with the value the interpreter read. Returns false if no query is waiting for an answer with the id.
+    pub fn answer(&self, id: QueryId, value: Option<String>) -> bool {
+        let answer = {
+            let mut pending = self.pending.borrow_mut();
+            let Some(answer) = pending
+                .answers
+                .get_mut(id.0 as usize)
+                .and_then(Option::take)
+            else {
+                return false;
+            };
+            pending.free.push(id.0);
+            answer
+        };
+        let waker = {
+            let mut answer = answer.borrow_mut();
+            answer.value = Some(value);
+            answer.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        true
+    }
+
+    /// The number of queries waiting for an answer
+    pub fn len(&self) -> usize {
+        let pending = self.pending.borrow();
+        pending.answers.len() - pending.free.len()
+    }
+
+    /// If no query is waiting for an answer
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl std::fmt::Debug for Queries {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Queries")
+            .field("pending", &self.len())
+            .finish()
+    }
+}
+
+/// A value the interpreter reads when it applies a batch. See [`crate::queries`]
+///
+/// The query resolves once the batch with the read is flushed.
+#[must_use = "queries do nothing unless they are awaited or taken"]
+pub struct Query<T> {
+    answer: Rc<RefCell<Answer>>,
+    convert: fn(Option<String>) -> T,
+}
+
+impl<T> Query<T> {
+    /// Take the value if the interpreter answered the query. Returns None before the batch with the read is flushed, and after the value was taken.
+    pub fn try_take(&mut self) -> Option<T> {
+        self.answer.borrow_mut().value.take().map(self.convert)
+    }
+}
+
+impl<T> Future for Query<T> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        match self.try_take() {
+            Some(value) => Poll::Ready(value),
+            None => {
+                self.answer.borrow_mut().waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for Query<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Query")
+            .field("answered", &self.answer.borrow().value.is_some())
+            .finish()
+    }
+}
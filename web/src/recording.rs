@@ -0,0 +1,171 @@
+//! Recording flushed batches and replaying them.
+//!
+//! Start a recording with [`MsgChannel::start_recording`] to capture every batch the channel applies with the time it was applied. The [`Recording`] can be saved with [`Recording::to_bytes`] as a session log and replayed step by step into a fresh root with a [`Replay`] to reproduce a bug deterministically.
+//!
+//! Replays only contain batches, so nodes that were assigned with [`MsgChannel::set_node`] must be assigned again on the channel the recording is replayed into. Idle batches are not recorded.
+//!
+//! Example:
+//! ```rust
+//! # #[cfg(not(target_arch = "wasm32"))]
+//! # {
+//! use sledgehammer::{recording::Replay, *};
+//!
+//! let mut channel = MsgChannel::default();
+//! let root = channel.dom_mut().create_element("div");
+//! channel.dom_mut().set_node(NodeId(0), root);
+//! channel.start_recording();
+//! channel.create_element("p", None);
+//! channel.append_child(MaybeId::Node(NodeId(0)), MaybeId::LastNode);
+//! channel.flush().unwrap();
+//! channel.set_text("hello", MaybeId::Node(NodeId(0)));
+//! channel.flush().unwrap();
+//! let recording = channel.stop_recording().unwrap();
+//!
+//! // replay the first flush into a fresh root
+//! let mut fresh = MsgChannel::default();
+//! let fresh_root = fresh.dom_mut().create_element("div");
+//! fresh.dom_mut().set_node(NodeId(0), fresh_root);
+//! let mut replay = Replay::new(&recording);
+//! replay.step(&mut fresh).unwrap().unwrap();
+//! assert_eq!(fresh.dom().inner_html(fresh_root), "<p></p>");
+//! # }
+//! ```
+
+use sledgehammer_encoder::{batch::FinalizedBatch, WIRE_FORMAT_VERSION};
+
+use crate::{DomError, MsgChannel};
+
+/// A batch that was applied while recording
+pub struct RecordedFlush {
+    /// The time the batch was applied in milliseconds. In the browser this is the time from `performance.now()`, on native targets it is the time since the first timestamp was taken.
+    pub time_ms: f64,
+    pub batch: FinalizedBatch,
+}
+
+/// Every batch a [`MsgChannel`] applied while it was recording
+#[derive(Default)]
+pub struct Recording {
+    flushes: Vec<RecordedFlush>,
+}
+
+impl Recording {
+    pub(crate) fn push(&mut self, time_ms: f64, msg: &[u8], str_buf: &[u8]) {
+        self.flushes.push(RecordedFlush {
+            time_ms,
+            batch: FinalizedBatch {
+                msg: msg.to_vec(),
+                str: str_buf.to_vec(),
+            },
+        });
+    }
+
+    /// The recorded batches in the order they were applied
+    pub fn flushes(&self) -> &[RecordedFlush] {
+        &self.flushes
+    }
+
+    /// The number of recorded batches
+    pub fn len(&self) -> usize {
+        self.flushes.len()
+    }
+
+    /// If no batches were recorded
+    pub fn is_empty(&self) -> bool {
+        self.flushes.is_empty()
+    }
+
+    /// Serialize the recording to bytes that can be saved as a session log. The bytes start with the [`WIRE_FORMAT_VERSION`] the batches were encoded with, and then every flush is written as the timestamp as a little endian f64 followed by the message and string buffer, each prefixed with their length as a little endian u32.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![WIRE_FORMAT_VERSION];
+        for flush in &self.flushes {
+            bytes.extend_from_slice(&flush.time_ms.to_le_bytes());
+            for buf in [&flush.batch.msg, &flush.batch.str] {
+                bytes.extend_from_slice(&(buf.len() as u32).to_le_bytes());
+                bytes.extend_from_slice(buf);
+            }
+        }
+        bytes
+    }
+
+    /// Read a recording that was serialized with [`Recording::to_bytes`]. Returns None if the bytes end in the middle of a flush or the recording was made with a different format version.
+    pub fn from_bytes(mut bytes: &[u8]) -> Option<Self> {
+        fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+            if bytes.len() < len {
+                return None;
+            }
+            let (taken, rest) = bytes.split_at(len);
+            *bytes = rest;
+            Some(taken)
+        }
+        fn take_buf(bytes: &mut &[u8]) -> Option<Vec<u8>> {
+            let len = u32::from_le_bytes(take(bytes, 4)?.try_into().unwrap());
+            take(bytes, len as usize).map(<[u8]>::to_vec)
+        }
+
+        if take(&mut bytes, 1)? != [WIRE_FORMAT_VERSION] {
+            return None;
+        }
+        let mut recording = Self::default();
+        while !bytes.is_empty() {
+            let time_ms = f64::from_le_bytes(take(&mut bytes, 8)?.try_into().unwrap());
+            let msg = take_buf(&mut bytes)?;
+            let str = take_buf(&mut bytes)?;
+            recording.flushes.push(RecordedFlush {
+                time_ms,
+                batch: FinalizedBatch { msg, str },
+            });
+        }
+        Some(recording)
+    }
+}
+
+/// Replays a [`Recording`] one batch at a time
+///
+/// Replays only move forward. To go back in time, create a fresh root and replay up to an earlier step with [`Replay::step_to`].
+pub struct Replay<'a> {
+    recording: &'a Recording,
+    position: usize,
+}
+
+impl<'a> Replay<'a> {
+    /// Start replaying a recording from the first batch
+    pub fn new(recording: &'a Recording) -> Self {
+        Self {
+            recording,
+            position: 0,
+        }
+    }
+
+    /// The number of batches that have been replayed
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// If every batch has been replayed
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.recording.len()
+    }
+
+    /// The batch that the next step applies
+    pub fn peek(&self) -> Option<&'a RecordedFlush> {
+        self.recording.flushes.get(self.position)
+    }
+
+    /// Apply the next batch to the channel. Returns None if every batch has been replayed.
+    pub fn step(&mut self, channel: &mut MsgChannel) -> Option<Result<(), DomError>> {
+        let flush = self.peek()?;
+        self.position += 1;
+        Some(channel.run_batch(&flush.batch))
+    }
+
+    /// Apply batches until `position` batches have been replayed or a batch fails. The replay stops at the end of the recording, and does nothing if `position` batches have already been replayed.
+    pub fn step_to(&mut self, position: usize, channel: &mut MsgChannel) -> Result<(), DomError> {
+        while self.position < position {
+            match self.step(channel) {
+                Some(result) => result?,
+                None => break,
+            }
+        }
+        Ok(())
+    }
+}
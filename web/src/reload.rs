@@ -0,0 +1,42 @@
+//! Restarting the interpreter without reloading the page.
+//!
+//! [`MsgChannel::restart_interpreter`] tears down the interpreter and creates a new one that takes over the page. Hot reloading can swap in a new interpreter after the code changed, and long-lived pages can recover from an interpreter that was left in a broken state by an operation that threw, without a full page refresh that loses the state of the page.
+//!
+//! The nodes on the page are not touched. The new interpreter starts with an empty stack of parents, no last node and no cached values, and keeps the nodes chosen with [`KeepNodes`] from the node table of the old interpreter. Everything else the old interpreter did is torn down:
+//! - listeners added to nodes stay on the nodes but no longer dispatch events, so add them again
+//! - media query, animation frame, canvas size, fullscreen, pointer lock and custom element subscriptions are removed
+//! - queries the old interpreter did not answer yet, like [`MsgChannel::decode_image`], are never answered
+//! - an idle batch that ran out of time is discarded, because the new interpreter cannot resume it. Idle batches that did not start yet are applied with the new interpreter
+//!
+//! Operations queued on the channel are kept and applied by the new interpreter with the next flush.
+//!
+//! Example:
+//! ```rust
+//! # use sledgehammer::{reload::KeepNodes, *};
+//! let mut channel = MsgChannel::default();
+//! channel.create_element("main", Some(NodeId(0)));
+//! channel.create_element("p", Some(NodeId(1)));
+//! channel.keys_mut().insert("app", NodeId(0));
+//! channel.flush()?;
+//! // the new interpreter only knows the node registered with a key, so the app is rendered into it again
+//! channel.restart_interpreter(KeepNodes::Keyed);
+//! channel.set_text("reloaded", channel.keys().node("app"));
+//! channel.flush()?;
+//! # Ok::<(), DomError>(())
+//! ```
+//!
+//! [`MsgChannel::restart_interpreter`]: crate::MsgChannel::restart_interpreter
+//! [`MsgChannel::decode_image`]: crate::MsgChannel::decode_image
+
+use sledgehammer_encoder::NodeId;
+
+/// The nodes the new interpreter keeps from the node table of the old interpreter when it is restarted with [`crate::MsgChannel::restart_interpreter`]. See [`crate::reload`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeepNodes<'a> {
+    /// Keep every node stored with an id
+    All,
+    /// Keep only the nodes registered with keys in [`crate::MsgChannel::keys`], the roots the app is rendered into
+    Keyed,
+    /// Keep only the nodes stored with these ids
+    Only(&'a [NodeId]),
+}
@@ -0,0 +1,342 @@
+//! Flushing a [`MsgChannel`] once per animation frame.
+//!
+//! Flushing many times in one frame can force the browser to recalculate layout between flushes. A [`FrameScheduler`] coalesces all of the operations queued during a tick and applies them in a single `requestAnimationFrame` callback.
+//!
+//! Operations that respond to input can be queued with [`FrameScheduler::queue`] and are applied first. Operations that can wait, like updating a list below the fold, can be queued with [`FrameScheduler::queue_deferred`] and are applied one frame later, after the urgent operations from that frame.
+//!
+//! When frames are delayed, for example while the tab is in the background, the operations queued in the meantime keep being merged into the batch for the next frame, and idle batches past [`MsgChannel::set_idle_backlog_limit`] are merged together, so a backlog never grows into a queue of separate flushes.
+//!
+//! Code that needs to read layout or focus after its operations are applied can await [`FrameScheduler::applied`], which resolves after the flush that applies them.
+//!
+//! Operations that are not needed until the browser is idle can be queued with [`FrameScheduler::queue_idle`] instead. They are applied in `requestIdleCallback` callbacks and yield back to the browser when the idle deadline runs out, even in the middle of a batch.
+
+use std::{
+    cell::RefCell,
+    future::Future,
+    mem,
+    pin::Pin,
+    rc::{Rc, Weak},
+    task::{Context, Poll, Waker},
+};
+
+use sledgehammer_encoder::batch::Batch;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+
+use crate::{DomError, MsgChannel};
+
+/// The time in milliseconds idle batches run for in browsers without `requestIdleCallback`
+#[cfg(target_arch = "wasm32")]
+const FALLBACK_IDLE_BUDGET_MS: f64 = 5.0;
+
+struct SchedulerState {
+    channel: MsgChannel,
+    /// If a frame has been requested and not run yet
+    frame_requested: bool,
+    /// Deferred operations queued since the last frame
+    deferred: Batch,
+    /// Deferred operations that are applied in the next frame after the urgent operations
+    deferred_ready: Batch,
+    /// If an idle callback has been requested and there are idle batches left
+    idle_requested: bool,
+    /// The error from the last flush that ran in an animation frame or idle callback
+    error: Option<DomError>,
+    /// The number of flushes the scheduler has applied
+    flushes: u64,
+    /// The largest flush count a pending [`Applied`] future is waiting for
+    max_target: u64,
+    /// The tasks waiting for [`Applied`] futures
+    wakers: Vec<Waker>,
+}
+
+impl SchedulerState {
+    /// Record that a flush was applied and return the tasks that should be woken
+    fn flushed(&mut self, all_applied: bool) -> Vec<Waker> {
+        self.flushes += 1;
+        // everything that was queued has been applied, so every future can resolve
+        if all_applied {
+            self.flushes = self.flushes.max(self.max_target);
+        }
+        mem::take(&mut self.wakers)
+    }
+}
+
+/// Schedules flushes of a [`MsgChannel`] for the next animation frame
+///
+/// On native targets there are no animation frames, so queued operations are only applied when [`FrameScheduler::flush_now`] is called.
+///
+/// Example:
+/// ```no_run
+/// # use sledgehammer::{scheduler::FrameScheduler, *};
+/// let scheduler = FrameScheduler::new(MsgChannel::default());
+/// // both of these updates are applied in the same frame
+/// scheduler.queue(|c| c.create_element("div", None));
+/// scheduler.queue(|c| c.set_text("hello", MaybeId::LastNode));
+/// ```
+#[derive(Clone)]
+pub struct FrameScheduler {
+    state: Rc<RefCell<SchedulerState>>,
+}
+
+impl FrameScheduler {
+    /// Create a scheduler that flushes the channel
+    pub fn new(channel: MsgChannel) -> Self {
+        Self {
+            state: Rc::new(RefCell::new(SchedulerState {
+                channel,
+                frame_requested: false,
+                deferred: Batch::default(),
+                deferred_ready: Batch::default(),
+                idle_requested: false,
+                error: None,
+                flushes: 0,
+                max_target: 0,
+                wakers: Vec::new(),
+            })),
+        }
+    }
+
+    /// Queue operations on the channel and flush them in the next animation frame. Operations queued before the frame runs are applied in the same flush.
+    pub fn queue<R>(&self, f: impl FnOnce(&mut MsgChannel) -> R) -> R {
+        let result = f(&mut self.state.borrow_mut().channel);
+        self.request_frame();
+        result
+    }
+
+    /// Queue operations that do not respond directly to input in a separate lane. They are applied one frame later than operations queued with [`FrameScheduler::queue`], after the urgent operations queued for that frame, so urgent updates never wait behind them.
+    ///
+    /// Deferred operations are appended to the urgent operations when they are applied, so they should not rely on [`MaybeId::LastNode`](crate::MaybeId::LastNode) being set by operations outside of the closure.
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::{scheduler::FrameScheduler, *};
+    /// let scheduler = FrameScheduler::new(MsgChannel::default());
+    /// // applied in the next frame
+    /// scheduler.queue(|c| c.set_text("typed text", MaybeId::Node(NodeId(0))));
+    /// // applied in the frame after that
+    /// scheduler.queue_deferred(|b| b.set_text("3 results", MaybeId::Node(NodeId(1))));
+    /// ```
+    pub fn queue_deferred<R>(&self, f: impl FnOnce(&mut Batch) -> R) -> R {
+        let result = f(&mut self.state.borrow_mut().deferred);
+        self.request_frame();
+        result
+    }
+
+    /// Queue low priority operations, like prefetching offscreen content, in a separate batch. The batch is applied during idle time a few operations at a time, and yields back to the browser when the idle deadline runs out. Browsers without `requestIdleCallback` fall back to `setTimeout` with a short budget.
+    ///
+    /// Idle batches keep their own last node. See [`MsgChannel::append_idle`]
+    ///
+    /// On native targets idle batches are only applied when [`MsgChannel::run_idle`] is called.
+    pub fn queue_idle<R>(&self, f: impl FnOnce(&mut Batch) -> R) -> R {
+        let mut batch = Batch::default();
+        let result = f(&mut batch);
+        self.state.borrow_mut().channel.append_idle(batch);
+        self.request_idle();
+        result
+    }
+
+    /// Apply the queued operations immediately instead of waiting for the next animation frame. Urgent operations are applied before deferred operations.
+    pub fn flush_now(&self) -> Result<(), DomError> {
+        let (result, wakers) = {
+            let mut state = self.state.borrow_mut();
+            // in the browser the requested frame still runs, but it has nothing to apply
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                state.frame_requested = false;
+            }
+            let state = &mut *state;
+            state.channel.append(mem::take(&mut state.deferred_ready));
+            state.channel.append(mem::take(&mut state.deferred));
+            (state.channel.flush(), state.flushed(true))
+        };
+        // wake the waiting tasks after the state is released in case they poll immediately
+        wakers.into_iter().for_each(Waker::wake);
+        result
+    }
+
+    /// A future that resolves once every operation queued with [`FrameScheduler::queue`] or [`FrameScheduler::queue_deferred`] before it was created has been applied, either in an animation frame or by [`FrameScheduler::flush_now`]. Idle operations are not waited for.
+    ///
+    /// The future resolves even if the flush failed, so check [`FrameScheduler::take_error`] afterwards. It also resolves if the scheduler is dropped before the operations are applied. On native targets, the future only resolves after [`FrameScheduler::flush_now`] is called.
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use sledgehammer::{scheduler::FrameScheduler, *};
+    /// # async fn focus(scheduler: FrameScheduler) {
+    /// scheduler.queue(|c| {
+    ///     c.create_element("input", Some(NodeId(1)));
+    ///     c.append_child(MaybeId::Node(NodeId(0)), MaybeId::LastNode);
+    /// });
+    /// scheduler.applied().await;
+    /// // the input is in the document now, so it can be focused
+    /// # }
+    /// ```
+    pub fn applied(&self) -> Applied {
+        let mut state = self.state.borrow_mut();
+        let pending = if state.deferred.op_count() > 0 {
+            // deferred operations wait one more frame
+            2
+        } else if state.frame_requested {
+            1
+        } else {
+            0
+        };
+        let target = state.flushes + pending;
+        state.max_target = state.max_target.max(target);
+        Applied {
+            state: Rc::downgrade(&self.state),
+            target,
+        }
+    }
+
+    /// Run a closure with the channel without scheduling a flush. This is useful for operations that run immediately like [`MsgChannel::run_batch`].
+    pub fn with_channel<R>(&self, f: impl FnOnce(&mut MsgChannel) -> R) -> R {
+        f(&mut self.state.borrow_mut().channel)
+    }
+
+    /// If there are operations waiting for the next animation frame
+    pub fn is_frame_requested(&self) -> bool {
+        self.state.borrow().frame_requested
+    }
+
+    /// If there are deferred operations that have not been applied yet
+    pub fn has_deferred(&self) -> bool {
+        let state = self.state.borrow();
+        state.deferred.op_count() > 0 || state.deferred_ready.op_count() > 0
+    }
+
+    /// If there are idle batches waiting for an idle callback
+    pub fn is_idle_requested(&self) -> bool {
+        self.state.borrow().idle_requested
+    }
+
+    /// Take the error from the last flush that ran in an animation frame or idle callback
+    pub fn take_error(&self) -> Option<DomError> {
+        self.state.borrow_mut().error.take()
+    }
+
+    /// The number of flushes the scheduler has applied in animation frames or with [`FrameScheduler::flush_now`]
+    pub fn flush_count(&self) -> u64 {
+        self.state.borrow().flushes
+    }
+
+    fn request_frame(&self) {
+        let mut state = self.state.borrow_mut();
+        if state.frame_requested {
+            return;
+        }
+        state.frame_requested = true;
+        #[cfg(target_arch = "wasm32")]
+        request_animation_frame(Rc::downgrade(&self.state));
+    }
+
+    fn request_idle(&self) {
+        let mut state = self.state.borrow_mut();
+        if state.idle_requested {
+            return;
+        }
+        state.idle_requested = true;
+        #[cfg(target_arch = "wasm32")]
+        request_idle_callback(Rc::downgrade(&self.state));
+    }
+}
+
+/// Flush the urgent operations and the deferred operations from the last frame in the next animation frame
+#[cfg(target_arch = "wasm32")]
+fn request_animation_frame(weak: Weak<RefCell<SchedulerState>>) {
+    let callback = Closure::once_into_js(move || {
+        // the scheduler may have been dropped before the frame
+        let state = match weak.upgrade() {
+            Some(state) => state,
+            None => return,
+        };
+        let wakers = {
+            let mut state = state.borrow_mut();
+            let state = &mut *state;
+            state.frame_requested = false;
+            state.channel.append(mem::take(&mut state.deferred_ready));
+            if let Err(err) = state.channel.flush() {
+                state.error = Some(err);
+            }
+            // operations deferred during the last tick wait for the next frame
+            state.deferred_ready = mem::take(&mut state.deferred);
+            let all_applied = state.deferred_ready.op_count() == 0;
+            if !all_applied {
+                state.frame_requested = true;
+                request_animation_frame(weak);
+            }
+            state.flushed(all_applied)
+        };
+        wakers.into_iter().for_each(Waker::wake);
+    });
+    web_sys::window()
+        .unwrap()
+        .request_animation_frame(callback.unchecked_ref())
+        .unwrap();
+}
+
+/// Run the idle batches in the next idle callback until they are all applied
+#[cfg(target_arch = "wasm32")]
+fn request_idle_callback(weak: Weak<RefCell<SchedulerState>>) {
+    let callback = Closure::once_into_js(move |deadline: JsValue| {
+        // the scheduler may have been dropped before the callback
+        let state = match weak.upgrade() {
+            Some(state) => state,
+            None => return,
+        };
+        let budget = deadline
+            .dyn_ref::<web_sys::IdleDeadline>()
+            .map(|deadline| deadline.time_remaining())
+            .unwrap_or(FALLBACK_IDLE_BUDGET_MS);
+        let mut state = state.borrow_mut();
+        let finished = match state.channel.run_idle(budget) {
+            Ok(finished) => finished,
+            Err(err) => {
+                state.error = Some(err);
+                !state.channel.has_idle_work()
+            }
+        };
+        if finished {
+            state.idle_requested = false;
+        } else {
+            drop(state);
+            request_idle_callback(weak);
+        }
+    });
+    let window = web_sys::window().unwrap();
+    if js_sys::Reflect::has(&window, &"requestIdleCallback".into()).unwrap_or(false) {
+        window
+            .request_idle_callback(callback.unchecked_ref())
+            .unwrap();
+    } else {
+        window
+            .set_timeout_with_callback(callback.unchecked_ref())
+            .unwrap();
+    }
+}
+
+/// A future that resolves once the operations queued before it was created have been applied. See [`FrameScheduler::applied`]
+#[must_use = "futures do nothing unless they are awaited"]
+pub struct Applied {
+    state: Weak<RefCell<SchedulerState>>,
+    target: u64,
+}
+
+impl Future for Applied {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // the operations will never be applied if the scheduler was dropped
+        let state = match self.state.upgrade() {
+            Some(state) => state,
+            None => return Poll::Ready(()),
+        };
+        let mut state = state.borrow_mut();
+        if state.flushes >= self.target {
+            return Poll::Ready(());
+        }
+        if !state.wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            state.wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
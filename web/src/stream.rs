@@ -0,0 +1,140 @@
+//! Streaming a large initial render into the document.
+//!
+//! Building a large page into one batch shows nothing until the whole batch is encoded and applied. A [`TreeStream`] appends every node to its parent as soon as it is encoded and flushes the channel every few operations, so the content at the top of the page is shown while the rest is still being built.
+//!
+//! Elements are opened with [`TreeStream::open`], filled with [`TreeStream::append`] and more opened elements, and closed with [`TreeStream::close`]. An opened element is appended to its parent before its children, and the interpreter keeps the open elements on its stack of parents between flushes, so every flush continues the tree where the last one stopped.
+//!
+//! Example:
+//! ```rust
+//! # use sledgehammer::*;
+//! let mut channel = MsgChannel::default();
+//! # #[cfg(not(target_arch = "wasm32"))]
+//! # { let root = channel.dom_mut().create_element("div"); channel.dom_mut().set_node(NodeId(0), root); }
+//! let mut stream = channel.stream_into(NodeId(0)).chunk_size(64);
+//! stream.open(ElementBuilder::new("main".into()))?;
+//! stream.append(ElementBuilder::new("h1".into()).children(&[TextBuilder::new("Results").into()]))?;
+//! stream.open(ElementBuilder::new("ul".into()))?;
+//! for i in 0..1000 {
+//!     // the list grows on the page every 64 operations
+//!     stream.append(ElementBuilder::new("li".into()).children(&[TextBuilder::new(&i.to_string()).into()]))?;
+//! }
+//! // close the open elements and apply the rest of the tree
+//! stream.finish()?;
+//! # Ok::<(), DomError>(())
+//! ```
+
+use sledgehammer_encoder::{ElementBuilder, NodeBuilder, NodeId};
+
+use crate::{DomError, MsgChannel};
+
+/// The number of operations a [`TreeStream`] queues before it flushes the channel if [`TreeStream::chunk_size`] is not set
+pub const DEFAULT_CHUNK_SIZE: usize = 256;
+
+/// Appends a tree to a parent in the document while it is built, flushing the channel every few operations. See [`crate::stream`]
+///
+/// Dropping the stream closes the elements that are still open without flushing the channel.
+pub struct TreeStream<'a> {
+    channel: &'a mut MsgChannel,
+    /// The number of elements opened with the stream that are not closed yet
+    open: usize,
+    /// If the parent the stream was started with is still on the stack of parents
+    started: bool,
+    chunk_size: usize,
+}
+
+impl MsgChannel {
+    /// Start streaming a tree into the node stored with an id. The node becomes the topmost parent until the stream is finished. See [`crate::stream`]
+    pub fn stream_into(&mut self, parent: NodeId) -> TreeStream<'_> {
+        self.set_last_node(parent);
+        self.push_parent();
+        TreeStream {
+            channel: self,
+            open: 0,
+            started: true,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+}
+
+impl<'a> TreeStream<'a> {
+    /// Flush the channel once this many operations are queued instead of [`DEFAULT_CHUNK_SIZE`]. Smaller chunks show content sooner, larger chunks take less time overall.
+    pub fn chunk_size(mut self, operations: usize) -> Self {
+        self.chunk_size = operations.max(1);
+        self
+    }
+
+    /// Append an element to the current parent and make it the parent of the nodes that follow until it is closed. The element is the last node afterwards, so operations on the last node queued with [`TreeStream::channel`] change it.
+    pub fn open(&mut self, element: ElementBuilder) -> Result<(), DomError> {
+        self.channel.build_full_element(element);
+        self.channel.open_parent();
+        self.open += 1;
+        self.flush_full_chunk()
+    }
+
+    /// Append a node with all of its children to the current parent. The node is the last node afterwards.
+    pub fn append<'b>(&mut self, node: impl Into<NodeBuilder<'b>>) -> Result<(), DomError> {
+        match node.into() {
+            NodeBuilder::Element(element) => self.channel.build_full_element(element),
+            NodeBuilder::Text(text) => self.channel.build_text_node(text),
+        }
+        self.channel.append_to_parent();
+        self.flush_full_chunk()
+    }
+
+    /// Close the element that was opened last. The closed element is the last node afterwards.
+    ///
+    /// # Panics
+    /// Panics if every element opened with the stream is already closed.
+    pub fn close(&mut self) -> Result<(), DomError> {
+        assert!(self.open > 0, "there is no open element to close");
+        self.channel.close_parent();
+        self.open -= 1;
+        self.flush_full_chunk()
+    }
+
+    /// The number of elements opened with the stream that are not closed yet
+    pub fn depth(&self) -> usize {
+        self.open
+    }
+
+    /// The channel the tree is streamed with, to queue other operations like listeners on the last node. Operations that change the stack of parents break the stream.
+    pub fn channel(&mut self) -> &mut MsgChannel {
+        self.channel
+    }
+
+    /// Apply the operations queued so far. The open elements stay open.
+    pub fn flush(&mut self) -> Result<(), DomError> {
+        self.channel.flush()
+    }
+
+    /// Close every open element, pop the parent the stream was started with and apply the rest of the tree
+    pub fn finish(mut self) -> Result<(), DomError> {
+        self.close_all();
+        self.channel.flush()
+    }
+
+    fn flush_full_chunk(&mut self) -> Result<(), DomError> {
+        if self.channel.op_count() >= self.chunk_size {
+            self.channel.flush()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn close_all(&mut self) {
+        for _ in 0..self.open {
+            self.channel.close_parent();
+        }
+        self.open = 0;
+        if self.started {
+            self.channel.close_parent();
+            self.started = false;
+        }
+    }
+}
+
+impl Drop for TreeStream<'_> {
+    fn drop(&mut self) {
+        self.close_all();
+    }
+}
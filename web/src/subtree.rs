@@ -0,0 +1,173 @@
+//! Rendering components into separate batches.
+//!
+//! A [`SubtreeChannel`] is a batch for the part of the page one component owns, bound to the id of the node the component renders into. Components encode their operations without access to the [`MsgChannel`], so a subtree can be built anywhere a [`Batch`] can, including on another thread, and submitted with [`MsgChannel::submit_subtree`] once it is done.
+//!
+//! The channel stitches the submitted subtrees into its batch when it is flushed, after the operations queued on the channel itself. Every subtree starts with its root as the last node. A subtree that renders into a node another component creates is marked with [`SubtreeChannel::after`], and is applied after the subtrees rendered into that component no matter in which order they were submitted. The size of every stitched subtree is kept in [`MsgChannel::last_subtree_stats`] to find the components that send the most operations.
+//!
+//! Example:
+//! ```rust
+//! # use sledgehammer::{subtree::SubtreeChannel, *};
+//! let mut channel = MsgChannel::default();
+//! # #[cfg(not(target_arch = "wasm32"))]
+//! # { let root = channel.dom_mut().create_element("div"); channel.dom_mut().set_node(NodeId(0), root); }
+//! // the sidebar renders into an element the layout creates, so it waits for the layout
+//! let mut sidebar = SubtreeChannel::new(NodeId(2)).after(NodeId(0));
+//! sidebar.batch().set_text("Settings", MaybeId::LastNode);
+//!
+//! let mut layout = SubtreeChannel::new(NodeId(0));
+//! layout.batch().create_element("nav", Some(NodeId(2)));
+//! layout.batch().append_child(MaybeId::Node(NodeId(0)), MaybeId::LastNode);
+//!
+//! channel.submit_subtree(sidebar);
+//! channel.submit_subtree(layout);
+//! channel.flush()?;
+//! let applied: Vec<_> = channel.last_subtree_stats().iter().map(|stats| stats.root).collect();
+//! assert_eq!(applied, [NodeId(0), NodeId(2)]);
+//! # Ok::<(), DomError>(())
+//! ```
+//!
+//! [`MsgChannel`]: crate::MsgChannel
+//! [`MsgChannel::submit_subtree`]: crate::MsgChannel::submit_subtree
+//! [`MsgChannel::last_subtree_stats`]: crate::MsgChannel::last_subtree_stats
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+use sledgehammer_encoder::{batch::Batch, NodeId};
+
+/// The operations of one component, bound to the node it renders into. See [`crate::subtree`]
+pub struct SubtreeChannel {
+    root: NodeId,
+    /// The root of the subtrees that are applied first
+    after: Option<NodeId>,
+    batch: Batch,
+}
+
+impl SubtreeChannel {
+    /// Start an empty subtree for the component that renders into the node stored with `root`. The root is the last node when the operations of the subtree are applied.
+    pub fn new(root: NodeId) -> Self {
+        Self {
+            root,
+            after: None,
+            batch: Batch::default(),
+        }
+    }
+
+    /// Apply the subtree after the subtrees with the root `parent` that are stitched into the same flush, for components that render into a node their parent component creates
+    pub fn after(mut self, parent: NodeId) -> Self {
+        self.after = Some(parent);
+        self
+    }
+
+    /// The node the subtree renders into
+    pub fn root(&self) -> NodeId {
+        self.root
+    }
+
+    /// The batch the operations of the subtree are queued in
+    pub fn batch(&mut self) -> &mut Batch {
+        &mut self.batch
+    }
+
+    /// The number of operations queued in the subtree
+    pub fn op_count(&self) -> usize {
+        self.batch.op_count()
+    }
+
+    /// The size of the subtree
+    pub fn stats(&self) -> SubtreeStats {
+        SubtreeStats {
+            root: self.root,
+            ops: self.batch.op_count(),
+            msg_bytes: self.batch.msg_bytes(),
+            str_bytes: self.batch.str_bytes(),
+        }
+    }
+}
+
+impl std::fmt::Debug for SubtreeChannel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SubtreeChannel")
+            .field("root", &self.root)
+            .field("after", &self.after)
+            .field("ops", &self.op_count())
+            .finish()
+    }
+}
+
+/// The size of a subtree that was stitched into a flush. The sizes are measured before the channel optimizes the batch, and do not include the operation that sets the root as the last node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubtreeStats {
+    /// The node the subtree renders into
+    pub root: NodeId,
+    /// The number of operations in the subtree
+    pub ops: usize,
+    /// The number of bytes of operations in the subtree
+    pub msg_bytes: usize,
+    /// The number of bytes of strings in the subtree
+    pub str_bytes: usize,
+}
+
+/// The subtrees submitted to a channel that are stitched into the next flush
+#[derive(Default)]
+pub(crate) struct Subtrees {
+    pending: Vec<SubtreeChannel>,
+    /// The subtrees stitched into the last flush in the order they were applied
+    stats: Vec<SubtreeStats>,
+}
+
+impl Subtrees {
+    pub(crate) fn submit(&mut self, subtree: SubtreeChannel) {
+        self.pending.push(subtree);
+    }
+
+    pub(crate) fn last_stats(&self) -> &[SubtreeStats] {
+        &self.stats
+    }
+
+    /// Append the pending subtrees to a batch. Subtrees are applied in the order they were submitted, except that a subtree waits until every subtree with the root it is marked to be applied after is done. Subtrees that wait for each other are applied last, in the order they were submitted.
+    pub(crate) fn stitch_into(&mut self, batch: &mut Batch) {
+        self.stats.clear();
+        if self.pending.is_empty() {
+            return;
+        }
+        let mut pending: Vec<Option<SubtreeChannel>> = self.pending.drain(..).map(Some).collect();
+        // the number of pending subtrees with every root
+        let mut remaining: HashMap<NodeId, usize> = HashMap::new();
+        for subtree in pending.iter().flatten() {
+            *remaining.entry(subtree.root).or_default() += 1;
+        }
+        // the subtrees that wait for a root, and the subtrees that can be applied ordered by when they were submitted
+        let mut waiting: HashMap<NodeId, Vec<usize>> = HashMap::new();
+        let mut ready = BinaryHeap::new();
+        for (index, subtree) in pending.iter().flatten().enumerate() {
+            match subtree.after {
+                Some(parent) if parent != subtree.root && remaining.contains_key(&parent) => {
+                    waiting.entry(parent).or_default().push(index);
+                }
+                _ => ready.push(Reverse(index)),
+            }
+        }
+        while let Some(Reverse(index)) = ready.pop() {
+            let subtree = pending[index].take().unwrap();
+            let root = subtree.root;
+            self.stitch(subtree, batch);
+            let left = remaining.get_mut(&root).unwrap();
+            *left -= 1;
+            if *left == 0 {
+                ready.extend(waiting.remove(&root).into_iter().flatten().map(Reverse));
+            }
+        }
+        for subtree in pending.into_iter().flatten() {
+            self.stitch(subtree, batch);
+        }
+    }
+
+    fn stitch(&mut self, subtree: SubtreeChannel, batch: &mut Batch) {
+        self.stats.push(subtree.stats());
+        batch.set_last_node(subtree.root);
+        batch.append(subtree.batch);
+    }
+}
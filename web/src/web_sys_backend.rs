@@ -0,0 +1,1603 @@
+//! A backend that applies batches with web-sys calls instead of the javascript interpreter. It is slower than the interpreter, but it does not require bundling any javascript.
+//!
+//! Because it decodes batches with the rust [`Decoder`], running the browser test suite with the `web-sys-backend` feature checks the interpreter against a second implementation of the same operations.
+
+use sledgehammer_encoder::{
+    attribute::AnyAttribute,
+    batch::Batch,
+    decoder::{DecodedNode, DecodedOp, Decoder, DecoderState},
+    element::AnyElement,
+    HandlerId, MaybeId, NodeId, QueryId, StorageArea,
+};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::{Rc, Weak},
+};
+
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use web_sys::{
+    AddEventListenerOptions, CompositionEvent, Document, Element, HtmlCanvasElement,
+    HtmlDialogElement, HtmlElement, HtmlIFrameElement, HtmlTemplateElement, KeyboardEvent,
+    MediaQueryList, MediaQueryListEvent, MediaQueryListEventInit, MouseEvent, Node, PointerEvent,
+    ResizeObserver, ResizeObserverBoxOptions, ResizeObserverEntry, ResizeObserverOptions,
+    ResizeObserverSize, ToggleEvent, TouchEvent, TouchList,
+};
+
+use crate::{
+    channel::{DomError, FlushStats},
+    events::{
+        CanvasSizeData, CompositionData, DialogCloseData, Event, EventData, FrameData, Handlers,
+        InputData, KeyboardData, LockData, MediaQueryData, Modifiers, MouseData, PointerData,
+        PointerType, ToggleData, TouchData, TouchPoint, ViewTransitionData,
+    },
+    inverse::{run_with_inverse, InverseDom, Snapshot},
+    queries::Queries,
+};
+
+/// The change and error events of fullscreen and pointer lock on the document
+const LOCK_EVENTS: [[&str; 2]; 2] = [
+    ["fullscreenchange", "fullscreenerror"],
+    ["pointerlockchange", "pointerlockerror"],
+];
+
+/// The namespace of html elements created with `createElement`
+const HTML_NAMESPACE: &str = "http://www.w3.org/1999/xhtml";
+
+/// Applies decoded operations to the dom with web-sys
+pub(crate) struct WebSysInterpreter {
+    document: Document,
+    /// The nodes stored with an id and the generation they were stored with
+    nodes: Vec<Option<(u8, Node)>>,
+    last_node: Option<Node>,
+    /// The parents pushed with [`Batch::push_parent`]
+    parents: Vec<Node>,
+    /// The values cached with an id for cached attributes
+    cached_values: Vec<String>,
+    /// The format function of the `Intl` formatter created for each kind, locale and options of formatted text
+    formatters: HashMap<(bool, String, String), js_sys::Function>,
+    /// Where the idle batch that ran out of time stopped and its last node
+    idle: Option<(DecoderState, Option<Node>)>,
+    /// The handlers listeners dispatch events to
+    handlers: Handlers,
+    /// If listeners dispatch events. It is cleared when the interpreter is restarted, because the nodes keep the listeners of the old interpreter
+    active: Rc<Cell<bool>>,
+    /// The function added as the listener for each handler. Adding the same function twice does nothing, so it is created once per handler
+    listeners: Vec<Option<Listener>>,
+    /// The function added as the coalesced listener for each handler
+    coalesced_listeners: Vec<Option<Listener>>,
+    coalesced: Rc<Coalesced>,
+    composition: Rc<Composition>,
+    /// The queries reads answer
+    queries: Queries,
+    /// The media query lists handlers are subscribed to with the query and the handler
+    media_queries: Rc<RefCell<Vec<(String, HandlerId, MediaQueryList)>>>,
+    animation_frames: Rc<AnimationFrames>,
+    /// The observer that resizes the canvases observed with [`Batch::observe_canvas_size`], created when the first canvas is observed
+    canvases: Option<CanvasObserver>,
+    /// The handlers the change and error events of fullscreen and pointer lock are dispatched to, in the order of [`LOCK_EVENTS`]
+    lock_handlers: [Option<HandlerId>; 2],
+    /// The listeners on the document that track compositions
+    composition_listeners: [Listener; 2],
+}
+
+/// The form control the user is composing text in with an input method, and the values that are set when the composition ends. Setting the value of a control cancels the composition, so it waits until the composition ends
+#[derive(Default)]
+struct Composition {
+    target: RefCell<Option<JsValue>>,
+    pending: RefCell<Vec<(JsValue, String)>>,
+}
+
+impl Composition {
+    fn set_value(&self, document: &Document, node: &JsValue, value: &str) -> Result<(), JsValue> {
+        if self.target.borrow().as_ref() == Some(node) {
+            let mut pending = self.pending.borrow_mut();
+            pending.retain(|(pending, _)| pending != node);
+            pending.push((node.clone(), value.to_string()));
+            return Ok(());
+        }
+        write_value(document, node, value)
+    }
+
+    fn end(&self, document: &Document) {
+        self.target.borrow_mut().take();
+        for (node, value) in self.pending.take() {
+            // there is no batch to report the error to
+            let _ = write_value(document, &node, &value);
+        }
+    }
+}
+
+/// A rust closure added as an event listener
+type Listener = Closure<dyn FnMut(web_sys::Event)>;
+
+/// The latest event of a coalesced listener for a node and event type
+struct PendingEvent {
+    handler: HandlerId,
+    /// The listener that dispatches the event to the handler
+    listener: js_sys::Function,
+    /// The node the listener was added to
+    node: Option<web_sys::EventTarget>,
+    event: web_sys::Event,
+}
+
+/// The events of coalesced listeners and the animation frame callback that dispatches them, like the javascript interpreter
+struct Coalesced {
+    events: RefCell<Vec<PendingEvent>>,
+    frame: Closure<dyn FnMut()>,
+}
+
+impl Coalesced {
+    fn new() -> Rc<Self> {
+        Rc::new_cyclic(|this: &Weak<Self>| {
+            let this = this.clone();
+            Self {
+                events: RefCell::default(),
+                frame: Closure::new(move || {
+                    if let Some(this) = this.upgrade() {
+                        // take the events first, so events dispatched by the handlers wait for the next frame
+                        for pending in this.events.take() {
+                            let _ = pending.listener.call1(&JsValue::NULL, &pending.event);
+                        }
+                    }
+                }),
+            }
+        })
+    }
+
+    fn push(&self, handler: HandlerId, listener: &js_sys::Function, event: web_sys::Event) {
+        let node = event.current_target();
+        let mut events = self.events.borrow_mut();
+        let pending = events.iter_mut().find(|pending| {
+            pending.handler == handler
+                && pending.node == node
+                && pending.event.type_() == event.type_()
+        });
+        match pending {
+            Some(pending) => pending.event = event,
+            None => {
+                if events.is_empty() {
+                    let _ = web_sys::window()
+                        .unwrap()
+                        .request_animation_frame(self.frame.as_ref().unchecked_ref());
+                }
+                events.push(PendingEvent {
+                    handler,
+                    listener: listener.clone(),
+                    node,
+                    event,
+                });
+            }
+        }
+    }
+
+    /// Drop the pending event of a listener that is removed
+    fn remove(&self, handler: HandlerId, node: &web_sys::EventTarget, name: &str) {
+        self.events.borrow_mut().retain(|pending| {
+            !(pending.handler == handler
+                && pending.node.as_ref() == Some(node)
+                && pending.event.type_() == name)
+        });
+    }
+}
+
+/// The handlers subscribed to animation frames and the callback that dispatches the frames to them, like the javascript interpreter
+struct AnimationFrames {
+    handlers: Handlers,
+    subscribed: RefCell<Vec<HandlerId>>,
+    /// The id of the requested frame
+    request: Cell<Option<i32>>,
+    callback: Closure<dyn FnMut(f64)>,
+}
+
+impl AnimationFrames {
+    fn new(handlers: Handlers) -> Rc<Self> {
+        Rc::new_cyclic(|this: &Weak<Self>| {
+            let this = this.clone();
+            Self {
+                handlers,
+                subscribed: RefCell::default(),
+                request: Cell::new(None),
+                callback: Closure::new(move |timestamp: f64| {
+                    if let Some(this) = this.upgrade() {
+                        // request the next frame first, so handlers that unsubscribe cancel it
+                        this.request();
+                        let subscribed = this.subscribed.borrow().clone();
+                        for handler in subscribed {
+                            if this.subscribed.borrow().contains(&handler) {
+                                let event = Event::new(
+                                    "animationframe",
+                                    EventData::AnimationFrame(FrameData { timestamp }),
+                                );
+                                this.handlers.dispatch(handler, &event);
+                            }
+                        }
+                    }
+                }),
+            }
+        })
+    }
+
+    fn request(&self) {
+        let request = web_sys::window()
+            .unwrap()
+            .request_animation_frame(self.callback.as_ref().unchecked_ref());
+        self.request.set(request.ok());
+    }
+
+    fn subscribe(&self, handler: HandlerId) {
+        let mut subscribed = self.subscribed.borrow_mut();
+        if !subscribed.contains(&handler) {
+            subscribed.push(handler);
+        }
+        if self.request.get().is_none() {
+            self.request();
+        }
+    }
+
+    fn unsubscribe(&self, handler: HandlerId) {
+        let mut subscribed = self.subscribed.borrow_mut();
+        subscribed.retain(|subscribed| *subscribed != handler);
+        if subscribed.is_empty() {
+            if let Some(request) = self.request.take() {
+                let _ = web_sys::window().unwrap().cancel_animation_frame(request);
+            }
+        }
+    }
+}
+
+/// The canvases observed with [`Batch::observe_canvas_size`] with the ids they were stored with and their handlers, and the `ResizeObserver` that resizes them like the javascript interpreter
+struct CanvasObserver {
+    canvases: Rc<RefCell<Vec<(HtmlCanvasElement, NodeId, HandlerId)>>>,
+    observer: ResizeObserver,
+    _callback: Closure<dyn FnMut(js_sys::Array)>,
+}
+
+impl CanvasObserver {
+    fn new(handlers: Handlers) -> Result<Self, String> {
+        let canvases: Rc<RefCell<Vec<(HtmlCanvasElement, NodeId, HandlerId)>>> = Rc::default();
+        let callback = {
+            let canvases = canvases.clone();
+            Closure::new(move |entries: js_sys::Array| {
+                for entry in entries.iter() {
+                    let entry: ResizeObserverEntry = entry.unchecked_into();
+                    let Some((canvas, node, handler)) = canvases
+                        .borrow()
+                        .iter()
+                        .find(|(canvas, ..)| entry.target() == ***canvas)
+                        .cloned()
+                    else {
+                        continue;
+                    };
+                    let ratio = web_sys::window().unwrap().device_pixel_ratio();
+                    let rect = entry.content_rect();
+                    let (width, height) = (rect.width(), rect.height());
+                    // the device pixel box is exact, while rounding the css size can be off by a pixel. Both assume a horizontal writing mode
+                    let pixels = js_sys::Reflect::get(&entry, &"devicePixelContentBoxSize".into())
+                        .ok()
+                        .filter(|pixels| !pixels.is_undefined())
+                        .map(|pixels| {
+                            let size: ResizeObserverSize = pixels
+                                .unchecked_into::<js_sys::Array>()
+                                .get(0)
+                                .unchecked_into();
+                            (size.inline_size() as u32, size.block_size() as u32)
+                        });
+                    let (pixel_width, pixel_height) = pixels.unwrap_or((
+                        (width * ratio).round() as u32,
+                        (height * ratio).round() as u32,
+                    ));
+                    canvas.set_width(pixel_width);
+                    canvas.set_height(pixel_height);
+                    let event = Event::new(
+                        "canvasresize",
+                        EventData::CanvasSize(CanvasSizeData {
+                            node,
+                            width,
+                            height,
+                            device_pixel_ratio: ratio,
+                            pixel_width,
+                            pixel_height,
+                        }),
+                    );
+                    handlers.dispatch(handler, &event);
+                }
+            })
+        };
+        let observer = ResizeObserver::new(callback.as_ref().unchecked_ref()).map_err(js_error)?;
+        Ok(Self {
+            canvases,
+            observer,
+            _callback: callback,
+        })
+    }
+
+    fn observe(&self, canvas: HtmlCanvasElement, node: NodeId, handler: HandlerId) {
+        let mut canvases = self.canvases.borrow_mut();
+        canvases.retain(|(observed, ..)| *observed != canvas);
+        canvases.push((canvas.clone(), node, handler));
+        // the device pixel box changes when the page is zoomed, even if the css size does not. Browsers without it throw
+        let options = ResizeObserverOptions::new();
+        options.set_box(ResizeObserverBoxOptions::DevicePixelContentBox);
+        let observe = js_sys::Reflect::get(&self.observer, &"observe".into())
+            .map(JsCast::unchecked_into::<js_sys::Function>);
+        let observed = observe.and_then(|observe| observe.call2(&self.observer, &canvas, &options));
+        if observed.is_err() {
+            self.observer.observe(&canvas);
+        }
+    }
+
+    fn unobserve(&self, canvas: &HtmlCanvasElement) {
+        let mut canvases = self.canvases.borrow_mut();
+        let len = canvases.len();
+        canvases.retain(|(observed, ..)| observed != canvas);
+        if canvases.len() != len {
+            self.observer.unobserve(canvas);
+        }
+    }
+}
+
+/// Set the value of a form control and keep the cursor the same distance from the end of the value, like the javascript interpreter
+fn write_value(document: &Document, node: &JsValue, value: &str) -> Result<(), JsValue> {
+    let old = js_sys::Reflect::get(node, &"value".into())?
+        .as_string()
+        .unwrap_or_default();
+    if old == value {
+        return Ok(());
+    }
+    let end = js_sys::Reflect::get(node, &"selectionEnd".into())?.as_f64();
+    let focused = document.active_element().map(JsValue::from).as_ref() == Some(node);
+    js_sys::Reflect::set(node, &"value".into(), &value.into())?;
+    if let (true, Some(end)) = (focused, end) {
+        // selection offsets are counted in utf-16 code units
+        let from_end = old.encode_utf16().count() as f64 - end;
+        let cursor = (value.encode_utf16().count() as f64 - from_end).max(0.0);
+        js_sys::Reflect::set(node, &"selectionStart".into(), &cursor.into())?;
+        js_sys::Reflect::set(node, &"selectionEnd".into(), &cursor.into())?;
+    }
+    Ok(())
+}
+
+/// If an element is rendered. Browsers without `checkVisibility` fall back to checking if the element has a box, which is false for elements that are not displayed
+fn check_visibility(element: &Element) -> bool {
+    let check = js_sys::Reflect::get(element, &"checkVisibility".into())
+        .ok()
+        .and_then(|check| check.dyn_into::<js_sys::Function>().ok());
+    match check {
+        Some(check) => check
+            .call0(element)
+            .ok()
+            .and_then(|visible| visible.as_bool())
+            .unwrap_or_default(),
+        None => element.get_client_rects().length() > 0,
+    }
+}
+
+/// Answer a query with true once an image is decoded, or with false if it fails to load or decode
+fn decode_image(image: &Node, queries: Queries, query: QueryId) -> Result<(), JsValue> {
+    let decode = js_sys::Reflect::get(image, &"decode".into())?
+        .dyn_into::<js_sys::Function>()
+        .map_err(|_| JsValue::from("the node is not an image"))?;
+    let decoded = decode.call0(image)?;
+    // allSettled resolves either way, so one callback answers the query
+    let settled = js_sys::Promise::all_settled(&js_sys::Array::of1(&decoded));
+    let answer = Closure::once_into_js(move |results: js_sys::Array| {
+        let status = js_sys::Reflect::get(&results.get(0), &"status".into()).ok();
+        let decoded = status.and_then(|status| status.as_string()).as_deref() == Some("fulfilled");
+        queries.answer(query, Some(decoded.to_string()));
+    });
+    js_sys::Reflect::get(&settled, &"then".into())?
+        .unchecked_into::<js_sys::Function>()
+        .call1(&settled, &answer)?;
+    Ok(())
+}
+
+/// Replace the children of a node with html that only contains the allowed elements and attributes. Browsers without `setHTML` parse the html in a template and clean it like the javascript interpreter
+fn set_sanitized_html(
+    document: &Document,
+    node: &Node,
+    html: &str,
+    elements: &str,
+    attributes: &str,
+) -> Result<(), JsValue> {
+    let elements: Vec<&str> = elements
+        .split(',')
+        .filter(|name| !name.is_empty())
+        .collect();
+    let attributes: Vec<&str> = attributes
+        .split(',')
+        .filter(|name| !name.is_empty())
+        .collect();
+    let set_html = js_sys::Reflect::get(node, &"setHTML".into())?
+        .dyn_into::<js_sys::Function>()
+        .ok();
+    if let Some(set_html) = set_html {
+        let names = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| JsValue::from(*name))
+                .collect::<js_sys::Array>()
+        };
+        let sanitizer = js_sys::Object::new();
+        js_sys::Reflect::set(&sanitizer, &"elements".into(), &names(&elements))?;
+        js_sys::Reflect::set(&sanitizer, &"attributes".into(), &names(&attributes))?;
+        let options = js_sys::Object::new();
+        js_sys::Reflect::set(&options, &"sanitizer".into(), &sanitizer)?;
+        set_html.call2(node, &html.into(), &options)?;
+        return Ok(());
+    }
+    // nothing parsed into a template runs or loads before it is cleaned
+    let template: HtmlTemplateElement = document.create_element("template")?.unchecked_into();
+    template.set_inner_html(html);
+    let content = template.content();
+    sanitize_children(&content, &elements, &attributes)?;
+    node.set_text_content(None);
+    node.append_child(&content)?;
+    Ok(())
+}
+
+/// The namespace of html elements
+const HTML_NS: &str = "http://www.w3.org/1999/xhtml";
+
+/// Remove the children of a node that are not text or allowed html elements along with their content, and the attributes of the allowed elements that are not allowed, handle events or hold a `javascript:` url
+fn sanitize_children(parent: &Node, elements: &[&str], attributes: &[&str]) -> Result<(), JsValue> {
+    let mut child = parent.first_child();
+    while let Some(node) = child {
+        child = node.next_sibling();
+        match node.dyn_ref::<Element>() {
+            Some(element)
+                if element.namespace_uri().as_deref() == Some(HTML_NS)
+                    && elements.contains(&element.local_name().as_str()) =>
+            {
+                for name in element.get_attribute_names().iter() {
+                    let name = name.as_string().unwrap_or_default();
+                    let value = element.get_attribute(&name).unwrap_or_default();
+                    if !attributes.contains(&name.as_str())
+                        || name.starts_with("on")
+                        || is_javascript_url(&value)
+                    {
+                        element.remove_attribute(&name)?;
+                    }
+                }
+                match element.dyn_ref::<HtmlTemplateElement>() {
+                    Some(template) => sanitize_children(&template.content(), elements, attributes)?,
+                    None => sanitize_children(&node, elements, attributes)?,
+                }
+            }
+            _ if node.node_type() == Node::TEXT_NODE => {}
+            _ => {
+                parent.remove_child(&node)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// If a url runs a script when it is followed. Browsers ignore control characters and spaces before the scheme, and tabs and newlines anywhere in a url
+fn is_javascript_url(url: &str) -> bool {
+    let mut scheme = url
+        .trim_start_matches(|c: char| c <= ' ')
+        .chars()
+        .filter(|c| !matches!(c, '\t' | '\n' | '\r'));
+    "javascript:"
+        .chars()
+        .all(|expected| scheme.next().map(|c| c.to_ascii_lowercase()) == Some(expected))
+}
+
+/// Create the `Intl.DateTimeFormat` or `Intl.NumberFormat` for formatted text and return its format function
+fn formatter(date: bool, locale: &str, options: &str) -> Result<js_sys::Function, JsValue> {
+    let locales = js_sys::Array::new();
+    if !locale.is_empty() {
+        locales.push(&locale.into());
+    }
+    let options: js_sys::Object = js_sys::JSON::parse(options)?.dyn_into()?;
+    Ok(if date {
+        js_sys::Intl::DateTimeFormat::new(&locales, &options).format()
+    } else {
+        js_sys::Intl::NumberFormat::new(&locales, &options).format()
+    })
+}
+
+/// Turn a thrown javascript value into a message in the same format as the javascript interpreter
+fn js_error(value: JsValue) -> String {
+    match value.dyn_ref::<js_sys::Error>() {
+        Some(err) => format!("{}: {}", err.name(), err.message()),
+        None => value.as_string().unwrap_or_else(|| format!("{:?}", value)),
+    }
+}
+
+/// Read the data the javascript interpreter encodes for an event from a web-sys event
+fn read_event(event: &web_sys::Event) -> Event {
+    let (modifiers, data) = if let Some(mouse) = event.dyn_ref::<MouseEvent>() {
+        (
+            Modifiers {
+                alt: mouse.alt_key(),
+                ctrl: mouse.ctrl_key(),
+                meta: mouse.meta_key(),
+                shift: mouse.shift_key(),
+            },
+            {
+                let data = MouseData {
+                    client_x: mouse.client_x() as f64,
+                    client_y: mouse.client_y() as f64,
+                    button: mouse.button(),
+                    buttons: mouse.buttons(),
+                };
+                match event.dyn_ref::<PointerEvent>() {
+                    Some(pointer) => EventData::Pointer(PointerData {
+                        mouse: data,
+                        pointer_id: pointer.pointer_id(),
+                        pointer_type: match pointer.pointer_type().as_str() {
+                            "mouse" => PointerType::Mouse,
+                            "pen" => PointerType::Pen,
+                            "touch" => PointerType::Touch,
+                            _ => PointerType::Other,
+                        },
+                        pressure: pointer.pressure(),
+                        width: pointer.width() as f32,
+                        height: pointer.height() as f32,
+                        is_primary: pointer.is_primary(),
+                    }),
+                    None => EventData::Mouse(data),
+                }
+            },
+        )
+    } else if let Some(touch) = event.dyn_ref::<TouchEvent>() {
+        let points = |list: TouchList| {
+            (0..list.length())
+                .filter_map(|i| list.get(i))
+                .map(|point| TouchPoint {
+                    identifier: point.identifier(),
+                    client_x: point.client_x() as f64,
+                    client_y: point.client_y() as f64,
+                    force: point.force(),
+                })
+                .collect()
+        };
+        (
+            Modifiers {
+                alt: touch.alt_key(),
+                ctrl: touch.ctrl_key(),
+                meta: touch.meta_key(),
+                shift: touch.shift_key(),
+            },
+            EventData::Touch(TouchData {
+                touches: points(touch.touches()),
+                changed: points(touch.changed_touches()),
+            }),
+        )
+    } else if let Some(keyboard) = event.dyn_ref::<KeyboardEvent>() {
+        (
+            Modifiers {
+                alt: keyboard.alt_key(),
+                ctrl: keyboard.ctrl_key(),
+                meta: keyboard.meta_key(),
+                shift: keyboard.shift_key(),
+            },
+            EventData::Keyboard(KeyboardData {
+                key: keyboard.key(),
+                code: keyboard.code(),
+                repeat: keyboard.repeat(),
+                location: keyboard.location() as u8,
+            }),
+        )
+    } else if let Some(composition) = event.dyn_ref::<CompositionEvent>() {
+        (
+            Modifiers::default(),
+            EventData::Composition(CompositionData {
+                data: composition.data().unwrap_or_default(),
+            }),
+        )
+    } else if let Some(query) = event.dyn_ref::<MediaQueryListEvent>() {
+        (
+            Modifiers::default(),
+            EventData::MediaQuery(MediaQueryData {
+                media: query.media(),
+                matches: query.matches(),
+            }),
+        )
+    } else if let Some(toggle) = event.dyn_ref::<ToggleEvent>() {
+        (
+            Modifiers::default(),
+            EventData::Toggle(ToggleData {
+                was_open: toggle.old_state() == "open",
+                open: toggle.new_state() == "open",
+            }),
+        )
+    } else if let Some(dialog) = event
+        .target()
+        .filter(|_| event.type_() == "close")
+        .and_then(|target| target.dyn_into::<HtmlDialogElement>().ok())
+    {
+        (
+            Modifiers::default(),
+            EventData::DialogClose(DialogCloseData {
+                return_value: dialog.return_value(),
+            }),
+        )
+    } else if let Some(lock) = LOCK_EVENTS
+        .iter()
+        .position(|events| events.contains(&event.type_().as_str()))
+    {
+        let document = web_sys::window().unwrap().document().unwrap();
+        let active = if lock == 0 {
+            document.fullscreen_element().is_some()
+        } else {
+            document.pointer_lock_element().is_some()
+        };
+        (Modifiers::default(), EventData::Lock(LockData { active }))
+    } else {
+        let target = event.target().map(JsValue::from).unwrap_or_default();
+        let property = |name: &str| js_sys::Reflect::get(&target, &name.into()).ok();
+        let data = match property("value").and_then(|value| value.as_string()) {
+            Some(value) => EventData::Input(InputData {
+                value,
+                checked: property("checked")
+                    .and_then(|checked| checked.as_bool())
+                    .unwrap_or_default(),
+                composing: js_sys::Reflect::get(event, &"isComposing".into())
+                    .ok()
+                    .and_then(|composing| composing.as_bool())
+                    .unwrap_or_default(),
+            }),
+            None => EventData::Other,
+        };
+        (Modifiers::default(), data)
+    };
+    Event::new(event.type_(), data).with_modifiers(modifiers)
+}
+
+impl WebSysInterpreter {
+    pub(crate) fn new(handlers: Handlers, queries: Queries) -> Self {
+        let document = web_sys::window().unwrap().document().unwrap();
+        let composition = Rc::new(Composition::default());
+        let start = {
+            let composition = composition.clone();
+            Closure::new(move |event: web_sys::Event| {
+                *composition.target.borrow_mut() = event.target().map(JsValue::from);
+            })
+        };
+        let end = {
+            let composition = composition.clone();
+            let document = document.clone();
+            Closure::new(move |_: web_sys::Event| composition.end(&document))
+        };
+        for (name, listener) in [("compositionstart", &start), ("compositionend", &end)] {
+            // listen in the capture phase so the composition is tracked before any handler runs
+            document
+                .add_event_listener_with_callback_and_bool(
+                    name,
+                    listener.as_ref().unchecked_ref(),
+                    true,
+                )
+                .unwrap();
+        }
+        Self {
+            document,
+            nodes: Vec::new(),
+            last_node: None,
+            parents: Vec::new(),
+            cached_values: Vec::new(),
+            formatters: HashMap::new(),
+            idle: None,
+            animation_frames: AnimationFrames::new(handlers.clone()),
+            canvases: None,
+            handlers,
+            active: Rc::new(Cell::new(true)),
+            listeners: Vec::new(),
+            coalesced_listeners: Vec::new(),
+            coalesced: Coalesced::new(),
+            composition,
+            queries,
+            media_queries: Rc::default(),
+            lock_handlers: [None; 2],
+            composition_listeners: [start, end],
+        }
+    }
+
+    /// The local or session storage of the window
+    fn storage(area: StorageArea) -> Result<web_sys::Storage, String> {
+        let window = web_sys::window().unwrap();
+        match area {
+            StorageArea::Local => window.local_storage(),
+            StorageArea::Session => window.session_storage(),
+        }
+        .map_err(js_error)?
+        .ok_or_else(|| format!("{:?} storage is not available", area))
+    }
+
+    /// Dispatch the change and error events of fullscreen or pointer lock to a handler instead of the handler of the last request
+    fn route_lock_events(&mut self, lock: usize, handler: HandlerId) -> Result<(), String> {
+        if let Some(old) = self.lock_handlers[lock].replace(handler) {
+            let listener = self.listener(old).clone();
+            for name in LOCK_EVENTS[lock] {
+                self.document
+                    .remove_event_listener_with_callback(name, &listener)
+                    .map_err(js_error)?;
+            }
+        }
+        let listener = self.listener(handler).clone();
+        for name in LOCK_EVENTS[lock] {
+            self.document
+                .add_event_listener_with_callback(name, &listener)
+                .map_err(js_error)?;
+        }
+        Ok(())
+    }
+
+    /// The function that dispatches events to a handler
+    fn listener(&mut self, handler: HandlerId) -> &js_sys::Function {
+        let index = handler.0 as usize;
+        if self.listeners.len() <= index {
+            self.listeners.resize_with(index + 1, || None);
+        }
+        let (handlers, active, composition, document) = (
+            &self.handlers,
+            &self.active,
+            &self.composition,
+            &self.document,
+        );
+        let listener: &Listener = self.listeners[index].get_or_insert_with(|| {
+            let handlers = handlers.clone();
+            let active = active.clone();
+            let composition = composition.clone();
+            let document = document.clone();
+            Closure::new(move |event: web_sys::Event| {
+                if !active.get() {
+                    return;
+                }
+                let read = read_event(&event);
+                handlers.dispatch(handler, &read);
+                if read.default_prevented() {
+                    event.prevent_default();
+                }
+                if read.propagation_stopped() {
+                    event.stop_propagation();
+                }
+                if let (Some(value), Some(target)) = (read.take_value(), event.target()) {
+                    // there is no batch to report the error to
+                    let _ = composition.set_value(&document, &target.into(), &value);
+                }
+            })
+        });
+        listener.as_ref().unchecked_ref()
+    }
+
+    /// The function that keeps the latest event for a handler and dispatches it in the next animation frame
+    fn coalesced_listener(&mut self, handler: HandlerId) -> &js_sys::Function {
+        let index = handler.0 as usize;
+        if self.coalesced_listeners.len() <= index {
+            self.coalesced_listeners.resize_with(index + 1, || None);
+        }
+        let listener = self.listener(handler).clone();
+        let coalesced = self.coalesced.clone();
+        let listener: &Listener = self.coalesced_listeners[index].get_or_insert_with(|| {
+            Closure::new(move |event: web_sys::Event| coalesced.push(handler, &listener, event))
+        });
+        listener.as_ref().unchecked_ref()
+    }
+
+    /// Dispatch a `viewtransitionend` event to a handler once the batch is done, so the handler does not run while the batch is applied
+    fn end_view_transition(&self, handler: HandlerId, error: Option<String>) {
+        let handlers = self.handlers.clone();
+        let event = Event::new(
+            "viewtransitionend",
+            EventData::ViewTransition(ViewTransitionData {
+                animated: false,
+                error,
+            }),
+        );
+        let dispatch = Closure::once_into_js(move || handlers.dispatch(handler, &event));
+        web_sys::window()
+            .unwrap()
+            .queue_microtask(dispatch.unchecked_ref());
+    }
+
+    pub(crate) fn set_node(&mut self, id: NodeId, node: Node) {
+        let index = id.index() as usize;
+        if self.nodes.len() <= index {
+            self.nodes.resize(index + 1, None);
+        }
+        self.nodes[index] = Some((id.generation(), node));
+    }
+
+    /// Replace the interpreter with a new one that takes over the page, like the javascript interpreter is replaced. Keeps the nodes stored with the ids in `keep`, or every stored node if it is None
+    pub(crate) fn restart(&mut self, keep: Option<&[NodeId]>) {
+        let mut restarted = Self::new(self.handlers.clone(), self.queries.clone());
+        match keep {
+            None => restarted.nodes = self.nodes.clone(),
+            Some(ids) => {
+                for &id in ids {
+                    if let Ok(node) = self.stored(id) {
+                        restarted.set_node(id, node);
+                    }
+                }
+            }
+        }
+        std::mem::replace(self, restarted).dispose();
+    }
+
+    /// Stop dispatching events and remove the listeners, subscriptions and observers the interpreter added outside of the nodes it created
+    fn dispose(mut self) {
+        self.active.set(false);
+        for (_, handler, list) in self.media_queries.take() {
+            let listener = self.listener(handler).clone();
+            let _ = list.remove_event_listener_with_callback("change", &listener);
+        }
+        for (lock, handler) in self.lock_handlers.into_iter().enumerate() {
+            if let Some(handler) = handler {
+                let listener = self.listener(handler).clone();
+                for name in LOCK_EVENTS[lock] {
+                    let _ = self
+                        .document
+                        .remove_event_listener_with_callback(name, &listener);
+                }
+            }
+        }
+        self.animation_frames.subscribed.take();
+        if let Some(request) = self.animation_frames.request.take() {
+            let _ = web_sys::window().unwrap().cancel_animation_frame(request);
+        }
+        if let Some(canvases) = &self.canvases {
+            canvases.observer.disconnect();
+        }
+        for (name, listener) in ["compositionstart", "compositionend"]
+            .into_iter()
+            .zip(&self.composition_listeners)
+        {
+            let _ = self.document.remove_event_listener_with_callback_and_bool(
+                name,
+                listener.as_ref().unchecked_ref(),
+                true,
+            );
+        }
+        // the nodes keep the listeners and a frame may be requested for coalesced events, so they are leaked instead of dropped to keep calling them from throwing
+        self.coalesced.events.borrow_mut().clear();
+        std::mem::forget(self.coalesced);
+        for listener in self
+            .listeners
+            .into_iter()
+            .chain(self.coalesced_listeners)
+            .flatten()
+        {
+            listener.forget();
+        }
+    }
+
+    pub(crate) fn get_node(&self, id: NodeId) -> Node {
+        self.stored(id).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    fn stored(&self, id: NodeId) -> Result<Node, String> {
+        match self.nodes.get(id.index() as usize) {
+            Some(Some((generation, node))) => {
+                if *generation != id.generation() {
+                    return Err(format!(
+                        "node {} was used with the stale generation {}, the current generation is {}",
+                        id.index(),
+                        id.generation(),
+                        generation
+                    ));
+                }
+                Ok(node.clone())
+            }
+            _ => Err(format!("node {} was used before it was stored", id.index())),
+        }
+    }
+
+    fn node(&self, id: MaybeId) -> Result<Node, String> {
+        match id {
+            MaybeId::LastNode => self
+                .last_node
+                .clone()
+                .ok_or_else(|| "there is no last node".to_string()),
+            MaybeId::Node(id) => self.stored(id),
+        }
+    }
+
+    fn nodes(&self, ids: &[MaybeId]) -> Result<js_sys::Array, String> {
+        ids.iter()
+            .map(|id| self.node(*id).map(JsValue::from))
+            .collect()
+    }
+
+    fn element(&self, id: MaybeId) -> Result<Element, String> {
+        // methods like remove and replaceWith exist on text nodes as well as elements
+        self.node(id).map(JsCast::unchecked_into)
+    }
+
+    /// The ids a node is stored with
+    fn ids_of<'a>(&'a self, node: &'a Node) -> impl Iterator<Item = NodeId> + 'a {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter_map(move |(index, stored)| match stored {
+                Some((generation, stored)) if stored == node => {
+                    Some(NodeId::with_generation(index as u32, *generation))
+                }
+                _ => None,
+            })
+    }
+
+    fn canvas(&self, id: MaybeId) -> Result<HtmlCanvasElement, String> {
+        self.node(id)?
+            .dyn_into()
+            .map_err(|_| "the node is not a canvas element".to_string())
+    }
+
+    fn dialog(&self, id: MaybeId) -> Result<HtmlDialogElement, String> {
+        self.node(id)?
+            .dyn_into()
+            .map_err(|_| "the node is not a dialog element".to_string())
+    }
+
+    fn create_element(&self, tag: &AnyElement) -> Result<Element, JsValue> {
+        match tag {
+            #[cfg(feature = "elements")]
+            AnyElement::Element(el) => self.document.create_element(el.name()),
+            #[cfg(feature = "elements")]
+            AnyElement::InNamespace(el) => self.document.create_element_ns(Some(el.1), el.0.name()),
+            AnyElement::Str(el) => self.document.create_element(el),
+            AnyElement::InNamespaceStr(el) => self.document.create_element_ns(Some(el.1), el.0),
+        }
+    }
+
+    fn set_attribute(el: &Element, attr: &AnyAttribute, value: &str) -> Result<(), JsValue> {
+        match attr {
+            #[cfg(feature = "attributes")]
+            AnyAttribute::Attribute(attr) => el.set_attribute(attr.name(), value),
+            #[cfg(feature = "attributes")]
+            AnyAttribute::InNamespace(attr) => {
+                el.set_attribute_ns(Some(attr.1), attr.0.name(), value)
+            }
+            AnyAttribute::Str(attr) => el.set_attribute(attr, value),
+            AnyAttribute::InNamespaceStr(attr) => el.set_attribute_ns(Some(attr.1), attr.0, value),
+        }
+    }
+
+    fn remove_attribute(el: &Element, attr: &AnyAttribute) -> Result<(), JsValue> {
+        match attr {
+            #[cfg(feature = "attributes")]
+            AnyAttribute::Attribute(attr) => el.remove_attribute(attr.name()),
+            #[cfg(feature = "attributes")]
+            AnyAttribute::InNamespace(attr) => el.remove_attribute_ns(Some(attr.1), attr.0.name()),
+            AnyAttribute::Str(attr) => el.remove_attribute(attr),
+            AnyAttribute::InNamespaceStr(attr) => el.remove_attribute_ns(Some(attr.1), attr.0),
+        }
+    }
+
+    fn store(&mut self, id: Option<NodeId>, node: &Node) {
+        if let Some(id) = id {
+            self.set_node(id, node.clone());
+        }
+    }
+
+    fn build(&mut self, node: &DecodedNode) -> Result<Node, String> {
+        match node {
+            DecodedNode::Text { id, text } => {
+                let node: Node = self.document.create_text_node(text).into();
+                self.store(*id, &node);
+                Ok(node)
+            }
+            DecodedNode::Element {
+                id,
+                kind,
+                attrs,
+                children,
+            } => {
+                let el = self.create_element(kind).map_err(js_error)?;
+                for (attr, value) in attrs {
+                    Self::set_attribute(&el, attr, value).map_err(js_error)?;
+                }
+                for child in children {
+                    let child = self.build(child)?;
+                    el.append_child(&child).map_err(js_error)?;
+                }
+                let node: Node = el.into();
+                self.store(*id, &node);
+                Ok(node)
+            }
+        }
+    }
+
+    fn apply(&mut self, op: DecodedOp) -> Result<(), String> {
+        match op {
+            DecodedOp::FirstChild => {
+                self.last_node = self.node(MaybeId::LastNode)?.first_child();
+            }
+            DecodedOp::NextSibling => {
+                self.last_node = self.node(MaybeId::LastNode)?.next_sibling();
+            }
+            DecodedOp::ParentNode => {
+                self.last_node = self.node(MaybeId::LastNode)?.parent_node();
+            }
+            DecodedOp::StoreWithId(id) => {
+                let node = self.node(MaybeId::LastNode)?;
+                self.set_node(id, node);
+            }
+            DecodedOp::SetLastNode(id) => {
+                self.last_node = Some(self.stored(id)?);
+            }
+            DecodedOp::HintNodeCapacity(capacity) => {
+                self.nodes
+                    .reserve((capacity as usize).saturating_sub(self.nodes.len()));
+            }
+            // only the javascript interpreter pools nodes
+            DecodedOp::Stop | DecodedOp::NoOp | DecodedOp::SetPoolSize { .. } => {}
+            DecodedOp::BuildFullElement(node) => {
+                self.last_node = Some(self.build(&node)?);
+            }
+            DecodedOp::AppendChildren { root, child } => {
+                let child = self.node(child)?;
+                self.node(root)?.append_child(&child).map_err(js_error)?;
+            }
+            DecodedOp::ReplaceWith { root, nodes } => {
+                let nodes = self.nodes(&nodes)?;
+                self.element(root)?
+                    .replace_with_with_node(&nodes)
+                    .map_err(js_error)?;
+            }
+            DecodedOp::InsertAfter { root, nodes } => {
+                let nodes = self.nodes(&nodes)?;
+                self.element(root)?
+                    .after_with_node(&nodes)
+                    .map_err(js_error)?;
+            }
+            DecodedOp::InsertBefore { root, nodes } => {
+                let nodes = self.nodes(&nodes)?;
+                self.element(root)?
+                    .before_with_node(&nodes)
+                    .map_err(js_error)?;
+            }
+            DecodedOp::Remove(id) => self.element(id)?.remove(),
+            DecodedOp::CreateTextNode { text, id } => {
+                let node: Node = self.document.create_text_node(text).into();
+                self.store(id, &node);
+                self.last_node = Some(node);
+            }
+            DecodedOp::CreateElement { tag, id } => {
+                let node: Node = self.create_element(&tag).map_err(js_error)?.into();
+                self.store(id, &node);
+                self.last_node = Some(node);
+            }
+            DecodedOp::SetText { root, text } => self.node(root)?.set_text_content(Some(text)),
+            DecodedOp::SetFormattedText {
+                root,
+                date,
+                value,
+                locale,
+                options,
+            } => {
+                let key = (date, locale.to_string(), options.to_string());
+                let format = match self.formatters.get(&key) {
+                    Some(format) => format.clone(),
+                    None => {
+                        let format = formatter(date, locale, options).map_err(js_error)?;
+                        self.formatters.insert(key, format.clone());
+                        format
+                    }
+                };
+                // values that are not numbers are formatted as NaN like in javascript
+                let value = value.parse().unwrap_or(f64::NAN);
+                let text = format
+                    .call1(&JsValue::NULL, &JsValue::from_f64(value))
+                    .map_err(js_error)?;
+                self.node(root)?
+                    .set_text_content(text.as_string().as_deref());
+            }
+            DecodedOp::SetSanitizedHtml {
+                root,
+                html,
+                elements,
+                attributes,
+            } => {
+                let node = self.node(root)?;
+                set_sanitized_html(&self.document, &node, html, elements, attributes)
+                    .map_err(js_error)?;
+            }
+            DecodedOp::SetAttribute { root, attr, value } => {
+                Self::set_attribute(&self.element(root)?, &attr, value).map_err(js_error)?
+            }
+            DecodedOp::RemoveAttribute { root, attr } => {
+                Self::remove_attribute(&self.element(root)?, &attr).map_err(js_error)?
+            }
+            DecodedOp::AddListener {
+                root,
+                event,
+                handler,
+                coalesced: false,
+            } => {
+                let node = self.node(root)?;
+                node.add_event_listener_with_callback(event, self.listener(handler))
+                    .map_err(js_error)?
+            }
+            DecodedOp::AddListener {
+                root,
+                event,
+                handler,
+                coalesced: true,
+            } => {
+                let node = self.node(root)?;
+                let options = AddEventListenerOptions::new();
+                options.set_passive(true);
+                node.add_event_listener_with_callback_and_add_event_listener_options(
+                    event,
+                    self.coalesced_listener(handler),
+                    &options,
+                )
+                .map_err(js_error)?
+            }
+            DecodedOp::RemoveListener {
+                root,
+                event,
+                handler,
+            } => {
+                let node = self.node(root)?;
+                node.remove_event_listener_with_callback(event, self.listener(handler))
+                    .map_err(js_error)?;
+                node.remove_event_listener_with_callback(event, self.coalesced_listener(handler))
+                    .map_err(js_error)?;
+                self.coalesced.remove(handler, &node, event);
+            }
+            DecodedOp::SetValue { root, value } => self
+                .composition
+                .set_value(&self.document, &self.element(root)?.into(), value)
+                .map_err(js_error)?,
+            DecodedOp::ShowModal(root) => self.dialog(root)?.show_modal().map_err(js_error)?,
+            DecodedOp::CloseDialog(root) => self.dialog(root)?.close(),
+            DecodedOp::ShowPopover(root) => self
+                .element(root)?
+                .unchecked_into::<HtmlElement>()
+                .show_popover()
+                .map_err(js_error)?,
+            DecodedOp::HidePopover(root) => self
+                .element(root)?
+                .unchecked_into::<HtmlElement>()
+                .hide_popover()
+                .map_err(js_error)?,
+            DecodedOp::RequestFullscreen { root, handler } => {
+                self.route_lock_events(0, handler)?;
+                self.element(root)?.request_fullscreen().map_err(js_error)?;
+            }
+            DecodedOp::ExitFullscreen => {
+                // exiting while nothing is in fullscreen rejects the promise exitFullscreen returns
+                if self.document.fullscreen_element().is_some() {
+                    self.document.exit_fullscreen()
+                }
+            }
+            DecodedOp::RequestPointerLock { root, handler } => {
+                self.route_lock_events(1, handler)?;
+                self.element(root)?.request_pointer_lock();
+            }
+            DecodedOp::ExitPointerLock => self.document.exit_pointer_lock(),
+            DecodedOp::ObserveCanvasSize { root, handler } => {
+                let canvas = self.canvas(root)?;
+                let node = match root {
+                    MaybeId::Node(id) => id,
+                    MaybeId::LastNode => self
+                        .ids_of(&canvas)
+                        .next()
+                        .ok_or("the canvas must be stored with an id to be observed")?,
+                };
+                if self.canvases.is_none() {
+                    self.canvases = Some(CanvasObserver::new(self.handlers.clone())?);
+                }
+                self.canvases
+                    .as_ref()
+                    .unwrap()
+                    .observe(canvas, node, handler);
+            }
+            DecodedOp::UnobserveCanvasSize(root) => {
+                let canvas = self.canvas(root)?;
+                if let Some(canvases) = &self.canvases {
+                    canvases.unobserve(&canvas);
+                }
+            }
+            DecodedOp::SubscribeAnimationFrames(handler) => {
+                self.animation_frames.subscribe(handler)
+            }
+            DecodedOp::UnsubscribeAnimationFrames(handler) => {
+                self.animation_frames.unsubscribe(handler)
+            }
+            DecodedOp::SubscribeMediaQuery { query, handler } => {
+                if self
+                    .media_queries
+                    .borrow()
+                    .iter()
+                    .any(|(subscribed, added, _)| subscribed == query && *added == handler)
+                {
+                    return Ok(());
+                }
+                let window = web_sys::window().unwrap();
+                let list = window
+                    .match_media(query)
+                    .map_err(js_error)?
+                    .ok_or_else(|| format!("{:?} is not a media query", query))?;
+                let listener = self.listener(handler).clone();
+                list.add_event_listener_with_callback("change", &listener)
+                    .map_err(js_error)?;
+                self.media_queries
+                    .borrow_mut()
+                    .push((query.to_string(), handler, list.clone()));
+                // send the current state once the batch is done, so the handler does not run while the batch is applied
+                let media_queries = self.media_queries.clone();
+                let initial = Closure::once_into_js(move || {
+                    let subscribed = media_queries
+                        .borrow()
+                        .iter()
+                        .any(|(_, _, subscribed)| *subscribed == list);
+                    if subscribed {
+                        let init = MediaQueryListEventInit::new();
+                        init.set_media(&list.media());
+                        init.set_matches(list.matches());
+                        if let Ok(event) =
+                            MediaQueryListEvent::new_with_event_init_dict("change", &init)
+                        {
+                            let _ = listener.call1(&JsValue::NULL, &event);
+                        }
+                    }
+                });
+                window.queue_microtask(initial.unchecked_ref());
+            }
+            DecodedOp::UnsubscribeMediaQuery { query, handler } => {
+                let listener = self.listener(handler).clone();
+                let mut media_queries = self.media_queries.borrow_mut();
+                if let Some(index) = media_queries
+                    .iter()
+                    .position(|(subscribed, added, _)| subscribed == query && *added == handler)
+                {
+                    let (_, _, list) = media_queries.remove(index);
+                    list.remove_event_listener_with_callback("change", &listener)
+                        .map_err(js_error)?;
+                }
+            }
+            DecodedOp::ObserveCustomElement { tag, .. }
+            | DecodedOp::UnobserveCustomElement { tag, .. } => {
+                // custom elements read their callbacks when they are defined, and wrapping them needs javascript functions that rust closures cannot express
+                return Err(format!(
+                    "the lifecycle callbacks of {} can only be observed with the javascript interpreter",
+                    tag
+                ));
+            }
+            DecodedOp::GetStorageItem { area, key, query } => {
+                let value = Self::storage(area)?.get_item(key).map_err(js_error)?;
+                self.queries.answer(query, value);
+            }
+            DecodedOp::ActiveElement(query) => {
+                let id = self
+                    .document
+                    .active_element()
+                    .and_then(|element| self.ids_of(&element).next());
+                self.queries.answer(query, id.map(|id| id.0.to_string()));
+            }
+            DecodedOp::ElementFromPoint { x, y, query } => {
+                let id = self
+                    .document
+                    .element_from_point(x as f32, y as f32)
+                    .and_then(|element| self.ids_of(&element).next());
+                self.queries.answer(query, id.map(|id| id.0.to_string()));
+            }
+            DecodedOp::CheckVisibility { root, query } => {
+                let element: Element = self
+                    .node(root)?
+                    .dyn_into()
+                    .map_err(|_| "the node is not an element".to_string())?;
+                let visible = check_visibility(&element);
+                self.queries.answer(query, Some(visible.to_string()));
+            }
+            DecodedOp::SetImageLoading {
+                root,
+                lazy,
+                async_decoding,
+            } => {
+                let element = self.element(root)?;
+                element
+                    .set_attribute("loading", if lazy { "lazy" } else { "eager" })
+                    .map_err(js_error)?;
+                element
+                    .set_attribute("decoding", if async_decoding { "async" } else { "auto" })
+                    .map_err(js_error)?;
+            }
+            DecodedOp::DecodeImage { root, query } => {
+                decode_image(&self.node(root)?, self.queries.clone(), query).map_err(js_error)?;
+            }
+            DecodedOp::ToggleClass { root, class, add } => {
+                self.element(root)?
+                    .class_list()
+                    .toggle_with_force(class, add)
+                    .map_err(js_error)?;
+            }
+            // the update callback of a transition runs after the batch, when the backend is not borrowed anymore, so the operations are applied in place without animating them
+            DecodedOp::ViewTransition {
+                handler,
+                msg,
+                str_buf,
+            } => {
+                let error = self.run(msg, str_buf, false).err().map(|err| err.message);
+                self.end_view_transition(handler, error);
+            }
+            DecodedOp::SetStorageItem { area, key, value } => Self::storage(area)?
+                .set_item(key, value)
+                .map_err(js_error)?,
+            DecodedOp::RemoveStorageItem { area, key } => {
+                Self::storage(area)?.remove_item(key).map_err(js_error)?
+            }
+            DecodedOp::CacheValue { id, value } => {
+                let index = id as usize;
+                if self.cached_values.len() <= index {
+                    self.cached_values.resize(index + 1, String::new());
+                }
+                self.cached_values[index] = value.to_string();
+            }
+            DecodedOp::SetAttributeCached { root, attr, value } => {
+                let element = self.element(root)?;
+                let value = self
+                    .cached_values
+                    .get(value as usize)
+                    .ok_or_else(|| format!("no value was cached with the id {}", value))?;
+                Self::set_attribute(&element, &attr, value).map_err(js_error)?
+            }
+            DecodedOp::SetStyle { root, style, value } => self
+                .element(root)?
+                .unchecked_into::<HtmlElement>()
+                .style()
+                .set_property(style, value)
+                .map_err(js_error)?,
+            DecodedOp::RemoveStyle { root, style } => {
+                self.element(root)?
+                    .unchecked_into::<HtmlElement>()
+                    .style()
+                    .remove_property(style)
+                    .map_err(js_error)?;
+            }
+            DecodedOp::CloneNode { id, new_id } => {
+                let node = self
+                    .node(id)?
+                    .clone_node_with_deep(true)
+                    .map_err(js_error)?;
+                if let MaybeId::Node(new_id) = new_id {
+                    self.set_node(new_id, node.clone());
+                }
+                self.last_node = Some(node);
+            }
+            DecodedOp::AdoptIframeDocument { iframe, id } => {
+                let body = self
+                    .node(iframe)?
+                    .unchecked_into::<HtmlIFrameElement>()
+                    .content_document()
+                    .and_then(|document| document.body())
+                    .ok_or_else(|| "the iframe does not have a same-origin document".to_string())?;
+                self.set_node(id, body.into());
+            }
+            DecodedOp::PushParent => {
+                let node = self.node(MaybeId::LastNode)?;
+                self.parents.push(node);
+            }
+            DecodedOp::PopParent => {
+                let child = self.node(MaybeId::LastNode)?;
+                let parent = self
+                    .parents
+                    .pop()
+                    .ok_or_else(|| "there is no parent to append to".to_string())?;
+                parent.append_child(&child).map_err(js_error)?;
+                self.last_node = Some(parent);
+            }
+            DecodedOp::OpenParent | DecodedOp::AppendToParent => {
+                let child = self.node(MaybeId::LastNode)?;
+                let parent = self
+                    .parents
+                    .last()
+                    .ok_or_else(|| "there is no parent to append to".to_string())?;
+                parent.append_child(&child).map_err(js_error)?;
+                if let DecodedOp::OpenParent = op {
+                    self.parents.push(child);
+                }
+            }
+            DecodedOp::CloseParent => {
+                let parent = self
+                    .parents
+                    .pop()
+                    .ok_or_else(|| "there is no parent to close".to_string())?;
+                self.last_node = Some(parent);
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply all operations in a finalized message. Operations before a failing operation are still applied.
+    pub(crate) fn run(
+        &mut self,
+        msg: &[u8],
+        str_buf: &[u8],
+        profile: bool,
+    ) -> Result<Option<FlushStats>, DomError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            "run_batch",
+            msg_bytes = msg.len(),
+            str_bytes = str_buf.len(),
+            ops = tracing::field::Empty,
+            duration_ms = tracing::field::Empty,
+        )
+        .entered();
+        let performance = profile.then(|| web_sys::window().unwrap().performance().unwrap());
+        let start = performance.as_ref().map(|p| p.now());
+        let mut ops = 0;
+        for op in Decoder::new(msg, str_buf) {
+            let (offset, op) = op.map_err(|err| DomError {
+                offset: err.offset,
+                message: err.to_string(),
+            })?;
+            if op == DecodedOp::Stop {
+                break;
+            }
+            self.apply(op)
+                .map_err(|message| DomError { offset, message })?;
+            ops += 1;
+        }
+        let stats = performance
+            .zip(start)
+            .map(|(performance, start)| FlushStats {
+                msg_bytes: msg.len(),
+                str_bytes: str_buf.len(),
+                ops,
+                duration_ms: performance.now() - start,
+            });
+        #[cfg(feature = "tracing")]
+        if let Some(stats) = &stats {
+            _span
+                .record("ops", stats.ops)
+                .record("duration_ms", stats.duration_ms);
+        }
+        Ok(stats)
+    }
+    /// Apply all operations in a finalized message and return a batch that undoes them
+    pub(crate) fn run_with_inverse(
+        &mut self,
+        msg: &[u8],
+        str_buf: &[u8],
+        scratch: NodeId,
+    ) -> Result<Batch, DomError> {
+        run_with_inverse(self, msg, str_buf, scratch)
+    }
+
+    /// Apply the operations in a finalized message until the deadline from [`crate::channel::now_ms`] passes. If `resume` is true, the message that ran out of time last is continued. Returns true if the message finished.
+    pub(crate) fn run_idle(
+        &mut self,
+        msg: &[u8],
+        str_buf: &[u8],
+        deadline: f64,
+        resume: bool,
+    ) -> Result<bool, DomError> {
+        let (mut decoder, last_node) = match self.idle.take().filter(|_| resume) {
+            Some((state, last_node)) => (Decoder::resume(msg, str_buf, state), last_node),
+            None => (Decoder::new(msg, str_buf), self.last_node.clone()),
+        };
+        // idle batches keep their own last node
+        let last_node = std::mem::replace(&mut self.last_node, last_node);
+        let mut ops = 0;
+        let result = loop {
+            let (offset, op) = match decoder.next() {
+                Some(Ok(op)) => op,
+                Some(Err(err)) => {
+                    break Err(DomError {
+                        offset: err.offset,
+                        message: err.to_string(),
+                    })
+                }
+                None => break Ok(true),
+            };
+            if op == DecodedOp::Stop {
+                break Ok(true);
+            }
+            if let Err(message) = self.apply(op) {
+                break Err(DomError { offset, message });
+            }
+            ops += 1;
+            // check the time after every four operations like the javascript interpreter
+            if ops % 4 == 0 && crate::channel::now_ms() >= deadline {
+                self.idle = Some((decoder.state(), self.last_node.clone()));
+                break Ok(false);
+            }
+        };
+        self.last_node = last_node;
+        result
+    }
+}
+
+impl InverseDom for WebSysInterpreter {
+    type Node = Node;
+
+    fn resolve_node(&self, id: MaybeId) -> Result<Node, String> {
+        self.node(id)
+    }
+
+    fn parent_of(&self, node: &Node) -> Option<Node> {
+        node.parent_node()
+    }
+
+    fn children_of(&self, node: &Node) -> Vec<Node> {
+        let children = node.child_nodes();
+        (0..children.length())
+            .filter_map(|i| children.get(i))
+            .collect()
+    }
+
+    fn id_of(&self, node: &Node, except: NodeId) -> Option<NodeId> {
+        self.ids_of(node).find(|id| id.index() != except.index())
+    }
+
+    fn snapshot(&self, node: &Node) -> Option<Snapshot> {
+        match node.node_type() {
+            Node::TEXT_NODE => Some(Snapshot::Text(node.text_content().unwrap_or_default())),
+            Node::ELEMENT_NODE => {
+                let el: &Element = node.unchecked_ref();
+                let attributes = el.attributes();
+                Some(Snapshot::Element {
+                    tag: el.local_name(),
+                    namespace: el.namespace_uri().filter(|ns| ns != HTML_NAMESPACE),
+                    attributes: (0..attributes.length())
+                        .filter_map(|i| attributes.item(i))
+                        .map(|attr| (attr.name(), attr.namespace_uri(), attr.value()))
+                        .collect(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn attribute_value(&self, node: &Node, name: &str, namespace: Option<&str>) -> Option<String> {
+        let el: &Element = node.dyn_ref()?;
+        match namespace {
+            // namespaced attributes are looked up by their local name
+            Some(namespace) => {
+                let local_name = name.rsplit(':').next().unwrap_or(name);
+                el.get_attribute_ns(Some(namespace), local_name)
+            }
+            None => el.get_attribute(name),
+        }
+    }
+
+    fn style_value(&self, node: &Node, property: &str) -> Option<String> {
+        // svg elements have a style declaration as well
+        let value = node
+            .dyn_ref::<Element>()?
+            .unchecked_ref::<HtmlElement>()
+            .style()
+            .get_property_value(property)
+            .ok()?;
+        Some(value).filter(|value| !value.is_empty())
+    }
+
+    fn form_value(&self, node: &Node) -> String {
+        js_sys::Reflect::get(node, &"value".into())
+            .ok()
+            .and_then(|value| value.as_string())
+            .unwrap_or_default()
+    }
+
+    fn popover_open(&self, node: &Node) -> bool {
+        node.dyn_ref::<Element>()
+            .and_then(|element| element.matches(":popover-open").ok())
+            .unwrap_or_default()
+    }
+
+    fn canvas_handler(&self, node: &Node) -> Option<HandlerId> {
+        let canvases = self.canvases.as_ref()?.canvases.borrow();
+        canvases
+            .iter()
+            .find_map(|(canvas, _, handler)| (****canvas == *node).then_some(*handler))
+    }
+
+    fn top_parent(&self) -> Option<Node> {
+        self.parents.last().cloned()
+    }
+
+    fn end_view_transition(&mut self, handler: HandlerId, error: Option<String>) {
+        WebSysInterpreter::end_view_transition(self, handler, error)
+    }
+
+    fn apply_op(&mut self, op: DecodedOp) -> Result<(), String> {
+        self.apply(op)
+    }
+}
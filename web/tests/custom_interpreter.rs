@@ -0,0 +1,47 @@
+//! Checks that a [`MsgChannel`] created with a custom interpreter sends its batches to that interpreter.
+
+#![cfg(all(target_arch = "wasm32", not(feature = "web-sys-backend")))]
+
+use sledgehammer::{MsgChannel, NodeId};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+#[wasm_bindgen(inline_js = "
+export class CountingInterpreter {
+    constructor(mem, metadata_ptr, msg_ptr, str_ptr, str_len_ptr, debug, elements, attributes) {
+        this.nodes = [];
+        this.flushes = 0;
+    }
+    Work() { this.flushes++; }
+    WorkChecked() { this.flushes++; return null; }
+    WorkProfiled() { this.flushes++; return [null, 0, 0]; }
+    SetNode(id, node) { this.nodes[id] = node; }
+    GetNode(id) { return this.nodes[id]; }
+}
+export function countingInterpreter() { return CountingInterpreter; }
+")]
+extern "C" {
+    #[wasm_bindgen(js_name = countingInterpreter)]
+    fn counting_interpreter() -> js_sys::Function;
+}
+
+#[wasm_bindgen_test]
+fn custom_interpreter() {
+    let mut channel = MsgChannel::with_interpreter(&counting_interpreter());
+    let body = web_sys::window()
+        .unwrap()
+        .document()
+        .unwrap()
+        .body()
+        .unwrap();
+    channel.set_node(NodeId(0), body.clone().into());
+    assert!(channel.get_node(NodeId(0)).is_same_node(Some(&body)));
+    channel.create_element("div", None);
+    channel.flush().unwrap();
+    channel.set_profiling(true);
+    channel.flush().unwrap();
+    // the counting interpreter reports that it did not run any operations
+    assert_eq!(channel.last_flush_stats().unwrap().ops, 0);
+}
@@ -0,0 +1,1657 @@
+//! The conformance suite for the headless dom. These tests apply the same operations as the browser suite in `interpreter.rs` on native targets and check the serialized html.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::{
+    cell::RefCell,
+    future::Future,
+    pin::pin,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll, Wake, Waker},
+};
+
+use sledgehammer::{
+    controlled::Reconcile,
+    events::{
+        AttributeChange, CanvasSizeData, CompositionData, DialogCloseData, Event, EventData,
+        FrameData, InputData, KeyboardData, LifecycleData, LockData, MediaQueryData, MouseData,
+        PointerData, PointerType, ToggleData, TouchData, TouchPoint, ViewTransitionData,
+    },
+    head::{CrossOrigin, ResourceHint},
+    headless::{HeadlessNode, NodeRef},
+    intl::{DateTimeFormat, DateTimeStyle, NumberFormat, NumberStyle},
+    mutations,
+    recording::{Recording, Replay},
+    reload::KeepNodes,
+    sanitize::Allowlist,
+    scheduler::FrameScheduler,
+    sledgehammer_encoder::{batch::Batch, WithNsExt},
+    subtree::SubtreeChannel,
+    Attribute, Element, ElementBuilder, FragmentBuilder, MaybeId, MsgChannel, NodeId, StorageArea,
+    TextBuilder,
+};
+
+const SVG_NS: &str = "http://www.w3.org/2000/svg";
+
+/// The id of the root element each test renders into
+const ROOT: NodeId = NodeId(0);
+
+/// Create a channel with an empty root element stored with the [`ROOT`] id
+fn channel() -> (MsgChannel, NodeRef) {
+    let mut channel = MsgChannel::default();
+    let root = channel.dom_mut().create_element("div");
+    channel.dom_mut().set_node(ROOT, root);
+    (channel, root)
+}
+
+/// Render `<div id=1><p id=2></p><span id=3></span></div>` into the root
+fn render_list() -> (MsgChannel, NodeRef) {
+    let (mut channel, root) = channel();
+    channel.build_full_element(
+        ElementBuilder::new(Element::div.into())
+            .id(NodeId(1))
+            .children(&[
+                ElementBuilder::new(Element::p.into()).id(NodeId(2)).into(),
+                ElementBuilder::new(Element::span.into())
+                    .id(NodeId(3))
+                    .into(),
+            ]),
+    );
+    channel.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    channel.flush().unwrap();
+    (channel, root)
+}
+
+#[test]
+fn create_element() {
+    let (mut c, root) = channel();
+    c.create_element(Element::div, Some(NodeId(1)));
+    c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    c.create_element("custom-element", None);
+    c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    c.create_element("svg".in_namespace(SVG_NS), None);
+    c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    c.flush().unwrap();
+    assert_eq!(
+        c.dom().inner_html(root),
+        "<div></div><custom-element></custom-element><svg></svg>"
+    );
+    let svg = c.dom().children(root)[2];
+    match c.dom().node(svg) {
+        HeadlessNode::Element(el) => assert_eq!(el.namespace.as_deref(), Some(SVG_NS)),
+        HeadlessNode::Text(_) => panic!("expected an element"),
+    }
+}
+
+#[test]
+fn build_full_element() {
+    let (mut c, root) = channel();
+    c.build_full_element(
+        ElementBuilder::new(Element::div.into())
+            .id(NodeId(1))
+            .attrs(&[(Attribute::class.into(), "a"), ("data-custom".into(), "b")])
+            .children(&[
+                ElementBuilder::new(Element::input.into()).into(),
+                TextBuilder::new("text").id(NodeId(2)).into(),
+            ]),
+    );
+    c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    c.flush().unwrap();
+    assert_eq!(
+        c.dom().inner_html(root),
+        r#"<div class="a" data-custom="b"><input>text</div>"#
+    );
+    let text = c.dom().get_node(NodeId(2)).unwrap();
+    assert_eq!(c.dom().text_content(text), "text");
+}
+
+#[test]
+fn traverse() {
+    let (mut c, _) = render_list();
+    c.set_last_node(NodeId(1));
+    c.first_child();
+    c.store_with_id(NodeId(4));
+    c.next_sibling();
+    c.store_with_id(NodeId(5));
+    c.parent_node();
+    c.store_with_id(NodeId(6));
+    c.flush().unwrap();
+    let dom = c.dom();
+    assert_eq!(dom.get_node(NodeId(4)), dom.get_node(NodeId(2)));
+    assert_eq!(dom.get_node(NodeId(5)), dom.get_node(NodeId(3)));
+    assert_eq!(dom.get_node(NodeId(6)), dom.get_node(NodeId(1)));
+}
+
+#[test]
+fn move_nodes() {
+    let (mut c, root) = render_list();
+    c.create_element(Element::h1, None);
+    c.replace_with(MaybeId::Node(NodeId(2)), MaybeId::LastNode);
+    c.create_element(Element::a, Some(NodeId(4)));
+    c.insert_after(MaybeId::Node(NodeId(3)), MaybeId::LastNode);
+    c.create_element(Element::b, None);
+    c.insert_nodes_before(
+        MaybeId::Node(NodeId(3)),
+        &[MaybeId::Node(NodeId(4)), MaybeId::LastNode],
+    );
+    c.remove(MaybeId::Node(NodeId(3)));
+    c.flush().unwrap();
+    assert_eq!(
+        c.dom().inner_html(root),
+        "<div><h1></h1><a></a><b></b></div>"
+    );
+}
+
+#[test]
+fn fragments() {
+    let (mut c, root) = render_list();
+    c.append_fragment(
+        NodeId(1),
+        FragmentBuilder::new(&[
+            ElementBuilder::new(Element::b.into()).into(),
+            TextBuilder::new("x").into(),
+        ]),
+    );
+    c.insert_fragment_before(
+        NodeId(3),
+        FragmentBuilder::new(&[
+            ElementBuilder::new(Element::i.into()).id(NodeId(4)).into(),
+            ElementBuilder::new(Element::em.into()).into(),
+        ]),
+    );
+    c.insert_fragment_after(
+        NodeId(2),
+        FragmentBuilder::new(&[
+            ElementBuilder::new(Element::h1.into()).into(),
+            ElementBuilder::new(Element::hr.into()).into(),
+        ]),
+    );
+    c.replace_with_fragment(
+        NodeId(4),
+        FragmentBuilder::new(&[
+            TextBuilder::new("y").into(),
+            ElementBuilder::new(Element::a.into()).into(),
+        ]),
+    );
+    c.flush().unwrap();
+    assert_eq!(
+        c.dom().inner_html(root),
+        "<div><p></p><h1></h1><hr>y<a></a><em></em><span></span><b></b>x</div>"
+    );
+}
+
+#[test]
+fn mutation_writer() {
+    let (mut c, root) = channel();
+    c.create(Element::ul)
+        .id(NodeId(1))
+        .attr(Attribute::class, "items")
+        .child(|c| c.create(Element::li).text("one"))
+        .child(|c| {
+            c.create(Element::li)
+                .style("color", "red")
+                .child(|c| c.create(Element::b).text_child("two"))
+        })
+        .mount(ROOT);
+    c.flush().unwrap();
+    let before = c.dom().inner_html(root);
+    assert_eq!(
+        before,
+        r#"<ul class="items"><li>one</li><li style="color: red;"><b>two</b></li></ul>"#
+    );
+
+    // appending with the parent stack is undone like any other append
+    c.set_last_node(NodeId(1));
+    c.push_parent();
+    c.create(Element::li).text_child("three");
+    c.pop_parent();
+    let undo = c.flush_with_inverse(NodeId(100)).unwrap();
+    assert!(c.dom().inner_html(root).ends_with("<li>three</li></ul>"));
+    c.append(undo);
+    c.flush().unwrap();
+    assert_eq!(c.dom().inner_html(root), before);
+
+    c.pop_parent();
+    assert!(c.flush().is_err());
+}
+
+#[test]
+fn streamed_trees_are_shown_before_they_are_finished() {
+    let (mut c, root) = channel();
+    let mut stream = c.stream_into(ROOT).chunk_size(8);
+    stream
+        .open(ElementBuilder::new(Element::ul.into()).id(NodeId(1)))
+        .unwrap();
+    let mut items = Vec::new();
+    for i in 0..6 {
+        let text = i.to_string();
+        stream
+            .append(
+                ElementBuilder::new(Element::li.into()).children(&[TextBuilder::new(&text).into()]),
+            )
+            .unwrap();
+        items.push(format!("<li>{i}</li>"));
+    }
+    stream
+        .open(ElementBuilder::new(Element::li.into()))
+        .unwrap();
+    stream.append(TextBuilder::new("nested")).unwrap();
+    assert_eq!(stream.depth(), 2);
+    // the chunks that were flushed are in the document while the list is still open
+    assert_eq!(stream.channel().op_count(), 4);
+    assert_eq!(
+        stream.channel().dom().inner_html(root),
+        format!("<ul>{}</ul>", items.concat())
+    );
+    stream.close().unwrap();
+    stream.append(TextBuilder::new("after")).unwrap();
+    stream.finish().unwrap();
+    assert_eq!(
+        c.dom().inner_html(root),
+        format!("<ul>{}<li>nested</li>after</ul>", items.concat())
+    );
+
+    // the parent the stream started with is popped, so the last node is the root
+    c.create_element(Element::p, None);
+    c.pop_parent();
+    assert!(c.flush().is_err());
+
+    // dropping a stream closes the open elements, and appends are undone like any other append
+    let mut stream = c.stream_into(NodeId(1));
+    stream
+        .open(ElementBuilder::new(Element::li.into()))
+        .unwrap();
+    stream.append(TextBuilder::new("dropped")).unwrap();
+    drop(stream);
+    let undo = c.flush_with_inverse(NodeId(100)).unwrap();
+    assert!(c.dom().inner_html(root).ends_with("<li>dropped</li></ul>"));
+    c.append(undo);
+    c.flush().unwrap();
+    assert_eq!(
+        c.dom().inner_html(root),
+        format!("<ul>{}<li>nested</li>after</ul>", items.concat())
+    );
+}
+
+#[test]
+fn subtrees_are_stitched_after_the_subtrees_they_render_into() {
+    let (mut c, root) = channel();
+    // components are encoded on other threads and submitted in any order
+    let (item, list) = std::thread::scope(|scope| {
+        let item = scope.spawn(|| {
+            let mut item = SubtreeChannel::new(NodeId(2)).after(NodeId(1));
+            item.batch().set_text("item", MaybeId::LastNode);
+            item
+        });
+        let list = scope.spawn(|| {
+            let mut list = SubtreeChannel::new(NodeId(1)).after(ROOT);
+            list.batch().create_element("li", Some(NodeId(2)));
+            list.batch()
+                .append_child(MaybeId::Node(NodeId(1)), MaybeId::LastNode);
+            list
+        });
+        (item.join().unwrap(), list.join().unwrap())
+    });
+    c.submit_subtree(item);
+    c.submit_subtree(list);
+    let mut page = SubtreeChannel::new(ROOT);
+    page.batch().create_element("ul", Some(NodeId(1)));
+    page.batch()
+        .append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    c.submit_subtree(page);
+    c.flush().unwrap();
+    assert_eq!(c.dom().inner_html(root), "<ul><li>item</li></ul>");
+    let applied: Vec<_> = c
+        .last_subtree_stats()
+        .iter()
+        .map(|stats| (stats.root, stats.ops))
+        .collect();
+    assert_eq!(applied, [(ROOT, 2), (NodeId(1), 2), (NodeId(2), 1)]);
+
+    // subtrees that wait for each other are applied in the order they were submitted
+    c.submit_subtree(SubtreeChannel::new(NodeId(2)).after(NodeId(1)));
+    c.submit_subtree(SubtreeChannel::new(NodeId(1)).after(NodeId(2)));
+    c.flush().unwrap();
+    let applied: Vec<_> = c
+        .last_subtree_stats()
+        .iter()
+        .map(|stats| stats.root)
+        .collect();
+    assert_eq!(applied, [NodeId(2), NodeId(1)]);
+
+    c.flush().unwrap();
+    assert!(c.last_subtree_stats().is_empty());
+}
+
+#[test]
+fn mutations_macro() {
+    let (mut c, root) = render_list();
+    let count = 2;
+    mutations!(c,
+        set_text NodeId(2) => "hi";
+        set_attribute NodeId(1) => class = "list";
+        set_style NodeId(1) => "color" = "red";
+        remove_style NodeId(1) => "color";
+        remove NodeId(3);
+        append NodeId(1) => {
+            ul [NodeId(4)] (class = "items", "data-count" = count) {
+                li { "one" }
+                li { b { "count: " (count) } }
+            }
+            "text"
+        }
+        insert_before NodeId(2) => { hr {} }
+        replace NodeId(4) => { "my-element" {} }
+        remove_attribute NodeId(1) => class;
+    );
+    c.flush().unwrap();
+    assert_eq!(
+        c.dom().inner_html(root),
+        r#"<div style=""><hr><p>hi</p><my-element></my-element>text</div>"#
+    );
+
+    let (mut c, root) = channel();
+    mutations!(c,
+        append ROOT => {
+            ul (class = "items") {
+                li { "one" }
+                li { b { (count) } }
+            }
+        }
+    );
+    c.flush().unwrap();
+    assert_eq!(
+        c.dom().inner_html(root),
+        r#"<ul class="items"><li>one</li><li><b>2</b></li></ul>"#
+    );
+}
+
+#[test]
+fn keyed_nodes() {
+    let (mut c, root) = render_list();
+    c.keys_mut().insert("list", NodeId(1));
+    c.keys_mut().insert("first", NodeId(2));
+    assert_eq!(c.keys_mut().insert("list", NodeId(1)), Some(NodeId(1)));
+    c.set_text("first", c.keys().node("first"));
+    c.set_attribute(Attribute::class, "list", c.keys().node("list"));
+    c.flush().unwrap();
+    assert_eq!(
+        c.dom().inner_html(root),
+        r#"<div class="list"><p>first</p><span></span></div>"#
+    );
+
+    assert_eq!(c.keys().key_of(NodeId(2)), Some("first"));
+    c.keys_mut().insert("item", NodeId(2));
+    c.keys_mut().remove_node(NodeId(2));
+    assert_eq!(c.keys().get("first"), None);
+    assert_eq!(c.keys().iter().collect::<Vec<_>>(), [("list", NodeId(1))]);
+}
+
+#[test]
+fn sparse_ids() {
+    let (mut c, root) = channel();
+    let id = NodeId(u32::MAX);
+    c.hint_node_capacity(u32::MAX);
+    c.create_element(Element::div, Some(id));
+    c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    c.set_text("far", MaybeId::Node(id));
+    c.flush().unwrap();
+    assert_eq!(c.dom().inner_html(root), "<div>far</div>");
+    assert!(c.dom().get_node(id).is_some());
+    assert_eq!(c.dom().get_node(NodeId(u32::MAX - 1)), None);
+}
+
+#[test]
+fn optimized_flushes_apply_the_same_changes() {
+    fn queue(c: &mut MsgChannel) {
+        for i in 0..3 {
+            c.set_text(i, MaybeId::Node(NodeId(2)));
+            c.set_attribute(Attribute::class, i, MaybeId::Node(NodeId(1)));
+            c.set_style("color", "red", MaybeId::Node(NodeId(3)));
+        }
+        c.remove_style("color", MaybeId::Node(NodeId(3)));
+        c.create_element(Element::b, Some(NodeId(2)));
+        c.set_text("bold", MaybeId::Node(NodeId(2)));
+        c.append_child(MaybeId::Node(NodeId(3)), MaybeId::Node(NodeId(2)));
+    }
+    let (mut c, root) = render_list();
+    queue(&mut c);
+    c.flush().unwrap();
+    let (mut optimized, optimized_root) = render_list();
+    optimized.set_optimize(true);
+    optimized.set_profiling(true);
+    queue(&mut optimized);
+    optimized.flush().unwrap();
+    assert_eq!(
+        optimized.dom().inner_html(optimized_root),
+        c.dom().inner_html(root)
+    );
+    assert_eq!(optimized.last_flush_stats().unwrap().ops, 7);
+}
+
+#[test]
+fn set_text() {
+    let (mut c, root) = render_list();
+    c.set_text("first", MaybeId::Node(NodeId(2)));
+    c.set_text("<b>&</b>", MaybeId::Node(NodeId(3)));
+    c.flush().unwrap();
+    assert_eq!(
+        c.dom().inner_html(root),
+        "<div><p>first</p><span>&lt;b&gt;&amp;&lt;/b&gt;</span></div>"
+    );
+}
+
+#[test]
+fn attributes_and_styles() {
+    let (mut c, root) = render_list();
+    c.set_attribute(Attribute::class, "a", MaybeId::Node(NodeId(2)));
+    c.set_attribute("title", "\"quoted\"", MaybeId::Node(NodeId(2)));
+    c.set_attribute(Attribute::class, "b", MaybeId::Node(NodeId(2)));
+    c.set_attribute("data-gone", "c", MaybeId::Node(NodeId(3)));
+    c.remove_attribute("data-gone", MaybeId::Node(NodeId(3)));
+    c.set_style("color", "blue", MaybeId::Node(NodeId(3)));
+    c.set_style("margin-top", "1px", MaybeId::Node(NodeId(3)));
+    c.remove_style("color", MaybeId::Node(NodeId(3)));
+    c.flush().unwrap();
+    assert_eq!(
+        c.dom().inner_html(root),
+        r#"<div><p class="b" title="&quot;quoted&quot;"></p><span style="margin-top: 1px;"></span></div>"#
+    );
+}
+
+#[test]
+fn conditional_attributes_and_classes() {
+    let (mut c, root) = render_list();
+    c.set_attribute_if("data-on", "yes", true, MaybeId::Node(NodeId(2)));
+    c.set_attribute_if("data-off", "no", false, MaybeId::Node(NodeId(2)));
+    c.set_attribute(
+        Attribute::class,
+        "row  row selected",
+        MaybeId::Node(NodeId(2)),
+    );
+    c.toggle_class_if("selected", false, MaybeId::Node(NodeId(2)));
+    c.toggle_class_if("focused", true, MaybeId::Node(NodeId(2)));
+    // removing a class an element does not have does not add a class attribute
+    c.toggle_class_if("selected", false, MaybeId::Node(NodeId(3)));
+    c.set_attribute(Attribute::class, "a  b", MaybeId::Node(NodeId(3)));
+    // the class list is not written again if it does not change
+    c.toggle_class_if("a", true, MaybeId::Node(NodeId(3)));
+    c.flush().unwrap();
+    assert_eq!(
+        c.dom().inner_html(root),
+        r#"<div><p data-on="yes" class="row focused"></p><span class="a  b"></span></div>"#
+    );
+
+    c.toggle_class_if("two words", true, MaybeId::Node(NodeId(3)));
+    assert!(c.flush().is_err());
+}
+
+#[test]
+fn resource_hints_are_appended_to_the_head() {
+    let (mut c, head) = channel();
+    c.append_resource_hint(
+        ResourceHint::preload("/inter.woff2", "font")
+            .crossorigin(CrossOrigin::Anonymous)
+            .mime_type("font/woff2"),
+        ROOT,
+    );
+    c.append_resource_hint(ResourceHint::prefetch("/next.json"), ROOT);
+    c.append_resource_hint(ResourceHint::module_preload("/route.js"), ROOT);
+    c.flush().unwrap();
+    assert_eq!(
+        c.dom().inner_html(head),
+        concat!(
+            r#"<link rel="preload" href="/inter.woff2" as="font" crossorigin="anonymous" type="font/woff2">"#,
+            r#"<link rel="prefetch" href="/next.json">"#,
+            r#"<link rel="modulepreload" href="/route.js">"#,
+        )
+    );
+}
+
+#[test]
+fn formatted_text_is_set_to_the_raw_value_without_locale_data() {
+    let (mut c, root) = render_list();
+    c.set_formatted_number(
+        1234.5,
+        &NumberFormat::new()
+            .locale("de-DE")
+            .style(NumberStyle::Currency("EUR")),
+        MaybeId::Node(NodeId(2)),
+    );
+    c.set_formatted_date(
+        1_700_000_000_000.0,
+        &DateTimeFormat::new().date_style(DateTimeStyle::Long),
+        MaybeId::Node(NodeId(3)),
+    );
+    c.flush().unwrap();
+    assert_eq!(
+        c.dom().inner_html(root),
+        "<div><p>1234.5</p><span>1700000000000</span></div>"
+    );
+}
+
+#[test]
+fn sanitized_html_cannot_be_parsed_without_a_browser() {
+    let (mut c, root) = render_list();
+    c.set_sanitized_html("<b>bold</b>", &Allowlist::DEFAULT, MaybeId::Node(NodeId(2)));
+    assert!(c.flush().is_err());
+    assert_eq!(c.dom().inner_html(root), "<div><p></p><span></span></div>");
+}
+
+#[test]
+fn clone_node() {
+    let (mut c, root) = render_list();
+    c.clone_node(MaybeId::Node(NodeId(1)), MaybeId::Node(NodeId(4)));
+    c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    c.flush().unwrap();
+    assert_eq!(
+        c.dom().inner_html(root),
+        "<div><p></p><span></span></div><div><p></p><span></span></div>"
+    );
+    assert_ne!(c.dom().get_node(NodeId(4)), c.dom().get_node(NodeId(1)));
+}
+
+#[test]
+fn hint_node_capacity() {
+    let (mut c, root) = channel();
+    c.hint_node_capacity(100);
+    for i in 1..100 {
+        c.create_text_node(i % 10, Some(NodeId(i)));
+        c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    }
+    c.flush().unwrap();
+    assert_eq!(c.dom().inner_html(root).len(), 99);
+    assert_eq!(
+        c.dom()
+            .get_node(NodeId(42))
+            .map(|node| c.dom().text_content(node)),
+        Some("2".to_string())
+    );
+}
+
+#[test]
+fn errors_are_reported() {
+    let (mut c, _) = channel();
+    c.create_element(Element::div, None);
+    // the node 1000 was never created
+    c.append_child(MaybeId::Node(NodeId(1000)), MaybeId::LastNode);
+    let err = c.flush().unwrap_err();
+    assert_eq!(err.offset, 1);
+}
+
+#[test]
+fn profiling() {
+    let (mut c, root) = channel();
+    c.set_profiling(true);
+    c.create_element("div", None);
+    c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    c.flush().unwrap();
+    let stats = c.last_flush_stats().unwrap();
+    assert_eq!(stats.ops, 2);
+    assert_eq!(stats.str_bytes, 3);
+    assert_eq!(c.dom().inner_html(root), "<div></div>");
+}
+
+#[test]
+fn idle_batches_yield_and_resume() {
+    let (mut c, root) = channel();
+    let mut batch = Batch::default();
+    for i in 0..16 {
+        batch.create_text_node(i, None);
+        batch.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    }
+    c.append_idle(batch);
+    c.create_element("p", None);
+    // an exhausted budget still applies the first four operations
+    assert!(!c.run_idle(0.0).unwrap());
+    assert_eq!(c.dom().inner_html(root), "01");
+    assert!(c.has_idle_work());
+    // idle batches do not change the last node of the channel
+    c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    c.flush().unwrap();
+    assert!(c.run_idle(f64::INFINITY).unwrap());
+    assert!(!c.has_idle_work());
+    assert_eq!(c.dom().inner_html(root), "01<p></p>23456789101112131415");
+}
+
+#[test]
+fn idle_errors_skip_the_batch() {
+    let (mut c, root) = channel();
+    let mut batch = Batch::default();
+    batch.create_element(Element::div, None);
+    // the node 1000 was never created
+    batch.append_child(MaybeId::Node(NodeId(1000)), MaybeId::LastNode);
+    c.append_idle(batch);
+    let mut batch = Batch::default();
+    batch.create_element(Element::span, None);
+    batch.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    c.append_idle(batch);
+    assert_eq!(c.run_idle(f64::INFINITY).unwrap_err().offset, 1);
+    assert!(c.run_idle(f64::INFINITY).unwrap());
+    assert_eq!(c.dom().inner_html(root), "<span></span>");
+}
+
+#[test]
+fn idle_backlog_merges_batches() {
+    fn texts(range: std::ops::Range<u32>) -> Batch {
+        let mut batch = Batch::default();
+        for i in range {
+            batch.create_text_node(i, None);
+            batch.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+        }
+        batch
+    }
+    let (mut c, root) = channel();
+    c.set_idle_backlog_limit(2);
+    c.append_idle(texts(0..4));
+    for i in 4..7 {
+        c.append_idle(texts(i..i + 1));
+    }
+    assert_eq!(c.idle_backlog(), 2);
+    assert!(!c.run_idle(0.0).unwrap());
+    assert_eq!(c.dom().inner_html(root), "01");
+    // the batch that is being applied does not count towards the limit
+    c.append_idle(texts(7..8));
+    assert_eq!(c.idle_backlog(), 3);
+    assert!(c.run_idle(f64::INFINITY).unwrap());
+    assert_eq!(c.idle_backlog(), 0);
+    assert_eq!(c.dom().inner_html(root), "01234567");
+}
+
+#[test]
+fn deferred_operations_flush_after_urgent_ones() {
+    let (c, root) = channel();
+    let scheduler = FrameScheduler::new(c);
+    scheduler.queue_deferred(|b| {
+        b.create_element(Element::span, None);
+        b.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    });
+    scheduler.queue(|c| {
+        c.create_element("p", None);
+        c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    });
+    assert!(scheduler.has_deferred());
+    scheduler.flush_now().unwrap();
+    assert!(!scheduler.has_deferred());
+    scheduler.with_channel(|c| assert_eq!(c.dom().inner_html(root), "<p></p><span></span>"));
+}
+
+#[test]
+fn applied_futures_resolve_after_the_flush() {
+    struct Flag(AtomicBool);
+    impl Wake for Flag {
+        fn wake(self: Arc<Self>) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+    let flag = Arc::new(Flag(AtomicBool::new(false)));
+    let waker = Waker::from(flag.clone());
+    let mut cx = Context::from_waker(&waker);
+
+    let (c, root) = channel();
+    let scheduler = FrameScheduler::new(c);
+    // nothing is queued, so there is nothing to wait for
+    assert!(pin!(scheduler.applied()).poll(&mut cx).is_ready());
+
+    scheduler.queue_deferred(|b| b.create_element(Element::span, None));
+    let mut applied = pin!(scheduler.applied());
+    assert!(applied.as_mut().poll(&mut cx).is_pending());
+    scheduler.flush_now().unwrap();
+    assert!(flag.0.load(Ordering::SeqCst));
+    assert!(applied.poll(&mut cx).is_ready());
+    assert_eq!(scheduler.flush_count(), 2);
+
+    // the future resolves if the scheduler is dropped before the flush
+    scheduler.queue(|c| c.create_element("p", None));
+    let mut applied = pin!(scheduler.applied());
+    assert!(applied.as_mut().poll(&mut cx).is_pending());
+    scheduler.with_channel(|c| assert_eq!(c.dom().inner_html(root), ""));
+    drop(scheduler);
+    assert!(applied.poll(&mut cx).is_ready());
+}
+
+#[test]
+fn inverse_batches_undo_and_redo() {
+    let (mut c, root) = render_list();
+    c.set_attribute(Attribute::class, "list", MaybeId::Node(NodeId(1)));
+    c.set_style("color", "red", MaybeId::Node(NodeId(2)));
+    c.set_text("hello", MaybeId::Node(NodeId(3)));
+    c.flush().unwrap();
+    let before = c.dom().inner_html(root);
+
+    c.set_attribute(Attribute::class, "grid", MaybeId::Node(NodeId(1)));
+    c.toggle_class_if("wide", true, MaybeId::Node(NodeId(1)));
+    c.set_attribute(Attribute::title, "new", MaybeId::Node(NodeId(1)));
+    c.remove_style("color", MaybeId::Node(NodeId(2)));
+    c.set_style("margin", "0px", MaybeId::Node(NodeId(2)));
+    c.set_text("world", MaybeId::Node(NodeId(3)));
+    // move the span before the p and add a new element
+    c.insert_before(MaybeId::Node(NodeId(2)), MaybeId::Node(NodeId(3)));
+    c.create_element("b", Some(NodeId(4)));
+    c.append_child(MaybeId::Node(NodeId(1)), MaybeId::LastNode);
+    c.remove(MaybeId::Node(NodeId(2)));
+    let undo = c.flush_with_inverse(NodeId(100)).unwrap();
+    let after = c.dom().inner_html(root);
+    assert_eq!(
+        after,
+        r#"<div class="grid wide" title="new"><span>world</span><b></b></div>"#
+    );
+
+    c.append(undo);
+    let redo = c.flush_with_inverse(NodeId(100)).unwrap();
+    assert_eq!(c.dom().inner_html(root), before);
+    // the restored p is stored with its old id again
+    c.set_text("restored", MaybeId::Node(NodeId(2)));
+    c.flush().unwrap();
+    assert_eq!(
+        c.dom().inner_html(root),
+        r#"<div class="list"><p style="color: red;">restored</p><span>hello</span></div>"#
+    );
+    c.set_text("", MaybeId::Node(NodeId(2)));
+    c.flush().unwrap();
+
+    c.append(redo);
+    c.flush().unwrap();
+    assert_eq!(c.dom().inner_html(root), after);
+}
+
+#[test]
+fn inverse_batches_restore_replaced_nodes() {
+    let (mut c, root) = render_list();
+    let before = c.dom().inner_html(root);
+    c.create_text_node("a", None);
+    c.replace_with(MaybeId::Node(NodeId(2)), MaybeId::LastNode);
+    c.set_text("replaced children", MaybeId::Node(NodeId(1)));
+    let undo = c.flush_with_inverse(NodeId(100)).unwrap();
+    assert_eq!(c.dom().inner_html(root), "<div>replaced children</div>");
+    c.append(undo);
+    c.flush().unwrap();
+    assert_eq!(c.dom().inner_html(root), before);
+}
+
+#[test]
+fn recordings_replay_into_a_fresh_root() {
+    let (mut c, root) = channel();
+    c.start_recording();
+    for i in 0..3 {
+        c.create_text_node(i, None);
+        c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+        c.flush().unwrap();
+    }
+    let recording = c.stop_recording().unwrap();
+    assert!(!c.is_recording());
+    assert_eq!(recording.len(), 3);
+    assert!(recording
+        .flushes()
+        .windows(2)
+        .all(|w| w[0].time_ms <= w[1].time_ms));
+
+    // the recording survives being saved as a session log
+    let recording = Recording::from_bytes(&recording.to_bytes()).unwrap();
+    let (mut fresh, fresh_root) = channel();
+    let mut replay = Replay::new(&recording);
+    replay.step_to(2, &mut fresh).unwrap();
+    assert_eq!(fresh.dom().inner_html(fresh_root), "01");
+    replay.step(&mut fresh).unwrap().unwrap();
+    assert!(replay.is_finished());
+    assert!(replay.step(&mut fresh).is_none());
+    assert_eq!(fresh.dom().inner_html(fresh_root), c.dom().inner_html(root));
+    assert!(Recording::from_bytes(&[0; 9]).is_none());
+
+    // recordings made with a different format version are rejected
+    let mut bytes = recording.to_bytes();
+    bytes[0] += 1;
+    assert!(Recording::from_bytes(&bytes).is_none());
+}
+
+#[test]
+fn restarted_interpreters_keep_the_chosen_nodes() {
+    let (mut c, root) = render_list();
+    let on_click = c.handlers().insert(|_| {});
+    c.add_listener("click", on_click, MaybeId::Node(NodeId(2)));
+    c.subscribe_animation_frames(on_click);
+    c.flush().unwrap();
+    let p = c.dom().get_node(NodeId(2)).unwrap();
+
+    c.restart_interpreter(KeepNodes::All);
+    assert_eq!(c.dom().get_node(NodeId(2)), Some(p));
+    // the listeners and subscriptions of the old interpreter do nothing
+    assert!(c.dom().listeners(p).is_empty());
+    assert!(c.dom().animation_frame_handlers().is_empty());
+    // the page is not changed
+    assert_eq!(c.dom().inner_html(root), "<div><p></p><span></span></div>");
+
+    c.keys_mut().insert("list", NodeId(1));
+    c.restart_interpreter(KeepNodes::Keyed);
+    assert!(c.dom().get_node(NodeId(1)).is_some());
+    assert_eq!(c.dom().get_node(ROOT), None);
+    assert_eq!(c.dom().get_node(NodeId(2)), None);
+    // queued operations are applied by the new interpreter
+    c.set_text("reloaded", c.keys().node("list"));
+    c.flush().unwrap();
+    assert_eq!(c.dom().inner_html(root), "<div>reloaded</div>");
+
+    c.restart_interpreter(KeepNodes::Only(&[NodeId(1), NodeId(5)]));
+    assert!(c.dom().get_node(NodeId(1)).is_some());
+    assert_eq!(c.dom().get_node(NodeId(5)), None);
+}
+
+#[test]
+fn listeners_dispatch_events_to_handlers() {
+    let (mut c, _) = render_list();
+    let calls = Rc::new(RefCell::new(Vec::new()));
+    let record = |name: &'static str| {
+        let calls = calls.clone();
+        move |event: &Event| calls.borrow_mut().push((name, event.name.clone()))
+    };
+    let on_p = c.handlers().insert(record("p"));
+    let on_div = c.handlers().insert(record("div"));
+    c.add_listener("click", on_p, MaybeId::Node(NodeId(2)));
+    // adding the same listener twice does nothing
+    c.add_listener("click", on_p, MaybeId::Node(NodeId(2)));
+    c.add_listener("click", on_div, MaybeId::Node(NodeId(1)));
+    c.add_listener("input", on_div, MaybeId::Node(NodeId(1)));
+    c.flush().unwrap();
+    let p = c.dom().get_node(NodeId(2)).unwrap();
+    assert_eq!(c.dom().listeners(p), [("click".to_string(), on_p)]);
+
+    // the event bubbles from the p to the div
+    assert!(c.dispatch_event(p, &Event::new("click", EventData::Other)));
+    assert_eq!(
+        *calls.borrow(),
+        [("p", "click".into()), ("div", "click".into())]
+    );
+
+    // stopping propagation in the p keeps the event from reaching the div
+    calls.borrow_mut().clear();
+    let calls_in_p = calls.clone();
+    assert!(c.handlers().replace(on_p, move |event| {
+        calls_in_p.borrow_mut().push(("p", event.name.clone()));
+        event.stop_propagation();
+        event.prevent_default();
+    }));
+    assert!(!c.dispatch_event(p, &Event::new("click", EventData::Other)));
+    assert_eq!(*calls.borrow(), [("p", "click".into())]);
+
+    // removed listeners and handlers are not called
+    calls.borrow_mut().clear();
+    c.remove_listener("click", on_p, MaybeId::Node(NodeId(2)));
+    c.flush().unwrap();
+    assert!(c.dom().listeners(p).is_empty());
+    assert!(c.handlers().remove(on_div));
+    assert!(!c.handlers().contains(on_div));
+    assert!(c.dispatch_event(p, &Event::new("click", EventData::Other)));
+    assert!(calls.borrow().is_empty());
+    assert_eq!(c.handlers().len(), 1);
+    // the handle of the removed handler is reused
+    assert_eq!(c.handlers().insert(|_| {}), on_div);
+}
+
+#[test]
+fn coalesced_listeners_dispatch_every_event() {
+    let (mut c, _) = render_list();
+    let calls = Rc::new(RefCell::new(0));
+    let calls_in_handler = calls.clone();
+    let handler = c
+        .handlers()
+        .insert(move |_| *calls_in_handler.borrow_mut() += 1);
+    c.add_coalesced_listener("scroll", handler, MaybeId::Node(NodeId(1)));
+    c.flush().unwrap();
+    let div = c.dom().get_node(NodeId(1)).unwrap();
+    assert_eq!(c.dom().listeners(div), [("scroll".to_string(), handler)]);
+    c.dispatch_event(div, &Event::new("scroll", EventData::Other));
+    c.dispatch_event(div, &Event::new("scroll", EventData::Other));
+    assert_eq!(*calls.borrow(), 2);
+
+    c.remove_listener("scroll", handler, MaybeId::Node(NodeId(1)));
+    c.flush().unwrap();
+    assert!(c.dom().listeners(div).is_empty());
+}
+
+#[test]
+fn animation_frames_are_dispatched_to_subscribed_handlers() {
+    let (mut c, _) = channel();
+    let frames = Rc::new(RefCell::new(Vec::new()));
+    let record = |name: &'static str| {
+        let frames = frames.clone();
+        move |event: &Event| {
+            if let EventData::AnimationFrame(frame) = &event.data {
+                frames.borrow_mut().push((name, frame.timestamp));
+            }
+        }
+    };
+    let first = c.handlers().insert(record("first"));
+    let second = c.handlers().insert(record("second"));
+    c.subscribe_animation_frames(first);
+    c.subscribe_animation_frames(second);
+    // subscribing twice does nothing
+    c.subscribe_animation_frames(first);
+    c.flush().unwrap();
+    assert_eq!(c.dom().animation_frame_handlers(), [first, second]);
+    c.animation_frame(16.0);
+    assert_eq!(*frames.borrow(), [("first", 16.0), ("second", 16.0)]);
+
+    c.unsubscribe_animation_frames(first);
+    let undo = c.flush_with_inverse(NodeId(100)).unwrap();
+    c.animation_frame(32.0);
+    assert_eq!(frames.borrow().last(), Some(&("second", 32.0)));
+    assert_eq!(frames.borrow().len(), 3);
+    c.append(undo);
+    c.flush().unwrap();
+    assert_eq!(c.dom().animation_frame_handlers(), [second, first]);
+}
+
+#[test]
+fn view_transitions_apply_in_place_and_finish_with_an_event() {
+    let (mut c, root) = render_list();
+    let ended = Rc::new(RefCell::new(Vec::new()));
+    let done = c.handlers().insert({
+        let ended = ended.clone();
+        move |event| {
+            if let EventData::ViewTransition(transition) = &event.data {
+                ended.borrow_mut().push(transition.clone());
+            }
+        }
+    });
+    let value = c.with_view_transition(done, |batch| {
+        batch.set_text("new", MaybeId::Node(NodeId(2)));
+        batch.set_attribute(Attribute::class, "page", MaybeId::Node(NodeId(1)));
+        2
+    });
+    assert_eq!(value, 2);
+    let undo = c.flush_with_inverse(NodeId(100)).unwrap();
+    assert_eq!(
+        c.dom().inner_html(root),
+        r#"<div class="page"><p>new</p><span></span></div>"#
+    );
+    // the event is dispatched when the transition is finished
+    assert!(ended.borrow().is_empty());
+    c.finish_view_transitions();
+    assert_eq!(
+        *ended.borrow(),
+        [ViewTransitionData {
+            animated: false,
+            error: None,
+        }]
+    );
+
+    // the inverse is a transition back to the old page
+    c.append(undo);
+    c.flush().unwrap();
+    assert_eq!(c.dom().inner_html(root), "<div><p></p><span></span></div>");
+
+    // errors in the transition are reported to the handler instead of failing the flush
+    c.with_view_transition(done, |batch| {
+        batch.set_text("lost", MaybeId::Node(NodeId(50)));
+    });
+    c.flush().unwrap();
+    c.finish_view_transitions();
+    assert_eq!(ended.borrow().len(), 3);
+    assert!(ended.borrow()[2].error.is_some());
+}
+
+#[test]
+fn view_transitions_keep_the_last_node() {
+    let (mut c, root) = render_list();
+    let done = c.handlers().insert(|_| {});
+    c.create_element(Element::b, None);
+    c.with_view_transition(done, |batch| {
+        batch.create_element(Element::i, None);
+        batch.append_child(MaybeId::Node(NodeId(2)), MaybeId::LastNode);
+    });
+    c.flush().unwrap();
+    c.finish_view_transitions();
+    // the next batch still appends the element created before the transition
+    c.append_child(MaybeId::Node(NodeId(3)), MaybeId::LastNode);
+    c.flush().unwrap();
+    assert_eq!(
+        c.dom().inner_html(root),
+        "<div><p><i></i></p><span><b></b></span></div>"
+    );
+}
+
+#[test]
+fn handlers_can_be_removed_while_they_run() {
+    let (c, _) = channel();
+    let handlers = c.handlers().clone();
+    let id = Rc::new(RefCell::new(None));
+    let id_in_handler = id.clone();
+    let handler = c.handlers().insert(move |_| {
+        handlers.remove(id_in_handler.borrow().unwrap());
+    });
+    *id.borrow_mut() = Some(handler);
+    let event = Event::new("click", EventData::Other);
+    assert!(c.handlers().dispatch(handler, &event));
+    assert!(!c.handlers().contains(handler));
+    assert!(!c.handlers().dispatch(handler, &event));
+}
+
+#[test]
+fn inverse_batches_undo_listeners() {
+    let (mut c, _) = render_list();
+    let handler = c.handlers().insert(|_| {});
+    c.add_listener("click", handler, MaybeId::Node(NodeId(2)));
+    let undo = c.flush_with_inverse(NodeId(100)).unwrap();
+    let p = c.dom().get_node(NodeId(2)).unwrap();
+    assert_eq!(c.dom().listeners(p).len(), 1);
+    c.append(undo);
+    c.flush().unwrap();
+    assert!(c.dom().listeners(p).is_empty());
+}
+
+#[test]
+fn events_are_decoded_from_the_interpreter_layout() {
+    let mut payload = [0; 24];
+    payload[0] = 1;
+    payload[1] = 2 | 8;
+    payload[2..4].copy_from_slice(&5u16.to_le_bytes());
+    payload[4..6].copy_from_slice(&2i16.to_le_bytes());
+    payload[6..8].copy_from_slice(&4u16.to_le_bytes());
+    payload[8..16].copy_from_slice(&1.5f64.to_le_bytes());
+    payload[16..24].copy_from_slice(&(-3.0f64).to_le_bytes());
+    let event = Event::decode(&payload, "click").unwrap();
+    assert_eq!(event.name, "click");
+    assert!(event.modifiers.ctrl && event.modifiers.shift && !event.modifiers.alt);
+    assert_eq!(
+        event.data,
+        EventData::Mouse(MouseData {
+            client_x: 1.5,
+            client_y: -3.0,
+            button: 2,
+            buttons: 4,
+        })
+    );
+
+    // the lengths of strings are counted in utf-16 code units
+    let mut payload = [0; 24];
+    payload[0] = 2;
+    payload[2..4].copy_from_slice(&7u16.to_le_bytes());
+    payload[4] = 1;
+    payload[6..8].copy_from_slice(&2u16.to_le_bytes());
+    let event = Event::decode(&payload, "keydown😀KeyA").unwrap();
+    assert_eq!(
+        event.data,
+        EventData::Keyboard(KeyboardData {
+            key: "😀".into(),
+            code: "KeyA".into(),
+            repeat: true,
+            location: 0,
+        })
+    );
+
+    assert!(Event::decode(&payload, "key").is_none());
+    assert!(Event::decode(&payload[..8], "keydown😀KeyA").is_none());
+
+    let mut payload = [0; 24];
+    payload[0] = 4;
+    payload[2..4].copy_from_slice(&6u16.to_le_bytes());
+    payload[4] = 1;
+    let event = Event::decode(&payload, "change(prefers-color-scheme: dark)").unwrap();
+    assert_eq!(
+        event.data,
+        EventData::MediaQuery(MediaQueryData {
+            media: "(prefers-color-scheme: dark)".into(),
+            matches: true,
+        })
+    );
+
+    let mut payload = [0; 24];
+    payload[0] = 5;
+    payload[2..4].copy_from_slice(&16u16.to_le_bytes());
+    payload[4..8].copy_from_slice(&7u32.to_le_bytes());
+    payload[8] = 1 | 4;
+    payload[10..12].copy_from_slice(&4u16.to_le_bytes());
+    let event = Event::decode(&payload, "attributechangedsizelarge").unwrap();
+    assert_eq!(
+        event.data,
+        EventData::Lifecycle(LifecycleData {
+            node: NodeId(7),
+            attribute: Some(AttributeChange {
+                name: "size".into(),
+                old_value: None,
+                new_value: Some("large".into()),
+            }),
+        })
+    );
+
+    let mut payload = [0; 24];
+    payload[0] = 6;
+    payload[2..4].copy_from_slice(&6u16.to_le_bytes());
+    payload[5] = 1;
+    let event = Event::decode(&payload, "toggle").unwrap();
+    assert_eq!(
+        event.data,
+        EventData::Toggle(ToggleData {
+            was_open: false,
+            open: true,
+        })
+    );
+
+    let mut payload = [0; 24];
+    payload[0] = 7;
+    payload[2..4].copy_from_slice(&5u16.to_le_bytes());
+    let event = Event::decode(&payload, "closeconfirm").unwrap();
+    assert_eq!(
+        event.data,
+        EventData::DialogClose(DialogCloseData {
+            return_value: "confirm".into(),
+        })
+    );
+
+    let mut payload = [0; 24];
+    payload[0] = 8;
+    payload[2..4].copy_from_slice(&16u16.to_le_bytes());
+    payload[4] = 1;
+    let event = Event::decode(&payload, "fullscreenchange").unwrap();
+    assert_eq!(event.data, EventData::Lock(LockData { active: true }));
+
+    let mut payload = [0; 48];
+    payload[0] = 9;
+    payload[2..4].copy_from_slice(&11u16.to_le_bytes());
+    payload[8..16].copy_from_slice(&4.0f64.to_le_bytes());
+    payload[24..28].copy_from_slice(&3i32.to_le_bytes());
+    payload[28..32].copy_from_slice(&0.5f32.to_le_bytes());
+    payload[32..36].copy_from_slice(&2.0f32.to_le_bytes());
+    payload[36..40].copy_from_slice(&1.0f32.to_le_bytes());
+    payload[40] = 1;
+    payload[41] = 1;
+    let event = Event::decode(&payload, "pointerdown").unwrap();
+    assert_eq!(
+        event.data,
+        EventData::Pointer(PointerData {
+            mouse: MouseData {
+                client_x: 4.0,
+                ..Default::default()
+            },
+            pointer_id: 3,
+            pointer_type: PointerType::Pen,
+            pressure: 0.5,
+            width: 2.0,
+            height: 1.0,
+            is_primary: true,
+        })
+    );
+    assert!(Event::decode(&payload[..40], "pointerdown").is_none());
+
+    // touches are followed by the changed touches, 16 bytes each
+    let mut payload = [0; 24 + 3 * 16];
+    payload[0] = 10;
+    payload[2..4].copy_from_slice(&9u16.to_le_bytes());
+    payload[4] = 2;
+    payload[5] = 1;
+    for (i, point) in payload[24..].chunks_exact_mut(16).enumerate() {
+        point[0..4].copy_from_slice(&(i as i32).to_le_bytes());
+        point[4..8].copy_from_slice(&(i as f32 * 10.0).to_le_bytes());
+    }
+    let event = Event::decode(&payload, "touchmove").unwrap();
+    let point = |identifier: i32| TouchPoint {
+        identifier,
+        client_x: identifier as f64 * 10.0,
+        ..Default::default()
+    };
+    assert_eq!(
+        event.data,
+        EventData::Touch(TouchData {
+            touches: vec![point(0), point(1)],
+            changed: vec![point(2)],
+        })
+    );
+    assert!(Event::decode(&payload[..56], "touchmove").is_none());
+    payload[0] = 1;
+    assert!(Event::decode(&payload, "touchmove").is_none());
+
+    let mut payload = [0; 24];
+    payload[0] = 11;
+    payload[2..4].copy_from_slice(&17u16.to_le_bytes());
+    let event = Event::decode(&payload, "compositionupdate日本").unwrap();
+    assert_eq!(
+        event.data,
+        EventData::Composition(CompositionData {
+            data: "日本".into(),
+        })
+    );
+
+    let mut payload = [0; 24];
+    payload[0] = 12;
+    payload[2..4].copy_from_slice(&14u16.to_le_bytes());
+    payload[8..16].copy_from_slice(&16.5f64.to_le_bytes());
+    let event = Event::decode(&payload, "animationframe").unwrap();
+    assert_eq!(
+        event.data,
+        EventData::AnimationFrame(FrameData { timestamp: 16.5 })
+    );
+
+    let mut payload = [0; 40];
+    payload[0] = 13;
+    payload[2..4].copy_from_slice(&12u16.to_le_bytes());
+    payload[4..8].copy_from_slice(&NodeId(3).0.to_le_bytes());
+    payload[8..16].copy_from_slice(&150.5f64.to_le_bytes());
+    payload[16..24].copy_from_slice(&100.0f64.to_le_bytes());
+    payload[24..32].copy_from_slice(&2.0f64.to_le_bytes());
+    payload[32..36].copy_from_slice(&301u32.to_le_bytes());
+    payload[36..40].copy_from_slice(&200u32.to_le_bytes());
+    let event = Event::decode(&payload, "canvasresize").unwrap();
+    assert_eq!(
+        event.data,
+        EventData::CanvasSize(CanvasSizeData {
+            node: NodeId(3),
+            width: 150.5,
+            height: 100.0,
+            device_pixel_ratio: 2.0,
+            pixel_width: 301,
+            pixel_height: 200,
+        })
+    );
+    assert!(Event::decode(&payload[..32], "canvasresize").is_none());
+
+    // the error of a transition follows the type
+    let mut payload = [0; 24];
+    payload[0] = 14;
+    payload[2..4].copy_from_slice(&17u16.to_le_bytes());
+    payload[5] = 1;
+    let event = Event::decode(&payload, "viewtransitionendAbortError").unwrap();
+    assert_eq!(
+        event.data,
+        EventData::ViewTransition(ViewTransitionData {
+            animated: false,
+            error: Some("AbortError".into()),
+        })
+    );
+}
+
+#[test]
+fn fullscreen_and_pointer_lock_are_tracked() {
+    let (mut c, _) = render_list();
+    let handler = c.handlers().insert(|_| {});
+    c.request_fullscreen(MaybeId::Node(NodeId(1)), handler);
+    c.request_pointer_lock(MaybeId::Node(NodeId(2)), handler);
+    c.flush().unwrap();
+    let (div, p) = (
+        c.dom().get_node(NodeId(1)).unwrap(),
+        c.dom().get_node(NodeId(2)).unwrap(),
+    );
+    assert_eq!(c.dom().fullscreen_element(), Some(div));
+    assert_eq!(c.dom().pointer_lock_element(), Some(p));
+
+    // fullscreen is not part of the dom, so the inverse batch does not exit it
+    c.exit_pointer_lock();
+    let undo = c.flush_with_inverse(NodeId(100)).unwrap();
+    assert_eq!(c.dom().pointer_lock_element(), None);
+    c.append(undo);
+    c.flush().unwrap();
+    assert_eq!(c.dom().pointer_lock_element(), None);
+    c.exit_fullscreen();
+    c.flush().unwrap();
+    assert_eq!(c.dom().fullscreen_element(), None);
+
+    // only elements can be shown in fullscreen
+    c.create_text_node("text", Some(NodeId(4)));
+    c.request_fullscreen(MaybeId::Node(NodeId(4)), handler);
+    assert!(c.flush().is_err());
+}
+
+#[test]
+fn canvases_are_resized_to_the_device_pixel_ratio() {
+    let (mut c, root) = channel();
+    let sizes = Rc::new(RefCell::new(Vec::new()));
+    let handler = c.handlers().insert({
+        let sizes = sizes.clone();
+        move |event: &Event| {
+            if let EventData::CanvasSize(size) = &event.data {
+                sizes.borrow_mut().push(*size);
+            }
+        }
+    });
+    c.create_element("canvas", Some(NodeId(1)));
+    c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    c.observe_canvas_size(MaybeId::LastNode, handler);
+    c.flush().unwrap();
+    let canvas = c.dom().get_node(NodeId(1)).unwrap();
+    assert_eq!(
+        c.dom().observed_canvases().collect::<Vec<_>>(),
+        [(canvas, handler)]
+    );
+
+    c.resize_canvas(canvas, 150.5, 100.0, 2.0);
+    assert_eq!(
+        c.dom().inner_html(root),
+        r#"<canvas width="301" height="200"></canvas>"#
+    );
+    assert_eq!(
+        *sizes.borrow(),
+        [CanvasSizeData {
+            node: NodeId(1),
+            width: 150.5,
+            height: 100.0,
+            device_pixel_ratio: 2.0,
+            pixel_width: 301,
+            pixel_height: 200,
+        }]
+    );
+
+    c.unobserve_canvas_size(MaybeId::Node(NodeId(1)));
+    let undo = c.flush_with_inverse(NodeId(100)).unwrap();
+    c.resize_canvas(canvas, 10.0, 10.0, 1.0);
+    assert_eq!(sizes.borrow().len(), 1);
+    c.append(undo);
+    c.flush().unwrap();
+    assert_eq!(c.dom().observed_canvases().count(), 1);
+
+    // only canvases stored with an id can be observed
+    c.create_element(Element::div, Some(NodeId(2)));
+    c.observe_canvas_size(MaybeId::LastNode, handler);
+    assert!(c.flush().is_err());
+    c.create_element("canvas", None);
+    c.observe_canvas_size(MaybeId::LastNode, handler);
+    assert!(c.flush().is_err());
+}
+
+#[test]
+fn dialogs_and_popovers_open_and_close() {
+    let (mut c, root) = channel();
+    c.create_element("dialog", Some(NodeId(1)));
+    c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    c.create_element(Element::div, Some(NodeId(2)));
+    c.set_attribute("popover", "", MaybeId::LastNode);
+    c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    c.flush().unwrap();
+
+    c.show_modal(MaybeId::Node(NodeId(1)));
+    c.show_popover(MaybeId::Node(NodeId(2)));
+    let undo = c.flush_with_inverse(NodeId(100)).unwrap();
+    assert_eq!(
+        c.dom().inner_html(root),
+        r#"<dialog open=""></dialog><div popover=""></div>"#
+    );
+    let popover = c.dom().get_node(NodeId(2)).unwrap();
+    assert!(c.dom().popover_open(popover));
+    c.append(undo);
+    c.flush().unwrap();
+    assert_eq!(
+        c.dom().inner_html(root),
+        r#"<dialog></dialog><div popover=""></div>"#
+    );
+    assert!(!c.dom().popover_open(popover));
+
+    // closing a closed dialog does nothing
+    c.close_dialog(MaybeId::Node(NodeId(1)));
+    c.flush().unwrap();
+    assert_eq!(
+        c.dom().inner_html(root),
+        r#"<dialog></dialog><div popover=""></div>"#
+    );
+
+    // only dialogs can be shown as a modal, and only popovers can be shown as a popover
+    c.show_modal(MaybeId::Node(NodeId(2)));
+    assert!(c.flush().is_err());
+    c.show_popover(MaybeId::Node(NodeId(1)));
+    assert!(c.flush().is_err());
+}
+
+#[test]
+fn observed_custom_elements_are_recorded() {
+    let (mut c, _) = channel();
+    let first = c.handlers().insert(|_| {});
+    let second = c.handlers().insert(|_| {});
+    c.observe_custom_element("my-widget", first);
+    // observing the same tag with the same handler twice does nothing
+    c.observe_custom_element("my-widget", first);
+    c.observe_custom_element("my-widget", second);
+    c.flush().unwrap();
+    assert_eq!(
+        c.dom().observed_custom_elements(),
+        [
+            ("my-widget".to_string(), first),
+            ("my-widget".to_string(), second)
+        ]
+    );
+
+    c.unobserve_custom_element("my-widget", first);
+    let undo = c.flush_with_inverse(NodeId(100)).unwrap();
+    assert_eq!(
+        c.dom().observed_custom_elements(),
+        [("my-widget".to_string(), second)]
+    );
+    c.append(undo);
+    c.flush().unwrap();
+    assert_eq!(c.dom().observed_custom_elements().len(), 2);
+}
+
+#[test]
+fn media_queries_dispatch_changes_to_subscribed_handlers() {
+    const DARK: &str = "(prefers-color-scheme: dark)";
+    let (mut c, _) = channel();
+    let calls = Rc::new(RefCell::new(Vec::new()));
+    let record = |name: &'static str| {
+        let calls = calls.clone();
+        move |event: &Event| {
+            if let EventData::MediaQuery(query) = &event.data {
+                calls.borrow_mut().push((name, query.matches));
+            }
+        }
+    };
+    let first = c.handlers().insert(record("first"));
+    let second = c.handlers().insert(record("second"));
+    c.subscribe_media_query(DARK, first);
+    // subscribing the same handler twice does nothing
+    c.subscribe_media_query(DARK, first);
+    c.subscribe_media_query("(min-width: 600px)", second);
+    c.flush().unwrap();
+    assert_eq!(
+        c.dom().media_queries().collect::<Vec<_>>(),
+        [(DARK, first), ("(min-width: 600px)", second)]
+    );
+
+    c.set_media_matches(DARK, true);
+    assert_eq!(*calls.borrow(), [("first", true)]);
+    // handlers only receive changes
+    c.set_media_matches(DARK, true);
+    assert_eq!(calls.borrow().len(), 1);
+
+    // a new subscriber receives the state even if it did not change
+    calls.borrow_mut().clear();
+    c.subscribe_media_query(DARK, second);
+    c.flush().unwrap();
+    c.set_media_matches(DARK, true);
+    assert_eq!(*calls.borrow(), [("second", true)]);
+
+    // unsubscribed handlers are not called, and the unsubscribe can be undone
+    calls.borrow_mut().clear();
+    c.unsubscribe_media_query(DARK, first);
+    let undo = c.flush_with_inverse(NodeId(100)).unwrap();
+    c.set_media_matches(DARK, false);
+    assert_eq!(*calls.borrow(), [("second", false)]);
+    c.append(undo);
+    c.flush().unwrap();
+    assert_eq!(c.dom().media_queries().count(), 3);
+}
+
+/// An input event for a user edit
+fn edit(value: &str, composing: bool) -> Event {
+    Event::new(
+        "input",
+        EventData::Input(InputData {
+            value: value.to_string(),
+            composing,
+            ..Default::default()
+        }),
+    )
+}
+
+#[test]
+fn controlled_inputs_reconcile_edits() {
+    let (mut c, _) = channel();
+    c.create_element("input", Some(NodeId(1)));
+    c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    let input = c.controlled_input(NodeId(1), "1", |_, edited| {
+        if edited.chars().all(|c| c.is_ascii_digit()) {
+            Reconcile::Accept
+        } else if edited.chars().all(|c| c.is_ascii_alphanumeric()) {
+            Reconcile::Replace(edited.chars().filter(char::is_ascii_digit).collect())
+        } else {
+            Reconcile::Reject
+        }
+    });
+    c.flush().unwrap();
+    let node = c.dom().get_node(NodeId(1)).unwrap();
+    assert_eq!(c.dom().value(node), Some("1"));
+
+    c.dispatch_event(node, &edit("12", false));
+    assert_eq!(c.dom().value(node), Some("12"));
+    c.dispatch_event(node, &edit("1a2", false));
+    assert_eq!(c.dom().value(node), Some("12"));
+    c.dispatch_event(node, &edit("12 3", false));
+    assert_eq!(c.dom().value(node), Some("12"));
+    assert_eq!(input.value(), "12");
+
+    // edits are not reconciled until the composition ends
+    c.dispatch_event(node, &edit("12に", true));
+    assert_eq!(c.dom().value(node), Some("12に"));
+    assert_eq!(input.value(), "12");
+    c.dispatch_event(node, &edit("12に", false));
+    assert_eq!(c.dom().value(node), Some("12"));
+
+    input.set_value(&mut c, "7");
+    c.flush().unwrap();
+    assert_eq!(c.dom().value(node), Some("7"));
+    assert_eq!(input.value(), "7");
+
+    input.release(&mut c);
+    c.flush().unwrap();
+    assert!(c.dom().listeners(node).is_empty());
+    c.dispatch_event(node, &edit("x", false));
+    assert_eq!(c.dom().value(node), Some("x"));
+}
+
+#[test]
+fn inverse_batches_restore_values() {
+    let (mut c, _) = render_list();
+    c.create_element("input", Some(NodeId(4)));
+    c.set_attribute(Attribute::value, "initial", MaybeId::LastNode);
+    c.append_child(MaybeId::Node(NodeId(1)), MaybeId::LastNode);
+    c.flush().unwrap();
+    c.set_value("changed", MaybeId::Node(NodeId(4)));
+    let undo = c.flush_with_inverse(NodeId(100)).unwrap();
+    let input = c.dom().get_node(NodeId(4)).unwrap();
+    assert_eq!(c.dom().value(input), Some("changed"));
+    c.append(undo);
+    c.flush().unwrap();
+    assert_eq!(c.dom().value(input), Some("initial"));
+}
+
+#[test]
+fn focus_and_visibility_queries_are_answered() {
+    let (mut c, _) = channel();
+    c.create_element(Element::input, Some(NodeId(1)));
+    c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    c.create_element(Element::div, Some(NodeId(2)));
+    c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    c.create_element(Element::span, Some(NodeId(3)));
+    c.append_child(MaybeId::Node(NodeId(2)), MaybeId::LastNode);
+    c.flush().unwrap();
+
+    let mut nothing_focused = c.active_element();
+    c.flush().unwrap();
+    assert_eq!(nothing_focused.try_take(), Some(None));
+    let input = c.dom().get_node(NodeId(1)).unwrap();
+    c.dom_mut().set_active_element(Some(input));
+    let mut focused = c.active_element();
+    // the headless dom has no layout
+    let mut at_point = c.element_from_point(10, 10);
+    c.flush().unwrap();
+    assert_eq!(focused.try_take(), Some(Some(NodeId(1))));
+    assert_eq!(at_point.try_take(), Some(None));
+
+    // elements inside of a hidden element are not rendered
+    let mut before = c.check_visibility(MaybeId::Node(NodeId(3)));
+    c.set_style("display", "none", MaybeId::Node(NodeId(2)));
+    let mut display_none = c.check_visibility(MaybeId::Node(NodeId(3)));
+    c.remove_style("display", MaybeId::Node(NodeId(2)));
+    c.set_attribute("hidden", "", MaybeId::Node(NodeId(2)));
+    let mut hidden = c.check_visibility(MaybeId::Node(NodeId(3)));
+    c.flush().unwrap();
+    assert_eq!(before.try_take(), Some(true));
+    assert_eq!(display_none.try_take(), Some(false));
+    assert_eq!(hidden.try_take(), Some(false));
+
+    // only elements have a visibility
+    c.create_text_node("text", Some(NodeId(4)));
+    let _visible = c.check_visibility(MaybeId::LastNode);
+    assert!(c.flush().is_err());
+}
+
+#[test]
+fn images_are_lazy_and_decoded_without_loading() {
+    let (mut c, root) = channel();
+    c.create_element("img", Some(NodeId(1)));
+    c.set_attribute("src", "/photo.jpg", MaybeId::LastNode);
+    c.set_image_loading(true, true, MaybeId::LastNode);
+    c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    let mut decoded = c.decode_image(MaybeId::Node(NodeId(1)));
+    c.flush().unwrap();
+    assert_eq!(
+        c.dom().inner_html(root),
+        r#"<img src="/photo.jpg" loading="lazy" decoding="async">"#
+    );
+    assert_eq!(decoded.try_take(), Some(true));
+
+    c.set_image_loading(false, false, MaybeId::Node(NodeId(1)));
+    c.flush().unwrap();
+    assert_eq!(
+        c.dom().inner_html(root),
+        r#"<img src="/photo.jpg" loading="eager" decoding="auto">"#
+    );
+
+    // only images can be decoded
+    let _decoded = c.decode_image(MaybeId::Node(ROOT));
+    assert!(c.flush().is_err());
+}
+
+#[test]
+fn storage_reads_answer_queries() {
+    let (mut c, _) = channel();
+    c.dom_mut()
+        .storage_mut(StorageArea::Local)
+        .insert("theme".into(), "light".into());
+    let mut before = c.get_storage_item(StorageArea::Local, "theme");
+    c.set_storage_item(StorageArea::Local, "theme", "dark");
+    c.set_storage_item(StorageArea::Session, "panel", "open");
+    let mut after = c.get_storage_item(StorageArea::Local, "theme");
+    // the areas are separate
+    let mut missing = c.get_storage_item(StorageArea::Session, "theme");
+    assert_eq!(before.try_take(), None);
+    assert_eq!(c.queries().len(), 3);
+    c.flush().unwrap();
+    assert!(c.queries().is_empty());
+    assert_eq!(before.try_take(), Some(Some("light".into())));
+    assert_eq!(after.try_take(), Some(Some("dark".into())));
+    assert_eq!(missing.try_take(), Some(None));
+    // the value can only be taken once
+    assert_eq!(after.try_take(), None);
+
+    c.remove_storage_item(StorageArea::Session, "panel");
+    let removed = c.get_storage_item(StorageArea::Session, "panel");
+    c.flush().unwrap();
+    assert!(c.dom().storage(StorageArea::Session).is_empty());
+
+    // queries wake the task awaiting them when they are answered
+    struct Flag(AtomicBool);
+    impl Wake for Flag {
+        fn wake(self: Arc<Self>) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+    let flag = Arc::new(Flag(AtomicBool::new(false)));
+    let waker = Waker::from(flag.clone());
+    let mut cx = Context::from_waker(&waker);
+    assert_eq!(pin!(removed).poll(&mut cx), Poll::Ready(None));
+    let mut pending = pin!(c.get_storage_item(StorageArea::Local, "theme"));
+    assert!(pending.as_mut().poll(&mut cx).is_pending());
+    c.flush().unwrap();
+    assert!(flag.0.load(Ordering::SeqCst));
+    assert_eq!(pending.poll(&mut cx), Poll::Ready(Some("dark".into())));
+}
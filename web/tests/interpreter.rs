@@ -0,0 +1,1247 @@
+//! A conformance suite for the interpreter. Every test applies a batch of operations in the browser and checks the resulting DOM with web-sys.
+//!
+//! Run it with `wasm-pack test --headless --firefox web` (or `--chrome`), and with `--features debug` to include the debug only checks.
+
+#![cfg(target_arch = "wasm32")]
+
+use std::{cell::RefCell, rc::Rc};
+
+use sledgehammer::{
+    controlled::Reconcile,
+    events::{CompositionData, Event, EventData},
+    intl::{DateTimeFormat, DateTimeStyle, NumberFormat, NumberStyle},
+    mutations,
+    reload::KeepNodes,
+    sanitize::Allowlist,
+    sledgehammer_encoder::{batch::Batch, WithNsExt},
+    Attribute, Element, ElementBuilder, FragmentBuilder, MaybeId, MsgChannel, NodeId, StorageArea,
+    TextBuilder,
+};
+use wasm_bindgen::JsCast;
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+const SVG_NS: &str = "http://www.w3.org/2000/svg";
+
+/// The id of the root element each test renders into
+const ROOT: NodeId = NodeId(0);
+
+thread_local! {
+    // only one channel can exist at a time, so every test shares the same channel
+    static CHANNEL: RefCell<MsgChannel> = RefCell::new(MsgChannel::default());
+}
+
+fn document() -> web_sys::Document {
+    web_sys::window().unwrap().document().unwrap()
+}
+
+/// Create a new empty root element, append it to the body and store it with the [`ROOT`] id
+fn root() -> web_sys::Element {
+    let document = document();
+    let root = document.create_element("div").unwrap();
+    document.body().unwrap().append_child(&root).unwrap();
+    CHANNEL.with(|c| c.borrow_mut().set_node(ROOT, root.clone().into()));
+    root
+}
+
+/// Queue operations on the channel and flush them
+fn apply(f: impl FnOnce(&mut MsgChannel)) {
+    CHANNEL.with(|c| {
+        let mut channel = c.borrow_mut();
+        f(&mut channel);
+        channel.flush().unwrap();
+    });
+}
+
+fn node(id: NodeId) -> web_sys::Node {
+    CHANNEL.with(|c| c.borrow_mut().get_node(id))
+}
+
+fn element(id: NodeId) -> web_sys::Element {
+    node(id).dyn_into().unwrap()
+}
+
+/// Render `<div id=1><p id=2></p><span id=3></span></div>` into the root
+fn render_list() -> web_sys::Element {
+    let root = root();
+    apply(|c| {
+        c.build_full_element(
+            ElementBuilder::new(Element::div.into())
+                .id(NodeId(1))
+                .children(&[
+                    ElementBuilder::new(Element::p.into()).id(NodeId(2)).into(),
+                    ElementBuilder::new(Element::span.into())
+                        .id(NodeId(3))
+                        .into(),
+                ]),
+        );
+        c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    });
+    root
+}
+
+#[wasm_bindgen_test]
+fn create_element() {
+    let root = root();
+    apply(|c| {
+        c.create_element(Element::div, Some(NodeId(1)));
+        c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+        c.create_element("custom-element", None);
+        c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    });
+    assert_eq!(
+        root.inner_html(),
+        "<div></div><custom-element></custom-element>"
+    );
+    assert_eq!(element(NodeId(1)).tag_name(), "DIV");
+}
+
+#[wasm_bindgen_test]
+fn create_element_in_namespace() {
+    let root = root();
+    apply(|c| {
+        c.create_element("svg".in_namespace(SVG_NS), Some(NodeId(1)));
+        c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+        c.create_element(Element::a.in_namespace(SVG_NS), Some(NodeId(2)));
+        c.append_child(MaybeId::Node(NodeId(1)), MaybeId::LastNode);
+    });
+    assert_eq!(root.inner_html(), "<svg><a></a></svg>");
+    assert_eq!(element(NodeId(1)).namespace_uri().as_deref(), Some(SVG_NS));
+    assert_eq!(element(NodeId(2)).namespace_uri().as_deref(), Some(SVG_NS));
+}
+
+#[wasm_bindgen_test]
+fn create_text_node() {
+    let root = root();
+    apply(|c| {
+        c.create_text_node("hello ", None);
+        c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+        c.create_text_node(42u32, Some(NodeId(1)));
+        c.append_child(MaybeId::Node(ROOT), MaybeId::Node(NodeId(1)));
+    });
+    assert_eq!(root.text_content().as_deref(), Some("hello 42"));
+    assert!(node(NodeId(1)).dyn_into::<web_sys::Text>().is_ok());
+}
+
+#[wasm_bindgen_test]
+fn build_full_element() {
+    let root = root();
+    apply(|c| {
+        c.build_full_element(
+            ElementBuilder::new(Element::div.into())
+                .id(NodeId(1))
+                .attrs(&[(Attribute::class.into(), "a"), ("data-custom".into(), "b")])
+                .children(&[
+                    ElementBuilder::new(Element::p.into()).into(),
+                    TextBuilder::new("text").id(NodeId(2)).into(),
+                ]),
+        );
+        c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    });
+    assert_eq!(
+        root.inner_html(),
+        r#"<div class="a" data-custom="b"><p></p>text</div>"#
+    );
+    assert_eq!(node(NodeId(2)).text_content().as_deref(), Some("text"));
+}
+
+#[wasm_bindgen_test]
+fn build_full_element_in_namespace() {
+    let root = root();
+    apply(|c| {
+        c.build_full_element(
+            ElementBuilder::new("svg".in_namespace(SVG_NS).into())
+                .attrs(&[("width".in_namespace(SVG_NS).into(), "100%")]),
+        );
+        c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    });
+    let svg = root.first_element_child().unwrap();
+    assert_eq!(svg.namespace_uri().as_deref(), Some(SVG_NS));
+    assert_eq!(
+        svg.get_attribute_ns(Some(SVG_NS), "width").as_deref(),
+        Some("100%")
+    );
+}
+
+#[wasm_bindgen_test]
+fn traverse() {
+    render_list();
+    apply(|c| {
+        c.set_last_node(NodeId(1));
+        c.first_child();
+        c.store_with_id(NodeId(4));
+        c.next_sibling();
+        c.store_with_id(NodeId(5));
+        c.parent_node();
+        c.store_with_id(NodeId(6));
+    });
+    assert_eq!(element(NodeId(4)).tag_name(), "P");
+    assert_eq!(element(NodeId(5)).tag_name(), "SPAN");
+    assert_eq!(element(NodeId(6)).tag_name(), "DIV");
+}
+
+#[wasm_bindgen_test]
+fn append_child() {
+    let root = root();
+    apply(|c| {
+        c.create_element(Element::div, Some(NodeId(1)));
+        c.create_element(Element::p, None);
+        c.append_child(MaybeId::Node(NodeId(1)), MaybeId::LastNode);
+        c.append_child(MaybeId::Node(ROOT), MaybeId::Node(NodeId(1)));
+    });
+    assert_eq!(root.inner_html(), "<div><p></p></div>");
+}
+
+#[wasm_bindgen_test]
+fn replace_with() {
+    let root = render_list();
+    apply(|c| {
+        c.create_element(Element::h1, None);
+        c.replace_with(MaybeId::Node(NodeId(2)), MaybeId::LastNode);
+        c.create_element(Element::a, Some(NodeId(4)));
+        c.create_element(Element::b, None);
+        c.replace_with_nodes(
+            MaybeId::Node(NodeId(3)),
+            &[MaybeId::Node(NodeId(4)), MaybeId::LastNode],
+        );
+    });
+    assert_eq!(root.inner_html(), "<div><h1></h1><a></a><b></b></div>");
+}
+
+#[wasm_bindgen_test]
+fn insert_after() {
+    let root = render_list();
+    apply(|c| {
+        c.create_element(Element::h1, None);
+        c.insert_after(MaybeId::Node(NodeId(2)), MaybeId::LastNode);
+        c.create_element(Element::a, Some(NodeId(4)));
+        c.create_element(Element::b, None);
+        c.insert_nodes_after(
+            MaybeId::Node(NodeId(3)),
+            &[MaybeId::Node(NodeId(4)), MaybeId::LastNode],
+        );
+    });
+    assert_eq!(
+        root.inner_html(),
+        "<div><p></p><h1></h1><span></span><a></a><b></b></div>"
+    );
+}
+
+#[wasm_bindgen_test]
+fn insert_before() {
+    let root = render_list();
+    apply(|c| {
+        c.create_element(Element::h1, None);
+        c.insert_before(MaybeId::Node(NodeId(2)), MaybeId::LastNode);
+        c.create_element(Element::a, Some(NodeId(4)));
+        c.create_element(Element::b, None);
+        c.insert_nodes_before(
+            MaybeId::Node(NodeId(3)),
+            &[MaybeId::Node(NodeId(4)), MaybeId::LastNode],
+        );
+    });
+    assert_eq!(
+        root.inner_html(),
+        "<div><h1></h1><p></p><a></a><b></b><span></span></div>"
+    );
+}
+
+#[wasm_bindgen_test]
+fn remove() {
+    let root = render_list();
+    apply(|c| {
+        c.remove(MaybeId::Node(NodeId(2)));
+        c.set_last_node(NodeId(3));
+        c.remove(MaybeId::LastNode);
+    });
+    assert_eq!(root.inner_html(), "<div></div>");
+}
+
+#[wasm_bindgen_test]
+fn fragments() {
+    let root = render_list();
+    apply(|c| {
+        c.append_fragment(
+            NodeId(1),
+            FragmentBuilder::new(&[
+                ElementBuilder::new(Element::b.into()).into(),
+                TextBuilder::new("x").into(),
+            ]),
+        );
+        c.insert_fragment_before(
+            NodeId(3),
+            FragmentBuilder::new(&[
+                ElementBuilder::new(Element::i.into()).id(NodeId(4)).into(),
+                ElementBuilder::new(Element::em.into()).into(),
+            ]),
+        );
+        c.insert_fragment_after(
+            NodeId(2),
+            FragmentBuilder::new(&[
+                ElementBuilder::new(Element::h1.into()).into(),
+                ElementBuilder::new(Element::hr.into()).into(),
+            ]),
+        );
+        c.replace_with_fragment(
+            NodeId(4),
+            FragmentBuilder::new(&[
+                TextBuilder::new("y").into(),
+                ElementBuilder::new(Element::a.into()).into(),
+            ]),
+        );
+    });
+    assert_eq!(
+        root.inner_html(),
+        "<div><p></p><h1></h1><hr>y<a></a><em></em><span></span><b></b>x</div>"
+    );
+}
+
+#[wasm_bindgen_test]
+fn mutation_writer() {
+    let root = root();
+    apply(|c| {
+        c.create(Element::ul)
+            .id(NodeId(1))
+            .attr(Attribute::class, "items")
+            .child(|c| c.create(Element::li).text("one"))
+            .child(|c| {
+                c.create(Element::li)
+                    .style("color", "red")
+                    .child(|c| c.create(Element::b).text_child("two"))
+            })
+            .mount(ROOT);
+    });
+    assert_eq!(
+        root.inner_html(),
+        r#"<ul class="items"><li>one</li><li style="color: red;"><b>two</b></li></ul>"#
+    );
+}
+
+#[wasm_bindgen_test]
+fn mutations_macro() {
+    let root = render_list();
+    let count = 2;
+    apply(|c| {
+        mutations!(c,
+            set_text NodeId(2) => "hi";
+            set_attribute NodeId(1) => class = "list";
+            set_style NodeId(1) => "color" = "red";
+            remove_style NodeId(1) => "color";
+            remove NodeId(3);
+            append NodeId(1) => {
+                ul [NodeId(4)] (class = "items", "data-count" = count) {
+                    li { "one" }
+                    li { b { "count: " (count) } }
+                }
+                "text"
+            }
+            insert_before NodeId(2) => { hr {} }
+            replace NodeId(4) => { "my-element" {} }
+            remove_attribute NodeId(1) => class;
+        );
+    });
+    assert_eq!(
+        root.inner_html(),
+        r#"<div style=""><hr><p>hi</p><my-element></my-element>text</div>"#
+    );
+}
+
+#[wasm_bindgen_test]
+fn set_text() {
+    let root = render_list();
+    apply(|c| {
+        c.set_text("first", MaybeId::Node(NodeId(2)));
+        c.set_last_node(NodeId(3));
+        c.set_text(-7i32, MaybeId::LastNode);
+    });
+    assert_eq!(root.inner_html(), "<div><p>first</p><span>-7</span></div>");
+}
+
+#[wasm_bindgen_test]
+fn set_attribute() {
+    render_list();
+    apply(|c| {
+        c.set_attribute(Attribute::class, "a", MaybeId::Node(NodeId(2)));
+        c.set_attribute("data-custom", "b", MaybeId::Node(NodeId(2)));
+        c.set_last_node(NodeId(3));
+        c.set_attribute(
+            Attribute::title.in_namespace(SVG_NS),
+            "c",
+            MaybeId::LastNode,
+        );
+        c.set_attribute("data-ns".in_namespace(SVG_NS), "d", MaybeId::LastNode);
+    });
+    let p = element(NodeId(2));
+    assert_eq!(p.get_attribute("class").as_deref(), Some("a"));
+    assert_eq!(p.get_attribute("data-custom").as_deref(), Some("b"));
+    let span = element(NodeId(3));
+    assert_eq!(
+        span.get_attribute_ns(Some(SVG_NS), "title").as_deref(),
+        Some("c")
+    );
+    assert_eq!(
+        span.get_attribute_ns(Some(SVG_NS), "data-ns").as_deref(),
+        Some("d")
+    );
+}
+
+#[wasm_bindgen_test]
+fn remove_attribute() {
+    render_list();
+    let p = element(NodeId(2));
+    p.set_attribute("class", "a").unwrap();
+    p.set_attribute("data-custom", "b").unwrap();
+    p.set_attribute_ns(Some(SVG_NS), "title", "c").unwrap();
+    p.set_attribute_ns(Some(SVG_NS), "data-ns", "d").unwrap();
+    apply(|c| {
+        c.remove_attribute(Attribute::class, MaybeId::Node(NodeId(2)));
+        c.remove_attribute("data-custom", MaybeId::Node(NodeId(2)));
+        c.remove_attribute(
+            Attribute::title.in_namespace(SVG_NS),
+            MaybeId::Node(NodeId(2)),
+        );
+        c.remove_attribute("data-ns".in_namespace(SVG_NS), MaybeId::Node(NodeId(2)));
+    });
+    assert!(!p.has_attributes());
+}
+
+#[wasm_bindgen_test]
+fn conditional_attributes_and_classes() {
+    render_list();
+    apply(|c| {
+        c.set_attribute_if("data-on", "yes", true, MaybeId::Node(NodeId(2)));
+        c.set_attribute_if("data-off", "no", false, MaybeId::Node(NodeId(2)));
+        c.set_attribute(
+            Attribute::class,
+            "row  row selected",
+            MaybeId::Node(NodeId(2)),
+        );
+        c.toggle_class_if("selected", false, MaybeId::Node(NodeId(2)));
+        c.toggle_class_if("focused", true, MaybeId::Node(NodeId(2)));
+        c.toggle_class_if("selected", false, MaybeId::Node(NodeId(3)));
+        c.set_attribute(Attribute::class, "a  b", MaybeId::Node(NodeId(3)));
+        c.toggle_class_if("a", true, MaybeId::Node(NodeId(3)));
+    });
+    let p = element(NodeId(2));
+    assert_eq!(p.get_attribute("data-on").as_deref(), Some("yes"));
+    assert!(!p.has_attribute("data-off"));
+    assert_eq!(p.get_attribute("class").as_deref(), Some("row focused"));
+    assert_eq!(
+        element(NodeId(3)).get_attribute("class").as_deref(),
+        Some("a  b")
+    );
+}
+
+#[wasm_bindgen_test]
+fn formatted_text() {
+    render_list();
+    apply(|c| {
+        c.set_formatted_number(
+            1234.5,
+            &NumberFormat::new().locale("en-US"),
+            MaybeId::Node(NodeId(2)),
+        );
+        c.set_formatted_date(
+            0.0,
+            &DateTimeFormat::new()
+                .locale("en-US")
+                .date_style(DateTimeStyle::Long)
+                .time_zone("UTC"),
+            MaybeId::Node(NodeId(3)),
+        );
+    });
+    assert_eq!(
+        element(NodeId(2)).text_content().as_deref(),
+        Some("1,234.5")
+    );
+    assert_eq!(
+        element(NodeId(3)).text_content().as_deref(),
+        Some("January 1, 1970")
+    );
+
+    // the formatter is reused for the same format
+    apply(|c| {
+        c.set_formatted_number(
+            0.25,
+            &NumberFormat::new()
+                .locale("en-US")
+                .style(NumberStyle::Percent),
+            MaybeId::Node(NodeId(2)),
+        );
+        c.set_formatted_number(
+            1234.5,
+            &NumberFormat::new().locale("en-US"),
+            MaybeId::Node(NodeId(3)),
+        );
+    });
+    assert_eq!(element(NodeId(2)).text_content().as_deref(), Some("25%"));
+    assert_eq!(
+        element(NodeId(3)).text_content().as_deref(),
+        Some("1,234.5")
+    );
+}
+
+#[wasm_bindgen_test]
+fn sanitized_html() {
+    render_list();
+    apply(|c| {
+        c.set_sanitized_html(
+            concat!(
+                r#"<b onclick="steal()">bold</b><script>steal()</script>"#,
+                r#"<a href="javascript:steal()" title="link">link</a><img src="x" onerror="steal()">"#,
+            ),
+            &Allowlist::DEFAULT,
+            MaybeId::Node(NodeId(2)),
+        );
+        c.set_sanitized_html(
+            r#"<b>bold</b> <i class="x">italic</i>"#,
+            &Allowlist::new(&["i"], &["class"]),
+            MaybeId::Node(NodeId(3)),
+        );
+        // the allowlist does not replace the element names the interpreter creates elements with
+        c.create_element(Element::p, Some(NodeId(4)));
+    });
+    assert_eq!(element(NodeId(4)).tag_name(), "P");
+    assert_eq!(
+        element(NodeId(2)).inner_html(),
+        r#"<b>bold</b><a title="link">link</a>"#
+    );
+    assert_eq!(
+        element(NodeId(3)).inner_html(),
+        r#" <i class="x">italic</i>"#
+    );
+}
+
+#[wasm_bindgen_test]
+fn set_style() {
+    render_list();
+    apply(|c| {
+        c.set_style("color", "blue", MaybeId::Node(NodeId(2)));
+        c.set_style("margin-top", "1px", MaybeId::Node(NodeId(2)));
+        c.remove_style("margin-top", MaybeId::Node(NodeId(2)));
+    });
+    let style = element(NodeId(2))
+        .dyn_into::<web_sys::HtmlElement>()
+        .unwrap()
+        .style();
+    assert_eq!(style.get_property_value("color").unwrap(), "blue");
+    assert_eq!(style.get_property_value("margin-top").unwrap(), "");
+}
+
+#[wasm_bindgen_test]
+fn clone_node() {
+    let root = render_list();
+    apply(|c| {
+        c.clone_node(MaybeId::Node(NodeId(1)), MaybeId::Node(NodeId(4)));
+        c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    });
+    assert_eq!(
+        root.inner_html(),
+        "<div><p></p><span></span></div><div><p></p><span></span></div>"
+    );
+    assert!(!node(NodeId(4)).is_same_node(Some(&node(NodeId(1)))));
+}
+
+#[wasm_bindgen_test]
+fn streamed_trees_are_shown_before_they_are_finished() {
+    let root = root();
+    CHANNEL.with(|c| {
+        let mut c = c.borrow_mut();
+        let mut stream = c.stream_into(ROOT).chunk_size(4);
+        stream
+            .open(ElementBuilder::new(Element::ul.into()))
+            .unwrap();
+        // the list was flushed while it is still open
+        assert_eq!(root.inner_html(), "<ul></ul>");
+        stream
+            .append(ElementBuilder::new(Element::li.into()))
+            .unwrap();
+        stream
+            .open(ElementBuilder::new(Element::li.into()))
+            .unwrap();
+        stream.append(TextBuilder::new("nested")).unwrap();
+        stream.finish().unwrap();
+    });
+    assert_eq!(root.inner_html(), "<ul><li></li><li>nested</li></ul>");
+}
+
+#[wasm_bindgen_test]
+fn append_batch() {
+    let root = root();
+    let mut batch = Batch::default();
+    batch.create_element(Element::div, None);
+    batch.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    apply(|c| {
+        // appending pads the operations with no-ops
+        c.create_element(Element::p, None);
+        c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+        c.append(batch);
+    });
+    assert_eq!(root.inner_html(), "<p></p><div></div>");
+}
+
+#[wasm_bindgen_test]
+fn run_batch() {
+    let root = root();
+    let mut batch = Batch::default();
+    batch.create_element(Element::div, None);
+    batch.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    let batch = batch.finalize();
+    CHANNEL.with(|c| {
+        let mut channel = c.borrow_mut();
+        channel.run_batch(&batch).unwrap();
+        channel.run_batch(&batch).unwrap();
+    });
+    assert_eq!(root.inner_html(), "<div></div><div></div>");
+}
+
+#[wasm_bindgen_test]
+fn adopt_iframe_document() {
+    let root = root();
+    let iframe: web_sys::HtmlIFrameElement = document()
+        .create_element("iframe")
+        .unwrap()
+        .unchecked_into();
+    root.append_child(&iframe).unwrap();
+    CHANNEL.with(|c| c.borrow_mut().set_node(NodeId(1), iframe.clone().into()));
+    apply(|c| {
+        c.adopt_iframe_document(MaybeId::Node(NodeId(1)), NodeId(2));
+        c.create_element(Element::p, None);
+        c.append_child(MaybeId::Node(NodeId(2)), MaybeId::LastNode);
+    });
+    let body = iframe.content_document().unwrap().body().unwrap();
+    assert_eq!(body.inner_html(), "<p></p>");
+}
+
+#[wasm_bindgen_test]
+fn hint_node_capacity() {
+    let root = root();
+    apply(|c| {
+        c.hint_node_capacity(100);
+        for i in 1..100 {
+            c.create_text_node(i % 10, Some(NodeId(i)));
+            c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+        }
+    });
+    assert_eq!(root.inner_html().len(), 99);
+    assert_eq!(node(NodeId(42)).text_content().unwrap(), "2");
+}
+
+// only the javascript interpreter pools nodes
+#[cfg(not(feature = "web-sys-backend"))]
+#[wasm_bindgen_test]
+fn removed_nodes_are_pooled() {
+    let root = root();
+    apply(|c| {
+        c.set_pool_size("tr", 10);
+        c.create_element(Element::tr, Some(NodeId(1)));
+        c.set_attribute(Attribute::class, "row", MaybeId::LastNode);
+        c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    });
+    let removed = node(NodeId(1));
+    apply(|c| {
+        c.remove(MaybeId::Node(NodeId(1)));
+        c.create_element(Element::tr, Some(NodeId(2)));
+        c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+    });
+    // the removed row is reset and reused
+    assert!(node(NodeId(2)).is_same_node(Some(&removed)));
+    assert_eq!(root.inner_html(), "<tr></tr>");
+
+    // disabling the pool drops the pooled nodes
+    apply(|c| {
+        c.remove(MaybeId::Node(NodeId(2)));
+        c.set_pool_size("tr", 0);
+        c.create_element(Element::tr, Some(NodeId(3)));
+    });
+    assert!(!node(NodeId(3)).is_same_node(Some(&removed)));
+}
+
+#[cfg(debug_assertions)]
+#[wasm_bindgen_test]
+fn errors_are_reported() {
+    root();
+    CHANNEL.with(|c| {
+        let mut channel = c.borrow_mut();
+        channel.create_element(Element::div, None);
+        // the node 1000 was never created
+        channel.append_child(MaybeId::Node(NodeId(1000)), MaybeId::LastNode);
+        let err = channel.flush().unwrap_err();
+        assert_eq!(err.offset, 1);
+    });
+}
+
+#[cfg(feature = "debug")]
+#[wasm_bindgen_test]
+fn stale_generational_ids_are_reported() {
+    root();
+    apply(|c| c.create_element(Element::div, Some(NodeId::with_generation(1, 1))));
+    CHANNEL.with(|c| {
+        let mut channel = c.borrow_mut();
+        channel.remove(MaybeId::Node(NodeId::with_generation(1, 0)));
+        assert!(channel.flush().is_err());
+    });
+}
+
+#[wasm_bindgen_test]
+fn profiling() {
+    let root = root();
+    CHANNEL.with(|c| {
+        let mut channel = c.borrow_mut();
+        channel.set_profiling(true);
+        channel.create_element("div", None);
+        channel.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+        channel.flush().unwrap();
+        let stats = channel.last_flush_stats().unwrap();
+        channel.set_profiling(false);
+        assert_eq!(stats.ops, 2);
+        assert_eq!(stats.str_bytes, 3);
+        assert!(stats.msg_bytes > 0);
+        assert!(stats.duration_ms >= 0.0);
+        assert!(channel.last_flush_stats().is_none());
+    });
+    assert_eq!(root.inner_html(), "<div></div>");
+}
+
+#[wasm_bindgen_test]
+fn listeners_dispatch_events_to_handlers() {
+    render_list();
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let events_in_handler = events.clone();
+    let handler = CHANNEL.with(|c| {
+        c.borrow().handlers().insert(move |event: &Event| {
+            event.prevent_default();
+            events_in_handler.borrow_mut().push(event.clone());
+        })
+    });
+    apply(|c| c.add_listener("click", handler, MaybeId::Node(NodeId(1))));
+    let p: web_sys::HtmlElement = element(NodeId(2)).dyn_into().unwrap();
+    // the click bubbles from the p to the div
+    p.click();
+    {
+        let events = events.borrow();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].name, "click");
+        assert!(matches!(events[0].data, EventData::Mouse(_)));
+    }
+
+    apply(|c| c.remove_listener("click", handler, MaybeId::Node(NodeId(1))));
+    p.click();
+    assert_eq!(events.borrow().len(), 1);
+    CHANNEL.with(|c| c.borrow().handlers().remove(handler));
+}
+
+#[wasm_bindgen_test]
+fn restarted_interpreters_keep_the_nodes_and_drop_the_listeners() {
+    render_list();
+    let clicks = Rc::new(RefCell::new(0));
+    let clicks_in_handler = clicks.clone();
+    let handler = CHANNEL.with(|c| {
+        c.borrow()
+            .handlers()
+            .insert(move |_: &Event| *clicks_in_handler.borrow_mut() += 1)
+    });
+    apply(|c| c.add_listener("click", handler, MaybeId::Node(NodeId(2))));
+    let p: web_sys::HtmlElement = element(NodeId(2)).dyn_into().unwrap();
+    CHANNEL.with(|c| c.borrow_mut().restart_interpreter(KeepNodes::All));
+    assert_eq!(node(NodeId(2)), **p);
+    // the listener of the old interpreter does nothing
+    p.click();
+    assert_eq!(*clicks.borrow(), 0);
+
+    apply(|c| {
+        c.add_listener("click", handler, MaybeId::Node(NodeId(2)));
+        c.set_text("reloaded", MaybeId::Node(NodeId(2)));
+    });
+    p.click();
+    assert_eq!(*clicks.borrow(), 1);
+    assert_eq!(p.text_content().as_deref(), Some("reloaded"));
+    apply(|c| c.remove_listener("click", handler, MaybeId::Node(NodeId(2))));
+    CHANNEL.with(|c| c.borrow().handlers().remove(handler));
+}
+
+#[wasm_bindgen_test]
+async fn coalesced_listeners_dispatch_the_latest_event_once_per_frame() {
+    render_list();
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let events_in_handler = events.clone();
+    let handler = CHANNEL.with(|c| {
+        c.borrow().handlers().insert(move |event: &Event| {
+            if let EventData::Mouse(mouse) = &event.data {
+                events_in_handler.borrow_mut().push(mouse.client_x);
+            }
+        })
+    });
+    apply(|c| {
+        c.add_coalesced_listener("mousemove", handler, MaybeId::Node(NodeId(1)));
+        c.add_coalesced_listener("mousemove", handler, MaybeId::Node(NodeId(2)));
+    });
+    let move_to = |id: NodeId, x: i32| {
+        let init = web_sys::MouseEventInit::new();
+        init.set_client_x(x);
+        let event =
+            web_sys::MouseEvent::new_with_mouse_event_init_dict("mousemove", &init).unwrap();
+        element(id).dispatch_event(&event).unwrap();
+    };
+    move_to(NodeId(1), 1);
+    move_to(NodeId(1), 2);
+    move_to(NodeId(2), 3);
+    move_to(NodeId(1), 4);
+    assert!(events.borrow().is_empty());
+    animation_frame().await;
+    // each node keeps its latest event, in the order the nodes first received an event
+    assert_eq!(*events.borrow(), [4.0, 3.0]);
+
+    // removing the listener drops the pending event
+    move_to(NodeId(1), 5);
+    apply(|c| {
+        c.remove_listener("mousemove", handler, MaybeId::Node(NodeId(1)));
+        c.remove_listener("mousemove", handler, MaybeId::Node(NodeId(2)));
+    });
+    animation_frame().await;
+    assert_eq!(events.borrow().len(), 2);
+    CHANNEL.with(|c| c.borrow().handlers().remove(handler));
+}
+
+#[wasm_bindgen_test]
+async fn animation_frames_are_dispatched_until_unsubscribed() {
+    root();
+    let frames = Rc::new(RefCell::new(Vec::new()));
+    let frames_in_handler = frames.clone();
+    let handler = CHANNEL.with(|c| {
+        c.borrow().handlers().insert(move |event: &Event| {
+            if let EventData::AnimationFrame(frame) = &event.data {
+                frames_in_handler.borrow_mut().push(frame.timestamp);
+            }
+        })
+    });
+    apply(|c| c.subscribe_animation_frames(handler));
+    animation_frame().await;
+    animation_frame().await;
+    {
+        let frames = frames.borrow();
+        assert!(frames.len() >= 2);
+        assert!(frames.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    apply(|c| c.unsubscribe_animation_frames(handler));
+    let count = frames.borrow().len();
+    animation_frame().await;
+    assert_eq!(frames.borrow().len(), count);
+    CHANNEL.with(|c| c.borrow().handlers().remove(handler));
+}
+
+#[wasm_bindgen_test]
+async fn images_are_decoded_after_the_batch() {
+    let mut decoded = None;
+    let mut broken = None;
+    apply(|c| {
+        c.create_element("img", Some(NodeId(1)));
+        // a transparent 1x1 gif
+        c.set_attribute(
+            "src",
+            "data:image/gif;base64,R0lGODlhAQABAIAAAAAAAP///yH5BAEAAAAALAAAAAABAAEAAAIBRAA7",
+            MaybeId::LastNode,
+        );
+        c.set_image_loading(false, true, MaybeId::LastNode);
+        decoded = Some(c.decode_image(MaybeId::LastNode));
+        c.create_element("img", Some(NodeId(2)));
+        c.set_attribute("src", "data:image/gif;base64,AAAA", MaybeId::LastNode);
+        broken = Some(c.decode_image(MaybeId::LastNode));
+    });
+    let image = element(NodeId(1));
+    assert_eq!(image.get_attribute("loading").as_deref(), Some("eager"));
+    assert_eq!(image.get_attribute("decoding").as_deref(), Some("async"));
+    assert!(decoded.unwrap().await);
+    assert!(!broken.unwrap().await);
+}
+
+#[wasm_bindgen_test]
+async fn view_transitions_apply_the_batch_and_dispatch_the_end() {
+    render_list();
+    let ended = Rc::new(RefCell::new(Vec::new()));
+    let ended_in_handler = ended.clone();
+    let handler = CHANNEL.with(|c| {
+        c.borrow().handlers().insert(move |event: &Event| {
+            if let EventData::ViewTransition(transition) = &event.data {
+                ended_in_handler.borrow_mut().push(transition.error.clone());
+            }
+        })
+    });
+    apply(|c| {
+        c.with_view_transition(handler, |batch| {
+            batch.set_text("new", MaybeId::Node(NodeId(2)));
+        })
+    });
+    timeout(1000).await;
+    assert_eq!(element(NodeId(2)).text_content().as_deref(), Some("new"));
+    assert_eq!(*ended.borrow(), [None]);
+
+    // the error of a failing operation is sent to the handler
+    apply(|c| {
+        c.with_view_transition(handler, |batch| {
+            batch.set_text("lost", MaybeId::Node(NodeId(50)));
+        })
+    });
+    timeout(1000).await;
+    assert_eq!(ended.borrow().len(), 2);
+    assert!(ended.borrow()[1].is_some());
+    CHANNEL.with(|c| c.borrow().handlers().remove(handler));
+}
+
+#[wasm_bindgen_test]
+async fn observed_canvases_match_their_css_size() {
+    root();
+    let sizes = Rc::new(RefCell::new(Vec::new()));
+    let sizes_in_handler = sizes.clone();
+    let handler = CHANNEL.with(|c| {
+        c.borrow().handlers().insert(move |event: &Event| {
+            if let EventData::CanvasSize(size) = &event.data {
+                sizes_in_handler.borrow_mut().push(*size);
+            }
+        })
+    });
+    apply(|c| {
+        c.create_element("canvas", Some(NodeId(1)));
+        c.set_style("width", "30px", MaybeId::LastNode);
+        c.set_style("height", "20px", MaybeId::LastNode);
+        c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+        c.observe_canvas_size(MaybeId::Node(NodeId(1)), handler);
+    });
+    // resize observers are notified after layout, before the next frame is painted
+    animation_frame().await;
+    animation_frame().await;
+    let ratio = web_sys::window().unwrap().device_pixel_ratio();
+    let canvas: web_sys::HtmlCanvasElement = element(NodeId(1)).unchecked_into();
+    {
+        let sizes = sizes.borrow();
+        let size = sizes.last().unwrap();
+        assert_eq!(size.node, NodeId(1));
+        assert_eq!((size.width, size.height), (30.0, 20.0));
+        assert_eq!(size.device_pixel_ratio, ratio);
+        assert_eq!(
+            (canvas.width(), canvas.height()),
+            (size.pixel_width, size.pixel_height)
+        );
+        assert_eq!(size.pixel_width, (30.0 * ratio).round() as u32);
+    }
+
+    apply(|c| c.unobserve_canvas_size(MaybeId::Node(NodeId(1))));
+    let count = sizes.borrow().len();
+    canvas.style().set_property("width", "60px").unwrap();
+    animation_frame().await;
+    animation_frame().await;
+    assert_eq!(sizes.borrow().len(), count);
+    CHANNEL.with(|c| c.borrow().handlers().remove(handler));
+}
+
+#[wasm_bindgen_test]
+fn composition_events_carry_the_composed_text() {
+    render_list();
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let events_in_handler = events.clone();
+    let handler = CHANNEL.with(|c| {
+        c.borrow().handlers().insert(move |event: &Event| {
+            events_in_handler
+                .borrow_mut()
+                .push((event.name.clone(), event.data.clone()));
+        })
+    });
+    apply(|c| {
+        c.add_listener("compositionupdate", handler, MaybeId::Node(NodeId(1)));
+        c.add_listener("compositionend", handler, MaybeId::Node(NodeId(1)));
+    });
+    let div = element(NodeId(1));
+    for (name, data) in [("compositionupdate", "にほ"), ("compositionend", "日本")] {
+        let init = web_sys::CompositionEventInit::new();
+        init.set_data(data);
+        let event = web_sys::CompositionEvent::new_with_event_init_dict(name, &init).unwrap();
+        div.dispatch_event(&event).unwrap();
+    }
+    let composition = |data: &str| {
+        EventData::Composition(CompositionData {
+            data: data.to_string(),
+        })
+    };
+    assert_eq!(
+        *events.borrow(),
+        [
+            ("compositionupdate".to_string(), composition("にほ")),
+            ("compositionend".to_string(), composition("日本")),
+        ]
+    );
+
+    apply(|c| {
+        c.remove_listener("compositionupdate", handler, MaybeId::Node(NodeId(1)));
+        c.remove_listener("compositionend", handler, MaybeId::Node(NodeId(1)));
+    });
+    CHANNEL.with(|c| c.borrow().handlers().remove(handler));
+}
+
+/// Wait until the microtasks that were queued so far have run
+async fn microtasks() {
+    wasm_bindgen_futures::JsFuture::from(js_sys::Promise::resolve(&wasm_bindgen::JsValue::NULL))
+        .await
+        .unwrap();
+}
+
+/// Wait until the callbacks of the next animation frame have run
+async fn animation_frame() {
+    let promise = js_sys::Promise::new(&mut |resolve, _| {
+        web_sys::window()
+            .unwrap()
+            .request_animation_frame(&resolve)
+            .unwrap();
+    });
+    wasm_bindgen_futures::JsFuture::from(promise).await.unwrap();
+}
+
+/// Wait for the tasks the browser queued, like events that are dispatched asynchronously
+async fn timeout(ms: i32) {
+    let promise = js_sys::Promise::new(&mut |resolve, _| {
+        web_sys::window()
+            .unwrap()
+            .set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, ms)
+            .unwrap();
+    });
+    wasm_bindgen_futures::JsFuture::from(promise).await.unwrap();
+}
+
+#[wasm_bindgen_test]
+async fn fullscreen_errors_are_dispatched_to_the_handler() {
+    root();
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let events_in_handler = events.clone();
+    let handler = CHANNEL.with(|c| {
+        c.borrow().handlers().insert(move |event: &Event| {
+            if let EventData::Lock(lock) = &event.data {
+                events_in_handler
+                    .borrow_mut()
+                    .push((event.name.clone(), lock.active));
+            }
+        })
+    });
+    // fullscreen is denied without a user gesture
+    apply(|c| {
+        c.create_element(Element::div, Some(NodeId(1)));
+        c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+        c.request_fullscreen(MaybeId::Node(NodeId(1)), handler);
+        // exiting while nothing is in fullscreen does nothing
+        c.exit_fullscreen();
+        c.exit_pointer_lock();
+    });
+    timeout(100).await;
+    assert_eq!(*events.borrow(), [("fullscreenerror".to_string(), false)]);
+    CHANNEL.with(|c| c.borrow().handlers().remove(handler));
+}
+
+#[wasm_bindgen_test]
+async fn media_queries_dispatch_the_current_state() {
+    let states = Rc::new(RefCell::new(Vec::new()));
+    let states_in_handler = states.clone();
+    let handler = CHANNEL.with(|c| {
+        c.borrow().handlers().insert(move |event: &Event| {
+            if let EventData::MediaQuery(query) = &event.data {
+                states_in_handler.borrow_mut().push(query.matches);
+            }
+        })
+    });
+    apply(|c| {
+        c.subscribe_media_query("(min-width: 0px)", handler);
+        c.subscribe_media_query("not all", handler);
+    });
+    // the current state is dispatched after the flush returns
+    assert!(states.borrow().is_empty());
+    microtasks().await;
+    assert_eq!(*states.borrow(), [true, false]);
+
+    // unsubscribing before the state is dispatched cancels it
+    apply(|c| {
+        c.subscribe_media_query("all", handler);
+        c.unsubscribe_media_query("all", handler);
+        c.unsubscribe_media_query("(min-width: 0px)", handler);
+        c.unsubscribe_media_query("not all", handler);
+    });
+    microtasks().await;
+    assert_eq!(states.borrow().len(), 2);
+    CHANNEL.with(|c| c.borrow().handlers().remove(handler));
+}
+
+#[wasm_bindgen_test]
+async fn storage_reads_answer_queries() {
+    let (set, missing) = CHANNEL.with(|c| {
+        let mut c = c.borrow_mut();
+        c.set_storage_item(StorageArea::Session, "sledgehammer-test", "value");
+        let set = c.get_storage_item(StorageArea::Session, "sledgehammer-test");
+        c.remove_storage_item(StorageArea::Local, "sledgehammer-test");
+        let missing = c.get_storage_item(StorageArea::Local, "sledgehammer-test");
+        c.flush().unwrap();
+        (set, missing)
+    });
+    assert_eq!(set.await.as_deref(), Some("value"));
+    assert_eq!(missing.await, None);
+    let session = web_sys::window()
+        .unwrap()
+        .session_storage()
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        session.get_item("sledgehammer-test").unwrap().as_deref(),
+        Some("value")
+    );
+    apply(|c| c.remove_storage_item(StorageArea::Session, "sledgehammer-test"));
+    assert_eq!(session.get_item("sledgehammer-test").unwrap(), None);
+}
+
+#[wasm_bindgen_test]
+async fn focus_and_visibility_queries_are_answered() {
+    root();
+    let (focused, visible, hidden) = CHANNEL.with(|c| {
+        let mut c = c.borrow_mut();
+        c.create_element(Element::input, Some(NodeId(1)));
+        c.set_style("position", "fixed", MaybeId::LastNode);
+        c.set_style("left", "0px", MaybeId::LastNode);
+        c.set_style("top", "0px", MaybeId::LastNode);
+        c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+        c.create_element(Element::div, Some(NodeId(2)));
+        c.set_attribute("hidden", "", MaybeId::LastNode);
+        c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+        c.flush().unwrap();
+        element(NodeId(1))
+            .unchecked_into::<web_sys::HtmlElement>()
+            .focus()
+            .unwrap();
+        let focused = c.active_element();
+        let visible = c.check_visibility(MaybeId::Node(NodeId(1)));
+        let hidden = c.check_visibility(MaybeId::Node(NodeId(2)));
+        c.flush().unwrap();
+        (focused, visible, hidden)
+    });
+    assert_eq!(focused.await, Some(NodeId(1)));
+    assert!(visible.await);
+    assert!(!hidden.await);
+    let at_point = CHANNEL.with(|c| {
+        let mut c = c.borrow_mut();
+        let at_point = c.element_from_point(1, 1);
+        c.flush().unwrap();
+        at_point
+    });
+    assert_eq!(at_point.await, Some(NodeId(1)));
+}
+
+#[wasm_bindgen_test]
+async fn custom_element_lifecycle_callbacks_are_forwarded() {
+    root();
+    // the interpreter only sees the callbacks of elements defined after it is created
+    js_sys::Function::new_no_args(
+        "customElements.define('sledgehammer-widget', class extends HTMLElement { static observedAttributes = ['size']; })",
+    )
+    .call0(&wasm_bindgen::JsValue::NULL)
+    .unwrap();
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let events_in_handler = events.clone();
+    let handler = CHANNEL.with(|c| {
+        c.borrow().handlers().insert(move |event: &Event| {
+            if let EventData::Lifecycle(lifecycle) = &event.data {
+                events_in_handler.borrow_mut().push((
+                    event.name.clone(),
+                    lifecycle.node,
+                    lifecycle.attribute.clone(),
+                ));
+            }
+        })
+    });
+    apply(|c| {
+        c.observe_custom_element("sledgehammer-widget", handler);
+        c.create_element("sledgehammer-widget", Some(NodeId(1)));
+        c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+        c.set_attribute(Attribute::size, "large", MaybeId::Node(NodeId(1)));
+    });
+    // the callbacks are forwarded after the flush returns
+    assert!(events.borrow().is_empty());
+    microtasks().await;
+    assert_eq!(events.borrow().len(), 2);
+    assert_eq!(
+        events.borrow()[0],
+        ("connected".to_string(), NodeId(1), None)
+    );
+    let (name, node, attribute) = events.borrow()[1].clone();
+    assert_eq!((name.as_str(), node), ("attributechanged", NodeId(1)));
+    let attribute = attribute.unwrap();
+    assert_eq!(attribute.name, "size");
+    assert_eq!(attribute.old_value, None);
+    assert_eq!(attribute.new_value.as_deref(), Some("large"));
+
+    apply(|c| {
+        c.remove(MaybeId::Node(NodeId(1)));
+        c.unobserve_custom_element("sledgehammer-widget", handler);
+    });
+    microtasks().await;
+    assert_eq!(events.borrow().len(), 2);
+    CHANNEL.with(|c| c.borrow().handlers().remove(handler));
+}
+
+#[wasm_bindgen_test]
+fn dialogs_and_popovers_open_and_close() {
+    root();
+    apply(|c| {
+        c.create_element("dialog", Some(NodeId(1)));
+        c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+        c.create_element(Element::div, Some(NodeId(2)));
+        c.set_attribute("popover", "", MaybeId::LastNode);
+        c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+        c.show_modal(MaybeId::Node(NodeId(1)));
+        c.show_popover(MaybeId::Node(NodeId(2)));
+    });
+    let dialog = element(NodeId(1));
+    let popover = element(NodeId(2));
+    assert!(dialog.has_attribute("open"));
+    assert!(dialog.matches(":modal").unwrap());
+    assert!(popover.matches(":popover-open").unwrap());
+
+    apply(|c| {
+        c.close_dialog(MaybeId::Node(NodeId(1)));
+        c.hide_popover(MaybeId::Node(NodeId(2)));
+    });
+    assert!(!dialog.has_attribute("open"));
+    assert!(!popover.matches(":popover-open").unwrap());
+}
+
+#[wasm_bindgen_test]
+fn controlled_inputs_reconcile_edits() {
+    let root = root();
+    let input = CHANNEL.with(|c| {
+        let mut c = c.borrow_mut();
+        c.create_element("input", Some(NodeId(1)));
+        c.append_child(MaybeId::Node(ROOT), MaybeId::LastNode);
+        let input = c.controlled_input(NodeId(1), "12", |_, edited| {
+            if edited.chars().all(|c| c.is_ascii_digit()) {
+                Reconcile::Accept
+            } else {
+                Reconcile::Reject
+            }
+        });
+        c.flush().unwrap();
+        input
+    });
+    let element: web_sys::HtmlInputElement = element(NodeId(1)).dyn_into().unwrap();
+    assert_eq!(element.value(), "12");
+    // the value attribute is not changed
+    assert_eq!(root.inner_html(), "<input>");
+
+    let edit = |value: &str| {
+        element.set_value(value);
+        element
+            .dispatch_event(&web_sys::Event::new("input").unwrap())
+            .unwrap();
+    };
+    edit("123");
+    assert_eq!(element.value(), "123");
+    edit("1a23");
+    assert_eq!(element.value(), "123");
+    assert_eq!(input.value(), "123");
+
+    CHANNEL.with(|c| input.release(&mut c.borrow_mut()));
+    apply(|_| {});
+}
@@ -0,0 +1,16 @@
+//! Checks that `interpreter_opt.js`, the interpreter the wasm bindings import, is generated from `interpreter.js`
+
+#![cfg(not(target_arch = "wasm32"))]
+
+#[path = "../examples/minify/minify.rs"]
+#[allow(dead_code)]
+mod minify;
+
+#[test]
+fn the_minified_interpreter_is_up_to_date() {
+    let minified = minify::minify(include_str!("../interpreter.js"));
+    assert!(
+        minified.code == include_str!("../interpreter_opt.js"),
+        "interpreter_opt.js is out of date, regenerate it with `cargo run -p sledgehammer --example minify`"
+    );
+}
@@ -0,0 +1,560 @@
+//! Checks the javascript interpreter against the opcode table of the encoder so the two sides of the binary format cannot drift apart
+//!
+//! The operation switch of the interpreter is walked for every combination of the flags of each operation, counting the bytes and strings the interpreter reads and comparing them with the operands in the table. The minified interpreter the bindings import is walked as well, with the names the minifier gave the state of the interpreter.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+#[path = "../examples/minify/minify.rs"]
+#[allow(dead_code)]
+mod minify;
+
+use sledgehammer_encoder::{
+    opcodes::{Opcode, OperandKind, OPCODES},
+    Op, WIRE_FORMAT_VERSION,
+};
+
+const INTERPRETER: &str = include_str!("../interpreter.js");
+const MINIFIED: &str = include_str!("../interpreter_opt.js");
+
+/// The source of an interpreter with the names it uses for the state the operation switch reads
+struct Interpreter {
+    file: &'static str,
+    src: &'static str,
+    ex_op: String,
+    op: String,
+    inptr: String,
+    u8_buf_pos: String,
+    str_pos: String,
+    /// The methods of the interpreter that read from the batch themselves
+    reading_methods: Vec<String>,
+}
+
+impl Interpreter {
+    fn new(file: &'static str, src: &'static str, name: impl Fn(&str) -> String) -> Self {
+        let mut interpreter = Self {
+            file,
+            src,
+            ex_op: name("exOp"),
+            op: name("op"),
+            inptr: name("inptr"),
+            u8_buf_pos: name("u8BufPos"),
+            str_pos: name("strPos"),
+            reading_methods: Vec::new(),
+        };
+        interpreter.reading_methods = interpreter.find_reading_methods();
+        interpreter
+    }
+}
+
+/// The readable interpreter and the minified interpreter
+fn interpreters() -> [Interpreter; 2] {
+    let minified = minify::minify(INTERPRETER);
+    [
+        Interpreter::new("interpreter.js", INTERPRETER, str::to_string),
+        Interpreter::new("interpreter_opt.js", MINIFIED, |name| {
+            minified.name(name).to_string()
+        }),
+    ]
+}
+
+/// What an operation reads from the batch
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct Reads {
+    /// The number of bytes read from the operation buffer
+    bytes: usize,
+    /// The number of strings read from the string buffer
+    strings: usize,
+    /// The operands with a variable length, which the interpreter reads with a loop or a helper, in order
+    nested: Vec<OperandKind>,
+}
+
+/// The reads of an operation with the flags set in `bits` according to the opcode table
+fn expected(code: &Opcode, bits: u8) -> Reads {
+    let flag = |name: &str| {
+        let idx = code.flags.iter().position(|flag| *flag == name);
+        let idx = idx.unwrap_or_else(|| panic!("{} has no flag {name}", code.name));
+        bits & (1 << idx) != 0
+    };
+    let mut reads = Reads::default();
+    for operand in code.operands {
+        if !operand
+            .when
+            .iter()
+            .all(|(name, value)| flag(name) == *value)
+        {
+            continue;
+        }
+        match operand.kind {
+            OperandKind::U8 => reads.bytes += 1,
+            OperandKind::U32 | OperandKind::I32 | OperandKind::Id => reads.bytes += 4,
+            OperandKind::Str => {
+                reads.bytes += 2;
+                reads.strings += 1;
+            }
+            OperandKind::Element | OperandKind::Node => reads.nested.push(operand.kind),
+            // the number of nodes
+            OperandKind::Nodes => {
+                reads.bytes += 1;
+                reads.nested.push(operand.kind);
+            }
+            // the length of the bytes
+            OperandKind::Bytes => {
+                reads.bytes += 4;
+                reads.nested.push(operand.kind);
+            }
+        }
+    }
+    reads
+}
+
+/// A case of the operation switch in the interpreter. Cases of the extended operation switch have the extended discriminant.
+struct Case {
+    op: u8,
+    extended: Option<u8>,
+    body: String,
+}
+
+/// The cases of the operation switch in the interpreter
+fn cases(interpreter: &Interpreter) -> Vec<Case> {
+    let start = interpreter
+        .src
+        .find(&format!("function {}()", interpreter.ex_op))
+        .unwrap();
+    let body = strip_comments(block(&interpreter.src[start..]));
+    let mut cases = Vec::new();
+    for (op, body) in switch_cases(block(&body)) {
+        if op == Op::Extended as u8 {
+            for (extended, body) in switch_cases(block(&body)) {
+                cases.push(Case {
+                    op,
+                    extended: Some(extended),
+                    body,
+                });
+            }
+        } else {
+            cases.push(Case {
+                op,
+                extended: None,
+                body,
+            });
+        }
+    }
+    cases
+}
+
+/// The text inside the first braces in `src`
+fn block(src: &str) -> &str {
+    let open = src.find('{').unwrap();
+    &src[open + 1..closing(src, open)]
+}
+
+/// Skip the string literal starting at `start` and return the index of the closing quote
+fn string_end(src: &str, start: usize) -> usize {
+    let bytes = src.as_bytes();
+    let quote = bytes[start];
+    let mut i = start + 1;
+    while bytes[i] != quote {
+        i += if bytes[i] == b'\\' { 2 } else { 1 };
+    }
+    i
+}
+
+/// The index of the bracket that closes the bracket at `open`
+fn closing(src: &str, open: usize) -> usize {
+    let bytes = src.as_bytes();
+    let mut depth = 0;
+    let mut i = open;
+    loop {
+        match bytes[i] {
+            b'"' | b'\'' | b'`' => i = string_end(src, i),
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' | b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+}
+
+fn strip_comments(src: &str) -> String {
+    let bytes = src.as_bytes();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' | b'\'' | b'`' => {
+                let end = string_end(src, i);
+                out.push_str(&src[i..=end]);
+                i = end + 1;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                i = src[i..].find('\n').map_or(src.len(), |end| i + end);
+            }
+            _ => {
+                let len = src[i..].chars().next().unwrap().len_utf8();
+                out.push_str(&src[i..i + len]);
+                i += len;
+            }
+        }
+    }
+    out
+}
+
+/// The labels of a switch statement with the code each label runs. Labels without code fall through to the next label.
+fn switch_cases(src: &str) -> Vec<(u8, String)> {
+    let bytes = src.as_bytes();
+    // the labels with the index of the label and the index after the colon
+    let mut labels = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' | b'\'' | b'`' => i = string_end(src, i),
+            b'(' | b'[' | b'{' => i = closing(src, i),
+            _ if is_word(src, i, "case") || is_word(src, i, "default") => {
+                let colon = i + src[i..].find(':').unwrap();
+                let label = src[i..colon].strip_prefix("case").map(|value| {
+                    value
+                        .trim()
+                        .parse::<u8>()
+                        .unwrap_or_else(|_| panic!("unexpected case label {value:?}"))
+                });
+                labels.push((label, i, colon + 1));
+                i = colon;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    let mut cases = Vec::new();
+    let mut pending = Vec::new();
+    for (idx, (label, _, start)) in labels.iter().enumerate() {
+        let end = labels.get(idx + 1).map_or(src.len(), |(_, end, _)| *end);
+        let body = src[*start..end].trim();
+        pending.extend(*label);
+        if !body.is_empty() {
+            cases.extend(pending.drain(..).map(|label| (label, body.to_string())));
+        }
+    }
+    cases
+}
+
+fn is_ident(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_' || c == b'$'
+}
+
+/// If the identifier `word` starts at `i`
+fn is_word(src: &str, i: usize, word: &str) -> bool {
+    let ident = |c: u8| is_ident(c) || c == b'.';
+    src[i..].starts_with(word)
+        && (i == 0 || !ident(src.as_bytes()[i - 1]))
+        && !src
+            .as_bytes()
+            .get(i + word.len())
+            .copied()
+            .is_some_and(ident)
+}
+
+/// If the identifier `word` appears anywhere in `src`
+fn mentions(src: &str, word: &str) -> bool {
+    src.match_indices(word).any(|(i, _)| is_word(src, i, word))
+}
+
+/// The property of `object` accessed at `i` with the index after the property
+fn property_at<'a>(src: &'a str, i: usize, object: &str) -> Option<(&'a str, usize)> {
+    if i > 0 && (is_ident(src.as_bytes()[i - 1]) || src.as_bytes()[i - 1] == b'.') {
+        return None;
+    }
+    let start = src.len() - src[i..].strip_prefix(object)?.strip_prefix('.')?.len();
+    let end = src[start..]
+        .bytes()
+        .position(|c| !is_ident(c))
+        .map_or(src.len(), |len| start + len);
+    Some((&src[start..end], end))
+}
+
+/// The properties of `object` accessed in `src`
+fn properties<'a>(src: &'a str, object: &'a str) -> impl Iterator<Item = &'a str> {
+    src.match_indices(object)
+        .filter_map(|(i, _)| property_at(src, i, object).map(|(property, _)| property))
+}
+
+enum Statement<'a> {
+    Block(&'a str),
+    If {
+        condition: &'a str,
+        then: &'a str,
+        otherwise: Option<&'a str>,
+    },
+    Loop(&'a str),
+    Simple(&'a str),
+}
+
+/// Split the first statement off of `src`
+fn next_statement(src: &str) -> Option<(Statement<'_>, &str)> {
+    let src = src.trim_start();
+    if src.is_empty() {
+        return None;
+    }
+    if src.starts_with('{') {
+        let close = closing(src, 0);
+        return Some((Statement::Block(&src[1..close]), &src[close + 1..]));
+    }
+    for keyword in ["if", "for", "while"] {
+        if !is_word(src, 0, keyword) {
+            continue;
+        }
+        let open = src.find('(').unwrap();
+        let close = closing(src, open);
+        let condition = &src[open + 1..close];
+        let body_start = close + 1;
+        let (_, rest) = next_statement(&src[body_start..]).unwrap();
+        let then = &src[body_start..src.len() - rest.len()];
+        if keyword != "if" {
+            return Some((Statement::Loop(then), rest));
+        }
+        let after = rest.trim_start();
+        if is_word(after, 0, "else") {
+            let otherwise = &after["else".len()..];
+            let (_, rest) = next_statement(otherwise).unwrap();
+            let otherwise = &otherwise[..otherwise.len() - rest.len()];
+            return Some((
+                Statement::If {
+                    condition,
+                    then,
+                    otherwise: Some(otherwise),
+                },
+                rest,
+            ));
+        }
+        return Some((
+            Statement::If {
+                condition,
+                then,
+                otherwise: None,
+            },
+            rest,
+        ));
+    }
+    assert!(!is_word(src, 0, "switch"), "unexpected switch in {src}");
+    let bytes = src.as_bytes();
+    let mut i = 0;
+    loop {
+        match bytes[i] {
+            b'"' | b'\'' | b'`' => i = string_end(src, i),
+            b'(' | b'[' | b'{' => i = closing(src, i),
+            b';' => return Some((Statement::Simple(&src[..i]), &src[i + 1..])),
+            _ => {}
+        }
+        i += 1;
+    }
+}
+
+/// The words javascript uses for control flow before parentheses, which are not methods
+const CONTROL_FLOW: [&str; 6] = ["if", "for", "while", "switch", "catch", "function"];
+
+impl Interpreter {
+    /// The mask of the flag test `op & 0x20` starting at `i` with the index after the mask
+    fn flag_test(&self, src: &str, i: usize) -> Option<(u8, usize)> {
+        if !is_word(src, i, &self.op) {
+            return None;
+        }
+        let rest = src[i + self.op.len()..].trim_start().strip_prefix('&')?;
+        let digits = rest.trim_start().strip_prefix("0x")?;
+        let len = digits
+            .find(|c: char| !c.is_ascii_hexdigit())
+            .unwrap_or(digits.len());
+        let mask = u8::from_str_radix(&digits[..len], 16).ok()?;
+        Some((mask, src.len() - digits.len() + len))
+    }
+
+    /// The value of `op & 0x20` and the other flag bits in a condition, or `None` if the condition does not depend on the flags
+    fn flag_condition(&self, condition: &str, bits: u8) -> Option<bool> {
+        let condition = condition.trim();
+        let (mask, end) = self.flag_test(condition, 0)?;
+        assert!(
+            end == condition.len(),
+            "cannot evaluate the condition {condition:?}"
+        );
+        Some(bits << 5 & mask != 0)
+    }
+
+    /// If the statements in `src` read from the batch
+    fn reads_batch(&self, src: &str, object: &str) -> bool {
+        properties(src, object)
+            .any(|property| property == self.u8_buf_pos || property == self.str_pos)
+    }
+
+    /// Add the reads of the statements in `src` with the flags set in `bits` to `reads`
+    fn walk(&self, src: &str, bits: u8, reads: &mut Reads) {
+        let mut src = src;
+        while let Some((statement, rest)) = next_statement(src) {
+            src = rest;
+            match statement {
+                Statement::Block(body) => self.walk(body, bits, reads),
+                Statement::If {
+                    condition,
+                    then,
+                    otherwise,
+                } => match self.flag_condition(condition, bits) {
+                    Some(true) => self.walk(then, bits, reads),
+                    Some(false) => self.walk(otherwise.unwrap_or_default(), bits, reads),
+                    None => {
+                        assert!(
+                            !mentions(condition, &self.op),
+                            "cannot evaluate the condition {condition:?}"
+                        );
+                        // the state of the page cannot change the layout of the operation
+                        let mut then_reads = reads.clone();
+                        self.walk(then, bits, &mut then_reads);
+                        self.walk(otherwise.unwrap_or_default(), bits, reads);
+                        assert_eq!(
+                            then_reads, *reads,
+                            "the branches of {condition:?} read different data"
+                        );
+                    }
+                },
+                // the only operand read in a loop is the list of nodes, which decides if each node has an id as it reads it
+                Statement::Loop(body) => {
+                    if self.reads_batch(body, &self.inptr) {
+                        reads.nested.push(OperandKind::Nodes);
+                    }
+                }
+                Statement::Simple(statement) => {
+                    if is_word(statement, 0, "break") || is_word(statement, 0, "return") {
+                        return;
+                    }
+                    self.simple_reads(statement, reads);
+                }
+            }
+        }
+    }
+
+    /// The methods of the interpreter that read from the batch themselves
+    fn find_reading_methods(&self) -> Vec<String> {
+        let src = &strip_comments(self.src);
+        let mut methods = Vec::new();
+        for (open, _) in src.match_indices('(') {
+            let start = src[..open]
+                .bytes()
+                .rposition(|c| !is_ident(c))
+                .map_or(0, |i| i + 1);
+            let name = &src[start..open];
+            if name.is_empty() || CONTROL_FLOW.contains(&name) {
+                continue;
+            }
+            // methods follow the start of the class or the previous member
+            if !src[..start].trim_end().ends_with(['{', '}', ';']) {
+                continue;
+            }
+            let body = src[closing(src, open) + 1..].trim_start();
+            if body.starts_with('{') && self.reads_batch(block(body), "this") {
+                methods.push(name.to_string());
+            }
+        }
+        methods
+    }
+
+    /// Add the reads of a statement without control flow to `reads` in the order they appear
+    fn simple_reads(&self, statement: &str, reads: &mut Reads) {
+        for (i, _) in statement.match_indices(&self.inptr) {
+            let Some((property, end)) = property_at(statement, i, &self.inptr) else {
+                continue;
+            };
+            let rest = statement[end..].trim_start();
+            if property == self.u8_buf_pos && rest.starts_with("++") {
+                reads.bytes += 1;
+            } else if let Some(len) = rest
+                .strip_prefix("+=")
+                .filter(|_| property == self.u8_buf_pos)
+            {
+                let len = len.trim_start();
+                let len = &len[..len
+                    .find(|c: char| !c.is_ascii_alphanumeric())
+                    .unwrap_or(len.len())];
+                match len.parse::<usize>() {
+                    Ok(len) => reads.bytes += len,
+                    Err(_) => reads.nested.push(OperandKind::Bytes),
+                }
+            } else if property == self.str_pos && rest.starts_with("+=") {
+                reads.strings += 1;
+            } else if property == "createElement" && rest.starts_with("()") {
+                reads.nested.push(OperandKind::Element);
+            } else if property == "createFullElement" && rest.starts_with("()") {
+                reads.nested.push(OperandKind::Node);
+            } else {
+                assert!(
+                    !self.reading_methods.iter().any(|name| name == property),
+                    "{property} reads from the batch in {statement:?}"
+                );
+            }
+        }
+    }
+
+    /// The flag bits tested anywhere in the case
+    fn tested_flags(&self, body: &str) -> u8 {
+        body.match_indices(&self.op)
+            .filter_map(|(i, _)| self.flag_test(body, i))
+            .fold(0, |bits, (mask, _)| bits | mask >> 5)
+    }
+}
+
+#[test]
+fn the_interpreter_reads_the_operands_in_the_table() {
+    for interpreter in interpreters() {
+        reads_the_operands_in_the_table(&interpreter);
+    }
+}
+
+fn reads_the_operands_in_the_table(interpreter: &Interpreter) {
+    let file = interpreter.file;
+    let cases = cases(interpreter);
+    for code in OPCODES {
+        let op = code.op as u8;
+        let extended = code.extended.map(|op| op as u8);
+        let case = cases
+            .iter()
+            .find(|case| case.op == op && case.extended == extended);
+        let Some(case) = case else {
+            panic!("{file} does not handle {}", code.name);
+        };
+        let flags = (1u8 << code.flags.len()) - 1;
+        assert_eq!(
+            interpreter.tested_flags(&case.body),
+            flags,
+            "{file} reads different flags than {:?} for {}",
+            code.flags,
+            code.name
+        );
+        for bits in 0..=flags {
+            let mut reads = Reads::default();
+            interpreter.walk(&case.body, bits, &mut reads);
+            assert_eq!(
+                reads,
+                expected(code, bits),
+                "{file} reads different data for {} with the flags {bits:03b}",
+                code.name
+            );
+        }
+    }
+    for case in &cases {
+        assert!(
+            OPCODES.iter().any(|code| code.op as u8 == case.op
+                && code.extended.map(|op| op as u8) == case.extended),
+            "{file} handles ({}, {:?}) which is not in the opcode table",
+            case.op,
+            case.extended
+        );
+    }
+}
+
+#[test]
+fn the_javascript_format_versions_match() {
+    let version = format!("FORMAT_VERSION = {WIRE_FORMAT_VERSION};");
+    assert!(INTERPRETER.contains(&version));
+    assert!(include_str!("../decompress.js").contains(&version));
+    assert!(MINIFIED.contains(&format!("FORMAT_VERSION={WIRE_FORMAT_VERSION};")));
+}
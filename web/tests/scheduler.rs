@@ -0,0 +1,119 @@
+//! Checks that a [`FrameScheduler`] coalesces operations into one flush in the next animation frame.
+
+#![cfg(target_arch = "wasm32")]
+
+use sledgehammer::{scheduler::FrameScheduler, MaybeId, MsgChannel, NodeId};
+use wasm_bindgen::{closure::Closure, JsCast};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+/// Wait until after the next animation frame
+async fn next_frame() {
+    let promise = js_sys::Promise::new(&mut |resolve, _| {
+        let callback = Closure::once_into_js(move || {
+            resolve.call0(&wasm_bindgen::JsValue::NULL).unwrap();
+        });
+        web_sys::window()
+            .unwrap()
+            .request_animation_frame(callback.unchecked_ref())
+            .unwrap();
+    });
+    wasm_bindgen_futures::JsFuture::from(promise).await.unwrap();
+}
+
+#[wasm_bindgen_test]
+async fn flushes_in_the_next_frame() {
+    let document = web_sys::window().unwrap().document().unwrap();
+    let root = document.create_element("div").unwrap();
+    document.body().unwrap().append_child(&root).unwrap();
+
+    let scheduler = FrameScheduler::new(MsgChannel::default());
+    scheduler.with_channel(|c| c.set_node(NodeId(0), root.clone().into()));
+    scheduler.queue(|c| {
+        c.create_element("p", None);
+        c.append_child(MaybeId::Node(NodeId(0)), MaybeId::LastNode);
+    });
+    scheduler.queue(|c| {
+        c.create_element("span", None);
+        c.append_child(MaybeId::Node(NodeId(0)), MaybeId::LastNode);
+    });
+    assert!(scheduler.is_frame_requested());
+    assert_eq!(root.inner_html(), "");
+
+    next_frame().await;
+    assert!(!scheduler.is_frame_requested());
+    assert!(scheduler.take_error().is_none());
+    assert_eq!(root.inner_html(), "<p></p><span></span>");
+}
+
+#[wasm_bindgen_test]
+async fn applies_idle_batches() {
+    let document = web_sys::window().unwrap().document().unwrap();
+    let root = document.create_element("div").unwrap();
+    document.body().unwrap().append_child(&root).unwrap();
+
+    let scheduler = FrameScheduler::new(MsgChannel::default());
+    scheduler.with_channel(|c| c.set_node(NodeId(1), root.clone().into()));
+    scheduler.queue_idle(|batch| {
+        for i in 0..100 {
+            batch.create_text_node(i, None);
+            batch.append_child(MaybeId::Node(NodeId(1)), MaybeId::LastNode);
+        }
+    });
+    assert!(scheduler.is_idle_requested());
+    while scheduler.is_idle_requested() {
+        next_frame().await;
+    }
+    assert!(scheduler.take_error().is_none());
+    assert_eq!(root.child_nodes().length(), 100);
+}
+
+#[wasm_bindgen_test]
+async fn deferred_operations_wait_a_frame() {
+    let document = web_sys::window().unwrap().document().unwrap();
+    let root = document.create_element("div").unwrap();
+    document.body().unwrap().append_child(&root).unwrap();
+
+    let scheduler = FrameScheduler::new(MsgChannel::default());
+    scheduler.with_channel(|c| c.set_node(NodeId(2), root.clone().into()));
+    scheduler.queue_deferred(|b| {
+        b.create_element("span", None);
+        b.append_child(MaybeId::Node(NodeId(2)), MaybeId::LastNode);
+    });
+    scheduler.queue(|c| {
+        c.create_element("p", None);
+        c.append_child(MaybeId::Node(NodeId(2)), MaybeId::LastNode);
+    });
+
+    next_frame().await;
+    assert!(scheduler.has_deferred());
+    assert_eq!(root.inner_html(), "<p></p>");
+
+    next_frame().await;
+    assert!(!scheduler.has_deferred());
+    assert!(!scheduler.is_frame_requested());
+    assert!(scheduler.take_error().is_none());
+    assert_eq!(root.inner_html(), "<p></p><span></span>");
+}
+
+#[wasm_bindgen_test]
+async fn applied_resolves_after_the_frame() {
+    let document = web_sys::window().unwrap().document().unwrap();
+    let root = document.create_element("div").unwrap();
+    document.body().unwrap().append_child(&root).unwrap();
+
+    let scheduler = FrameScheduler::new(MsgChannel::default());
+    scheduler.with_channel(|c| c.set_node(NodeId(0), root.clone().into()));
+    scheduler.queue(|c| {
+        c.create_element("p", None);
+        c.append_child(MaybeId::Node(NodeId(0)), MaybeId::LastNode);
+    });
+    scheduler.queue_deferred(|b| {
+        b.create_element("span", None);
+        b.append_child(MaybeId::Node(NodeId(0)), MaybeId::LastNode);
+    });
+    scheduler.applied().await;
+    assert!(scheduler.take_error().is_none());
+    assert_eq!(root.inner_html(), "<p></p><span></span>");
+}